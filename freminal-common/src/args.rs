@@ -5,26 +5,213 @@
 
 use anyhow::Result;
 
+use crate::{
+    cell_geometry::{SizeLimits, TerminalGeometry},
+    char_width::UnicodeVersion,
+    colors::{Palette, Theme},
+    config::Config,
+    cursor::{CursorSettings, CursorVisualStyle},
+    font::FontSettings,
+    links::LinkOpening,
+    presentation::PresentationSettings,
+    redaction::validate_pattern,
+    scroll::ScrollSettings,
+    shell::ShellSettings,
+    text_classes::{UrlSchemes, WordCharacters},
+    timestamps::TimestampGutter,
+    title_format::TitleFormat,
+};
+
+/// Default cap on how many frames per second we will draw while output is streaming in
+pub const DEFAULT_MAX_FPS: u32 = 60;
+
+/// Default cap on how much of a single OSC or DCS payload the parser keeps, in bytes
+pub const DEFAULT_MAX_SEQUENCE_LENGTH: usize = 4 * 1024 * 1024;
+
+/// Default number of lines kept, the screen included, before the oldest are dropped
+pub const DEFAULT_SCROLLBACK_LINES: usize = 2000;
+
+/// Scrollback that never drops a line, however much is written
+pub const UNLIMITED_SCROLLBACK_LINES: usize = usize::MAX;
+
+/// Scrollback as it is given on the command line: a number of lines, at least 1, or `unlimited`
+#[must_use]
+pub fn parse_scrollback_lines(value: &str) -> Option<usize> {
+    if value == "unlimited" {
+        return Some(UNLIMITED_SCROLLBACK_LINES);
+    }
+
+    value.parse().ok().filter(|lines| *lines > 0)
+}
+
+/// Options that belong to a single window, so a new window opened from it mustn't copy them. Each
+/// takes a value
+const WINDOW_ONLY_ARGS: [&str; 3] = ["--recording-path", "--dump-on-exit", "--control-socket"];
+
+#[allow(clippy::struct_excessive_bools)]
 pub struct Args {
     pub recording: Option<String>,
+    /// Record each session to a new, timestamped file in this directory
+    pub recording_dir: Option<String>,
     pub shell: Option<String>,
+    /// Extra arguments for the shell
+    pub shell_args: Vec<String>,
+    /// Start `shell` as a login shell. The default shell always is
+    pub login_shell: bool,
     pub show_all_debug: bool,
     pub write_logs_to_file: bool,
+    /// Maximum frames per second. 0 means uncapped
+    pub max_fps: u32,
+    /// Longest OSC or DCS payload kept, in bytes. Longer ones are dropped and reported
+    pub max_sequence_length: usize,
+    /// Characters besides letters and digits that are part of a word when selecting
+    pub word_characters: WordCharacters,
+    /// URL schemes recognized when detecting links in the output
+    pub url_schemes: UrlSchemes,
+    pub scroll_settings: ScrollSettings,
+    pub cursor_settings: CursorSettings,
+    /// Only let the window be resized in whole character cells
+    pub snap_to_cells: bool,
+    /// Size of the terminal when the window first opens
+    pub geometry: TerminalGeometry,
+    /// Drop spaces from the end of each line when copying
+    pub trim_trailing_spaces_on_copy: bool,
+    /// Replace colors picked by applications with the nearest of the 16 palette colors
+    pub clamp_colors_to_palette: bool,
+    /// Where to listen for control requests. No control socket is opened if this isn't set
+    pub control_socket: Option<String>,
+    /// Save escape sequences we can't handle to this directory
+    pub fuzz_corpus_dir: Option<String>,
+    /// Time key presses through to the screen and show the latency in the metrics HUD
+    pub measure_input_latency: bool,
+    /// Show when each line of output was written
+    pub timestamp_gutter: TimestampGutter,
+    /// Put right to left text, such as Arabic and Hebrew, in display order
+    pub bidi: bool,
+    /// Whose tables decide how many cells each character takes up
+    pub unicode_version: UnicodeVersion,
+    /// Give ambiguous width characters two cells, as East Asian locales expect
+    pub ambiguous_wide: bool,
+    /// Draw Powerline separators ourselves so they line up with the cells either side
+    pub powerline_glyphs: bool,
+    /// Draw box drawing characters and block elements ourselves so borders join up
+    pub box_drawing_glyphs: bool,
+    /// Write the terminal's text, scrollback included, here when freminal exits. `-` is stdout
+    pub dump_on_exit: Option<String>,
+    /// How the window title is put together from the application's title, directory and ID
+    pub title_format: TitleFormat,
+    /// Hide the shell's output until its first prompt mark, dropping banners such as the message
+    /// of the day
+    pub hide_startup_banner: bool,
+    /// Suggest another `TERM` when a program complains it doesn't know ours
+    pub warn_unknown_term: bool,
+    /// Blank the screen where it is when it is cleared, rather than scrolling it in to the
+    /// scrollback
+    pub clear_in_place: bool,
+    /// Show typed characters before the shell echoes them, for slow connections
+    pub predict_echo: bool,
+    /// Show long lines on one row, cut off at the edge of the screen, rather than wrapping them
+    pub no_wrap: bool,
+    pub font_settings: FontSettings,
+    /// The theme, and any of its colors the user has swapped for their own
+    pub palette: Palette,
+    /// Most lines kept, the screen included, before the oldest are dropped.
+    /// `UNLIMITED_SCROLLBACK_LINES` keeps them all
+    pub scrollback_lines: usize,
+    /// Most lines the alternate screen keeps, its own included, from the config file. 0 keeps
+    /// none
+    pub alternate_scrollback_lines: usize,
+    /// Regular expressions for secrets to mask on screen and in recordings
+    pub redact_patterns: Vec<String>,
+    pub presentation_settings: PresentationSettings,
+    /// The smallest and largest the terminal can be made, from the config file
+    pub size_limits: SizeLimits,
+    /// What it takes to open a link by clicking on it
+    pub link_opening: LinkOpening,
 }
 
 impl Args {
+    /// The arguments, without the program name, to start a new window with the same settings as
+    /// the one started with `it`. Options tied to the first window, like where it records to, are
+    /// left out
+    #[must_use]
+    pub fn new_window_args<It: Iterator<Item = String>>(it: It) -> Vec<String> {
+        let mut it = it.skip(1);
+        let mut args = Vec::new();
+
+        while let Some(arg) = it.next() {
+            if WINDOW_ONLY_ARGS.contains(&arg.as_str()) {
+                it.next();
+            } else {
+                args.push(arg);
+            }
+        }
+
+        args
+    }
+
     /// Parse the arguments
     ///
     /// # Errors
     /// Will return an error if the arguments are invalid
-    pub fn parse<It: Iterator<Item = String>>(mut it: It) -> Result<Self> {
+    pub fn parse<It: Iterator<Item = String>>(it: It) -> Result<Self> {
+        Self::parse_with_config(it, &Config::default())
+    }
+
+    /// Parse the arguments, taking anything they don't set from `config`
+    ///
+    /// # Errors
+    /// Will return an error if the arguments are invalid
+    #[allow(clippy::too_many_lines)]
+    pub fn parse_with_config<It: Iterator<Item = String>>(
+        mut it: It,
+        config: &Config,
+    ) -> Result<Self> {
         trace!("Parsing args");
 
         let program_name = it.next();
         let mut recording_path = None;
+        let mut recording_dir = None;
         let mut shell = None;
+        let mut shell_args = Vec::new();
+        let mut login_shell = false;
         let mut error = false;
         let mut show_all_debug = false;
+        let mut max_fps = DEFAULT_MAX_FPS;
+        let mut max_sequence_length = DEFAULT_MAX_SEQUENCE_LENGTH;
+        let mut word_characters = config.word_characters.clone();
+        let mut url_schemes = config.url_schemes.clone();
+        let mut scroll_settings = ScrollSettings::default();
+        let mut cursor_settings = CursorSettings {
+            style: config.cursor_style,
+            ..CursorSettings::default()
+        };
+        let mut snap_to_cells = false;
+        let mut geometry = TerminalGeometry::default();
+        let mut trim_trailing_spaces_on_copy = true;
+        let mut clamp_colors_to_palette = false;
+        let mut control_socket = None;
+        let mut fuzz_corpus_dir = None;
+        let mut measure_input_latency = false;
+        let mut timestamp_gutter = TimestampGutter::default();
+        let mut bidi = false;
+        let mut unicode_version = UnicodeVersion::default();
+        let mut ambiguous_wide = false;
+        let mut powerline_glyphs = true;
+        let mut box_drawing_glyphs = true;
+        let mut dump_on_exit = None;
+        let mut title_format = TitleFormat::default();
+        let mut hide_startup_banner = false;
+        let mut warn_unknown_term = false;
+        let mut clear_in_place = false;
+        let mut predict_echo = false;
+        let mut no_wrap = false;
+        let mut font_settings = config.font.clone();
+        let mut scrollback_lines = config.scrollback_lines;
+        let mut redact_patterns = config.redact_patterns.clone();
+        let mut presentation_settings = config.presentation.clone();
+        let mut palette = config.palette;
+        let mut link_opening = config.link_opening;
         #[cfg(debug_assertions)]
         let mut write_logs_to_file = true;
         #[cfg(not(debug_assertions))]
@@ -43,6 +230,28 @@ impl Args {
                         Some,
                     );
                 }
+                arg if arg.as_str() == "--recording-dir" => {
+                    recording_dir = it.next().map_or_else(
+                        || {
+                            println!("Missing argument for --recording-dir");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                            None
+                        },
+                        Some,
+                    );
+                }
+                arg if arg.as_str() == "--dump-on-exit" => {
+                    dump_on_exit = it.next().map_or_else(
+                        || {
+                            println!("Missing argument for --dump-on-exit");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                            None
+                        },
+                        Some,
+                    );
+                }
                 arg if arg.as_str() == "--shell" => {
                     shell = it.next().map_or_else(
                         || {
@@ -54,6 +263,336 @@ impl Args {
                         Some,
                     );
                 }
+                arg if arg.as_str() == "--shell-arg" => {
+                    if let Some(val) = it.next() {
+                        shell_args.push(val);
+                    } else {
+                        println!("Missing argument for --shell-arg");
+                        Self::help(program_name.as_deref());
+                        error = true;
+                    }
+                }
+                arg if arg.as_str() == "--login" => login_shell = true,
+                arg if arg.as_str() == "--control-socket" => {
+                    control_socket = it.next().map_or_else(
+                        || {
+                            println!("Missing argument for --control-socket");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                            None
+                        },
+                        Some,
+                    );
+                }
+                arg if arg.as_str() == "--fuzz-corpus-dir" => {
+                    fuzz_corpus_dir = it.next().map_or_else(
+                        || {
+                            println!("Missing argument for --fuzz-corpus-dir");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                            None
+                        },
+                        Some,
+                    );
+                }
+                arg if arg.as_str() == "--max-fps" => {
+                    max_fps = match it.next().map(|val| val.parse::<u32>()) {
+                        Some(Ok(val)) => val,
+                        Some(Err(_)) => {
+                            println!("Invalid argument for --max-fps");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                            DEFAULT_MAX_FPS
+                        }
+                        None => {
+                            println!("Missing argument for --max-fps");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                            DEFAULT_MAX_FPS
+                        }
+                    };
+                }
+                arg if arg.as_str() == "--max-sequence-length" => {
+                    match it.next().map(|val| val.parse::<usize>()) {
+                        Some(Ok(val)) if val > 0 => max_sequence_length = val,
+                        Some(_) => {
+                            println!("Invalid argument for --max-sequence-length");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --max-sequence-length");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--geometry" => {
+                    match it.next().map(|val| val.parse::<TerminalGeometry>()) {
+                        Some(Ok(val)) => geometry = val,
+                        Some(Err(e)) => {
+                            println!("Invalid argument for --geometry: {e}");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --geometry");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--timestamps" => {
+                    match it.next().map(|val| val.parse::<TimestampGutter>()) {
+                        Some(Ok(val)) => timestamp_gutter = val,
+                        Some(Err(e)) => {
+                            println!("Invalid argument for --timestamps: {e}");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --timestamps");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--unicode-version" => {
+                    match it.next().map(|val| val.parse::<UnicodeVersion>()) {
+                        Some(Ok(val)) => unicode_version = val,
+                        Some(Err(e)) => {
+                            println!("Invalid argument for --unicode-version: {e}");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --unicode-version");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--hide-startup-banner" => hide_startup_banner = true,
+                arg if arg.as_str() == "--warn-unknown-term" => warn_unknown_term = true,
+                arg if arg.as_str() == "--clear-in-place" => clear_in_place = true,
+                arg if arg.as_str() == "--predict-echo" => predict_echo = true,
+                arg if arg.as_str() == "--no-wrap" => no_wrap = true,
+                arg if arg.as_str() == "--title-format" => {
+                    match it.next().map(|val| val.parse::<TitleFormat>()) {
+                        Some(Ok(val)) => title_format = val,
+                        Some(Err(e)) => {
+                            println!("Invalid argument for --title-format: {e}");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --title-format");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--font-family" => {
+                    if let Some(val) = it.next() {
+                        font_settings.family = Some(val);
+                    } else {
+                        println!("Missing argument for --font-family");
+                        Self::help(program_name.as_deref());
+                        error = true;
+                    }
+                }
+                arg if arg.as_str() == "--theme" => {
+                    match it.next().map(|val| val.parse::<Theme>()) {
+                        Some(Ok(val)) => palette.set_theme(val),
+                        Some(Err(e)) => {
+                            println!("{e}");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --theme");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--font-size" => {
+                    match it.next().map(|val| val.parse::<f32>()) {
+                        Some(Ok(val)) if (1.0..=100.0).contains(&val) => font_settings.size = val,
+                        Some(_) => {
+                            println!("Invalid argument for --font-size");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --font-size");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--scrollback-lines" => {
+                    match it.next().map(|val| parse_scrollback_lines(&val)) {
+                        Some(Some(val)) => scrollback_lines = val,
+                        Some(_) => {
+                            println!("Invalid argument for --scrollback-lines");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --scrollback-lines");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                // The config file has already been read by the time the arguments are
+                arg if arg.as_str() == "--config" => {
+                    if it.next().is_none() {
+                        println!("Missing argument for --config");
+                        Self::help(program_name.as_deref());
+                        error = true;
+                    }
+                }
+                arg if arg.as_str() == "--presentation-scale" => {
+                    match it.next().map(|val| val.parse::<f32>()) {
+                        Some(Ok(val)) if (1.0..=10.0).contains(&val) => {
+                            presentation_settings.font_scale = val;
+                        }
+                        Some(_) => {
+                            println!("Invalid argument for --presentation-scale");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --presentation-scale");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--redact" => {
+                    if let Some(val) = it.next() {
+                        match validate_pattern(&val) {
+                            Ok(()) => redact_patterns.push(val),
+                            Err(e) => {
+                                println!("Invalid argument for --redact: {e}");
+                                Self::help(program_name.as_deref());
+                                error = true;
+                            }
+                        }
+                    } else {
+                        println!("Missing argument for --redact");
+                        Self::help(program_name.as_deref());
+                        error = true;
+                    }
+                }
+                arg if arg.as_str() == "--word-chars" => {
+                    if let Some(val) = it.next() {
+                        word_characters = WordCharacters::new(&val);
+                    } else {
+                        println!("Missing argument for --word-chars");
+                        Self::help(program_name.as_deref());
+                        error = true;
+                    }
+                }
+                arg if arg.as_str() == "--url-schemes" => {
+                    if let Some(val) = it.next() {
+                        url_schemes = UrlSchemes::from_list(&val);
+                    } else {
+                        println!("Missing argument for --url-schemes");
+                        Self::help(program_name.as_deref());
+                        error = true;
+                    }
+                }
+                arg if arg.as_str() == "--scroll-lines" => {
+                    match it.next().map(|val| val.parse::<f32>()) {
+                        Some(Ok(val)) if val > 0.0 => scroll_settings.lines_per_wheel_tick = val,
+                        Some(_) => {
+                            println!("Invalid argument for --scroll-lines");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --scroll-lines");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--cursor-thickness" => {
+                    match it.next().map(|val| val.parse::<f32>()) {
+                        Some(Ok(val)) if val > 0.0 => cursor_settings.thickness = val,
+                        Some(_) => {
+                            println!("Invalid argument for --cursor-thickness");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --cursor-thickness");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--cursor-outline-width" => {
+                    match it.next().map(|val| val.parse::<f32>()) {
+                        Some(Ok(val)) if val > 0.0 => cursor_settings.outline_width = val,
+                        Some(_) => {
+                            println!("Invalid argument for --cursor-outline-width");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --cursor-outline-width");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--cursor-style" => {
+                    match it.next().map(|val| val.parse::<CursorVisualStyle>()) {
+                        Some(Ok(val)) => cursor_settings.style = val,
+                        Some(Err(e)) => {
+                            println!("{e}");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --cursor-style");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--open-links" => {
+                    match it.next().map(|val| val.parse::<LinkOpening>()) {
+                        Some(Ok(val)) => link_opening = val,
+                        Some(Err(e)) => {
+                            println!("{e}");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                        None => {
+                            println!("Missing argument for --open-links");
+                            Self::help(program_name.as_deref());
+                            error = true;
+                        }
+                    }
+                }
+                arg if arg.as_str() == "--no-cursor-blink" => cursor_settings.blink = false,
+                arg if arg.as_str() == "--invert-scroll" => scroll_settings.invert = true,
+                arg if arg.as_str() == "--no-smooth-scroll" => scroll_settings.smooth = false,
+                arg if arg.as_str() == "--snap-to-cells" => snap_to_cells = true,
+                arg if arg.as_str() == "--keep-trailing-spaces" => {
+                    trim_trailing_spaces_on_copy = false;
+                }
+                arg if arg.as_str() == "--palette-colors-only" => clamp_colors_to_palette = true,
+                arg if arg.as_str() == "--measure-latency" => measure_input_latency = true,
+                arg if arg.as_str() == "--bidi" => bidi = true,
+                arg if arg.as_str() == "--ambiguous-wide" => ambiguous_wide = true,
+                arg if arg.as_str() == "--no-powerline-glyphs" => powerline_glyphs = false,
+                arg if arg.as_str() == "--no-box-drawing-glyphs" => box_drawing_glyphs = false,
                 arg if arg.as_str() == "--help" => Self::help(program_name.as_deref()),
                 arg if arg.as_str() == "--show-all-debug" => show_all_debug = true,
                 arg if arg.as_str().contains("--write-logs-to-file") => {
@@ -87,18 +626,80 @@ impl Args {
             }
         }
 
+        // The config's shell arguments are for the config's shell, not one picked here
+        if shell.is_none() {
+            shell.clone_from(&config.shell);
+            login_shell |= config.login_shell;
+            if shell_args.is_empty() {
+                shell_args.clone_from(&config.shell_args);
+            }
+        }
+
+        if recording_path.is_some() && recording_dir.is_some() {
+            println!("--recording-path and --recording-dir can't be used together");
+            Self::help(program_name.as_deref());
+            error = true;
+        }
+
         if error {
             return Err(anyhow::anyhow!("Invalid arguments"));
         }
 
         Ok(Self {
             recording: recording_path,
+            recording_dir,
             shell,
+            shell_args,
+            login_shell,
             show_all_debug,
             write_logs_to_file,
+            max_fps,
+            max_sequence_length,
+            word_characters,
+            url_schemes,
+            scroll_settings,
+            cursor_settings,
+            snap_to_cells,
+            geometry,
+            trim_trailing_spaces_on_copy,
+            clamp_colors_to_palette,
+            control_socket,
+            fuzz_corpus_dir,
+            measure_input_latency,
+            timestamp_gutter,
+            bidi,
+            unicode_version,
+            ambiguous_wide,
+            powerline_glyphs,
+            box_drawing_glyphs,
+            dump_on_exit,
+            title_format,
+            hide_startup_banner,
+            warn_unknown_term,
+            clear_in_place,
+            predict_echo,
+            no_wrap,
+            font_settings,
+            palette,
+            scrollback_lines,
+            alternate_scrollback_lines: config.alternate_scrollback_lines,
+            redact_patterns,
+            presentation_settings,
+            size_limits: config.size_limits,
+            link_opening,
         })
     }
 
+    /// What to run in the terminal
+    #[must_use]
+    pub fn shell_settings(&self) -> ShellSettings {
+        ShellSettings {
+            program: self.shell.clone(),
+            args: self.shell_args.clone(),
+            login: self.login_shell,
+        }
+    }
+
     fn help(program_name: Option<&str>) {
         trace!("Showing help");
 
@@ -107,9 +708,10 @@ impl Args {
             "\
                  Usage:\n\
                  {program_name} [ARGS]\n\
+                 {program_name} msg [--to <socket>] <command>: Control a running freminal, see msg --help\n\
                  \n\
                  Args:\n\
-                    --recording-path: Optional, where to output recordings to\n--shell: Optional, shell to run\n--help: Show this help message\n--write-logs-to-file=[true/false]\
+                    --recording-path: Optional, where to output recordings to\n--recording-dir: Optional, directory to record each session to a new timestamped file in\n--shell: Optional, shell to run (default is your login shell)\n--shell-arg: Optional, argument to pass to the shell, may be repeated\n--login: Optional, start --shell as a login shell\n--help: Show this help message\n--write-logs-to-file=[true/false]\n--max-fps: Optional, maximum frames per second to draw (default 60, 0 for uncapped)\n--max-sequence-length: Optional, longest OSC or DCS payload in bytes to accept before dropping the rest (default 4194304)\n--word-chars: Optional, characters other than letters and digits that are part of a word (default \"_\")\n--url-schemes: Optional, comma separated URL schemes to detect in output\n--scroll-lines: Optional, lines to scroll per mouse wheel notch (default 3)\n--invert-scroll: Optional, scroll in the opposite direction to the wheel\n--no-smooth-scroll: Optional, jump straight to the new position when scrolling\n--cursor-thickness: Optional, thickness of underline and bar cursors in points (default 2)\n--cursor-outline-width: Optional, width of the cursor outline drawn when the window isn't focused (default 1)\n--cursor-style: Optional, cursor until an application picks one: block (default), underline or bar, with blinking- in front to blink\n--no-cursor-blink: Optional, never blink the cursor, even if an application asks for it\n--snap-to-cells: Optional, resize the window in whole character cells\n--geometry: Optional, initial size of the terminal as COLUMNSxROWS (default 80x24)\n--keep-trailing-spaces: Optional, keep spaces at the end of lines when copying\n--palette-colors-only: Optional, draw 256 and true color output with the nearest of the 16 palette colors\n--control-socket: Optional, path of a unix socket to accept JSON-RPC control requests on\n--fuzz-corpus-dir: Optional, directory to save escape sequences freminal can't handle to\n--timestamps: Optional, show when each line of output was written, in local time: off (default), hover or always\n--measure-latency: Optional, show key press to screen latency in the metrics overlay\n--bidi: Optional, show right to left text such as Arabic and Hebrew in reading order\n--unicode-version: Optional, Unicode version to take character widths from, 8 for single width emoji (default 9)\n--ambiguous-wide: Optional, give ambiguous width characters such as box drawing two cells, as in East Asian locales\n--no-powerline-glyphs: Optional, draw Powerline prompt separators with the font instead of filling their cells exactly\n--no-box-drawing-glyphs: Optional, draw box drawing and block characters with the font instead of joining them up exactly\n--dump-on-exit: Optional, file to write the terminal's text to when freminal exits, - for stdout\n--title-format: Optional, window title made from {{title}}, {{cwd}} and {{id}}, such as \"{{title}} - {{cwd}}\" (default {{title}})\n--hide-startup-banner: Optional, hide the shell's output until its first OSC 133 prompt mark, such as the message of the day\n--warn-unknown-term: Optional, suggest TERM=xterm-256color when a program says it doesn't know freminal's TERM\n--clear-in-place: Optional, blank the screen when it is cleared instead of scrolling it in to the scrollback\n--predict-echo: Optional, experimental, show typed characters straight away rather than waiting for the shell to echo them, for slow connections\n--no-wrap: Optional, show long lines cut off at the edge of the screen instead of wrapping them, scrolling sideways with Shift and the mouse wheel\n--config: Optional, config file to read instead of ~/.config/freminal/config.toml\n--font-family: Optional, installed font family to draw the text with (default is the bundled Meslo)\n--theme: Optional, colors to use: xterm (default), solarized-dark, solarized-light, dracula or gruvbox\n--font-size: Optional, size of the text in points (default 12)\n--scrollback-lines: Optional, most lines to keep, the screen included, or unlimited (default 2000)\n--redact: Optional, regular expression for secrets to mask on screen and in recordings, may be repeated\n--presentation-scale: Optional, how much bigger presentation mode (Ctrl+Shift+P) makes the text (default 1.5)\n--open-links: Optional, what opens a link that is clicked on: click (default), ctrl-click, or confirm to ask first\
                  "
         );
     }
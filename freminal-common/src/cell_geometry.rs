@@ -0,0 +1,351 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::str::FromStr;
+
+use conv::ConvUtil;
+
+/// How close to a whole number of cells a size has to be to count as that number of cells.
+/// Float error in the font metrics would otherwise turn 80 columns in to 79.999 and then 79
+const CELL_EPSILON: f32 = 0.001;
+
+/// Conversions between character cells, points and physical pixels
+///
+/// Everything the GUI measures is in points. Sizes reported to the pty and through XTWINOPS are in
+/// physical pixels, which depend on the display scale and zoom (`pixels_per_point`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellGeometry {
+    /// Size of a single cell, in points
+    pub cell_width: f32,
+    pub cell_height: f32,
+    pub pixels_per_point: f32,
+    /// Space around the text area inside the window, in points
+    pub padding_width: f32,
+    pub padding_height: f32,
+}
+
+impl CellGeometry {
+    #[must_use]
+    pub const fn new(cell_width: f32, cell_height: f32, pixels_per_point: f32) -> Self {
+        Self {
+            cell_width,
+            cell_height,
+            pixels_per_point,
+            padding_width: 0.0,
+            padding_height: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_padding(mut self, padding_width: f32, padding_height: f32) -> Self {
+        self.padding_width = padding_width;
+        self.padding_height = padding_height;
+        self
+    }
+
+    /// How many whole cells fit in an area of `width` x `height` points
+    #[must_use]
+    pub fn cells_for_size(&self, width: f32, height: f32) -> (usize, usize) {
+        (
+            whole_cells(width, self.cell_width),
+            whole_cells(height, self.cell_height),
+        )
+    }
+
+    /// How many whole cells fit in a window of `width` x `height` points, once the padding is
+    /// taken off
+    #[must_use]
+    pub fn cells_for_window_size(&self, width: f32, height: f32) -> (usize, usize) {
+        self.cells_for_size(width - self.padding_width, height - self.padding_height)
+    }
+
+    /// The size in points of `columns` x `rows` cells
+    #[must_use]
+    pub fn size_for_cells(&self, columns: usize, rows: usize) -> (f32, f32) {
+        (
+            usize_to_f32(columns) * self.cell_width,
+            usize_to_f32(rows) * self.cell_height,
+        )
+    }
+
+    /// The window size in points needed to show `columns` x `rows` cells
+    #[must_use]
+    pub fn window_size_for_cells(&self, columns: usize, rows: usize) -> (f32, f32) {
+        let (width, height) = self.size_for_cells(columns, rows);
+        (width + self.padding_width, height + self.padding_height)
+    }
+
+    /// Convert points to physical pixels
+    #[must_use]
+    pub fn points_to_pixels(&self, points: f32) -> usize {
+        f32_to_usize((points * self.pixels_per_point).round())
+    }
+
+    /// Convert physical pixels to points
+    #[must_use]
+    pub fn pixels_to_points(&self, pixels: usize) -> f32 {
+        if self.pixels_per_point <= 0.0 {
+            return 0.0;
+        }
+
+        usize_to_f32(pixels) / self.pixels_per_point
+    }
+
+    /// Round `points` to the nearest whole physical pixel
+    #[must_use]
+    pub fn snap_to_pixel(&self, points: f32) -> f32 {
+        if self.pixels_per_point <= 0.0 {
+            return points;
+        }
+
+        (points * self.pixels_per_point).round() / self.pixels_per_point
+    }
+
+    /// The edges of `columns` cells starting at `column` on `row`, as `(left, top, right, bottom)`
+    /// in points, for a text area whose top left corner is at `origin`
+    ///
+    /// Every edge is worked out from its own cell index and rounded to a whole physical pixel, so
+    /// the right edge of one cell is exactly the left edge of the next and nothing shows through
+    /// between rows or columns at any zoom.
+    #[must_use]
+    pub fn cell_span_edges(
+        &self,
+        origin: (f32, f32),
+        column: usize,
+        row: usize,
+        columns: usize,
+    ) -> (f32, f32, f32, f32) {
+        let (left, top) = self.size_for_cells(column, row);
+        let (right, bottom) = self.size_for_cells(column + columns, row + 1);
+
+        (
+            self.snap_to_pixel(origin.0 + left),
+            self.snap_to_pixel(origin.1 + top),
+            self.snap_to_pixel(origin.0 + right),
+            self.snap_to_pixel(origin.1 + bottom),
+        )
+    }
+
+    /// The size of a single cell in physical pixels
+    #[must_use]
+    pub fn cell_size_in_pixels(&self) -> (usize, usize) {
+        (
+            self.points_to_pixels(self.cell_width),
+            self.points_to_pixels(self.cell_height),
+        )
+    }
+
+    /// The size of `columns` x `rows` cells in physical pixels
+    #[must_use]
+    pub fn text_area_size_in_pixels(&self, columns: usize, rows: usize) -> (usize, usize) {
+        let (width, height) = self.size_for_cells(columns, rows);
+        (self.points_to_pixels(width), self.points_to_pixels(height))
+    }
+}
+
+/// Where the terminal's cells are on screen, for working out which cell the pointer is over
+///
+/// Selection, mouse reporting and link hovering all go through this, so they agree on which cell
+/// a point belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellGrid {
+    pub geometry: CellGeometry,
+    /// Top left corner of the first cell, in points. While a scroll is animating this is where
+    /// the output will end up, not where it is drawn
+    pub origin: (f32, f32),
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl CellGrid {
+    #[must_use]
+    pub const fn new(
+        geometry: CellGeometry,
+        origin: (f32, f32),
+        columns: usize,
+        rows: usize,
+    ) -> Self {
+        Self {
+            geometry,
+            origin,
+            columns,
+            rows,
+        }
+    }
+
+    /// The cell under `pointer`, as `(column, row)` counting from 0. A point on the edge between
+    /// two cells belongs to the one after it
+    ///
+    /// Returns `None` if the point is outside the cells, including over the padding or over a
+    /// partial cell left at the edge of the window.
+    #[must_use]
+    pub fn cell_at(&self, pointer: (f32, f32)) -> Option<(usize, usize)> {
+        let column = cell_index(
+            pointer.0 - self.origin.0,
+            self.geometry.cell_width,
+            self.columns,
+        )?;
+        let row = cell_index(
+            pointer.1 - self.origin.1,
+            self.geometry.cell_height,
+            self.rows,
+        )?;
+
+        Some((column, row))
+    }
+
+    /// The cell closest to `pointer`, as `(column, row)` counting from 0. Points outside the
+    /// cells go to the nearest one on the edge, as mouse reports and drag selections want
+    #[must_use]
+    pub fn nearest_cell(&self, pointer: (f32, f32)) -> (usize, usize) {
+        (
+            nearest_cell_index(
+                pointer.0 - self.origin.0,
+                self.geometry.cell_width,
+                self.columns,
+            ),
+            nearest_cell_index(
+                pointer.1 - self.origin.1,
+                self.geometry.cell_height,
+                self.rows,
+            ),
+        )
+    }
+}
+
+/// Which of `count` cells of `cell_length` an `offset` from the first one falls in
+fn cell_index(offset: f32, cell_length: f32, count: usize) -> Option<usize> {
+    if cell_length <= 0.0 || offset < 0.0 || !offset.is_finite() {
+        return None;
+    }
+
+    let index = f32_to_usize((offset / cell_length).floor());
+    (index < count).then_some(index)
+}
+
+fn nearest_cell_index(offset: f32, cell_length: f32, count: usize) -> usize {
+    if cell_length <= 0.0 || offset.is_nan() {
+        return 0;
+    }
+
+    f32_to_usize(
+        (offset / cell_length)
+            .floor()
+            .clamp(0.0, usize_to_f32(count.saturating_sub(1))),
+    )
+}
+
+/// The size of the terminal in character cells, written `COLUMNSxROWS` (e.g. `120x40`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalGeometry {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl TerminalGeometry {
+    #[must_use]
+    pub const fn new(columns: usize, rows: usize) -> Self {
+        Self { columns, rows }
+    }
+}
+
+impl Default for TerminalGeometry {
+    fn default() -> Self {
+        Self::new(80, 24)
+    }
+}
+
+impl FromStr for TerminalGeometry {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || anyhow::anyhow!("Invalid geometry {s}. Expected COLUMNSxROWS, e.g. 120x40");
+
+        let (columns, rows) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let columns = columns.trim().parse::<usize>().map_err(|_| invalid())?;
+        let rows = rows.trim().parse::<usize>().map_err(|_| invalid())?;
+
+        if columns == 0 || rows == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(columns, rows))
+    }
+}
+
+/// The smallest size the terminal is made by default. Anything smaller isn't usable
+pub const DEFAULT_MIN_GEOMETRY: TerminalGeometry = TerminalGeometry::new(20, 5);
+
+/// The largest size the terminal is made by default, well past any real screen. Much bigger
+/// and the buffer would use more memory than any window could be worth
+pub const DEFAULT_MAX_GEOMETRY: TerminalGeometry = TerminalGeometry::new(1000, 500);
+
+/// The smallest and largest the terminal can be, whatever size the window is or an application
+/// asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeLimits {
+    pub min: TerminalGeometry,
+    pub max: TerminalGeometry,
+}
+
+impl SizeLimits {
+    /// # Errors
+    /// Will return an error if `min` is bigger than `max` in either direction
+    pub fn new(min: TerminalGeometry, max: TerminalGeometry) -> anyhow::Result<Self> {
+        if min.columns > max.columns || min.rows > max.rows {
+            return Err(anyhow::anyhow!(
+                "The smallest size {}x{} is bigger than the largest {}x{}",
+                min.columns,
+                min.rows,
+                max.columns,
+                max.rows
+            ));
+        }
+
+        Ok(Self { min, max })
+    }
+
+    /// `columns` x `rows` brought within the limits
+    #[must_use]
+    pub fn clamp(&self, columns: usize, rows: usize) -> (usize, usize) {
+        (
+            columns.min(self.max.columns).max(self.min.columns),
+            rows.min(self.max.rows).max(self.min.rows),
+        )
+    }
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            min: DEFAULT_MIN_GEOMETRY,
+            max: DEFAULT_MAX_GEOMETRY,
+        }
+    }
+}
+
+fn whole_cells(length: f32, cell_length: f32) -> usize {
+    if cell_length <= 0.0 || length <= 0.0 {
+        return 0;
+    }
+
+    f32_to_usize((length / cell_length + CELL_EPSILON).floor())
+}
+
+fn usize_to_f32(value: usize) -> f32 {
+    value.approx_as::<f32>().unwrap_or_else(|e| {
+        error!("Failed to convert {value} to f32: {e}. Using 0 as default");
+        0.0
+    })
+}
+
+/// Negative sizes are treated as 0
+fn f32_to_usize(value: f32) -> usize {
+    value.max(0.0).approx_as::<usize>().unwrap_or_else(|e| {
+        error!("Failed to convert {value} to usize: {e}. Using 0 as default");
+        0
+    })
+}
@@ -0,0 +1,150 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{fmt, str::FromStr};
+
+use unicode_width::UnicodeWidthChar;
+
+/// Asks for the emoji before it to be drawn as a picture, two cells wide
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{FE0F}';
+
+/// The Unicode version whose character widths we follow
+///
+/// Programs work out where the cursor ends up from the same tables, so if they disagree with us
+/// prompts and full screen programs are drawn in the wrong place.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum UnicodeVersion {
+    /// Before Unicode 9 emoji took up a single cell
+    Unicode8,
+    /// Emoji take up two cells. Later versions haven't changed any widths we care about, so this
+    /// covers them too
+    #[default]
+    Unicode9,
+}
+
+impl FromStr for UnicodeVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let major = s.trim().split('.').next().unwrap_or_default();
+        match major.parse::<u32>() {
+            Ok(0..=8) => Ok(Self::Unicode8),
+            Ok(_) => Ok(Self::Unicode9),
+            Err(_) => Err(anyhow::anyhow!(
+                "Invalid Unicode version {s}. Expected a version number such as 8 or 15.1"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UnicodeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unicode8 => write!(f, "8"),
+            Self::Unicode9 => write!(f, "9"),
+        }
+    }
+}
+
+/// Decides how many cells each character takes up
+///
+/// Everything that needs to know, from moving the cursor to drawing, asks this so they all agree.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct WidthProvider {
+    pub unicode_version: UnicodeVersion,
+    /// Give characters whose width depends on the locale, such as box drawing, arrows and circled
+    /// numbers, two cells like East Asian terminals do. The same as xterm's `cjkWidth`
+    pub ambiguous_wide: bool,
+}
+
+impl WidthProvider {
+    #[must_use]
+    pub const fn new(unicode_version: UnicodeVersion, ambiguous_wide: bool) -> Self {
+        Self {
+            unicode_version,
+            ambiguous_wide,
+        }
+    }
+
+    /// Cells taken up by `c` on its own: 0, 1 or 2
+    #[must_use]
+    pub fn char_width(&self, c: char) -> usize {
+        if is_zero_width_char(c) {
+            return 0;
+        }
+
+        let width = if self.ambiguous_wide {
+            c.width_cjk()
+        } else {
+            c.width()
+        }
+        // Control characters never make it to the buffer, but if one does it still needs a cell
+        .unwrap_or(1);
+
+        if width == 2 && self.unicode_version == UnicodeVersion::Unicode8 && is_emoji(c) {
+            return 1;
+        }
+
+        width
+    }
+
+    /// Cells taken up by a grapheme cluster. The cluster gets the width of its first character,
+    /// unless it asks to be drawn as an emoji
+    #[must_use]
+    pub fn grapheme_width(&self, grapheme: &str) -> usize {
+        let mut chars = grapheme.chars().filter(|c| !is_zero_width_char(*c));
+        let Some(first) = chars.next() else {
+            return 0;
+        };
+
+        let width = self.char_width(first).max(1);
+        if self.unicode_version != UnicodeVersion::Unicode8
+            && chars.any(|c| c == EMOJI_PRESENTATION_SELECTOR)
+        {
+            return 2;
+        }
+
+        width
+    }
+}
+
+/// Characters that are kept in the buffer but never take up a cell: zero width spaces and
+/// joiners, the word joiner, soft hyphens and byte order marks
+#[must_use]
+pub const fn is_zero_width_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}'
+    )
+}
+
+/// The zero width joiner and non-joiner
+///
+/// They change how the characters either side of them are drawn, joining emoji in to one or
+/// choosing the form of a letter in Indic and Persian text, so they are drawn with the character
+/// before them rather than left out.
+#[must_use]
+pub const fn is_joiner(c: char) -> bool {
+    matches!(c, '\u{200C}' | '\u{200D}')
+}
+
+/// Whether `c` is only wide because ambiguous width characters are. Fonts draw these narrow, so
+/// they need padding out to fill both cells
+#[must_use]
+pub fn is_ambiguous_width(c: char) -> bool {
+    c.width() != c.width_cjk()
+}
+
+/// Pictographs that became two cells wide in Unicode 9, when they were given emoji presentation
+const fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{231A}'..='\u{23FF}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2B00}'..='\u{2BFF}'
+            | '\u{1F000}'..='\u{1F1FF}'
+            | '\u{1F300}'..='\u{1FAFF}'
+    )
+}
@@ -80,6 +80,26 @@ pub enum TerminalColor {
     Custom(u8, u8, u8),
 }
 
+/// The 16 colors of the theme palette, in SGR order
+pub const PALETTE_COLORS: [TerminalColor; 16] = [
+    TerminalColor::Black,
+    TerminalColor::Red,
+    TerminalColor::Green,
+    TerminalColor::Yellow,
+    TerminalColor::Blue,
+    TerminalColor::Magenta,
+    TerminalColor::Cyan,
+    TerminalColor::White,
+    TerminalColor::BrightBlack,
+    TerminalColor::BrightRed,
+    TerminalColor::BrightGreen,
+    TerminalColor::BrightYellow,
+    TerminalColor::BrightBlue,
+    TerminalColor::BrightMagenta,
+    TerminalColor::BrightCyan,
+    TerminalColor::BrightWhite,
+];
+
 impl TerminalColor {
     #[must_use]
     pub const fn default_to_regular(self) -> Self {
@@ -89,6 +109,377 @@ impl TerminalColor {
             _ => self,
         }
     }
+
+    /// The RGB value the theme uses for this color. The defaults depend on the GUI, so they
+    /// have none
+    #[must_use]
+    pub const fn palette_rgb(self) -> Option<(u8, u8, u8)> {
+        let rgb = match self {
+            Self::Default
+            | Self::DefaultBackground
+            | Self::DefaultUnderlineColor
+            | Self::DefaultCursorColor => return None,
+            Self::Black => (0, 0, 0),
+            Self::Red => (205, 0, 0),
+            Self::Green => (0, 205, 0),
+            Self::Yellow => (205, 205, 0),
+            Self::Blue => (0, 0, 238),
+            Self::Magenta => (205, 0, 205),
+            Self::Cyan => (0, 205, 205),
+            Self::White => (229, 229, 229),
+            Self::BrightYellow => (255, 255, 0),
+            Self::BrightRed => (255, 0, 0),
+            Self::BrightGreen => (0, 255, 0),
+            Self::BrightBlue => (92, 92, 255),
+            Self::BrightMagenta => (255, 0, 255),
+            Self::BrightCyan => (0, 255, 255),
+            Self::BrightWhite => (255, 255, 255),
+            Self::BrightBlack => (127, 127, 127),
+            Self::Custom(r, g, b) => (r, g, b),
+        };
+
+        Some(rgb)
+    }
+
+    /// Where this color sits in the 16 color palette, if it is one of them
+    #[must_use]
+    pub fn palette_index(self) -> Option<usize> {
+        PALETTE_COLORS.iter().position(|color| *color == self)
+    }
+
+    /// The palette color that looks closest to `r`, `g`, `b`
+    #[must_use]
+    pub fn nearest_palette_color(r: u8, g: u8, b: u8) -> Self {
+        PALETTE_COLORS
+            .into_iter()
+            .min_by_key(|color| {
+                color
+                    .palette_rgb()
+                    .map_or(u32::MAX, |rgb| color_distance((r, g, b), rgb))
+            })
+            .unwrap_or(Self::Default)
+    }
+
+    /// Replace a color the application picked itself with the nearest palette color. Palette and
+    /// default colors are left alone
+    #[must_use]
+    pub fn clamp_to_palette(self) -> Self {
+        match self {
+            Self::Custom(r, g, b) => Self::nearest_palette_color(r, g, b),
+            _ => self,
+        }
+    }
+}
+
+/// A built in set of colors for the palette and the default foreground and background
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Theme {
+    /// xterm's colors, with the GUI's default foreground and background
+    #[default]
+    Xterm,
+    SolarizedDark,
+    SolarizedLight,
+    Dracula,
+    Gruvbox,
+}
+
+/// Every built in theme, in the order they are offered to the user
+pub const THEMES: [Theme; 5] = [
+    Theme::Xterm,
+    Theme::SolarizedDark,
+    Theme::SolarizedLight,
+    Theme::Dracula,
+    Theme::Gruvbox,
+];
+
+const SOLARIZED_COLORS: [(u8, u8, u8); 16] = [
+    (0x07, 0x36, 0x42),
+    (0xdc, 0x32, 0x2f),
+    (0x85, 0x99, 0x00),
+    (0xb5, 0x89, 0x00),
+    (0x26, 0x8b, 0xd2),
+    (0xd3, 0x36, 0x82),
+    (0x2a, 0xa1, 0x98),
+    (0xee, 0xe8, 0xd5),
+    (0x00, 0x2b, 0x36),
+    (0xcb, 0x4b, 0x16),
+    (0x58, 0x6e, 0x75),
+    (0x65, 0x7b, 0x83),
+    (0x83, 0x94, 0x96),
+    (0x6c, 0x71, 0xc4),
+    (0x93, 0xa1, 0xa1),
+    (0xfd, 0xf6, 0xe3),
+];
+
+const DRACULA_COLORS: [(u8, u8, u8); 16] = [
+    (0x21, 0x22, 0x2c),
+    (0xff, 0x55, 0x55),
+    (0x50, 0xfa, 0x7b),
+    (0xf1, 0xfa, 0x8c),
+    (0xbd, 0x93, 0xf9),
+    (0xff, 0x79, 0xc6),
+    (0x8b, 0xe9, 0xfd),
+    (0xf8, 0xf8, 0xf2),
+    (0x62, 0x72, 0xa4),
+    (0xff, 0x6e, 0x6e),
+    (0x69, 0xff, 0x94),
+    (0xff, 0xff, 0xa5),
+    (0xd6, 0xac, 0xff),
+    (0xff, 0x92, 0xdf),
+    (0xa4, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+const GRUVBOX_COLORS: [(u8, u8, u8); 16] = [
+    (0x28, 0x28, 0x28),
+    (0xcc, 0x24, 0x1d),
+    (0x98, 0x97, 0x1a),
+    (0xd7, 0x99, 0x21),
+    (0x45, 0x85, 0x88),
+    (0xb1, 0x62, 0x86),
+    (0x68, 0x9d, 0x6a),
+    (0xa8, 0x99, 0x84),
+    (0x92, 0x83, 0x74),
+    (0xfb, 0x49, 0x34),
+    (0xb8, 0xbb, 0x26),
+    (0xfa, 0xbd, 0x2f),
+    (0x83, 0xa5, 0x98),
+    (0xd3, 0x86, 0x9b),
+    (0x8e, 0xc0, 0x7c),
+    (0xeb, 0xdb, 0xb2),
+];
+
+impl Theme {
+    /// The colors of the 16 palette entries, in SGR order
+    #[must_use]
+    pub fn colors(self) -> [(u8, u8, u8); 16] {
+        match self {
+            Self::Xterm => PALETTE_COLORS.map(|color| color.palette_rgb().unwrap_or_default()),
+            Self::SolarizedDark | Self::SolarizedLight => SOLARIZED_COLORS,
+            Self::Dracula => DRACULA_COLORS,
+            Self::Gruvbox => GRUVBOX_COLORS,
+        }
+    }
+
+    /// The color of text that doesn't pick one, or `None` to leave it to the GUI
+    #[must_use]
+    pub const fn foreground(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Self::Xterm => None,
+            Self::SolarizedDark => Some((0x83, 0x94, 0x96)),
+            Self::SolarizedLight => Some((0x65, 0x7b, 0x83)),
+            Self::Dracula => Some((0xf8, 0xf8, 0xf2)),
+            Self::Gruvbox => Some((0xeb, 0xdb, 0xb2)),
+        }
+    }
+
+    /// The color behind text that doesn't pick one, or `None` to leave it to the GUI
+    #[must_use]
+    pub const fn background(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Self::Xterm => None,
+            Self::SolarizedDark => Some((0x00, 0x2b, 0x36)),
+            Self::SolarizedLight => Some((0xfd, 0xf6, 0xe3)),
+            Self::Dracula => Some((0x28, 0x2a, 0x36)),
+            Self::Gruvbox => Some((0x28, 0x28, 0x28)),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Xterm => "xterm",
+            Self::SolarizedDark => "solarized-dark",
+            Self::SolarizedLight => "solarized-light",
+            Self::Dracula => "dracula",
+            Self::Gruvbox => "gruvbox",
+        })
+    }
+}
+
+impl std::str::FromStr for Theme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        THEMES
+            .into_iter()
+            .find(|theme| theme.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<String> = THEMES.iter().map(ToString::to_string).collect();
+                anyhow::anyhow!("Unknown theme {s}, expected one of {}", names.join(", "))
+            })
+    }
+}
+
+/// The colors the 16 palette entries and the default foreground and background are drawn with
+///
+/// Starts out as a built in theme, xterm's colors unless the user picks another. Any of the
+/// colors can be swapped for the user's own, and the swaps are kept if the theme is changed.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Palette {
+    theme: Theme,
+    overrides: [Option<(u8, u8, u8)>; 16],
+    foreground: Option<(u8, u8, u8)>,
+    background: Option<(u8, u8, u8)>,
+}
+
+impl Palette {
+    #[must_use]
+    pub fn new(theme: Theme) -> Self {
+        Self {
+            theme,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub const fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Switch to `theme`, keeping the colors the user picked themselves
+    pub const fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// The color of palette entry `index`, 0 to 15
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<(u8, u8, u8)> {
+        let theme_color = self.theme.colors().get(index).copied();
+        self.overrides.get(index).copied().flatten().or(theme_color)
+    }
+
+    /// Draw palette entry `index` with `rgb`. Indexes past the 16 entries are ignored
+    pub fn set(&mut self, index: usize, rgb: (u8, u8, u8)) {
+        if let Some(color) = self.overrides.get_mut(index) {
+            *color = Some(rgb);
+        }
+    }
+
+    /// Go back to the theme's color for palette entry `index`
+    pub fn reset(&mut self, index: usize) {
+        if let Some(color) = self.overrides.get_mut(index) {
+            *color = None;
+        }
+    }
+
+    /// Take palette entry `index`, and whether it is the theme's color, from `other`
+    pub fn copy_entry(&mut self, index: usize, other: &Self) {
+        if let (Some(color), Some(other_color)) =
+            (self.overrides.get_mut(index), other.overrides.get(index))
+        {
+            *color = *other_color;
+        }
+    }
+
+    /// The default text color, or `None` to leave it to the GUI
+    #[must_use]
+    pub fn foreground(&self) -> Option<(u8, u8, u8)> {
+        self.foreground.or_else(|| self.theme.foreground())
+    }
+
+    /// The default background color, or `None` to leave it to the GUI
+    #[must_use]
+    pub fn background(&self) -> Option<(u8, u8, u8)> {
+        self.background.or_else(|| self.theme.background())
+    }
+
+    /// Draw text that doesn't pick a color with `rgb`, or the theme's color for `None`
+    pub const fn set_foreground(&mut self, rgb: Option<(u8, u8, u8)>) {
+        self.foreground = rgb;
+    }
+
+    /// Draw behind text that doesn't pick a color with `rgb`, or the theme's color for `None`
+    pub const fn set_background(&mut self, rgb: Option<(u8, u8, u8)>) {
+        self.background = rgb;
+    }
+
+    /// The color `color` is drawn with, or `None` for the default colors, which the GUI decides
+    /// if the theme doesn't
+    #[must_use]
+    pub fn rgb(&self, color: TerminalColor) -> Option<(u8, u8, u8)> {
+        match color {
+            TerminalColor::Custom(r, g, b) => Some((r, g, b)),
+            TerminalColor::Default
+            | TerminalColor::DefaultUnderlineColor
+            | TerminalColor::DefaultCursorColor => self.foreground(),
+            TerminalColor::DefaultBackground => self.background(),
+            _ => self.get(color.palette_index()?),
+        }
+    }
+}
+
+/// Parse a color written as `#rrggbb`
+///
+/// # Errors
+/// Will return an error if `s` isn't a `#` followed by six hex digits
+pub fn parse_hex_color(s: &str) -> anyhow::Result<(u8, u8, u8)> {
+    let digits = s
+        .strip_prefix('#')
+        .filter(|digits| digits.len() == 6 && digits.is_ascii())
+        .ok_or_else(|| anyhow::anyhow!("Invalid color {s}, expected #rrggbb"))?;
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&digits[i..i + 2], 16)
+            .map_err(|_| anyhow::anyhow!("Invalid color {s}, expected #rrggbb"))
+    };
+
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Parse a color the way xterm does in OSC sequences: `rgb:r/g/b` with one to four hex digits
+/// for each channel, or `#` followed by one to four hex digits for each channel. Color names
+/// aren't supported
+#[must_use]
+pub fn parse_x11_color(s: &str) -> Option<(u8, u8, u8)> {
+    let channels: Vec<&str> = if let Some(rgb) = s.strip_prefix("rgb:") {
+        rgb.split('/').collect()
+    } else {
+        let digits = s.strip_prefix('#').filter(|digits| digits.is_ascii())?;
+        let width = digits.len() / 3;
+        if width == 0 || width * 3 != digits.len() {
+            return None;
+        }
+
+        (0..3)
+            .map(|i| &digits[i * width..(i + 1) * width])
+            .collect()
+    };
+
+    let [r, g, b] = channels.as_slice() else {
+        return None;
+    };
+
+    Some((x11_channel(r)?, x11_channel(g)?, x11_channel(b)?))
+}
+
+/// Scale a channel of one to four hex digits to 8 bits
+fn x11_channel(digits: &str) -> Option<u8> {
+    if digits.is_empty() || digits.len() > 4 {
+        return None;
+    }
+
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let max = (1u32 << (4 * digits.len())) - 1;
+    u8::try_from((value * 255 + max / 2) / max).ok()
+}
+
+/// Write a color the way xterm reports it in OSC replies, as `rgb:rrrr/gggg/bbbb`
+#[must_use]
+pub fn format_x11_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}")
+}
+
+/// Squared distance between two colors, weighted for how sensitive the eye is to each channel
+/// ("redmean"). Plain RGB distance picks some odd matches, especially for greens and blues
+fn color_distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let mean_red = (u32::from(a.0) + u32::from(b.0)) / 2;
+    let dr = u32::from(a.0.abs_diff(b.0));
+    let dg = u32::from(a.1.abs_diff(b.1));
+    let db = u32::from(a.2.abs_diff(b.2));
+
+    (((512 + mean_red) * dr * dr) >> 8) + 4 * dg * dg + (((767 - mean_red) * db * db) >> 8)
 }
 
 impl fmt::Display for TerminalColor {
@@ -0,0 +1,433 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use crate::{
+    args::{DEFAULT_SCROLLBACK_LINES, UNLIMITED_SCROLLBACK_LINES},
+    cell_geometry::{SizeLimits, TerminalGeometry},
+    colors::{parse_hex_color, Palette, Theme},
+    cursor::CursorVisualStyle,
+    font::FontSettings,
+    links::LinkOpening,
+    presentation::PresentationSettings,
+    redaction::validate_pattern,
+    text_classes::{UrlSchemes, WordCharacters},
+};
+
+/// Settings read from the config file, which the command line arguments override
+///
+/// The file is TOML:
+///
+/// ```toml
+/// shell = "/usr/bin/fish"
+/// shell_args = ["--private"]
+/// login = true
+/// # A number of lines, or "unlimited" to keep them all
+/// scrollback_lines = 10000
+/// # Lines the alternate screen, used by full screen applications like less, keeps so they can
+/// # be scrolled back to. None by default
+/// alternate_scrollback_lines = 500
+/// cursor_style = "blinking-bar"
+///
+/// [font]
+/// family = "JetBrains Mono"
+/// size = 13
+///
+/// [colors]
+/// # xterm (default), solarized-dark, solarized-light, dracula or gruvbox
+/// theme = "dracula"
+/// # Black, red, green, yellow, blue, magenta, cyan, white, then the bright versions. Entries
+/// # that are left out keep the theme's color
+/// palette = ["#282828", "#cc241d", "#98971a", "#d79921"]
+/// foreground = "#ebdbb2"
+/// background = "#1d2021"
+///
+/// [size]
+/// # Smallest and largest the terminal can be, as COLUMNSxROWS, whatever size the window is
+/// # or an application asks for
+/// min = "20x5"
+/// max = "1000x500"
+///
+/// [links]
+/// # What opens a link that is clicked on: click (default), ctrl-click, or confirm to ask first
+/// open = "ctrl-click"
+/// # URL schemes to find and open in plain text output, as well as OSC 8 hyperlinks
+/// url_schemes = ["https", "http", "gopher"]
+///
+/// [selection]
+/// # Characters other than letters and digits that a double click treats as part of a word
+/// word_characters = "-_:/."
+///
+/// [presentation]
+/// # Presentation mode, Ctrl+Shift+P, makes the text bigger and shows the keys being typed
+/// font_scale = 2
+/// show_keys = true
+///
+/// [redaction]
+/// # Text matching any of these regular expressions is masked on screen and in recordings
+/// patterns = ["ghp_[A-Za-z0-9]{36}", "AKIA[0-9A-Z]{16}"]
+/// ```
+///
+/// Settings it doesn't know are ignored.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "ConfigFile")]
+pub struct Config {
+    pub shell: Option<String>,
+    /// Arguments for `shell`. Ignored if another shell is picked on the command line
+    pub shell_args: Vec<String>,
+    /// Start `shell` as a login shell
+    pub login_shell: bool,
+    pub font: FontSettings,
+    pub palette: Palette,
+    /// Most lines kept, the screen included, before the oldest are dropped.
+    /// `UNLIMITED_SCROLLBACK_LINES` keeps them all
+    pub scrollback_lines: usize,
+    /// Most lines the alternate screen keeps, its own included. 0 keeps none, and scrolling
+    /// there sends arrow keys to the application instead
+    pub alternate_scrollback_lines: usize,
+    pub cursor_style: CursorVisualStyle,
+    /// Regular expressions for secrets to mask on screen and in recordings
+    pub redact_patterns: Vec<String>,
+    pub presentation: PresentationSettings,
+    pub size_limits: SizeLimits,
+    pub link_opening: LinkOpening,
+    pub word_characters: WordCharacters,
+    pub url_schemes: UrlSchemes,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            shell: None,
+            shell_args: Vec::new(),
+            login_shell: false,
+            font: FontSettings::default(),
+            palette: Palette::default(),
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            alternate_scrollback_lines: 0,
+            cursor_style: CursorVisualStyle::default(),
+            redact_patterns: Vec::new(),
+            presentation: PresentationSettings::default(),
+            size_limits: SizeLimits::default(),
+            link_opening: LinkOpening::default(),
+            word_characters: WordCharacters::default(),
+            url_schemes: UrlSchemes::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Where the config file is looked for: `$XDG_CONFIG_HOME/freminal/config.toml`, falling
+    /// back to `~/.config/freminal/config.toml`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_dir.join("freminal").join("config.toml"))
+    }
+
+    /// The config file to read: the one picked with `--config` in `args`, or the default
+    #[must_use]
+    pub fn path_for_args(args: &[String]) -> Option<PathBuf> {
+        args.iter()
+            .position(|arg| arg == "--config")
+            .and_then(|i| args.get(i + 1))
+            .map(PathBuf::from)
+            .or_else(Self::default_path)
+    }
+
+    /// Read the config file at `path`. A file that doesn't exist gives the defaults
+    ///
+    /// # Errors
+    /// Will return an error if the file can't be read or isn't a valid config
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => text.parse(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow!("Failed to read {}: {e}", path.display())),
+        }
+    }
+}
+
+/// Notices when the config file changes, so it can be read again
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    /// When the file was last changed, as of the last check. `None` if it didn't exist
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`, which was read just now
+    #[must_use]
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified_time(&path);
+        Self { path, modified }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file has been changed, created or deleted since the last check
+    pub fn changed(&mut self) -> bool {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| anyhow!("{e}"))
+    }
+}
+
+impl TryFrom<ConfigFile> for Config {
+    type Error = anyhow::Error;
+
+    fn try_from(file: ConfigFile) -> Result<Self> {
+        let defaults = Self::default();
+
+        let mut palette = defaults.palette;
+        if let Some(theme) = file.colors.theme {
+            palette.set_theme(theme);
+        }
+        for (index, rgb) in file.colors.palette.into_iter().enumerate() {
+            palette.set(index, rgb);
+        }
+        if let Some(rgb) = file.colors.foreground {
+            palette.set_foreground(Some(rgb));
+        }
+        if let Some(rgb) = file.colors.background {
+            palette.set_background(Some(rgb));
+        }
+
+        // Checked once everything is read, so min and max can be given in either order
+        let size_limits = SizeLimits::new(
+            file.size.min.unwrap_or(defaults.size_limits.min),
+            file.size.max.unwrap_or(defaults.size_limits.max),
+        )?;
+
+        Ok(Self {
+            shell: file.shell,
+            shell_args: file.shell_args,
+            login_shell: file.login,
+            font: FontSettings {
+                family: file.font.family,
+                size: file.font.size.unwrap_or(defaults.font.size),
+            },
+            palette,
+            scrollback_lines: file.scrollback_lines.unwrap_or(defaults.scrollback_lines),
+            alternate_scrollback_lines: file.alternate_scrollback_lines,
+            cursor_style: file.cursor_style.unwrap_or(defaults.cursor_style),
+            redact_patterns: file.redaction.patterns,
+            presentation: PresentationSettings {
+                font_scale: file
+                    .presentation
+                    .font_scale
+                    .unwrap_or(defaults.presentation.font_scale),
+                show_keys: file
+                    .presentation
+                    .show_keys
+                    .unwrap_or(defaults.presentation.show_keys),
+            },
+            size_limits,
+            link_opening: file.links.open.unwrap_or(defaults.link_opening),
+            word_characters: file
+                .selection
+                .word_characters
+                .map_or(defaults.word_characters, |extra| {
+                    WordCharacters::new(&extra)
+                }),
+            url_schemes: file
+                .links
+                .url_schemes
+                .map_or(defaults.url_schemes, |schemes| {
+                    UrlSchemes::new(schemes.iter().map(String::as_str))
+                }),
+        })
+    }
+}
+
+/// The config file as it is written. Settings left out are `None` or empty, and take their
+/// defaults when the file is turned in to a `Config`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    shell: Option<String>,
+    shell_args: Vec<String>,
+    login: bool,
+    #[serde(deserialize_with = "scrollback_lines")]
+    scrollback_lines: Option<usize>,
+    alternate_scrollback_lines: usize,
+    #[serde(deserialize_with = "parsed")]
+    cursor_style: Option<CursorVisualStyle>,
+    font: FontTable,
+    colors: ColorsTable,
+    size: SizeTable,
+    links: LinksTable,
+    selection: SelectionTable,
+    presentation: PresentationTable,
+    redaction: RedactionTable,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FontTable {
+    family: Option<String>,
+    #[serde(deserialize_with = "font_size")]
+    size: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ColorsTable {
+    #[serde(deserialize_with = "parsed")]
+    theme: Option<Theme>,
+    #[serde(deserialize_with = "palette")]
+    palette: Vec<(u8, u8, u8)>,
+    #[serde(deserialize_with = "hex_color")]
+    foreground: Option<(u8, u8, u8)>,
+    #[serde(deserialize_with = "hex_color")]
+    background: Option<(u8, u8, u8)>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SizeTable {
+    #[serde(deserialize_with = "parsed")]
+    min: Option<TerminalGeometry>,
+    #[serde(deserialize_with = "parsed")]
+    max: Option<TerminalGeometry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct LinksTable {
+    #[serde(deserialize_with = "parsed")]
+    open: Option<LinkOpening>,
+    url_schemes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SelectionTable {
+    word_characters: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PresentationTable {
+    #[serde(deserialize_with = "font_scale")]
+    font_scale: Option<f32>,
+    show_keys: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RedactionTable {
+    #[serde(deserialize_with = "redact_patterns")]
+    patterns: Vec<String>,
+}
+
+/// A setting written as a string, such as a geometry or a cursor style
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+/// A number of lines, at least 1, or "unlimited"
+fn scrollback_lines<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    match toml::Value::deserialize(deserializer)? {
+        toml::Value::String(lines) if lines == "unlimited" => Ok(Some(UNLIMITED_SCROLLBACK_LINES)),
+        toml::Value::Integer(lines) => usize::try_from(lines)
+            .ok()
+            .filter(|lines| *lines > 0)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom("scrollback_lines must be at least 1")),
+        other => Err(D::Error::custom(format!(
+            "expected a number of lines or \"unlimited\", found {}",
+            other.type_str()
+        ))),
+    }
+}
+
+fn font_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    let size = f32::deserialize(deserializer)?;
+    if !(1.0..=100.0).contains(&size) {
+        return Err(D::Error::custom("font size must be from 1 to 100"));
+    }
+
+    Ok(Some(size))
+}
+
+fn font_scale<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    let scale = f32::deserialize(deserializer)?;
+    if !(1.0..=10.0).contains(&scale) {
+        return Err(D::Error::custom("font_scale must be from 1 to 10"));
+    }
+
+    Ok(Some(scale))
+}
+
+fn hex_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<(u8, u8, u8)>, D::Error> {
+    parse_hex_color(&String::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+/// Up to 16 colors. Entries that are left out keep the theme's color
+fn palette<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(u8, u8, u8)>, D::Error> {
+    let colors = Vec::<String>::deserialize(deserializer)?;
+    if colors.len() > 16 {
+        return Err(D::Error::custom("the palette has only 16 colors"));
+    }
+
+    colors
+        .iter()
+        .map(|color| parse_hex_color(color).map_err(D::Error::custom))
+        .collect()
+}
+
+fn redact_patterns<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let patterns = Vec::<String>::deserialize(deserializer)?;
+    for pattern in &patterns {
+        validate_pattern(pattern).map_err(D::Error::custom)?;
+    }
+
+    Ok(patterns)
+}
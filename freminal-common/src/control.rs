@@ -0,0 +1,516 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    io::BufRead,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Set in the shell's environment to the control socket path, so `freminal msg` run inside the
+/// terminal knows where to send
+pub const CONTROL_SOCKET_ENV: &str = "FREMINAL_CONTROL_SOCKET";
+
+/// How long a connection waits for the GUI to answer a request before giving up
+pub const CONTROL_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest request or response line, in bytes. Plenty for pasting a large file, but a client
+/// can't make us buffer without end by never sending a line end
+pub const MAX_CONTROL_LINE_LENGTH: usize = 4 * 1024 * 1024;
+
+/// Most control connections served at once. Others are turned away until one closes
+pub const MAX_CONTROL_CONNECTIONS: usize = 16;
+
+// Standard JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// The request was understood but the terminal couldn't do it
+pub const SERVER_ERROR: i64 = -32000;
+
+/// Commands that can be sent over the control socket
+///
+/// On the wire these are the `method` and `params` of a JSON-RPC 2.0 request, one request per
+/// line. For example `{"jsonrpc":"2.0","id":1,"method":"paste_text","params":{"text":"ls\n"}}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum ControlCommand {
+    /// Paste text in to the terminal, as if it came from the clipboard
+    PasteText {
+        text: String,
+    },
+    /// Write text to the terminal as-is, as if it was typed
+    SendKeys {
+        text: String,
+    },
+    /// Get the text currently on screen
+    GetText,
+    SetFontSize {
+        size: f32,
+    },
+    OpenTab,
+}
+
+impl ControlCommand {
+    /// Every method name we accept
+    pub const METHODS: &'static [&'static str] = &[
+        "paste_text",
+        "send_keys",
+        "get_text",
+        "set_font_size",
+        "open_tab",
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlRequest {
+    #[serde(default = "jsonrpc_version")]
+    pub jsonrpc: String,
+    /// Echoed back in the response so clients can match them up
+    #[serde(default)]
+    pub id: Value,
+    #[serde(flatten)]
+    pub command: ControlCommand,
+}
+
+fn jsonrpc_version() -> String {
+    JSONRPC_VERSION.to_string()
+}
+
+impl ControlRequest {
+    #[must_use]
+    pub fn new(id: impl Into<Value>, command: ControlCommand) -> Self {
+        Self {
+            jsonrpc: jsonrpc_version(),
+            id: id.into(),
+            command,
+        }
+    }
+
+    /// Parse a single line of the protocol
+    ///
+    /// # Errors
+    /// Returns the error response to send back if the line isn't a request we understand
+    pub fn parse(line: &str) -> Result<Self, ControlResponse> {
+        let value: Value = serde_json::from_str(line)
+            .map_err(|e| ControlResponse::error(Value::Null, PARSE_ERROR, e.to_string()))?;
+        let id = value.get("id").cloned().unwrap_or(Value::Null);
+
+        let Some(method) = value.get("method").and_then(Value::as_str) else {
+            return Err(ControlResponse::error(
+                id,
+                INVALID_REQUEST,
+                "Missing method",
+            ));
+        };
+
+        if !ControlCommand::METHODS.contains(&method) {
+            return Err(ControlResponse::error(
+                id,
+                METHOD_NOT_FOUND,
+                format!("Unknown method {method}"),
+            ));
+        }
+
+        serde_json::from_value(value)
+            .map_err(|e| ControlResponse::error(id, INVALID_PARAMS, e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ControlError>,
+}
+
+impl ControlResponse {
+    #[must_use]
+    pub fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: jsonrpc_version(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    #[must_use]
+    pub fn error(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: jsonrpc_version(),
+            id,
+            result: None,
+            error: Some(ControlError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+
+    /// The response as a single line, ready to write to the socket
+    #[must_use]
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).unwrap_or_else(|e| {
+            error!("Failed to serialize control response: {e}");
+            format!(
+                "{{\"jsonrpc\":\"{JSONRPC_VERSION}\",\"id\":null,\"error\":{{\"code\":{INTERNAL_ERROR},\"message\":\"Failed to serialize response\"}}}}"
+            )
+        });
+        line.push('\n');
+
+        line
+    }
+}
+
+/// A request waiting for the GUI to carry it out
+#[derive(Debug)]
+pub struct PendingControlRequest {
+    pub command: ControlCommand,
+    id: Value,
+    reply: Sender<ControlResponse>,
+}
+
+impl PendingControlRequest {
+    /// Send the result back to the client. Errors are reported as server errors
+    pub fn reply(self, result: Result<Value>) {
+        let response = match result {
+            Ok(value) => ControlResponse::success(self.id, value),
+            Err(e) => ControlResponse::error(self.id, SERVER_ERROR, e.to_string()),
+        };
+
+        if self.reply.send(response).is_err() {
+            warn!("Control client went away before we replied");
+        }
+    }
+}
+
+/// Listens on a Unix socket for control requests
+///
+/// This is only available on unix. On Windows `bind` always fails, as a named pipe server
+/// hasn't been written yet.
+///
+/// Each connection gets its own thread, which parses requests and hands them to the GUI through
+/// `try_recv`. `waker` is called whenever a request arrives so the GUI can wake up and answer it.
+///
+/// Anything that can connect can type in to the shell, so the socket is only open to its owner,
+/// at most `MAX_CONTROL_CONNECTIONS` are served at once and requests are limited to
+/// `MAX_CONTROL_LINE_LENGTH`.
+pub struct ControlServer {
+    path: PathBuf,
+    requests: Receiver<PendingControlRequest>,
+}
+
+impl ControlServer {
+    /// Start listening on `path`
+    ///
+    /// # Errors
+    /// Will return an error if the socket can't be created, if something other than a socket is
+    /// already at `path`, or if another terminal is already listening on it
+    #[cfg(unix)]
+    pub fn bind(path: &Path, waker: impl Fn() + Send + Sync + 'static) -> Result<Self> {
+        use std::{
+            io::{ErrorKind, Write},
+            os::unix::{
+                fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+                net::{UnixListener, UnixStream},
+            },
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+        };
+
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(anyhow::anyhow!(
+                    "{} exists and is not a socket",
+                    path.display()
+                ));
+            }
+
+            // Only a socket left behind by a previous run, that nothing answers on, is replaced
+            match UnixStream::connect(path) {
+                Ok(_) => {
+                    return Err(anyhow::anyhow!(
+                        "Another terminal is already listening on {}",
+                        path.display()
+                    ))
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+                Err(e) => {
+                    return Err(anyhow::anyhow!(
+                        "Can't tell if {} is in use: {e}",
+                        path.display()
+                    ))
+                }
+            }
+        }
+
+        // The socket is created in a directory only we can get in to and moved in to place once
+        // it is 0600, so nobody else can connect in between, whatever the umask
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} is not a file path", path.display()))?;
+        let private_dir = path.with_file_name(format!(
+            ".{}.{}",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&private_dir)?;
+        let private_path = private_dir.join(file_name);
+        let listener = UnixListener::bind(&private_path).and_then(|listener| {
+            std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&private_path, path)?;
+            Ok(listener)
+        });
+        if let Err(e) = std::fs::remove_dir_all(&private_dir) {
+            warn!("Failed to remove {}: {e}", private_dir.display());
+        }
+        let listener = listener?;
+
+        let (requests_tx, requests) = crossbeam_channel::unbounded();
+        let waker: Arc<dyn Fn() + Send + Sync> = Arc::new(waker);
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        std::thread::Builder::new()
+            .name("control-socket".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            error!("Failed to accept control connection: {e}");
+                            continue;
+                        }
+                    };
+
+                    if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONTROL_CONNECTIONS {
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                        warn!("Turned away a control connection, {MAX_CONTROL_CONNECTIONS} are already open");
+                        let response = ControlResponse::error(
+                            Value::Null,
+                            SERVER_ERROR,
+                            "too many control connections",
+                        );
+                        let _ = stream.write_all(response.to_line().as_bytes());
+                        continue;
+                    }
+
+                    let requests_tx = requests_tx.clone();
+                    let waker = Arc::clone(&waker);
+                    let connections = Arc::clone(&connections);
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, &requests_tx, &*waker) {
+                            warn!("Control connection closed: {e}");
+                        }
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                    });
+                }
+            })?;
+
+        info!("Listening for control requests on {}", path.display());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            requests,
+        })
+    }
+
+    /// Control sockets are Unix sockets, which we don't have here. There is no named pipe
+    /// version for Windows yet
+    ///
+    /// # Errors
+    /// Always
+    #[cfg(not(unix))]
+    pub fn bind(path: &Path, _waker: impl Fn() + Send + Sync + 'static) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "Can't listen on {}: the control socket is only supported on unix",
+            path.display()
+        ))
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The next request waiting to be answered, if there is one
+    #[must_use]
+    pub fn try_recv(&self) -> Option<PendingControlRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove control socket {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Send a single command to the terminal listening on `path` and wait for the result
+///
+/// # Errors
+/// Will return an error if we can't talk to the socket, or the terminal answered with an error
+#[cfg(unix)]
+pub fn send_request(path: &Path, command: ControlCommand) -> Result<Value> {
+    use std::{
+        io::{BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| anyhow::anyhow!("Failed to connect to {}: {e}", path.display()))?;
+    let mut line = serde_json::to_string(&ControlRequest::new(1, command))?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let line = read_line_limited(&mut BufReader::new(stream), MAX_CONTROL_LINE_LENGTH)?
+        .ok_or_else(|| anyhow::anyhow!("The terminal closed the connection without answering"))?;
+    let response: ControlResponse = serde_json::from_str(&line)?;
+
+    match (response.result, response.error) {
+        (_, Some(error)) => Err(anyhow::anyhow!("{} ({})", error.message, error.code)),
+        (result, None) => Ok(result.unwrap_or(Value::Null)),
+    }
+}
+
+/// Control sockets are Unix sockets, which we don't have here
+///
+/// # Errors
+/// Always
+#[cfg(not(unix))]
+pub fn send_request(path: &Path, _command: ControlCommand) -> Result<Value> {
+    Err(anyhow::anyhow!(
+        "Can't connect to {}: the control socket is only supported on unix",
+        path.display()
+    ))
+}
+
+#[cfg(unix)]
+fn handle_connection(
+    stream: std::os::unix::net::UnixStream,
+    requests: &Sender<PendingControlRequest>,
+    waker: &dyn Fn(),
+) -> Result<()> {
+    use std::io::{BufReader, ErrorKind, Write};
+
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let line = match read_line_limited(&mut reader, MAX_CONTROL_LINE_LENGTH) {
+            Ok(Some(line)) => line,
+            Ok(None) => return Ok(()),
+            // Tell the client why it is being cut off
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                let response = ControlResponse::error(Value::Null, INVALID_REQUEST, e.to_string());
+                writer.write_all(response.to_line().as_bytes())?;
+                return Err(e.into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match ControlRequest::parse(&line) {
+            Ok(request) => dispatch(request, requests, waker),
+            Err(response) => response,
+        };
+
+        writer.write_all(response.to_line().as_bytes())?;
+    }
+}
+
+/// Read a line of at most `limit` bytes, without its line end. `None` at the end of the stream
+///
+/// # Errors
+/// Will return an error if the read fails, or an `InvalidData` error if the line is longer than
+/// `limit` or isn't UTF-8
+pub fn read_line_limited(
+    reader: &mut impl BufRead,
+    limit: usize,
+) -> std::io::Result<Option<String>> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    // One byte more than the limit, for the line end
+    let mut line = Vec::new();
+    let mut limited = std::io::Read::take(
+        &mut *reader,
+        u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1),
+    );
+    limited.read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    if line.last() == Some(&b'\n') {
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+    } else if line.len() > limit {
+        return Err(invalid(format!("line is longer than {limit} bytes")));
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|e| invalid(e.to_string()))
+}
+
+/// Hand a request to the GUI and wait for the answer
+fn dispatch(
+    request: ControlRequest,
+    requests: &Sender<PendingControlRequest>,
+    waker: &dyn Fn(),
+) -> ControlResponse {
+    let (reply, reply_rx) = crossbeam_channel::bounded(1);
+    let id = request.id.clone();
+
+    if requests
+        .send(PendingControlRequest {
+            command: request.command,
+            id: id.clone(),
+            reply,
+        })
+        .is_err()
+    {
+        return ControlResponse::error(id, INTERNAL_ERROR, "The terminal is shutting down");
+    }
+
+    waker();
+
+    reply_rx
+        .recv_timeout(CONTROL_REPLY_TIMEOUT)
+        .unwrap_or_else(|_| {
+            ControlResponse::error(id, INTERNAL_ERROR, "Timed out waiting for the terminal")
+        })
+}
@@ -3,8 +3,10 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+use std::str::FromStr;
+
 #[allow(clippy::module_name_repetitions)]
-#[derive(Default, Debug, Eq, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CursorVisualStyle {
     #[default]
     BlockCursorBlink,
@@ -27,3 +29,106 @@ impl From<usize> for CursorVisualStyle {
         }
     }
 }
+
+impl FromStr for CursorVisualStyle {
+    type Err = anyhow::Error;
+
+    /// A shape, `block`, `underline` or `bar`, optionally with `blinking-` in front of it
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let (blinking, shape) = s
+            .strip_prefix("blinking-")
+            .map_or((false, s.as_str()), |shape| (true, shape));
+
+        match (shape, blinking) {
+            ("block", true) => Ok(Self::BlockCursorBlink),
+            ("block", false) => Ok(Self::BlockCursorSteady),
+            ("underline", true) => Ok(Self::UnderlineCursorBlink),
+            ("underline", false) => Ok(Self::UnderlineCursorSteady),
+            ("bar", true) => Ok(Self::VerticalLineCursorBlink),
+            ("bar", false) => Ok(Self::VerticalLineCursorSteady),
+            _ => Err(anyhow::anyhow!(
+                "Invalid cursor style {s}. Expected block, underline or bar, optionally starting with blinking-"
+            )),
+        }
+    }
+}
+
+/// The shape of the cursor, without the blinking
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    VerticalLine,
+}
+
+impl CursorVisualStyle {
+    #[must_use]
+    pub const fn shape(&self) -> CursorShape {
+        match self {
+            Self::BlockCursorBlink | Self::BlockCursorSteady => CursorShape::Block,
+            Self::UnderlineCursorBlink | Self::UnderlineCursorSteady => CursorShape::Underline,
+            Self::VerticalLineCursorBlink | Self::VerticalLineCursorSteady => {
+                CursorShape::VerticalLine
+            }
+        }
+    }
+
+    #[must_use]
+    pub const fn is_blinking(&self) -> bool {
+        matches!(
+            self,
+            Self::BlockCursorBlink | Self::UnderlineCursorBlink | Self::VerticalLineCursorBlink
+        )
+    }
+}
+
+/// Default thickness of underline and bar cursors, in points
+pub const DEFAULT_CURSOR_THICKNESS: f32 = 2.0;
+/// Default width of the outline drawn for the cursor when the window isn't focused, in points
+pub const DEFAULT_CURSOR_OUTLINE_WIDTH: f32 = 1.0;
+/// How long the cursor stays on, and then off, when blinking
+pub const CURSOR_BLINK_INTERVAL_SECONDS: f64 = 0.5;
+
+/// How the user wants the cursor drawn, whatever shape applications ask for
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorSettings {
+    /// Thickness of underline and bar cursors, in points
+    pub thickness: f32,
+    /// Width of the outline drawn for the cursor when the window isn't focused, in points
+    pub outline_width: f32,
+    /// Let the cursor blink when applications ask for it. Off keeps it steady
+    pub blink: bool,
+    /// The cursor style until an application asks for another with DECSCUSR, and again once it
+    /// asks for the default or the terminal is reset
+    pub style: CursorVisualStyle,
+}
+
+impl Default for CursorSettings {
+    fn default() -> Self {
+        Self {
+            thickness: DEFAULT_CURSOR_THICKNESS,
+            outline_width: DEFAULT_CURSOR_OUTLINE_WIDTH,
+            blink: true,
+            style: CursorVisualStyle::BlockCursorSteady,
+        }
+    }
+}
+
+impl CursorSettings {
+    /// Whether a cursor that wants to blink is shown `time` seconds in. Cursors that don't blink,
+    /// or blink that has been turned off, are always shown
+    #[must_use]
+    pub fn is_visible_at(&self, blinking: bool, time: f64) -> bool {
+        if !self.blink || !blinking {
+            return true;
+        }
+
+        (time / CURSOR_BLINK_INTERVAL_SECONDS)
+            .floor()
+            .rem_euclid(2.0)
+            < 1.0
+    }
+}
@@ -0,0 +1,27 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+/// Default size of the terminal text, in points
+pub const DEFAULT_FONT_SIZE: f32 = 12.0;
+
+/// The font the terminal text is drawn with
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontSettings {
+    /// Name of an installed font family, such as `JetBrains Mono`, to use instead of the
+    /// bundled Meslo
+    pub family: Option<String>,
+    /// Size of the text in points, until the user changes it
+    pub size: f32,
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        Self {
+            family: None,
+            size: DEFAULT_FONT_SIZE,
+        }
+    }
+}
@@ -16,9 +16,24 @@
 // #![warn(missing_docs)]
 
 pub mod args;
+pub mod cell_geometry;
+pub mod char_width;
 pub mod colors;
+pub mod config;
+pub mod control;
 pub mod cursor;
+pub mod font;
+pub mod links;
+pub mod metrics;
+pub mod mosaic;
+pub mod msg;
+pub mod presentation;
+pub mod redaction;
 pub mod scroll;
+pub mod shell;
+pub mod text_classes;
+pub mod timestamps;
+pub mod title_format;
 pub mod window_manipulation;
 
 #[macro_use]
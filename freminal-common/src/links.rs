@@ -0,0 +1,52 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::str::FromStr;
+
+/// What it takes to open a link in the output by clicking on it
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum LinkOpening {
+    /// Any click opens it
+    #[default]
+    Click,
+    /// Only a click with Ctrl held opens it, so clicking to focus the window or select text
+    /// doesn't
+    CtrlClick,
+    /// A click asks first, showing where the link goes
+    Confirm,
+}
+
+impl LinkOpening {
+    /// Whether a click on a link, with or without Ctrl held, opens it or asks to
+    #[must_use]
+    pub const fn click_opens(self, ctrl: bool) -> bool {
+        match self {
+            Self::Click | Self::Confirm => true,
+            Self::CtrlClick => ctrl,
+        }
+    }
+
+    /// Whether to ask before opening a link that was clicked on
+    #[must_use]
+    pub const fn asks_first(self) -> bool {
+        matches!(self, Self::Confirm)
+    }
+}
+
+impl FromStr for LinkOpening {
+    type Err = anyhow::Error;
+
+    /// `click`, `ctrl-click` or `confirm`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "click" => Ok(Self::Click),
+            "ctrl-click" => Ok(Self::CtrlClick),
+            "confirm" => Ok(Self::Confirm),
+            _ => Err(anyhow::anyhow!(
+                "Invalid way to open links {s}. Expected click, ctrl-click or confirm"
+            )),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+/// Running statistics for something we time repeatedly, like drawing a frame
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TimingStats {
+    last: Duration,
+    average: Duration,
+    max: Duration,
+    samples: u64,
+}
+
+impl TimingStats {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            last: Duration::ZERO,
+            average: Duration::ZERO,
+            max: Duration::ZERO,
+            samples: 0,
+        }
+    }
+
+    pub fn record(&mut self, elapsed: Duration) {
+        self.last = elapsed;
+        self.max = self.max.max(elapsed);
+
+        // Exponential moving average, so a long session doesn't drown out recent changes
+        self.average = if self.samples == 0 {
+            elapsed
+        } else {
+            (self.average * 7 + elapsed) / 8
+        };
+
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    #[must_use]
+    pub const fn last(&self) -> Duration {
+        self.last
+    }
+
+    #[must_use]
+    pub const fn average(&self) -> Duration {
+        self.average
+    }
+
+    #[must_use]
+    pub const fn max(&self) -> Duration {
+        self.max
+    }
+
+    #[must_use]
+    pub const fn samples(&self) -> u64 {
+        self.samples
+    }
+}
+
+impl fmt::Display for TimingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "last {:.2?} avg {:.2?} max {:.2?}",
+            self.last, self.average, self.max
+        )
+    }
+}
+
+/// Performance metrics shown in the debug HUD
+///
+/// Timing is only collected while `enabled` is set, so there is no cost when nobody is looking.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Metrics {
+    pub enabled: bool,
+    pub frame_time: TimingStats,
+    pub data_processing_time: TimingStats,
+    pub bytes_processed: u64,
+}
+
+impl Metrics {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            enabled: false,
+            frame_time: TimingStats::new(),
+            data_processing_time: TimingStats::new(),
+            bytes_processed: 0,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.reset();
+        }
+
+        self.enabled = enabled;
+    }
+
+    pub fn reset(&mut self) {
+        self.frame_time.reset();
+        self.data_processing_time.reset();
+        self.bytes_processed = 0;
+    }
+}
+
+/// How many of the most recent samples percentiles are taken over
+pub const PERCENTILE_SAMPLES: usize = 500;
+
+/// Percentiles of something we time repeatedly, over the most recent samples
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PercentileStats {
+    samples: VecDeque<Duration>,
+}
+
+impl PercentileStats {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.samples.len() == PERCENTILE_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(elapsed);
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    #[must_use]
+    pub fn samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// The sample that `percent` percent of samples are at or below. `None` until something
+    /// has been recorded
+    #[must_use]
+    pub fn percentile(&self, percent: usize) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+
+        let rank = (sorted.len() * percent.min(100)).div_ceil(100);
+        sorted.get(rank.saturating_sub(1)).copied()
+    }
+}
+
+impl fmt::Display for PercentileStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.percentile(50), self.percentile(95)) {
+            (Some(p50), Some(p95)) => write!(f, "p50 {p50:.2?} p95 {p95:.2?}"),
+            _ => write!(f, "no samples"),
+        }
+    }
+}
+
+/// A probe that is given up on if the key press it follows is never echoed, for example
+/// because the application turned echo off
+pub const LATENCY_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// One key press making its way through to the screen
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct LatencyProbe {
+    pressed: Instant,
+    written: Option<Instant>,
+    echoed: Option<Instant>,
+}
+
+/// Time from a key press to it being written to the pty, to the shell's echo coming back, and to
+/// a frame showing the echo
+///
+/// Only one key press is followed at a time. Presses while it is on its way are not timed, so
+/// fast typing doesn't mix up whose echo is whose.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct InputLatency {
+    pub enabled: bool,
+    pub to_write: PercentileStats,
+    pub to_echo: PercentileStats,
+    pub to_present: PercentileStats,
+    probe: Option<LatencyProbe>,
+}
+
+impl InputLatency {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            enabled: false,
+            to_write: PercentileStats::new(),
+            to_echo: PercentileStats::new(),
+            to_present: PercentileStats::new(),
+            probe: None,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.reset();
+        }
+
+        self.enabled = enabled;
+    }
+
+    pub fn reset(&mut self) {
+        self.to_write.reset();
+        self.to_echo.reset();
+        self.to_present.reset();
+        self.probe = None;
+    }
+
+    pub fn key_pressed(&mut self, now: Instant) {
+        if !self.enabled {
+            return;
+        }
+
+        let in_flight = self.probe.is_some_and(|probe| {
+            now.saturating_duration_since(probe.pressed) < LATENCY_PROBE_TIMEOUT
+        });
+        if !in_flight {
+            self.probe = Some(LatencyProbe {
+                pressed: now,
+                written: None,
+                echoed: None,
+            });
+        }
+    }
+
+    /// Input was written to the pty
+    pub fn written(&mut self, now: Instant) {
+        if let Some(probe) = &mut self.probe {
+            if probe.written.is_none() {
+                probe.written = Some(now);
+                self.to_write
+                    .record(now.saturating_duration_since(probe.pressed));
+            }
+        }
+    }
+
+    /// Output arrived from the pty. The first after the key press was written is its echo
+    pub fn output_received(&mut self, now: Instant) {
+        if let Some(probe) = &mut self.probe {
+            if probe.written.is_some() && probe.echoed.is_none() {
+                probe.echoed = Some(now);
+                self.to_echo
+                    .record(now.saturating_duration_since(probe.pressed));
+            }
+        }
+    }
+
+    /// A frame was drawn. If it shows the echo, the key press has made it to the screen
+    pub fn frame_presented(&mut self, now: Instant) {
+        if let Some(probe) = self.probe {
+            if probe.echoed.is_some() {
+                self.to_present
+                    .record(now.saturating_duration_since(probe.pressed));
+                self.probe = None;
+            }
+        }
+    }
+}
+
+/// Input latency shared between the GUI, the pty writer and the output processing, which each
+/// time their own part
+///
+/// Whether it is enabled can be checked without the lock, so the output and frame paths neither
+/// lock nor read the clock when nobody is measuring.
+#[derive(Debug, Clone, Default)]
+pub struct SharedInputLatency {
+    latency: Arc<Mutex<InputLatency>>,
+    enabled: Arc<AtomicBool>,
+}
+
+impl SharedInputLatency {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timing is all that is behind the lock, so a poisoned lock is used as-is
+    pub fn lock(&self) -> MutexGuard<'_, InputLatency> {
+        self.latency.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Start or stop measuring. Use this rather than `InputLatency::set_enabled`, so `enabled`
+    /// stays in step
+    pub fn set_enabled(&self, enabled: bool) {
+        self.lock().set_enabled(enabled);
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
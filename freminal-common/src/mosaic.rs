@@ -0,0 +1,137 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+/// Which quarters of a cell a mosaic character fills: upper left, upper right, lower left and
+/// lower right
+pub type Quadrants = [bool; 4];
+
+/// The quarters of the cell `c` fills, if it is a half block, the full block or a quadrant
+/// block
+///
+/// Image viewers like chafa and viu draw pictures out of these, with a truecolor foreground and
+/// background on every cell, so each cell is two or four pixels of the picture.
+#[must_use]
+pub const fn mosaic_quadrants(c: char) -> Option<Quadrants> {
+    let quadrants = match c {
+        '\u{2580}' => [true, true, false, false],
+        '\u{2584}' => [false, false, true, true],
+        '\u{2588}' => [true, true, true, true],
+        '\u{258C}' => [true, false, true, false],
+        '\u{2590}' => [false, true, false, true],
+        '\u{2596}' => [false, false, true, false],
+        '\u{2597}' => [false, false, false, true],
+        '\u{2598}' => [true, false, false, false],
+        '\u{2599}' => [true, false, true, true],
+        '\u{259A}' => [true, false, false, true],
+        '\u{259B}' => [true, true, true, false],
+        '\u{259C}' => [true, true, false, true],
+        '\u{259D}' => [false, true, false, false],
+        '\u{259E}' => [false, true, true, false],
+        '\u{259F}' => [false, true, true, true],
+        _ => return None,
+    };
+
+    Some(quadrants)
+}
+
+/// A strip of one color across the upper or lower half of a row, measured in half cells
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MosaicRun<C> {
+    pub row: usize,
+    /// The lower half of the row rather than the upper
+    pub lower: bool,
+    /// Where the strip starts, in half cells from the left edge
+    pub half_column: usize,
+    /// How many half cells the strip covers
+    pub half_columns: usize,
+    pub color: C,
+}
+
+/// Joins the filled quarters of mosaic cells in to as few strips as it can
+///
+/// Drawing each cell of a picture as its own glyph is a shape per quarter and a run of text to
+/// lay out. Side by side quarters of the same color, in one cell or the next, are drawn as one
+/// rectangle instead. Cells have to be pushed in order, left to right along each row.
+#[derive(Clone, Debug)]
+pub struct MosaicRuns<C> {
+    runs: Vec<MosaicRun<C>>,
+    /// The last strip in the upper and lower half, which the next cell's quarters may carry on
+    open: [Option<usize>; 2],
+}
+
+impl<C> Default for MosaicRuns<C> {
+    fn default() -> Self {
+        Self {
+            runs: Vec::new(),
+            open: [None, None],
+        }
+    }
+}
+
+impl<C: Clone + PartialEq> MosaicRuns<C> {
+    /// Add the cell `columns` wide at `column` on `row`, filled in `color` where `quadrants` says
+    pub fn push(
+        &mut self,
+        row: usize,
+        column: usize,
+        columns: usize,
+        quadrants: Quadrants,
+        color: &C,
+    ) {
+        let start = column * 2;
+        for (half, lower) in [false, true].into_iter().enumerate() {
+            let [left, right] = if lower {
+                [quadrants[2], quadrants[3]]
+            } else {
+                [quadrants[0], quadrants[1]]
+            };
+
+            for (filled, half_column) in [(left, start), (right, start + columns)] {
+                if filled {
+                    self.fill(half, row, lower, half_column, columns, color);
+                }
+            }
+        }
+    }
+
+    fn fill(
+        &mut self,
+        half: usize,
+        row: usize,
+        lower: bool,
+        half_column: usize,
+        half_columns: usize,
+        color: &C,
+    ) {
+        if let Some(run) = self.open[half].and_then(|index| self.runs.get_mut(index)) {
+            if run.row == row
+                && run.half_column + run.half_columns == half_column
+                && run.color == *color
+            {
+                run.half_columns += half_columns;
+                return;
+            }
+        }
+
+        self.open[half] = Some(self.runs.len());
+        self.runs.push(MosaicRun {
+            row,
+            lower,
+            half_column,
+            half_columns,
+            color: color.clone(),
+        });
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    #[must_use]
+    pub fn into_runs(self) -> Vec<MosaicRun<C>> {
+        self.runs
+    }
+}
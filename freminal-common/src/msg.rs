@@ -0,0 +1,133 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use anyhow::Result;
+
+use crate::control::{ControlCommand, CONTROL_SOCKET_ENV};
+
+pub const MSG_USAGE: &str = "\
+Usage:
+freminal msg [--to <socket>] <command>
+
+Send a command to a running freminal over its control socket. Inside a freminal started with
+--control-socket the socket is found through $FREMINAL_CONTROL_SOCKET.
+
+Commands:
+    get-text: Print the text currently on screen
+    send-keys <text>: Type text in to the terminal. \\n, \\r, \\t, \\e, \\\\ and \\xHH are expanded
+    paste <text>: Paste text in to the terminal, as if it came from the clipboard
+    set-font-size <size>: Change the font size";
+
+/// Arguments for `freminal msg`, the client side of the control socket
+#[derive(Debug, Clone, PartialEq)]
+pub struct MsgArgs {
+    /// Socket to send to. Taken from `$FREMINAL_CONTROL_SOCKET` if `--to` isn't given
+    pub socket: Option<String>,
+    pub command: ControlCommand,
+}
+
+impl MsgArgs {
+    /// Parse everything after `msg` on the command line
+    ///
+    /// # Errors
+    /// Will return an error if the command is missing, unknown, or missing its argument
+    pub fn parse<It: Iterator<Item = String>>(mut it: It) -> Result<Self> {
+        let mut socket = None;
+        let mut command = None;
+
+        while let Some(arg) = it.next() {
+            if command.is_some() {
+                return Err(anyhow::anyhow!("Unexpected argument {arg}"));
+            }
+
+            command = Some(match arg.as_str() {
+                "--to" => {
+                    socket = Some(
+                        it.next()
+                            .ok_or_else(|| anyhow::anyhow!("Missing argument for --to"))?,
+                    );
+                    continue;
+                }
+                "get-text" => ControlCommand::GetText,
+                "send-keys" => ControlCommand::SendKeys {
+                    text: unescape_keys(&next_argument(&mut it, &arg)?)?,
+                },
+                "paste" => ControlCommand::PasteText {
+                    text: next_argument(&mut it, &arg)?,
+                },
+                "set-font-size" => {
+                    let size = next_argument(&mut it, &arg)?;
+                    ControlCommand::SetFontSize {
+                        size: size
+                            .parse()
+                            .map_err(|_| anyhow::anyhow!("Invalid font size {size}"))?,
+                    }
+                }
+                _ => return Err(anyhow::anyhow!("Unknown command {arg}")),
+            });
+        }
+
+        let command = command.ok_or_else(|| anyhow::anyhow!("Missing command"))?;
+
+        Ok(Self { socket, command })
+    }
+
+    /// The socket to send to: `--to` if it was given, otherwise the one the terminal we're
+    /// running in exported
+    ///
+    /// # Errors
+    /// Will return an error if there is no socket to send to
+    pub fn socket_path(&self) -> Result<String> {
+        self.socket
+            .clone()
+            .or_else(|| std::env::var(CONTROL_SOCKET_ENV).ok())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No control socket. Pass --to, or run inside a freminal started with --control-socket"
+                )
+            })
+    }
+}
+
+fn next_argument<It: Iterator<Item = String>>(it: &mut It, command: &str) -> Result<String> {
+    it.next()
+        .ok_or_else(|| anyhow::anyhow!("Missing argument for {command}"))
+}
+
+/// Expand the escapes `send-keys` understands, so control keys can be typed from a shell
+///
+/// # Errors
+/// Will return an error for an unknown escape, or a `\xHH` that isn't two hex digits of ASCII
+pub fn unescape_keys(text: &str) -> Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some('t') => output.push('\t'),
+            Some('e') => output.push('\x1b'),
+            Some('\\') => output.push('\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let value = u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|value| hex.len() == 2 && value.is_ascii())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid escape \\x{hex}"))?;
+                output.push(char::from(value));
+            }
+            Some(other) => return Err(anyhow::anyhow!("Unknown escape \\{other}")),
+            None => return Err(anyhow::anyhow!("Trailing \\ in {text}")),
+        }
+    }
+
+    Ok(output)
+}
@@ -0,0 +1,184 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Default amount presentation mode multiplies the font size by
+pub const DEFAULT_PRESENTATION_FONT_SCALE: f32 = 1.5;
+
+/// How long a key stays on screen after it was typed
+pub const KEYSTROKE_DISPLAY_TIME: Duration = Duration::from_secs(2);
+
+/// Most keys shown at once. Older ones make way for new ones
+pub const MAX_KEYSTROKES: usize = 8;
+
+/// Longest run of typed text shown as one entry, in characters
+const MAX_TEXT_LENGTH: usize = 24;
+
+/// How presentation mode, for screencasts and teaching, changes the terminal
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresentationSettings {
+    /// What the font size is multiplied by
+    pub font_scale: f32,
+    /// Show the keys being typed in an overlay
+    pub show_keys: bool,
+}
+
+impl Default for PresentationSettings {
+    fn default() -> Self {
+        Self {
+            font_scale: DEFAULT_PRESENTATION_FONT_SCALE,
+            show_keys: true,
+        }
+    }
+}
+
+/// Whether presentation mode is on, and the font size to go back to when it is turned off
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PresentationMode {
+    settings: PresentationSettings,
+    /// The font size before presentation mode was turned on. `None` while it is off
+    normal_font_size: Option<f32>,
+    keystrokes: KeystrokeLog,
+}
+
+impl PresentationMode {
+    #[must_use]
+    pub fn new(settings: PresentationSettings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        self.normal_font_size.is_some()
+    }
+
+    #[must_use]
+    pub const fn settings(&self) -> &PresentationSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: PresentationSettings) {
+        self.settings = settings;
+    }
+
+    /// The normal font size has changed to `font_size`. Returns the font size to switch to,
+    /// which is bigger while presentation mode is on
+    pub fn resize(&mut self, font_size: f32) -> f32 {
+        if self.is_active() {
+            self.normal_font_size = Some(font_size);
+            font_size * self.settings.font_scale
+        } else {
+            font_size
+        }
+    }
+
+    /// Turn presentation mode on or off, given the font size now. Returns the font size to
+    /// switch to
+    pub fn toggle(&mut self, font_size: f32) -> f32 {
+        if let Some(normal_font_size) = self.normal_font_size.take() {
+            self.keystrokes.clear();
+            normal_font_size
+        } else {
+            self.normal_font_size = Some(font_size);
+            font_size * self.settings.font_scale
+        }
+    }
+
+    /// Whether typed keys should be recorded for the overlay
+    #[must_use]
+    pub const fn shows_keys(&self) -> bool {
+        self.is_active() && self.settings.show_keys
+    }
+
+    #[must_use]
+    pub const fn keystrokes(&self) -> &KeystrokeLog {
+        &self.keystrokes
+    }
+
+    pub const fn keystrokes_mut(&mut self) -> &mut KeystrokeLog {
+        &mut self.keystrokes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Keystroke {
+    label: String,
+    /// Typed text rather than a key combination, so more text can be added to it
+    text: bool,
+    at: Instant,
+}
+
+/// The keys typed in the last few seconds, for showing on screen
+///
+/// Typed text is gathered up in to words rather than shown a letter at a time. Keys that don't
+/// type anything, like Ctrl+C or the arrow keys, each get their own entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeystrokeLog {
+    keys: VecDeque<Keystroke>,
+}
+
+impl KeystrokeLog {
+    /// Add typed text, carrying on from the last entry if that was text too
+    pub fn push_text(&mut self, text: &str, now: Instant) {
+        if let Some(last) = self.keys.back_mut() {
+            if last.text && last.label.chars().count() + text.chars().count() <= MAX_TEXT_LENGTH {
+                last.label.push_str(text);
+                last.at = now;
+                return;
+            }
+        }
+
+        self.push(text.to_string(), true, now);
+    }
+
+    /// Add a key that doesn't type anything, such as `Ctrl+C` or `Enter`
+    pub fn push_key(&mut self, label: &str, now: Instant) {
+        self.push(label.to_string(), false, now);
+    }
+
+    fn push(&mut self, label: String, text: bool, now: Instant) {
+        self.keys.push_back(Keystroke {
+            label,
+            text,
+            at: now,
+        });
+        while self.keys.len() > MAX_KEYSTROKES {
+            self.keys.pop_front();
+        }
+    }
+
+    /// Forget keys that have been on screen long enough. Returns how long until the next one
+    /// goes, if any are left
+    pub fn expire(&mut self, now: Instant) -> Option<Duration> {
+        self.keys
+            .retain(|key| now.duration_since(key.at) < KEYSTROKE_DISPLAY_TIME);
+        self.keys
+            .iter()
+            .map(|key| KEYSTROKE_DISPLAY_TIME.saturating_sub(now.duration_since(key.at)))
+            .min()
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// What to show for each entry, oldest first
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(|key| key.label.as_str())
+    }
+}
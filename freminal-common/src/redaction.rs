@@ -0,0 +1,211 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{borrow::Cow, ops::Range};
+
+use anyhow::{anyhow, Result};
+use regex::bytes::{Captures, Regex};
+
+/// The character secrets are masked with
+pub const REDACTION_CHAR: u8 = b'*';
+
+/// Longest secret, in bytes, still found when it arrives in pieces. Patterns that can match
+/// anything longer, such as `token=\S+`, are only followed this far back in to earlier output
+pub const MAX_SPLIT_SECRET_LENGTH: usize = 1024;
+
+/// Masks text matching any of the user's patterns, such as API keys, so they don't show up on
+/// screen while streaming or in a recording
+///
+/// Each character of a match becomes a `*`, so the text around it stays where it was.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    regex: Regex,
+    /// Most bytes a match can be, up to `MAX_SPLIT_SECRET_LENGTH`
+    max_match_len: usize,
+}
+
+impl Redactor {
+    /// A redactor for `patterns`, which are regular expressions
+    ///
+    /// # Errors
+    /// Will return an error if there are no patterns, or naming the first that isn't a valid
+    /// regular expression
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        if patterns.is_empty() {
+            return Err(anyhow!("No redaction patterns"));
+        }
+
+        for pattern in patterns {
+            validate_pattern(pattern)?;
+        }
+
+        let combined = patterns
+            .iter()
+            .map(|pattern| format!("(?:{pattern})"))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        let max_match_len = regex_syntax::parse(&combined)
+            .ok()
+            .and_then(|hir| hir.properties().maximum_len())
+            .map_or(MAX_SPLIT_SECRET_LENGTH, |len| {
+                len.min(MAX_SPLIT_SECRET_LENGTH)
+            });
+
+        Ok(Self {
+            regex: Regex::new(&combined)?,
+            max_match_len,
+        })
+    }
+
+    /// The most bytes of earlier output that need to be looked at again to find a secret that
+    /// was split
+    #[must_use]
+    pub const fn max_match_len(&self) -> usize {
+        self.max_match_len
+    }
+
+    /// Where the secrets in `data` are
+    pub fn find_iter<'a>(&'a self, data: &'a [u8]) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.regex.find_iter(data).map(|m| m.range())
+    }
+
+    /// `data` with every match masked, or borrowed unchanged if nothing matched
+    #[must_use]
+    pub fn redact<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        self.regex.replace_all(data, |captures: &Captures<'_>| {
+            let chars = String::from_utf8_lossy(&captures[0]).chars().count();
+            vec![REDACTION_CHAR; chars]
+        })
+    }
+}
+
+/// Masks secrets in output that arrives in pieces, such as reads from the pty, before it is
+/// written somewhere that can't be changed afterwards like a recording
+///
+/// Escape sequences are skipped over when matching, so a secret split by a color change part way
+/// through is still found. The end of each piece, up to the longest a secret can be, is held back
+/// until the next piece shows whether it was the start of one.
+#[derive(Debug, Clone)]
+pub struct StreamRedactor {
+    redactor: Redactor,
+    /// Output held back, as it may be the start of a secret
+    pending: Vec<u8>,
+}
+
+impl StreamRedactor {
+    #[must_use]
+    pub const fn new(redactor: Redactor) -> Self {
+        Self {
+            redactor,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Add `data` to the stream, and get back the output that is known to be masked
+    pub fn push(&mut self, data: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(data);
+
+        let (text, offsets) = text_without_escapes(&self.pending);
+        let mut held = text.len().saturating_sub(self.redactor.max_match_len());
+        let mut secrets = Vec::new();
+        for secret in self.redactor.find_iter(&text) {
+            if secret.start >= held {
+                break;
+            }
+            // What comes next may make it longer
+            if secret.end > held {
+                held = secret.start;
+                break;
+            }
+            secrets.push(secret);
+        }
+
+        let end = offsets.get(held).copied().unwrap_or(self.pending.len());
+        let output = mask(&self.pending[..end], &offsets, &secrets);
+        self.pending.drain(..end);
+
+        output
+    }
+
+    /// Everything held back, masked. For the end of the stream
+    pub fn finish(&mut self) -> Vec<u8> {
+        let (text, offsets) = text_without_escapes(&self.pending);
+        let secrets = self.redactor.find_iter(&text).collect::<Vec<_>>();
+        let output = mask(&self.pending, &offsets, &secrets);
+        self.pending.clear();
+
+        output
+    }
+}
+
+/// The text in `data` with escape sequences taken out, and where each byte of it is in `data`
+fn text_without_escapes(data: &[u8]) -> (Vec<u8>, Vec<usize>) {
+    let mut text = Vec::with_capacity(data.len());
+    let mut offsets = Vec::with_capacity(data.len());
+    let mut i = 0;
+
+    while i < data.len() {
+        if data[i] != b'\x1b' {
+            text.push(data[i]);
+            offsets.push(i);
+            i += 1;
+            continue;
+        }
+
+        i = match data.get(i + 1) {
+            // CSI, up to its final byte
+            Some(b'[') => data[i + 2..]
+                .iter()
+                .position(|b| (0x40..=0x7e).contains(b))
+                .map_or(data.len(), |end| i + 2 + end + 1),
+            // OSC, DCS and the other strings, up to BEL or ST
+            Some(b']' | b'P' | b'X' | b'^' | b'_') => data[i + 2..]
+                .iter()
+                .enumerate()
+                .find(|(end, b)| {
+                    **b == b'\x07' || (**b == b'\\' && data[i + 2 + end - 1] == b'\x1b')
+                })
+                .map_or(data.len(), |(end, _)| i + 2 + end + 1),
+            _ => (i + 2).min(data.len()),
+        };
+    }
+
+    (text, offsets)
+}
+
+/// `data` with the `secrets` in its text masked, one `REDACTION_CHAR` for each character.
+/// Escape sequences inside a secret are kept
+fn mask(data: &[u8], offsets: &[usize], secrets: &[Range<usize>]) -> Vec<u8> {
+    let mut output = data.to_vec();
+    // Backwards, so masking one doesn't move the ones before it
+    for secret in secrets.iter().rev() {
+        for &offset in offsets[secret.clone()].iter().rev() {
+            if output[offset] & 0xc0 == 0x80 {
+                // The rest of a UTF-8 character
+                output.remove(offset);
+            } else {
+                output[offset] = REDACTION_CHAR;
+            }
+        }
+    }
+
+    output
+}
+
+/// Check `pattern` is a regular expression that can't match nothing, which would mask nothing
+/// anyway
+///
+/// # Errors
+/// Will return an error if it isn't
+pub fn validate_pattern(pattern: &str) -> Result<()> {
+    let regex =
+        Regex::new(pattern).map_err(|e| anyhow!("Invalid redaction pattern {pattern:?}: {e}"))?;
+    if regex.is_match(b"") {
+        return Err(anyhow!("Redaction pattern {pattern:?} matches empty text"));
+    }
+
+    Ok(())
+}
@@ -15,3 +15,105 @@ impl Default for ScrollDirection {
         Self::Up(1)
     }
 }
+
+/// A jump of the viewport through the scrollback, usually from the keyboard
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ViewportMovement {
+    PageUp,
+    PageDown,
+    /// The oldest line in the scrollback
+    Top,
+    /// The live screen
+    Bottom,
+}
+
+/// Default number of lines to scroll for each notch of a mouse wheel
+pub const DEFAULT_LINES_PER_WHEEL_TICK: f32 = 3.0;
+
+/// How wheel and trackpad movement is turned in to scrolling
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollSettings {
+    pub lines_per_wheel_tick: f32,
+    /// Scroll the opposite way to the wheel ("natural" scrolling)
+    pub invert: bool,
+    /// Animate the viewport when it moves instead of jumping straight there
+    pub smooth: bool,
+}
+
+impl Default for ScrollSettings {
+    fn default() -> Self {
+        Self {
+            lines_per_wheel_tick: DEFAULT_LINES_PER_WHEEL_TICK,
+            invert: false,
+            smooth: true,
+        }
+    }
+}
+
+impl ScrollSettings {
+    /// Lines to scroll for `ticks` notches of a mouse wheel. Positive scrolls up
+    #[must_use]
+    pub fn wheel_ticks_to_lines(&self, ticks: f32) -> f32 {
+        self.apply_direction(ticks * self.lines_per_wheel_tick)
+    }
+
+    /// Lines to scroll for a pixel precise (trackpad) scroll of `pixels`. Positive scrolls up
+    #[must_use]
+    pub fn pixels_to_lines(&self, pixels: f32, line_height: f32) -> f32 {
+        if line_height <= 0.0 {
+            return 0.0;
+        }
+
+        self.apply_direction(pixels / line_height)
+    }
+
+    #[must_use]
+    pub fn apply_direction(&self, lines: f32) -> f32 {
+        if self.invert {
+            -lines
+        } else {
+            lines
+        }
+    }
+}
+
+/// Collects fractional scroll amounts until they add up to whole lines
+///
+/// Trackpads report many small movements. Rounding each of them on its own either scrolls far too
+/// fast or not at all, so we keep the remainder around for the next event.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrollAccumulator {
+    pending: f32,
+}
+
+impl ScrollAccumulator {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { pending: 0.0 }
+    }
+
+    /// Add `lines` to the pending scroll and take out any whole lines. Positive scrolls up
+    pub fn accumulate(&mut self, lines: f32) -> f32 {
+        // Changing direction throws away whatever was left over from the other direction
+        if self.pending != 0.0 && self.pending.signum() != lines.signum() {
+            self.pending = 0.0;
+        }
+
+        self.pending += lines;
+        let whole = self.pending.trunc();
+        self.pending -= whole;
+
+        whole
+    }
+
+    #[must_use]
+    pub const fn pending(&self) -> f32 {
+        self.pending
+    }
+
+    pub fn reset(&mut self) {
+        self.pending = 0.0;
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+/// What to run in the terminal
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellSettings {
+    /// The shell to run. The user's default shell is used if this isn't set
+    pub program: Option<String>,
+    /// Extra arguments passed to the shell
+    pub args: Vec<String>,
+    /// Start the shell as a login shell
+    pub login: bool,
+}
+
+impl ShellSettings {
+    /// The full command line to run, using `default_shell` to find the shell if none was set
+    ///
+    /// The user's default shell is always started as a login shell, the same as a console login.
+    /// A shell picked with `--shell` is only a login shell if that was asked for.
+    #[must_use]
+    pub fn command_line(&self, default_shell: impl FnOnce() -> String) -> Vec<String> {
+        let login = self.login || self.program.is_none();
+        let program = self.program.clone().unwrap_or_else(default_shell);
+
+        let login_flag = if login { login_flag(&program) } else { None };
+        if self.login && login_flag.is_none() {
+            warn!("{program} has no login shell option. Starting it normally");
+        }
+
+        let mut command_line = vec![program];
+        command_line.extend(login_flag.map(ToString::to_string));
+        command_line.extend(self.args.iter().cloned());
+
+        command_line
+    }
+}
+
+/// The option that makes `program` a login shell, if it has one
+fn login_flag(program: &str) -> Option<&'static str> {
+    let name = program
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(program)
+        .to_ascii_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+
+    match name {
+        "cmd" | "powershell" => None,
+        "pwsh" => Some("-Login"),
+        _ => Some("-l"),
+    }
+}
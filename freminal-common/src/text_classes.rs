@@ -0,0 +1,187 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::ops::Range;
+
+/// Characters other than letters and digits that are treated as part of a word by default
+pub const DEFAULT_WORD_CHARACTERS: &str = "_";
+
+/// URL schemes recognized in terminal output by default
+pub const DEFAULT_URL_SCHEMES: &[&str] = &["http", "https", "ftp", "file", "mailto", "ssh", "git"];
+
+/// Characters that are never part of a detected URL
+const URL_TERMINATORS: &[char] = &['<', '>', '"', '\'', '`', '{', '}', '|', '\\', '^'];
+
+/// Characters that are dropped from the end of a detected URL, since they are almost always
+/// punctuation from the surrounding text
+const URL_TRAILING_PUNCTUATION: &[char] = &['.', ',', ':', ';', '!', '?', ')', ']'];
+
+/// Which characters make up a word when selecting by word
+///
+/// Letters and digits are always part of a word. Anything else has to be listed explicitly.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WordCharacters {
+    extra: String,
+}
+
+impl Default for WordCharacters {
+    fn default() -> Self {
+        Self::new(DEFAULT_WORD_CHARACTERS)
+    }
+}
+
+impl WordCharacters {
+    #[must_use]
+    pub fn new(extra: &str) -> Self {
+        Self {
+            extra: extra.to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn extra(&self) -> &str {
+        &self.extra
+    }
+
+    #[must_use]
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || self.extra.contains(c)
+    }
+
+    /// Find the word in `line` that contains the character at `index`
+    #[must_use]
+    pub fn word_range(&self, line: &[char], index: usize) -> Option<Range<usize>> {
+        if !self.is_word_char(*line.get(index)?) {
+            return None;
+        }
+
+        let start = line[..index]
+            .iter()
+            .rposition(|c| !self.is_word_char(*c))
+            .map_or(0, |pos| pos + 1);
+        let end = line[index..]
+            .iter()
+            .position(|c| !self.is_word_char(*c))
+            .map_or(line.len(), |pos| index + pos);
+
+        Some(start..end)
+    }
+}
+
+/// The URL schemes we look for when detecting links in plain terminal output
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UrlSchemes {
+    schemes: Vec<String>,
+}
+
+impl Default for UrlSchemes {
+    fn default() -> Self {
+        Self::new(DEFAULT_URL_SCHEMES.iter().copied())
+    }
+}
+
+impl UrlSchemes {
+    pub fn new<'a>(schemes: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            schemes: schemes
+                .into_iter()
+                .map(str::trim)
+                .filter(|scheme| !scheme.is_empty())
+                .map(str::to_ascii_lowercase)
+                .collect(),
+        }
+    }
+
+    /// Parse a comma separated list of schemes, like `https,http,ftp`
+    #[must_use]
+    pub fn from_list(list: &str) -> Self {
+        Self::new(list.split(','))
+    }
+
+    #[must_use]
+    pub fn schemes(&self) -> &[String] {
+        &self.schemes
+    }
+
+    /// Find every URL in `line`, returning the range of characters each one covers
+    #[must_use]
+    pub fn find_urls(&self, line: &[char]) -> Vec<Range<usize>> {
+        let mut urls = Vec::new();
+        let mut index = 0;
+
+        while index < line.len() {
+            match self.url_starting_at(line, index) {
+                Some(range) => {
+                    index = range.end;
+                    urls.push(range);
+                }
+                None => index += 1,
+            }
+        }
+
+        urls
+    }
+
+    /// Find the URL in `line` that contains the character at `index`
+    #[must_use]
+    pub fn url_at(&self, line: &[char], index: usize) -> Option<String> {
+        self.find_urls(line)
+            .into_iter()
+            .find(|range| range.contains(&index))
+            .map(|range| line[range].iter().collect())
+    }
+
+    fn url_starting_at(&self, line: &[char], start: usize) -> Option<Range<usize>> {
+        // A scheme has to start at a word boundary, otherwise "xhttp://" would match
+        if start > 0 && line[start - 1].is_alphanumeric() {
+            return None;
+        }
+
+        let scheme_len = self.schemes.iter().find_map(|scheme| {
+            let len = scheme.chars().count();
+            let candidate = line.get(start..start + len)?;
+            let matches = candidate
+                .iter()
+                .zip(scheme.chars())
+                .all(|(a, b)| a.to_ascii_lowercase() == b);
+
+            (matches && line.get(start + len) == Some(&':')).then_some(len)
+        })?;
+
+        let body_start = start + scheme_len + 1;
+        let mut end = line[body_start..]
+            .iter()
+            .position(|c| c.is_whitespace() || c.is_control() || URL_TERMINATORS.contains(c))
+            .map_or(line.len(), |pos| body_start + pos);
+
+        while end > body_start && URL_TRAILING_PUNCTUATION.contains(&line[end - 1]) {
+            // Keep closing brackets that are part of the URL, like wikipedia links
+            let c = line[end - 1];
+            let opening = match c {
+                ')' => Some('('),
+                ']' => Some('['),
+                _ => None,
+            };
+
+            if let Some(opening) = opening {
+                let url = &line[body_start..end];
+                let opened = url.iter().filter(|x| **x == opening).count();
+                let closed = url.iter().filter(|x| **x == c).count();
+                if opened >= closed {
+                    break;
+                }
+            }
+
+            end -= 1;
+        }
+
+        // Just a scheme on its own isn't a URL
+        if end == body_start || line[body_start..end].iter().all(|c| *c == '/') {
+            return None;
+        }
+
+        Some(start..end)
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    str::FromStr,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use time::UtcOffset;
+
+/// Columns the timestamp gutter takes up when it is always shown: `HH:MM:SS` with a space
+/// either side
+pub const TIMESTAMP_GUTTER_COLUMNS: usize = 10;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// The local time zone's offset from UTC, read once at startup
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
+/// Whether to show when each line of output was written, next to the line
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum TimestampGutter {
+    #[default]
+    Off,
+    /// Only for the line under the mouse
+    Hover,
+    /// For every line, in a gutter to the right of the output
+    Always,
+}
+
+impl TimestampGutter {
+    /// Columns taken away from the terminal to make room for the gutter
+    #[must_use]
+    pub const fn columns(self) -> usize {
+        match self {
+            Self::Off | Self::Hover => 0,
+            Self::Always => TIMESTAMP_GUTTER_COLUMNS,
+        }
+    }
+}
+
+impl FromStr for TimestampGutter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "hover" => Ok(Self::Hover),
+            "always" => Ok(Self::Always),
+            _ => Err(anyhow::anyhow!(
+                "Invalid timestamp gutter {s}. Expected off, hover or always"
+            )),
+        }
+    }
+}
+
+/// Read the local time zone's offset from UTC, for `format_time_of_day`
+///
+/// This has to be called before any other threads are started, as the time zone can't be read
+/// safely while another thread could be changing the environment. If it isn't called, or the
+/// time zone can't be read, times are shown in UTC.
+///
+/// # Errors
+/// Will return an error if the time zone can't be read
+pub fn read_local_offset() -> Result<(), time::error::IndeterminateOffset> {
+    let offset = UtcOffset::current_local_offset()?;
+    let _ = LOCAL_OFFSET.set(offset);
+
+    Ok(())
+}
+
+/// `None` when times are shown in local time, otherwise the time zone they are shown in, for
+/// labelling them
+#[must_use]
+pub fn time_zone_label() -> Option<&'static str> {
+    match LOCAL_OFFSET.get() {
+        Some(_) => None,
+        None => Some("UTC"),
+    }
+}
+
+/// The local time of day `time` falls on, as `HH:MM:SS`. See `read_local_offset`
+#[must_use]
+pub fn format_time_of_day(time: SystemTime) -> String {
+    format_time_of_day_with_offset(time, LOCAL_OFFSET.get().copied().unwrap_or(UtcOffset::UTC))
+}
+
+/// The time of day `time` falls on in the time zone `offset` from UTC, as `HH:MM:SS`
+#[must_use]
+pub fn format_time_of_day_with_offset(time: SystemTime, offset: UtcOffset) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| i64::try_from(since.as_secs()).unwrap_or_default())
+        .unwrap_or_default();
+    let seconds = (seconds + i64::from(offset.whole_seconds())).rem_euclid(SECONDS_PER_DAY);
+
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,53 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::str::FromStr;
+
+/// Placeholders a title format can use
+const TITLE_PLACEHOLDER: &str = "{title}";
+const CWD_PLACEHOLDER: &str = "{cwd}";
+const ID_PLACEHOLDER: &str = "{id}";
+
+/// How the window title is put together, such as `{title} — {cwd} — freminal:{id}`
+///
+/// `{title}` is the title the application asked for, `{cwd}` the directory the shell last
+/// reported and `{id}` the terminal's ID. Anything else is shown as it is. The default is just
+/// `{title}`, showing the application's title unchanged.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TitleFormat(String);
+
+impl Default for TitleFormat {
+    fn default() -> Self {
+        Self(TITLE_PLACEHOLDER.to_string())
+    }
+}
+
+impl FromStr for TitleFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid title format. Expected some text, such as {{title}} — {{cwd}}"
+            ));
+        }
+
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl TitleFormat {
+    /// The title to show. A directory that isn't known yet is left empty, and space left at
+    /// either end is trimmed
+    #[must_use]
+    pub fn render(&self, title: &str, cwd: Option<&str>, id: &str) -> String {
+        self.0
+            .replace(TITLE_PLACEHOLDER, title)
+            .replace(CWD_PLACEHOLDER, cwd.unwrap_or_default())
+            .replace(ID_PLACEHOLDER, id)
+            .trim()
+            .to_string()
+    }
+}
@@ -17,6 +17,8 @@ pub enum WindowManipulation {
     RefreshWindow,
     ResizeWindowToLinesAndColumns(usize, usize),
     MaximizeWindow,
+    MaximizeWindowVertically,
+    MaximizeWindowHorizontally,
     RestoreNonMaximizedWindow,
     NotFullScreen,
     FullScreen,
@@ -52,6 +54,8 @@ impl TryFrom<(usize, usize, usize)> for WindowManipulation {
             (8, x, y) => Ok(Self::ResizeWindowToLinesAndColumns(x, y)),
             (9, 1, _) => Ok(Self::MaximizeWindow),
             (9, 0, _) => Ok(Self::RestoreNonMaximizedWindow),
+            (9, 2, _) => Ok(Self::MaximizeWindowVertically),
+            (9, 3, _) => Ok(Self::MaximizeWindowHorizontally),
             (10, 0, _) => Ok(Self::NotFullScreen),
             (10, 1, _) => Ok(Self::FullScreen),
             (10, 2, _) => Ok(Self::ToggleFullScreen),
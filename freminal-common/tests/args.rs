@@ -3,7 +3,23 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
-use freminal_common::args::Args;
+use freminal_common::{
+    args::{
+        Args, DEFAULT_MAX_FPS, DEFAULT_MAX_SEQUENCE_LENGTH, DEFAULT_SCROLLBACK_LINES,
+        UNLIMITED_SCROLLBACK_LINES,
+    },
+    cell_geometry::TerminalGeometry,
+    char_width::UnicodeVersion,
+    colors::Theme,
+    config::Config,
+    cursor::CursorVisualStyle,
+    font::FontSettings,
+    links::LinkOpening,
+    shell::ShellSettings,
+    text_classes::{UrlSchemes, WordCharacters},
+    timestamps::TimestampGutter,
+    title_format::TitleFormat,
+};
 use test_log::test;
 
 #[test]
@@ -118,3 +134,732 @@ fn test_log_file_arg() {
     );
     assert!(args.is_err());
 }
+
+#[test]
+fn test_max_fps_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.max_fps, DEFAULT_MAX_FPS);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--max-fps".to_string(),
+            "30".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.max_fps, 30);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--max-fps".to_string(),
+            "0".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.max_fps, 0);
+
+    let args = Args::parse(vec!["freminal".to_string(), "--max-fps".to_string()].into_iter());
+    assert!(args.is_err());
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--max-fps".to_string(),
+            "fast".to_string(),
+        ]
+        .into_iter(),
+    );
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_max_sequence_length_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.max_sequence_length, DEFAULT_MAX_SEQUENCE_LENGTH);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--max-sequence-length".to_string(),
+            "1024".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.max_sequence_length, 1024);
+
+    for bad in ["0", "-1", "lots"] {
+        let args = Args::parse(
+            vec![
+                "freminal".to_string(),
+                "--max-sequence-length".to_string(),
+                bad.to_string(),
+            ]
+            .into_iter(),
+        );
+        assert!(args.is_err(), "{bad}");
+    }
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--max-sequence-length".to_string()].into_iter());
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_text_class_args() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.word_characters, WordCharacters::default());
+    assert_eq!(args.url_schemes, UrlSchemes::default());
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--word-chars".to_string(),
+            "-_:".to_string(),
+            "--url-schemes".to_string(),
+            "https,gopher".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.word_characters.extra(), "-_:");
+    assert_eq!(args.url_schemes.schemes(), ["https", "gopher"]);
+
+    let args = Args::parse(vec!["freminal".to_string(), "--word-chars".to_string()].into_iter());
+    assert!(args.is_err());
+    // The config file's settings are used unless the command line gives others
+    let config: Config = "[selection]\nword_characters = \"-\"\n[links]\nurl_schemes = [\"ftp\"]"
+        .parse()
+        .unwrap();
+    let args = Args::parse_with_config(["freminal".to_string()].into_iter(), &config).unwrap();
+    assert_eq!(args.word_characters.extra(), "-");
+    assert_eq!(args.url_schemes.schemes(), ["ftp"]);
+    let args = Args::parse_with_config(
+        ["freminal", "--word-chars", ":"]
+            .map(str::to_string)
+            .into_iter(),
+        &config,
+    )
+    .unwrap();
+    assert_eq!(args.word_characters.extra(), ":");
+}
+
+#[test]
+fn test_scroll_args() {
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--scroll-lines".to_string(),
+            "1.5".to_string(),
+            "--invert-scroll".to_string(),
+            "--no-smooth-scroll".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert!((args.scroll_settings.lines_per_wheel_tick - 1.5).abs() < f32::EPSILON);
+    assert!(args.scroll_settings.invert);
+    assert!(!args.scroll_settings.smooth);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--scroll-lines".to_string(),
+            "0".to_string(),
+        ]
+        .into_iter(),
+    );
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_cursor_args() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(args.cursor_settings.blink);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--cursor-thickness".to_string(),
+            "4".to_string(),
+            "--cursor-outline-width".to_string(),
+            "2.5".to_string(),
+            "--no-cursor-blink".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert!((args.cursor_settings.thickness - 4.0).abs() < f32::EPSILON);
+    assert!((args.cursor_settings.outline_width - 2.5).abs() < f32::EPSILON);
+    assert!(!args.cursor_settings.blink);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--cursor-style".to_string(),
+            "blinking-bar".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(
+        args.cursor_settings.style,
+        CursorVisualStyle::VerticalLineCursorBlink
+    );
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--cursor-style".to_string(),
+            "triangle".to_string(),
+        ]
+        .into_iter(),
+    );
+    assert!(args.is_err());
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--cursor-thickness".to_string(),
+            "-1".to_string(),
+        ]
+        .into_iter(),
+    );
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_measure_latency_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.measure_input_latency);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--measure-latency".to_string()].into_iter())
+            .unwrap();
+    assert!(args.measure_input_latency);
+}
+
+#[test]
+fn test_snap_to_cells_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.snap_to_cells);
+
+    let args = Args::parse(vec!["freminal".to_string(), "--snap-to-cells".to_string()].into_iter())
+        .unwrap();
+    assert!(args.snap_to_cells);
+}
+
+#[test]
+fn test_keep_trailing_spaces_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(args.trim_trailing_spaces_on_copy);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--keep-trailing-spaces".to_string()].into_iter())
+            .unwrap();
+    assert!(!args.trim_trailing_spaces_on_copy);
+}
+
+#[test]
+fn test_palette_colors_only_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.clamp_colors_to_palette);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--palette-colors-only".to_string()].into_iter())
+            .unwrap();
+    assert!(args.clamp_colors_to_palette);
+}
+
+#[test]
+fn test_geometry_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.geometry, TerminalGeometry::new(80, 24));
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--geometry".to_string(),
+            "120x40".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.geometry, TerminalGeometry::new(120, 40));
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--geometry".to_string(),
+            "big".to_string(),
+        ]
+        .into_iter(),
+    );
+    assert!(args.is_err());
+
+    let args = Args::parse(vec!["freminal".to_string(), "--geometry".to_string()].into_iter());
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_shell_args() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.shell_settings(), ShellSettings::default());
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--shell".to_string(),
+            "/bin/bash".to_string(),
+            "--shell-arg".to_string(),
+            "--norc".to_string(),
+            "--shell-arg".to_string(),
+            "-x".to_string(),
+            "--login".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(
+        args.shell_settings(),
+        ShellSettings {
+            program: Some("/bin/bash".to_string()),
+            args: vec!["--norc".to_string(), "-x".to_string()],
+            login: true,
+        }
+    );
+
+    let args = Args::parse(vec!["freminal".to_string(), "--shell-arg".to_string()].into_iter());
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_recording_dir_arg() {
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--recording-dir".to_string(),
+            "/tmp/recordings".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.recording_dir.as_deref(), Some("/tmp/recordings"));
+    assert_eq!(args.recording, None);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--recording-dir".to_string(),
+            "/tmp/recordings".to_string(),
+            "--recording-path".to_string(),
+            "/tmp/session.rec".to_string(),
+        ]
+        .into_iter(),
+    );
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_bidi_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.bidi);
+
+    let args = Args::parse(vec!["freminal".to_string(), "--bidi".to_string()].into_iter()).unwrap();
+    assert!(args.bidi);
+}
+
+#[test]
+fn test_unicode_version_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.unicode_version, UnicodeVersion::Unicode9);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--unicode-version".to_string(),
+            "8".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.unicode_version, UnicodeVersion::Unicode8);
+
+    assert!(Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--unicode-version".to_string(),
+            "new".to_string(),
+        ]
+        .into_iter(),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_ambiguous_wide_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.ambiguous_wide);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--ambiguous-wide".to_string()].into_iter())
+            .unwrap();
+    assert!(args.ambiguous_wide);
+}
+
+#[test]
+fn test_powerline_glyphs_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(args.powerline_glyphs);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--no-powerline-glyphs".to_string()].into_iter())
+            .unwrap();
+    assert!(!args.powerline_glyphs);
+}
+
+#[test]
+fn test_box_drawing_glyphs_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(args.box_drawing_glyphs);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--no-box-drawing-glyphs".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert!(!args.box_drawing_glyphs);
+    assert!(args.powerline_glyphs);
+}
+
+#[test]
+fn test_timestamps_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.timestamp_gutter, TimestampGutter::Off);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--timestamps".to_string(),
+            "always".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.timestamp_gutter, TimestampGutter::Always);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--timestamps".to_string(),
+            "sometimes".to_string(),
+        ]
+        .into_iter(),
+    );
+    assert!(args.is_err());
+}
+
+#[test]
+fn test_dump_on_exit_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.dump_on_exit, None);
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--dump-on-exit".to_string(),
+            "-".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.dump_on_exit, Some("-".to_string()));
+
+    assert!(
+        Args::parse(vec!["freminal".to_string(), "--dump-on-exit".to_string()].into_iter())
+            .is_err()
+    );
+}
+
+#[test]
+fn test_title_format_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.title_format, TitleFormat::default());
+
+    let args = Args::parse(
+        vec![
+            "freminal".to_string(),
+            "--title-format".to_string(),
+            "{title} - {cwd}".to_string(),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.title_format, "{title} - {cwd}".parse().unwrap());
+
+    for bad in [vec!["--title-format", ""], vec!["--title-format"]] {
+        let args = std::iter::once("freminal")
+            .chain(bad)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        assert!(Args::parse(args.into_iter()).is_err());
+    }
+}
+
+#[test]
+fn test_open_links_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.link_opening, LinkOpening::Click);
+
+    let args = Args::parse(
+        ["freminal", "--open-links", "ctrl-click"]
+            .map(str::to_string)
+            .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.link_opening, LinkOpening::CtrlClick);
+
+    for bad in [vec!["--open-links", "double-click"], vec!["--open-links"]] {
+        let args = std::iter::once("freminal")
+            .chain(bad)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        assert!(Args::parse(args.into_iter()).is_err());
+    }
+}
+
+#[test]
+fn test_hide_startup_banner_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.hide_startup_banner);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--hide-startup-banner".to_string()].into_iter())
+            .unwrap();
+    assert!(args.hide_startup_banner);
+}
+
+#[test]
+fn test_warn_unknown_term_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.warn_unknown_term);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--warn-unknown-term".to_string()].into_iter())
+            .unwrap();
+    assert!(args.warn_unknown_term);
+}
+
+#[test]
+fn test_clear_in_place_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.clear_in_place);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--clear-in-place".to_string()].into_iter())
+            .unwrap();
+    assert!(args.clear_in_place);
+}
+
+#[test]
+fn test_new_window_args() {
+    let args = [
+        "freminal",
+        "--shell",
+        "zsh",
+        "--recording-path",
+        "session.rec",
+        "--bidi",
+        "--control-socket",
+        "/tmp/freminal.sock",
+        "--dump-on-exit",
+        "-",
+        "--max-fps",
+        "30",
+    ]
+    .map(str::to_string);
+
+    assert_eq!(
+        Args::new_window_args(args.into_iter()),
+        ["--shell", "zsh", "--bidi", "--max-fps", "30"].map(str::to_string)
+    );
+    assert!(Args::new_window_args(["freminal".to_string()].into_iter()).is_empty());
+}
+
+#[test]
+fn test_args_font_and_scrollback() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert_eq!(args.font_settings, FontSettings::default());
+    assert_eq!(args.scrollback_lines, DEFAULT_SCROLLBACK_LINES);
+
+    let args = Args::parse(
+        [
+            "freminal",
+            "--font-family",
+            "JetBrains Mono",
+            "--font-size",
+            "14.5",
+            "--scrollback-lines",
+            "10000",
+        ]
+        .map(str::to_string)
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.font_settings.family.as_deref(), Some("JetBrains Mono"));
+    assert!((args.font_settings.size - 14.5).abs() < f32::EPSILON);
+    assert_eq!(args.scrollback_lines, 10000);
+
+    let args = Args::parse(
+        ["freminal", "--scrollback-lines", "unlimited"]
+            .map(str::to_string)
+            .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(args.scrollback_lines, UNLIMITED_SCROLLBACK_LINES);
+
+    let args = Args::parse(
+        ["freminal", "--presentation-scale", "2.5"]
+            .map(str::to_string)
+            .into_iter(),
+    )
+    .unwrap();
+    assert!((args.presentation_settings.font_scale - 2.5).abs() < f32::EPSILON);
+    assert!(args.presentation_settings.show_keys);
+
+    for bad in [
+        ["freminal", "--presentation-scale", "0.5"],
+        ["freminal", "--presentation-scale", "huge"],
+        ["freminal", "--font-size", "0"],
+        ["freminal", "--font-size", "big"],
+        ["freminal", "--scrollback-lines", "0"],
+        ["freminal", "--scrollback-lines", "lots"],
+    ] {
+        assert!(Args::parse(bad.map(str::to_string).into_iter()).is_err());
+    }
+}
+
+#[test]
+fn test_args_override_config() {
+    let config: Config = "\
+        shell = \"fish\"\n\
+        shell_args = [\"--private\"]\n\
+        scrollback_lines = 500\n\
+        alternate_scrollback_lines = 50\n\
+        cursor_style = \"bar\"\n\
+        [font]\n\
+        family = \"Fira Code\"\n\
+        size = 16\n\
+        [colors]\n\
+        palette = [\"#101010\"]\n\
+        [size]\n\
+        max = \"200x60\"\n\
+        [links]\n\
+        open = \"confirm\"\n"
+        .parse()
+        .unwrap();
+
+    let args = Args::parse_with_config(["freminal".to_string()].into_iter(), &config).unwrap();
+    assert_eq!(args.shell.as_deref(), Some("fish"));
+    assert_eq!(args.shell_args, ["--private"]);
+    assert_eq!(args.scrollback_lines, 500);
+    assert_eq!(args.alternate_scrollback_lines, 50);
+    assert_eq!(
+        args.cursor_settings.style,
+        CursorVisualStyle::VerticalLineCursorSteady
+    );
+    assert_eq!(args.font_settings.family.as_deref(), Some("Fira Code"));
+    assert_eq!(args.palette.get(0), Some((0x10, 0x10, 0x10)));
+    assert_eq!(args.size_limits.max, TerminalGeometry::new(200, 60));
+    assert_eq!(args.link_opening, LinkOpening::Confirm);
+
+    let args = Args::parse_with_config(
+        [
+            "freminal",
+            "--config",
+            "other.toml",
+            "--shell",
+            "zsh",
+            "--scrollback-lines",
+            "100",
+            "--cursor-style",
+            "underline",
+            "--font-size",
+            "10",
+            "--theme",
+            "gruvbox",
+        ]
+        .map(str::to_string)
+        .into_iter(),
+        &config,
+    )
+    .unwrap();
+    // The config's shell arguments don't go to another shell
+    assert_eq!(args.shell.as_deref(), Some("zsh"));
+    assert!(args.shell_args.is_empty());
+    assert_eq!(args.scrollback_lines, 100);
+    assert_eq!(
+        args.cursor_settings.style,
+        CursorVisualStyle::UnderlineCursorSteady
+    );
+    assert_eq!(args.font_settings.family.as_deref(), Some("Fira Code"));
+    assert!((args.font_settings.size - 10.0).abs() < f32::EPSILON);
+    // The theme changes, but the color picked in the config stays
+    assert_eq!(args.palette.theme(), Theme::Gruvbox);
+    assert_eq!(args.palette.get(0), Some((0x10, 0x10, 0x10)));
+
+    assert!(Args::parse(
+        ["freminal", "--theme", "neon"]
+            .map(str::to_string)
+            .into_iter()
+    )
+    .is_err());
+}
+
+#[test]
+fn test_args_redact() {
+    let config: Config = "[redaction]\npatterns = [\"hunter2\"]".parse().unwrap();
+    let args = Args::parse_with_config(
+        ["freminal", "--redact", "sk-[a-z]+"]
+            .map(str::to_string)
+            .into_iter(),
+        &config,
+    )
+    .unwrap();
+    assert_eq!(args.redact_patterns, ["hunter2", "sk-[a-z]+"]);
+
+    assert!(Args::parse(
+        ["freminal", "--redact", "("]
+            .map(str::to_string)
+            .into_iter()
+    )
+    .is_err());
+    assert!(Args::parse(["freminal", "--redact"].map(str::to_string).into_iter()).is_err());
+}
+
+#[test]
+fn test_predict_echo_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.predict_echo);
+
+    let args = Args::parse(vec!["freminal".to_string(), "--predict-echo".to_string()].into_iter())
+        .unwrap();
+    assert!(args.predict_echo);
+}
+
+#[test]
+fn test_no_wrap_arg() {
+    let args = Args::parse(vec!["freminal".to_string()].into_iter()).unwrap();
+    assert!(!args.no_wrap);
+
+    let args =
+        Args::parse(vec!["freminal".to_string(), "--no-wrap".to_string()].into_iter()).unwrap();
+    assert!(args.no_wrap);
+}
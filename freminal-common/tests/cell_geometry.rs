@@ -0,0 +1,163 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::cell_geometry::{CellGeometry, CellGrid, TerminalGeometry};
+use test_log::test;
+
+#[test]
+fn test_cells_for_size() {
+    let geometry = CellGeometry::new(7.2, 14.0, 1.0);
+
+    assert_eq!(geometry.cells_for_size(576.0, 336.0), (80, 24));
+    assert_eq!(geometry.cells_for_size(579.0, 349.0), (80, 24));
+    assert_eq!(geometry.cells_for_size(0.0, -5.0), (0, 0));
+
+    // Float error just under a whole cell still counts as that cell
+    let (width, height) = geometry.size_for_cells(80, 24);
+    assert_eq!(geometry.cells_for_size(width - 0.0001, height), (80, 24));
+}
+
+#[test]
+fn test_window_round_trip() {
+    // Cell sizes that aren't exactly representable, at several display scales
+    for pixels_per_point in [1.0, 1.25, 1.5, 2.0, 3.0] {
+        for (cell_width, cell_height) in [(7.2, 14.0), (8.4, 17.1), (9.6015, 19.3)] {
+            let geometry = CellGeometry::new(cell_width, cell_height, pixels_per_point)
+                .with_padding(16.0, 8.0);
+
+            for (columns, rows) in [(20, 5), (80, 24), (132, 43), (311, 97)] {
+                let (width, height) = geometry.window_size_for_cells(columns, rows);
+                assert_eq!(
+                    geometry.cells_for_window_size(width, height),
+                    (columns, rows),
+                    "{cell_width}x{cell_height} at {pixels_per_point}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pixel_sizes() {
+    let geometry = CellGeometry::new(7.2, 14.0, 1.0);
+    assert_eq!(geometry.cell_size_in_pixels(), (7, 14));
+    assert_eq!(geometry.text_area_size_in_pixels(80, 24), (576, 336));
+
+    let geometry = CellGeometry::new(7.2, 14.0, 1.5);
+    assert_eq!(geometry.cell_size_in_pixels(), (11, 21));
+    assert_eq!(geometry.text_area_size_in_pixels(80, 24), (864, 504));
+
+    let geometry = CellGeometry::new(7.2, 14.0, 2.0);
+    assert_eq!(geometry.cell_size_in_pixels(), (14, 28));
+    assert_eq!(geometry.text_area_size_in_pixels(80, 24), (1152, 672));
+    assert!((geometry.pixels_to_points(1152) - 576.0).abs() < f32::EPSILON);
+
+    let geometry = CellGeometry::new(7.2, 14.0, 0.0);
+    assert_eq!(geometry.cell_size_in_pixels(), (0, 0));
+    assert!(geometry.pixels_to_points(100).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_parse_terminal_geometry() {
+    assert_eq!(
+        "120x40".parse::<TerminalGeometry>().unwrap(),
+        TerminalGeometry::new(120, 40)
+    );
+    assert_eq!(
+        "80X24".parse::<TerminalGeometry>().unwrap(),
+        TerminalGeometry::new(80, 24)
+    );
+
+    for invalid in ["", "120", "120x", "x40", "0x40", "120x0", "-1x40", "axb"] {
+        assert!(invalid.parse::<TerminalGeometry>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_cell_span_edges_meet() {
+    for pixels_per_point in [1.0, 1.25, 1.5, 2.0] {
+        let geometry = CellGeometry::new(8.4, 17.1, pixels_per_point);
+        let origin = (3.3, 7.7);
+
+        for row in 0..30 {
+            for column in 0..100 {
+                let (left, top, right, bottom) = geometry.cell_span_edges(origin, column, row, 1);
+                let (next_left, ..) = geometry.cell_span_edges(origin, column + 1, row, 1);
+                let (_, below_top, ..) = geometry.cell_span_edges(origin, column, row + 1, 1);
+
+                assert!((right - next_left).abs() < f32::EPSILON);
+                assert!((bottom - below_top).abs() < f32::EPSILON);
+                assert!(right > left && bottom > top);
+
+                // Edges land on whole physical pixels
+                let pixels = left * pixels_per_point;
+                assert!((pixels - pixels.round()).abs() < 0.001, "{pixels}");
+            }
+        }
+    }
+
+    // A run of cells covers the same area as the cells in it
+    let geometry = CellGeometry::new(7.2, 14.0, 1.5);
+    let (left, top, _, bottom) = geometry.cell_span_edges((0.0, 0.0), 4, 2, 1);
+    let (.., right, _) = geometry.cell_span_edges((0.0, 0.0), 9, 2, 1);
+    assert_eq!(
+        geometry.cell_span_edges((0.0, 0.0), 4, 2, 6),
+        (left, top, right, bottom)
+    );
+}
+
+#[test]
+fn test_cell_grid_hit_testing() {
+    // 10x5 cells of 8x16 points, drawn 12 points in from the window corner
+    let grid = CellGrid::new(CellGeometry::new(8.0, 16.0, 1.0), (12.0, 12.0), 10, 5);
+
+    assert_eq!(grid.cell_at((12.0, 12.0)), Some((0, 0)));
+    assert_eq!(grid.cell_at((19.9, 27.9)), Some((0, 0)));
+    // The edge between two cells belongs to the one after it
+    assert_eq!(grid.cell_at((20.0, 28.0)), Some((1, 1)));
+    assert_eq!(grid.cell_at((91.9, 91.9)), Some((9, 4)));
+
+    // The padding around the cells, and the partial cell past the last one, aren't cells
+    for outside in [
+        (11.9, 20.0),
+        (20.0, 11.9),
+        (92.0, 20.0),
+        (20.0, 92.0),
+        (-5.0, -5.0),
+    ] {
+        assert_eq!(grid.cell_at(outside), None, "{outside:?}");
+    }
+    assert_eq!(grid.cell_at((f32::NAN, 20.0)), None);
+
+    // Nearest cell clamps to the edge instead
+    assert_eq!(grid.nearest_cell((0.0, 0.0)), (0, 0));
+    assert_eq!(grid.nearest_cell((500.0, 20.0)), (9, 0));
+    assert_eq!(grid.nearest_cell((50.0, 500.0)), (4, 4));
+    assert_eq!(
+        grid.nearest_cell((50.0, 50.0)),
+        grid.cell_at((50.0, 50.0)).unwrap()
+    );
+    assert_eq!(grid.nearest_cell((f32::NAN, f32::NAN)), (0, 0));
+}
+
+#[test]
+fn test_cell_grid_follows_origin() {
+    // A scroll in progress moves the origin, and the cells move with it
+    let geometry = CellGeometry::new(8.0, 16.0, 2.0);
+    let resting = CellGrid::new(geometry, (0.0, 0.0), 80, 24);
+    let scrolled = CellGrid::new(geometry, (0.0, -8.0), 80, 24);
+
+    assert_eq!(resting.cell_at((4.0, 20.0)), Some((0, 1)));
+    assert_eq!(scrolled.cell_at((4.0, 20.0)), Some((0, 1)));
+    assert_eq!(scrolled.cell_at((4.0, 25.0)), Some((0, 2)));
+
+    // Degenerate grids never hit anything
+    let empty = CellGrid::new(CellGeometry::new(0.0, 0.0, 1.0), (0.0, 0.0), 80, 24);
+    assert_eq!(empty.cell_at((4.0, 4.0)), None);
+    assert_eq!(empty.nearest_cell((4.0, 4.0)), (0, 0));
+    let no_cells = CellGrid::new(geometry, (0.0, 0.0), 0, 0);
+    assert_eq!(no_cells.cell_at((4.0, 4.0)), None);
+    assert_eq!(no_cells.nearest_cell((4.0, 4.0)), (0, 0));
+}
@@ -0,0 +1,62 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::char_width::{is_ambiguous_width, UnicodeVersion, WidthProvider};
+use test_log::test;
+
+#[test]
+fn test_char_widths() {
+    let widths = WidthProvider::default();
+    assert_eq!(widths.char_width('a'), 1);
+    assert_eq!(widths.char_width('日'), 2);
+    assert_eq!(widths.char_width('한'), 2);
+    assert_eq!(widths.char_width('👍'), 2);
+    assert_eq!(widths.char_width('\u{200B}'), 0);
+    // Ambiguous width characters are narrow unless asked otherwise
+    assert_eq!(widths.char_width('─'), 1);
+    assert_eq!(widths.char_width('①'), 1);
+}
+
+#[test]
+fn test_unicode_versions() {
+    assert_eq!(
+        "8".parse::<UnicodeVersion>().unwrap(),
+        UnicodeVersion::Unicode8
+    );
+    assert_eq!(
+        "9".parse::<UnicodeVersion>().unwrap(),
+        UnicodeVersion::Unicode9
+    );
+    assert_eq!(
+        "15.1".parse::<UnicodeVersion>().unwrap(),
+        UnicodeVersion::Unicode9
+    );
+    assert!("latest".parse::<UnicodeVersion>().is_err());
+
+    let widths = WidthProvider::new(UnicodeVersion::Unicode8, false);
+    assert_eq!(widths.char_width('👍'), 1);
+    assert_eq!(widths.char_width('日'), 2);
+    assert_eq!(widths.grapheme_width("❤\u{FE0F}"), 1);
+
+    let widths = WidthProvider::new(UnicodeVersion::Unicode9, false);
+    assert_eq!(widths.grapheme_width("❤\u{FE0F}"), 2);
+}
+
+#[test]
+fn test_ambiguous_wide() {
+    let widths = WidthProvider::new(UnicodeVersion::Unicode9, true);
+    assert_eq!(widths.char_width('─'), 2);
+    assert_eq!(widths.char_width('①'), 2);
+    assert_eq!(widths.char_width('a'), 1);
+    assert_eq!(widths.char_width('日'), 2);
+}
+
+#[test]
+fn test_is_ambiguous_width() {
+    assert!(is_ambiguous_width('─'));
+    assert!(!is_ambiguous_width('a'));
+    // Always wide, so the font draws it wide too
+    assert!(!is_ambiguous_width('日'));
+}
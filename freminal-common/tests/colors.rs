@@ -4,7 +4,10 @@
 // https://opensource.org/licenses/MIT.
 use test_log::test;
 
-use freminal_common::colors::{cube_component, TerminalColor};
+use freminal_common::colors::{
+    cube_component, format_x11_color, parse_hex_color, parse_x11_color, Palette, TerminalColor,
+    Theme, PALETTE_COLORS, THEMES,
+};
 
 #[test]
 fn test_cube_component() {
@@ -451,3 +454,140 @@ fn default_to_regular() {
     let result = color.default_to_regular();
     assert_eq!(result, TerminalColor::Black);
 }
+
+#[test]
+fn test_palette_colors_map_to_themselves() {
+    for color in PALETTE_COLORS {
+        let (r, g, b) = color.palette_rgb().unwrap();
+        assert_eq!(TerminalColor::nearest_palette_color(r, g, b), color);
+    }
+}
+
+#[test]
+fn test_clamp_to_palette() {
+    assert_eq!(
+        TerminalColor::Custom(250, 10, 10).clamp_to_palette(),
+        TerminalColor::BrightRed
+    );
+    assert_eq!(
+        TerminalColor::Custom(120, 0, 0).clamp_to_palette(),
+        TerminalColor::Red
+    );
+    assert_eq!(
+        TerminalColor::Custom(140, 140, 140).clamp_to_palette(),
+        TerminalColor::BrightBlack
+    );
+    assert_eq!(
+        TerminalColor::Custom(10, 10, 10).clamp_to_palette(),
+        TerminalColor::Black
+    );
+
+    // Palette and default colors are left alone
+    assert_eq!(TerminalColor::Blue.clamp_to_palette(), TerminalColor::Blue);
+    assert_eq!(
+        TerminalColor::DefaultBackground.clamp_to_palette(),
+        TerminalColor::DefaultBackground
+    );
+    assert_eq!(TerminalColor::Default.palette_rgb(), None);
+}
+
+#[test]
+fn test_palette() {
+    let mut palette = Palette::default();
+    for color in PALETTE_COLORS {
+        assert_eq!(palette.rgb(color), color.palette_rgb());
+    }
+    assert_eq!(palette.rgb(TerminalColor::Default), None);
+    assert_eq!(palette.rgb(TerminalColor::DefaultBackground), None);
+    assert_eq!(palette.rgb(TerminalColor::Custom(1, 2, 3)), Some((1, 2, 3)));
+
+    palette.set(9, (10, 20, 30));
+    assert_eq!(palette.rgb(TerminalColor::BrightRed), Some((10, 20, 30)));
+    assert_eq!(palette.get(9), Some((10, 20, 30)));
+    assert_eq!(
+        palette.rgb(TerminalColor::Red),
+        TerminalColor::Red.palette_rgb()
+    );
+
+    // Past the end of the palette
+    palette.set(16, (1, 1, 1));
+    assert_eq!(palette.get(16), None);
+}
+
+#[test]
+fn test_themes() {
+    for theme in THEMES {
+        assert_eq!(theme.to_string().parse::<Theme>().unwrap(), theme);
+        assert_eq!(Palette::new(theme).get(15), theme.colors().get(15).copied());
+    }
+    assert!("monokai".parse::<Theme>().is_err());
+    assert_eq!(Theme::default(), Theme::Xterm);
+
+    let mut palette = Palette::new(Theme::Dracula);
+    assert_eq!(palette.rgb(TerminalColor::Red), Some((0xff, 0x55, 0x55)));
+    assert_eq!(
+        palette.rgb(TerminalColor::Default),
+        Some((0xf8, 0xf8, 0xf2))
+    );
+    assert_eq!(
+        palette.rgb(TerminalColor::DefaultBackground),
+        Some((0x28, 0x2a, 0x36))
+    );
+
+    // Colors the user picks are kept when the theme changes, until they are reset
+    palette.set(1, (1, 2, 3));
+    palette.set_background(Some((4, 5, 6)));
+    palette.set_theme(Theme::Gruvbox);
+    assert_eq!(palette.rgb(TerminalColor::Red), Some((1, 2, 3)));
+    assert_eq!(palette.rgb(TerminalColor::Green), Some((0x98, 0x97, 0x1a)));
+    assert_eq!(palette.background(), Some((4, 5, 6)));
+    assert_eq!(palette.foreground(), Theme::Gruvbox.foreground());
+
+    let mut copy = Palette::new(Theme::Gruvbox);
+    copy.copy_entry(1, &palette);
+    copy.copy_entry(2, &palette);
+    assert_eq!(copy.get(1), Some((1, 2, 3)));
+    assert_eq!(copy.get(2), Some((0x98, 0x97, 0x1a)));
+
+    palette.reset(1);
+    palette.set_background(None);
+    assert_eq!(palette.rgb(TerminalColor::Red), Some((0xcc, 0x24, 0x1d)));
+    assert_eq!(palette.background(), Theme::Gruvbox.background());
+}
+
+#[test]
+fn test_x11_colors() {
+    assert_eq!(parse_x11_color("rgb:ff/80/00"), Some((255, 128, 0)));
+    assert_eq!(parse_x11_color("rgb:ffff/8080/0000"), Some((255, 128, 0)));
+    assert_eq!(parse_x11_color("rgb:f/8/0"), Some((255, 136, 0)));
+    assert_eq!(parse_x11_color("#ff8000"), Some((255, 128, 0)));
+    assert_eq!(parse_x11_color("#f80"), Some((255, 136, 0)));
+    assert_eq!(parse_x11_color("#ffff80800000"), Some((255, 128, 0)));
+    for bad in [
+        "red",
+        "rgb:ff/80",
+        "rgb:ff/80/00/00",
+        "rgb:fffff/0/0",
+        "rgb:gg/0/0",
+        "rgb://",
+        "#ff80",
+        "#",
+    ] {
+        assert_eq!(parse_x11_color(bad), None, "{bad}");
+    }
+
+    assert_eq!(format_x11_color((255, 128, 0)), "rgb:ffff/8080/0000");
+    assert_eq!(
+        parse_x11_color(&format_x11_color((1, 2, 3))),
+        Some((1, 2, 3))
+    );
+}
+
+#[test]
+fn test_parse_hex_color() {
+    assert_eq!(parse_hex_color("#ff8000").unwrap(), (255, 128, 0));
+    assert_eq!(parse_hex_color("#FFFFFF").unwrap(), (255, 255, 255));
+    for bad in ["ff8000", "#ff80", "#ff80000", "#gg0000", "#ÿÿÿ", ""] {
+        assert!(parse_hex_color(bad).is_err(), "{bad}");
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::path::PathBuf;
+
+use freminal_common::{
+    args::{DEFAULT_SCROLLBACK_LINES, UNLIMITED_SCROLLBACK_LINES},
+    cell_geometry::{SizeLimits, TerminalGeometry, DEFAULT_MAX_GEOMETRY, DEFAULT_MIN_GEOMETRY},
+    colors::{Palette, Theme},
+    config::{Config, ConfigWatcher},
+    cursor::CursorVisualStyle,
+    font::{FontSettings, DEFAULT_FONT_SIZE},
+    text_classes::{UrlSchemes, WordCharacters},
+};
+use test_log::test;
+
+#[test]
+fn test_empty_config_is_default() {
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config, Config::default());
+    assert_eq!(config.scrollback_lines, DEFAULT_SCROLLBACK_LINES);
+    assert_eq!(config.alternate_scrollback_lines, 0);
+    assert_eq!(config.font, FontSettings::default());
+
+    let config: Config = "# nothing but a comment\n\n   \n".parse().unwrap();
+    assert_eq!(config, Config::default());
+}
+
+#[test]
+fn test_full_config() {
+    let config: Config = r##"
+# freminal config
+shell = "/usr/bin/fish"   # the shell
+shell_args = [
+    "--private",
+    'C:\literal',  # trailing comma and comment
+]
+login = true
+scrollback_lines = 10_000
+alternate_scrollback_lines = 200
+cursor_style = "blinking-bar"
+
+[font]
+family = "JetBrains Mono"
+size = 13.5
+
+[colors]
+palette = ["#282828", "#CC241D"]
+theme = "solarized-dark"
+background = "#000000"
+
+[presentation]
+font_scale = 2
+show_keys = false
+"##
+    .parse()
+    .unwrap();
+
+    assert_eq!(config.shell.as_deref(), Some("/usr/bin/fish"));
+    assert_eq!(config.shell_args, ["--private", "C:\\literal"]);
+    assert!(config.login_shell);
+    assert_eq!(config.scrollback_lines, 10_000);
+    assert_eq!(config.alternate_scrollback_lines, 200);
+    assert_eq!(
+        config.cursor_style,
+        CursorVisualStyle::VerticalLineCursorBlink
+    );
+    assert_eq!(config.font.family.as_deref(), Some("JetBrains Mono"));
+    assert!((config.font.size - 13.5).abs() < f32::EPSILON);
+
+    // The palette colors are kept, even though the theme comes after them
+    let theme = Palette::new(Theme::SolarizedDark);
+    assert_eq!(config.palette.theme(), Theme::SolarizedDark);
+    assert_eq!(config.palette.get(0), Some((0x28, 0x28, 0x28)));
+    assert_eq!(config.palette.get(1), Some((0xcc, 0x24, 0x1d)));
+    assert_eq!(config.palette.get(2), theme.get(2));
+    assert_eq!(config.palette.foreground(), theme.foreground());
+    assert_eq!(config.palette.background(), Some((0, 0, 0)));
+
+    assert!((config.presentation.font_scale - 2.0).abs() < f32::EPSILON);
+    assert!(!config.presentation.show_keys);
+}
+
+#[test]
+fn test_toml_syntax() {
+    // Dotted keys, inline tables and multi-line strings are all TOML
+    let config: Config = r#"
+font.family = "Fira Code"
+presentation = { font_scale = 3, show_keys = true }
+shell = """
+/bin/zsh"""
+"#
+    .parse()
+    .unwrap();
+    assert_eq!(config.font.family.as_deref(), Some("Fira Code"));
+    assert!((config.presentation.font_scale - 3.0).abs() < f32::EPSILON);
+    assert!(config.presentation.show_keys);
+    assert_eq!(config.shell.as_deref(), Some("/bin/zsh"));
+}
+
+#[test]
+fn test_config_strings() {
+    let config: Config = r#"shell = "say \"hi\"\t\u00e9""#.parse().unwrap();
+    assert_eq!(config.shell.as_deref(), Some("say \"hi\"\té"));
+
+    let config: Config = "[font]\nsize = 20".parse().unwrap();
+    assert!((config.font.size - 20.0).abs() < f32::EPSILON);
+    assert!((Config::default().font.size - DEFAULT_FONT_SIZE).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_unlimited_scrollback() {
+    let config: Config = "scrollback_lines = \"unlimited\"".parse().unwrap();
+    assert_eq!(config.scrollback_lines, UNLIMITED_SCROLLBACK_LINES);
+}
+
+#[test]
+fn test_unknown_settings_are_ignored() {
+    let config: Config = "colour = \"red\"\n[window]\nopacity = 0.5\n"
+        .parse()
+        .unwrap();
+    assert_eq!(config, Config::default());
+}
+
+#[test]
+fn test_invalid_config() {
+    for (text, message) in [
+        ("shell = fish", "line 1"),
+        ("shell = 3", "expected a string"),
+        ("\n\nscrollback_lines = 0", "line 3"),
+        ("scrollback_lines = -5", "at least 1"),
+        ("scrollback_lines = 1.5", "expected a number of lines"),
+        ("scrollback_lines = \"lots\"", "expected a number of lines"),
+        ("alternate_scrollback_lines = -1", "line 1"),
+        ("cursor_style = \"blob\"", "line 1"),
+        ("login = \"yes\"", "expected a boolean"),
+        ("[font]\nsize = 500", "from 1 to 100"),
+        ("[colors]\npalette = [\"red\"]", "#rrggbb"),
+        ("[colors]\npalette = [\"#12345\"]", "#rrggbb"),
+        ("[colors]\ntheme = \"neon\"", "Unknown theme neon"),
+        ("[colors]\nforeground = \"white\"", "#rrggbb"),
+        ("[presentation]\nfont_scale = 0.5", "from 1 to 10"),
+        ("[presentation]\nshow_keys = 1", "expected a boolean"),
+        ("[redaction]\npatterns = [\"(open\"]", "line 2"),
+        ("[size]\nmin = \"0x5\"", "line 2"),
+        ("[size]\nmax = 500", "expected a string"),
+        (
+            "[size]\nmin = \"100x5\"\nmax = \"80x24\"",
+            "bigger than the largest",
+        ),
+        ("[size]\nmax = \"10x2\"", "bigger than the largest"),
+        ("[redaction]\npatterns = [\"x*\"]", "matches empty text"),
+    ] {
+        let error = text.parse::<Config>().unwrap_err().to_string();
+        assert!(error.contains(message), "{text:?} gave {error:?}");
+    }
+
+    let too_many = format!(
+        "[colors]\npalette = [{}]",
+        vec!["\"#000000\""; 17].join(", ")
+    );
+    assert!(too_many.parse::<Config>().is_err());
+}
+
+#[test]
+fn test_size_limits() {
+    assert_eq!(Config::default().size_limits, SizeLimits::default());
+
+    let config: Config = "[size]\nmax = \"300x100\"\nmin = \"40x10\"\n"
+        .parse()
+        .unwrap();
+    let limits = config.size_limits;
+    assert_eq!(limits.min, TerminalGeometry::new(40, 10));
+    assert_eq!(limits.max, TerminalGeometry::new(300, 100));
+    assert_eq!(limits.clamp(1, 1), (40, 10));
+    assert_eq!(limits.clamp(10_000, 10_000), (300, 100));
+    assert_eq!(limits.clamp(120, 0), (120, 10));
+
+    let limits = SizeLimits::default();
+    assert_eq!(
+        limits.clamp(0, 0),
+        (DEFAULT_MIN_GEOMETRY.columns, DEFAULT_MIN_GEOMETRY.rows)
+    );
+    assert_eq!(
+        limits.clamp(usize::MAX, usize::MAX),
+        (DEFAULT_MAX_GEOMETRY.columns, DEFAULT_MAX_GEOMETRY.rows)
+    );
+    assert!(SizeLimits::new(TerminalGeometry::new(2, 2), TerminalGeometry::new(1, 3)).is_err());
+}
+
+#[test]
+fn test_load_config() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let missing = dir.path().join("config.toml");
+    assert_eq!(Config::load(&missing).unwrap(), Config::default());
+
+    std::fs::write(&missing, "scrollback_lines = 42\n").unwrap();
+    assert_eq!(Config::load(&missing).unwrap().scrollback_lines, 42);
+
+    std::fs::write(&missing, "scrollback_lines = \n").unwrap();
+    assert!(Config::load(&missing).is_err());
+}
+
+#[test]
+fn test_config_path_for_args() {
+    let args = ["freminal", "--bidi", "--config", "my.toml"].map(str::to_string);
+    assert_eq!(Config::path_for_args(&args), Some(PathBuf::from("my.toml")));
+
+    let args = ["freminal", "--config"].map(str::to_string);
+    assert_eq!(Config::path_for_args(&args), Config::default_path());
+    assert_eq!(Config::path_for_args(&[]), Config::default_path());
+}
+
+#[test]
+fn test_config_watcher() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+
+    let mut watcher = ConfigWatcher::new(path.clone());
+    assert_eq!(watcher.path(), path);
+    assert!(!watcher.changed());
+
+    std::fs::write(&path, "scrollback_lines = 10\n").unwrap();
+    assert!(watcher.changed());
+    assert!(!watcher.changed());
+
+    // Make sure the new modified time is different, whatever the filesystem's resolution
+    let file = std::fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap();
+    assert!(watcher.changed());
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(watcher.changed());
+    assert!(!watcher.changed());
+}
+
+#[test]
+fn test_word_characters_and_url_schemes() {
+    let config = Config::default();
+    assert_eq!(config.word_characters, WordCharacters::default());
+    assert_eq!(config.url_schemes, UrlSchemes::default());
+
+    let config: Config =
+        "[selection]\nword_characters = \"-_:\"\n[links]\nurl_schemes = [\"https\", \"Gopher\"]"
+            .parse()
+            .unwrap();
+    assert_eq!(config.word_characters.extra(), "-_:");
+    assert_eq!(config.url_schemes.schemes(), ["https", "gopher"]);
+}
+
+#[test]
+fn test_redaction_patterns() {
+    let config: Config = "[redaction]\npatterns = ['ghp_[A-Za-z0-9]{36}', \"AKIA[0-9A-Z]{16}\"]"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        config.redact_patterns,
+        ["ghp_[A-Za-z0-9]{36}", "AKIA[0-9A-Z]{16}"]
+    );
+}
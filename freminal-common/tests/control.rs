@@ -0,0 +1,300 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::control::{
+    ControlCommand, ControlRequest, ControlResponse, INVALID_PARAMS, METHOD_NOT_FOUND, PARSE_ERROR,
+};
+use serde_json::{json, Value};
+use test_log::test;
+
+#[test]
+fn test_parse_requests() {
+    let request = ControlRequest::parse(
+        r#"{"jsonrpc":"2.0","id":1,"method":"paste_text","params":{"text":"ls\n"}}"#,
+    )
+    .unwrap();
+    assert_eq!(request.id, json!(1));
+    assert_eq!(
+        request.command,
+        ControlCommand::PasteText {
+            text: "ls\n".to_string()
+        }
+    );
+
+    let request =
+        ControlRequest::parse(r#"{"jsonrpc":"2.0","id":"a","method":"get_text"}"#).unwrap();
+    assert_eq!(request.id, json!("a"));
+    assert_eq!(request.command, ControlCommand::GetText);
+
+    let request =
+        ControlRequest::parse(r#"{"id":2,"method":"set_font_size","params":{"size":14}}"#).unwrap();
+    assert_eq!(request.jsonrpc, "2.0");
+    assert_eq!(request.command, ControlCommand::SetFontSize { size: 14.0 });
+}
+
+#[test]
+fn test_parse_bad_requests() {
+    let error = ControlRequest::parse("not json").unwrap_err();
+    assert_eq!(error.id, Value::Null);
+    assert_eq!(error.error.unwrap().code, PARSE_ERROR);
+
+    let error = ControlRequest::parse(r#"{"id":3,"method":"reboot"}"#).unwrap_err();
+    assert_eq!(error.id, json!(3));
+    assert_eq!(error.error.unwrap().code, METHOD_NOT_FOUND);
+
+    let error =
+        ControlRequest::parse(r#"{"id":4,"method":"set_font_size","params":{"size":"big"}}"#)
+            .unwrap_err();
+    assert_eq!(error.id, json!(4));
+    assert_eq!(error.error.unwrap().code, INVALID_PARAMS);
+}
+
+#[test]
+fn test_request_round_trip() {
+    let request = ControlRequest::new(
+        7,
+        ControlCommand::PasteText {
+            text: "echo hi".to_string(),
+        },
+    );
+    let line = serde_json::to_string(&request).unwrap();
+    assert_eq!(ControlRequest::parse(&line).unwrap(), request);
+
+    let request = ControlRequest::new(
+        8,
+        ControlCommand::SendKeys {
+            text: "\x03".to_string(),
+        },
+    );
+    let line = serde_json::to_string(&request).unwrap();
+    assert_eq!(ControlRequest::parse(&line).unwrap(), request);
+
+    let request = ControlRequest::new(9, ControlCommand::GetText);
+    let line = serde_json::to_string(&request).unwrap();
+    assert_eq!(ControlRequest::parse(&line).unwrap(), request);
+}
+
+#[test]
+fn test_response_lines() {
+    let response = ControlResponse::success(json!(1), json!({ "text": "hi" }));
+    assert_eq!(
+        response.to_line(),
+        "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"text\":\"hi\"}}\n"
+    );
+
+    let response = ControlResponse::error(json!(2), -32000, "nope");
+    assert_eq!(
+        response.to_line(),
+        "{\"jsonrpc\":\"2.0\",\"id\":2,\"error\":{\"code\":-32000,\"message\":\"nope\"}}\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_control_server_round_trip() {
+    use freminal_common::control::{ControlServer, SERVER_ERROR};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.sock");
+    let server = ControlServer::bind(&path, || ()).unwrap();
+
+    // Stand in for the GUI
+    let gui = std::thread::spawn(move || {
+        for _ in 0..2 {
+            let request = loop {
+                if let Some(request) = server.try_recv() {
+                    break request;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            };
+
+            match request.command {
+                ControlCommand::GetText => request.reply(Ok(json!({ "text": "hello" }))),
+                _ => request.reply(Err(anyhow::anyhow!("unsupported"))),
+            }
+        }
+
+        server
+    });
+
+    let mut stream = UnixStream::connect(&path).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    stream
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"get_text\"}\n")
+        .unwrap();
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let response: ControlResponse = serde_json::from_str(&line).unwrap();
+    assert_eq!(response.result, Some(json!({ "text": "hello" })));
+
+    stream
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"open_tab\"}\n")
+        .unwrap();
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    let response: ControlResponse = serde_json::from_str(&line).unwrap();
+    assert_eq!(response.id, json!(2));
+    assert_eq!(response.error.unwrap().code, SERVER_ERROR);
+
+    // The socket is cleaned up when the server goes away
+    drop(gui.join().unwrap());
+    assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_control_server_in_use() {
+    use freminal_common::control::{send_request, ControlServer};
+    use std::os::unix::net::UnixListener;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.sock");
+
+    // A socket left behind by a run that has gone is replaced
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+    let server = ControlServer::bind(&path, || ()).unwrap();
+
+    // But one that is still being listened on is left alone
+    assert!(ControlServer::bind(&path, || ()).is_err());
+    let gui = std::thread::spawn(move || loop {
+        if let Some(request) = server.try_recv() {
+            request.reply(Ok(json!("still here")));
+            break server;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    });
+    assert_eq!(
+        send_request(&path, ControlCommand::GetText).unwrap(),
+        json!("still here")
+    );
+    drop(gui.join().unwrap());
+
+    // Neither the socket nor the directory it was made in are left behind
+    assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_send_request() {
+    use freminal_common::control::{send_request, ControlServer};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.sock");
+    let server = ControlServer::bind(&path, || ()).unwrap();
+
+    let gui = std::thread::spawn(move || {
+        for _ in 0..2 {
+            let request = loop {
+                if let Some(request) = server.try_recv() {
+                    break request;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            };
+
+            match request.command {
+                ControlCommand::GetText => request.reply(Ok(json!({ "text": "hello" }))),
+                _ => request.reply(Err(anyhow::anyhow!("unsupported"))),
+            }
+        }
+    });
+
+    assert_eq!(
+        send_request(&path, ControlCommand::GetText).unwrap(),
+        json!({ "text": "hello" })
+    );
+    let error = send_request(&path, ControlCommand::OpenTab).unwrap_err();
+    assert!(error.to_string().contains("unsupported"));
+
+    gui.join().unwrap();
+
+    // Nobody listening any more
+    assert!(send_request(&path, ControlCommand::GetText).is_err());
+}
+
+#[test]
+fn test_read_line_limited() {
+    use freminal_common::control::read_line_limited;
+
+    let mut reader = std::io::Cursor::new(b"short\nfour\r\nmuch too long\nend".to_vec());
+    assert_eq!(
+        read_line_limited(&mut reader, 5).unwrap().as_deref(),
+        Some("short")
+    );
+    assert_eq!(
+        read_line_limited(&mut reader, 5).unwrap().as_deref(),
+        Some("four")
+    );
+    let error = read_line_limited(&mut reader, 5).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    let mut reader = std::io::Cursor::new(b"end".to_vec());
+    assert_eq!(
+        read_line_limited(&mut reader, 5).unwrap().as_deref(),
+        Some("end")
+    );
+    assert_eq!(read_line_limited(&mut reader, 5).unwrap(), None);
+
+    let mut reader = std::io::Cursor::new(b"\xff\xfe\n".to_vec());
+    assert!(read_line_limited(&mut reader, 5).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_control_server_limits() {
+    use freminal_common::control::{
+        ControlServer, INVALID_REQUEST, MAX_CONTROL_CONNECTIONS, MAX_CONTROL_LINE_LENGTH,
+        SERVER_ERROR,
+    };
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        os::unix::{fs::PermissionsExt, net::UnixStream},
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.sock");
+    let _server = ControlServer::bind(&path, || ()).unwrap();
+
+    // Only we can connect, whatever the umask
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // A request that never ends is cut off
+    let mut stream = UnixStream::connect(&path).unwrap();
+    stream
+        .write_all(&vec![b'x'; MAX_CONTROL_LINE_LENGTH + 1])
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let response: ControlResponse = serde_json::from_str(&line).unwrap();
+    assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+    line.clear();
+    assert_eq!(reader.read_line(&mut line).unwrap(), 0, "closed");
+    drop(stream);
+
+    // Connections past the limit are turned away. The ones above may still be closing, so wait
+    // for them to be let go
+    let open: Vec<_> = (0..MAX_CONTROL_CONNECTIONS)
+        .map(|_| UnixStream::connect(&path).unwrap())
+        .collect();
+    let turned_away = (0..100).find_map(|_| {
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_millis(50)))
+            .unwrap();
+        let mut text = String::new();
+        stream.read_to_string(&mut text).ok()?;
+        (!text.is_empty()).then_some(text)
+    });
+    let response: ControlResponse = serde_json::from_str(&turned_away.unwrap()).unwrap();
+    assert_eq!(response.error.unwrap().code, SERVER_ERROR);
+    drop(open);
+}
@@ -1,4 +1,4 @@
-use freminal_common::cursor::CursorVisualStyle;
+use freminal_common::cursor::{CursorSettings, CursorShape, CursorVisualStyle};
 // Copyright (C) 2024-2025 Fred Clausen
 // Use of this source code is governed by an MIT-style
 // license that can be found in the LICENSE file or at
@@ -25,3 +25,62 @@ fn test_cursor_visual_style_from_usize() {
     let cursor = CursorVisualStyle::from(7);
     assert_eq!(cursor, CursorVisualStyle::BlockCursorBlink);
 }
+
+#[test]
+fn test_cursor_visual_style_shape() {
+    assert_eq!(
+        CursorVisualStyle::BlockCursorBlink.shape(),
+        CursorShape::Block
+    );
+    assert_eq!(
+        CursorVisualStyle::UnderlineCursorSteady.shape(),
+        CursorShape::Underline
+    );
+    assert_eq!(
+        CursorVisualStyle::VerticalLineCursorBlink.shape(),
+        CursorShape::VerticalLine
+    );
+
+    assert!(CursorVisualStyle::BlockCursorBlink.is_blinking());
+    assert!(!CursorVisualStyle::BlockCursorSteady.is_blinking());
+    assert!(CursorVisualStyle::VerticalLineCursorBlink.is_blinking());
+    assert!(!CursorVisualStyle::UnderlineCursorSteady.is_blinking());
+}
+
+#[test]
+fn test_cursor_blink() {
+    let settings = CursorSettings::default();
+    assert!(settings.is_visible_at(true, 0.1));
+    assert!(!settings.is_visible_at(true, 0.6));
+    assert!(settings.is_visible_at(true, 1.1));
+    assert!(settings.is_visible_at(false, 0.6));
+
+    let settings = CursorSettings {
+        blink: false,
+        ..CursorSettings::default()
+    };
+    assert!(settings.is_visible_at(true, 0.6));
+}
+
+#[test]
+fn test_cursor_visual_style_from_str() {
+    assert_eq!(
+        "block".parse::<CursorVisualStyle>().unwrap(),
+        CursorVisualStyle::BlockCursorSteady
+    );
+    assert_eq!(
+        "Blinking-Underline".parse::<CursorVisualStyle>().unwrap(),
+        CursorVisualStyle::UnderlineCursorBlink
+    );
+    assert_eq!(
+        " bar ".parse::<CursorVisualStyle>().unwrap(),
+        CursorVisualStyle::VerticalLineCursorSteady
+    );
+    assert!("blinking-".parse::<CursorVisualStyle>().is_err());
+    assert!("beam".parse::<CursorVisualStyle>().is_err());
+
+    assert_eq!(
+        CursorSettings::default().style,
+        CursorVisualStyle::BlockCursorSteady
+    );
+}
@@ -0,0 +1,45 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::{config::Config, links::LinkOpening};
+use test_log::test;
+
+#[test]
+fn test_link_opening_from_str() {
+    assert_eq!("click".parse::<LinkOpening>().unwrap(), LinkOpening::Click);
+    assert_eq!(
+        " Ctrl-Click ".parse::<LinkOpening>().unwrap(),
+        LinkOpening::CtrlClick
+    );
+    assert_eq!(
+        "confirm".parse::<LinkOpening>().unwrap(),
+        LinkOpening::Confirm
+    );
+    assert!("double-click".parse::<LinkOpening>().is_err());
+}
+
+#[test]
+fn test_link_opening_clicks() {
+    assert!(LinkOpening::Click.click_opens(false));
+    assert!(LinkOpening::Click.click_opens(true));
+    assert!(!LinkOpening::CtrlClick.click_opens(false));
+    assert!(LinkOpening::CtrlClick.click_opens(true));
+    assert!(LinkOpening::Confirm.click_opens(false));
+
+    assert!(!LinkOpening::Click.asks_first());
+    assert!(!LinkOpening::CtrlClick.asks_first());
+    assert!(LinkOpening::Confirm.asks_first());
+}
+
+#[test]
+fn test_link_opening_config() {
+    assert_eq!(Config::default().link_opening, LinkOpening::Click);
+
+    let config: Config = "[links]\nopen = \"ctrl-click\"\n".parse().unwrap();
+    assert_eq!(config.link_opening, LinkOpening::CtrlClick);
+
+    assert!("[links]\nopen = \"never\"\n".parse::<Config>().is_err());
+    assert!("[links]\nopen = true\n".parse::<Config>().is_err());
+}
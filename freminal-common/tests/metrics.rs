@@ -0,0 +1,139 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::{Duration, Instant};
+
+use freminal_common::metrics::{
+    InputLatency, Metrics, PercentileStats, SharedInputLatency, TimingStats, LATENCY_PROBE_TIMEOUT,
+    PERCENTILE_SAMPLES,
+};
+use test_log::test;
+
+#[test]
+fn test_timing_stats_record() {
+    let mut stats = TimingStats::new();
+    assert_eq!(stats.samples(), 0);
+
+    stats.record(Duration::from_millis(8));
+    assert_eq!(stats.last(), Duration::from_millis(8));
+    assert_eq!(stats.average(), Duration::from_millis(8));
+    assert_eq!(stats.max(), Duration::from_millis(8));
+
+    stats.record(Duration::from_millis(16));
+    assert_eq!(stats.last(), Duration::from_millis(16));
+    assert_eq!(stats.average(), Duration::from_millis(9));
+    assert_eq!(stats.max(), Duration::from_millis(16));
+
+    stats.record(Duration::from_millis(1));
+    assert_eq!(stats.max(), Duration::from_millis(16));
+    assert_eq!(stats.samples(), 3);
+
+    stats.reset();
+    assert_eq!(stats, TimingStats::new());
+}
+
+#[test]
+fn test_timing_stats_display() {
+    let mut stats = TimingStats::new();
+    stats.record(Duration::from_millis(2));
+    assert_eq!(stats.to_string(), "last 2.00ms avg 2.00ms max 2.00ms");
+}
+
+#[test]
+fn test_metrics_enable_resets() {
+    let mut metrics = Metrics::new();
+    metrics.frame_time.record(Duration::from_millis(5));
+    metrics.bytes_processed = 100;
+
+    metrics.set_enabled(true);
+    assert!(metrics.enabled);
+    assert_eq!(metrics.frame_time.samples(), 0);
+    assert_eq!(metrics.bytes_processed, 0);
+
+    metrics.bytes_processed = 50;
+    metrics.set_enabled(true);
+    assert_eq!(metrics.bytes_processed, 50);
+
+    metrics.set_enabled(false);
+    assert!(!metrics.enabled);
+}
+
+#[test]
+fn test_percentile_stats() {
+    let mut stats = PercentileStats::new();
+    assert_eq!(stats.percentile(50), None);
+    assert_eq!(stats.to_string(), "no samples");
+
+    for ms in (1..=100).rev() {
+        stats.record(Duration::from_millis(ms));
+    }
+    assert_eq!(stats.percentile(50), Some(Duration::from_millis(50)));
+    assert_eq!(stats.percentile(95), Some(Duration::from_millis(95)));
+    assert_eq!(stats.percentile(100), Some(Duration::from_millis(100)));
+
+    // Only the most recent samples count
+    for _ in 0..PERCENTILE_SAMPLES {
+        stats.record(Duration::from_millis(2));
+    }
+    assert_eq!(stats.samples(), PERCENTILE_SAMPLES);
+    assert_eq!(stats.percentile(95), Some(Duration::from_millis(2)));
+}
+
+#[test]
+fn test_input_latency() {
+    let start = Instant::now();
+    let mut latency = InputLatency::new();
+
+    // Nothing is timed until it is turned on
+    latency.key_pressed(start);
+    latency.written(start);
+    assert_eq!(latency.to_write.samples(), 0);
+
+    latency.set_enabled(true);
+    latency.key_pressed(start);
+    // Output before the key press was written isn't its echo
+    latency.output_received(start + Duration::from_millis(1));
+    latency.written(start + Duration::from_millis(2));
+    // A second key press while the first is on its way isn't timed
+    latency.key_pressed(start + Duration::from_millis(3));
+    latency.output_received(start + Duration::from_millis(5));
+    latency.frame_presented(start + Duration::from_millis(9));
+
+    assert_eq!(
+        latency.to_write.percentile(50),
+        Some(Duration::from_millis(2))
+    );
+    assert_eq!(
+        latency.to_echo.percentile(50),
+        Some(Duration::from_millis(5))
+    );
+    assert_eq!(
+        latency.to_present.percentile(50),
+        Some(Duration::from_millis(9))
+    );
+
+    // A key press that is never echoed is given up on
+    latency.key_pressed(start + Duration::from_millis(10));
+    latency.written(start + Duration::from_millis(11));
+    let later = start + Duration::from_millis(10) + LATENCY_PROBE_TIMEOUT;
+    latency.key_pressed(later);
+    latency.written(later + Duration::from_millis(1));
+    assert_eq!(latency.to_write.samples(), 3);
+}
+
+#[test]
+fn test_shared_input_latency_enabled() {
+    let shared = SharedInputLatency::new();
+    let other = shared.clone();
+    assert!(!other.enabled());
+
+    shared.set_enabled(true);
+    assert!(other.enabled());
+    assert!(other.lock().enabled);
+
+    shared.set_enabled(false);
+    assert!(!other.enabled());
+    assert!(!other.lock().enabled);
+}
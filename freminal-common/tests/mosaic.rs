@@ -0,0 +1,82 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::mosaic::{mosaic_quadrants, MosaicRun, MosaicRuns};
+use test_log::test;
+
+#[test]
+fn test_mosaic_quadrants() {
+    assert_eq!(mosaic_quadrants('▀'), Some([true, true, false, false]));
+    assert_eq!(mosaic_quadrants('▄'), Some([false, false, true, true]));
+    assert_eq!(mosaic_quadrants('▌'), Some([true, false, true, false]));
+    assert_eq!(mosaic_quadrants('▐'), Some([false, true, false, true]));
+    assert_eq!(mosaic_quadrants('█'), Some([true; 4]));
+    assert_eq!(mosaic_quadrants('▚'), Some([true, false, false, true]));
+    assert_eq!(mosaic_quadrants('▟'), Some([false, true, true, true]));
+
+    // Eighth blocks and shades aren't made of quarters
+    assert_eq!(mosaic_quadrants('▁'), None);
+    assert_eq!(mosaic_quadrants('░'), None);
+    assert_eq!(mosaic_quadrants('─'), None);
+    assert_eq!(mosaic_quadrants('a'), None);
+}
+
+#[test]
+fn test_mosaic_runs_join_matching_halves() {
+    let mut runs = MosaicRuns::default();
+    assert!(runs.is_empty());
+
+    // An upper half block picture row, the first two cells the same color
+    for (column, color) in [(0, 'r'), (1, 'r'), (2, 'g')] {
+        runs.push(0, column, 1, mosaic_quadrants('▀').unwrap(), &color);
+    }
+    // A lower half block on the next row carries on nothing from the row above
+    runs.push(1, 3, 1, mosaic_quadrants('▄').unwrap(), &'g');
+
+    let run = |row, lower, half_column, half_columns, color| MosaicRun {
+        row,
+        lower,
+        half_column,
+        half_columns,
+        color,
+    };
+    assert_eq!(
+        runs.into_runs(),
+        [
+            run(0, false, 0, 4, 'r'),
+            run(0, false, 4, 2, 'g'),
+            run(1, true, 6, 2, 'g'),
+        ]
+    );
+}
+
+#[test]
+fn test_mosaic_runs_keep_halves_apart() {
+    let mut runs = MosaicRuns::default();
+    // ▙ then ▟ fill the lower half of both cells, the upper left of the first only
+    runs.push(0, 0, 1, mosaic_quadrants('▙').unwrap(), &1);
+    runs.push(0, 1, 1, mosaic_quadrants('▟').unwrap(), &1);
+    // A gap breaks a run
+    runs.push(0, 3, 1, mosaic_quadrants('▄').unwrap(), &1);
+    // A double width cell's quarters are a cell wide each
+    runs.push(0, 5, 2, mosaic_quadrants('▐').unwrap(), &1);
+
+    let runs: Vec<_> = runs
+        .into_runs()
+        .into_iter()
+        .map(|run| (run.lower, run.half_column, run.half_columns))
+        .collect();
+    assert_eq!(
+        runs,
+        [
+            (false, 0, 1),
+            (true, 0, 4),
+            (false, 3, 1),
+            (true, 6, 2),
+            (false, 12, 2),
+            (true, 12, 2),
+        ]
+    );
+}
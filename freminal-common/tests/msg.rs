@@ -0,0 +1,70 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::{
+    control::ControlCommand,
+    msg::{unescape_keys, MsgArgs},
+};
+use test_log::test;
+
+fn parse(args: &[&str]) -> anyhow::Result<MsgArgs> {
+    MsgArgs::parse(args.iter().map(ToString::to_string))
+}
+
+#[test]
+fn test_parse_msg_commands() {
+    let msg = parse(&["get-text"]).unwrap();
+    assert_eq!(msg.socket, None);
+    assert_eq!(msg.command, ControlCommand::GetText);
+
+    let msg = parse(&["--to", "/tmp/freminal.sock", "send-keys", "ls\\r"]).unwrap();
+    assert_eq!(msg.socket.as_deref(), Some("/tmp/freminal.sock"));
+    assert_eq!(
+        msg.command,
+        ControlCommand::SendKeys {
+            text: "ls\r".to_string()
+        }
+    );
+
+    let msg = parse(&["paste", "a\\nb"]).unwrap();
+    assert_eq!(
+        msg.command,
+        ControlCommand::PasteText {
+            text: "a\\nb".to_string()
+        }
+    );
+
+    let msg = parse(&["set-font-size", "16.5"]).unwrap();
+    assert_eq!(msg.command, ControlCommand::SetFontSize { size: 16.5 });
+}
+
+#[test]
+fn test_parse_bad_msg_commands() {
+    assert!(parse(&[]).is_err());
+    assert!(parse(&["--to"]).is_err());
+    assert!(parse(&["reboot"]).is_err());
+    assert!(parse(&["send-keys"]).is_err());
+    assert!(parse(&["set-font-size", "big"]).is_err());
+    assert!(parse(&["get-text", "extra"]).is_err());
+}
+
+#[test]
+fn test_socket_path_prefers_to() {
+    let msg = parse(&["--to", "/tmp/a.sock", "get-text"]).unwrap();
+    assert_eq!(msg.socket_path().unwrap(), "/tmp/a.sock");
+}
+
+#[test]
+fn test_unescape_keys() {
+    assert_eq!(unescape_keys("plain").unwrap(), "plain");
+    assert_eq!(unescape_keys("a\\r\\n\\t").unwrap(), "a\r\n\t");
+    assert_eq!(unescape_keys("\\e[A\\\\").unwrap(), "\x1b[A\\");
+    assert_eq!(unescape_keys("\\x03").unwrap(), "\x03");
+
+    assert!(unescape_keys("\\q").is_err());
+    assert!(unescape_keys("oops\\").is_err());
+    assert!(unescape_keys("\\x3").is_err());
+    assert!(unescape_keys("\\xff").is_err());
+}
@@ -0,0 +1,88 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::{Duration, Instant};
+
+use freminal_common::presentation::{
+    KeystrokeLog, PresentationMode, PresentationSettings, DEFAULT_PRESENTATION_FONT_SCALE,
+    KEYSTROKE_DISPLAY_TIME, MAX_KEYSTROKES,
+};
+use test_log::test;
+
+#[test]
+fn test_toggle_presentation_mode() {
+    let mut mode = PresentationMode::new(PresentationSettings::default());
+    assert!(!mode.is_active());
+    assert!(!mode.shows_keys());
+
+    let size = mode.toggle(12.0);
+    assert!((size - 12.0 * DEFAULT_PRESENTATION_FONT_SCALE).abs() < f32::EPSILON);
+    assert!(mode.is_active());
+    assert!(mode.shows_keys());
+
+    mode.keystrokes_mut().push_key("Enter", Instant::now());
+    assert!((mode.toggle(size) - 12.0).abs() < f32::EPSILON);
+    assert!(!mode.is_active());
+    assert!(mode.keystrokes().is_empty());
+}
+
+#[test]
+fn test_presentation_resize() {
+    let mut mode = PresentationMode::new(PresentationSettings {
+        font_scale: 2.0,
+        show_keys: false,
+    });
+    assert!((mode.resize(14.0) - 14.0).abs() < f32::EPSILON);
+
+    assert!((mode.toggle(14.0) - 28.0).abs() < f32::EPSILON);
+    assert!(!mode.shows_keys());
+
+    // A new normal size while presenting is scaled up, and is what turning it off goes back to
+    assert!((mode.resize(10.0) - 20.0).abs() < f32::EPSILON);
+    mode.set_settings(PresentationSettings {
+        font_scale: 3.0,
+        show_keys: true,
+    });
+    assert!((mode.resize(10.0) - 30.0).abs() < f32::EPSILON);
+    assert!((mode.toggle(30.0) - 10.0).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_keystroke_log() {
+    let start = Instant::now();
+    let mut log = KeystrokeLog::default();
+    assert_eq!(log.expire(start), None);
+
+    log.push_text("l", start);
+    log.push_text("s", start);
+    log.push_key("⏎ Enter", start);
+    log.push_text("c", start);
+    log.push_text("d", start);
+    assert_eq!(log.labels().collect::<Vec<_>>(), ["ls", "⏎ Enter", "cd"]);
+
+    // Long runs of text are split up
+    log.push_text(&"x".repeat(30), start);
+    assert_eq!(log.labels().count(), 4);
+
+    let later = start + Duration::from_millis(500);
+    log.push_key("Ctrl+C", later);
+    assert_eq!(
+        log.expire(later),
+        Some(KEYSTROKE_DISPLAY_TIME - Duration::from_millis(500))
+    );
+    assert_eq!(
+        log.expire(start + KEYSTROKE_DISPLAY_TIME),
+        Some(Duration::from_millis(500))
+    );
+    assert_eq!(log.labels().collect::<Vec<_>>(), ["Ctrl+C"]);
+
+    for _ in 0..MAX_KEYSTROKES + 3 {
+        log.push_key("Tab", later);
+    }
+    assert_eq!(log.labels().count(), MAX_KEYSTROKES);
+
+    log.clear();
+    assert!(log.is_empty());
+}
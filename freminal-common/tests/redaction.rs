@@ -0,0 +1,80 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::borrow::Cow;
+
+use freminal_common::redaction::{
+    validate_pattern, Redactor, StreamRedactor, MAX_SPLIT_SECRET_LENGTH,
+};
+use test_log::test;
+
+fn redactor(patterns: &[&str]) -> Redactor {
+    Redactor::new(&patterns.iter().map(ToString::to_string).collect::<Vec<_>>()).unwrap()
+}
+
+#[test]
+fn test_redact() {
+    let redactor = redactor(&["ghp_[A-Za-z0-9]{8}", "AKIA[0-9A-Z]{4}"]);
+
+    assert_eq!(
+        redactor.redact(b"token ghp_abcd1234 and AKIAQWER!"),
+        Cow::<[u8]>::Owned(b"token ************ and ********!".to_vec())
+    );
+    assert!(matches!(
+        redactor.redact(b"nothing to hide"),
+        Cow::Borrowed(b"nothing to hide")
+    ));
+}
+
+#[test]
+fn test_redact_keeps_width() {
+    // One star for each character, not each byte
+    let redactor = redactor(&["pässwörd"]);
+    assert_eq!(
+        redactor.redact("my pässwörd".as_bytes()).as_ref(),
+        b"my ********"
+    );
+}
+
+#[test]
+fn test_invalid_patterns() {
+    assert!(Redactor::new(&[]).is_err());
+    assert!(Redactor::new(&["(unclosed".to_string()]).is_err());
+    assert!(validate_pattern("a*").is_err());
+    assert!(validate_pattern("").is_err());
+    assert!(validate_pattern("secret").is_ok());
+}
+
+#[test]
+fn test_max_match_len() {
+    assert_eq!(redactor(&["ghp_[a-z]{8}", "AKIA"]).max_match_len(), 12);
+    assert_eq!(
+        redactor(&["token=\\S+"]).max_match_len(),
+        MAX_SPLIT_SECRET_LENGTH
+    );
+}
+
+#[test]
+fn test_stream_redactor() {
+    let mut stream = StreamRedactor::new(redactor(&["ghp_[a-z0-9]{6}"]));
+
+    // Only what can't be the start of a secret is passed on
+    assert_eq!(stream.push(b"token ghp_ab"), b"to");
+    assert_eq!(stream.push(b"c123 and more text"), b"ken ********** and");
+    // A color change part way through doesn't hide it
+    assert_eq!(
+        stream.push(b" ghp_\x1b[31mzzz999\x1b]0;title\x07 "),
+        b" more text "
+    );
+    assert_eq!(stream.finish(), b"****\x1b[31m******\x1b]0;title\x07 ");
+    assert_eq!(stream.finish(), b"");
+
+    // One star for each character, however many bytes it is
+    let mut stream = StreamRedactor::new(redactor(&["pässwörd"]));
+    let mut output = stream.push("my päss".as_bytes());
+    output.extend(stream.push("wörd!".as_bytes()));
+    output.extend(stream.finish());
+    assert_eq!(output, b"my ********!");
+}
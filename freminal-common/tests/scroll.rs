@@ -1,8 +1,10 @@
-use freminal_common::scroll::ScrollDirection;
 // Copyright (C) 2024-2025 Fred Clausen
 // Use of this source code is governed by an MIT-style
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
+use freminal_common::scroll::{
+    ScrollAccumulator, ScrollDirection, ScrollSettings, DEFAULT_LINES_PER_WHEEL_TICK,
+};
 use test_log::test;
 
 #[test]
@@ -10,3 +12,37 @@ fn test_scroll_default() {
     let scroll = ScrollDirection::default();
     assert_eq!(scroll, ScrollDirection::Up(1));
 }
+
+#[test]
+fn test_scroll_settings() {
+    let settings = ScrollSettings::default();
+    assert!((settings.lines_per_wheel_tick - DEFAULT_LINES_PER_WHEEL_TICK).abs() < f32::EPSILON);
+    assert!((settings.wheel_ticks_to_lines(2.0) - 6.0).abs() < f32::EPSILON);
+    assert!((settings.pixels_to_lines(-30.0, 15.0) + 2.0).abs() < f32::EPSILON);
+    assert!(settings.pixels_to_lines(30.0, 0.0).abs() < f32::EPSILON);
+
+    let settings = ScrollSettings {
+        lines_per_wheel_tick: 1.0,
+        invert: true,
+        smooth: false,
+    };
+    assert!((settings.wheel_ticks_to_lines(1.0) + 1.0).abs() < f32::EPSILON);
+    assert!((settings.pixels_to_lines(-30.0, 15.0) - 2.0).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_scroll_accumulator() {
+    let mut accumulator = ScrollAccumulator::new();
+
+    assert!(accumulator.accumulate(0.4).abs() < f32::EPSILON);
+    assert!(accumulator.accumulate(0.4).abs() < f32::EPSILON);
+    assert!((accumulator.accumulate(0.4) - 1.0).abs() < f32::EPSILON);
+    assert!((accumulator.pending() - 0.2).abs() < 0.001);
+
+    // Changing direction drops what was left over
+    assert!(accumulator.accumulate(-0.5).abs() < f32::EPSILON);
+    assert!((accumulator.accumulate(-2.6) + 3.0).abs() < f32::EPSILON);
+
+    accumulator.reset();
+    assert!(accumulator.pending().abs() < f32::EPSILON);
+}
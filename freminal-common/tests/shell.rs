@@ -0,0 +1,66 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::shell::ShellSettings;
+use test_log::test;
+
+fn default_shell() -> String {
+    "/bin/zsh".to_string()
+}
+
+#[test]
+fn test_default_shell_is_a_login_shell() {
+    let settings = ShellSettings::default();
+    assert_eq!(settings.command_line(default_shell), vec!["/bin/zsh", "-l"]);
+}
+
+#[test]
+fn test_configured_shell() {
+    let mut settings = ShellSettings {
+        program: Some("/usr/bin/fish".to_string()),
+        args: vec!["--private".to_string()],
+        login: false,
+    };
+    assert_eq!(
+        settings.command_line(default_shell),
+        vec!["/usr/bin/fish", "--private"]
+    );
+
+    settings.login = true;
+    assert_eq!(
+        settings.command_line(default_shell),
+        vec!["/usr/bin/fish", "-l", "--private"]
+    );
+}
+
+#[test]
+fn test_windows_shells_login_flags() {
+    let settings = ShellSettings {
+        program: Some("C:\\Windows\\System32\\cmd.exe".to_string()),
+        args: vec![],
+        login: true,
+    };
+    assert_eq!(
+        settings.command_line(default_shell),
+        vec!["C:\\Windows\\System32\\cmd.exe"]
+    );
+
+    let settings = ShellSettings {
+        program: None,
+        args: vec!["-NoLogo".to_string()],
+        login: false,
+    };
+    assert_eq!(
+        settings.command_line(|| "powershell.exe".to_string()),
+        vec!["powershell.exe", "-NoLogo"]
+    );
+
+    let settings = ShellSettings {
+        program: Some("pwsh".to_string()),
+        args: vec![],
+        login: true,
+    };
+    assert_eq!(settings.command_line(default_shell), vec!["pwsh", "-Login"]);
+}
@@ -0,0 +1,82 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::text_classes::{UrlSchemes, WordCharacters};
+use test_log::test;
+
+fn chars(s: &str) -> Vec<char> {
+    s.chars().collect()
+}
+
+#[test]
+fn test_word_range_default() {
+    let words = WordCharacters::default();
+    let line = chars("cargo build-all foo_bar");
+
+    assert_eq!(words.word_range(&line, 0), Some(0..5));
+    assert_eq!(words.word_range(&line, 4), Some(0..5));
+    assert_eq!(words.word_range(&line, 5), None);
+    assert_eq!(words.word_range(&line, 7), Some(6..11));
+    assert_eq!(words.word_range(&line, 13), Some(12..15));
+    assert_eq!(words.word_range(&line, 18), Some(16..23));
+    assert_eq!(words.word_range(&line, 100), None);
+}
+
+#[test]
+fn test_word_range_custom() {
+    let words = WordCharacters::new("-:/");
+    let line = chars("see build-all and host:/tmp");
+
+    assert_eq!(words.word_range(&line, 6), Some(4..13));
+    assert_eq!(words.word_range(&line, 20), Some(18..27));
+    assert!(!words.is_word_char('_'));
+}
+
+#[test]
+fn test_find_urls() {
+    let schemes = UrlSchemes::default();
+    let line = chars("see https://example.com/a, or (http://x.org/b_(c)).");
+
+    let urls = schemes.find_urls(&line);
+    let found: Vec<String> = urls
+        .iter()
+        .map(|range| line[range.clone()].iter().collect())
+        .collect();
+
+    assert_eq!(found, ["https://example.com/a", "http://x.org/b_(c)"]);
+}
+
+#[test]
+fn test_url_at() {
+    let schemes = UrlSchemes::default();
+    let line = chars("open HTTPS://example.com now");
+
+    assert_eq!(
+        schemes.url_at(&line, 10),
+        Some("HTTPS://example.com".to_string())
+    );
+    assert_eq!(schemes.url_at(&line, 0), None);
+    assert_eq!(schemes.url_at(&line, 25), None);
+}
+
+#[test]
+fn test_url_rejects_partial_matches() {
+    let schemes = UrlSchemes::default();
+
+    assert!(schemes.find_urls(&chars("xhttp://example.com")).is_empty());
+    assert!(schemes.find_urls(&chars("https:// nothing")).is_empty());
+    assert!(schemes.find_urls(&chars("https")).is_empty());
+}
+
+#[test]
+fn test_custom_url_schemes() {
+    let schemes = UrlSchemes::from_list(" gopher, , HTTPS ");
+    assert_eq!(schemes.schemes(), ["gopher", "https"]);
+
+    let line = chars("gopher://a.b http://c.d");
+    let urls = schemes.find_urls(&line);
+    assert_eq!(urls.len(), 1);
+    assert_eq!(urls[0], 0..12);
+}
@@ -0,0 +1,68 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use freminal_common::timestamps::{
+    format_time_of_day, format_time_of_day_with_offset, TimestampGutter, TIMESTAMP_GUTTER_COLUMNS,
+};
+use test_log::test;
+use time::UtcOffset;
+
+#[test]
+fn test_timestamp_gutter_parse() {
+    assert_eq!(
+        "off".parse::<TimestampGutter>().unwrap(),
+        TimestampGutter::Off
+    );
+    assert_eq!(
+        "Hover".parse::<TimestampGutter>().unwrap(),
+        TimestampGutter::Hover
+    );
+    assert_eq!(
+        " always ".parse::<TimestampGutter>().unwrap(),
+        TimestampGutter::Always
+    );
+    assert!("".parse::<TimestampGutter>().is_err());
+}
+
+#[test]
+fn test_timestamp_gutter_columns() {
+    assert_eq!(TimestampGutter::Off.columns(), 0);
+    assert_eq!(TimestampGutter::Hover.columns(), 0);
+    assert_eq!(TimestampGutter::Always.columns(), TIMESTAMP_GUTTER_COLUMNS);
+}
+
+#[test]
+fn test_format_time_of_day() {
+    assert_eq!(format_time_of_day(UNIX_EPOCH), "00:00:00");
+    // Two days, 13 hours, 4 minutes and 5 seconds in
+    let time = UNIX_EPOCH + Duration::from_secs(2 * 86_400 + 13 * 3600 + 4 * 60 + 5);
+    assert_eq!(format_time_of_day(time), "13:04:05");
+}
+
+#[test]
+fn test_format_time_of_day_with_offset() {
+    let time = UNIX_EPOCH + Duration::from_secs(13 * 3600 + 4 * 60 + 5);
+    let offset = |hours, minutes| UtcOffset::from_hms(hours, minutes, 0).unwrap();
+
+    assert_eq!(
+        format_time_of_day_with_offset(time, offset(0, 0)),
+        "13:04:05"
+    );
+    assert_eq!(
+        format_time_of_day_with_offset(time, offset(5, 30)),
+        "18:34:05"
+    );
+    // Back past midnight in to the day before
+    assert_eq!(
+        format_time_of_day_with_offset(time, offset(-14, 0)),
+        "23:04:05"
+    );
+    assert_eq!(
+        format_time_of_day_with_offset(UNIX_EPOCH, offset(-8, 0)),
+        "16:00:00"
+    );
+}
@@ -0,0 +1,34 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::title_format::TitleFormat;
+use test_log::test;
+
+#[test]
+fn test_default_title_format() {
+    let format = TitleFormat::default();
+    assert_eq!(format.render("vim", Some("/home/fred"), "1-0"), "vim");
+    assert_eq!(format.render("", None, "1-0"), "");
+}
+
+#[test]
+fn test_title_format_render() {
+    let format: TitleFormat = "{title} — {cwd} — freminal:{id}".parse().unwrap();
+    assert_eq!(
+        format.render("vim", Some("/home/fred"), "42-1"),
+        "vim — /home/fred — freminal:42-1"
+    );
+    assert_eq!(format.render("vim", None, "42-1"), "vim —  — freminal:42-1");
+
+    let format: TitleFormat = "{title} {cwd}".parse().unwrap();
+    assert_eq!(format.render("bash", None, "1-0"), "bash");
+}
+
+#[test]
+fn test_title_format_parse() {
+    assert!("".parse::<TitleFormat>().is_err());
+    assert!("   ".parse::<TitleFormat>().is_err());
+    assert!("freminal".parse::<TitleFormat>().is_ok());
+}
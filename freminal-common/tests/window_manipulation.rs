@@ -52,6 +52,18 @@ fn test_window_manipulation_try_from() {
         WindowManipulation::RestoreNonMaximizedWindow
     );
 
+    let window_manipulation = WindowManipulation::try_from((9, 2, 0)).unwrap();
+    assert_eq!(
+        window_manipulation,
+        WindowManipulation::MaximizeWindowVertically
+    );
+
+    let window_manipulation = WindowManipulation::try_from((9, 3, 0)).unwrap();
+    assert_eq!(
+        window_manipulation,
+        WindowManipulation::MaximizeWindowHorizontally
+    );
+
     let window_manipulation = WindowManipulation::try_from((10, 0, 0)).unwrap();
     assert_eq!(window_manipulation, WindowManipulation::NotFullScreen);
 
@@ -5,7 +5,10 @@
 
 use crate::{
     ansi_components::{
+        capture::{InvalidSequence, SequenceCapture},
         csi::AnsiCsiParser,
+        csi_commands::kitty_keyboard::KittyKeyboardRequest,
+        dcs::{AnsiDcsParser, ControlStringKind},
         line_draw::{DecSpecialGraphics, DecSpecialGraphicsParser},
         mode::Mode,
         osc::{AnsiOscParser, AnsiOscType},
@@ -15,9 +18,15 @@ use crate::{
 };
 
 use anyhow::Result;
-use freminal_common::{cursor::CursorVisualStyle, window_manipulation::WindowManipulation};
+use freminal_common::{
+    args::DEFAULT_MAX_SEQUENCE_LENGTH, cursor::CursorVisualStyle,
+    window_manipulation::WindowManipulation,
+};
 
+/// Something the terminal has been asked to do, as parsed from its output. Text is passed through
+/// as `Data`, and each escape sequence becomes the action it asks for
 #[derive(Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum TerminalOutput {
     SetCursorPos {
         x: Option<usize>,
@@ -36,6 +45,12 @@ pub enum TerminalOutput {
     ClearLineBackwards,
     ClearLine,
     Newline,
+    /// IND, moving down a line and scrolling at the bottom of the scrolling region
+    Index,
+    /// NEL, a carriage return and an index together
+    NextLine,
+    /// RI, moving up a line and scrolling the region down at its top
+    ReverseIndex,
     Backspace,
     Bell,
     ApplicationKeypadMode,
@@ -52,14 +67,30 @@ pub enum TerminalOutput {
     CursorReport,
     Invalid,
     Skipped,
+    /// A well formed sequence for something we don't support, named so it can be reported
+    Unsupported(String),
     DecSpecialGraphics(DecSpecialGraphics),
     CursorVisualStyle(CursorVisualStyle),
+    /// DECSCUSR 0, going back to the user's cursor style
+    DefaultCursorVisualStyle,
+    /// DECSTR, putting modes back to their defaults without clearing the screen
+    SoftReset,
     WindowManipulation(WindowManipulation),
     RequestDeviceAttributes,
+    /// DECRQSS, holding the setting being asked about (ie, `m` for SGR)
+    RequestStatusString(String),
     SetTopAndBottomMargins {
         top_margin: usize,
         bottom_margin: usize,
     },
+    /// XTSAVE, holding the numbers of the private modes to remember
+    SavePrivateModes(Vec<usize>),
+    /// XTRESTORE, holding the numbers of the private modes to put back
+    RestorePrivateModes(Vec<usize>),
+    /// XTMODKEYS for modifyOtherKeys, holding the level to use. 0 is off
+    ModifyOtherKeys(u8),
+    /// The kitty keyboard protocol's flags being changed or asked about
+    KittyKeyboard(KittyKeyboardRequest),
 }
 
 // impl format display for TerminalOutput
@@ -82,6 +113,9 @@ impl std::fmt::Display for TerminalOutput {
             Self::ClearLineBackwards => write!(f, "ClearLineBackwards"),
             Self::ClearLine => write!(f, "ClearLine"),
             Self::Newline => write!(f, "Newline"),
+            Self::Index => write!(f, "Index"),
+            Self::NextLine => write!(f, "NextLine"),
+            Self::ReverseIndex => write!(f, "ReverseIndex"),
             Self::Backspace => write!(f, "Backspace"),
             Self::Bell => write!(f, "Bell"),
             Self::InsertLines(n) => write!(f, "InsertLines({n})"),
@@ -100,11 +134,14 @@ impl std::fmt::Display for TerminalOutput {
             Self::Invalid => write!(f, "Invalid"),
             Self::CursorReport => write!(f, "CursorReport"),
             Self::Skipped => write!(f, "Skipped"),
+            Self::Unsupported(feature) => write!(f, "Unsupported({feature})"),
             Self::ApplicationKeypadMode => write!(f, "ApplicationKeypadMode"),
             Self::NormalKeypadMode => write!(f, "NormalKeypadMode"),
             Self::CursorVisualStyle(cursor_visual_style) => {
                 write!(f, "CursorVisualStyle({cursor_visual_style:?})")
             }
+            Self::DefaultCursorVisualStyle => write!(f, "DefaultCursorVisualStyle"),
+            Self::SoftReset => write!(f, "SoftReset"),
             Self::WindowManipulation(window_manipulation) => {
                 write!(f, "WindowManipulation({window_manipulation:?})")
             }
@@ -115,6 +152,11 @@ impl std::fmt::Display for TerminalOutput {
                 write!(f, "SetTopAndBottomMargins({top_margin}, {bottom_margin})")
             }
             Self::RequestDeviceAttributes => write!(f, "RequestDeviceAttributes"),
+            Self::RequestStatusString(setting) => write!(f, "RequestStatusString({setting})"),
+            Self::SavePrivateModes(modes) => write!(f, "SavePrivateModes({modes:?})"),
+            Self::RestorePrivateModes(modes) => write!(f, "RestorePrivateModes({modes:?})"),
+            Self::ModifyOtherKeys(level) => write!(f, "ModifyOtherKeys({level})"),
+            Self::KittyKeyboard(request) => write!(f, "KittyKeyboard({request:?})"),
         }
     }
 }
@@ -169,12 +211,21 @@ pub enum ParserInner {
     Escape,
     Csi(AnsiCsiParser),
     Osc(AnsiOscParser),
+    Dcs(AnsiDcsParser),
     DecSpecialGraphics(DecSpecialGraphicsParser),
 }
 
+/// Turns the bytes a program writes to the terminal in to `TerminalOutput`
+///
+/// The parser keeps track of partial sequences, so output can be pushed in however it arrives
+/// from the program, even if a read splits an escape sequence in two.
 #[derive(Debug, Eq, PartialEq)]
 pub struct FreminalAnsiParser {
-    pub inner: ParserInner,
+    inner: ParserInner,
+    /// Only set when invalid sequences are being collected
+    capture: Option<SequenceCapture>,
+    /// Longest OSC or DCS payload kept before the rest of it is dropped
+    max_sequence_length: usize,
 }
 
 impl Default for FreminalAnsiParser {
@@ -188,6 +239,52 @@ impl FreminalAnsiParser {
     pub const fn new() -> Self {
         Self {
             inner: ParserInner::Empty,
+            capture: None,
+            max_sequence_length: DEFAULT_MAX_SEQUENCE_LENGTH,
+        }
+    }
+
+    /// The state of the sequence being parsed. Not part of the stable `parser` API
+    #[must_use]
+    pub const fn inner(&self) -> &ParserInner {
+        &self.inner
+    }
+
+    /// Keep at most `max_sequence_length` bytes of an OSC or DCS payload. Anything past that is
+    /// still consumed, so it doesn't end up on screen, but the sequence is treated as invalid
+    pub const fn set_max_sequence_length(&mut self, max_sequence_length: usize) {
+        self.max_sequence_length = max_sequence_length;
+    }
+
+    /// Keep the raw bytes of sequences we can't handle, to be collected with
+    /// `take_invalid_sequences`
+    pub fn capture_invalid_sequences(&mut self) {
+        self.capture.get_or_insert_with(SequenceCapture::new);
+    }
+
+    /// The invalid sequences seen since the last call. Always empty unless
+    /// `capture_invalid_sequences` was called
+    pub fn take_invalid_sequences(&mut self) -> Vec<InvalidSequence> {
+        self.capture
+            .as_mut()
+            .map(SequenceCapture::take)
+            .unwrap_or_default()
+    }
+
+    fn capture_byte(&mut self, b: u8) {
+        if let Some(capture) = &mut self.capture {
+            let parsing_empty = matches!(self.inner, ParserInner::Empty);
+            capture.push(
+                b,
+                !parsing_empty || b == b'\x1b',
+                parsing_empty && b == b'\x1b',
+            );
+        }
+    }
+
+    fn capture_invalid_sequence(&mut self) {
+        if let Some(capture) = &mut self.capture {
+            capture.mark_invalid();
         }
     }
 
@@ -242,8 +339,13 @@ impl FreminalAnsiParser {
                 self.inner = ParserInner::Csi(AnsiCsiParser::new());
             }
             b']' => {
-                self.inner = ParserInner::Osc(AnsiOscParser::new());
+                self.inner =
+                    ParserInner::Osc(AnsiOscParser::with_max_length(self.max_sequence_length));
             }
+            b'P' => self.start_control_string(ControlStringKind::Dcs),
+            b'_' => self.start_control_string(ControlStringKind::Apc),
+            b'^' => self.start_control_string(ControlStringKind::Pm),
+            b'X' => self.start_control_string(ControlStringKind::Sos),
             b'(' => {
                 self.inner = ParserInner::DecSpecialGraphics(DecSpecialGraphicsParser::new());
             }
@@ -255,6 +357,18 @@ impl FreminalAnsiParser {
                 self.inner = ParserInner::Empty;
                 output.push(TerminalOutput::NormalKeypadMode);
             }
+            b'D' => {
+                self.inner = ParserInner::Empty;
+                output.push(TerminalOutput::Index);
+            }
+            b'E' => {
+                self.inner = ParserInner::Empty;
+                output.push(TerminalOutput::NextLine);
+            }
+            b'M' => {
+                self.inner = ParserInner::Empty;
+                output.push(TerminalOutput::ReverseIndex);
+            }
             _ => {
                 let char_decoded = b as char;
                 error!(
@@ -270,12 +384,22 @@ impl FreminalAnsiParser {
         Ok(())
     }
 
+    fn start_control_string(&mut self, kind: ControlStringKind) {
+        self.inner = ParserInner::Dcs(AnsiDcsParser::for_kind(kind, self.max_sequence_length));
+    }
+
+    /// Parse `incoming`, returning everything it asked for in order. Anything after the last
+    /// complete sequence is kept and finished off by the next push
+    #[allow(clippy::too_many_lines)]
     pub fn push(&mut self, incoming: &[u8]) -> Vec<TerminalOutput> {
         let mut output = Vec::new();
         let mut data_output = Vec::new();
         let mut output_string_sequence = String::new();
 
         for b in incoming {
+            self.capture_byte(*b);
+            let mut invalid = false;
+
             match &mut self.inner {
                 ParserInner::Empty => {
                     if !output_string_sequence.is_empty() {
@@ -294,6 +418,7 @@ impl FreminalAnsiParser {
                         error!("Parser Error: {e}");
                         error!("Escape Sequence that threw an error: {output_string_sequence}");
                         self.inner = ParserInner::Empty;
+                        invalid = true;
                     }
                 }
                 ParserInner::Csi(parser) => {
@@ -310,6 +435,7 @@ impl FreminalAnsiParser {
                                         "CSI Sequence that threw an error: {}",
                                         output_string_sequence
                                     );
+                                    invalid = true;
                                 }
                             }
                             None => continue,
@@ -318,6 +444,7 @@ impl FreminalAnsiParser {
                             error!("Parser Error: {e}");
                             error!("CSI Sequence that threw an error: {output_string_sequence}");
                             self.inner = ParserInner::Empty;
+                            invalid = true;
                         }
                     }
                 }
@@ -333,6 +460,7 @@ impl FreminalAnsiParser {
                                 error!(
                                     "OSC Sequence that threw an error: {output_string_sequence}",
                                 );
+                                invalid = true;
                             }
                         }
                         Ok(None) => continue,
@@ -340,9 +468,17 @@ impl FreminalAnsiParser {
                             error!("Parser Error: {e}");
                             error!("OSC Sequence that threw an error: {output_string_sequence}");
                             self.inner = ParserInner::Empty;
+                            invalid = true;
                         }
                     }
                 }
+                ParserInner::Dcs(parser) => {
+                    output_string_sequence.push(*b as char);
+                    if let Some(value) = parser.ansiparser_inner_dcs(*b, &mut output) {
+                        self.inner = value;
+                        invalid = output.last() == Some(&TerminalOutput::Invalid);
+                    }
+                }
                 ParserInner::DecSpecialGraphics(parser) => {
                     output_string_sequence.push(*b as char);
                     match parser.ansi_parser_inner_line_draw(*b, &mut output) {
@@ -355,12 +491,17 @@ impl FreminalAnsiParser {
                                 error!(
                                     "DecSpecialGraphics Sequence that threw an error: {output_string_sequence}",
                                 );
+                                invalid = true;
                             }
                         }
                         None => continue,
                     }
                 }
             }
+
+            if invalid {
+                self.capture_invalid_sequence();
+            }
         }
 
         if !data_output.is_empty() {
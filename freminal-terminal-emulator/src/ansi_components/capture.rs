@@ -0,0 +1,93 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::collections::VecDeque;
+
+/// Bytes of output kept from before a sequence, to show what it was mixed in with
+pub const CAPTURE_CONTEXT_BYTES: usize = 64;
+/// Longest sequence we keep. Anything past this is dropped, so a runaway OSC can't use up memory
+pub const MAX_CAPTURED_SEQUENCE_BYTES: usize = 256;
+
+/// An escape sequence the parser couldn't handle, exactly as it came in from the pty
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InvalidSequence {
+    /// Output just before the sequence
+    pub context: Vec<u8>,
+    /// The sequence itself, starting at the ESC
+    pub sequence: Vec<u8>,
+}
+
+impl InvalidSequence {
+    /// The context followed by the sequence, ready to feed back in to the parser
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.context.clone();
+        bytes.extend_from_slice(&self.sequence);
+        bytes
+    }
+}
+
+/// Follows the raw bytes going through the parser so the ones making up an invalid sequence can
+/// be handed out afterwards
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct SequenceCapture {
+    sequence: Vec<u8>,
+    context: VecDeque<u8>,
+    captured: Vec<InvalidSequence>,
+}
+
+impl SequenceCapture {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note the next byte. `in_sequence` is whether the byte belongs to an escape sequence,
+    /// including the ESC that starts one
+    pub fn push(&mut self, b: u8, in_sequence: bool, starts_sequence: bool) {
+        if starts_sequence || !in_sequence {
+            self.finish_sequence();
+        }
+
+        if in_sequence {
+            if self.sequence.len() < MAX_CAPTURED_SEQUENCE_BYTES {
+                self.sequence.push(b);
+            }
+        } else {
+            self.push_context(b);
+        }
+    }
+
+    /// The sequence being parsed turned out to be invalid
+    pub fn mark_invalid(&mut self) {
+        if self.sequence.is_empty() {
+            return;
+        }
+
+        self.captured.push(InvalidSequence {
+            context: self.context.iter().copied().collect(),
+            sequence: self.sequence.clone(),
+        });
+    }
+
+    /// Everything captured since the last call
+    pub fn take(&mut self) -> Vec<InvalidSequence> {
+        std::mem::take(&mut self.captured)
+    }
+
+    /// A finished sequence becomes context for whatever comes next
+    fn finish_sequence(&mut self) {
+        for b in std::mem::take(&mut self.sequence) {
+            self.push_context(b);
+        }
+    }
+
+    fn push_context(&mut self, b: u8) {
+        if self.context.len() == CAPTURE_CONTEXT_BYTES {
+            self.context.pop_front();
+        }
+        self.context.push_back(b);
+    }
+}
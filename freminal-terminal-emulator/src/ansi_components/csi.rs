@@ -20,8 +20,11 @@ use super::{
         ed::ansi_parser_inner_csi_finished_set_position_j,
         el::ansi_parser_inner_csi_finished_set_position_k, ict::ansi_parser_inner_csi_finished_ich,
         il::ansi_parser_inner_csi_finished_set_position_l,
+        kitty_keyboard::ansi_parser_inner_csi_finished_kitty_keyboard,
         send_device_attributes::ansi_parser_inner_csi_finished_send_da,
         sgr::ansi_parser_inner_csi_finished_sgr_ansi,
+        xtmodkeys::ansi_parser_inner_csi_finished_xtmodkeys,
+        xtsave::ansi_parser_inner_csi_finished_xtsave,
     },
     mode::{terminal_mode_from_params, SetMode},
 };
@@ -157,6 +160,10 @@ impl AnsiCsiParser {
             AnsiCsiParserState::Finished(b'X') => {
                 ansi_parser_inner_csi_finished_set_position_x(&self.params, output)
             }
+            // XTMODKEYS, ESC [ > Pp ; Pv m and ESC [ > Pp n
+            AnsiCsiParserState::Finished(b'm' | b'n') if self.params.starts_with(b">") => {
+                ansi_parser_inner_csi_finished_xtmodkeys(&self.params, b, output)
+            }
             AnsiCsiParserState::Finished(b'm') => {
                 ansi_parser_inner_csi_finished_sgr_ansi(&self.params, output)
             }
@@ -184,26 +191,38 @@ impl AnsiCsiParser {
             AnsiCsiParserState::Finished(b't') => {
                 ansi_parser_inner_csi_finished_set_position_t(&self.params, output)
             }
+            // DECSTR, ESC [ ! p
+            AnsiCsiParserState::Finished(b'p') if self.intermediates == b"!" => {
+                output.push(TerminalOutput::SoftReset);
+                Ok(Some(ParserInner::Empty))
+            }
             AnsiCsiParserState::Finished(b'p') => {
                 ansi_parser_inner_csi_finished_decrqm(&self.params, &self.intermediates, b, output)
             }
             AnsiCsiParserState::Finished(b'q') => {
                 ansi_parser_inner_csi_finished_set_position_q(&self.params, output)
             }
+            // XTSAVE and XTRESTORE, ESC [ ? Pm s and ESC [ ? Pm r
+            AnsiCsiParserState::Finished(b's' | b'r') if self.params.starts_with(b"?") => {
+                ansi_parser_inner_csi_finished_xtsave(&self.params, b, output)
+            }
             AnsiCsiParserState::Finished(b'r') => {
                 ansi_parser_inner_csi_set_top_and_bottom_margins(&self.params, output)
             }
+            // The kitty keyboard protocol. ESC [ u on its own is SCORC
+            AnsiCsiParserState::Finished(b'u')
+                if self.params.first().is_some_and(|b| b"?><=".contains(b)) =>
+            {
+                ansi_parser_inner_csi_finished_kitty_keyboard(&self.params, output)
+            }
             AnsiCsiParserState::Finished(b'c') => {
                 ansi_parser_inner_csi_finished_send_da(&self.params, &self.intermediates, output)
             }
-            AnsiCsiParserState::Finished(b'u') => {
-                format_error_output(&self.sequence);
-                output.push(TerminalOutput::Skipped);
-                Ok(Some(ParserInner::Empty))
-            }
             AnsiCsiParserState::Finished(_esc) => {
-                format_error_output(&self.sequence);
-                output.push(TerminalOutput::Invalid);
+                output.push(TerminalOutput::Unsupported(unsupported_sequence_name(
+                    "CSI",
+                    &self.sequence,
+                )));
 
                 Ok(Some(ParserInner::Empty))
             }
@@ -229,6 +248,34 @@ fn is_csi_intermediate(b: u8) -> bool {
     (0x20..=0x2f).contains(&b)
 }
 
+/// A name for a sequence we don't support, for the report of what programs asked for: the
+/// introducer, then any private marker, intermediates and the final byte, but not the numbers,
+/// such as `CSI > u`
+#[must_use]
+pub fn unsupported_sequence_name(introducer: &str, sequence: &[u8]) -> String {
+    let mut name = introducer.to_string();
+    for &b in sequence {
+        match b {
+            // Numbers and their separators
+            b'0'..=b';' => (),
+            b' ' => name.push_str(" SP"),
+            // Private markers and intermediates
+            b'<'..=b'?' | 0x21..=0x2f => {
+                name.push(' ');
+                name.push(char::from(b));
+            }
+            0x40..=0x7e => {
+                name.push(' ');
+                name.push(char::from(b));
+                break;
+            }
+            _ => break,
+        }
+    }
+
+    name
+}
+
 fn format_error_output(sequence: &[u8]) {
     let params = String::from_utf8(sequence.to_vec())
         .unwrap_or_else(|_| "Unable To Parse Params".to_string());
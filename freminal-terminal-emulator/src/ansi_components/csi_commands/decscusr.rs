@@ -10,7 +10,8 @@ use anyhow::Result;
 /// DECSCUSR—Set Cursor Style
 ///
 /// Select the style of the cursor on the screen.
-/// 0, 1, or none: Blink Block (default)
+/// 0 or none: the user's cursor style
+/// 1: Blink Block
 /// 2: Steady Block
 /// 3: Blink Underline
 /// 4: Steady Underline
@@ -31,9 +32,10 @@ pub fn ansi_parser_inner_csi_finished_set_position_q(
         return Err(ParserFailures::UnhandledDECSCUSRCommand(format!("{params:?}")).into());
     };
 
-    output.push(TerminalOutput::CursorVisualStyle(
-        param.unwrap_or_default().into(),
-    ));
+    match param.unwrap_or_default() {
+        0 => output.push(TerminalOutput::DefaultCursorVisualStyle),
+        style => output.push(TerminalOutput::CursorVisualStyle(style.into())),
+    }
 
     Ok(Some(ParserInner::Empty))
 }
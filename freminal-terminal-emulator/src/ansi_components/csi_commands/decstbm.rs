@@ -55,14 +55,15 @@ pub fn ansi_parser_inner_csi_set_top_and_bottom_margins(
         .into());
     };
 
-    if params.len() != 2 {
+    // Pb can be left off entirely, leaving the region running to the bottom of the page
+    if params.is_empty() || params.len() > 2 {
         warn!("DECSTBM command with invalid number of params. Expected 2, got {params:?}");
         output.push(TerminalOutput::Invalid);
         return Err(ParserFailures::UnhandledDECSTBMCommand(format!("{params:?}")).into());
     }
 
     let pt = params[0].unwrap_or(1);
-    let pb = params[1].unwrap_or(usize::MAX);
+    let pb = params.get(1).copied().flatten().unwrap_or(usize::MAX);
 
     if pt >= pb || pt == 0 || pb == 0 {
         warn!("Invalid DECSTBM command with out of bounds params. pt: {pt}, pb: {pb}");
@@ -0,0 +1,78 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use crate::ansi::{extract_param, split_params_into_semicolon_delimited_usize};
+use crate::ansi::{ParserInner, TerminalOutput};
+use crate::error::ParserFailures;
+use anyhow::Result;
+
+/// A request about the kitty keyboard protocol's flags
+///
+/// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KittyKeyboardRequest {
+    /// ESC [ > flags u, remembering the flags in use and using these instead
+    Push(u8),
+    /// ESC [ < count u, going back to the flags in use before the last `count` pushes
+    Pop(usize),
+    /// ESC [ = flags ; mode u. Mode 1 uses these flags, 2 adds them to the ones in use and 3
+    /// takes them away
+    Set { flags: u8, mode: usize },
+    /// ESC [ ? u, asking which flags are in use
+    Query,
+}
+
+/// The kitty keyboard protocol
+///
+/// ESC [ ? u
+/// ESC [ > flags u
+/// ESC [ < count u
+/// ESC [ = flags ; mode u
+///
+/// Flags left out are 0, a count left out is 1 and a mode left out is 1.
+///
+/// # Errors
+/// Will return an error if a parameter is not a valid number, the flags don't fit in the five
+/// the protocol has or the mode isn't 1, 2 or 3
+pub fn ansi_parser_inner_csi_finished_kitty_keyboard(
+    params: &[u8],
+    output: &mut Vec<TerminalOutput>,
+) -> Result<Option<ParserInner>> {
+    let request = params.split_first().and_then(|(marker, values)| {
+        let values = split_params_into_semicolon_delimited_usize(values).ok()?;
+        let flags = || {
+            u8::try_from(extract_param(0, &values).unwrap_or_default())
+                .ok()
+                .filter(|flags| *flags < 32)
+        };
+
+        match marker {
+            b'?' if values == [None] => Some(KittyKeyboardRequest::Query),
+            b'>' => Some(KittyKeyboardRequest::Push(flags()?)),
+            b'<' => Some(KittyKeyboardRequest::Pop(
+                extract_param(0, &values).unwrap_or(1),
+            )),
+            b'=' => {
+                let mode = extract_param(1, &values).unwrap_or(1);
+                (1..=3).contains(&mode).then_some(())?;
+                Some(KittyKeyboardRequest::Set {
+                    flags: flags()?,
+                    mode,
+                })
+            }
+            _ => None,
+        }
+    });
+
+    let Some(request) = request else {
+        warn!("Invalid kitty keyboard protocol command");
+        output.push(TerminalOutput::Invalid);
+        return Err(ParserFailures::UnhandledKittyKeyboardCommand(params.to_vec()).into());
+    };
+
+    output.push(TerminalOutput::KittyKeyboard(request));
+
+    Ok(Some(ParserInner::Empty))
+}
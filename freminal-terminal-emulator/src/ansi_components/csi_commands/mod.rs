@@ -19,5 +19,8 @@ pub mod ed;
 pub mod el;
 pub mod ict;
 pub mod il;
+pub mod kitty_keyboard;
 pub mod send_device_attributes;
 pub mod sgr;
+pub mod xtmodkeys;
+pub mod xtsave;
@@ -12,7 +12,7 @@ use crate::{
     ansi_components::sgr::SelectGraphicRendition,
 };
 use anyhow::Result;
-use freminal_common::colors::{lookup_256_color_by_index, TerminalColor};
+use freminal_common::colors::{lookup_256_color_by_index, TerminalColor, PALETTE_COLORS};
 
 /// Select Graphic Rendition
 ///
@@ -85,6 +85,17 @@ fn default_color(output: &mut Vec<TerminalOutput>, custom_color_control_code: us
     });
 }
 
+const fn palette_color_sgr(
+    custom_color_control_code: usize,
+    color: TerminalColor,
+) -> SelectGraphicRendition {
+    match custom_color_control_code {
+        38 => SelectGraphicRendition::Foreground(color),
+        48 => SelectGraphicRendition::Background(color),
+        _ => SelectGraphicRendition::UnderlineColor(color),
+    }
+}
+
 pub fn handle_custom_color(
     output: &mut Vec<TerminalOutput>,
     param_iter: &mut IntoIter<Option<usize>>,
@@ -131,7 +142,18 @@ pub fn handle_custom_color(
         }
         5 => {
             let lookup = match param_iter.next() {
-                Some(Some(lookup)) => lookup,
+                Some(Some(lookup)) => {
+                    // The first 16 are the palette colors, so they follow the theme
+                    if let Some(color) = PALETTE_COLORS.get(lookup) {
+                        output.push(TerminalOutput::Sgr(palette_color_sgr(
+                            custom_color_control_code,
+                            *color,
+                        )));
+                        return;
+                    }
+
+                    lookup
+                }
                 _ => 0,
             };
 
@@ -0,0 +1,63 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use crate::ansi::{extract_param, split_params_into_semicolon_delimited_usize};
+use crate::ansi::{ParserInner, TerminalOutput};
+use crate::error::ParserFailures;
+use anyhow::Result;
+
+/// The XTMODKEYS resource for modifyOtherKeys
+const MODIFY_OTHER_KEYS: usize = 4;
+
+/// XTMODKEYS—Set or reset key modifier options
+///
+/// ESC [ > Pp ; Pv m sets resource Pp to Pv, and ESC [ > Pp n turns it off. Leaving Pv out
+/// resets the resource, and leaving Pp out too resets all of them.
+///
+/// Only modifyOtherKeys (Pp = 4) is supported, with Pv 0 for off, 1 or 2. The others are
+/// reported as unsupported.
+///
+/// # Errors
+/// Will return an error if a parameter is not a valid number, or modifyOtherKeys is set to
+/// something other than 0, 1 or 2
+pub fn ansi_parser_inner_csi_finished_xtmodkeys(
+    params: &[u8],
+    terminator: u8,
+    output: &mut Vec<TerminalOutput>,
+) -> Result<Option<ParserInner>> {
+    let values = params
+        .strip_prefix(b">")
+        .map(split_params_into_semicolon_delimited_usize);
+
+    let Some(Ok(values)) = values else {
+        warn!("Invalid XTMODKEYS command");
+        output.push(TerminalOutput::Invalid);
+        return Err(ParserFailures::UnhandledXTMODKEYSCommand(params.to_vec()).into());
+    };
+
+    let resource = extract_param(0, &values);
+    let level = if terminator == b'n' {
+        0
+    } else {
+        extract_param(1, &values).unwrap_or_default()
+    };
+
+    match resource {
+        None | Some(MODIFY_OTHER_KEYS) => {
+            let Some(level) = u8::try_from(level).ok().filter(|level| *level <= 2) else {
+                warn!("Invalid modifyOtherKeys level: {level}");
+                output.push(TerminalOutput::Invalid);
+                return Err(ParserFailures::UnhandledXTMODKEYSCommand(params.to_vec()).into());
+            };
+
+            output.push(TerminalOutput::ModifyOtherKeys(level));
+        }
+        Some(resource) => output.push(TerminalOutput::Unsupported(format!(
+            "XTMODKEYS for resource {resource}"
+        ))),
+    }
+
+    Ok(Some(ParserInner::Empty))
+}
@@ -0,0 +1,44 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use crate::ansi::{split_params_into_semicolon_delimited_usize, ParserInner, TerminalOutput};
+use crate::error::ParserFailures;
+use anyhow::Result;
+
+/// Save or Restore DEC Private Modes
+///
+/// XTSAVE: ESC [ ? Pm s
+/// XTRESTORE: ESC [ ? Pm r
+///
+/// Pm is one or more private mode numbers, separated by `;`. XTSAVE remembers whether each is
+/// set, and XTRESTORE puts them back how they were, so a program can change modes for a while and
+/// leave them as it found them.
+///
+/// # Errors
+/// Will return an error if a parameter is not a valid number
+pub fn ansi_parser_inner_csi_finished_xtsave(
+    params: &[u8],
+    terminator: u8,
+    output: &mut Vec<TerminalOutput>,
+) -> Result<Option<ParserInner>> {
+    let modes = params
+        .strip_prefix(b"?")
+        .map(split_params_into_semicolon_delimited_usize);
+
+    let Some(Ok(modes)) = modes else {
+        warn!("Invalid XTSAVE/XTRESTORE command");
+        output.push(TerminalOutput::Invalid);
+        return Err(ParserFailures::UnhandledXTSAVECommand(params.to_vec()).into());
+    };
+
+    let modes = modes.into_iter().flatten().collect();
+    output.push(if terminator == b's' {
+        TerminalOutput::SavePrivateModes(modes)
+    } else {
+        TerminalOutput::RestorePrivateModes(modes)
+    });
+
+    Ok(Some(ParserInner::Empty))
+}
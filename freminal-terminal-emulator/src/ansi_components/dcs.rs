@@ -0,0 +1,121 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use crate::{
+    ansi::{ParserInner, TerminalOutput},
+    ansi_components::csi::unsupported_sequence_name,
+    error::ParserFailures,
+};
+use freminal_common::args::DEFAULT_MAX_SEQUENCE_LENGTH;
+
+// DCS Sequence looks like this:
+// 1bP$qm1b\
+
+/// The control strings that run until a terminator
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum ControlStringKind {
+    /// Device Control String, `ESC P`
+    #[default]
+    Dcs,
+    /// Application Program Command, `ESC _`
+    Apc,
+    /// Privacy Message, `ESC ^`
+    Pm,
+    /// Start of String, `ESC X`
+    Sos,
+}
+
+/// Parser for Device Control Strings, and the other control strings we don't act on
+///
+/// Everything up to the string terminator (ST, or BEL as xterm also allows) is collected. The
+/// only control string we act on is DECRQSS (`DCS $q`); anything else is consumed so it doesn't
+/// end up on screen, however long it is and however it is split across reads.
+#[derive(Eq, PartialEq, Debug, Default)]
+pub struct AnsiDcsParser {
+    pub(crate) kind: ControlStringKind,
+    pub(crate) data: Vec<u8>,
+    /// The most of the payload we keep. Anything longer is still consumed, so it doesn't end up
+    /// on screen, but is never acted on
+    pub(crate) max_length: usize,
+    /// The payload went past `max_length`, so `data` only has the start of it
+    pub(crate) truncated: bool,
+    /// The last byte was ESC, which is the start of ST
+    pub(crate) escape: bool,
+}
+
+impl AnsiDcsParser {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::for_kind(ControlStringKind::Dcs, DEFAULT_MAX_SEQUENCE_LENGTH)
+    }
+
+    #[must_use]
+    pub const fn for_kind(kind: ControlStringKind, max_length: usize) -> Self {
+        Self {
+            kind,
+            data: Vec::new(),
+            max_length,
+            truncated: false,
+            escape: false,
+        }
+    }
+
+    /// Push a byte in to the parser. Returns the next parser state once the sequence has finished
+    pub fn ansiparser_inner_dcs(
+        &mut self,
+        b: u8,
+        output: &mut Vec<TerminalOutput>,
+    ) -> Option<ParserInner> {
+        let finished = b == 0x07 || (self.escape && b == b'\\');
+        self.escape = b == 0x1b;
+
+        if !finished {
+            if self.data.len() < self.max_length {
+                self.data.push(b);
+            } else if !self.truncated {
+                error!(
+                    "{}",
+                    ParserFailures::SequenceTooLong(format!("{:?}", self.kind), self.max_length)
+                );
+                self.truncated = true;
+            }
+
+            return None;
+        }
+
+        // The ESC of ST isn't part of the payload
+        if b == b'\\' && self.data.last() == Some(&0x1b) {
+            self.data.pop();
+        }
+
+        match self.data.strip_prefix(b"$q") {
+            // Too much of it was dropped to know what it was
+            _ if self.truncated => output.push(TerminalOutput::Invalid),
+            Some(setting) if self.kind == ControlStringKind::Dcs => {
+                output.push(TerminalOutput::RequestStatusString(
+                    String::from_utf8_lossy(setting).to_string(),
+                ));
+            }
+            _ => {
+                debug!(
+                    "Unhandled {:?} sequence: {}",
+                    self.kind,
+                    String::from_utf8_lossy(&self.data)
+                );
+                let introducer = match self.kind {
+                    ControlStringKind::Dcs => "DCS",
+                    ControlStringKind::Apc => "APC",
+                    ControlStringKind::Pm => "PM",
+                    ControlStringKind::Sos => "SOS",
+                };
+                output.push(TerminalOutput::Unsupported(unsupported_sequence_name(
+                    introducer, &self.data,
+                )));
+            }
+        }
+
+        Some(ParserInner::Empty)
+    }
+}
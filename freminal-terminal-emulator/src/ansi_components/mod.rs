@@ -3,8 +3,10 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+pub mod capture;
 pub mod csi;
 pub mod csi_commands;
+pub mod dcs;
 pub mod line_draw;
 pub mod mode;
 pub mod modes;
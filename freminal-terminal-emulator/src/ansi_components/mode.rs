@@ -6,13 +6,15 @@
 use std::fmt;
 
 use super::modes::{
-    decawm::Decawm, decckm::Decckm, dectcem::Dectcem, rl_bracket::RlBracket,
+    alt_scroll::AltScroll, bdsm::Bdsm, decawm::Decawm, decbkm::Decbkm, decckm::Decckm,
+    decnkm::Decnkm, decom::Decom, dectcem::Dectcem, rl_bracket::RlBracket, save_cursor::SaveCursor,
     sync_updates::SynchronizedUpdates, unknown::UnknownMode, xtcblink::XtCBlink,
     xtextscrn::XtExtscrn, xtmsewin::XtMseWin, MouseModeNumber, ReportMode,
 };
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug, Eq, PartialEq, Default)]
+#[non_exhaustive]
 pub enum SetMode {
     DecSet,
     #[default]
@@ -20,10 +22,48 @@ pub enum SetMode {
     DecQuery,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// How mouse reports are written, chosen separately from which events are reported
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum MouseEncoding {
+    /// `CSI M` with the button and position as single bytes, so only up to column 223
+    #[default]
     X11,
-    Sgr,
+    Utf8,  // ?1005
+    Sgr,   // ?1006
+    Urxvt, // ?1015
+}
+
+impl MouseModeNumber for MouseEncoding {
+    fn mouse_mode_number(&self) -> usize {
+        match self {
+            Self::X11 => 0,
+            Self::Utf8 => 1005,
+            Self::Sgr => 1006,
+            Self::Urxvt => 1015,
+        }
+    }
+}
+
+impl ReportMode for MouseEncoding {
+    fn report(&self, override_mode: Option<SetMode>) -> String {
+        let set_mode = match override_mode {
+            Some(SetMode::DecSet) => 1,
+            Some(SetMode::DecRst) => 2,
+            Some(SetMode::DecQuery) | None => 0,
+        };
+        format!("\x1b[?{};{set_mode}$y", self.mouse_mode_number())
+    }
+}
+
+impl fmt::Display for MouseEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::X11 => write!(f, "X11 Mouse Encoding"),
+            Self::Utf8 => write!(f, "UTF-8 Mouse Encoding"),
+            Self::Sgr => write!(f, "SGR Mouse Encoding"),
+            Self::Urxvt => write!(f, "URXVT Mouse Encoding"),
+        }
+    }
 }
 
 // https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-Mouse-Tracking
@@ -35,9 +75,6 @@ pub enum MouseTrack {
     XtMseX11,       // ?1000
     XtMseBtn,       // ?1002
     XtMseAny,       // ?1003
-    XtMseUtf,       // ?1005
-    XtMseSgr,       // ?1006
-    XtMseUrXvt,     // ?1015
     XtMseSgrPixels, // ?1016
     Query(usize),
 }
@@ -50,9 +87,6 @@ impl MouseModeNumber for MouseTrack {
             Self::XtMseX11 => 1000,
             Self::XtMseBtn => 1002,
             Self::XtMseAny => 1003,
-            Self::XtMseUtf => 1005,
-            Self::XtMseSgr => 1006,
-            Self::XtMseUrXvt => 1015,
             Self::XtMseSgrPixels => 1016,
             Self::Query(v) => *v,
         }
@@ -67,9 +101,6 @@ impl ReportMode for MouseTrack {
             Self::XtMseX11 => 1000,
             Self::XtMseBtn => 1002,
             Self::XtMseAny => 1003,
-            Self::XtMseUtf => 1005,
-            Self::XtMseSgr => 1006,
-            Self::XtMseUrXvt => 1015,
             Self::XtMseSgrPixels => 1016,
         };
 
@@ -83,57 +114,23 @@ impl ReportMode for MouseTrack {
 }
 
 impl MouseTrack {
+    /// Whether releasing a button is reported. X10 mode only reports presses
     #[must_use]
-    pub fn get_encoding(&self) -> MouseEncoding {
-        if self == &Self::XtMseSgr {
-            MouseEncoding::Sgr
-        } else {
-            MouseEncoding::X11
-        }
+    pub const fn reports_release(&self) -> bool {
+        matches!(self, Self::XtMseX11 | Self::XtMseBtn | Self::XtMseAny)
     }
 
-    // #[must_use]
-    // pub const fn should_scroll(&self) -> bool {
-    //     match self {
-    //         Self::NoTracking | Self::XtMsex10 | Self::XtMseX11 => false,
-    //         Self::XtMseBtn
-    //         | Self::XtMseAny
-    //         | Self::XtMseUtf
-    //         | Self::XtMseSgr
-    //         | Self::XtMseUrXvt
-    //         | Self::XtMseSgrPixels => true,
-    //     }
-    // }
-
-    // /// Function to determine if motion is require to be reported
-    // #[must_use]
-    // pub const fn should_report_motion(&self) -> bool {
-    //     match self {
-    //         Self::NoTracking | Self::XtMsex10 | Self::XtMseX11 => false,
-    //         Self::XtMseBtn
-    //         | Self::XtMseAny
-    //         | Self::XtMseUtf
-    //         | Self::XtMseSgr
-    //         | Self::XtMseUrXvt
-    //         | Self::XtMseSgrPixels => true,
-    //     }
-    // }
+    /// Whether moving the mouse with a button held is reported
+    #[must_use]
+    pub const fn reports_drag(&self) -> bool {
+        matches!(self, Self::XtMseBtn | Self::XtMseAny)
+    }
 
-    // /// Function to determine if button presses should be tracked. x10 only wants button presses, everybody else
-    // /// cares if the button is pressed or released.
-    // #[must_use]
-    // pub const fn should_retain_position_with_button_down(&self) -> bool {
-    //     match self {
-    //         Self::NoTracking | Self::XtMsex10 => false,
-    //         Self::XtMseX11
-    //         | Self::XtMseBtn
-    //         | Self::XtMseAny
-    //         | Self::XtMseUtf
-    //         | Self::XtMseSgr
-    //         | Self::XtMseUrXvt
-    //         | Self::XtMseSgrPixels => true,
-    //     }
-    // }
+    /// Whether moving the mouse with no buttons held is reported
+    #[must_use]
+    pub const fn reports_motion(&self) -> bool {
+        matches!(self, Self::XtMseAny)
+    }
 }
 
 impl fmt::Display for MouseTrack {
@@ -144,9 +141,6 @@ impl fmt::Display for MouseTrack {
             Self::XtMsex10 => write!(f, "XtMsex10"),
             Self::XtMseBtn => write!(f, "XtMseBtn"),
             Self::XtMseAny => write!(f, "XtMseAny"),
-            Self::XtMseUtf => write!(f, "XtMseUtf"),
-            Self::XtMseSgr => write!(f, "XtMseSgr"),
-            Self::XtMseUrXvt => write!(f, "XtMseUrXvt"),
             Self::XtMseSgrPixels => write!(f, "XtMseSgrPixels"),
             Self::Query(v) => write!(f, "Query Mouse Tracking({v})"),
         }
@@ -154,17 +148,25 @@ impl fmt::Display for MouseTrack {
 }
 
 #[derive(Eq, PartialEq, Debug)]
+#[non_exhaustive]
 pub enum Mode {
     // Cursor keys mode
     // https://vt100.net/docs/vt100-ug/chapter3.html
     Decckm(Decckm),
     Decawm(Decawm),
     Dectem(Dectcem),
+    Decnkm(Decnkm),
+    Decbkm(Decbkm),
+    Decom(Decom),
+    Bdsm(Bdsm),
     XtCBlink(XtCBlink),
+    SaveCursor(SaveCursor),
     XtExtscrn(XtExtscrn),
     XtMseWin(XtMseWin),
+    AltScroll(AltScroll),
     BracketedPaste(RlBracket),
     MouseMode(MouseTrack),
+    MouseEncodingMode(MouseEncoding),
     SynchronizedUpdates(SynchronizedUpdates),
     UnknownQuery(Vec<u8>),
     Unknown(UnknownMode),
@@ -176,11 +178,18 @@ impl ReportMode for Mode {
             Self::Decckm(decckm) => decckm.report(override_mode),
             Self::Decawm(decawm) => decawm.report(override_mode),
             Self::Dectem(dectem) => dectem.report(override_mode),
+            Self::Decnkm(decnkm) => decnkm.report(override_mode),
+            Self::Decbkm(decbkm) => decbkm.report(override_mode),
+            Self::Decom(decom) => decom.report(override_mode),
+            Self::Bdsm(bdsm) => bdsm.report(override_mode),
             Self::XtCBlink(xt_cblink) => xt_cblink.report(override_mode),
+            Self::SaveCursor(save_cursor) => save_cursor.report(override_mode),
             Self::XtExtscrn(xt_extscrn) => xt_extscrn.report(override_mode),
             Self::XtMseWin(xt_mse_win) => xt_mse_win.report(override_mode),
+            Self::AltScroll(alt_scroll) => alt_scroll.report(override_mode),
             Self::BracketedPaste(rl_bracket) => rl_bracket.report(override_mode),
             Self::MouseMode(mouse_mode) => mouse_mode.report(override_mode),
+            Self::MouseEncodingMode(encoding) => encoding.report(override_mode),
             Self::SynchronizedUpdates(sync_updates) => sync_updates.report(override_mode),
             Self::Unknown(mode) => mode.report(override_mode),
             Self::UnknownQuery(v) => {
@@ -192,14 +201,23 @@ impl ReportMode for Mode {
     }
 }
 
+/// Modes shared by both screens. Modes that belong to one screen, such as autowrap, origin mode
+/// and cursor visibility, live on its `Buffer` instead
 #[derive(Debug, Eq, PartialEq, Default)]
 pub struct TerminalModes {
     pub cursor_key: Decckm,
+    pub keypad: Decnkm,
+    pub backarrow_key: Decbkm,
     pub bracketed_paste: RlBracket,
     pub focus_reporting: XtMseWin,
+    pub alternate_scroll: AltScroll,
     pub cursor_blinking: XtCBlink,
     pub mouse_tracking: MouseTrack,
+    pub mouse_encoding: MouseEncoding,
     pub synchronized_updates: SynchronizedUpdates,
+    pub bidi: Bdsm,
+    /// xterm's modifyOtherKeys level, set with XTMODKEYS. 0 is off
+    pub modify_other_keys: u8,
 }
 
 impl fmt::Display for Mode {
@@ -208,9 +226,16 @@ impl fmt::Display for Mode {
             Self::Decckm(decckm) => write!(f, "{decckm}"),
             Self::Decawm(decawm) => write!(f, "{decawm}"),
             Self::Dectem(dectem) => write!(f, "{dectem}"),
+            Self::Decnkm(decnkm) => write!(f, "{decnkm}"),
+            Self::Decbkm(decbkm) => write!(f, "{decbkm}"),
+            Self::Decom(decom) => write!(f, "{decom}"),
+            Self::Bdsm(bdsm) => write!(f, "{bdsm}"),
             Self::XtCBlink(xt_cblink) => write!(f, "{xt_cblink}"),
             Self::MouseMode(mouse_mode) => write!(f, "{mouse_mode}"),
+            Self::MouseEncodingMode(encoding) => write!(f, "{encoding}"),
             Self::XtMseWin(xt_mse_win) => write!(f, "{xt_mse_win}"),
+            Self::AltScroll(alt_scroll) => write!(f, "{alt_scroll}"),
+            Self::SaveCursor(save_cursor) => write!(f, "{save_cursor}"),
             Self::XtExtscrn(xt_extscrn) => write!(f, "{xt_extscrn}"),
             Self::BracketedPaste(bracketed_paste) => write!(f, "{bracketed_paste}"),
             Self::SynchronizedUpdates(sync_updates) => write!(f, "{sync_updates}"),
@@ -220,12 +245,25 @@ impl fmt::Display for Mode {
     }
 }
 
+/// Setting an encoding switches to it, and resetting it goes back to X11
+fn mouse_encoding_mode(encoding: MouseEncoding, mode: &SetMode) -> Mode {
+    match mode {
+        SetMode::DecSet => Mode::MouseEncodingMode(encoding),
+        SetMode::DecRst => Mode::MouseEncodingMode(MouseEncoding::X11),
+        SetMode::DecQuery => Mode::MouseMode(MouseTrack::Query(encoding.mouse_mode_number())),
+    }
+}
+
 #[must_use]
 pub fn terminal_mode_from_params(params: &[u8], mode: &SetMode) -> Mode {
     match params {
         // https://vt100.net/docs/vt510-rm/DECCKM.html
         b"?1" => Mode::Decckm(Decckm::new(mode)),
+        // https://vt100.net/docs/vt510-rm/DECOM.html
+        b"?6" => Mode::Decom(Decom::new(mode)),
         b"?7" => Mode::Decawm(Decawm::new(mode)),
+        // https://vt100.net/docs/vt510-rm/BDSM.html
+        b"8" => Mode::Bdsm(Bdsm::new(mode)),
         // TODO: Implement this
         b"?9" => {
             if mode == &SetMode::DecSet {
@@ -238,6 +276,10 @@ pub fn terminal_mode_from_params(params: &[u8], mode: &SetMode) -> Mode {
         }
         b"?12" => Mode::XtCBlink(XtCBlink::new(mode)),
         b"?25" => Mode::Dectem(Dectcem::new(mode)),
+        // https://vt100.net/docs/vt510-rm/DECNKM.html
+        b"?66" => Mode::Decnkm(Decnkm::new(mode)),
+        // https://vt100.net/docs/vt510-rm/DECBKM.html
+        b"?67" => Mode::Decbkm(Decbkm::new(mode)),
         b"?1000" => {
             if mode == &SetMode::DecSet {
                 Mode::MouseMode(MouseTrack::XtMseX11)
@@ -266,43 +308,12 @@ pub fn terminal_mode_from_params(params: &[u8], mode: &SetMode) -> Mode {
             }
         }
         b"?1004" => Mode::XtMseWin(XtMseWin::new(mode)),
-        // TODO: Implement this
-        b"?1005" => {
-            if mode == &SetMode::DecSet {
-                Mode::MouseMode(MouseTrack::XtMseUtf)
-            } else if mode == &SetMode::DecRst {
-                Mode::MouseMode(MouseTrack::NoTracking)
-            } else {
-                Mode::MouseMode(MouseTrack::Query(1005))
-            }
-        }
-        // TODO: Implement this
-        b"?1006" => {
-            if mode == &SetMode::DecSet {
-                Mode::MouseMode(MouseTrack::XtMseSgr)
-            } else if mode == &SetMode::DecRst {
-                Mode::MouseMode(MouseTrack::NoTracking)
-            } else {
-                Mode::MouseMode(MouseTrack::Query(1006))
-            }
-        }
-        // For now, we'll ignore this. Reading documentation it seems like this is
-        // a pretty terrible format to use for mouse tracking.
-        // From the documentation:
-        // However, CSI M  can be mistaken for DL (delete lines), while
-        //   the highlight tracking CSI T  can be mistaken for SD (scroll
-        //   down), and the Window manipulation controls.  For these
-        //   reasons, the 1015 control is not recommended; it is not an
-        //  improvement over 1006.
-        // b"?1015" => {
-        //     if mode == &SetMode::DecSet {
-        //         Mode::MouseMode(MouseTrack::XtMseUrXvt)
-        //     } else if mode == &SetMode::DecRst {
-        //         Mode::MouseMode(MouseTrack::NoTracking)
-        //     } else {
-        //         Mode::MouseMode(MouseTrack::Query(1015))
-        //     }
-        // }
+        b"?1005" => mouse_encoding_mode(MouseEncoding::Utf8, mode),
+        b"?1006" => mouse_encoding_mode(MouseEncoding::Sgr, mode),
+        b"?1007" => Mode::AltScroll(AltScroll::new(mode)),
+        // Not recommended, as reports can be mistaken for other sequences, but urxvt and some
+        // older programs use it
+        b"?1015" => mouse_encoding_mode(MouseEncoding::Urxvt, mode),
         // TODO: Implement this
         b"?1016" => {
             if mode == &SetMode::DecSet {
@@ -313,6 +324,7 @@ pub fn terminal_mode_from_params(params: &[u8], mode: &SetMode) -> Mode {
                 Mode::MouseMode(MouseTrack::Query(1016))
             }
         }
+        b"?1048" => Mode::SaveCursor(SaveCursor::new(mode)),
         b"?1049" => Mode::XtExtscrn(XtExtscrn::new(mode)),
         b"?2004" => Mode::BracketedPaste(RlBracket::new(mode)),
         b"?2026" => Mode::SynchronizedUpdates(SynchronizedUpdates::new(mode)),
@@ -0,0 +1,63 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use core::fmt;
+
+use crate::ansi_components::mode::SetMode;
+
+use super::ReportMode;
+
+/// Alternate scroll mode (`?1007`)
+///
+/// Whether the mouse wheel sends arrow keys on an alternate screen with no scrollback, so it
+/// moves through programs like less. xterm starts with it off, but we start with it on, like VTE
+/// and most other terminals, and programs that don't want it can turn it off.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub enum AltScroll {
+    #[default]
+    /// The wheel sends arrow keys
+    Enabled,
+    /// The wheel does nothing
+    Disabled,
+    Query,
+}
+
+impl AltScroll {
+    #[must_use]
+    pub const fn new(mode: &SetMode) -> Self {
+        match mode {
+            SetMode::DecSet => Self::Enabled,
+            SetMode::DecRst => Self::Disabled,
+            SetMode::DecQuery => Self::Query,
+        }
+    }
+}
+
+impl ReportMode for AltScroll {
+    fn report(&self, override_mode: Option<SetMode>) -> String {
+        override_mode.map_or_else(
+            || match self {
+                Self::Enabled => "\x1b[?1007;1$y".to_string(),
+                Self::Disabled => "\x1b[?1007;2$y".to_string(),
+                Self::Query => "\x1b[?1007;0$y".to_string(),
+            },
+            |override_mode| match override_mode {
+                SetMode::DecSet => "\x1b[?1007;1$y".to_string(),
+                SetMode::DecRst => "\x1b[?1007;2$y".to_string(),
+                SetMode::DecQuery => "\x1b[?1007;0$y".to_string(),
+            },
+        )
+    }
+}
+
+impl fmt::Display for AltScroll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enabled => f.write_str("Alternate Scroll Mode Enabled"),
+            Self::Disabled => f.write_str("Alternate Scroll Mode Disabled"),
+            Self::Query => f.write_str("Alternate Scroll Mode Query"),
+        }
+    }
+}
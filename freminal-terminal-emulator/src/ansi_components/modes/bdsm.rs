@@ -0,0 +1,63 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use core::fmt;
+
+use crate::ansi_components::mode::SetMode;
+
+use super::ReportMode;
+
+/// Bi-Directional Support Mode (BDSM) 8
+///
+/// An ANSI mode rather than a DEC private one, so it is set with `CSI 8 h` without the `?`.
+#[derive(Eq, PartialEq, Debug, Default, Clone)]
+pub enum Bdsm {
+    #[default]
+    /// Implicit (Set) Mode
+    /// The terminal puts right to left text in display order itself.
+    Implicit,
+    /// Explicit (Reset) Mode
+    /// The application has already put the text in display order, so it is shown as sent.
+    Explicit,
+    Query,
+}
+
+impl Bdsm {
+    #[must_use]
+    pub const fn new(mode: &SetMode) -> Self {
+        match mode {
+            SetMode::DecSet => Self::Implicit,
+            SetMode::DecRst => Self::Explicit,
+            SetMode::DecQuery => Self::Query,
+        }
+    }
+}
+
+impl ReportMode for Bdsm {
+    fn report(&self, override_mode: Option<SetMode>) -> String {
+        override_mode.map_or_else(
+            || match self {
+                Self::Implicit => "\x1b[8;1$y".to_string(),
+                Self::Explicit => "\x1b[8;2$y".to_string(),
+                Self::Query => "\x1b[8;0$y".to_string(),
+            },
+            |override_mode| match override_mode {
+                SetMode::DecSet => "\x1b[8;1$y".to_string(),
+                SetMode::DecRst => "\x1b[8;2$y".to_string(),
+                SetMode::DecQuery => "\x1b[8;0$y".to_string(),
+            },
+        )
+    }
+}
+
+impl fmt::Display for Bdsm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Implicit => write!(f, "Bi-Directional Support Mode (BDSM) Implicit"),
+            Self::Explicit => write!(f, "Bi-Directional Support Mode (BDSM) Explicit"),
+            Self::Query => write!(f, "Bi-Directional Support Mode (BDSM) Query"),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use core::fmt;
+
+use crate::ansi_components::mode::SetMode;
+
+use super::ReportMode;
+
+/// Backarrow Key Mode (DECBKM) ?67
+#[derive(Eq, PartialEq, Debug, Default, Clone)]
+pub enum Decbkm {
+    #[default]
+    /// Normal (Reset) Mode
+    /// The backarrow key sends DEL (0x7f).
+    Delete,
+    /// Alternate (Set) Mode
+    /// The backarrow key sends BS (0x08).
+    Backspace,
+    Query,
+}
+
+impl Decbkm {
+    #[must_use]
+    pub const fn new(mode: &SetMode) -> Self {
+        match mode {
+            SetMode::DecSet => Self::Backspace,
+            SetMode::DecRst => Self::Delete,
+            SetMode::DecQuery => Self::Query,
+        }
+    }
+}
+
+impl ReportMode for Decbkm {
+    fn report(&self, override_mode: Option<SetMode>) -> String {
+        override_mode.map_or_else(
+            || match self {
+                Self::Delete => "\x1b[?67;2$y".to_string(),
+                Self::Backspace => "\x1b[?67;1$y".to_string(),
+                Self::Query => "\x1b[?67;0$y".to_string(),
+            },
+            |override_mode| match override_mode {
+                SetMode::DecSet => "\x1b[?67;1$y".to_string(),
+                SetMode::DecRst => "\x1b[?67;2$y".to_string(),
+                SetMode::DecQuery => "\x1b[?67;0$y".to_string(),
+            },
+        )
+    }
+}
+
+impl fmt::Display for Decbkm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Delete => write!(f, "Backarrow Key Mode (DECBKM) Delete"),
+            Self::Backspace => write!(f, "Backarrow Key Mode (DECBKM) Backspace"),
+            Self::Query => write!(f, "Backarrow Key Mode (DECBKM) Query"),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use core::fmt;
+
+use crate::ansi_components::mode::SetMode;
+
+use super::ReportMode;
+
+/// Numeric Keypad Mode (DECNKM) ?66
+///
+/// The same switch as DECKPAM (`ESC =`) and DECKPNM (`ESC >`), as a mode that can be queried.
+#[derive(Eq, PartialEq, Debug, Default, Clone)]
+pub enum Decnkm {
+    #[default]
+    /// Normal (Reset) Mode
+    /// The keypad sends the characters printed on it.
+    Numeric,
+    /// Alternate (Set) Mode
+    /// The keypad sends application sequences.
+    Application,
+    Query,
+}
+
+impl Decnkm {
+    #[must_use]
+    pub const fn new(mode: &SetMode) -> Self {
+        match mode {
+            SetMode::DecSet => Self::Application,
+            SetMode::DecRst => Self::Numeric,
+            SetMode::DecQuery => Self::Query,
+        }
+    }
+}
+
+impl ReportMode for Decnkm {
+    fn report(&self, override_mode: Option<SetMode>) -> String {
+        override_mode.map_or_else(
+            || match self {
+                Self::Numeric => "\x1b[?66;2$y".to_string(),
+                Self::Application => "\x1b[?66;1$y".to_string(),
+                Self::Query => "\x1b[?66;0$y".to_string(),
+            },
+            |override_mode| match override_mode {
+                SetMode::DecSet => "\x1b[?66;1$y".to_string(),
+                SetMode::DecRst => "\x1b[?66;2$y".to_string(),
+                SetMode::DecQuery => "\x1b[?66;0$y".to_string(),
+            },
+        )
+    }
+}
+
+impl fmt::Display for Decnkm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Numeric => write!(f, "Numeric Keypad Mode (DECNKM) Numeric"),
+            Self::Application => write!(f, "Numeric Keypad Mode (DECNKM) Application"),
+            Self::Query => write!(f, "Numeric Keypad Mode (DECNKM) Query"),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use core::fmt;
+
+use crate::ansi_components::mode::SetMode;
+
+use super::ReportMode;
+
+/// Origin Mode (DECOM) ?6
+#[derive(Eq, PartialEq, Debug, Default, Clone)]
+pub enum Decom {
+    #[default]
+    /// Normal (Reset) Mode
+    /// Cursor positions are relative to the top left of the screen.
+    Normal,
+    /// Alternate (Set) Mode
+    /// Cursor positions are relative to the top of the scrolling region, and the cursor can't
+    /// leave it.
+    Origin,
+    Query,
+}
+
+impl Decom {
+    #[must_use]
+    pub const fn new(mode: &SetMode) -> Self {
+        match mode {
+            SetMode::DecSet => Self::Origin,
+            SetMode::DecRst => Self::Normal,
+            SetMode::DecQuery => Self::Query,
+        }
+    }
+}
+
+impl ReportMode for Decom {
+    fn report(&self, override_mode: Option<SetMode>) -> String {
+        override_mode.map_or_else(
+            || match self {
+                Self::Normal => "\x1b[?6;2$y".to_string(),
+                Self::Origin => "\x1b[?6;1$y".to_string(),
+                Self::Query => "\x1b[?6;0$y".to_string(),
+            },
+            |override_mode| match override_mode {
+                SetMode::DecSet => "\x1b[?6;1$y".to_string(),
+                SetMode::DecRst => "\x1b[?6;2$y".to_string(),
+                SetMode::DecQuery => "\x1b[?6;0$y".to_string(),
+            },
+        )
+    }
+}
+
+impl fmt::Display for Decom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Normal => write!(f, "Origin Mode (DECOM) Normal"),
+            Self::Origin => write!(f, "Origin Mode (DECOM) Origin"),
+            Self::Query => write!(f, "Origin Mode (DECOM) Query"),
+        }
+    }
+}
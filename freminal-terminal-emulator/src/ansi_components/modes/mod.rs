@@ -5,10 +5,16 @@
 
 use super::mode::SetMode;
 
+pub mod alt_scroll;
+pub mod bdsm;
 pub mod decawm;
+pub mod decbkm;
 pub mod decckm;
+pub mod decnkm;
+pub mod decom;
 pub mod dectcem;
 pub mod rl_bracket;
+pub mod save_cursor;
 pub mod sync_updates;
 pub mod unknown;
 pub mod xtcblink;
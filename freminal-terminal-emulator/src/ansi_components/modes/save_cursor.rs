@@ -0,0 +1,64 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use core::fmt;
+
+use crate::ansi_components::mode::SetMode;
+
+use super::ReportMode;
+
+/// Save Cursor (`XT_SAVE_CURSOR`) ?1048
+///
+/// Saves or restores the cursor position of the current screen, without switching screens the
+/// way ?1049 does.
+#[derive(Eq, PartialEq, Debug, Default, Clone)]
+pub enum SaveCursor {
+    /// Set Mode
+    /// Save the cursor position.
+    Save,
+    /// Reset Mode
+    /// Restore the cursor position saved last.
+    #[default]
+    Restore,
+    Query,
+}
+
+impl SaveCursor {
+    #[must_use]
+    pub const fn new(mode: &SetMode) -> Self {
+        match mode {
+            SetMode::DecSet => Self::Save,
+            SetMode::DecRst => Self::Restore,
+            SetMode::DecQuery => Self::Query,
+        }
+    }
+}
+
+impl ReportMode for SaveCursor {
+    fn report(&self, override_mode: Option<SetMode>) -> String {
+        override_mode.map_or_else(
+            || match self {
+                Self::Save => "\x1b[?1048;1$y".to_string(),
+                Self::Restore => "\x1b[?1048;2$y".to_string(),
+                Self::Query => "\x1b[?1048;0$y".to_string(),
+            },
+            |override_mode| match override_mode {
+                SetMode::DecSet => "\x1b[?1048;1$y".to_string(),
+                SetMode::DecRst => "\x1b[?1048;2$y".to_string(),
+                SetMode::DecQuery => "\x1b[?1048;0$y".to_string(),
+            },
+        )
+    }
+}
+
+impl fmt::Display for SaveCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Save => write!(f, "Save Cursor (XT_SAVE_CURSOR) Save"),
+            Self::Restore => write!(f, "Save Cursor (XT_SAVE_CURSOR) Restore"),
+            Self::Query => write!(f, "Save Cursor (XT_SAVE_CURSOR) Query"),
+        }
+    }
+}
@@ -4,6 +4,7 @@
 // https://opensource.org/licenses/MIT.
 
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 //use eframe::egui::Color32;
@@ -11,6 +12,7 @@ use std::str::FromStr;
 use crate::ansi::{ParserInner, TerminalOutput};
 use crate::error::ParserFailures;
 use anyhow::{Error, Result};
+use freminal_common::args::DEFAULT_MAX_SEQUENCE_LENGTH;
 
 #[derive(Eq, PartialEq, Debug)]
 pub enum AnsiOscInternalType {
@@ -59,8 +61,12 @@ impl From<Vec<Option<AnsiOscToken>>> for AnsiOscInternalType {
 enum OscTarget {
     TitleBar,
     IconName,
+    Palette,
+    ResetPalette,
     Background,
     Foreground,
+    ResetBackground,
+    ResetForeground,
     // https://iterm2.com/documentation-escape-codes.html
     Ftcs,
     RemoteHost,
@@ -103,10 +109,14 @@ impl From<AnsiOscToken> for OscTarget {
         match value {
             AnsiOscToken::U8(0 | 2) => Self::TitleBar,
             AnsiOscToken::U8(1) => Self::IconName,
+            AnsiOscToken::U8(4) => Self::Palette,
             AnsiOscToken::U8(7) => Self::RemoteHost,
             AnsiOscToken::U8(8) => Self::Url,
             AnsiOscToken::U8(11) => Self::Background,
             AnsiOscToken::U8(10) => Self::Foreground,
+            AnsiOscToken::U8(104) => Self::ResetPalette,
+            AnsiOscToken::U8(110) => Self::ResetForeground,
+            AnsiOscToken::U8(111) => Self::ResetBackground,
             AnsiOscToken::U8(112) => Self::ResetCursorColor,
             AnsiOscToken::U8(133) => Self::Ftcs,
             _ => Self::Unknown,
@@ -171,10 +181,176 @@ impl fmt::Display for Url {
     }
 }
 
+/// The working directory a shell reports with OSC 7, as a `file://host/path` URL
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct WorkingDirectory {
+    /// The machine the shell is running on. Empty if the shell left it out
+    pub host: String,
+    pub path: PathBuf,
+}
+
+impl WorkingDirectory {
+    /// Parse a `file://` URL. Returns `None` for anything else
+    #[must_use]
+    pub fn from_url(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix("file://")?;
+        let (host, path) = rest
+            .find('/')
+            .map_or((rest, ""), |slash| rest.split_at(slash));
+        if path.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            host: host.to_string(),
+            path: PathBuf::from(percent_decode(path)),
+        })
+    }
+
+    /// Whether the directory is on this machine, rather than one the shell has logged in to
+    #[must_use]
+    pub fn is_local(&self) -> bool {
+        self.host.is_empty()
+            || self.host == "localhost"
+            || local_hostname().is_some_and(|hostname| hostname == self.host)
+    }
+}
+
+/// The path, with the host in front of it when it is on another machine
+impl fmt::Display for WorkingDirectory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_local() {
+            write!(f, "{}", self.path.display())
+        } else {
+            write!(f, "{}:{}", self.host, self.path.display())
+        }
+    }
+}
+
+fn local_hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|hostname| hostname.trim().to_string())
+}
+
+/// Undo the `%XX` escapes in a URL path. Anything that isn't a valid escape is kept as it is
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Shell integration marks sent via OSC 133 (FTCS)
+///
+/// See <https://gitlab.freedesktop.org/Per_Bothner/specifications/blob/master/proposals/semantic-prompts.md>
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum FtcsMarker {
+    /// `A` - The prompt is about to be drawn
+    PromptStart,
+    /// `B` - The prompt has been drawn and the user is typing a command
+    CommandStart,
+    /// `C` - The command has been submitted and output will follow
+    CommandExecuted,
+    /// `D[;exit code]` - The command has finished
+    CommandFinished(Option<i32>),
+    Unknown(String),
+}
+
+impl From<&str> for FtcsMarker {
+    fn from(value: &str) -> Self {
+        let mut parts = value.split(';');
+
+        match parts.next() {
+            Some("A") => Self::PromptStart,
+            Some("B") => Self::CommandStart,
+            Some("C") => Self::CommandExecuted,
+            Some("D") => Self::CommandFinished(parts.next().and_then(|v| v.parse::<i32>().ok())),
+            _ => Self::Unknown(value.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for FtcsMarker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PromptStart => write!(f, "PromptStart"),
+            Self::CommandStart => write!(f, "CommandStart"),
+            Self::CommandExecuted => write!(f, "CommandExecuted"),
+            Self::CommandFinished(Some(code)) => write!(f, "CommandFinished({code})"),
+            Self::CommandFinished(None) => write!(f, "CommandFinished"),
+            Self::Unknown(value) => write!(f, "Unknown({value})"),
+        }
+    }
+}
+
+/// The index and color, or query, of each entry in an OSC 4 sequence
+///
+/// `ESC ] 4 ; 1 ; ? ST` asks for palette entry 1, and `ESC ] 4 ; 1 ; #ff0000 ; 2 ; #00ff00 ST`
+/// sets entries 1 and 2.
+///
+/// # Errors
+/// Will return an error if an index isn't a number or is missing its color
+pub fn parse_palette_entries(params: &str) -> Result<Vec<(usize, AnsiOscInternalType)>> {
+    let parts: Vec<&str> = params.split(';').collect();
+    parts
+        .chunks(2)
+        .map(|entry| match entry {
+            [index, spec] => {
+                let index = index.parse::<usize>()?;
+                let value = if *spec == "?" {
+                    AnsiOscInternalType::Query
+                } else {
+                    AnsiOscInternalType::String((*spec).to_string())
+                };
+                Ok((index, value))
+            }
+            _ => Err(anyhow::anyhow!("Palette entry {entry:?} has no color")),
+        })
+        .collect()
+}
+
+/// The indexes in an OSC 104 sequence. None means the whole palette
+///
+/// # Errors
+/// Will return an error if an index isn't a number
+pub fn parse_palette_reset(params: &str) -> Result<Vec<usize>> {
+    params
+        .split(';')
+        .filter(|index| !index.is_empty())
+        .map(|index| Ok(index.parse::<usize>()?))
+        .collect()
+}
+
 #[derive(Eq, PartialEq, Debug)]
+#[non_exhaustive]
 pub enum AnsiOscType {
     RequestColorQueryBackground(AnsiOscInternalType),
     RequestColorQueryForeground(AnsiOscInternalType),
+    /// Set or ask for palette entries (OSC 4)
+    Palette(Vec<(usize, AnsiOscInternalType)>),
+    /// Put palette entries back to the user's colors, or all of them if there are none (OSC 104)
+    ResetPalette(Vec<usize>),
+    ResetForeground,
+    ResetBackground,
     Ftcs(String),
     // FIXME: We're handling 0 and 2 as just title bar for now
     // if we go tabbed, we'll need to handle 2 differently
@@ -193,6 +369,10 @@ impl std::fmt::Display for AnsiOscType {
             Self::RequestColorQueryForeground(value) => {
                 write!(f, "RequestColorQueryForeground({value:?})")
             }
+            Self::Palette(entries) => write!(f, "Palette({entries:?})"),
+            Self::ResetPalette(indexes) => write!(f, "ResetPalette({indexes:?})"),
+            Self::ResetForeground => write!(f, "ResetForeground"),
+            Self::ResetBackground => write!(f, "ResetBackground"),
             Self::Url(url) => write!(f, "Url({url})"),
             Self::SetTitleBar(value) => write!(f, "SetTitleBar({value:?})"),
             Self::Ftcs(value) => write!(f, "Ftcs ({value:?})"),
@@ -216,6 +396,9 @@ pub struct AnsiOscParser {
     pub(crate) state: AnsiOscParserState,
     pub(crate) params: Vec<u8>,
     pub(crate) intermediates: Vec<u8>,
+    /// Longest payload we keep. A longer one is swallowed and reported, rather than growing
+    /// `params` without limit
+    pub(crate) max_length: usize,
 }
 
 // OSC Sequence looks like this:
@@ -230,10 +413,16 @@ impl Default for AnsiOscParser {
 impl AnsiOscParser {
     #[must_use]
     pub const fn new() -> Self {
+        Self::with_max_length(DEFAULT_MAX_SEQUENCE_LENGTH)
+    }
+
+    #[must_use]
+    pub const fn with_max_length(max_length: usize) -> Self {
         Self {
             state: AnsiOscParserState::Params,
             params: Vec::new(),
             intermediates: Vec::new(),
+            max_length,
         }
     }
 
@@ -248,19 +437,27 @@ impl AnsiOscParser {
 
         match self.state {
             AnsiOscParserState::Params => {
-                if is_valid_osc_param(b) {
-                    self.params.push(b);
-                } else {
+                if !is_valid_osc_param(b) {
                     warn!("Invalid OSC param: {:x}", b);
                     self.state = AnsiOscParserState::Invalid;
+                } else if self.params.len() >= self.max_length
+                    && !(b == 0x07 || (b == 0x5c && self.params.last() == Some(&0x1b)))
+                {
+                    error!(
+                        "{}",
+                        ParserFailures::SequenceTooLong("OSC".to_string(), self.max_length)
+                    );
+                    self.state = AnsiOscParserState::Invalid;
                 }
 
+                self.params.push(b);
                 if is_osc_terminator(&self.params) {
-                    self.state = AnsiOscParserState::Finished;
-
-                    while is_final_character_osc_terminator(self.params[self.params.len() - 1]) {
-                        self.params.pop();
-                    }
+                    let terminator_len = if self.params.ends_with(&[0x07]) { 1 } else { 2 };
+                    self.params.truncate(self.params.len() - terminator_len);
+                    self.state = match self.state {
+                        AnsiOscParserState::Params => AnsiOscParserState::Finished,
+                        _ => AnsiOscParserState::InvalidFinished,
+                    };
                 }
             }
             // OscParserState::Intermediates => {
@@ -269,8 +466,18 @@ impl AnsiOscParser {
             AnsiOscParserState::Finished | AnsiOscParserState::InvalidFinished => {
                 unreachable!()
             }
+            // Swallow the rest of the sequence, so none of it ends up on screen. Only the last
+            // byte is needed to spot the terminator
             AnsiOscParserState::Invalid => {
+                let escape = self.params.last() == Some(&0x1b);
+                self.params.clear();
+                if escape {
+                    self.params.push(0x1b);
+                }
+                self.params.push(b);
+
                 if is_osc_terminator(&self.params) {
+                    self.params.clear();
                     self.state = AnsiOscParserState::InvalidFinished;
                 }
             }
@@ -302,7 +509,39 @@ impl AnsiOscParser {
                     let osc_target = OscTarget::from(type_number.clone());
                     let osc_internal_type = AnsiOscInternalType::from(params.clone());
 
+                    // Everything after the OSC number
+                    let value = String::from_utf8_lossy(&self.params);
+                    let value = value.split_once(';').map_or("", |(_, rest)| rest);
+
                     match osc_target {
+                        OscTarget::Palette => match parse_palette_entries(value) {
+                            Ok(entries) => {
+                                output.push(TerminalOutput::OscResponse(AnsiOscType::Palette(
+                                    entries,
+                                )));
+                            }
+                            Err(e) => {
+                                warn!("Invalid OSC 4 sequence {value:?}: {e}");
+                                output.push(TerminalOutput::Invalid);
+                            }
+                        },
+                        OscTarget::ResetPalette => match parse_palette_reset(value) {
+                            Ok(indexes) => {
+                                output.push(TerminalOutput::OscResponse(
+                                    AnsiOscType::ResetPalette(indexes),
+                                ));
+                            }
+                            Err(e) => {
+                                warn!("Invalid OSC 104 sequence {value:?}: {e}");
+                                output.push(TerminalOutput::Invalid);
+                            }
+                        },
+                        OscTarget::ResetForeground => {
+                            output.push(TerminalOutput::OscResponse(AnsiOscType::ResetForeground));
+                        }
+                        OscTarget::ResetBackground => {
+                            output.push(TerminalOutput::OscResponse(AnsiOscType::ResetBackground));
+                        }
                         OscTarget::Background => {
                             output.push(TerminalOutput::OscResponse(
                                 AnsiOscType::RequestColorQueryBackground(osc_internal_type),
@@ -319,9 +558,10 @@ impl AnsiOscParser {
                             )));
                         }
                         OscTarget::Ftcs => {
-                            warn!("Ftcs is not supported");
+                            // The marker can carry its own parameters (ie, the exit code in `D;1`)
+                            // so we hand everything after the OSC number along
                             output.push(TerminalOutput::OscResponse(AnsiOscType::Ftcs(
-                                osc_internal_type.to_string(),
+                                value.to_string(),
                             )));
                         }
 
@@ -339,8 +579,11 @@ impl AnsiOscParser {
                             output.push(TerminalOutput::OscResponse(AnsiOscType::ResetCursorColor));
                         }
                         OscTarget::Unknown => {
-                            warn!("Unknown OSC target: {:?}", type_number);
-                            output.push(TerminalOutput::Invalid);
+                            let number = match type_number {
+                                AnsiOscToken::U8(number) => number.to_string(),
+                                AnsiOscToken::String(number) => number,
+                            };
+                            output.push(TerminalOutput::Unsupported(format!("OSC {number}")));
                         }
                     }
                 } else {
@@ -350,29 +593,18 @@ impl AnsiOscParser {
 
                 Ok(Some(ParserInner::Empty))
             }
-            AnsiOscParserState::Invalid => {
+            AnsiOscParserState::InvalidFinished => {
                 output.push(TerminalOutput::Invalid);
                 Ok(Some(ParserInner::Empty))
             }
-            _ => Ok(None),
+            AnsiOscParserState::Params | AnsiOscParserState::Invalid => Ok(None),
         }
     }
 }
 
-// the terminator of the OSC sequence is a ST (0x5C) or BEL (0x07)
-const fn is_osc_terminator(b: &[u8]) -> bool {
-    // the array has to be at least 4 bytes long, and the last two characters need to be 0x1b and 0x5c
-
-    if b.len() < 2 {
-        return false;
-    }
-
-    b[b.len() - 2] == 0x1b && b[b.len() - 1] == 0x5c || b[b.len() - 1] == 0x07
-}
-
-// FIXME: Support ST (0x1b)\ as a terminator
-const fn is_final_character_osc_terminator(b: u8) -> bool {
-    b == 0x5c || b == 0x07 || b == 0x1b
+// the terminator of the OSC sequence is a ST (0x1b 0x5c) or BEL (0x07)
+fn is_osc_terminator(b: &[u8]) -> bool {
+    b.ends_with(&[0x07]) || b.ends_with(&[0x1b, 0x5c])
 }
 
 fn is_valid_osc_param(b: u8) -> bool {
@@ -7,6 +7,7 @@ use anyhow::Result;
 use freminal_common::colors::TerminalColor;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum SelectGraphicRendition {
     // NOTE: Non-exhaustive list
     Reset,
@@ -48,4 +48,12 @@ pub enum ParserFailures {
     UnhandledDECRQMCommand(Vec<u8>),
     #[error("Invalid send device attributes (DA) set position sequence: {0}")]
     UnhandledDACommand(String),
+    #[error("Invalid save or restore private modes (XTSAVE/XTRESTORE) sequence: {0:?}")]
+    UnhandledXTSAVECommand(Vec<u8>),
+    #[error("Invalid set key modifier options (XTMODKEYS) sequence: {0:?}")]
+    UnhandledXTMODKEYSCommand(Vec<u8>),
+    #[error("Invalid kitty keyboard protocol sequence: {0:?}")]
+    UnhandledKittyKeyboardCommand(Vec<u8>),
+    #[error("{0} sequence is longer than {1} bytes, the rest of it was dropped")]
+    SequenceTooLong(String, usize),
 }
@@ -138,6 +138,18 @@ pub struct FormatTag {
     pub url: Option<Url>,
 }
 
+impl FormatTag {
+    /// Whether `other` looks the same as this, wherever the two are
+    #[must_use]
+    pub fn has_same_format(&self, other: &Self) -> bool {
+        self.colors == other.colors
+            && self.font_weight == other.font_weight
+            && self.font_decorations == other.font_decorations
+            && self.line_wrap_mode == other.line_wrap_mode
+            && self.url == other.url
+    }
+}
+
 impl Default for FormatTag {
     fn default() -> Self {
         Self {
@@ -180,6 +192,18 @@ impl FormatTracker {
     }
 
     pub fn push_range(&mut self, cursor: &CursorState, range: Range<usize>) {
+        if range.start > range.end {
+            error!(
+                "Ignoring inverted format range {}-{}",
+                range.start, range.end
+            );
+            return;
+        }
+
+        if range.is_empty() {
+            return;
+        }
+
         adjust_existing_format_ranges(&mut self.color_info, &range);
 
         self.color_info.push(FormatTag {
@@ -193,26 +217,29 @@ impl FormatTracker {
         });
 
         // FIXME: Insertion sort
-        // FIXME: Merge adjacent
         self.color_info.sort_by(|a, b| a.start.cmp(&b.start));
+        self.merge_adjacent();
+        self.finish_change("push_range");
     }
 
     /// Move all tags > range.start to range.start + range.len
     /// No gaps in coloring data, so one range must expand instead of just be adjusted
     pub fn push_range_adjustment(&mut self, range: Range<usize>) {
-        let range_len = range.end - range.start;
+        let range_len = range.end.saturating_sub(range.start);
         for info in &mut self.color_info {
-            if info.end <= range.start {
-                continue;
-            }
-            if info.start > range.start {
-                info.start += range_len;
-                if info.end != usize::MAX {
-                    info.end += range_len;
-                }
-            } else if info.end != usize::MAX {
-                info.end += range_len;
-            }
+            info.start = position_after_insert(info.start, range.start, range_len);
+            info.end = position_after_insert(info.end, range.start, range_len);
+        }
+        self.finish_change("push_range_adjustment");
+    }
+
+    /// [`Self::push_range_adjustment`] for several insertions at once, such as the blank cells
+    /// DECIC puts on every line. `ranges` are where each insertion starts in the buffer as it was
+    /// before any of them, and don't need to be in order.
+    pub fn push_range_adjustments(&mut self, ranges: &[Range<usize>]) {
+        // Working from the end means earlier insertions haven't moved yet
+        for range in sorted_by_start(ranges).iter().rev() {
+            self.push_range_adjustment(range.clone());
         }
     }
 
@@ -223,66 +250,199 @@ impl FormatTracker {
 
     /// Delete ranges
     ///
+    /// Tags inside `range` are dropped, tags after it move back by its length, and tags that
+    /// straddle it shrink.
+    ///
     /// # Errors
     /// if the ranges overlap in an unhandled way it will return an error
     pub fn delete_range(&mut self, range: Range<usize>) -> Result<()> {
-        let mut to_delete = Vec::new();
-        let del_size = range.end - range.start;
+        if range.start > range.end {
+            return Err(anyhow::anyhow!(
+                "Invalid delete range {}-{}",
+                range.start,
+                range.end
+            ));
+        }
 
-        for (i, info) in &mut self.color_info.iter_mut().enumerate() {
-            let info_range = info.start..info.end;
-            if info.end <= range.start {
-                continue;
-            }
+        if range.is_empty() {
+            return Ok(());
+        }
 
-            if ranges_overlap(&range, &info_range) {
-                if range_fully_contains(&range, &info_range) {
-                    to_delete.push(i);
-                } else if range_starts_overlapping(&range, &info_range) {
-                    if info.end != usize::MAX {
-                        info.end = range.start;
-                    }
-                } else if range_ends_overlapping(&range, &info_range) {
-                    info.start = range.start;
-                    if info.end != usize::MAX {
-                        info.end -= del_size;
-                    }
-                } else if range_fully_contains(&info_range, &range) {
-                    if info.end != usize::MAX {
-                        info.end -= del_size;
-                    }
-                } else {
-                    return Err(anyhow::anyhow!(
-                        "Unhandled overlap case {}-{}, {}-{}",
-                        info.start,
-                        info.end,
-                        range.start,
-                        range.end
-                    ));
-                }
-            } else {
-                //assert!(!ranges_overlap(range.clone(), info_range.clone()));
-
-                if ranges_overlap(&range, &info_range) {
-                    return Err(anyhow::anyhow!(
-                        "Unhandled overlap case {}-{}, {}-{}",
-                        info.start,
-                        info.end,
-                        range.start,
-                        range.end
-                    ));
-                }
-                info.start -= del_size;
-                if info.end != usize::MAX {
-                    info.end -= del_size;
+        self.color_info
+            .retain(|info| !range_fully_contains(&range, &(info.start..info.end)));
+        for info in &mut self.color_info {
+            info.start = position_after_delete(info.start, &range);
+            info.end = position_after_delete(info.end, &range);
+        }
+        self.finish_change("delete_range");
+
+        Ok(())
+    }
+
+    /// [`Self::delete_range`] for several ranges at once, such as the cells DECDC takes out of
+    /// every line. `ranges` are positions in the buffer as it was before any of them were
+    /// deleted, and don't need to be in order.
+    ///
+    /// # Errors
+    /// if any of the ranges are invalid or overlap each other
+    pub fn delete_ranges(&mut self, ranges: &[Range<usize>]) -> Result<()> {
+        let sorted = sorted_by_start(ranges);
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0].end > pair[1].start) {
+            return Err(anyhow::anyhow!(
+                "Overlapping delete ranges {}-{}, {}-{}",
+                pair[0].start,
+                pair[0].end,
+                pair[1].start,
+                pair[1].end
+            ));
+        }
+
+        // Working from the end means earlier ranges haven't moved yet
+        for range in sorted.into_iter().rev() {
+            self.delete_range(range)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check the tags are consistent: in order, starting at 0, each one starting where the last
+    /// ended, none of them empty or inverted, and the last one running to the end of the buffer
+    ///
+    /// # Errors
+    /// describing the first problem found
+    pub fn validate(&self) -> Result<()> {
+        let (Some(first), Some(last)) = (self.color_info.first(), self.color_info.last()) else {
+            return Err(anyhow::anyhow!("No format tags"));
+        };
+
+        if first.start != 0 {
+            return Err(anyhow::anyhow!("First tag starts at {}", first.start));
+        }
+
+        if last.end != usize::MAX {
+            return Err(anyhow::anyhow!("Last tag ends at {}", last.end));
+        }
+
+        if let Some(tag) = self.color_info.iter().find(|tag| tag.start >= tag.end) {
+            return Err(anyhow::anyhow!(
+                "Empty or inverted tag {}-{}",
+                tag.start,
+                tag.end
+            ));
+        }
+
+        if let Some(pair) = self
+            .color_info
+            .windows(2)
+            .find(|pair| pair[0].end != pair[1].start)
+        {
+            return Err(anyhow::anyhow!(
+                "Tags {}-{} and {}-{} overlap or leave a gap",
+                pair[0].start,
+                pair[0].end,
+                pair[1].start,
+                pair[1].end
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Put the tags back in to a consistent state, as described by [`Self::validate`]
+    ///
+    /// Empty and inverted tags are dropped, and every other tag runs until the next one starts.
+    /// That way a bad tag can only ever mis-color the cells it claimed, rather than everything
+    /// after it. Consistent tags are left as they are.
+    pub fn repair(&mut self) {
+        self.color_info.retain(|tag| tag.start < tag.end);
+        self.color_info.sort_by_key(|tag| tag.start);
+
+        // Of tags starting in the same place the last one pushed wins, as it would have when
+        // they were pushed
+        let mut repaired: Vec<FormatTag> = Vec::with_capacity(self.color_info.len());
+        for tag in self.color_info.drain(..) {
+            match repaired.last_mut() {
+                Some(last) if last.start == tag.start => *last = tag,
+                Some(last) => {
+                    last.end = tag.start;
+                    repaired.push(tag);
                 }
+                None => repaired.push(tag),
             }
         }
 
-        for i in to_delete.into_iter().rev() {
-            self.color_info.remove(i);
+        match repaired.first_mut() {
+            Some(first) => first.start = 0,
+            None => repaired.push(FormatTag::default()),
+        }
+        if let Some(last) = repaired.last_mut() {
+            last.end = usize::MAX;
         }
 
-        Ok(())
+        self.color_info = repaired;
     }
+
+    /// Every change is checked to have left the tags consistent, and repaired if not, so
+    /// rendering never sees bad tags. Debug builds log the tags as they were, to help find the
+    /// change that broke them
+    /// Join neighbouring tags with the same formatting. That way text gets the same tags whether
+    /// it was written in one go or a piece at a time, as the shell's output can be split
+    /// anywhere
+    fn merge_adjacent(&mut self) {
+        self.color_info.dedup_by(|tag, previous| {
+            let merge = previous.end == tag.start && previous.has_same_format(tag);
+            if merge {
+                previous.end = tag.end;
+            }
+
+            merge
+        });
+    }
+
+    fn finish_change(&mut self, change: &str) {
+        let Err(e) = self.validate() else {
+            return;
+        };
+
+        error!("Format tags are inconsistent after {change}, repairing them: {e}");
+        if cfg!(debug_assertions) {
+            debug!("Inconsistent format tags: {:?}", self.color_info);
+        }
+
+        self.repair();
+    }
+}
+
+/// Where a tag boundary at `position` ends up once `len` cells are inserted at `at`
+///
+/// Boundaries after the insertion point move along with the cells, so the tag the insertion
+/// lands in grows to cover it. The open end of the last tag stays open.
+#[must_use]
+pub const fn position_after_insert(position: usize, at: usize, len: usize) -> usize {
+    if position == usize::MAX || position <= at {
+        position
+    } else {
+        position + len
+    }
+}
+
+/// Where a tag boundary at `position` ends up once `removed` is cut out of the buffer
+///
+/// Boundaries after the cut move back by its length, and boundaries inside it end up where it
+/// started. The open end of the last tag stays open.
+#[must_use]
+pub const fn position_after_delete(position: usize, removed: &Range<usize>) -> usize {
+    if position == usize::MAX || position <= removed.start {
+        position
+    } else if position >= removed.end {
+        position - (removed.end - removed.start)
+    } else {
+        removed.start
+    }
+}
+
+fn sorted_by_start(ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by_key(|range| range.start);
+    sorted
 }
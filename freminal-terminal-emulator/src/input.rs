@@ -0,0 +1,450 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Mapping of key presses from the GUI to the input we send to the terminal
+//!
+//! Nothing in here touches the GUI or the pty, so encoding can be tested without either. Keys,
+//! modifiers and mouse buttons have their own types here, and the frontend converts its events
+//! to them.
+
+use std::{borrow::Cow, path::Path};
+
+use anyhow::Result;
+
+use crate::{
+    ansi_components::{
+        mode::{MouseEncoding, MouseTrack},
+        modes::rl_bracket::RlBracket,
+    },
+    interface::{collect_text, KeyModifiers, TerminalInput},
+    state::kitty_keyboard::DISAMBIGUATE_ESCAPE_CODES,
+};
+
+/// Pastes longer than this are only sent once the user has confirmed them, so a stray paste
+/// can't flood a slow serial or SSH session
+pub const LARGE_PASTE_BYTES: usize = 64 * 1024;
+
+/// Files longer than this are never pasted
+pub const MAX_PASTE_FILE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// A key we can send something for, by what it is rather than by how a GUI toolkit names it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// F1, F2 and so on, by number
+    Function(u8),
+    /// A key that types a character, by what it types with nothing held down: `'a'` for A
+    Char(char),
+    /// A key on the numeric keypad, numbered the way `TerminalInput::KeyPad` numbers them
+    Keypad(u8),
+}
+
+impl Key {
+    const fn is_letter_or_digit(self) -> bool {
+        matches!(self, Self::Char(c) if c.is_ascii_alphanumeric())
+    }
+}
+
+fn control_key(key: Key) -> Option<Cow<'static, [TerminalInput]>> {
+    match key {
+        Key::Char(c @ 'a'..='z') => {
+            let c = u8::try_from(c.to_ascii_uppercase()).ok()?;
+            Some(vec![TerminalInput::Ctrl(c)].into())
+        }
+        Key::Char('[') => Some([TerminalInput::Ctrl(b'[')].as_ref().into()),
+        Key::Char(']') => Some([TerminalInput::Ctrl(b']')].as_ref().into()),
+        Key::Char('\\') => Some([TerminalInput::Ctrl(b'\\')].as_ref().into()),
+        _ => None,
+    }
+}
+
+/// Whether a key press is `wanted`, for matching our own shortcuts on any keyboard layout
+///
+/// `key` is what the layout says the key is, and `physical_key` where it is on a US keyboard.
+/// Letters and digits the layout has are matched by what they type, so Ctrl+Shift+C is C on
+/// Dvorak too. Keys that type something else, like a symbol or a letter the GUI has no key for
+/// (egui already falls back to the physical key for those), are matched by where they are, so
+/// shortcuts still work on layouts like AZERTY and Cyrillic ones.
+#[must_use]
+pub fn shortcut_key_matches(wanted: Key, key: Key, physical_key: Option<Key>) -> bool {
+    key == wanted || (!key.is_letter_or_digit() && physical_key == Some(wanted))
+}
+
+/// Convert a key press in to the input to send to the terminal
+///
+/// Printable keys without ctrl arrive as text events and are not handled here, apart from the
+/// keypad's, which depend on the keypad mode. Cursor, editing and function keys pressed with
+/// Shift, Alt or Ctrl are sent with the modifiers, which `TerminalInput::to_payload` encodes the
+/// way xterm does. Returns `None` for keys we don't send anything for.
+#[must_use]
+pub fn key_to_terminal_input(
+    key: Key,
+    modifiers: KeyModifiers,
+) -> Option<Cow<'static, [TerminalInput]>> {
+    let input = match key {
+        Key::Enter => TerminalInput::Enter,
+        Key::ArrowUp => TerminalInput::ArrowUp,
+        Key::ArrowDown => TerminalInput::ArrowDown,
+        Key::ArrowLeft => TerminalInput::ArrowLeft,
+        Key::ArrowRight => TerminalInput::ArrowRight,
+        Key::Home => TerminalInput::Home,
+        Key::End => TerminalInput::End,
+        Key::Delete => TerminalInput::Delete,
+        Key::Insert => TerminalInput::Insert,
+        Key::PageUp => TerminalInput::PageUp,
+        Key::PageDown => TerminalInput::PageDown,
+        Key::Backspace => TerminalInput::Backspace,
+        Key::Tab => TerminalInput::Tab,
+        Key::Escape => TerminalInput::Escape,
+        Key::Function(number @ 1..=12) => TerminalInput::Function(number),
+        // Like Enter, the keypad sends the same thing with anything held
+        Key::Keypad(c) => return Some(vec![TerminalInput::KeyPad(c)].into()),
+        Key::Char(_) if modifiers.ctrl => return control_key(key),
+        Key::Function(_) | Key::Char(_) => return None,
+    };
+
+    // Enter is still enter with anything held
+    if modifiers.is_none() || input == TerminalInput::Enter {
+        Some(vec![input].into())
+    } else {
+        Some(vec![TerminalInput::Modified(Box::new(input), modifiers)].into())
+    }
+}
+
+/// The keyboard protocols an application can turn on, which send some keys differently
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyboardProtocol {
+    /// xterm's modifyOtherKeys level. 0 is off, 1 sends keys with their modifiers when the usual
+    /// sequence would lose them, and 2 sends every key with Ctrl or Alt held that way
+    pub modify_other_keys: u8,
+    /// The kitty keyboard protocol's flags. Only disambiguating escape codes is supported
+    pub kitty_flags: u8,
+}
+
+/// The number the kitty keyboard protocol has for each key on the keypad
+fn kitty_keypad_code(c: u8) -> Option<u32> {
+    Some(match c {
+        0..=9 => 57399 + u32::from(c),
+        b'.' => 57409,
+        b'/' => 57410,
+        b'*' => 57411,
+        b'-' => 57412,
+        b'+' => 57413,
+        b'\n' => 57414,
+        b'=' => 57415,
+        b',' => 57416,
+        _ => return None,
+    })
+}
+
+/// The kitty keyboard protocol with escape codes disambiguated
+///
+/// Escape, keys pressed with Ctrl or Alt, Enter, Tab and Backspace pressed with anything, and all
+/// of the keypad are sent as `CSI code ; modifiers u`, by the character the key types with nothing
+/// held down. Other keys keep xterm's sequences, which the protocol uses too, apart from F3.
+fn kitty_key(key: Key, modifiers: KeyModifiers) -> Option<Vec<u8>> {
+    let code = match key {
+        Key::Keypad(c) => kitty_keypad_code(c)?,
+        Key::Escape => 27,
+        Key::Enter if !modifiers.is_none() => 13,
+        Key::Tab if !modifiers.is_none() => 9,
+        Key::Backspace if !modifiers.is_none() => 127,
+        Key::Char(c) if modifiers.ctrl || modifiers.alt => u32::from(c),
+        // CSI 1 ; m R would look like a cursor position report
+        Key::Function(3) if !modifiers.is_none() => {
+            return Some(format!("\x1b[13;{}~", modifiers.parameter()).into_bytes());
+        }
+        _ => return None,
+    };
+
+    Some(if modifiers.is_none() {
+        format!("\x1b[{code}u").into_bytes()
+    } else {
+        format!("\x1b[{code};{}u", modifiers.parameter()).into_bytes()
+    })
+}
+
+/// xterm's modifyOtherKeys, which sends keys as `CSI 27 ; modifiers ; code ~`
+///
+/// Level 1 only sends keys pressed with Ctrl this way when their control character would lose
+/// which key it was or what else was held, such as Ctrl+1 or Ctrl+Shift+A. Level 2 sends every
+/// key typed with Ctrl or Alt this way, and Enter, Tab, Backspace and Escape with anything held,
+/// apart from Shift+Tab, which is still back tab.
+fn modify_other_keys(key: Key, modifiers: KeyModifiers, level: u8) -> Option<Vec<u8>> {
+    let code = match (key, level) {
+        (Key::Char(c), 1) if modifiers.ctrl && (modifiers.shift || control_key(key).is_none()) => c,
+        (Key::Char(c), 2) if modifiers.ctrl || modifiers.alt => c,
+        (Key::Tab, 2) if modifiers == KeyModifiers::SHIFT => return None,
+        (Key::Enter, 2) if !modifiers.is_none() => '\r',
+        (Key::Tab, 2) if !modifiers.is_none() => '\t',
+        (Key::Backspace, 2) if !modifiers.is_none() => '\x7f',
+        (Key::Escape, 2) if !modifiers.is_none() => '\x1b',
+        _ => return None,
+    };
+
+    // xterm sends the character the key types with Shift held
+    let code = if modifiers.shift {
+        code.to_ascii_uppercase()
+    } else {
+        code
+    };
+
+    Some(format!("\x1b[27;{};{}~", modifiers.parameter(), u32::from(code)).into_bytes())
+}
+
+/// Encode a key press for an application that turned on the kitty keyboard protocol or xterm's
+/// modifyOtherKeys. The kitty protocol wins if both are on
+///
+/// Returns `None` if neither is on, or neither changes what the key sends. The key is then sent
+/// the usual way, with `key_to_terminal_input`.
+#[must_use]
+pub fn encode_keyboard_protocol(
+    key: Key,
+    modifiers: KeyModifiers,
+    protocol: KeyboardProtocol,
+) -> Option<Vec<u8>> {
+    if protocol.kitty_flags & DISAMBIGUATE_ESCAPE_CODES != 0 {
+        kitty_key(key, modifiers)
+    } else if protocol.modify_other_keys > 0 {
+        modify_other_keys(key, modifiers, protocol.modify_other_keys)
+    } else {
+        None
+    }
+}
+
+/// A mouse button we can report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    Back,
+    Forward,
+}
+
+/// Something the mouse did, to report to an application that asked for mouse events
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseAction {
+    Press(MouseButton),
+    Release(MouseButton),
+    /// Moving with a button held
+    Drag(MouseButton),
+    /// Moving with no buttons held
+    Move,
+    /// The wheel turned, by `(x, y)`. Only one direction is reported, and up and down win over
+    /// sideways
+    Wheel((f32, f32)),
+}
+
+/// The button number xterm reports for `button`, in the low bits of the report
+const fn mouse_button_code(button: MouseButton) -> usize {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+        // Buttons 8 and 9
+        MouseButton::Back => 128,
+        MouseButton::Forward => 129,
+    }
+}
+
+/// Buttons 4 to 7, for the wheel turning up, down, left and right
+fn mouse_wheel_code((x, y): (f32, f32)) -> Option<usize> {
+    if y > 0.0 {
+        Some(64)
+    } else if y < 0.0 {
+        Some(65)
+    } else if x > 0.0 {
+        Some(66)
+    } else if x < 0.0 {
+        Some(67)
+    } else {
+        None
+    }
+}
+
+/// Encode a mouse report the way xterm does, for `action` in the cell at `column` and `row` of
+/// the screen, counting from 0
+///
+/// `tracking` decides which actions are reported at all, and `encoding` how they are written.
+/// Returns `None` if the action isn't reported, or the cell is too far out for the encoding to
+/// say where it is.
+#[must_use]
+pub fn encode_mouse_report(
+    action: MouseAction,
+    modifiers: KeyModifiers,
+    column: usize,
+    row: usize,
+    tracking: &MouseTrack,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
+    let reported = match action {
+        MouseAction::Press(_) => true,
+        MouseAction::Release(_) => tracking.reports_release(),
+        MouseAction::Drag(_) => tracking.reports_drag(),
+        MouseAction::Move => tracking.reports_motion(),
+        MouseAction::Wheel(_) => *tracking != MouseTrack::XtMsex10,
+    };
+    let tracking_mouse = matches!(
+        tracking,
+        MouseTrack::XtMsex10 | MouseTrack::XtMseX11 | MouseTrack::XtMseBtn | MouseTrack::XtMseAny
+    );
+    if !reported || !tracking_mouse {
+        return None;
+    }
+
+    let mut code = match action {
+        // Only SGR can say which button was released
+        MouseAction::Release(button) if encoding == MouseEncoding::Sgr => mouse_button_code(button),
+        MouseAction::Release(_) => 3,
+        MouseAction::Press(button) => mouse_button_code(button),
+        MouseAction::Drag(button) => mouse_button_code(button) + 32,
+        MouseAction::Move => 3 + 32,
+        MouseAction::Wheel(delta) => mouse_wheel_code(delta)?,
+    };
+
+    // X10 mode doesn't report the modifiers
+    if *tracking != MouseTrack::XtMsex10 {
+        if modifiers.shift {
+            code += 4;
+        }
+        if modifiers.alt {
+            code += 8;
+        }
+        if modifiers.ctrl {
+            code += 16;
+        }
+    }
+
+    // Reports count cells from 1
+    let (x, y) = (column + 1, row + 1);
+    match encoding {
+        MouseEncoding::X11 => {
+            let mut report = b"\x1b[M".to_vec();
+            for value in [code, x, y] {
+                report.push(u8::try_from(value + 32).ok()?);
+            }
+            Some(report)
+        }
+        MouseEncoding::Utf8 => {
+            let mut report = String::from("\x1b[M");
+            for value in [code, x, y] {
+                // Two UTF-8 bytes are as far as it goes
+                let value = u32::try_from(value + 32).ok().filter(|v| *v < 0x800)?;
+                report.push(char::from_u32(value)?);
+            }
+            Some(report.into_bytes())
+        }
+        MouseEncoding::Sgr => {
+            let last = if matches!(action, MouseAction::Release(_)) {
+                'm'
+            } else {
+                'M'
+            };
+            Some(format!("\x1b[<{code};{x};{y}{last}").into_bytes())
+        }
+        MouseEncoding::Urxvt => Some(format!("\x1b[{};{x};{y}M", code + 32).into_bytes()),
+    }
+}
+
+/// Convert pasted text in to terminal input, wrapping it in the bracketed paste markers if the
+/// application asked for them. The text is cleaned up with `sanitize_paste` first
+#[must_use]
+pub fn paste_to_terminal_input(
+    text: &str,
+    bracketed_paste: &RlBracket,
+) -> Cow<'static, [TerminalInput]> {
+    let text = sanitize_paste(text);
+    if *bracketed_paste == RlBracket::Enabled {
+        // ESC [ 200 ~, followed by the pasted text, followed by ESC [ 201 ~.
+        collect_text(&format!("\x1b[200~{text}\x1b[201~"))
+    } else {
+        collect_text(&text)
+    }
+}
+
+/// Take control characters out of pasted text
+///
+/// A paste then can't run escape sequences or end a bracketed paste early. Tabs are kept, and
+/// line endings become carriage returns, the same as pressing Enter, like xterm does.
+#[must_use]
+pub fn sanitize_paste(text: &str) -> String {
+    text.replace("\r\n", "\r")
+        .chars()
+        .filter_map(|c| match c {
+            '\n' => Some('\r'),
+            '\t' | '\r' => Some(c),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Whether `text` is long enough that the user should confirm it before it is pasted
+#[must_use]
+pub const fn is_large_paste(text: &str) -> bool {
+    text.len() > LARGE_PASTE_BYTES
+}
+
+/// Read a file to paste in to the terminal
+///
+/// # Errors
+/// Will error if the file can't be read, is longer than `MAX_PASTE_FILE_BYTES` or isn't UTF-8 text
+pub fn read_paste_file(path: &Path) -> Result<String> {
+    let len = std::fs::metadata(path)?.len();
+    if len > MAX_PASTE_FILE_BYTES {
+        return Err(anyhow::anyhow!(
+            "{} is {len} bytes, more than the {MAX_PASTE_FILE_BYTES} bytes we will paste",
+            path.display()
+        ));
+    }
+
+    let bytes = std::fs::read(path)?;
+    String::from_utf8(bytes)
+        .map_err(|_| anyhow::anyhow!("{} is not a UTF-8 text file", path.display()))
+}
+
+/// Parse bytes typed in hex, such as `1b 5b 41`, `0x03,0x04` or `1b5b41`, for sending to the
+/// terminal as they are
+///
+/// # Errors
+/// Will error if `text` has anything other than pairs of hex digits, `0x` prefixes, spaces and
+/// commas in it
+pub fn parse_hex_bytes(text: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    for word in text.split(|c: char| c.is_whitespace() || c == ',') {
+        let digits = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+
+        if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow::anyhow!(
+                "Invalid hex bytes {word}. Expected pairs of hex digits such as 1b 5b 41"
+            ));
+        }
+
+        for pair in digits.as_bytes().chunks(2) {
+            let pair = std::str::from_utf8(pair)?;
+            bytes.push(u8::from_str_radix(pair, 16)?);
+        }
+    }
+
+    Ok(bytes)
+}
@@ -3,23 +3,42 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    fmt,
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
 
 use crate::ansi_components::modes::dectcem::Dectcem;
 use crate::format_tracker::FormatTag;
 use crate::io::FreminalPtyInputOutput;
-use crate::io::{FreminalTermInputOutput, FreminalTerminalSize, PtyRead, PtyWrite};
+use crate::io::{
+    corpus::FuzzCorpus,
+    recording::{Recording, RecordingDestination, SharedRecording},
+    FreminalTermInputOutput, FreminalTerminalSize, PtyRead, PtyWrite,
+};
 use crate::state::{
     cursor::CursorPos,
-    data::TerminalSections,
-    internal::{TerminalState, TERMINAL_HEIGHT, TERMINAL_WIDTH},
+    data::{RenderSlice, TerminalSections},
+    echo_prediction::EchoPrediction,
+    internal::TerminalState,
+    output_processor::OutputProcessor,
+    redraw::SharedRedrawNotifier,
     term_char::TChar,
+    term_warning::TermWarning,
 };
 use anyhow::Result;
 use crossbeam_channel::{unbounded, Receiver};
-use eframe::egui;
 
-use freminal_common::args::Args;
+use freminal_common::{
+    args::Args, char_width::WidthProvider, metrics::SharedInputLatency, redaction::Redactor,
+};
 
 const fn char_to_ctrl_code(c: u8) -> u8 {
     // https://catern.com/posts/terminal_quirks.html
@@ -48,9 +67,100 @@ pub fn raw_ascii_bytes_to_terminal_input(buf: &[u8]) -> Cow<'static, [TerminalIn
 pub enum TerminalInputPayload {
     Single(u8),
     Many(&'static [u8]),
+    /// A sequence put together for this key press, such as one with modifiers in it
+    Owned(Vec<u8>),
 }
 
-#[derive(Clone, Debug)]
+impl TerminalInputPayload {
+    /// The bytes to send
+    #[must_use]
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Single(c) => vec![c],
+            Self::Many(bytes) => bytes.to_vec(),
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Shift, Alt and Ctrl held down with a key
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct KeyModifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl KeyModifiers {
+    pub const NONE: Self = Self {
+        shift: false,
+        alt: false,
+        ctrl: false,
+    };
+    pub const SHIFT: Self = Self {
+        shift: true,
+        ..Self::NONE
+    };
+    pub const ALT: Self = Self {
+        alt: true,
+        ..Self::NONE
+    };
+    pub const CTRL: Self = Self {
+        ctrl: true,
+        ..Self::NONE
+    };
+
+    #[must_use]
+    pub const fn is_none(self) -> bool {
+        !self.shift && !self.alt && !self.ctrl
+    }
+
+    /// The parameter xterm adds to a key's sequence for these modifiers: 1, plus 1 for Shift, 2
+    /// for Alt and 4 for Ctrl
+    #[must_use]
+    pub fn parameter(self) -> u8 {
+        1 + u8::from(self.shift) + 2 * u8::from(self.alt) + 4 * u8::from(self.ctrl)
+    }
+}
+
+impl std::ops::BitOr for KeyModifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            shift: self.shift || rhs.shift,
+            alt: self.alt || rhs.alt,
+            ctrl: self.ctrl || rhs.ctrl,
+        }
+    }
+}
+
+/// The numbers in `CSI n ~` the VT220 sends for F5 to F12
+const VT220_FUNCTION_KEY_CODES: [u8; 8] = [15, 17, 18, 19, 20, 21, 23, 24];
+
+/// The sequence for function key `number` pressed on its own. F1 to F4 are the VT100's PF1 to
+/// PF4, which always use SS3. The rest are the VT220's
+fn function_key_payload(number: u8) -> TerminalInputPayload {
+    match number {
+        1 => TerminalInputPayload::Many(b"\x1bOP"),
+        2 => TerminalInputPayload::Many(b"\x1bOQ"),
+        3 => TerminalInputPayload::Many(b"\x1bOR"),
+        4 => TerminalInputPayload::Many(b"\x1bOS"),
+        5..=12 => TerminalInputPayload::Owned(
+            format!(
+                "\x1b[{}~",
+                VT220_FUNCTION_KEY_CODES[usize::from(number - 5)]
+            )
+            .into_bytes(),
+        ),
+        _ => {
+            warn!("Unknown function key: F{number}");
+            TerminalInputPayload::Many(b"")
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum TerminalInput {
     // Normal keypress
     Ascii(u8),
@@ -72,12 +182,30 @@ pub enum TerminalInput {
     Escape,
     InFocus,
     LostFocus,
+    /// A key on the numeric keypad: 0 to 9 for the digits, `b'\n'` for Enter, or the character
+    /// on the key for the rest (`-`, `,`, `.`, `+`, `*`, `/` and `=`)
     KeyPad(u8),
+    /// F1 to F12
+    Function(u8),
+    /// A key pressed with modifiers held down
+    Modified(Box<Self>, KeyModifiers),
+    /// A key press a keyboard protocol the application turned on has already encoded, sent as
+    /// it is
+    Sequence(Vec<u8>),
 }
 
 impl TerminalInput {
+    /// Encode the input for the pty. `decckm_mode` is application cursor keys (DECCKM),
+    /// `keypad_mode` application keypad (DECNKM) and `backarrow_sends_bs` whether Backspace sends
+    /// BS instead of DEL (DECBKM)
     #[must_use]
-    pub fn to_payload(&self, decckm_mode: bool, keypad_mode: bool) -> TerminalInputPayload {
+    #[allow(clippy::too_many_lines)]
+    pub fn to_payload(
+        &self,
+        decckm_mode: bool,
+        keypad_mode: bool,
+        backarrow_sends_bs: bool,
+    ) -> TerminalInputPayload {
         match self {
             Self::Ascii(c) => TerminalInputPayload::Single(*c),
             Self::Ctrl(c) => TerminalInputPayload::Single(char_to_ctrl_code(*c)),
@@ -87,7 +215,13 @@ impl TerminalInput {
             // TODO: really fix this out one.
             Self::Enter => TerminalInputPayload::Single(char_to_ctrl_code(b'm')),
             // Hard to tie back, but check default VERASE in terminfo definition
-            Self::Backspace => TerminalInputPayload::Single(0x7f),
+            Self::Backspace => {
+                if backarrow_sends_bs {
+                    TerminalInputPayload::Single(0x08)
+                } else {
+                    TerminalInputPayload::Single(0x7f)
+                }
+            }
             Self::Escape => TerminalInputPayload::Single(0x1b),
             // https://vt100.net/docs/vt100-ug/chapter3.html
             // Table 3-6
@@ -133,30 +267,43 @@ impl TerminalInput {
                     TerminalInputPayload::Many(b"\x1b[F")
                 }
             }
+            // https://vt100.net/docs/vt100-ug/chapter3.html
+            // Table 3-8, and the VT220's. The operators the VT220 doesn't have are sent the way
+            // xterm sends them
             Self::KeyPad(c) => {
                 if keypad_mode {
-                    TerminalInputPayload::Single(*c)
-                } else {
                     match c {
-                        0 => TerminalInputPayload::Many(b"\x1b[Op"),
-                        1 => TerminalInputPayload::Many(b"\x1b[Oq"),
-                        2 => TerminalInputPayload::Many(b"\x1b[Or"),
-                        3 => TerminalInputPayload::Many(b"\x1b[Os"),
-                        4 => TerminalInputPayload::Many(b"\x1b[Ot"),
-                        5 => TerminalInputPayload::Many(b"\x1b[Ou"),
-                        6 => TerminalInputPayload::Many(b"\x1b[Ov"),
-                        7 => TerminalInputPayload::Many(b"\x1b[Ow"),
-                        8 => TerminalInputPayload::Many(b"\x1b[Ox"),
-                        9 => TerminalInputPayload::Many(b"\x1b[Oy"),
-                        b'-' => TerminalInputPayload::Many(b"\x1b[Om"),
-                        b',' => TerminalInputPayload::Many(b"\x1b[Ol"),
-                        b'.' => TerminalInputPayload::Many(b"\x1b[On"),
-                        b'\n' => TerminalInputPayload::Many(b"\x1b[OM"),
+                        0 => TerminalInputPayload::Many(b"\x1bOp"),
+                        1 => TerminalInputPayload::Many(b"\x1bOq"),
+                        2 => TerminalInputPayload::Many(b"\x1bOr"),
+                        3 => TerminalInputPayload::Many(b"\x1bOs"),
+                        4 => TerminalInputPayload::Many(b"\x1bOt"),
+                        5 => TerminalInputPayload::Many(b"\x1bOu"),
+                        6 => TerminalInputPayload::Many(b"\x1bOv"),
+                        7 => TerminalInputPayload::Many(b"\x1bOw"),
+                        8 => TerminalInputPayload::Many(b"\x1bOx"),
+                        9 => TerminalInputPayload::Many(b"\x1bOy"),
+                        b'-' => TerminalInputPayload::Many(b"\x1bOm"),
+                        b',' => TerminalInputPayload::Many(b"\x1bOl"),
+                        b'.' => TerminalInputPayload::Many(b"\x1bOn"),
+                        b'\n' => TerminalInputPayload::Many(b"\x1bOM"),
+                        b'*' => TerminalInputPayload::Many(b"\x1bOj"),
+                        b'+' => TerminalInputPayload::Many(b"\x1bOk"),
+                        b'/' => TerminalInputPayload::Many(b"\x1bOo"),
+                        b'=' => TerminalInputPayload::Many(b"\x1bOX"),
                         _ => {
                             warn!("Unknown keypad key: {c}");
                             TerminalInputPayload::Single(*c)
                         }
                     }
+                } else if *c <= 9 {
+                    // Digits are sent as themselves
+                    TerminalInputPayload::Single(b'0' + c)
+                } else if *c == b'\n' {
+                    // The same as the main Enter key
+                    TerminalInputPayload::Single(b'\r')
+                } else {
+                    TerminalInputPayload::Single(*c)
                 }
             }
             Self::Tab => TerminalInputPayload::Single(b'\t'),
@@ -170,6 +317,72 @@ impl TerminalInput {
             Self::PageDown => TerminalInputPayload::Many(b"\x1b[6~"),
             Self::LostFocus => TerminalInputPayload::Many(b"\x1b[O"),
             Self::InFocus => TerminalInputPayload::Many(b"\x1b[I"),
+            Self::Function(number) => function_key_payload(*number),
+            Self::Modified(key, modifiers) => {
+                key.modified_payload(*modifiers, decckm_mode, keypad_mode, backarrow_sends_bs)
+            }
+            Self::Sequence(bytes) => TerminalInputPayload::Owned(bytes.clone()),
+        }
+    }
+
+    /// Encode the input with `modifiers` held, the way xterm does
+    ///
+    /// Cursor and editing keys and function keys get the modifiers as a parameter, such as
+    /// `CSI 1 ; 5 C` for Ctrl+Right, whatever DECCKM is set to. Shift+Tab is back tab. Anything
+    /// else is sent as it is without them, after an ESC if Alt is held.
+    fn modified_payload(
+        &self,
+        modifiers: KeyModifiers,
+        decckm_mode: bool,
+        keypad_mode: bool,
+        backarrow_sends_bs: bool,
+    ) -> TerminalInputPayload {
+        let parameter = modifiers.parameter();
+        let letter = |letter: char| {
+            TerminalInputPayload::Owned(format!("\x1b[1;{parameter}{letter}").into_bytes())
+        };
+        let tilde = |number: u8| {
+            TerminalInputPayload::Owned(format!("\x1b[{number};{parameter}~").into_bytes())
+        };
+
+        match self {
+            _ if modifiers.is_none() => {
+                self.to_payload(decckm_mode, keypad_mode, backarrow_sends_bs)
+            }
+            Self::ArrowUp => letter('A'),
+            Self::ArrowDown => letter('B'),
+            Self::ArrowRight => letter('C'),
+            Self::ArrowLeft => letter('D'),
+            Self::Home => letter('H'),
+            Self::End => letter('F'),
+            Self::Function(1) => letter('P'),
+            Self::Function(2) => letter('Q'),
+            Self::Function(3) => letter('R'),
+            Self::Function(4) => letter('S'),
+            Self::Function(n @ 5..=12) => tilde(VT220_FUNCTION_KEY_CODES[usize::from(*n - 5)]),
+            Self::Insert => tilde(2),
+            Self::Delete => tilde(3),
+            Self::PageUp => tilde(5),
+            Self::PageDown => tilde(6),
+            Self::Tab
+                if modifiers
+                    == KeyModifiers {
+                        shift: true,
+                        ..KeyModifiers::default()
+                    } =>
+            {
+                TerminalInputPayload::Many(b"\x1b[Z")
+            }
+            _ => {
+                let payload = self.to_payload(decckm_mode, keypad_mode, backarrow_sends_bs);
+                if modifiers.alt {
+                    let mut bytes = payload.into_bytes();
+                    bytes.insert(0, 0x1b);
+                    TerminalInputPayload::Owned(bytes)
+                } else {
+                    payload
+                }
+            }
         }
     }
 }
@@ -212,11 +425,105 @@ pub fn split_format_data_for_scrollback(
     }
 }
 
+/// Cut `tags` down to the parts on each of `rows` of the buffer
+///
+/// The rows are shown one after another with a newline between each, so the tag at the end of a
+/// row carries on over the newline after it.
+#[must_use]
+pub fn format_data_for_rows(tags: &[FormatTag], rows: &[Range<usize>]) -> Vec<FormatTag> {
+    let mut row_tags = Vec::new();
+    let mut row_start = 0;
+    for (i, row) in rows.iter().enumerate() {
+        let newline = usize::from(i + 1 < rows.len());
+        let shown = row.start..row.end + newline;
+        for tag in tags {
+            let start = tag.start.max(shown.start);
+            let end = tag.end.min(shown.end);
+            if start < end {
+                row_tags.push(FormatTag {
+                    start: row_start + start - row.start,
+                    end: row_start + end - row.start,
+                    ..tag.clone()
+                });
+            }
+        }
+
+        row_start += shown.len();
+    }
+
+    row_tags
+}
+
+/// The recording asked for on the command line, already started. Without one, recordings started
+/// later go to the home directory. Everything recorded is masked with `redactor`
+fn recording_for_args(args: &Args, redactor: Option<Redactor>) -> Recording {
+    let destination = match (&args.recording, &args.recording_dir) {
+        (Some(path), _) => Some(RecordingDestination::File(path.into())),
+        (None, Some(directory)) => Some(RecordingDestination::Directory(directory.into())),
+        (None, None) => None,
+    };
+
+    let start = destination.is_some();
+    let mut recording = destination.map_or_else(Recording::default, Recording::new);
+    if let Some(redactor) = redactor {
+        recording.set_redactor(redactor);
+    }
+
+    if start {
+        if let Err(e) = recording.start() {
+            error!("Failed to start recording: {e}");
+        }
+    }
+
+    recording
+}
+
+/// Masks the secrets the user asked to hide, if they asked to hide any
+fn redactor_for_args(args: &Args) -> Option<Redactor> {
+    if args.redact_patterns.is_empty() {
+        return None;
+    }
+
+    Redactor::new(&args.redact_patterns)
+        .map_err(|e| error!("Failed to set up redaction: {e}"))
+        .ok()
+}
+
+/// Terminals created in this process so far, for handing out IDs
+static NEXT_EMULATOR_INDEX: AtomicU32 = AtomicU32::new(0);
+
+/// Tells terminals apart, even across freminal processes. Shown as `pid-index`, where `index`
+/// counts the terminals this process has created
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct EmulatorId {
+    pub process_id: u32,
+    pub index: u32,
+}
+
+impl EmulatorId {
+    /// An ID no other terminal has
+    #[must_use]
+    pub fn next() -> Self {
+        Self {
+            process_id: std::process::id(),
+            index: NEXT_EMULATOR_INDEX.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl fmt::Display for EmulatorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.process_id, self.index)
+    }
+}
+
 pub struct TerminalEmulator<Io: FreminalTermInputOutput> {
     pub internal: TerminalState,
-    _io: Io,
+    pub id: EmulatorId,
+    io: Io,
+    recording: SharedRecording,
     write_tx: crossbeam_channel::Sender<PtyWrite>,
-    ctx: Option<egui::Context>,
+    redraw_notifier: Option<SharedRedrawNotifier>,
     previous_pass_valid: bool,
 }
 
@@ -229,52 +536,133 @@ impl TerminalEmulator<FreminalPtyInputOutput> {
         let (write_tx, read_rx) = unbounded();
         let (pty_tx, pty_rx) = unbounded();
 
+        let redactor = redactor_for_args(args);
+        let recording = SharedRecording::new(recording_for_args(args, redactor.clone()));
+        let input_latency = SharedInputLatency::new();
+        input_latency.set_enabled(args.measure_input_latency);
         let io = FreminalPtyInputOutput::new(
             read_rx,
             pty_tx,
-            args.recording.clone(),
-            args.shell.clone(),
+            recording.clone(),
+            input_latency.clone(),
+            &args.shell_settings(),
+            args.control_socket.clone(),
         )?;
 
+        // The GUI sizes the window to fit this once it knows the font metrics. Start the pty at
+        // the same size so the shell doesn't see a resize straight away
+        let (width, height) = args
+            .size_limits
+            .clamp(args.geometry.columns, args.geometry.rows);
+
         if let Err(e) = write_tx.send(PtyWrite::Resize(FreminalTerminalSize {
-            width: TERMINAL_WIDTH,
-            height: TERMINAL_HEIGHT,
+            width,
+            height,
             pixel_width: 0,
             pixel_height: 0,
         })) {
             error!("Failed to send resize to pty: {e}");
         }
 
+        let mut internal = TerminalState::new(write_tx.clone());
+        internal.size_limits = args.size_limits;
+        internal.set_win_size(width, height);
+        internal.set_max_fps(args.max_fps);
+        internal.set_max_sequence_length(args.max_sequence_length);
+        internal.word_characters = args.word_characters.clone();
+        internal.url_schemes = args.url_schemes.clone();
+        internal.scroll_settings = args.scroll_settings.clone();
+        internal.cursor_settings = args.cursor_settings.clone();
+        internal.trim_trailing_spaces_on_copy = args.trim_trailing_spaces_on_copy;
+        internal.clamp_colors_to_palette = args.clamp_colors_to_palette;
+        internal.set_palette(args.palette);
+        internal.set_scrollback_lines(args.scrollback_lines);
+        internal.set_alternate_scrollback_lines(args.alternate_scrollback_lines);
+        internal.scrollback_on_clear = !args.clear_in_place;
+        internal.echo_prediction = EchoPrediction::new(args.predict_echo);
+        internal.set_no_wrap_view(args.no_wrap);
+        internal.bidi_enabled = args.bidi;
+        internal.set_width_provider(WidthProvider::new(
+            args.unicode_version,
+            args.ambiguous_wide,
+        ));
+        internal.input_latency = input_latency;
+        if let Some(redactor) = redactor {
+            internal.set_redactor(redactor);
+        }
+        internal.term_warning = TermWarning::new(args.warn_unknown_term);
+        if args.hide_startup_banner {
+            internal.hide_startup_banner();
+        }
+        if let Some(directory) = &args.fuzz_corpus_dir {
+            match FuzzCorpus::new(Path::new(directory)) {
+                Ok(corpus) => internal.set_fuzz_corpus(corpus),
+                Err(e) => error!("Failed to open fuzz corpus directory {directory}: {e}"),
+            }
+        }
+
         let ret = Self {
-            internal: TerminalState::new(write_tx.clone()),
-            _io: io,
+            internal,
+            id: EmulatorId::next(),
+            io,
+            recording,
             write_tx,
-            ctx: None,
+            redraw_notifier: None,
             previous_pass_valid: false,
         };
         Ok((ret, pty_rx))
     }
+
+    /// The name of the program running in the foreground, such as `vim`, if we can tell
+    #[must_use]
+    pub fn foreground_process_name(&self) -> Option<String> {
+        self.io.foreground_process_name()
+    }
+
+    /// Stop the shell and finish writing the recording. Call this once the window has closed
+    pub fn shutdown(&mut self) {
+        info!("Stopping the shell");
+        self.io.shutdown();
+    }
 }
 
 impl<Io: FreminalTermInputOutput> TerminalEmulator<Io> {
-    pub fn set_mouse_position_from_move_event(&mut self, pos: &egui::Pos2) {
-        self.internal.mouse_position = Some(*pos);
+    #[must_use]
+    pub fn is_recording(&self) -> bool {
+        self.recording.lock().is_recording()
+    }
+
+    /// Start or stop recording the shell's output
+    pub fn toggle_recording(&self) {
+        let result = self.recording.lock().toggle();
+        if let Err(e) = result {
+            error!("Failed to start recording: {e}");
+        }
     }
 
-    pub fn set_mouse_position(&mut self, pos: &Option<egui::Vec2>) {
-        // info!("Setting mouse position: {pos:?}");
-        self.internal.mouse_position = pos.map(|pos| egui::Pos2 {
-            x: pos[0],
-            y: pos[1],
-        });
+    /// Whether the shell echoes what is typed, which programs turn off while reading passwords
+    #[must_use]
+    pub fn echoes_input(&self) -> Option<bool> {
+        self.io.echoes_input()
     }
 
-    pub const fn get_mouse_position(&self) -> Option<egui::Pos2> {
+    /// Where the pointer is over the terminal, in points, or `None` once it has left
+    pub fn set_mouse_position(&mut self, pos: Option<(f32, f32)>) {
+        self.internal.mouse_position = pos;
+    }
+
+    pub const fn get_mouse_position(&self) -> Option<(f32, f32)> {
         self.internal.mouse_position
     }
 
-    pub fn is_mouse_hovered_on_url(&mut self, mouse_position: &CursorPos) -> Option<String> {
-        self.internal.is_mouse_hovered_on_url(mouse_position)
+    pub fn is_mouse_hovered_on_url(&mut self, buf_pos: usize) -> Option<String> {
+        self.internal.is_mouse_hovered_on_url(buf_pos)
+    }
+
+    /// Run `processor` on everything parsed from the shell's output before it is shown. See
+    /// `OutputProcessor`
+    pub fn add_output_processor(&mut self, processor: Box<dyn OutputProcessor>) {
+        self.internal.add_output_processor(processor);
     }
 
     pub fn set_window_focused(&mut self, focused: bool) {
@@ -285,18 +673,18 @@ impl<Io: FreminalTermInputOutput> TerminalEmulator<Io> {
         }
     }
 
-    pub fn set_egui_ctx_if_missing(&mut self, ctx: egui::Context) {
-        if self.ctx.is_none() {
-            self.ctx = Some(ctx.clone());
-            self.internal.set_ctx(ctx);
+    pub fn set_redraw_notifier_if_missing(&mut self, redraw_notifier: SharedRedrawNotifier) {
+        if self.redraw_notifier.is_none() {
+            self.redraw_notifier = Some(Arc::clone(&redraw_notifier));
+            self.internal.set_redraw_notifier(redraw_notifier);
         }
     }
 
     pub fn request_redraw(&mut self) {
         debug!("Terminal Emulator: Requesting redraw");
         self.previous_pass_valid = false;
-        if let Some(ctx) = &self.ctx {
-            ctx.request_repaint();
+        if let Some(redraw_notifier) = &self.redraw_notifier {
+            redraw_notifier.request_redraw();
         }
     }
 
@@ -306,7 +694,15 @@ impl<Io: FreminalTermInputOutput> TerminalEmulator<Io> {
     pub fn set_previous_pass_valid(&mut self) {
         self.previous_pass_valid = true;
     }
+
+    /// Let the repaint limiter know a frame was just drawn
+    pub fn frame_rendered(&mut self) {
+        self.internal.repaint_limiter.frame_rendered();
+    }
     pub fn needs_redraw(&mut self) -> bool {
+        self.internal.release_stuck_sync_update();
+        self.internal.release_startup_banner();
+
         let internal = if self.internal.is_changed() {
             self.internal.clear_changed();
             true
@@ -321,10 +717,11 @@ impl<Io: FreminalTermInputOutput> TerminalEmulator<Io> {
         self.internal.get_win_size()
     }
 
-    /// Set the window title
+    /// Resize the terminal to fit the window, within the configured size limits, and tell the
+    /// pty if the size changed
     ///
     /// # Errors
-    /// Will error if the terminal cannot be locked
+    /// Will error if the resize can't be sent to the pty
     pub fn set_win_size(
         &mut self,
         width_chars: usize,
@@ -332,6 +729,8 @@ impl<Io: FreminalTermInputOutput> TerminalEmulator<Io> {
         font_pixel_width: usize,
         font_pixel_height: usize,
     ) -> Result<()> {
+        let (width_chars, height_chars) =
+            self.internal.size_limits.clamp(width_chars, height_chars);
         let response = self.internal.set_win_size(width_chars, height_chars);
 
         if response.changed {
@@ -360,13 +759,12 @@ impl<Io: FreminalTermInputOutput> TerminalEmulator<Io> {
         self.internal.data(include_scrollback)
     }
 
-    pub fn data_and_format_data_for_gui(
-        &mut self,
-    ) -> (
-        TerminalSections<Vec<TChar>>,
-        TerminalSections<Vec<FormatTag>>,
-    ) {
-        self.internal.data_and_format_data_for_gui()
+    pub fn render_slice(&mut self) -> RenderSlice {
+        self.internal.render_slice()
+    }
+
+    pub fn visible_line_timestamps(&mut self) -> Vec<Option<SystemTime>> {
+        self.internal.visible_line_timestamps()
     }
 
     pub fn cursor_pos(&mut self) -> CursorPos {
@@ -0,0 +1,70 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+
+use crate::ansi_components::capture::InvalidSequence;
+
+/// Most files we will write in one session, so a program spewing garbage can't fill the disk
+pub const MAX_CORPUS_FILES: usize = 1000;
+
+/// Saves sequences the parser couldn't handle to a directory, one file each
+///
+/// Each file holds the raw bytes of the sequence with a little of the output before it, so they
+/// can be replayed while debugging or used to seed a fuzzer. A sequence is only saved once per
+/// session.
+#[derive(Debug)]
+pub struct FuzzCorpus {
+    directory: PathBuf,
+    seen: HashSet<Vec<u8>>,
+}
+
+impl FuzzCorpus {
+    /// # Errors
+    /// Will return an error if the directory doesn't exist and can't be created
+    pub fn new(directory: &Path) -> Result<Self> {
+        std::fs::create_dir_all(directory)?;
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Save `invalid`, unless we've already seen it or saved as many as we are allowed. Returns
+    /// where it was saved
+    ///
+    /// # Errors
+    /// Will return an error if the file couldn't be written
+    pub fn save(&mut self, invalid: &InvalidSequence) -> Result<Option<PathBuf>> {
+        if self.seen.len() >= MAX_CORPUS_FILES || self.seen.contains(&invalid.sequence) {
+            return Ok(None);
+        }
+
+        let path = self.directory.join(corpus_file_name(self.seen.len()));
+        std::fs::write(&path, invalid.to_bytes())?;
+        self.seen.insert(invalid.sequence.clone());
+
+        Ok(Some(path))
+    }
+}
+
+fn corpus_file_name(index: usize) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    format!(
+        "invalid-sequence-{timestamp}-{}-{index}.bin",
+        std::process::id()
+    )
+}
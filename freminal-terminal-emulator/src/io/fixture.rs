@@ -0,0 +1,160 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{fmt::Write as _, path::Path, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+use super::recording::parse_recording;
+use crate::state::internal::TerminalState;
+
+/// Size of the pty fixtures are recorded in, unless another is asked for
+pub const DEFAULT_FIXTURE_COLUMNS: usize = 80;
+pub const DEFAULT_FIXTURE_ROWS: usize = 24;
+
+/// One read of a program's output from its pty
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FixtureChunk {
+    /// How long after the program started the output arrived
+    pub at: Duration,
+    pub data: Vec<u8>,
+}
+
+/// Output a real program wrote to a pty, kept in `tests/fixtures` to test the parser against
+///
+/// Fixtures are recorded with `cargo xtask record-fixture`. The file is text, so changes to it
+/// show up in a diff:
+///
+/// ```text
+/// size 80x24
+/// 0 27,91,72,
+/// 15 104,105,13,10,
+/// ```
+///
+/// The first line is the size of the pty. Each line after it is one read: the milliseconds since
+/// the program started, then the bytes as a `Recording` writes them. Replaying ignores the times,
+/// they are kept to show how the output was split up and how long the program paused.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Fixture {
+    pub columns: usize,
+    pub rows: usize,
+    pub chunks: Vec<FixtureChunk>,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Self::new(DEFAULT_FIXTURE_COLUMNS, DEFAULT_FIXTURE_ROWS)
+    }
+}
+
+impl Fixture {
+    #[must_use]
+    pub const fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            rows,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Add output the program wrote `at` this long after it started. Empty reads are left out
+    pub fn push(&mut self, at: Duration, data: &[u8]) {
+        if !data.is_empty() {
+            self.chunks.push(FixtureChunk {
+                at,
+                data: data.to_vec(),
+            });
+        }
+    }
+
+    /// Everything the program wrote, in one piece
+    #[must_use]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| chunk.data.iter().copied())
+            .collect()
+    }
+
+    /// Read a fixture file
+    ///
+    /// # Errors
+    /// Will return an error if the file can't be read or isn't a fixture
+    pub fn read(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| anyhow!("{}: {e}", path.display()))
+    }
+
+    /// Write the fixture to `path`, replacing anything already there
+    ///
+    /// # Errors
+    /// Will return an error if the file can't be written
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    /// The fixture in the form it is kept in a file
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let mut text = format!("size {}x{}\n", self.columns, self.rows);
+        for chunk in &self.chunks {
+            let _ = write!(text, "{} ", chunk.at.as_millis());
+            for byte in &chunk.data {
+                let _ = write!(text, "{byte},");
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Feed the output through `terminal_state`, as if the program were running in it, after
+    /// making it the size the fixture was recorded at
+    pub fn replay(&self, terminal_state: &mut TerminalState) {
+        terminal_state.set_win_size(self.columns, self.rows);
+        for chunk in &self.chunks {
+            terminal_state.handle_incoming_data(&chunk.data);
+        }
+    }
+}
+
+impl FromStr for Fixture {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line));
+
+        let size = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix("size "))
+            .ok_or_else(|| anyhow!("line 1: expected the size, such as `size 80x24`"))?;
+        let (columns, rows) = size
+            .split_once('x')
+            .and_then(|(columns, rows)| Some((columns.parse().ok()?, rows.parse().ok()?)))
+            .ok_or_else(|| anyhow!("line 1: invalid size {size}"))?;
+
+        let mut fixture = Self::new(columns, rows);
+        for (line_number, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (at, data) = line.split_once(' ').unwrap_or((line, ""));
+            let at = at
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| anyhow!("line {line_number}: invalid time {at}"))?;
+            let data = parse_recording(data).map_err(|e| anyhow!("line {line_number}: {e}"))?;
+            fixture.push(at, &data);
+        }
+
+        Ok(fixture)
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use portable_pty::MasterPty;
+
+/// The name of the program in the pty's foreground process group, such as `vim` or `ssh`
+///
+/// Only unix ptys have a foreground process group to ask about.
+#[must_use]
+pub fn foreground_process_name(master: &dyn MasterPty) -> Option<String> {
+    #[cfg(unix)]
+    {
+        master
+            .process_group_leader()
+            .and_then(|leader| u32::try_from(leader).ok())
+            .and_then(process_name)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = master;
+        None
+    }
+}
+
+/// Whether the pty echoes what is typed. Programs turn this off while reading passwords
+///
+/// `None` where there is no termios to ask.
+#[must_use]
+pub fn echoes_input(master: &dyn MasterPty) -> Option<bool> {
+    #[cfg(unix)]
+    {
+        use nix::sys::termios::LocalFlags;
+
+        master
+            .get_termios()
+            .map(|termios| termios.local_flags.contains(LocalFlags::ECHO))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = master;
+        None
+    }
+}
+
+/// The name of process `pid`, without its path
+#[cfg(target_os = "linux")]
+#[must_use]
+pub fn process_name(pid: u32) -> Option<String> {
+    let name = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// The name of process `pid`, without its path
+///
+/// There is no `/proc` to read here, so we ask `ps`.
+#[cfg(all(unix, not(target_os = "linux")))]
+#[must_use]
+pub fn process_name(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let command = String::from_utf8_lossy(&output.stdout);
+    let name = command.trim().rsplit('/').next()?;
+    (!name.is_empty()).then(|| name.to_string())
+}
@@ -3,9 +3,16 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+pub mod corpus;
+pub mod fixture;
+pub mod foreground;
 mod pty;
+pub mod recording;
+use std::time::Duration;
+
 use anyhow::{Error, Result};
 use conv::ValueFrom;
+use crossbeam_channel::Receiver;
 use portable_pty::PtySize;
 pub use pty::FreminalPtyInputOutput;
 // pub type TermIoErr = Box<dyn std::error::Error>;
@@ -41,14 +48,73 @@ pub struct PtyRead {
     pub read_amount: usize,
 }
 
+/// While the window is hidden, output is left to pile up for this long and then parsed in one go
+pub const BACKGROUND_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The most output parsed in one go while the window is hidden
+pub const BACKGROUND_BATCH_BYTES: usize = 1024 * 1024;
+
+/// Join `first` and whatever output is already waiting in `rx` in to one batch, stopping once it
+/// is `limit` bytes or more. Parsing one big batch is much cheaper than thousands of small reads
+#[must_use]
+pub fn coalesce_reads(first: &PtyRead, rx: &Receiver<PtyRead>, limit: usize) -> Vec<u8> {
+    let mut batch = first.buf[..first.read_amount].to_vec();
+
+    while batch.len() < limit {
+        let Ok(read) = rx.try_recv() else {
+            break;
+        };
+
+        batch.extend_from_slice(&read.buf[..read.read_amount]);
+    }
+
+    batch
+}
+
+/// What the keys for interrupting, suspending and quitting do, sent to the program in the
+/// foreground whatever it has done to the keyboard
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PtySignal {
+    /// SIGINT, usually Ctrl+C
+    Interrupt,
+    /// SIGTSTP, usually Ctrl+Z
+    Suspend,
+    /// SIGQUIT, usually Ctrl+\
+    Quit,
+    /// End of file, usually Ctrl+D. This isn't a signal, so the program only sees it if the
+    /// terminal is reading lines
+    EndOfFile,
+}
+
+impl PtySignal {
+    /// The control character that usually sends this. Written instead of the signal where we
+    /// can't signal the program directly
+    #[must_use]
+    pub const fn control_char(self) -> u8 {
+        match self {
+            Self::Interrupt => 0x03,
+            Self::Suspend => 0x1a,
+            Self::Quit => 0x1c,
+            Self::EndOfFile => 0x04,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum PtyWrite {
     Write(Vec<u8>),
     Resize(FreminalTerminalSize),
+    Signal(PtySignal),
 }
 
 pub trait FreminalTermInputOutput {
     // fn read(&mut self, buf: &mut [u8]);
     // fn write(&mut self, buf: &[u8]);
     // fn set_win_size(&mut self, width: usize, height: usize);
+
+    /// Whether what is typed is echoed back, if we can tell. Programs turn this off while
+    /// reading passwords
+    fn echoes_input(&self) -> Option<bool> {
+        None
+    }
 }
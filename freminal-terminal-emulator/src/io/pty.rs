@@ -3,17 +3,39 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
-use std::{io::Write, path::Path};
+use std::{
+    io::{Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
 
-use super::{FreminalTermInputOutput, PtyRead, PtyWrite};
+use super::{
+    foreground::{echoes_input, foreground_process_name},
+    recording::SharedRecording,
+    FreminalTermInputOutput, PtyRead, PtySignal, PtyWrite,
+};
 use anyhow::Result;
 use crossbeam_channel::{Receiver, Sender};
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use freminal_common::{
+    control::CONTROL_SOCKET_ENV, metrics::SharedInputLatency, shell::ShellSettings,
+};
+use portable_pty::{
+    default_shell, Child, CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem,
+};
 use tempfile::TempDir;
 use thiserror::Error;
 
+/// How long we give the shell to exit, and the last of its output to be recorded, when we close
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
 pub struct FreminalPtyInputOutput {
     _termcaps: TempDir,
+    shell: RunningShell,
+    shutting_down: Arc<AtomicBool>,
 }
 
 const TERMINFO: &[u8] = include_bytes!(std::concat!(std::env!("OUT_DIR"), "/terminfo.tar"));
@@ -36,13 +58,37 @@ enum ExtractTerminfoError {
     CreateTempDir(#[source] std::io::Error),
 }
 
+/// The shell to run, with the environment it needs to find our terminfo and control socket
+fn shell_command(
+    shell: &ShellSettings,
+    control_socket: Option<String>,
+    termcaps: &Path,
+) -> CommandBuilder {
+    let mut cmd = CommandBuilder::from_argv(
+        shell
+            .command_line(default_shell)
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    );
+
+    cmd.env("TERMINFO", termcaps);
+    cmd.env("TERM", "freminal");
+    if let Some(control_socket) = control_socket {
+        cmd.env(CONTROL_SOCKET_ENV, control_socket);
+    }
+
+    cmd
+}
+
 pub fn run_terminal(
     write_rx: Receiver<PtyWrite>,
     send_tx: Sender<PtyRead>,
-    recording_path: Option<String>,
-    shell: Option<String>,
-    termcaps: &Path,
-) -> Result<()> {
+    recording: SharedRecording,
+    input_latency: SharedInputLatency,
+    command: CommandBuilder,
+    shutting_down: &Arc<AtomicBool>,
+) -> Result<RunningShell> {
     let pty_system = NativePtySystem::default();
 
     let pair = match pty_system.openpty(PtySize {
@@ -58,11 +104,7 @@ pub fn run_terminal(
         }
     };
 
-    let mut cmd = shell.map_or_else(CommandBuilder::new_default_prog, CommandBuilder::new);
-
-    cmd.env("TERMINFO", termcaps);
-    cmd.env("TERM", "freminal");
-    let _child = pair.slave.spawn_command(cmd)?;
+    let child = pair.slave.spawn_command(command)?;
 
     // Release any handles owned by the slave: we don't need it now
     // that we've spawned the child.
@@ -74,49 +116,18 @@ pub fn run_terminal(
     // or the spawned process.
     let mut reader = pair.master.try_clone_reader()?;
 
-    std::thread::spawn(move || {
-        let buf = &mut [0u8; 4096];
-        let mut recording = None;
-        // if recording path is some, open a file for writing
-        if let Some(path) = &recording_path {
-            recording = match std::fs::File::create(path) {
-                Ok(file) => Some(file),
-                Err(e) => {
-                    error!("Failed to create recording file: {e}");
-                    None
-                }
-            }
-        }
+    // The writer thread resizes and signals through the master, and the GUI asks it what is
+    // running
+    let master = Arc::new(Mutex::new(pair.master));
+    let writer_master = Arc::clone(&master);
 
-        // Consume the output from the child
-        while let Ok(amount_read) = reader.read(buf) {
-            if amount_read == 0 {
-                // PTY closed, exit(0)
-                std::process::exit(0);
-            }
-            let data = buf[..amount_read].to_vec();
-
-            // if recording is some, write to the file
+    let (reader_done_tx, reader_done) = crossbeam_channel::bounded(1);
+    let reader_shutting_down = Arc::clone(shutting_down);
 
-            if let Some(file) = &mut recording {
-                for byte in &data {
-                    if let Err(e) = file.write_all(format!("{byte},").as_bytes()) {
-                        error!("Failed to write to recording file: {e}");
-                        // exit
-                        std::process::exit(1);
-                    }
-                }
-            }
-
-            if let Err(e) = send_tx.send(PtyRead {
-                buf: data,
-                read_amount: amount_read,
-            }) {
-                error!("Failed to send data to terminal: {e}");
-                // exit
-                std::process::exit(1);
-            }
-        }
+    std::thread::spawn(move || {
+        read_from_pty(reader.as_mut(), &send_tx, &recording, &reader_shutting_down);
+        // Nothing to do if nobody is waiting for us
+        let _ = reader_done_tx.send(());
     });
 
     {
@@ -134,7 +145,9 @@ pub fn run_terminal(
                 std::thread::sleep(std::time::Duration::from_millis(20));
             }
 
-            let mut writer = match pair.master.take_writer() {
+            let lock_master = || writer_master.lock().unwrap_or_else(PoisonError::into_inner);
+            let writer = lock_master().take_writer();
+            let mut writer = match writer {
                 Ok(writer) => writer,
                 Err(e) => {
                     error!("Failed to take writer: {e}");
@@ -145,12 +158,22 @@ pub fn run_terminal(
             while let Ok(stuff_to_write) = write_rx.recv() {
                 match stuff_to_write {
                     PtyWrite::Write(data) => match writer.write_all(&data) {
-                        Ok(()) => {}
+                        Ok(()) => {
+                            if input_latency.enabled() {
+                                input_latency.lock().written(Instant::now());
+                            }
+                        }
                         Err(e) => {
                             error!("Failed to write to pty: {e}");
                             continue;
                         }
                     },
+                    PtyWrite::Signal(signal) => {
+                        let sent = send_signal(lock_master().as_ref(), writer.as_mut(), signal);
+                        if let Err(e) = sent {
+                            error!("Failed to send {signal:?} to the foreground program: {e}");
+                        }
+                    }
                     PtyWrite::Resize(size) => {
                         let size: PtySize = match PtySize::try_from(size) {
                             Ok(size) => size,
@@ -162,7 +185,8 @@ pub fn run_terminal(
 
                         debug!("resizing pty to {size:?}");
 
-                        match pair.master.resize(size) {
+                        let resized = lock_master().resize(size);
+                        match resized {
                             Ok(()) => {}
                             Err(e) => {
                                 error!("Failed to resize pty: {e}");
@@ -175,10 +199,111 @@ pub fn run_terminal(
         });
     }
 
+    Ok(RunningShell {
+        child,
+        master,
+        reader_done,
+    })
+}
+
+/// Send `signal` to the pty's foreground process group
+///
+/// Signals are sent directly rather than as their control character, so they still work when a
+/// program has turned the characters off or handles them itself. End of file isn't a signal, so
+/// it is always written, as whatever character the pty is using for it.
+#[cfg(unix)]
+fn send_signal(master: &dyn MasterPty, writer: &mut dyn Write, signal: PtySignal) -> Result<()> {
+    use nix::{
+        sys::{
+            signal::{killpg, Signal},
+            termios::SpecialCharacterIndices,
+        },
+        unistd::Pid,
+    };
+
+    let unix_signal = match signal {
+        PtySignal::Interrupt => Signal::SIGINT,
+        PtySignal::Suspend => Signal::SIGTSTP,
+        PtySignal::Quit => Signal::SIGQUIT,
+        PtySignal::EndOfFile => {
+            let eof = master.get_termios().map_or_else(
+                || signal.control_char(),
+                |termios| termios.control_chars[SpecialCharacterIndices::VEOF as usize],
+            );
+            writer.write_all(&[eof])?;
+            return Ok(());
+        }
+    };
+
+    match master.process_group_leader() {
+        Some(group) => killpg(Pid::from_raw(group), unix_signal)?,
+        None => writer.write_all(&[signal.control_char()])?,
+    }
+
     Ok(())
 }
 
-impl FreminalTermInputOutput for FreminalPtyInputOutput {}
+/// `ConPTY` turns the control characters in to console events itself
+#[cfg(not(unix))]
+fn send_signal(_master: &dyn MasterPty, writer: &mut dyn Write, signal: PtySignal) -> Result<()> {
+    writer.write_all(&[signal.control_char()])?;
+    Ok(())
+}
+
+/// Pass everything the shell writes on to the terminal, and to the recording if there is one
+///
+/// Returns once the shell closes the pty. Unless we are shutting down, that means the shell exited
+/// and so do we.
+fn read_from_pty(
+    reader: &mut dyn Read,
+    send_tx: &Sender<PtyRead>,
+    recording: &SharedRecording,
+    shutting_down: &AtomicBool,
+) {
+    let buf = &mut [0u8; 4096];
+
+    // Consume the output from the child
+    while let Ok(amount_read) = reader.read(buf) {
+        if amount_read == 0 {
+            break;
+        }
+        let data = buf[..amount_read].to_vec();
+
+        recording.lock().write(&data);
+
+        if let Err(e) = send_tx.send(PtyRead {
+            buf: data,
+            read_amount: amount_read,
+        }) {
+            error!("Failed to send data to terminal: {e}");
+            // exit
+            std::process::exit(1);
+        }
+    }
+
+    if !shutting_down.load(Ordering::Acquire) {
+        // PTY closed, exit(0)
+        std::process::exit(0);
+    }
+}
+
+/// The shell we started, and a way to find out when we've read the last of its output
+pub struct RunningShell {
+    child: Box<dyn Child + Send + Sync>,
+    master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
+    reader_done: Receiver<()>,
+}
+
+impl FreminalTermInputOutput for FreminalPtyInputOutput {
+    fn echoes_input(&self) -> Option<bool> {
+        let master = self
+            .shell
+            .master
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        echoes_input(master.as_ref())
+    }
+}
 
 impl FreminalPtyInputOutput {
     /// Create a new `FreminalPtyInputOutput` instance.
@@ -188,17 +313,67 @@ impl FreminalPtyInputOutput {
     pub fn new(
         write_rx: Receiver<PtyWrite>,
         send_tx: Sender<PtyRead>,
-        recording: Option<String>,
-        shell: Option<String>,
+        recording: SharedRecording,
+        input_latency: SharedInputLatency,
+        shell: &ShellSettings,
+        control_socket: Option<String>,
     ) -> Result<Self> {
         let termcaps = extract_terminfo().unwrap_or_else(|e| {
             error!("Failed to extract terminfo: {e}");
             std::process::exit(1);
         });
 
-        run_terminal(write_rx, send_tx, recording, shell, termcaps.path())?;
+        let shutting_down = Arc::new(AtomicBool::new(false));
+        let shell = run_terminal(
+            write_rx,
+            send_tx,
+            recording,
+            input_latency,
+            shell_command(shell, control_socket, termcaps.path()),
+            &shutting_down,
+        )?;
         Ok(Self {
             _termcaps: termcaps,
+            shell,
+            shutting_down,
         })
     }
+
+    /// The name of the program running in the foreground, such as `vim`, if we can tell
+    #[must_use]
+    pub fn foreground_process_name(&self) -> Option<String> {
+        let master = self
+            .shell
+            .master
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        foreground_process_name(master.as_ref())
+    }
+
+    /// Hang up on the shell and wait briefly for it to go, so the recording gets everything it
+    /// wrote
+    ///
+    /// On unix the shell gets SIGHUP, the same as when a terminal is closed, and is only killed
+    /// if it is still running after a grace period.
+    pub fn shutdown(&mut self) {
+        self.shutting_down.store(true, Ordering::Release);
+
+        match self.shell.child.try_wait() {
+            Ok(Some(_)) => (),
+            _ => {
+                if let Err(e) = self.shell.child.kill() {
+                    warn!("Failed to stop the shell: {e}");
+                }
+            }
+        }
+
+        if self
+            .shell
+            .reader_done
+            .recv_timeout(SHUTDOWN_TIMEOUT)
+            .is_err()
+        {
+            warn!("Timed out waiting for the last of the shell's output");
+        }
+    }
 }
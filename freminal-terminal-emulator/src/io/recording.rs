@@ -0,0 +1,243 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use freminal_common::redaction::{Redactor, StreamRedactor};
+
+/// Where recordings of the shell's output go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingDestination {
+    /// Everything goes to one file. Stopping and starting again appends to it
+    File(PathBuf),
+    /// Each recording gets a new file in this directory, named for when it started
+    Directory(PathBuf),
+}
+
+impl Default for RecordingDestination {
+    /// The user's home directory, falling back to the current directory if we can't find it
+    fn default() -> Self {
+        Self::Directory(std::env::var_os("HOME").map_or_else(|| PathBuf::from("."), PathBuf::from))
+    }
+}
+
+/// Records everything the shell writes, so it can be played back later
+///
+/// Recording can be started and stopped while the terminal is running. Each byte is written as
+/// its decimal value followed by a comma.
+#[derive(Debug, Default)]
+pub struct Recording {
+    destination: RecordingDestination,
+    writer: Option<BufWriter<File>>,
+    path: Option<PathBuf>,
+    /// How many recordings this session has started. Keeps directory recordings started in the
+    /// same second apart
+    started: usize,
+    /// Masks secrets before they are written
+    redactor: Option<StreamRedactor>,
+}
+
+impl Recording {
+    #[must_use]
+    pub fn new(destination: RecordingDestination) -> Self {
+        Self {
+            destination,
+            ..Self::default()
+        }
+    }
+
+    /// Mask text `redactor` matches in everything recorded from now on
+    ///
+    /// The end of each write that could be the start of a secret is held back until the next
+    /// write, or until the recording is stopped.
+    pub fn set_redactor(&mut self, redactor: Redactor) {
+        self.redactor = Some(StreamRedactor::new(redactor));
+    }
+
+    #[must_use]
+    pub const fn is_recording(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// The file currently being recorded to
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Start a new recording. Does nothing if we are already recording
+    ///
+    /// # Errors
+    /// Will return an error if the recording file can't be created
+    pub fn start(&mut self) -> Result<()> {
+        if self.is_recording() {
+            return Ok(());
+        }
+
+        let (path, file) = match &self.destination {
+            RecordingDestination::File(path) => {
+                // Only the first recording starts the file afresh
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .append(self.started > 0)
+                    .truncate(self.started == 0)
+                    .open(path)?;
+                (path.clone(), file)
+            }
+            RecordingDestination::Directory(directory) => {
+                std::fs::create_dir_all(directory)?;
+                let path = directory.join(recording_file_name(self.started));
+                let file = File::create_new(&path)?;
+                (path, file)
+            }
+        };
+
+        info!("Recording to {}", path.display());
+        self.started += 1;
+        self.writer = Some(BufWriter::new(file));
+        self.path = Some(path);
+
+        Ok(())
+    }
+
+    /// Stop recording. The file is complete once this returns
+    pub fn stop(&mut self) {
+        if let Some(held) = self.redactor.as_mut().map(StreamRedactor::finish) {
+            self.write_bytes(&held);
+        }
+
+        if let Some(mut writer) = self.writer.take() {
+            if let Err(e) = writer.flush() {
+                error!("Failed to flush recording: {e}");
+            }
+        }
+
+        if let Some(path) = self.path.take() {
+            info!("Stopped recording to {}", path.display());
+        }
+    }
+
+    /// Start recording if we aren't, and stop if we are
+    ///
+    /// # Errors
+    /// Will return an error if a new recording file can't be created
+    pub fn toggle(&mut self) -> Result<()> {
+        if self.is_recording() {
+            self.stop();
+            Ok(())
+        } else {
+            self.start()
+        }
+    }
+
+    /// Record output from the shell. Does nothing if we aren't recording
+    ///
+    /// The data is flushed straight away so the recording is complete even if we don't get to
+    /// shut down cleanly, apart from anything the redactor is holding back. If writing fails the
+    /// recording is stopped.
+    pub fn write(&mut self, data: &[u8]) {
+        if self.writer.is_none() {
+            return;
+        }
+
+        match &mut self.redactor {
+            Some(redactor) => {
+                let data = redactor.push(data);
+                self.write_bytes(&data);
+            }
+            None => self.write_bytes(data),
+        }
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) {
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+
+        let written = data
+            .iter()
+            .try_for_each(|byte| writer.write_all(format!("{byte},").as_bytes()))
+            .and_then(|()| writer.flush());
+
+        if let Err(e) = written {
+            error!("Failed to write to recording file: {e}. Stopping recording");
+            self.writer = None;
+            self.path = None;
+            // Nothing held back belongs in the next recording
+            if let Some(redactor) = &mut self.redactor {
+                redactor.finish();
+            }
+        }
+    }
+}
+
+/// A recording shared between the pty reader, which writes to it, and the GUI, which starts and
+/// stops it
+#[derive(Debug, Clone, Default)]
+pub struct SharedRecording(Arc<Mutex<Recording>>);
+
+impl SharedRecording {
+    #[must_use]
+    pub fn new(recording: Recording) -> Self {
+        Self(Arc::new(Mutex::new(recording)))
+    }
+
+    /// A panic while recording leaves nothing half done that we care about, so a poisoned lock
+    /// is used as-is
+    pub fn lock(&self) -> MutexGuard<'_, Recording> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The bytes in a recording, as `Recording` wrote them, so they can be played back
+///
+/// # Errors
+/// Will return an error if the file can't be read or isn't a recording
+pub fn read_recording(path: &Path) -> Result<Vec<u8>> {
+    parse_recording(&std::fs::read_to_string(path)?)
+}
+
+/// The bytes in the text of a recording: decimal values, each followed by a comma
+///
+/// # Errors
+/// Will return an error naming the first value that isn't a byte
+pub fn parse_recording(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim_end();
+    let text = text.strip_suffix(',').unwrap_or(text);
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    text.split(',')
+        .enumerate()
+        .map(|(index, value)| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Value {index} of the recording, {value:?}, isn't a byte"))
+        })
+        .collect()
+}
+
+/// Named for the time and the process so recordings from several sessions can share a directory
+fn recording_file_name(index: usize) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    format!(
+        "freminal-recording-{timestamp}-{}-{index}.rec",
+        std::process::id()
+    )
+}
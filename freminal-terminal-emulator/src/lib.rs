@@ -20,8 +20,11 @@ pub mod ansi;
 pub mod ansi_components;
 pub mod error;
 pub mod format_tracker;
+pub mod input;
 pub mod interface;
 pub mod io;
+#[cfg(feature = "parser")]
+pub mod parser;
 // pub mod playback;
 pub mod state;
 
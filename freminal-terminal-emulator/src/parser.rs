@@ -0,0 +1,37 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Freminal's escape sequence parser, for use without the rest of the terminal
+//!
+//! Everything re-exported here is a stable API: it only changes in a breaking way with a new
+//! major version of this crate. The enums are `#[non_exhaustive]`, so new variants and modes can
+//! be added as support for more sequences is added without breaking anyone matching on them. The
+//! modules these are re-exported from are the emulator's internals, and can change in any
+//! release, as can `FreminalAnsiParser::inner`.
+//!
+//! This module is behind the `parser` feature, which is on by default. Turning the feature off
+//! only removes this module, the emulator itself does not depend on it.
+//!
+//! ```
+//! use freminal_terminal_emulator::parser::{FreminalAnsiParser, TerminalOutput};
+//!
+//! let mut parser = FreminalAnsiParser::new();
+//!
+//! // A sequence split across two reads is put back together
+//! assert_eq!(parser.push(b"hi\x1b["), vec![TerminalOutput::Data(b"hi".to_vec())]);
+//! assert_eq!(parser.push(b"2J"), vec![TerminalOutput::ClearDisplay]);
+//! ```
+
+pub use crate::{
+    ansi::{FreminalAnsiParser, TerminalOutput},
+    ansi_components::{
+        capture::InvalidSequence,
+        line_draw::DecSpecialGraphics,
+        mode::{Mode, MouseTrack, SetMode},
+        osc::AnsiOscType,
+        sgr::SelectGraphicRendition,
+    },
+};
+pub use freminal_common::{cursor::CursorVisualStyle, window_manipulation::WindowManipulation};
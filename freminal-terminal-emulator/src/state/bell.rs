@@ -0,0 +1,89 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+/// Tracks bells that ring and output that arrives while the window doesn't have focus, so the
+/// GUI can show a badge and ask for the user's attention
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct BellState {
+    unfocused_bells: usize,
+    unfocused_activity: bool,
+    attention_requested: bool,
+    attention_pending: bool,
+}
+
+impl BellState {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            unfocused_bells: 0,
+            unfocused_activity: false,
+            attention_requested: false,
+            attention_pending: false,
+        }
+    }
+
+    /// Record a bell. Bells while the window has focus are not counted
+    pub fn ring(&mut self, window_focused: bool) {
+        if window_focused {
+            return;
+        }
+
+        self.unfocused_bells = self.unfocused_bells.saturating_add(1);
+        self.request_attention();
+    }
+
+    /// Record output from the program. Output while the window has focus is not counted
+    pub fn output(&mut self, window_focused: bool) {
+        if window_focused {
+            return;
+        }
+
+        self.unfocused_activity = true;
+        self.request_attention();
+    }
+
+    /// Only ask for attention once until the user comes back to the window
+    const fn request_attention(&mut self) {
+        if !self.attention_requested {
+            self.attention_requested = true;
+            self.attention_pending = true;
+        }
+    }
+
+    /// The user has looked at the window, so forget about anything that happened while they were away
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    #[must_use]
+    pub const fn unfocused_bells(&self) -> usize {
+        self.unfocused_bells
+    }
+
+    /// Whether there was any output while the window was unfocused
+    #[must_use]
+    pub const fn unfocused_activity(&self) -> bool {
+        self.unfocused_activity
+    }
+
+    /// Whether the GUI should ask for the user's attention. Only returns true once each time the
+    /// window loses focus
+    pub fn take_attention_request(&mut self) -> bool {
+        std::mem::take(&mut self.attention_pending)
+    }
+}
+
+/// Prefix `title` with the number of bells that rang while the window was unfocused, or with `*`
+/// if there was output but no bells
+#[must_use]
+pub fn badged_title(title: &str, bells: usize, activity: bool) -> String {
+    if bells > 0 {
+        format!("({bells}) {title}")
+    } else if activity {
+        format!("(*) {title}")
+    } else {
+        title.to_string()
+    }
+}
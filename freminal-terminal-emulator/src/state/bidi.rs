@@ -0,0 +1,317 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::ops::Range;
+
+use crate::format_tracker::FormatTag;
+
+use super::term_char::TChar;
+
+/// The parts of the Unicode bidirectional character types we act on
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BidiClass {
+    /// Left to right letters, and anything we don't otherwise know about
+    L,
+    /// Right to left letters, such as Hebrew
+    R,
+    /// Arabic letters
+    AL,
+    /// European digits
+    EN,
+    /// Arabic-Indic digits
+    AN,
+    /// Whitespace
+    WS,
+    /// Punctuation and symbols
+    ON,
+}
+
+impl BidiClass {
+    #[must_use]
+    pub const fn of(c: char) -> Self {
+        match c {
+            '0'..='9' | '\u{06F0}'..='\u{06F9}' => Self::EN,
+            '\u{0660}'..='\u{0669}' => Self::AN,
+            ' ' | '\t' | '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{3000}' => Self::WS,
+            '\u{0590}'..='\u{05FF}'
+            | '\u{07C0}'..='\u{085F}'
+            | '\u{FB1D}'..='\u{FB4F}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}' => Self::R,
+            '\u{0600}'..='\u{07BF}'
+            | '\u{0860}'..='\u{08FF}'
+            | '\u{FB50}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}' => Self::AL,
+            c if c.is_ascii_punctuation() => Self::ON,
+            '\u{00A1}'..='\u{00BF}' | '\u{2010}'..='\u{2027}' | '\u{2030}'..='\u{205E}' => Self::ON,
+            _ => Self::L,
+        }
+    }
+
+    fn of_tchar(c: &TChar) -> Self {
+        match c {
+            TChar::Ascii(b) => Self::of(char::from(*b)),
+            TChar::Space => Self::WS,
+            TChar::NewLine => Self::ON,
+            // Goes with the double width character before it, which is never right to left
+            TChar::WideContinuation => Self::L,
+            TChar::Utf8(bytes) => std::str::from_utf8(bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map_or(Self::L, Self::of),
+        }
+    }
+
+    const fn is_neutral(self) -> bool {
+        matches!(self, Self::WS | Self::ON)
+    }
+
+    /// The direction this counts as when resolving the neutrals around it
+    const fn direction(self) -> Self {
+        match self {
+            Self::L => Self::L,
+            _ => Self::R,
+        }
+    }
+}
+
+/// The embedding level of each character in `line`, following UAX #9
+///
+/// The line is treated as a paragraph with no explicit embeddings. Even levels run left to right
+/// and odd levels run right to left.
+///
+/// Returns `None` if the line has nothing right to left in it, which is almost every line, so
+/// it can be shown as it is.
+#[must_use]
+pub fn embedding_levels(line: &[TChar]) -> Option<Vec<u8>> {
+    let mut classes: Vec<BidiClass> = line.iter().map(BidiClass::of_tchar).collect();
+    if !classes
+        .iter()
+        .any(|class| matches!(class, BidiClass::R | BidiClass::AL | BidiClass::AN))
+    {
+        return None;
+    }
+
+    // P2, P3: the paragraph takes the direction of its first strong character
+    let paragraph_level = u8::from(
+        classes
+            .iter()
+            .find(|class| matches!(class, BidiClass::L | BidiClass::R | BidiClass::AL))
+            .is_some_and(|class| *class != BidiClass::L),
+    );
+    let paragraph_direction = if paragraph_level == 0 {
+        BidiClass::L
+    } else {
+        BidiClass::R
+    };
+
+    // W2, W3, W7: digits take their meaning from the last strong character before them
+    let mut last_strong = paragraph_direction;
+    for class in &mut classes {
+        match *class {
+            BidiClass::L | BidiClass::R => last_strong = *class,
+            BidiClass::AL => {
+                last_strong = BidiClass::AL;
+                *class = BidiClass::R;
+            }
+            BidiClass::EN if last_strong == BidiClass::AL => *class = BidiClass::AN,
+            BidiClass::EN if last_strong == BidiClass::L => *class = BidiClass::L,
+            _ => (),
+        }
+    }
+
+    // N1, N2: neutrals between two runs of the same direction take that direction, otherwise
+    // they take the paragraph's
+    let mut i = 0;
+    while i < classes.len() {
+        if !classes[i].is_neutral() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < classes.len() && classes[i].is_neutral() {
+            i += 1;
+        }
+
+        let before = start
+            .checked_sub(1)
+            .map_or(paragraph_direction, |before| classes[before].direction());
+        let after = classes
+            .get(i)
+            .map_or(paragraph_direction, |after| after.direction());
+        let resolved = if before == after {
+            before
+        } else {
+            paragraph_direction
+        };
+
+        for class in &mut classes[start..i] {
+            *class = resolved;
+        }
+    }
+
+    // I1, I2
+    let mut levels: Vec<u8> = classes
+        .iter()
+        .map(|class| match (paragraph_level, class) {
+            (0, BidiClass::R) | (1, BidiClass::L | BidiClass::EN | BidiClass::AN) => {
+                paragraph_level + 1
+            }
+            (0, BidiClass::AN | BidiClass::EN) => paragraph_level + 2,
+            _ => paragraph_level,
+        })
+        .collect();
+
+    // L1: whitespace at the end of the line goes back to the paragraph level
+    for (level, c) in levels.iter_mut().zip(line).rev() {
+        if BidiClass::of_tchar(c) != BidiClass::WS {
+            break;
+        }
+
+        *level = paragraph_level;
+    }
+
+    Some(levels)
+}
+
+/// The order to show characters with the given embedding `levels` in, from left to right. Each
+/// entry is the index of the character to show in that column
+#[must_use]
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or_default();
+    let lowest_odd = levels
+        .iter()
+        .copied()
+        .filter(|level| level % 2 == 1)
+        .min()
+        .unwrap_or(highest + 1);
+
+    // L2: from the highest level down to the lowest odd one, reverse every run at that level
+    // or above
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+
+    order
+}
+
+/// Brackets in right to left text are drawn facing the other way (L4)
+const fn mirrored(c: &TChar) -> Option<TChar> {
+    let TChar::Ascii(b) = c else {
+        return None;
+    };
+
+    let mirrored = match b {
+        b'(' => b')',
+        b')' => b'(',
+        b'[' => b']',
+        b']' => b'[',
+        b'{' => b'}',
+        b'}' => b'{',
+        b'<' => b'>',
+        b'>' => b'<',
+        _ => return None,
+    };
+
+    Some(TChar::Ascii(mirrored))
+}
+
+/// Put each of `lines` of `data` in to display order, moving the format tags along with the
+/// characters they apply to. `lines` are ranges of `data`, and `tags` are positions in `data`.
+///
+/// Each line is reordered on its own, as a paragraph. Lines that wrapped are split up, so a
+/// right to left sentence that wraps reads from the right of each row rather than across them.
+pub fn reorder_lines(data: &mut [TChar], tags: &mut Vec<FormatTag>, lines: &[Range<usize>]) {
+    let mut new_positions: Option<Vec<usize>> = None;
+
+    for line in lines {
+        let end = line.end.min(data.len());
+        let start = line.start.min(end);
+        // The new line stays at the end
+        let end = if data[start..end].last() == Some(&TChar::NewLine) {
+            end - 1
+        } else {
+            end
+        };
+
+        let Some(levels) = embedding_levels(&data[start..end]) else {
+            continue;
+        };
+
+        let order = visual_order(&levels);
+        let original = data[start..end].to_vec();
+        for (column, logical) in order.iter().enumerate() {
+            let c = &original[*logical];
+            data[start + column] = if levels[*logical] % 2 == 1 {
+                mirrored(c).unwrap_or_else(|| c.clone())
+            } else {
+                c.clone()
+            };
+        }
+
+        let positions = new_positions.get_or_insert_with(|| (0..data.len()).collect());
+        for (column, logical) in order.iter().enumerate() {
+            positions[start + column] = start + logical;
+        }
+    }
+
+    if let Some(positions) = new_positions {
+        *tags = reorder_tags(tags, &positions);
+    }
+}
+
+/// Rebuild `tags` for data that has been shuffled so the character now at `i` came from
+/// `positions[i]`
+fn reorder_tags(tags: &[FormatTag], positions: &[usize]) -> Vec<FormatTag> {
+    let mut tag_at: Vec<Option<usize>> = vec![None; positions.len()];
+    for (index, tag) in tags.iter().enumerate() {
+        let end = tag.end.min(positions.len());
+        for slot in tag_at.iter_mut().take(end).skip(tag.start) {
+            *slot = Some(index);
+        }
+    }
+
+    let mut reordered: Vec<FormatTag> = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut finish = |current: Option<(usize, usize)>, end: usize| {
+        if let Some((index, start)) = current {
+            let tag: &FormatTag = &tags[index];
+            reordered.push(FormatTag {
+                start,
+                end: if end == positions.len() && tag.end == usize::MAX {
+                    usize::MAX
+                } else {
+                    end
+                },
+                ..tag.clone()
+            });
+        }
+    };
+
+    for (i, position) in positions.iter().enumerate() {
+        let index = tag_at[*position];
+        if current.map(|(current, _)| current) != index {
+            finish(current, i);
+            current = index.map(|index| (index, i));
+        }
+    }
+    finish(current, positions.len());
+
+    reordered
+}
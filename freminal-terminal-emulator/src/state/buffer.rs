@@ -3,10 +3,22 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
-use super::{cursor::CursorPos, data::TerminalSections, internal::BufferType, term_char::TChar};
+use super::{
+    cursor::CursorPos, data::TerminalSections, internal::BufferType, search::find_matches,
+    term_char::TChar,
+};
 use anyhow::Result;
-use freminal_common::scroll::ScrollDirection;
-use std::ops::Range;
+use freminal_common::{
+    args::DEFAULT_SCROLLBACK_LINES,
+    char_width::WidthProvider,
+    redaction::{Redactor, REDACTION_CHAR},
+    scroll::ScrollDirection,
+};
+use std::{collections::VecDeque, ops::Range};
+
+/// Once the buffer is over its scrollback limit, it drops this fraction of the limit in one go, so
+/// the cost of moving the offsets of the rest of the buffer is spread over many lines of output
+pub const SCROLLBACK_CLIP_BATCH_DIVISOR: usize = 8;
 
 pub struct PadBufferForWriteResponse {
     /// Where to copy data into
@@ -25,6 +37,11 @@ pub struct TerminalBufferInsertResponse {
     pub new_cursor_pos: CursorPos,
 }
 
+/// Blank lines added and lines removed when lines are inserted or deleted, or the scrolling
+/// region scrolls
+///
+/// Both ranges are positions in the buffer as it was before the change. Whichever comes later
+/// in the buffer was changed first, so it didn't move the other.
 #[derive(Debug)]
 pub struct TerminalBufferInsertLineResponse {
     /// Range of deleted data **before insertion**
@@ -33,6 +50,15 @@ pub struct TerminalBufferInsertLineResponse {
     pub inserted_range: Range<usize>,
 }
 
+impl TerminalBufferInsertLineResponse {
+    const fn unchanged() -> Self {
+        Self {
+            deleted_range: 0..0,
+            inserted_range: 0..0,
+        }
+    }
+}
+
 pub struct TerminalBufferSetWinSizeResponse {
     pub changed: bool,
     _insertion_range: Range<usize>,
@@ -41,7 +67,9 @@ pub struct TerminalBufferSetWinSizeResponse {
 
 #[derive(Eq, PartialEq, Debug)]
 pub struct TerminalBufferHolder {
-    pub buf: Vec<TChar>,
+    /// The cells of every line, scrollback included. A ring buffer, so the oldest lines can be
+    /// dropped without moving the rest
+    pub buf: VecDeque<TChar>,
     width: usize,
     height: usize,
     visible_line_ranges: Vec<Range<usize>>,
@@ -50,12 +78,19 @@ pub struct TerminalBufferHolder {
     top_margin: usize,
     bottom_margin: usize,
     buffer_type: BufferType,
+    width_provider: WidthProvider,
+    /// Most lines kept in the primary buffer, the screen included
+    scrollback_lines: usize,
+    /// Show each line on one row, cut off at the edge of the screen, rather than wrapping it
+    no_wrap_view: bool,
+    /// How many columns in the view starts, when it doesn't wrap
+    column_offset: usize,
 }
 
 impl Default for TerminalBufferHolder {
     fn default() -> Self {
         Self {
-            buf: Vec::with_capacity(500_000),
+            buf: VecDeque::with_capacity(500_000),
             width: 80,
             height: 24,
             visible_line_ranges: Vec::with_capacity(24),
@@ -64,15 +99,22 @@ impl Default for TerminalBufferHolder {
             top_margin: 0,
             bottom_margin: usize::MAX,
             buffer_type: BufferType::Primary,
+            width_provider: WidthProvider::default(),
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            no_wrap_view: false,
+            column_offset: 0,
         }
     }
 }
 
 impl TerminalBufferHolder {
+    /// A buffer `width` by `height` cells. A minimized window can report no rows or columns, so
+    /// the buffer is never smaller than one cell
     #[must_use]
     pub fn new(width: usize, height: usize, buffer_type: BufferType) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
         Self {
-            buf: Vec::with_capacity(500_000),
+            buf: VecDeque::with_capacity(500_000),
             width,
             height,
             visible_line_ranges: Vec::with_capacity(height),
@@ -81,9 +123,46 @@ impl TerminalBufferHolder {
             top_margin: 0,
             bottom_margin: usize::MAX,
             buffer_type,
+            width_provider: WidthProvider::default(),
+            scrollback_lines: match buffer_type {
+                BufferType::Primary => DEFAULT_SCROLLBACK_LINES,
+                BufferType::Alternate => 0,
+            },
+            no_wrap_view: false,
+            column_offset: 0,
         }
     }
 
+    #[must_use]
+    pub const fn width_provider(&self) -> WidthProvider {
+        self.width_provider
+    }
+
+    /// How wide characters written from now on are. What is already in the buffer keeps the
+    /// width it was written with
+    pub fn set_width_provider(&mut self, width_provider: WidthProvider) {
+        self.width_provider = width_provider;
+    }
+
+    /// Keep at most `lines` lines, the screen included, dropping the oldest as more are written.
+    /// They are dropped in batches of `1 / SCROLLBACK_CLIP_BATCH_DIVISOR` of the limit, so fewer
+    /// may be kept just after a batch goes. The alternate screen keeps none unless it is given some
+    pub fn set_scrollback_lines(&mut self, lines: usize) {
+        self.scrollback_lines = lines;
+    }
+
+    #[must_use]
+    pub const fn scrollback_lines(&self) -> usize {
+        self.scrollback_lines
+    }
+
+    /// Whether lines that scroll off the top of the screen are kept to scroll back to. The
+    /// alternate screen only keeps them if it has been given some scrollback
+    #[must_use]
+    pub fn has_scrollback(&self) -> bool {
+        self.buffer_type == BufferType::Primary || self.scrollback_lines > 0
+    }
+
     #[must_use]
     pub fn show_cursor(&self, cursor_pos: &CursorPos) -> bool {
         // FIXME: I think this logic is partially buggy. If the cursor is not in the last line it may break
@@ -93,7 +172,11 @@ impl TerminalBufferHolder {
             return true;
         }
 
-        if (self.buffer_line_ranges.len() - 1).saturating_sub(self.height)
+        if self
+            .buffer_line_ranges
+            .len()
+            .saturating_sub(1)
+            .saturating_sub(self.height)
             < self.viewable_index_bottom
         {
             return false;
@@ -103,7 +186,7 @@ impl TerminalBufferHolder {
     }
 
     pub fn scroll_down(&mut self, num_lines: &usize) {
-        if self.buffer_type == BufferType::Alternate {
+        if !self.has_scrollback() {
             return;
         }
 
@@ -117,6 +200,13 @@ impl TerminalBufferHolder {
             return;
         }
 
+        let num_lines = if self.no_wrap_view {
+            self.unwrapped_lines_down(self.viewable_index_bottom, *num_lines)
+                .saturating_sub(self.viewable_index_bottom)
+        } else {
+            *num_lines
+        };
+
         if self.viewable_index_bottom + num_lines >= self.buffer_line_ranges.len() {
             debug!("Down scroll is now at the bottom");
             self.viewable_index_bottom = usize::MAX;
@@ -128,7 +218,7 @@ impl TerminalBufferHolder {
     }
 
     pub fn scroll_up(&mut self, num_lines: &usize) {
-        if self.buffer_type == BufferType::Alternate {
+        if !self.has_scrollback() {
             return;
         }
 
@@ -138,12 +228,20 @@ impl TerminalBufferHolder {
         }
 
         if self.viewable_index_bottom == usize::MAX {
-            self.viewable_index_bottom = self.buffer_line_ranges.len() - 1;
+            self.viewable_index_bottom = self.buffer_line_ranges.len().saturating_sub(1);
         }
 
-        self.viewable_index_bottom = self.viewable_index_bottom.saturating_sub(*num_lines);
+        // Without wrapping, a line is a row however many rows it would wrap on to
+        let num_lines = if self.no_wrap_view {
+            self.viewable_index_bottom
+                .saturating_sub(self.unwrapped_lines_up(self.viewable_index_bottom, *num_lines))
+        } else {
+            *num_lines
+        };
+
+        self.viewable_index_bottom = self.viewable_index_bottom.saturating_sub(num_lines);
         if self.viewable_index_bottom < self.height {
-            self.viewable_index_bottom = self.height - 1;
+            self.viewable_index_bottom = self.height.saturating_sub(1);
             debug!("Up scroll already is at the top");
             return;
         }
@@ -158,6 +256,295 @@ impl TerminalBufferHolder {
         }
     }
 
+    /// Scroll the viewport to the oldest line in the scrollback
+    pub fn scroll_to_top(&mut self) {
+        if !self.has_scrollback() {
+            return;
+        }
+
+        if self.buffer_line_ranges.len() <= self.height {
+            debug!("not enough lines for scroll");
+            return;
+        }
+
+        self.viewable_index_bottom = self.height.saturating_sub(1);
+    }
+
+    /// Scroll the viewport back to the live screen
+    pub fn scroll_to_bottom(&mut self) {
+        self.viewable_index_bottom = usize::MAX;
+    }
+
+    /// How many lines there are, scrollback included
+    #[must_use]
+    pub fn line_count(&self) -> usize {
+        self.buffer_line_ranges.len()
+    }
+
+    /// Scroll the viewport so that the line containing `buf_pos` is the top line of the screen.
+    /// If that isn't possible (ie, the line is in the last screen of the buffer) we scroll as far as we can
+    pub fn scroll_to_buf_pos(&mut self, buf_pos: usize) {
+        if !self.has_scrollback() {
+            return;
+        }
+
+        let Some(line) = self
+            .buffer_line_ranges
+            .iter()
+            .position(|range| line_contains_or_follows(range, buf_pos))
+        else {
+            debug!("Buffer position {buf_pos} is not in the buffer");
+            return;
+        };
+
+        let bottom = (line + self.height.saturating_sub(1)).max(self.height.saturating_sub(1));
+
+        if bottom + 1 >= self.buffer_line_ranges.len() {
+            self.viewable_index_bottom = usize::MAX;
+        } else {
+            self.viewable_index_bottom = bottom;
+        }
+
+        debug!("Scrolled to buffer position {buf_pos}, line {line}");
+    }
+
+    /// The range of lines in the buffer that are currently in the viewport
+    #[must_use]
+    pub fn viewport_line_range(&self) -> Range<usize> {
+        if self.buffer_line_ranges.is_empty() {
+            return 0..0;
+        }
+
+        let end = if self.viewable_index_bottom == usize::MAX {
+            self.buffer_line_ranges.len() - 1
+        } else {
+            self.viewable_index_bottom
+                .min(self.buffer_line_ranges.len() - 1)
+        };
+        let start = end.saturating_sub(self.height.saturating_sub(1));
+
+        start..end + 1
+    }
+
+    /// Whether each line is shown on one row, cut off at the edge of the screen, rather than
+    /// wrapped
+    #[must_use]
+    pub const fn no_wrap_view(&self) -> bool {
+        self.no_wrap_view
+    }
+
+    /// Stop wrapping long lines in the view, or start again. Either way the view goes back to the
+    /// start of the lines
+    pub fn set_no_wrap_view(&mut self, no_wrap_view: bool) {
+        self.no_wrap_view = no_wrap_view;
+        self.column_offset = 0;
+    }
+
+    /// How many columns in the view starts, when it doesn't wrap
+    #[must_use]
+    pub const fn column_offset(&self) -> usize {
+        self.column_offset
+    }
+
+    /// How far the view can be scrolled to the right, so the end of the longest line in the
+    /// viewport is at the right edge of the screen
+    #[must_use]
+    pub fn max_column_offset(&self) -> usize {
+        if !self.no_wrap_view {
+            return 0;
+        }
+
+        self.viewport_lines()
+            .iter()
+            .map(ExactSizeIterator::len)
+            .max()
+            .unwrap_or(0)
+            .saturating_sub(self.width)
+    }
+
+    /// Start the view `column_offset` columns in to the lines, as far as the longest line allows
+    pub fn set_column_offset(&mut self, column_offset: usize) {
+        self.column_offset = column_offset.min(self.max_column_offset());
+    }
+
+    /// Scroll the view `columns` to the right, or to the left if negative
+    pub fn scroll_columns(&mut self, columns: isize) {
+        self.set_column_offset(self.column_offset.saturating_add_signed(columns));
+    }
+
+    /// The first row of the line that `row` is part of. A row that was soft wrapped carries on
+    /// from where the row before it ended, rather than after a newline
+    fn unwrapped_line_start(&self, row: usize) -> usize {
+        let lines = &self.buffer_line_ranges;
+        let mut row = row.min(lines.len().saturating_sub(1));
+        while row > 0 && lines[row].start == lines[row - 1].end {
+            row -= 1;
+        }
+
+        row
+    }
+
+    /// The last row of the line that `row` is part of
+    fn unwrapped_line_end(&self, row: usize) -> usize {
+        let lines = &self.buffer_line_ranges;
+        let mut row = row.min(lines.len().saturating_sub(1));
+        while row + 1 < lines.len() && lines[row + 1].start == lines[row].end {
+            row += 1;
+        }
+
+        row
+    }
+
+    /// The last row of the line `lines` lines above the one `row` is part of, or of the first
+    /// line if there aren't that many
+    fn unwrapped_lines_up(&self, row: usize, lines: usize) -> usize {
+        let mut row = self.unwrapped_line_end(row);
+        for _ in 0..lines {
+            let Some(above) = self.unwrapped_line_start(row).checked_sub(1) else {
+                break;
+            };
+            row = above;
+        }
+
+        row
+    }
+
+    /// The last row of the line `lines` lines below the one `row` is part of, or of the last
+    /// line if there aren't that many
+    fn unwrapped_lines_down(&self, row: usize, lines: usize) -> usize {
+        let mut row = self.unwrapped_line_end(row);
+        for _ in 0..lines {
+            if row + 1 >= self.buffer_line_ranges.len() {
+                break;
+            }
+            row = self.unwrapped_line_end(row + 1);
+        }
+
+        row
+    }
+
+    /// The part of the buffer on each row of the viewport, top to bottom. When the view doesn't
+    /// wrap, each row is a whole line however long it is, and the line at the bottom of the
+    /// viewport is the last one shown
+    #[must_use]
+    pub fn viewport_lines(&self) -> Vec<Range<usize>> {
+        let viewport = self.viewport_line_range();
+        if !self.no_wrap_view || viewport.is_empty() {
+            return self.buffer_line_ranges[viewport]
+                .iter()
+                .map(|line| self.clamp_to_buf(line))
+                .collect();
+        }
+
+        let ranges = &self.buffer_line_ranges;
+        let bottom = self.unwrapped_line_end(viewport.end - 1);
+        let mut lines = std::collections::VecDeque::with_capacity(self.height);
+        let mut last = Some(bottom);
+        while let Some(end) = last.filter(|_| lines.len() < self.height) {
+            let start = self.unwrapped_line_start(end);
+            lines.push_front(self.clamp_to_buf(&(ranges[start].start..ranges[end].end)));
+            last = start.checked_sub(1);
+        }
+
+        // Near the top of the scrollback there may not be enough lines above to fill the screen
+        let mut next = bottom + 1;
+        while lines.len() < self.height && next < ranges.len() {
+            let end = self.unwrapped_line_end(next);
+            lines.push_back(self.clamp_to_buf(&(ranges[next].start..ranges[end].end)));
+            next = end + 1;
+        }
+
+        lines.into()
+    }
+
+    /// The part of the buffer shown on each row of the viewport, top to bottom. When the view
+    /// doesn't wrap, lines are cut off at the edges of the screen
+    #[must_use]
+    pub fn viewport_rows(&self) -> Vec<Range<usize>> {
+        let mut rows = self.viewport_lines();
+        if self.no_wrap_view {
+            for row in &mut rows {
+                let start = (row.start + self.column_offset).min(row.end);
+                *row = start..(start + self.width).min(row.end);
+            }
+        }
+
+        rows
+    }
+
+    /// The characters on `rows` of the buffer one after another, with a newline between each.
+    /// Double width characters cut in half at the edge of a row are blanked out
+    #[must_use]
+    pub fn rows_data(&self, rows: &[Range<usize>]) -> Vec<TChar> {
+        let mut data = Vec::with_capacity(rows.iter().map(|row| row.len() + 1).sum());
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                data.push(TChar::NewLine);
+            }
+
+            let start = data.len();
+            data.extend(self.buf.range(self.clamp_to_buf(row)).cloned());
+            self.blank_split_wide_chars(&mut data[start..]);
+        }
+
+        data
+    }
+
+    /// `range` cut short at the end of the buffer, so a row can't reach past what is in it
+    fn clamp_to_buf(&self, range: &Range<usize>) -> Range<usize> {
+        range.start.min(self.buf.len())..range.end.min(self.buf.len())
+    }
+
+    /// Where the cursor is in the viewport, or `None` if the view doesn't wrap and the cursor is
+    /// off the edge of the screen or scrolled out of view
+    #[must_use]
+    pub fn viewport_cursor_pos(&self, cursor_pos: &CursorPos) -> Option<CursorPos> {
+        if !self.no_wrap_view {
+            return Some(cursor_pos.clone());
+        }
+
+        let line_start = self.visible_line_ranges.get(cursor_pos.y)?.start;
+        let (y, line) = self
+            .viewport_lines()
+            .into_iter()
+            .enumerate()
+            .find(|(_, line)| line.start <= line_start && line_start <= line.end)?;
+        let x = (line_start - line.start + cursor_pos.x).checked_sub(self.column_offset)?;
+
+        (x < self.width).then_some(CursorPos { x, y })
+    }
+
+    /// Convert a range of buffer positions in to the range of screen rows currently showing it
+    #[must_use]
+    pub fn buf_range_to_visible_rows(&self, buf_range: &Range<usize>) -> Option<Range<usize>> {
+        if self.buffer_line_ranges.is_empty() {
+            return None;
+        }
+
+        let mut rows = self
+            .viewport_lines()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                line_contains_or_follows(line, buf_range.start) && line.start < buf_range.end
+            })
+            .map(|(row, _)| row);
+
+        let first = rows.next()?;
+        let last = rows.next_back().unwrap_or(first);
+
+        Some(first..last + 1)
+    }
+
+    /// The buffer position of the start of the line the cursor is on. If the cursor is below
+    /// the end of the buffer, the end of the buffer is returned
+    #[must_use]
+    pub fn line_start_buf_pos(&self, cursor_pos: &CursorPos) -> usize {
+        self.visible_line_ranges
+            .get(cursor_pos.y)
+            .map_or(self.buf.len(), |range| range.start)
+    }
+
     #[must_use]
     pub fn get_visible_line_ranges(&self) -> &[Range<usize>] {
         &self.visible_line_ranges
@@ -189,7 +576,164 @@ impl TerminalBufferHolder {
         // loop through all of the characters
         // if the character is utf8, then we need all of the bytes to be written
 
-        let converted_buffer = TChar::from_vec(data)?;
+        let mut converted_buffer = TChar::from_vec(data)?;
+        self.attach_leading_zero_width(cursor_pos, &mut converted_buffer);
+        let converted_buffer = self.add_wide_continuations(cursor_pos.x, converted_buffer, true);
+
+        Ok(self.insert_tchars(cursor_pos, &converted_buffer))
+    }
+
+    /// Inserts data at the cursor position without wrapping (DECAWM reset)
+    ///
+    /// Anything that doesn't fit on the row overwrites the last column, so only the last
+    /// character of the overflow is kept. The cursor stays in the last column.
+    ///
+    /// # Errors
+    /// Will error if the data is not valid utf8
+    pub fn insert_data_without_wrap(
+        &mut self,
+        cursor_pos: &CursorPos,
+        data: &[u8],
+    ) -> Result<TerminalBufferInsertResponse> {
+        let mut converted_buffer = TChar::from_vec(data)?;
+        self.attach_leading_zero_width(cursor_pos, &mut converted_buffer);
+        let mut converted_buffer =
+            self.add_wide_continuations(cursor_pos.x, converted_buffer, false);
+        let last_column = self.width.saturating_sub(1);
+        let cursor_pos = CursorPos {
+            x: cursor_pos.x.min(last_column),
+            y: cursor_pos.y,
+        };
+
+        let available = self.width - cursor_pos.x;
+        if available > 0 && converted_buffer.len() > available {
+            let overflow = converted_buffer.len() - available;
+            converted_buffer.drain(available - 1..available - 1 + overflow);
+            self.blank_split_wide_chars(&mut converted_buffer);
+        }
+
+        let mut response = self.insert_tchars(&cursor_pos, &converted_buffer);
+        response.new_cursor_pos.x = response.new_cursor_pos.x.min(last_column);
+
+        Ok(response)
+    }
+
+    /// Zero width characters at the start of new data belong with the character before the
+    /// cursor, like a joiner continuing an emoji sequence that was split across reads. If there is
+    /// nothing before the cursor on the line, they go with the next character instead
+    fn attach_leading_zero_width(
+        &mut self,
+        cursor_pos: &CursorPos,
+        converted_buffer: &mut Vec<TChar>,
+    ) {
+        if !converted_buffer.first().is_some_and(TChar::is_zero_width) {
+            return;
+        }
+
+        let leading = converted_buffer.remove(0);
+        let zero_width = leading.to_string();
+
+        // The character before a wide character's second cell is the wide character itself
+        let previous = cursor_pos
+            .x
+            .checked_sub(1)
+            .and_then(|x| self.cursor_pos_to_buf_pos(&CursorPos { x, y: cursor_pos.y }))
+            .map(|buf_pos| match self.buf.get(buf_pos) {
+                Some(TChar::WideContinuation) => buf_pos.saturating_sub(1),
+                _ => buf_pos,
+            })
+            .and_then(|buf_pos| self.buf.get_mut(buf_pos))
+            .filter(|previous| **previous != TChar::NewLine);
+
+        if let Some(previous) = previous {
+            previous.append_zero_width(&zero_width);
+        } else if let Some(next) = converted_buffer
+            .first_mut()
+            .filter(|next| **next != TChar::NewLine)
+        {
+            next.prepend_zero_width(&zero_width);
+        } else {
+            trace!("Dropping zero width characters with nothing to keep them with");
+        }
+    }
+
+    /// Follow each double width character with a `WideContinuation` for its second cell, so the
+    /// rest of the buffer can count cells by counting `TChar`s. When `wrapping`, a double width
+    /// character that would start in the last column goes to the next row instead, leaving the
+    /// last column blank, as it can't be split across rows
+    fn add_wide_continuations(
+        &self,
+        start_column: usize,
+        converted_buffer: Vec<TChar>,
+        wrapping: bool,
+    ) -> Vec<TChar> {
+        if !converted_buffer
+            .iter()
+            .any(|c| c.cell_width(&self.width_provider) == 2)
+        {
+            return converted_buffer;
+        }
+
+        let mut column = start_column;
+        let mut tchars = Vec::with_capacity(converted_buffer.len() + 1);
+        for c in converted_buffer {
+            if c == TChar::NewLine {
+                column = 0;
+                tchars.push(c);
+                continue;
+            }
+
+            if c.cell_width(&self.width_provider) < 2 {
+                column += 1;
+                tchars.push(c);
+                continue;
+            }
+
+            if wrapping && self.width > 1 && column % self.width == self.width - 1 {
+                tchars.push(TChar::Space);
+                column += 1;
+            }
+
+            tchars.push(c);
+            tchars.push(TChar::WideContinuation);
+            column += 2;
+        }
+
+        tchars
+    }
+
+    /// Blank out the halves of double width characters in `tchars` that lost their other half
+    /// when the data was cut short
+    fn blank_split_wide_chars(&self, tchars: &mut [TChar]) {
+        for i in 0..tchars.len() {
+            let split = match &tchars[i] {
+                TChar::WideContinuation => {
+                    i == 0 || tchars[i - 1].cell_width(&self.width_provider) < 2
+                }
+                c if c.cell_width(&self.width_provider) == 2 => {
+                    tchars.get(i + 1) != Some(&TChar::WideContinuation)
+                }
+                _ => false,
+            };
+
+            if split {
+                tchars[i] = TChar::Space;
+            }
+        }
+    }
+
+    fn insert_tchars(
+        &mut self,
+        cursor_pos: &CursorPos,
+        converted_buffer: &[TChar],
+    ) -> TerminalBufferInsertResponse {
+        if converted_buffer.is_empty() {
+            return TerminalBufferInsertResponse {
+                written_range: 0..0,
+                insertion_range: 0..0,
+                new_cursor_pos: cursor_pos.clone(),
+            };
+        }
 
         let PadBufferForWriteResponse {
             write_idx,
@@ -197,18 +741,35 @@ impl TerminalBufferHolder {
         } = self.pad_buffer_for_write(cursor_pos, converted_buffer.len());
         let write_range = write_idx..write_idx + converted_buffer.len();
 
-        self.buf
-            .splice(write_range.clone(), converted_buffer.iter().cloned());
+        // Writing over one half of a double width character leaves the other half on its own
+        let splits_before = self.buf.get(write_range.start) == Some(&TChar::WideContinuation);
+        let splits_after = self.buf.get(write_range.end) == Some(&TChar::WideContinuation);
+
+        // The buffer was padded out to the end of the write, so it only overwrites
+        for (cell, c) in self
+            .buf
+            .range_mut(write_range.clone())
+            .zip(converted_buffer)
+        {
+            *cell = c.clone();
+        }
+
+        if splits_before && write_range.start > 0 {
+            self.buf[write_range.start - 1] = TChar::Space;
+        }
+        if splits_after {
+            self.buf[write_range.end] = TChar::Space;
+        }
 
         self.line_ranges_to_visible_line_ranges();
 
         let new_cursor_pos = self.buf_to_cursor_pos(write_range.end);
         //info!("buffer lines: {:?}", self.buffer_line_ranges);
-        Ok(TerminalBufferInsertResponse {
+        TerminalBufferInsertResponse {
             written_range: write_range,
             insertion_range: inserted_padding,
             new_cursor_pos,
-        })
+        }
     }
 
     /// Inserts data, but will not wrap. If line end is hit, data stops
@@ -223,18 +784,18 @@ impl TerminalBufferHolder {
         if let Some((buf_pos, line_range)) = buf_pos {
             // Insert spaces until either we hit num_spaces, or the line width is too long
             let line_len = line_range.end - line_range.start;
-            let num_inserted = (num_spaces).min(self.width - line_len);
+            let num_inserted = (num_spaces).min(self.width.saturating_sub(line_len));
 
             // Overwrite existing with spaces until we hit num_spaces or we hit the line end
-            let num_overwritten = (num_spaces - num_inserted).min(line_range.end - buf_pos);
+            let num_overwritten =
+                (num_spaces - num_inserted).min(line_range.end.saturating_sub(buf_pos));
 
             // NOTE: We do the overwrite first so we don't have to worry about adjusting
             // indices for the newly inserted data
-            self.buf[buf_pos..buf_pos + num_overwritten].fill(TChar::Space);
-            self.buf.splice(
-                buf_pos..buf_pos,
-                std::iter::repeat(TChar::Space).take(num_inserted),
-            );
+            self.buf
+                .range_mut(buf_pos..buf_pos + num_overwritten)
+                .for_each(|c| *c = TChar::Space);
+            self.insert_cells(buf_pos, std::iter::repeat_n(TChar::Space, num_inserted));
 
             let used_spaces = num_inserted + num_overwritten;
             self.line_ranges_to_visible_line_ranges();
@@ -257,58 +818,142 @@ impl TerminalBufferHolder {
         }
     }
 
+    /// Insert `num_lines` blank lines at the cursor's line, pushing the lines below it down.
+    /// Lines pushed past the bottom of the scrolling region are lost
+    ///
+    /// Nothing happens if the cursor is outside the scrolling region.
     pub fn insert_lines(
         &mut self,
         cursor_pos: &CursorPos,
-        mut num_lines: usize,
+        num_lines: usize,
     ) -> TerminalBufferInsertLineResponse {
-        let visible_line_ranges = &self.visible_line_ranges;
-
+        let (top, bottom) = self.get_scrolling_region();
         // NOTE: Cursor x position is not used. If the cursor position was too far to the right,
         // there may be no buffer position associated with it. Use Y only
-        let Some(line_range) = visible_line_ranges.get(cursor_pos.y) else {
-            return TerminalBufferInsertLineResponse {
-                deleted_range: 0..0,
-                inserted_range: 0..0,
-            };
-        };
+        let y = cursor_pos.y;
+        if y < top || y > bottom || y >= self.visible_line_ranges.len() {
+            return TerminalBufferInsertLineResponse::unchanged();
+        }
 
-        let available_space = self.height - visible_line_ranges.len();
-        // If height is 10, and y is 5, we can only insert 5 lines. If we inserted more it would
-        // adjust the visible line range, and that would be a problem
-        num_lines = num_lines.min(self.height - cursor_pos.y);
-
-        let deletion_range = if num_lines > available_space {
-            let num_lines_removed = num_lines - available_space;
-            let removal_start_idx =
-                visible_line_ranges[visible_line_ranges.len() - num_lines_removed].start;
-            let deletion_range = removal_start_idx..self.buf.len();
-            self.buf.truncate(removal_start_idx);
-            deletion_range
-        } else {
-            0..0
-        };
+        let num_lines = num_lines.min(bottom + 1 - y);
 
-        let insertion_pos = line_range.start;
+        // The lines pushed out of the region go first. They're after the insertion, so it
+        // doesn't move
+        let deleted_range = self.delete_rows(bottom + 1 - num_lines, bottom + 1);
+        let insertion_pos = self.visible_line_ranges[y].start;
+        let inserted_range =
+            self.insert_blank_rows(insertion_pos, num_lines, self.wraps_before(insertion_pos));
 
-        // Edge case, if the previous line ended in a line wrap, inserting a new line will not
-        // result in an extra line being shown on screen. E.g. with a width of 5, 01234 and 01234\n
-        // both look like a line of length 5. In this case we need to add another newline
-        if insertion_pos > 0 && self.buf[insertion_pos - 1] != TChar::NewLine {
-            num_lines += 1;
+        self.line_ranges_to_visible_line_ranges();
+
+        TerminalBufferInsertLineResponse {
+            deleted_range,
+            inserted_range,
         }
+    }
 
-        self.buf.splice(
-            insertion_pos..insertion_pos,
-            std::iter::repeat(TChar::NewLine).take(num_lines),
-        );
+    /// Delete `num_lines` lines from the cursor's line down, pulling the lines below it up and
+    /// adding blank lines at the bottom of the scrolling region. This is also how the region
+    /// scrolls up, from its top line
+    ///
+    /// Nothing happens if the cursor is outside the scrolling region.
+    pub fn delete_lines(
+        &mut self,
+        cursor_pos: &CursorPos,
+        num_lines: usize,
+    ) -> TerminalBufferInsertLineResponse {
+        let (top, bottom) = self.get_scrolling_region();
+        let y = cursor_pos.y;
+        let num_rows = self.visible_line_ranges.len();
+        if y < top || y > bottom || y >= num_rows {
+            return TerminalBufferInsertLineResponse::unchanged();
+        }
+
+        let num_lines = num_lines.min(bottom + 1 - y);
+        let num_deleted = num_lines.min(num_rows - y);
+
+        // The blank lines go below the region, before the lines under it, or at the end if
+        // there aren't any. Just as many are added as are deleted so the screen keeps its
+        // height and the scrollback doesn't slide in to view. They're after the deletion, so
+        // they don't move it
+        let deleted_range = self.row_start(y)..self.row_start(y + num_lines);
+        let insertion_pos = self.row_start(bottom + 1);
+        // Once the deletion is done, what comes before the blank lines may be what came before
+        // the deleted lines
+        let wrapped = if deleted_range.end == insertion_pos {
+            self.wraps_before(deleted_range.start)
+        } else {
+            self.wraps_before(insertion_pos)
+        };
+        let inserted_range = self.insert_blank_rows(insertion_pos, num_deleted, wrapped);
+        self.buf.drain(deleted_range.clone());
 
         self.line_ranges_to_visible_line_ranges();
 
         TerminalBufferInsertLineResponse {
-            deleted_range: deletion_range,
-            inserted_range: insertion_pos..insertion_pos + num_lines,
+            deleted_range,
+            inserted_range,
+        }
+    }
+
+    /// Where visible row `row` starts in the buffer. Rows past the last one start at the end
+    fn row_start(&self, row: usize) -> usize {
+        self.visible_line_ranges
+            .get(row)
+            .map_or(self.buf.len(), |range| range.start.min(self.buf.len()))
+    }
+
+    /// Remove the visible rows `start..end`. Returns the buffer range removed
+    fn delete_rows(&mut self, start: usize, end: usize) -> Range<usize> {
+        if start >= self.visible_line_ranges.len() {
+            return 0..0;
+        }
+
+        let range = self.row_start(start)..self.row_start(end);
+        self.buf.drain(range.clone());
+        range
+    }
+
+    /// Whether the row before `buf_pos` carries on to it rather than ending in a newline
+    fn wraps_before(&self, buf_pos: usize) -> bool {
+        buf_pos > 0 && self.buf[buf_pos - 1] != TChar::NewLine
+    }
+
+    /// Add `num_rows` blank rows at `buf_pos`, which is the start of a row or the end of the
+    /// buffer, after a row that `wrapped` on to the next. Returns the buffer range added
+    fn insert_blank_rows(
+        &mut self,
+        buf_pos: usize,
+        num_rows: usize,
+        wrapped: bool,
+    ) -> Range<usize> {
+        if num_rows == 0 {
+            return buf_pos..buf_pos;
         }
+
+        // If the row before ends in a line wrap, a newline only ends it and doesn't add a row.
+        // E.g. with a width of 5, 01234 and 01234\n both look like a line of length 5. In this
+        // case we need to add another newline
+        let num_newlines = if wrapped { num_rows + 1 } else { num_rows };
+
+        self.insert_cells(buf_pos, std::iter::repeat_n(TChar::NewLine, num_newlines));
+
+        buf_pos..buf_pos + num_newlines
+    }
+
+    /// Insert `cells` at `buf_pos`, moving what comes after it along
+    fn insert_cells(&mut self, buf_pos: usize, cells: impl IntoIterator<Item = TChar>) {
+        let after = self.buf.split_off(buf_pos);
+        self.buf.extend(cells);
+        self.buf.extend(after);
+    }
+
+    /// Whether the scrolling region is smaller than the screen. Only then are lines that scroll
+    /// off the top of it dropped, rather than going in to the scrollback
+    #[must_use]
+    pub fn has_scrolling_region(&self) -> bool {
+        let (top, bottom) = self.get_scrolling_region();
+        top > 0 || bottom + 1 < self.height
     }
 
     /// Clear backwards from the cursor position
@@ -328,17 +973,18 @@ impl TerminalBufferHolder {
 
         // clear from the buf pos that is the start of the visible line to the cursor pos
 
-        let previous_last_char = self.buf[buf_pos].clone();
+        let previous_last_char = self.buf.get(buf_pos).cloned();
 
         for line in &visible_line_ranges {
             // replace all characters from the start of the visible lines to buf_pos with spaces
             if line.start < buf_pos {
-                self.buf[line.start..buf_pos].fill(TChar::Space);
+                self.buf
+                    .range_mut(line.start..buf_pos)
+                    .for_each(|c| *c = TChar::Space);
             }
 
             // if the line is where the cursor is, we want to clear from the start of the line to the cursor pos
             if line.start == buf_pos {
-                self.buf[line.start..buf_pos].fill(TChar::Space);
                 break;
             }
         }
@@ -363,19 +1009,19 @@ impl TerminalBufferHolder {
         //
         // If we truncated a newline it's the same situation
         if cursor_pos.x == 0 && buf_pos > 0 && self.buf[buf_pos - 1] != TChar::NewLine
-            || previous_last_char == TChar::NewLine
+            || previous_last_char == Some(TChar::NewLine)
         {
-            self.buf.push(TChar::NewLine);
+            self.buf.push_back(TChar::NewLine);
         }
 
+        self.line_ranges_to_visible_line_ranges();
+
         if new_cursor_pos != cursor_pos.clone() {
             return Err(anyhow::anyhow!(
                 "Cursor position changed while clearing backwards"
             ));
         }
 
-        self.line_ranges_to_visible_line_ranges();
-
         Ok(Some(visible_line_ranges[cursor_pos.y].start..buf_pos))
     }
 
@@ -392,7 +1038,7 @@ impl TerminalBufferHolder {
             return Ok(None);
         };
 
-        let previous_last_char = self.buf[buf_pos].clone();
+        let previous_last_char = self.buf.get(buf_pos).cloned();
         self.buf.truncate(buf_pos);
 
         // If we truncate at the start of a line, and the previous line did not end with a newline,
@@ -404,14 +1050,14 @@ impl TerminalBufferHolder {
         //
         // If we truncated a newline it's the same situation
         if cursor_pos.x == 0 && buf_pos > 0 && self.buf[buf_pos - 1] != TChar::NewLine
-            || previous_last_char == TChar::NewLine
+            || previous_last_char == Some(TChar::NewLine)
         {
-            self.buf.push(TChar::NewLine);
+            self.buf.push_back(TChar::NewLine);
         }
 
         for line in visible_line_ranges {
             if line.end > buf_pos {
-                self.buf.push(TChar::NewLine);
+                self.buf.push_back(TChar::NewLine);
             }
         }
 
@@ -429,14 +1075,16 @@ impl TerminalBufferHolder {
 
         // assert_eq!(new_cursor_pos, cursor_pos.clone());
 
+        // The buffer has changed either way, so the rows have to be worked out again before
+        // giving up
+        self.line_ranges_to_visible_line_ranges();
+
         if new_cursor_pos != *cursor_pos {
             return Err(anyhow::anyhow!(
                 "Cursor position changed while clearing forwards"
             ));
         }
 
-        self.line_ranges_to_visible_line_ranges();
-
         Ok(Some(buf_pos))
     }
 
@@ -497,6 +1145,7 @@ impl TerminalBufferHolder {
     pub fn clear_all(&mut self) {
         self.buf.clear();
         self.visible_line_ranges.clear();
+        self.buffer_line_ranges.clear();
     }
 
     pub fn clear_visible(&mut self) -> Option<std::ops::Range<usize>> {
@@ -508,11 +1157,16 @@ impl TerminalBufferHolder {
 
         // replace all NONE newlines with spaces
         for line in &visible_line_ranges {
-            self.buf[line.start..line.end].iter_mut().for_each(|c| {
-                if *c != TChar::NewLine {
-                    *c = TChar::Space;
-                }
-            });
+            if line.end > self.buf.len() {
+                continue;
+            }
+            for c in self
+                .buf
+                .range_mut(line.clone())
+                .filter(|c| **c != TChar::NewLine)
+            {
+                *c = TChar::Space;
+            }
         }
 
         self.line_ranges_to_visible_line_ranges();
@@ -520,6 +1174,37 @@ impl TerminalBufferHolder {
         Some(visible_line_ranges[0].start..usize::MAX)
     }
 
+    /// Clear the screen by scrolling what is on it up in to the scrollback, so it can still be
+    /// scrolled back to. Returns the range of the blank lines that replaced it
+    ///
+    /// Rows below the end of the buffer are already blank, so they aren't added to the
+    /// scrollback. Without scrollback, as on the alternate screen, it is blanked in place instead.
+    pub fn scroll_visible_into_scrollback(&mut self) -> Option<Range<usize>> {
+        if !self.has_scrollback() {
+            return self.clear_visible();
+        }
+
+        if self.visible_line_ranges.is_empty() {
+            return None;
+        }
+
+        let old_end = self.buf.len();
+        for _ in 0..=self.height {
+            if self
+                .visible_line_ranges
+                .first()
+                .is_some_and(|line| line.start >= old_end)
+            {
+                break;
+            }
+
+            self.buf.push_back(TChar::NewLine);
+            self.line_ranges_to_visible_line_ranges();
+        }
+
+        Some(old_end..usize::MAX)
+    }
+
     pub fn delete_forwards(
         &mut self,
         cursor_pos: &CursorPos,
@@ -549,16 +1234,17 @@ impl TerminalBufferHolder {
     ) -> Option<Range<usize>> {
         let (buf_pos, line_range) = self.cursor_to_buf_pos(cursor_pos)?;
 
+        // ECH never goes past the end of the row, even if the line wraps on to the next one
         let mut erase_range = buf_pos..buf_pos + num_chars;
 
         if erase_range.end > line_range.end {
             erase_range.end = line_range.end;
         }
 
-        // // remove the range from the buffer
-        // self.buf.drain(erase_range.clone());
-        // replace all characters in range with spaces
-        self.buf[erase_range.clone()].fill(TChar::Space);
+        // Cells are blanked in place, nothing after them moves
+        self.buf
+            .range_mut(erase_range.clone())
+            .for_each(|c| *c = TChar::Space);
         self.line_ranges_to_visible_line_ranges();
 
         Some(erase_range)
@@ -570,13 +1256,15 @@ impl TerminalBufferHolder {
             self.buffer_line_ranges.len().saturating_sub(1)
         } else {
             self.viewable_index_bottom
+                .min(self.buffer_line_ranges.len().saturating_sub(1))
         };
 
-        if self.buf.is_empty() {
+        // A window with no rows has no lines to show, whatever is in the buffer
+        if self.buf.is_empty() || self.buffer_line_ranges.is_empty() {
             return (
                 TerminalSections {
                     scrollback: vec![],
-                    visible: self.buf.clone(),
+                    visible: self.buf.iter().cloned().collect(),
                 },
                 0,
                 0,
@@ -600,9 +1288,10 @@ impl TerminalBufferHolder {
         }
 
         // now ensure self.buffer_line_ranges[start].start..self.buffer_line_ranges[end].end falls
-        // entirely within self.buf
+        // entirely within self.buf, and is the right way round
         if self.buffer_line_ranges[start].start > self.buf.len()
             || self.buffer_line_ranges[end].end > self.buf.len()
+            || self.buffer_line_ranges[start].start > self.buffer_line_ranges[end].end
         {
             error!("Data for GUI: buffer line ranges are out of bounds. start: {}, buf start: {}, buf end: {}, end: {}, buf len: {}, height: {}, visible: {:?}, buffer ranges: {:?}",
                     start, self.buffer_line_ranges[start].start, end, self.buffer_line_ranges[end].end, self.buf.len(), self.height, self.visible_line_ranges.len(), self.buffer_line_ranges.len());
@@ -620,9 +1309,11 @@ impl TerminalBufferHolder {
         (
             TerminalSections {
                 scrollback: vec![],
-                visible: self.buf
-                    [self.buffer_line_ranges[start].start..self.buffer_line_ranges[end].end]
-                    .to_vec(),
+                visible: self
+                    .buf
+                    .range(self.buffer_line_ranges[start].start..self.buffer_line_ranges[end].end)
+                    .cloned()
+                    .collect(),
             },
             self.buffer_line_ranges[start].start,
             self.buffer_line_ranges[end].end,
@@ -636,14 +1327,14 @@ impl TerminalBufferHolder {
         if self.buf.is_empty() {
             return TerminalSections {
                 scrollback: vec![],
-                visible: self.buf.clone(),
+                visible: vec![],
             };
         }
 
         if visible_line_ranges.is_empty() {
             warn!("visible line ranges is empty but data in the buffer!");
             return TerminalSections {
-                scrollback: self.buf.clone(),
+                scrollback: self.buf.iter().cloned().collect(),
                 visible: vec![],
             };
         }
@@ -652,11 +1343,11 @@ impl TerminalBufferHolder {
 
         TerminalSections {
             scrollback: if include_scrollback {
-                self.buf[..start].to_vec()
+                self.buf.range(..start).cloned().collect()
             } else {
                 vec![]
             },
-            visible: self.buf[start..].to_vec(),
+            visible: self.buf.range(start..).cloned().collect(),
         }
     }
 
@@ -665,18 +1356,33 @@ impl TerminalBufferHolder {
         if self.buf.is_empty() {
             return None;
         }
-        // we want to keep the first 2000 lines + length of visible lines
-
-        let index = self
-            .buffer_line_ranges
-            .len()
-            .saturating_sub(2000 - self.visible_line_ranges.len() - 1);
-
-        if index == 0 {
+        // we want to keep the last `scrollback_lines` lines, visible lines included, but never
+        // fewer than the screen
+        let most = self
+            .scrollback_lines
+            .saturating_sub(self.visible_line_ranges.len() + 1)
+            .max(self.visible_line_ranges.len())
+            .max(1);
+        if self.buffer_line_ranges.len() <= most {
             return None;
         }
 
-        let keep_buf_pos = self.buffer_line_ranges[index].start - 1;
+        // The cells go from the front of the ring buffer without moving the rest, but every
+        // offset in to the buffer has to move back, here and in the format tags, selection,
+        // marks and timestamps. So drop a batch at once rather than a few lines for every chunk
+        // of output
+        let kept = most
+            .saturating_sub(most / SCROLLBACK_CLIP_BATCH_DIVISOR)
+            .max(self.visible_line_ranges.len())
+            .max(1);
+        let index = self.buffer_line_ranges.len() - kept;
+
+        // Keep the newline before the first line kept. The rows should all be in the buffer, but
+        // draining past its end would panic if one weren't
+        let keep_buf_pos = self.buffer_line_ranges[index]
+            .start
+            .saturating_sub(1)
+            .min(self.buf.len());
 
         self.buf.drain(0..keep_buf_pos);
         self.buffer_line_ranges.drain(0..index);
@@ -702,7 +1408,7 @@ impl TerminalBufferHolder {
 
     #[must_use]
     pub fn clip_lines_for_alternate_buffer(&mut self) -> Option<Range<usize>> {
-        if self.buf.is_empty() {
+        if self.buf.is_empty() || self.visible_line_ranges.is_empty() {
             return None;
         }
         // we want to keep the first height lines + length of visible lines
@@ -730,7 +1436,7 @@ impl TerminalBufferHolder {
             line_range.end = line_range.end.saturating_sub(keep_buf_pos);
 
             // ensure line range falls within the buffer
-            if line_range.start > self.buf.len() - 1 {
+            if line_range.start >= self.buf.len() {
                 error!(
                     "Line range start is greater than buffer length: {} > {}",
                     line_range.start,
@@ -753,7 +1459,7 @@ impl TerminalBufferHolder {
     }
 
     #[must_use]
-    pub fn get_raw_buffer(&self) -> &[TChar] {
+    pub const fn get_raw_buffer(&self) -> &VecDeque<TChar> {
         &self.buf
     }
 
@@ -768,6 +1474,7 @@ impl TerminalBufferHolder {
         height: usize,
         cursor_pos: &CursorPos,
     ) -> TerminalBufferSetWinSizeResponse {
+        let (width, height) = (width.max(1), height.max(1));
         let changed = self.width != width || self.height != height;
         if !changed {
             return TerminalBufferSetWinSizeResponse {
@@ -793,6 +1500,12 @@ impl TerminalBufferHolder {
         self.width = width;
         self.height = height;
 
+        // The rows were worked out for the old size, and editing the screen assumes they match
+        // the buffer
+        self.visible_line_ranges.clear();
+        self.buffer_line_ranges.clear();
+        self.line_ranges_to_visible_line_ranges();
+
         TerminalBufferSetWinSizeResponse {
             changed,
             _insertion_range: inserted_padding,
@@ -800,13 +1513,36 @@ impl TerminalBufferHolder {
         }
     }
 
-    pub fn set_top_and_bottom_margins(&mut self, top_margin: usize, bottom_margin: usize) {
-        self.top_margin = top_margin.saturating_sub(1);
-        self.bottom_margin = if bottom_margin == 0 {
+    /// Set the scrolling region to the lines from `top_margin` to `bottom_margin`, counted from
+    /// one. A `bottom_margin` of 0 or past the screen is the bottom of the screen
+    ///
+    /// Like xterm, a region of less than two lines on this screen is ignored. Returns whether
+    /// the region was set.
+    pub fn set_top_and_bottom_margins(&mut self, top_margin: usize, bottom_margin: usize) -> bool {
+        let top_margin = top_margin.saturating_sub(1);
+        let bottom_margin = if bottom_margin == 0 {
             usize::MAX
         } else {
             bottom_margin.saturating_sub(1)
         };
+
+        if top_margin >= bottom_margin.min(self.height.saturating_sub(1)) {
+            return false;
+        }
+
+        self.top_margin = top_margin;
+        self.bottom_margin = bottom_margin;
+        true
+    }
+
+    /// The first and last visible lines of the scrolling region, counted from zero
+    #[must_use]
+    pub fn get_scrolling_region(&self) -> (usize, usize) {
+        let last_line = self.height.saturating_sub(1);
+        (
+            self.top_margin.min(last_line),
+            self.bottom_margin.min(last_line),
+        )
     }
 
     /// Given terminal height `height`, extract the visible line ranges from all line ranges (which
@@ -920,6 +1656,12 @@ impl TerminalBufferHolder {
             }
         }
 
+        // The last row of a wrapped line takes in the newline after it. If the buffer doesn't end
+        // with one, that would run off the end
+        for range in &mut ret {
+            range.end = range.end.min(buf.len());
+        }
+
         // sort the ranges by start position
         ret.sort_by(|a, b| a.start.cmp(&b.start));
 
@@ -1120,7 +1862,7 @@ impl TerminalBufferHolder {
 
         if self.visible_line_ranges.is_empty()
             || self.visible_line_ranges.len() < self.height
-            || self.buffer_type == BufferType::Alternate
+            || !self.has_scrollback()
         {
             self.buffer_line_ranges = self.visible_line_ranges.clone();
             return;
@@ -1145,8 +1887,8 @@ impl TerminalBufferHolder {
             let mut start_pos = visible_start.saturating_sub(1);
             let mut end = self.buffer_line_ranges.last().unwrap_or(&(0..0)).end;
             let mut walk_anyway = false;
-            if self.buf[end.saturating_sub(1)] == TChar::NewLine {
-                start_pos -= 1;
+            if self.buf.get(end.saturating_sub(1)) == Some(&TChar::NewLine) {
+                start_pos = start_pos.saturating_sub(1);
             }
 
             if start_pos < end {
@@ -1209,6 +1951,142 @@ impl TerminalBufferHolder {
         }
     }
 
+    /// Where `query` appears in the buffer, scrollback included, oldest first
+    #[must_use]
+    pub fn search(&self, query: &str) -> Vec<Range<usize>> {
+        find_matches(&self.buf, query)
+    }
+
+    /// Convert a position on screen to a buffer position. Positions past the end of a line are
+    /// moved back to the end of that line, and rows below the last line to the end of the buffer
+    #[must_use]
+    pub fn visible_pos_to_buf_pos_clamped(&self, cursor_pos: &CursorPos) -> usize {
+        self.visible_line_ranges
+            .get(cursor_pos.y)
+            .map_or(self.buf.len(), |line_range| {
+                (line_range.start + cursor_pos.x).min(line_range.end)
+            })
+    }
+
+    /// The text in `buf_range`, as it should be copied
+    ///
+    /// Rows that were soft wrapped are joined back together, since only hard line breaks have a
+    /// newline in the buffer. Spaces at the end of each line are usually padding from cursor
+    /// movement, so they are dropped if `trim_trailing_spaces` is set.
+    #[must_use]
+    pub fn text_for_buf_range(
+        &self,
+        buf_range: Range<usize>,
+        trim_trailing_spaces: bool,
+    ) -> String {
+        let end = buf_range.end.min(self.buf.len());
+        let start = buf_range.start.min(end);
+        let mut text = String::new();
+
+        for c in self.buf.range(start..end) {
+            match c {
+                TChar::NewLine => {
+                    if trim_trailing_spaces {
+                        trim_spaces(&mut text);
+                    }
+                    text.push('\n');
+                }
+                TChar::Space => text.push(' '),
+                TChar::Ascii(c) => text.push(char::from(*c)),
+                TChar::Utf8(v) => text.push_str(std::str::from_utf8(v).unwrap_or_default()),
+                TChar::WideContinuation => (),
+            }
+        }
+
+        if trim_trailing_spaces {
+            trim_spaces(&mut text);
+        }
+
+        text
+    }
+
+    /// Mask the secrets `redactor` finds in the cells of `written`, which was just written.
+    /// Enough of the text before it is looked at too to find a secret that was split between
+    /// writes. Each cell of a secret becomes a `REDACTION_CHAR`, so nothing moves
+    pub fn redact(&mut self, redactor: &Redactor, written: &Range<usize>) {
+        let end = written.end.min(self.buf.len());
+        let start = written
+            .start
+            .saturating_sub(redactor.max_match_len())
+            .min(end);
+
+        // The text, and the cell each byte of it is in
+        let mut text = Vec::new();
+        let mut cells = Vec::new();
+        for (buf_pos, c) in (start..end).zip(self.buf.range(start..end)) {
+            let bytes = match c {
+                TChar::Ascii(c) => std::slice::from_ref(c),
+                TChar::Utf8(v) => v.as_slice(),
+                TChar::Space => b" ",
+                TChar::NewLine => b"\n",
+                TChar::WideContinuation => continue,
+            };
+            text.extend_from_slice(bytes);
+            cells.extend(std::iter::repeat_n(buf_pos, bytes.len()));
+        }
+
+        let secrets = redactor
+            .find_iter(&text)
+            .map(|secret| cells[secret.start]..cells[secret.end - 1] + 1)
+            .collect::<Vec<_>>();
+        for secret in secrets {
+            // Along with the second cell of a double width character at the end
+            let end = if self.buf.get(secret.end) == Some(&TChar::WideContinuation) {
+                secret.end + 1
+            } else {
+                secret.end
+            };
+            for c in self.buf.range_mut(secret.start..end) {
+                if *c != TChar::NewLine {
+                    *c = TChar::Ascii(REDACTION_CHAR);
+                }
+            }
+        }
+    }
+
+    /// The characters of the visible line `y`, along with where that line starts in the buffer
+    #[must_use]
+    pub fn visible_line_chars(&self, y: usize) -> Option<(usize, Vec<char>)> {
+        self.line_chars(self.visible_line_ranges.get(y)?)
+    }
+
+    /// The characters of the line in the viewport that `buf_pos` is on, along with where that
+    /// line starts in the buffer. When the view doesn't wrap, this is the whole line, including
+    /// any of it off the edges of the screen
+    #[must_use]
+    pub fn viewport_line_chars_at(&self, buf_pos: usize) -> Option<(usize, Vec<char>)> {
+        let line = self
+            .viewport_lines()
+            .into_iter()
+            .find(|line| line.contains(&buf_pos))?;
+        self.line_chars(&line)
+    }
+
+    fn line_chars(&self, line_range: &Range<usize>) -> Option<(usize, Vec<char>)> {
+        // The second cell of a double width character is part of the same character
+        let mut previous = ' ';
+        if line_range.end > self.buf.len() {
+            return None;
+        }
+        let chars = self
+            .buf
+            .range(line_range.clone())
+            .map(|c| {
+                if *c != TChar::WideContinuation {
+                    previous = c.to_char();
+                }
+                previous
+            })
+            .collect();
+
+        Some((line_range.start, chars))
+    }
+
     #[must_use]
     pub fn cursor_pos_to_buf_pos(&self, cursor_pos: &CursorPos) -> Option<usize> {
         let visible_line_ranges = &self.visible_line_ranges;
@@ -1245,7 +2123,7 @@ impl TerminalBufferHolder {
         }
 
         for _ in 0..vertical_padding_needed {
-            buf.push(TChar::NewLine);
+            buf.push_back(TChar::NewLine);
             let newline_pos = buf.len() - 1;
             visible_line_ranges.push(newline_pos..newline_pos);
         }
@@ -1311,6 +2189,15 @@ impl TerminalBufferHolder {
     }
 }
 
+/// A position equal to the end of a line is that line's newline, so it belongs to the line after it
+const fn line_contains_or_follows(line: &Range<usize>, buf_pos: usize) -> bool {
+    line.start >= buf_pos || line.end > buf_pos
+}
+
+fn trim_spaces(text: &mut String) {
+    text.truncate(text.trim_end_matches(' ').len());
+}
+
 fn ranges_from_start_and_end(
     current_length: usize,
     position: usize,
@@ -114,6 +114,13 @@ impl StateColors {
         }
     }
 
+    /// Swap any colors the application picked itself for the nearest palette color
+    pub fn clamp_to_palette(&mut self) {
+        self.color = self.color.clamp_to_palette();
+        self.background_color = self.background_color.clamp_to_palette();
+        self.underline_color = self.underline_color.clamp_to_palette();
+    }
+
     pub fn flip_reverse_video(&mut self) {
         self.reverse_video = match self.reverse_video {
             ReverseVideo::On => ReverseVideo::Off,
@@ -122,6 +129,22 @@ impl StateColors {
     }
 }
 
+/// SGR parameters that select `color`. `base` is the parameter for the first of the 8 normal
+/// colors (ie, 30 for foreground) and `extended` the one for 256 and true color (ie, 38)
+fn color_sgr_params(color: TerminalColor, base: Option<usize>, extended: usize) -> Option<String> {
+    if let TerminalColor::Custom(r, g, b) = color {
+        return Some(format!("{extended};2;{r};{g};{b}"));
+    }
+
+    let index = color.palette_index()?;
+
+    Some(match base {
+        Some(base) if index < 8 => (base + index).to_string(),
+        Some(base) => (base + 60 + index - 8).to_string(),
+        None => format!("{extended};5;{index}"),
+    })
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Eq, PartialEq, Debug, Clone, Default)]
 pub struct CursorState {
@@ -139,6 +162,56 @@ impl CursorState {
         Self::default()
     }
 
+    /// The attributes cells get when they are erased. Everything is reset apart from the
+    /// background color, which erased cells keep (background color erase)
+    #[must_use]
+    pub fn erased_cell_state(&self) -> Self {
+        Self {
+            pos: self.pos.clone(),
+            colors: StateColors::default().with_background_color(self.colors.background_color),
+            line_wrap_mode: self.line_wrap_mode.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// The SGR parameters that recreate the current attributes starting from a reset, like
+    /// `0;1;31`. Used to answer DECRQSS
+    #[must_use]
+    pub fn sgr_params(&self) -> String {
+        let mut params = vec!["0".to_string()];
+
+        if self.font_weight == FontWeight::Bold {
+            params.push("1".to_string());
+        }
+
+        for (decoration, param) in [
+            (FontDecorations::Faint, "2"),
+            (FontDecorations::Italic, "3"),
+            (FontDecorations::Underline, "4"),
+        ] {
+            if self.font_decorations.contains(&decoration) {
+                params.push(param.to_string());
+            }
+        }
+
+        if self.colors.reverse_video == ReverseVideo::On {
+            params.push("7".to_string());
+        }
+
+        if self
+            .font_decorations
+            .contains(&FontDecorations::Strikethrough)
+        {
+            params.push("9".to_string());
+        }
+
+        params.extend(color_sgr_params(self.colors.color, Some(30), 38));
+        params.extend(color_sgr_params(self.colors.background_color, Some(40), 48));
+        params.extend(color_sgr_params(self.colors.underline_color, None, 58));
+
+        params.join(";")
+    }
+
     #[must_use]
     pub fn with_background_color(mut self, background_color: TerminalColor) -> Self {
         self.colors.set_background_color(background_color);
@@ -3,8 +3,154 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+use std::ops::Range;
+
+use super::{cursor::CursorPos, term_char::TChar};
+use crate::format_tracker::FormatTag;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TerminalSections<T> {
     pub scrollback: T,
     pub visible: T,
 }
+
+/// One row of the viewport in a `RenderSlice`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RenderRow {
+    /// Where the row's characters are in the buffer
+    pub buffer: Range<usize>,
+    /// Where they are in `RenderSlice::text`
+    pub text: Range<usize>,
+    /// The row is the end of a line, rather than wrapped or cut off at the edge of the screen, so
+    /// the newline at the end of `buffer` goes with it
+    pub ends_line: bool,
+}
+
+/// What the GUI draws for the viewport, with everything it needs to line it up with the buffer
+///
+/// `text` starts at the start of the top row and stops at the end of the bottom one, and never
+/// ends with a newline. When the view wraps, it is the buffer from the top row to the bottom one,
+/// and the GUI wraps it at the width of the screen. When it doesn't, it is each row cut off at the
+/// edges of the screen, with a newline between them.
+///
+/// The offsets in `format_data` are into `text`, and each tag lies inside it. `rows` says where
+/// each row came from in the buffer, so buffer offsets, such as those of a selection, a search
+/// match or a command's output, can be turned in to offsets in `text` and back. When bidi text is
+/// reordered the characters inside a row are moved about, but the rows stay where they are.
+///
+/// A cell on screen is row `y` and column `x`, and each cell is one character in the buffer, so
+/// the GUI finds what is under the mouse and where to draw a selection from `rows` too.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RenderSlice {
+    pub text: Vec<TChar>,
+    pub format_data: Vec<FormatTag>,
+    /// Top to bottom
+    pub rows: Vec<RenderRow>,
+}
+
+impl RenderSlice {
+    /// A slice of `text` with `format_data` offsets already in to it. The newline the bottom row
+    /// ends with is dropped, and the tags are cut down to the text that is left
+    #[must_use]
+    pub fn new(
+        mut text: Vec<TChar>,
+        format_data: Vec<FormatTag>,
+        mut rows: Vec<RenderRow>,
+    ) -> Self {
+        if text.last() == Some(&TChar::NewLine) {
+            text.pop();
+        }
+
+        for row in &mut rows {
+            row.text.end = row.text.end.min(text.len());
+            row.buffer.end = row.buffer.start + row.text.len().min(row.buffer.len());
+        }
+
+        let len = text.len();
+        let format_data = format_data
+            .into_iter()
+            .filter(|tag| tag.start < len)
+            .map(|tag| FormatTag {
+                end: tag.end.min(len),
+                ..tag
+            })
+            .collect();
+
+        Self {
+            text,
+            format_data,
+            rows,
+        }
+    }
+
+    /// The part of the buffer shown, from the start of the top row to the end of the bottom one
+    #[must_use]
+    pub fn buffer_range(&self) -> Range<usize> {
+        let start = self.rows.first().map_or(0, |row| row.buffer.start);
+        let end = self.rows.last().map_or(0, |row| row.buffer.end);
+
+        start..end
+    }
+
+    /// Where `buffer_offset` is in `text`, or `None` if it isn't on screen. The end of a row counts
+    /// as being on it
+    #[must_use]
+    pub fn text_offset(&self, buffer_offset: usize) -> Option<usize> {
+        self.rows
+            .iter()
+            .find(|row| row.buffer.contains(&buffer_offset) || row.buffer.end == buffer_offset)
+            .map(|row| row.text.start + buffer_offset - row.buffer.start)
+    }
+
+    /// Where `text_offset` came from in the buffer, or `None` if it is a newline put between rows
+    /// or past the end of the text
+    #[must_use]
+    pub fn buffer_offset(&self, text_offset: usize) -> Option<usize> {
+        self.rows
+            .iter()
+            .find(|row| row.text.contains(&text_offset))
+            .map(|row| row.buffer.start + text_offset - row.text.start)
+    }
+
+    /// The buffer offset of the cell at `pos` on screen, or `None` if it is past the end of its
+    /// row
+    #[must_use]
+    pub fn buffer_offset_at(&self, pos: &CursorPos) -> Option<usize> {
+        let row = &self.rows.get(pos.y)?.buffer;
+        let offset = row.start + pos.x;
+
+        (offset < row.end).then_some(offset)
+    }
+
+    /// The buffer offset nearest the cell at `pos` on screen, for dragging out a selection. Past
+    /// the end of a row is the newline that ends it, or its last character if it carries on, and
+    /// below the bottom row is the end of that row
+    #[must_use]
+    pub fn nearest_buffer_offset(&self, pos: &CursorPos) -> Option<usize> {
+        let (row, x) = match self.rows.get(pos.y) {
+            Some(row) => (row, pos.x),
+            None => (self.rows.last()?, usize::MAX),
+        };
+        let last = if row.ends_line {
+            row.buffer.end
+        } else {
+            row.buffer.end.saturating_sub(1).max(row.buffer.start)
+        };
+
+        Some(row.buffer.start.saturating_add(x).min(last))
+    }
+
+    /// The cells on screen that show `buffer_range`, as a range of columns for each row
+    #[must_use]
+    pub fn cells(&self, buffer_range: &Range<usize>) -> Vec<(usize, Range<usize>)> {
+        self.rows
+            .iter()
+            .enumerate()
+            .filter_map(|(y, row)| {
+                let start = buffer_range.start.max(row.buffer.start);
+                let end = buffer_range.end.min(row.buffer.end);
+                (start < end).then(|| (y, start - row.buffer.start..end - row.buffer.start))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::interface::TerminalInput;
+
+/// How long a predicted character waits for the shell to echo it before we give up on it
+pub const PREDICTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Guesses at what the shell will echo for the keys being typed, so they can be shown straight
+/// away over a slow connection, like mosh does
+///
+/// Printable characters typed at the shell are assumed to be echoed as they are. The guesses are
+/// dropped as the real echo arrives, or all at once if the output is something else. Nothing is
+/// shown until the shell has echoed one of the guesses on the line, so typing at a password
+/// prompt is never drawn. If the echo doesn't come at all, no more guesses are made until Enter
+/// is pressed.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct EchoPrediction {
+    pub enabled: bool,
+    /// Characters typed that haven't been echoed yet, oldest first
+    pending: VecDeque<char>,
+    /// When the first pending character was typed, or the last one before it was echoed
+    oldest: Option<Instant>,
+    /// An echo didn't come in time, so stop guessing until the next line
+    suspended: bool,
+    /// The shell has echoed a guess on this line, so it is echoing and the guesses can be shown
+    confirmed: bool,
+}
+
+impl EchoPrediction {
+    #[must_use]
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    /// The characters to show after the cursor, oldest first. Empty until an echo has been
+    /// confirmed on this line
+    #[must_use]
+    pub fn predicted_text(&self) -> String {
+        if self.confirmed {
+            self.pending.iter().collect()
+        } else {
+            String::new()
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Guess at the echo for `input`, typed at `now`
+    pub fn typed(&mut self, input: &TerminalInput, now: Instant) {
+        if !self.enabled {
+            return;
+        }
+
+        match input {
+            TerminalInput::Ascii(c) if (0x20..0x7f).contains(c) => {
+                if self.suspended {
+                    return;
+                }
+
+                self.pending.push_back(char::from(*c));
+                self.oldest.get_or_insert(now);
+            }
+            TerminalInput::Backspace if !self.pending.is_empty() => {
+                self.pending.pop_back();
+                if self.pending.is_empty() {
+                    self.oldest = None;
+                }
+            }
+            TerminalInput::Enter => {
+                self.clear();
+                self.suspended = false;
+                self.confirmed = false;
+            }
+            // We can't tell what anything else will do to the line
+            _ => self.clear(),
+        }
+    }
+
+    /// The shell wrote `data`. Guesses it matches are confirmed, and the rest are dropped if it
+    /// doesn't match
+    pub fn output_received(&mut self, data: &[u8], now: Instant) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        for c in String::from_utf8_lossy(data).chars() {
+            if self.pending.front() == Some(&c) {
+                self.pending.pop_front();
+                self.oldest = (!self.pending.is_empty()).then_some(now);
+                self.confirmed = true;
+            } else {
+                self.clear();
+                self.confirmed = false;
+                return;
+            }
+        }
+    }
+
+    /// Forget all the guesses
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.oldest = None;
+    }
+
+    /// Give up on guesses the shell hasn't echoed in time. Returns how long until the rest time
+    /// out, if there are any
+    pub fn expire(&mut self, now: Instant) -> Option<Duration> {
+        let left = PREDICTION_TIMEOUT
+            .checked_sub(now.duration_since(self.oldest?))
+            .filter(|left| !left.is_zero());
+        if left.is_none() {
+            self.clear();
+            self.suspended = true;
+        }
+
+        left
+    }
+}
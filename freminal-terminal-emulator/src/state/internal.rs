@@ -6,43 +6,88 @@
 use anyhow::Result;
 use conv::ConvUtil;
 use core::str;
-use eframe::egui::{self, Color32, Context};
 use freminal_common::{
-    colors::TerminalColor, scroll::ScrollDirection, window_manipulation::WindowManipulation,
+    cell_geometry::SizeLimits,
+    char_width::WidthProvider,
+    colors::{
+        format_x11_color, lookup_256_color_by_index, parse_x11_color, Palette, TerminalColor,
+        PALETTE_COLORS,
+    },
+    cursor::{CursorSettings, CursorVisualStyle},
+    metrics::{Metrics, SharedInputLatency},
+    redaction::Redactor,
+    scroll::{ScrollAccumulator, ScrollDirection, ScrollSettings, ViewportMovement},
+    text_classes::{UrlSchemes, WordCharacters},
+    window_manipulation::WindowManipulation,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
-#[cfg(debug_assertions)]
-use std::time::Instant;
 
 use crate::{
     ansi::{FreminalAnsiParser, TerminalOutput},
     ansi_components::{
+        csi_commands::kitty_keyboard::KittyKeyboardRequest,
         line_draw::DecSpecialGraphics,
-        mode::{Mode, MouseTrack, SetMode, TerminalModes},
+        mode::{terminal_mode_from_params, Mode, MouseTrack, SetMode, TerminalModes},
         modes::{
-            decawm::Decawm, decckm::Decckm, dectcem::Dectcem, rl_bracket::RlBracket,
-            sync_updates::SynchronizedUpdates, xtcblink::XtCBlink, xtextscrn::XtExtscrn,
-            xtmsewin::XtMseWin, MouseModeNumber, ReportMode,
+            alt_scroll::AltScroll, bdsm::Bdsm, decawm::Decawm, decbkm::Decbkm, decckm::Decckm,
+            decnkm::Decnkm, decom::Decom, dectcem::Dectcem, rl_bracket::RlBracket,
+            save_cursor::SaveCursor, sync_updates::SynchronizedUpdates, xtcblink::XtCBlink,
+            xtextscrn::XtExtscrn, xtmsewin::XtMseWin, MouseModeNumber, ReportMode,
         },
-        osc::{AnsiOscInternalType, AnsiOscType, UrlResponse},
+        osc::{AnsiOscInternalType, AnsiOscType, FtcsMarker, Url, UrlResponse, WorkingDirectory},
         sgr::SelectGraphicRendition,
     },
     format_tracker::{FormatTag, FormatTracker},
-    interface::{
-        collect_text, split_format_data_for_scrollback, TerminalInput, TerminalInputPayload,
-    },
-    io::PtyWrite,
+    input::KeyboardProtocol,
+    interface::{collect_text, format_data_for_rows, TerminalInput},
+    io::{corpus::FuzzCorpus, PtySignal, PtyWrite},
 };
 
 use super::{
-    buffer::{TerminalBufferHolder, TerminalBufferSetWinSizeResponse},
+    bell::BellState,
+    bidi::reorder_lines,
+    buffer::{
+        TerminalBufferHolder, TerminalBufferInsertLineResponse, TerminalBufferSetWinSizeResponse,
+    },
     cursor::{CursorPos, CursorState, ReverseVideo},
-    data::TerminalSections,
+    data::{RenderRow, RenderSlice, TerminalSections},
+    echo_prediction::EchoPrediction,
     fonts::{FontDecorations, FontWeight},
+    kitty_keyboard::KittyKeyboardFlags,
+    line_timestamps::LineTimestamps,
+    output_processor::OutputProcessor,
+    redaction::RedactSecrets,
+    redraw::SharedRedrawNotifier,
+    repaint::RepaintLimiter,
+    search::SearchState,
+    selection::Selection,
+    shell_integration::{CommandHistory, CommandMark},
     term_char::TChar,
+    term_warning::TermWarning,
+    unsupported::UnsupportedFeatures,
 };
 
 pub const TERMINAL_WIDTH: usize = 50;
 pub const TERMINAL_HEIGHT: usize = 16;
+/// A synchronized update (mode ?2026) that lasts longer than this is ended for the application.
+/// One that died part way through would otherwise leave the screen frozen
+pub const SYNC_UPDATE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long to keep the shell's startup output hidden while waiting for its first prompt mark.
+/// Shells without OSC 133 integration never send one, so their output is shown after this
+pub const STARTUP_BANNER_TIMEOUT: Duration = Duration::from_secs(3);
+/// How many opened links are remembered to be shown as visited
+pub const VISITED_LINKS_LIMIT: usize = 64;
+/// The color visited links are drawn in, if the application left them the default color
+pub const VISITED_LINK_COLOR: TerminalColor = TerminalColor::Magenta;
+/// Reported for the text color (OSC 10) when the theme leaves it to the GUI
+pub const DEFAULT_FOREGROUND_REPLY: (u8, u8, u8) = (255, 255, 255);
+/// Reported for the background color (OSC 11) when the theme leaves it to the GUI
+pub const DEFAULT_BACKGROUND_REPLY: (u8, u8, u8) = (0, 0, 0);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum BufferType {
@@ -51,14 +96,23 @@ pub enum BufferType {
     Alternate,
 }
 
+/// One of the two screens, along with the modes that belong to it rather than to the terminal
+/// as a whole. Switching screens brings these back to how they were left
 #[derive(Debug, PartialEq, Eq)]
 pub struct Buffer {
     pub terminal_buffer: TerminalBufferHolder,
     pub format_tracker: FormatTracker,
     pub cursor_state: CursorState,
     pub show_cursor: Dectcem,
+    pub origin_mode: Decom,
     pub saved_cursor_position: Option<CursorPos>,
     pub cursor_color: TerminalColor,
+    pub command_history: CommandHistory,
+    pub line_timestamps: LineTimestamps,
+    /// Text the user has selected with the mouse
+    pub selection: Option<Selection>,
+    /// The kitty keyboard protocol's flags for this screen
+    pub kitty_keyboard: KittyKeyboardFlags,
 }
 
 impl Default for Buffer {
@@ -69,11 +123,16 @@ impl Default for Buffer {
             format_tracker: FormatTracker::new(),
             saved_cursor_position: None,
             show_cursor: Dectcem::default(),
+            origin_mode: Decom::default(),
             terminal_buffer: TerminalBufferHolder::new(
                 TERMINAL_WIDTH,
                 TERMINAL_HEIGHT,
                 BufferType::Primary,
             ),
+            command_history: CommandHistory::new(),
+            line_timestamps: LineTimestamps::new(),
+            selection: None,
+            kitty_keyboard: KittyKeyboardFlags::new(),
         }
     }
 }
@@ -86,8 +145,13 @@ impl Buffer {
             format_tracker: FormatTracker::new(),
             cursor_state: CursorState::default(),
             show_cursor: Dectcem::default(),
+            origin_mode: Decom::default(),
             saved_cursor_position: None,
             cursor_color: TerminalColor::DefaultCursorColor,
+            command_history: CommandHistory::new(),
+            line_timestamps: LineTimestamps::new(),
+            selection: None,
+            kitty_keyboard: KittyKeyboardFlags::new(),
         }
     }
 
@@ -95,8 +159,55 @@ impl Buffer {
     pub fn show_cursor(&self) -> bool {
         self.terminal_buffer.show_cursor(&self.cursor_state.pos)
     }
+
+    /// Move the formatting and line timestamps to follow lines being inserted or deleted
+    fn apply_line_changes(&mut self, response: TerminalBufferInsertLineResponse) {
+        let TerminalBufferInsertLineResponse {
+            deleted_range,
+            inserted_range,
+        } = response;
+        let deleted_first = deleted_range.start >= inserted_range.start;
+
+        if !deleted_first {
+            self.line_timestamps.insert_range(&inserted_range);
+            self.format_tracker
+                .push_range_adjustment(inserted_range.clone());
+        }
+
+        self.line_timestamps.delete_range(&deleted_range);
+        if let Err(e) = self.format_tracker.delete_range(deleted_range) {
+            error!("Failed to delete range: {e}");
+            return;
+        }
+
+        if deleted_first {
+            self.line_timestamps.insert_range(&inserted_range);
+            self.format_tracker.push_range_adjustment(inserted_range);
+        }
+    }
+
+    /// Remember where the cursor is. Each screen has its own saved position, so saving on one
+    /// doesn't lose what was saved on the other
+    pub fn save_cursor(&mut self) {
+        self.saved_cursor_position = Some(self.cursor_state.pos.clone());
+    }
+
+    /// Move the cursor back to where it was saved, or to the top left if it never was
+    pub fn restore_cursor(&mut self) {
+        self.cursor_state.pos = self.saved_cursor_position.clone().unwrap_or_default();
+    }
 }
 
+/// An OSC 8 hyperlink on screen
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Hyperlink {
+    /// Where the link goes
+    pub url: String,
+    /// What the link is shown as
+    pub text: String,
+}
+
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug)]
 pub struct TerminalState {
     pub parser: FreminalAnsiParser,
@@ -104,14 +215,83 @@ pub struct TerminalState {
     pub primary_buffer: Buffer,
     pub alternate_buffer: Buffer,
     pub modes: TerminalModes,
+    /// Private modes saved with XTSAVE, and whether each was set
+    pub saved_private_modes: BTreeMap<usize, bool>,
     pub write_tx: crossbeam_channel::Sender<PtyWrite>,
     pub changed: bool,
-    pub ctx: Option<Context>,
+    /// Told when there is something new to draw
+    pub redraw_notifier: Option<SharedRedrawNotifier>,
     pub leftover_data: Option<Vec<u8>>,
     pub character_replace: DecSpecialGraphics,
-    pub mouse_position: Option<egui::Pos2>,
+    /// Where the pointer is over the terminal, in points
+    pub mouse_position: Option<(f32, f32)>,
     pub window_focused: bool,
+    /// Whether the window can be seen. While it is minimized we keep the terminal up to date but
+    /// don't ask for frames nobody will see
+    pub window_visible: bool,
     pub window_commands: Vec<WindowManipulation>,
+    pub repaint_limiter: RepaintLimiter,
+    pub metrics: Metrics,
+    pub input_latency: SharedInputLatency,
+    pub word_characters: WordCharacters,
+    pub url_schemes: UrlSchemes,
+    pub scroll_settings: ScrollSettings,
+    pub cursor_settings: CursorSettings,
+    /// The cursor shape an application asked for with DECSCUSR, over the user's
+    /// `cursor_settings.style`
+    pub requested_cursor_style: Option<CursorVisualStyle>,
+    pub scroll_accumulator: ScrollAccumulator,
+    /// Columns scrolled sideways that don't add up to a whole one yet, when the view doesn't wrap
+    pub column_scroll_accumulator: ScrollAccumulator,
+    /// Lines the viewport was moved by the user since the GUI last asked. Positive is up
+    pub viewport_scrolled_lines: isize,
+    pub bell: BellState,
+    /// Drop spaces from the end of each line when copying
+    pub trim_trailing_spaces_on_copy: bool,
+    /// Draw colors picked by applications with the nearest palette color instead
+    pub clamp_colors_to_palette: bool,
+    /// Colors the 16 palette entries and the default colors are drawn with, including any
+    /// applications have set
+    pub palette: Palette,
+    /// The palette the user picked, which applications can put the colors back to
+    pub user_palette: Palette,
+    /// Clearing the screen (ED 2) scrolls what was on it in to the scrollback, rather than
+    /// blanking it where it is
+    pub scrollback_on_clear: bool,
+    /// Put right to left text, such as Arabic and Hebrew, in display order. Applications can
+    /// still turn this off with BDSM if they order the text themselves
+    pub bidi_enabled: bool,
+    /// Where sequences the parser couldn't handle are saved, if anywhere
+    pub fuzz_corpus: Option<FuzzCorpus>,
+    /// Run on everything parsed from the output before it is acted on
+    pub output_processors: Vec<Box<dyn OutputProcessor>>,
+    /// Masks secrets in text once it is written, if the user gave any patterns
+    pub redactor: Option<Redactor>,
+    /// The shell's working directory, if it reports it with OSC 7
+    pub working_directory: Option<WorkingDirectory>,
+    /// When the current synchronized update started, if one is going on
+    pub sync_update_started: Option<Instant>,
+    /// Output is kept off the screen until the shell's first prompt mark, or until this time if
+    /// it doesn't send one. Everything before the mark, such as the message of the day, is
+    /// thrown away
+    pub hide_startup_banner_until: Option<Instant>,
+    /// Looks out for programs that don't know our `TERM`, so the GUI can suggest another
+    pub term_warning: TermWarning,
+    /// The OSC 8 hyperlink under the mouse, and the part of the buffer it covers, drawn
+    /// underlined
+    pub hovered_link: Option<(Url, Range<usize>)>,
+    /// Links that have been opened, most recent last
+    pub visited_links: VecDeque<String>,
+    /// What the user is searching the scrollback for
+    pub search: SearchState,
+    /// Draw links that have been opened in `VISITED_LINK_COLOR`
+    pub style_visited_links: bool,
+    /// Typed characters shown before the shell echoes them
+    pub echo_prediction: EchoPrediction,
+    /// The smallest and largest the terminal can be resized to, by the window or an application
+    pub size_limits: SizeLimits,
+    /// What programs have asked for that we don't support
+    pub unsupported: UnsupportedFeatures,
 }
 
 impl Default for TerminalState {
@@ -129,7 +309,10 @@ impl PartialEq for TerminalState {
             && self.alternate_buffer == other.alternate_buffer
             && self.modes == other.modes
             && self.changed == other.changed
-            && self.ctx == other.ctx
+            && match (&self.redraw_notifier, &other.redraw_notifier) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
             && self.leftover_data == other.leftover_data
             && self.character_replace == other.character_replace
     }
@@ -144,20 +327,140 @@ impl TerminalState {
             primary_buffer: Buffer::new(TERMINAL_WIDTH, TERMINAL_HEIGHT, BufferType::Primary),
             alternate_buffer: Buffer::new(TERMINAL_WIDTH, TERMINAL_HEIGHT, BufferType::Alternate),
             modes: TerminalModes::default(),
+            saved_private_modes: BTreeMap::new(),
             write_tx,
             changed: false,
-            ctx: None,
+            redraw_notifier: None,
             leftover_data: None,
             character_replace: DecSpecialGraphics::DontReplace,
             mouse_position: None,
             window_focused: true,
+            window_visible: true,
             window_commands: Vec::new(),
+            repaint_limiter: RepaintLimiter::default(),
+            metrics: Metrics::new(),
+            input_latency: SharedInputLatency::new(),
+            word_characters: WordCharacters::default(),
+            url_schemes: UrlSchemes::default(),
+            scroll_settings: ScrollSettings::default(),
+            cursor_settings: CursorSettings::default(),
+            requested_cursor_style: None,
+            scroll_accumulator: ScrollAccumulator::new(),
+            column_scroll_accumulator: ScrollAccumulator::new(),
+            viewport_scrolled_lines: 0,
+            bell: BellState::new(),
+            trim_trailing_spaces_on_copy: true,
+            clamp_colors_to_palette: false,
+            palette: Palette::default(),
+            user_palette: Palette::default(),
+            scrollback_on_clear: true,
+            bidi_enabled: false,
+            fuzz_corpus: None,
+            output_processors: Vec::new(),
+            redactor: None,
+            working_directory: None,
+            sync_update_started: None,
+            hide_startup_banner_until: None,
+            term_warning: TermWarning::new(false),
+            hovered_link: None,
+            visited_links: VecDeque::new(),
+            style_visited_links: true,
+            search: SearchState::default(),
+            echo_prediction: EchoPrediction::default(),
+            size_limits: SizeLimits::default(),
+            unsupported: UnsupportedFeatures::default(),
+        }
+    }
+
+    /// The cursor to draw: the one the application asked for, or the user's if it hasn't
+    #[must_use]
+    pub fn cursor_visual_style(&self) -> CursorVisualStyle {
+        self.requested_cursor_style
+            .unwrap_or(self.cursor_settings.style)
+    }
+
+    /// Whether the application wants the cursor to blink, either through its DECSCUSR style or
+    /// with mode 12. The user can still turn blinking off with the cursor settings
+    #[must_use]
+    pub fn cursor_blinks(&self) -> bool {
+        self.cursor_visual_style().is_blinking() || self.modes.cursor_blinking == XtCBlink::Blinking
+    }
+
+    #[must_use]
+    pub fn width_provider(&mut self) -> WidthProvider {
+        self.get_current_buffer().terminal_buffer.width_provider()
+    }
+
+    /// How many cells each character written from now on takes up, on both screens
+    pub fn set_width_provider(&mut self, width_provider: WidthProvider) {
+        self.primary_buffer
+            .terminal_buffer
+            .set_width_provider(width_provider);
+        self.alternate_buffer
+            .terminal_buffer
+            .set_width_provider(width_provider);
+    }
+
+    /// Most lines the primary screen keeps, its own included. The alternate screen has no
+    /// scrollback
+    pub fn set_scrollback_lines(&mut self, lines: usize) {
+        self.primary_buffer
+            .terminal_buffer
+            .set_scrollback_lines(lines);
+    }
+
+    /// Most lines the alternate screen keeps, its own included, so what full screen applications
+    /// scroll off it can be scrolled back to. 0 keeps none, as in most terminals
+    pub fn set_alternate_scrollback_lines(&mut self, lines: usize) {
+        self.alternate_buffer
+            .terminal_buffer
+            .set_scrollback_lines(lines);
+    }
+
+    /// Longest OSC or DCS payload the parser keeps before dropping the rest
+    pub const fn set_max_sequence_length(&mut self, max_sequence_length: usize) {
+        self.parser.set_max_sequence_length(max_sequence_length);
+    }
+
+    /// Save every sequence the parser can't handle to `corpus`
+    pub fn set_fuzz_corpus(&mut self, corpus: FuzzCorpus) {
+        self.parser.capture_invalid_sequences();
+        self.fuzz_corpus = Some(corpus);
+    }
+
+    /// Run `processor` on everything parsed from the output from now on, after any processors
+    /// added before it
+    pub fn add_output_processor(&mut self, processor: Box<dyn OutputProcessor>) {
+        self.output_processors.push(processor);
+    }
+
+    /// Mask text `redactor` matches before it reaches the screen and the scrollback
+    ///
+    /// Each run of text is masked before it is written. A secret split between two reads from
+    /// the pty, or by an escape sequence such as a color change, is masked in the cells once the
+    /// rest of it has been written.
+    pub fn set_redactor(&mut self, redactor: Redactor) {
+        self.add_output_processor(Box::new(RedactSecrets::new(redactor.clone())));
+        self.redactor = Some(redactor);
+    }
+
+    fn save_invalid_sequences(&mut self) {
+        let Some(corpus) = &mut self.fuzz_corpus else {
+            return;
+        };
+
+        for invalid in self.parser.take_invalid_sequences() {
+            match corpus.save(&invalid) {
+                Ok(Some(path)) => info!("Saved invalid sequence to {}", path.display()),
+                Ok(None) => (),
+                Err(e) => error!("Failed to save invalid sequence: {e}"),
+            }
         }
     }
 
     #[must_use]
     pub fn show_cursor(&mut self) -> bool {
-        self.get_current_buffer().show_cursor()
+        !self.is_hiding_startup_banner() && self.get_current_buffer().show_cursor()
     }
 
     #[must_use]
@@ -167,9 +470,84 @@ impl TerminalState {
             return false;
         }
 
+        if self.is_hiding_startup_banner() {
+            return false;
+        }
+
         self.changed
     }
 
+    /// End a synchronized update that has gone on for longer than `SYNC_UPDATE_TIMEOUT`, as the
+    /// application that started it has probably died. Returns whether one was ended
+    pub fn release_stuck_sync_update(&mut self) -> bool {
+        let stuck = self.modes.synchronized_updates == SynchronizedUpdates::DontDraw
+            && self
+                .sync_update_started
+                .is_some_and(|started| started.elapsed() >= SYNC_UPDATE_TIMEOUT);
+        if !stuck {
+            return false;
+        }
+
+        warn!("Synchronized update never ended. Drawing anyway");
+        self.modes.synchronized_updates = SynchronizedUpdates::Draw;
+        self.sync_update_started = None;
+        self.changed = true;
+        true
+    }
+
+    /// Keep the shell's output hidden until its first prompt mark, for up to
+    /// `STARTUP_BANNER_TIMEOUT`
+    pub fn hide_startup_banner(&mut self) {
+        self.hide_startup_banner_until = Some(Instant::now() + STARTUP_BANNER_TIMEOUT);
+    }
+
+    /// How much longer startup output will stay hidden, if it is being hidden
+    #[must_use]
+    pub fn startup_banner_time_left(&self) -> Option<Duration> {
+        self.hide_startup_banner_until
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+            .filter(|left| !left.is_zero())
+    }
+
+    #[must_use]
+    pub fn is_hiding_startup_banner(&self) -> bool {
+        self.startup_banner_time_left().is_some()
+    }
+
+    /// Show the startup output if no prompt mark arrived in time. Returns whether it was shown
+    pub fn release_startup_banner(&mut self) -> bool {
+        if self.hide_startup_banner_until.is_none() || self.is_hiding_startup_banner() {
+            return false;
+        }
+
+        debug!("No prompt mark from the shell. Showing its startup output");
+        self.hide_startup_banner_until = None;
+        self.changed = true;
+        true
+    }
+
+    /// Put back the modes an application that died could have left the terminal in: a
+    /// synchronized update, a hidden cursor, no line wrapping, mouse reporting, application keys,
+    /// the keyboard protocols, the line drawing character set and the cursor style. What is on the
+    /// screen is left alone.
+    /// This is also what DECSTR does
+    pub fn reset_modes(&mut self) {
+        self.modes = TerminalModes::default();
+        self.requested_cursor_style = None;
+        self.sync_update_started = None;
+        self.character_replace = DecSpecialGraphics::DontReplace;
+
+        for buffer in [&mut self.primary_buffer, &mut self.alternate_buffer] {
+            buffer.show_cursor = Dectcem::default();
+            buffer.origin_mode = Decom::default();
+            buffer.cursor_state.line_wrap_mode = Decawm::default();
+            buffer.kitty_keyboard.clear();
+        }
+
+        self.changed = true;
+        self.request_redraw();
+    }
+
     pub fn set_state_changed(&mut self) {
         self.changed = true;
     }
@@ -178,22 +556,37 @@ impl TerminalState {
         self.changed = false;
     }
 
-    pub fn set_ctx(&mut self, ctx: Context) {
-        if self.ctx.is_some() {
+    pub fn set_redraw_notifier(&mut self, redraw_notifier: SharedRedrawNotifier) {
+        if self.redraw_notifier.is_some() {
             return;
         }
 
-        self.ctx = Some(ctx);
+        self.redraw_notifier = Some(redraw_notifier);
     }
 
     fn request_redraw(&mut self) {
         self.changed = true;
-        if let Some(ctx) = &self.ctx {
-            debug!("Internal State: Requesting repaint");
-            ctx.request_repaint();
+        if !self.window_visible {
+            return;
+        }
+
+        if let Some(redraw_notifier) = &self.redraw_notifier {
+            let delay = self.repaint_limiter.delay_until_next_frame();
+
+            if delay.is_zero() {
+                debug!("Internal State: Requesting repaint");
+                redraw_notifier.request_redraw();
+            } else {
+                debug!("Internal State: Requesting repaint in {delay:?}");
+                redraw_notifier.request_redraw_after(delay);
+            }
         }
     }
 
+    pub fn set_max_fps(&mut self, max_fps: u32) {
+        self.repaint_limiter = RepaintLimiter::new(max_fps);
+    }
+
     pub fn get_current_buffer(&mut self) -> &mut Buffer {
         match self.current_buffer {
             BufferType::Primary => &mut self.primary_buffer,
@@ -212,10 +605,9 @@ impl TerminalState {
             .data(include_scrollback)
     }
 
-    pub fn is_mouse_hovered_on_url(&mut self, pos: &CursorPos) -> Option<String> {
-        let current_buffer = self.get_current_buffer();
-        let buf_pos = current_buffer.terminal_buffer.cursor_pos_to_buf_pos(pos)?;
-
+    /// The link at `buf_pos`, either an OSC 8 hyperlink or a URL in the text. Hit-testing goes
+    /// through `RenderSlice`, which turns the cell under the mouse in to a buffer offset
+    pub fn is_mouse_hovered_on_url(&mut self, buf_pos: usize) -> Option<String> {
         let tags = self.get_current_buffer().format_tracker.tags();
 
         for tag in tags {
@@ -231,37 +623,508 @@ impl TerminalState {
             }
         }
 
-        None
+        // No explicit hyperlink, so look for a URL in the text itself
+        let (line_start, line) = self
+            .get_current_buffer()
+            .terminal_buffer
+            .viewport_line_chars_at(buf_pos)?;
+
+        self.url_schemes.url_at(&line, buf_pos - line_start)
     }
 
-    pub(crate) fn data_and_format_data_for_gui(
-        &mut self,
-    ) -> (
-        TerminalSections<Vec<TChar>>,
-        TerminalSections<Vec<FormatTag>>,
-    ) {
-        let (data, offset, end) = self.get_current_buffer().terminal_buffer.data_for_gui();
-
-        let format_data = split_format_data_for_scrollback(
-            self.get_current_buffer().format_tracker.tags(),
-            offset,
-            end,
-            false,
-        );
+    /// The OSC 8 hyperlink at `buf_pos`, and all of the buffer it covers. A link is often split
+    /// over several format tags, when part of it is bold for example, so neighbouring tags with
+    /// the same link are joined up
+    fn hyperlink_run(&mut self, buf_pos: usize) -> Option<(Url, Range<usize>)> {
+        let current_buffer = self.get_current_buffer();
+        let buf_len = current_buffer.terminal_buffer.buf.len();
+        let tags = current_buffer.format_tracker.tags();
+
+        let index = tags
+            .iter()
+            .position(|tag| tag.start <= buf_pos && buf_pos < tag.end)?;
+        let url = tags[index].url.clone()?;
+        let joins = |before: &FormatTag, after: &FormatTag| {
+            before.end == after.start
+                && before.url.as_ref() == Some(&url)
+                && after.url.as_ref() == Some(&url)
+        };
+
+        let mut first = index;
+        while first > 0 && joins(&tags[first - 1], &tags[first]) {
+            first -= 1;
+        }
+
+        let mut last = index;
+        while last + 1 < tags.len() && joins(&tags[last], &tags[last + 1]) {
+            last += 1;
+        }
+
+        Some((url, tags[first].start..tags[last].end.min(buf_len)))
+    }
+
+    /// Underline the OSC 8 hyperlink at `buf_pos`, or nothing if it is `None`. Returns true if
+    /// that changed which link is underlined
+    pub fn set_hovered_link(&mut self, buf_pos: Option<usize>) -> bool {
+        let hovered = buf_pos.and_then(|buf_pos| self.hyperlink_run(buf_pos));
+
+        if hovered == self.hovered_link {
+            return false;
+        }
+
+        self.hovered_link = hovered;
+        self.request_redraw();
+        true
+    }
+
+    /// Remember that `url` has been opened, so links to it are drawn as visited
+    pub fn mark_link_visited(&mut self, url: &str) {
+        self.visited_links.retain(|visited| visited != url);
+        if self.visited_links.len() == VISITED_LINKS_LIMIT {
+            self.visited_links.pop_front();
+        }
+        self.visited_links.push_back(url.to_string());
+        self.request_redraw();
+    }
+
+    /// Change how hyperlinks in `tags` are drawn: the one under the mouse is underlined, and ones
+    /// that have been opened are drawn in `VISITED_LINK_COLOR`. Only what is drawn changes, the
+    /// buffer's own tags are left alone
+    ///
+    /// Links with an id are the same link wherever they appear, so every part of one is
+    /// underlined. Links without one are only joined up with the tags right next to them.
+    fn style_links(&self, tags: &mut [FormatTag]) {
+        for tag in tags {
+            let Some(url) = &tag.url else {
+                continue;
+            };
+
+            let hovered = self.hovered_link.as_ref().is_some_and(|(hovered, range)| {
+                url == hovered
+                    && (hovered.id.is_some() || (range.start <= tag.start && tag.end <= range.end))
+            });
+            if hovered && !tag.font_decorations.contains(&FontDecorations::Underline) {
+                tag.font_decorations.push(FontDecorations::Underline);
+            }
+
+            if self.style_visited_links
+                && tag.colors.color == TerminalColor::Default
+                && self.visited_links.contains(&url.url)
+            {
+                tag.colors.color = VISITED_LINK_COLOR;
+            }
+        }
+    }
+
+    /// The OSC 8 hyperlink at `buf_pos`, and the text it is shown as
+    pub fn hyperlink_at(&mut self, buf_pos: usize) -> Option<Hyperlink> {
+        let (url, range) = self.hyperlink_run(buf_pos)?;
+        let text = self
+            .get_current_buffer()
+            .terminal_buffer
+            .text_for_buf_range(range, false);
+
+        Some(Hyperlink { url: url.url, text })
+    }
+
+    /// The OSC 8 hyperlink the selection is entirely inside, if there is one
+    pub fn selection_hyperlink(&mut self) -> Option<Hyperlink> {
+        let selected = self.get_current_buffer().selection?.range();
+        let (url, range) = self.hyperlink_run(selected.start)?;
+        if range.end < selected.end {
+            return None;
+        }
+
+        let text = self
+            .get_current_buffer()
+            .terminal_buffer
+            .text_for_buf_range(range, false);
+
+        Some(Hyperlink { url: url.url, text })
+    }
+
+    /// The text between two positions on screen, including the character at `end`
+    pub fn selection_text(&mut self, start: &CursorPos, end: &CursorPos) -> String {
+        let (start, end) = if (start.y, start.x) <= (end.y, end.x) {
+            (start, end)
+        } else {
+            (end, start)
+        };
+
+        let terminal_buffer = &self.get_current_buffer().terminal_buffer;
+        let start = terminal_buffer.visible_pos_to_buf_pos_clamped(start);
+        let end = terminal_buffer.visible_pos_to_buf_pos_clamped(&CursorPos {
+            x: end.x + 1,
+            y: end.y,
+        });
+
+        self.text_to_copy(start..end)
+    }
+
+    /// The text in `buf_range`, as it should be copied
+    fn text_to_copy(&mut self, buf_range: Range<usize>) -> String {
+        let trim_trailing_spaces = self.trim_trailing_spaces_on_copy;
+        let current_buffer = self.get_current_buffer();
+        let terminal_buffer = &current_buffer.terminal_buffer;
+        let Range { start, end } = buf_range;
+
+        // Continuation prompts are left out so a command typed over several lines can be pasted
+        // back in to the shell and run again
+        let mut text = String::new();
+        let mut copied_to = start;
+        for prompt in current_buffer.command_history.continuation_prompts() {
+            if prompt.end <= copied_to || prompt.start >= end {
+                continue;
+            }
+
+            text.push_str(
+                &terminal_buffer.text_for_buf_range(copied_to..prompt.start, trim_trailing_spaces),
+            );
+            copied_to = prompt.end;
+        }
+
+        text.push_str(&terminal_buffer.text_for_buf_range(copied_to..end, trim_trailing_spaces));
+        text
+    }
+
+    /// Start selecting text at `buf_pos`, dropping any earlier selection
+    pub fn start_selection(&mut self, buf_pos: usize) {
+        self.get_current_buffer().selection = Some(Selection::new(buf_pos));
+        self.request_redraw();
+    }
+
+    /// Move the end of the selection being dragged out to `buf_pos`
+    pub fn extend_selection(&mut self, buf_pos: usize) {
+        let Some(selection) = &mut self.get_current_buffer().selection else {
+            return;
+        };
+        if !selection.dragging || selection.extent == buf_pos {
+            return;
+        }
+
+        selection.extent = buf_pos;
+        self.request_redraw();
+    }
+
+    /// The mouse button was let go, so the selection stays as it is. A click that was never
+    /// dragged selects nothing
+    pub fn finish_selection(&mut self) {
+        let current_buffer = self.get_current_buffer();
+        match &mut current_buffer.selection {
+            Some(selection) if selection.is_click() => current_buffer.selection = None,
+            Some(selection) => selection.dragging = false,
+            None => return,
+        }
+
+        self.request_redraw();
+    }
+
+    pub fn clear_selection(&mut self) {
+        if self.get_current_buffer().selection.take().is_some() {
+            self.request_redraw();
+        }
+    }
 
-        (data, format_data)
+    /// Whether a selection is being dragged out with the mouse
+    pub fn is_selecting(&mut self) -> bool {
+        self.get_current_buffer()
+            .selection
+            .is_some_and(|selection| selection.dragging)
+    }
+
+    /// The selected text, as it should be copied
+    pub fn selected_text(&mut self) -> Option<String> {
+        let selection = self.get_current_buffer().selection?;
+        Some(self.text_to_copy(selection.range()))
+    }
+
+    /// The part of the buffer that is selected. `RenderSlice::cells` says where it is on screen
+    pub fn selection_range(&mut self) -> Option<Range<usize>> {
+        self.get_current_buffer()
+            .selection
+            .map(|selection| selection.range())
+    }
+
+    /// All of the text on screen
+    pub fn visible_text(&mut self) -> String {
+        let (width, height) = self.get_win_size();
+
+        self.selection_text(
+            &CursorPos::default(),
+            &CursorPos {
+                x: width.saturating_sub(1),
+                y: height.saturating_sub(1),
+            },
+        )
+    }
+
+    /// The terminal's text with no formatting, as it would be copied. Spaces at the end of lines
+    /// are dropped, and the text always ends with a newline unless it is empty
+    pub fn to_plain_text(&mut self, include_scrollback: bool) -> String {
+        let terminal_buffer = &self.get_current_buffer().terminal_buffer;
+        let start = if include_scrollback {
+            0
+        } else {
+            terminal_buffer
+                .get_visible_line_ranges()
+                .first()
+                .map_or(0, |line| line.start)
+        };
+
+        let mut text = terminal_buffer.text_for_buf_range(start..terminal_buffer.buf.len(), true);
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+
+        text
     }
 
+    /// The range of the buffer covered by the word at `buf_pos`
+    pub fn word_buf_range_at(&mut self, buf_pos: usize) -> Option<std::ops::Range<usize>> {
+        let (line_start, line) = self
+            .get_current_buffer()
+            .terminal_buffer
+            .viewport_line_chars_at(buf_pos)?;
+        let word = self
+            .word_characters
+            .word_range(&line, buf_pos - line_start)?;
+
+        Some(line_start + word.start..line_start + word.end)
+    }
+
+    /// Select the word at `buf_pos`, as a double click does. Which characters make up a word is
+    /// set by `word_characters`. Anything else at `buf_pos` selects nothing
+    pub fn select_word(&mut self, buf_pos: usize) {
+        let selection = self.word_buf_range_at(buf_pos).map(|word| Selection {
+            anchor: word.start,
+            extent: word.end - 1,
+            dragging: false,
+        });
+
+        self.get_current_buffer().selection = selection;
+        self.request_redraw();
+    }
+
+    /// What the GUI draws for the viewport. See `RenderSlice` for how it lines up with the buffer
+    pub fn render_slice(&mut self) -> RenderSlice {
+        if self.is_hiding_startup_banner() {
+            return RenderSlice::default();
+        }
+
+        if self.get_current_buffer().terminal_buffer.no_wrap_view() {
+            return self.unwrapped_render_slice();
+        }
+
+        let terminal_buffer = &self.get_current_buffer().terminal_buffer;
+        let (data, offset, end) = terminal_buffer.data_for_gui();
+        let mut text = data.visible;
+        let rows: Vec<_> = terminal_buffer.get_line_ranges()[terminal_buffer.viewport_line_range()]
+            .iter()
+            .map(|line| RenderRow {
+                buffer: line.clone(),
+                text: line.start.saturating_sub(offset)..line.end.saturating_sub(offset),
+                ends_line: terminal_buffer.get_raw_buffer().get(line.end) == Some(&TChar::NewLine),
+            })
+            .collect();
+
+        let shown = offset..end;
+        let tags = self.styled_format_data(&shown);
+        let mut format_data = format_data_for_rows(&tags, &[shown]);
+
+        if self.bidi_enabled && self.modes.bidi == Bdsm::Implicit {
+            let lines: Vec<_> = rows.iter().map(|row| row.text.clone()).collect();
+            reorder_lines(&mut text, &mut format_data, &lines);
+        }
+
+        RenderSlice::new(text, format_data, rows)
+    }
+
+    /// The render slice for a view that doesn't wrap, where each row of the viewport is a line cut
+    /// off at the edges of the screen. The rows are laid out one after another with a newline
+    /// between each
+    fn unwrapped_render_slice(&mut self) -> RenderSlice {
+        let terminal_buffer = &self.get_current_buffer().terminal_buffer;
+        let buffer_rows = terminal_buffer.viewport_rows();
+        let mut text = terminal_buffer.rows_data(&buffer_rows);
+        let shown = buffer_rows.first().map_or(0, |row| row.start)
+            ..buffer_rows.last().map_or(0, |row| row.end);
+
+        let mut start = 0;
+        let rows: Vec<_> = buffer_rows
+            .iter()
+            .map(|row| {
+                let line = start..start + row.len();
+                start = line.end + 1;
+                RenderRow {
+                    buffer: row.clone(),
+                    text: line,
+                    ends_line: terminal_buffer.get_raw_buffer().get(row.end)
+                        == Some(&TChar::NewLine),
+                }
+            })
+            .collect();
+
+        let tags = self.styled_format_data(&shown);
+        let mut format_data = format_data_for_rows(&tags, &buffer_rows);
+
+        if self.bidi_enabled && self.modes.bidi == Bdsm::Implicit {
+            let lines: Vec<_> = rows.iter().map(|row| row.text.clone()).collect();
+            reorder_lines(&mut text, &mut format_data, &lines);
+        }
+
+        RenderSlice::new(text, format_data, rows)
+    }
+
+    /// The buffer's format tags as they are drawn, with hyperlinks styled and the search matches
+    /// in `shown` highlighted
+    fn styled_format_data(&mut self, shown: &Range<usize>) -> Vec<FormatTag> {
+        let mut tags = self.get_current_buffer().format_tracker.tags();
+        self.style_links(&mut tags);
+        if self.search.is_stale() {
+            let query = self.search.query().to_string();
+            let matches = self.get_current_buffer().terminal_buffer.search(&query);
+            self.search.refresh(matches);
+        }
+
+        self.search.highlight(tags, shown)
+    }
+
+    /// When each line of the buffer, including the scrollback, was last written to
+    pub fn line_timestamps(&mut self) -> Vec<Option<SystemTime>> {
+        let current_buffer = self.get_current_buffer();
+
+        current_buffer
+            .line_timestamps
+            .for_lines(current_buffer.terminal_buffer.get_line_ranges())
+    }
+
+    /// When each row on screen was last written to, top to bottom
+    pub fn visible_line_timestamps(&mut self) -> Vec<Option<SystemTime>> {
+        let current_buffer = self.get_current_buffer();
+        let terminal_buffer = &current_buffer.terminal_buffer;
+
+        current_buffer
+            .line_timestamps
+            .for_lines(&terminal_buffer.viewport_lines())
+    }
+
+    /// The cell the cursor is on in the live screen, counting from 0 at the top left. The
+    /// scroll region and origin mode don't change it, and neither does scrolling the viewport
     #[must_use]
     pub fn cursor_pos(&mut self) -> CursorPos {
         self.get_current_buffer().cursor_state.pos.clone()
     }
 
+    /// The cursor position as applications see it in a cursor position report: 1-based
+    /// `(row, column)`, with the row counted from the top margin of the scroll region in origin
+    /// mode
+    #[must_use]
+    pub fn reported_cursor_pos(&mut self) -> (usize, usize) {
+        let current_buffer = self.get_current_buffer();
+        let pos = &current_buffer.cursor_state.pos;
+        let mut row = pos.y + 1;
+        if current_buffer.origin_mode == Decom::Origin {
+            let (top, _) = current_buffer.terminal_buffer.get_scrolling_region();
+            row = row.saturating_sub(top);
+        }
+
+        (row, pos.x + 1)
+    }
+
+    /// The viewport cell the cursor is drawn in, counting from 0 at the top left of what is
+    /// shown. This is what the renderer and the input method window are placed by. `None` if the
+    /// viewport is scrolled back far enough that the cursor's row is below the bottom of it, or
+    /// the view doesn't wrap and the cursor is off the edge of the screen
+    #[must_use]
+    pub fn viewport_cursor_pos(&mut self) -> Option<CursorPos> {
+        if !self.no_wrap_view() {
+            let pos = self.cursor_pos();
+            let y = self.screen_row_to_viewport_row(pos.y)?;
+            return Some(CursorPos { x: pos.x, y });
+        }
+
+        let current_buffer = self.get_current_buffer();
+        current_buffer
+            .terminal_buffer
+            .viewport_cursor_pos(&current_buffer.cursor_state.pos)
+    }
+
+    /// Whether long lines run off the edge of the screen rather than wrapping. Full screen
+    /// applications on the alternate screen lay out their own lines, so it always wraps
+    #[must_use]
+    pub const fn no_wrap_view(&self) -> bool {
+        matches!(self.current_buffer, BufferType::Primary)
+            && self.primary_buffer.terminal_buffer.no_wrap_view()
+    }
+
+    /// Stop wrapping long lines in the view, so they can be scrolled sideways instead, or start
+    /// wrapping them again. Only what is drawn changes, not what applications see
+    pub fn set_no_wrap_view(&mut self, no_wrap_view: bool) {
+        self.primary_buffer
+            .terminal_buffer
+            .set_no_wrap_view(no_wrap_view);
+        self.column_scroll_accumulator.reset();
+        self.set_state_changed();
+        self.request_redraw();
+    }
+
+    /// How many columns the view has been scrolled sideways, and how far it can go
+    #[must_use]
+    pub fn column_scroll(&self) -> (usize, usize) {
+        if !self.no_wrap_view() {
+            return (0, 0);
+        }
+
+        let terminal_buffer = &self.primary_buffer.terminal_buffer;
+        (
+            terminal_buffer.column_offset(),
+            terminal_buffer.max_column_offset(),
+        )
+    }
+
+    /// Scroll the view sideways to start `column_offset` columns in, when it doesn't wrap
+    pub fn set_column_offset(&mut self, column_offset: usize) {
+        if !self.no_wrap_view() {
+            return;
+        }
+
+        self.primary_buffer
+            .terminal_buffer
+            .set_column_offset(column_offset);
+        self.set_state_changed();
+        self.request_redraw();
+    }
+
+    /// Scroll the view sideways by `columns`, when it doesn't wrap. Positive values scroll to
+    /// the right. Fractions of a column are kept until enough have built up to scroll a whole one
+    pub fn scroll_columns(&mut self, columns: f32) {
+        if !self.no_wrap_view() {
+            return;
+        }
+
+        let columns = self.column_scroll_accumulator.accumulate(columns);
+        let columns = match columns.approx_as::<isize>() {
+            Ok(columns) => columns,
+            Err(e) => {
+                error!("Failed to convert column scroll to isize: {e}");
+                return;
+            }
+        };
+
+        if columns != 0 {
+            self.primary_buffer.terminal_buffer.scroll_columns(columns);
+            self.set_state_changed();
+            self.request_redraw();
+        }
+    }
+
+    /// Resize the terminal to `width` x `height` cells, or as near as `size_limits` allows
     pub fn set_win_size(
         &mut self,
         width: usize,
         height: usize,
     ) -> TerminalBufferSetWinSizeResponse {
+        let (width, height) = self.size_limits.clamp(width, height);
         let current_buffer = self.get_current_buffer();
         let response = current_buffer.terminal_buffer.set_win_size(
             width,
@@ -278,9 +1141,24 @@ impl TerminalState {
         self.modes.cursor_key.clone()
     }
 
+    /// The window was minimized or brought back. Anything that changed while it was hidden is
+    /// drawn as soon as it is visible again
+    pub fn set_window_visible(&mut self, visible: bool) {
+        let became_visible = visible && !self.window_visible;
+        self.window_visible = visible;
+
+        if became_visible && self.changed {
+            self.request_redraw();
+        }
+    }
+
     pub fn set_window_focused(&mut self, focused: bool) {
         self.window_focused = focused;
 
+        if focused {
+            self.bell.clear();
+        }
+
         if self.modes.focus_reporting == XtMseWin::Disabled {
             return;
         }
@@ -382,11 +1260,18 @@ impl TerminalState {
         };
 
         let current_buffer = self.get_current_buffer();
+        let cursor_pos = &current_buffer.cursor_state.pos;
+
+        let response = match current_buffer.cursor_state.line_wrap_mode {
+            Decawm::NoAutoWrap => current_buffer
+                .terminal_buffer
+                .insert_data_without_wrap(cursor_pos, &data),
+            _ => current_buffer
+                .terminal_buffer
+                .insert_data(cursor_pos, &data),
+        };
 
-        let response = match current_buffer
-            .terminal_buffer
-            .insert_data(&current_buffer.cursor_state.pos, &data)
-        {
+        let response = match response {
             Ok(response) => response,
             Err(e) => {
                 error!("Failed to insert data: {e}");
@@ -394,13 +1279,34 @@ impl TerminalState {
             }
         };
 
+        // Only zero width characters, which were kept with a character already on screen
+        if response.written_range.is_empty() {
+            return;
+        }
+
+        current_buffer
+            .line_timestamps
+            .insert_range(&response.insertion_range);
+        current_buffer
+            .line_timestamps
+            .written(response.written_range.clone(), SystemTime::now());
         current_buffer
             .format_tracker
             .push_range_adjustment(response.insertion_range);
         current_buffer
             .format_tracker
-            .push_range(&current_buffer.cursor_state, response.written_range);
+            .push_range(&current_buffer.cursor_state, response.written_range.clone());
         current_buffer.cursor_state.pos = response.new_cursor_pos;
+
+        if let Some(redactor) = &self.redactor {
+            let current_buffer = match self.current_buffer {
+                BufferType::Primary => &mut self.primary_buffer,
+                BufferType::Alternate => &mut self.alternate_buffer,
+            };
+            current_buffer
+                .terminal_buffer
+                .redact(redactor, &response.written_range);
+        }
     }
 
     pub fn set_cursor_pos(&mut self, x: Option<usize>, y: Option<usize>) {
@@ -409,7 +1315,12 @@ impl TerminalState {
             current_buffer.cursor_state.pos.x = x - 1;
         }
         if let Some(y) = y {
-            current_buffer.cursor_state.pos.y = y - 1;
+            current_buffer.cursor_state.pos.y = if current_buffer.origin_mode == Decom::Origin {
+                let (top, bottom) = current_buffer.terminal_buffer.get_scrolling_region();
+                (top + y - 1).min(bottom)
+            } else {
+                y - 1
+            };
         }
     }
 
@@ -486,12 +1397,22 @@ impl TerminalState {
             .format_tracker
             .push_range(&current_buffer.cursor_state, 0..usize::MAX);
         current_buffer.terminal_buffer.clear_all();
+        current_buffer.command_history.clear();
+        current_buffer.line_timestamps.clear();
     }
 
     pub(crate) fn clear_visible(&mut self) {
+        let scrollback_on_clear = self.scrollback_on_clear;
         let current_buffer = self.get_current_buffer();
 
-        let Some(range) = current_buffer.terminal_buffer.clear_visible() else {
+        let cleared = if scrollback_on_clear {
+            current_buffer
+                .terminal_buffer
+                .scroll_visible_into_scrollback()
+        } else {
+            current_buffer.terminal_buffer.clear_visible()
+        };
+        let Some(range) = cleared else {
             return;
         };
 
@@ -560,8 +1481,40 @@ impl TerminalState {
         self.get_current_buffer().cursor_state.pos.x = 0;
     }
 
+    /// Move down a line. At the bottom of a scrolling region the region scrolls up instead, and
+    /// below it the cursor stops at the bottom of the screen
     pub(crate) fn new_line(&mut self) {
-        self.get_current_buffer().cursor_state.pos.y += 1;
+        let current_buffer = self.get_current_buffer();
+        let terminal_buffer = &current_buffer.terminal_buffer;
+        if !terminal_buffer.has_scrolling_region() {
+            current_buffer.cursor_state.pos.y += 1;
+            return;
+        }
+
+        let (top, bottom) = terminal_buffer.get_scrolling_region();
+        let last_line = terminal_buffer.get_win_size().1.saturating_sub(1);
+        let y = current_buffer.cursor_state.pos.y;
+        if y == bottom {
+            self.scroll_region_up(top, 1);
+        } else if y < last_line {
+            current_buffer.cursor_state.pos.y += 1;
+        }
+    }
+
+    /// Move up a line. At the top of the scrolling region the region scrolls down instead,
+    /// losing its bottom line
+    pub(crate) fn reverse_index(&mut self) {
+        let current_buffer = self.get_current_buffer();
+        let (top, _) = current_buffer.terminal_buffer.get_scrolling_region();
+        let y = current_buffer.cursor_state.pos.y;
+        if y == top {
+            let response = current_buffer
+                .terminal_buffer
+                .insert_lines(&CursorPos { x: 0, y: top }, 1);
+            current_buffer.apply_line_changes(response);
+        } else if y > 0 {
+            current_buffer.cursor_state.pos.y -= 1;
+        }
     }
 
     pub(crate) fn backspace(&mut self) {
@@ -581,19 +1534,18 @@ impl TerminalState {
         let response = current_buffer
             .terminal_buffer
             .insert_lines(&current_buffer.cursor_state.pos, num_lines);
-        match current_buffer
-            .format_tracker
-            .delete_range(response.deleted_range)
-        {
-            Ok(()) => (),
-            Err(e) => {
-                error!("Failed to delete range: {e}");
-                return;
-            }
-        };
-        current_buffer
-            .format_tracker
-            .push_range_adjustment(response.inserted_range);
+        current_buffer.apply_line_changes(response);
+    }
+
+    /// Scroll the scrolling region up `num_lines` from line `from`, dropping the lines that go
+    /// off its top
+    fn scroll_region_up(&mut self, from: usize, num_lines: usize) {
+        let current_buffer = self.get_current_buffer();
+
+        let response = current_buffer
+            .terminal_buffer
+            .delete_lines(&CursorPos { x: 0, y: from }, num_lines);
+        current_buffer.apply_line_changes(response);
     }
 
     pub(crate) fn delete(&mut self, num_chars: usize) {
@@ -603,6 +1555,7 @@ impl TerminalState {
             .terminal_buffer
             .delete_forwards(&current_buffer.cursor_state.pos, num_chars);
         if let Some(range) = deleted_buf_range {
+            current_buffer.line_timestamps.delete_range(&range);
             match current_buffer.format_tracker.delete_range(range) {
                 Ok(()) => (),
                 Err(e) => {
@@ -612,21 +1565,22 @@ impl TerminalState {
         }
     }
 
+    /// ECH. Blanks cells in place from the cursor, without moving anything after them
     pub(crate) fn erase_forwards(&mut self, num_chars: usize) {
         let current_buffer = self.get_current_buffer();
 
-        let deleted_buf_range = current_buffer
+        let erased_buf_range = current_buffer
             .terminal_buffer
             .erase_forwards(&current_buffer.cursor_state.pos, num_chars);
-        if let Some(range) = deleted_buf_range {
-            match current_buffer.format_tracker.delete_range(range.clone()) {
-                Ok(()) => (),
-                Err(e) => {
-                    error!("Failed to delete range: {e}");
-                }
+        if let Some(range) = erased_buf_range {
+            if range.is_empty() {
+                return;
             }
 
-            current_buffer.format_tracker.push_range_adjustment(range);
+            let erased_state = current_buffer.cursor_state.erased_cell_state();
+            current_buffer
+                .format_tracker
+                .push_range(&erased_state, range);
         }
     }
 
@@ -762,11 +1716,9 @@ impl TerminalState {
             | SelectGraphicRendition::Superscript
             | SelectGraphicRendition::Subscript
             | SelectGraphicRendition::NeitherSuperscriptNorSubscript
-            | SelectGraphicRendition::Revealed => {
-                warn!("Unhandled sgr: {:?}", sgr);
-            }
-            SelectGraphicRendition::Unknown(_) => {
-                warn!("Unknown sgr: {:?}", sgr);
+            | SelectGraphicRendition::Revealed
+            | SelectGraphicRendition::Unknown(_) => {
+                self.unsupported.record(&format!("SGR {sgr:?}"));
             }
         }
     }
@@ -777,6 +1729,9 @@ impl TerminalState {
         let response = current_buffer
             .terminal_buffer
             .insert_spaces(&current_buffer.cursor_state.pos, num_spaces);
+        current_buffer
+            .line_timestamps
+            .insert_range(&response.insertion_range);
         current_buffer
             .format_tracker
             .push_range_adjustment(response.insertion_range);
@@ -810,6 +1765,33 @@ impl TerminalState {
             Mode::Dectem(dectem) => {
                 self.get_current_buffer().show_cursor = dectem.clone();
             }
+            Mode::Decom(Decom::Query) => {
+                let to_write = self.get_current_buffer().origin_mode.report(None);
+                self.report_mode(&to_write);
+            }
+            Mode::Decom(decom) => {
+                self.get_current_buffer().origin_mode = decom.clone();
+                // Changing origin mode sends the cursor to the new home position
+                self.set_cursor_pos(Some(1), Some(1));
+            }
+            Mode::Bdsm(Bdsm::Query) => {
+                self.report_mode(&self.modes.bidi.report(None));
+            }
+            Mode::Bdsm(bdsm) => {
+                self.modes.bidi = bdsm.clone();
+            }
+            Mode::Decnkm(Decnkm::Query) => {
+                self.report_mode(&self.modes.keypad.report(None));
+            }
+            Mode::Decnkm(decnkm) => {
+                self.modes.keypad = decnkm.clone();
+            }
+            Mode::Decbkm(Decbkm::Query) => {
+                self.report_mode(&self.modes.backarrow_key.report(None));
+            }
+            Mode::Decbkm(decbkm) => {
+                self.modes.backarrow_key = decbkm.clone();
+            }
             Mode::BracketedPaste(RlBracket::Query) => {
                 self.report_mode(&self.modes.bracketed_paste.report(None));
             }
@@ -822,6 +1804,18 @@ impl TerminalState {
             Mode::XtCBlink(xtcblink) => {
                 self.modes.cursor_blinking = xtcblink.clone();
             }
+            Mode::SaveCursor(SaveCursor::Query) => {
+                let to_write = if self.get_current_buffer().saved_cursor_position.is_some() {
+                    SaveCursor::Save
+                } else {
+                    SaveCursor::Restore
+                }
+                .report(None);
+
+                self.report_mode(&to_write);
+            }
+            Mode::SaveCursor(SaveCursor::Save) => self.get_current_buffer().save_cursor(),
+            Mode::SaveCursor(SaveCursor::Restore) => self.get_current_buffer().restore_cursor(),
             Mode::XtExtscrn(XtExtscrn::Query) => {
                 let to_write = match self.current_buffer {
                     BufferType::Primary => XtExtscrn::Primary,
@@ -837,14 +1831,26 @@ impl TerminalState {
                 // 2. Switch to the alternate screen buffer
                 // 3. Clear the screen
 
-                // TODO: We're supposed to save the cursor POS here. Do we assign the current cursor pos to the saved cursor pos?
-                // I don't see why we need to explicitly do that, as the cursor pos is already saved in the buffer
-                // Do we copy the cursor pos to the new buffer?
-                // Also, the "clear screen" bit implies to me that the buffer we switch to is *always* new, but is that correct?
-                // This is why we're making a "new" buffer here
+                // The buffer we switch to is always new, which takes care of clearing the screen
+                self.get_current_buffer().save_cursor();
+
+                let scrollback_lines = self.alternate_buffer.terminal_buffer.scrollback_lines();
+                let primary = self.get_current_buffer();
+                let (width, height) = primary.terminal_buffer.get_win_size();
+                let mut alternate = Buffer::new(width, height, BufferType::Alternate);
+                // The alternate screen starts out with the primary's modes, but changes made
+                // to them while it is showing are dropped when we switch back
+                alternate.show_cursor = primary.show_cursor.clone();
+                alternate.cursor_state.line_wrap_mode = primary.cursor_state.line_wrap_mode.clone();
+                alternate.origin_mode = primary.origin_mode.clone();
+                alternate
+                    .terminal_buffer
+                    .set_width_provider(primary.terminal_buffer.width_provider());
+                alternate
+                    .terminal_buffer
+                    .set_scrollback_lines(scrollback_lines);
 
-                let (width, height) = self.get_current_buffer().terminal_buffer.get_win_size();
-                self.alternate_buffer = Buffer::new(width, height, BufferType::Alternate);
+                self.alternate_buffer = alternate;
                 self.current_buffer = BufferType::Alternate;
             }
             Mode::XtExtscrn(XtExtscrn::Primary) => {
@@ -853,11 +1859,15 @@ impl TerminalState {
                 // 1. Restore the cursor position
                 // 2. Switch to the primary screen buffer
                 // 3. Clear the screen
-                // See set mode for notes on the cursor pos
 
                 self.current_buffer = BufferType::Primary;
+                self.get_current_buffer().restore_cursor();
                 let (width, height) = self.get_current_buffer().terminal_buffer.get_win_size();
+                let scrollback_lines = self.alternate_buffer.terminal_buffer.scrollback_lines();
                 self.alternate_buffer = Buffer::new(width, height, BufferType::Alternate);
+                self.alternate_buffer
+                    .terminal_buffer
+                    .set_scrollback_lines(scrollback_lines);
             }
             Mode::XtMseWin(XtMseWin::Query) => {
                 self.report_mode(&self.modes.focus_reporting.report(None));
@@ -881,43 +1891,51 @@ impl TerminalState {
             Mode::XtMseWin(XtMseWin::Disabled) => {
                 self.modes.focus_reporting = XtMseWin::Disabled;
             }
+            Mode::AltScroll(AltScroll::Query) => {
+                self.report_mode(&self.modes.alternate_scroll.report(None));
+            }
+            Mode::AltScroll(alternate_scroll) => {
+                self.modes.alternate_scroll = alternate_scroll.clone();
+            }
             Mode::MouseMode(MouseTrack::Query(v)) => {
-                let is_set = if self.modes.mouse_tracking.mouse_mode_number() == *v {
-                    SetMode::DecSet
-                } else {
-                    SetMode::DecRst
-                };
-
-                self.report_mode(&self.modes.mouse_tracking.report(Some(is_set)));
+                let is_set = self.modes.mouse_tracking.mouse_mode_number() == *v
+                    || self.modes.mouse_encoding.mouse_mode_number() == *v;
+                self.report_mode(&format!("\x1b[?{v};{}$y", if is_set { 1 } else { 2 }));
+            }
+            Mode::MouseMode(MouseTrack::XtMseSgrPixels) => {
+                self.unsupported.record(&format!("Mouse mode {mode}"));
             }
             Mode::MouseMode(mode) => {
-                if let MouseTrack::XtMsex10
-                | MouseTrack::XtMseX11
-                | MouseTrack::XtMseBtn
-                | MouseTrack::NoTracking
-                | MouseTrack::XtMseAny
-                | MouseTrack::XtMseSgr = mode
-                {
-                    debug!("Setting mode to: {mode}");
-                    self.modes.mouse_tracking = mode.clone();
-                } else {
-                    warn!("Unhandled mouse mode: {mode}");
-                }
+                debug!("Setting mode to: {mode}");
+                self.modes.mouse_tracking = mode.clone();
+            }
+            Mode::MouseEncodingMode(encoding) => {
+                debug!("Setting mouse encoding to: {encoding}");
+                self.modes.mouse_encoding = *encoding;
             }
             Mode::SynchronizedUpdates(SynchronizedUpdates::Query) => {
                 self.report_mode(&self.modes.synchronized_updates.report(None));
             }
             Mode::SynchronizedUpdates(sync) => {
                 self.modes.synchronized_updates = sync.clone();
+                if *sync == SynchronizedUpdates::DontDraw {
+                    self.sync_update_started = Some(Instant::now());
+                    // Make sure the GUI gets a frame to notice if the update is never ended
+                    if let Some(redraw_notifier) = &self.redraw_notifier {
+                        redraw_notifier.request_redraw_after(SYNC_UPDATE_TIMEOUT);
+                    }
+                } else {
+                    self.sync_update_started = None;
+                }
             }
             Mode::UnknownQuery(m) => {
                 let query = String::from_utf8(m.clone())
                     .unwrap_or_else(|_| "Unable to convert to string".to_string());
-                warn!("Querying unknown mode: {query}");
+                self.unsupported.record(&format!("DECRQM for mode {query}"));
                 self.report_mode(&mode.report(None));
             }
             Mode::Unknown(_) => {
-                warn!("unhandled set mode: {mode}");
+                self.unsupported.record(&format!("Mode {mode}"));
             }
         }
     }
@@ -945,6 +1963,65 @@ impl TerminalState {
         }
     }
 
+    /// Answer an OSC color query with `rgb`
+    fn reply_with_color(&self, code: &str, rgb: (u8, u8, u8)) {
+        let output = collect_text(&format!("\x1b]{code};{}\x1b\\", format_x11_color(rgb)));
+
+        for byte in output.iter() {
+            if let Err(e) = self.write(byte) {
+                error!("Failed to write osc color response: {e}");
+            }
+        }
+    }
+
+    /// Set or ask for palette entry `index` (OSC 4). Entries past the 16 palette colors can be
+    /// asked for, but not changed, since their colors are worked out as they are parsed
+    fn palette_entry(&mut self, index: usize, color: AnsiOscInternalType) {
+        match color {
+            AnsiOscInternalType::Query => {
+                let rgb = self.palette.get(index).or_else(|| {
+                    let (r, g, b) = (index < 256).then(|| lookup_256_color_by_index(index))?;
+                    Some((
+                        u8::try_from(r).ok()?,
+                        u8::try_from(g).ok()?,
+                        u8::try_from(b).ok()?,
+                    ))
+                });
+
+                if let Some(rgb) = rgb {
+                    self.reply_with_color(&format!("4;{index}"), rgb);
+                } else {
+                    warn!("OSC 4: no palette entry {index}");
+                }
+            }
+            AnsiOscInternalType::String(spec) => match parse_x11_color(&spec) {
+                Some(rgb) if index < PALETTE_COLORS.len() => {
+                    self.palette.set(index, rgb);
+                    self.changed = true;
+                }
+                Some(_) => {
+                    self.unsupported
+                        .record("OSC 4 for palette entries past the first 16");
+                }
+                None => warn!("OSC 4: unsupported color {spec:?}"),
+            },
+            AnsiOscInternalType::Unknown(_) => {
+                self.unsupported.record("OSC 4 with an unknown value");
+            }
+        }
+    }
+
+    /// Put palette entry `index` back to the color the user picked
+    fn reset_palette_entry(&mut self, index: usize) {
+        self.palette.copy_entry(index, &self.user_palette);
+    }
+
+    /// Use `palette`, from the user's settings, throwing away any colors applications have set
+    pub const fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.user_palette = palette;
+    }
+
     pub(crate) fn osc_response(&mut self, osc: AnsiOscType) {
         match osc {
             AnsiOscType::Url(url) => match url {
@@ -956,72 +2033,81 @@ impl TerminalState {
                 }
             },
             AnsiOscType::RequestColorQueryBackground(color) => {
+                let background = self.palette.background();
                 match color {
-                    // OscInternalType::SetColor(_) => {
-                    //     warn!("RequestColorQueryBackground: Set is not supported");
-                    // }
                     AnsiOscInternalType::Query => {
-                        // lets get the color as a hex string
-
-                        let (r, g, b, a) = Color32::BLACK.to_tuple();
-                        let output = collect_text(&format!(
-                            "\x1b]11;rgb:{r:02x}/{g:02x}/{b:02x}{a:02x}\x1b\\"
-                        ));
-
-                        for byte in output.iter() {
-                            if let Err(e) = self.write(byte) {
-                                error!("Failed to write osc color response: {e}");
-                            }
+                        self.reply_with_color("11", background.unwrap_or(DEFAULT_BACKGROUND_REPLY));
+                    }
+                    AnsiOscInternalType::String(spec) => {
+                        if let Some(rgb) = parse_x11_color(&spec) {
+                            self.palette.set_background(Some(rgb));
+                            self.changed = true;
+                        } else {
+                            warn!("OSC 11: unsupported color {spec:?}");
                         }
                     }
                     AnsiOscInternalType::Unknown(_) => {
-                        warn!("OSC Unknown is not supported");
-                    }
-                    AnsiOscInternalType::String(_) => {
-                        warn!("OSC Type {color:?} Skipped");
+                        self.unsupported.record("OSC 11 with an unknown value");
                     }
                 }
             }
             AnsiOscType::RequestColorQueryForeground(color) => {
+                let foreground = self.palette.foreground();
                 match color {
-                    // OscInternalType::SetColor(_) => {
-                    //     warn!("RequestColorQueryForeground: Set is not supported");
-                    // }
                     AnsiOscInternalType::Query => {
-                        // lets get the color as a hex string
-                        let (r, g, b, a) = Color32::WHITE.to_tuple();
-
-                        let output = collect_text(&format!(
-                            "\x1b]10;rgb:{r:02x}/{g:02x}/{b:02x}{a:02x}\x1b\\"
-                        ));
-
-                        for byte in output.iter() {
-                            if let Err(e) = self.write(byte) {
-                                error!("Failed to write osc color response: {e}");
-                            }
+                        self.reply_with_color("10", foreground.unwrap_or(DEFAULT_FOREGROUND_REPLY));
+                    }
+                    AnsiOscInternalType::String(spec) => {
+                        if let Some(rgb) = parse_x11_color(&spec) {
+                            self.palette.set_foreground(Some(rgb));
+                            self.changed = true;
+                        } else {
+                            warn!("OSC 10: unsupported color {spec:?}");
                         }
                     }
                     AnsiOscInternalType::Unknown(_) => {
-                        warn!("OSC Unknown is not supported");
+                        self.unsupported.record("OSC 10 with an unknown value");
+                    }
+                }
+            }
+            AnsiOscType::Palette(entries) => {
+                for (index, color) in entries {
+                    self.palette_entry(index, color);
+                }
+            }
+            AnsiOscType::ResetPalette(indexes) => {
+                if indexes.is_empty() {
+                    for index in 0..PALETTE_COLORS.len() {
+                        self.reset_palette_entry(index);
                     }
-                    AnsiOscInternalType::String(_) => {
-                        warn!("OSC Type {color:?} Skipped");
+                } else {
+                    for index in indexes {
+                        self.reset_palette_entry(index);
                     }
                 }
+                self.changed = true;
+            }
+            AnsiOscType::ResetForeground => {
+                self.palette.set_foreground(self.user_palette.foreground());
+                self.changed = true;
+            }
+            AnsiOscType::ResetBackground => {
+                self.palette.set_background(self.user_palette.background());
+                self.changed = true;
             }
             AnsiOscType::SetTitleBar(title) => {
                 self.window_commands
                     .push(WindowManipulation::SetTitleBarText(title));
             }
-            AnsiOscType::Ftcs(value) => {
-                warn!("Ftcs is not supported: {value}");
-            }
-            // FIXME: I think once we get in to muxxing we'll need to handle this
-            // I think the idea here is that OSC 7 is emitted to inform the terminal of the current working directory
-            // So that if you open a new tab in the terminal, it will start in the same directory as the current tab
-            // https://github.com/jarun/nnn/issues/1147
+            AnsiOscType::Ftcs(value) => self.handle_ftcs(&FtcsMarker::from(value.as_str())),
+            // OSC 7 tells us the shell's working directory, so a new window opened from this one
+            // can start there. https://github.com/jarun/nnn/issues/1147
             AnsiOscType::RemoteHost(value) => {
-                debug!("Received for remote host: {value}");
+                if let Some(directory) = WorkingDirectory::from_url(&value) {
+                    self.working_directory = Some(directory);
+                } else {
+                    warn!("Invalid working directory URL: {value}");
+                }
             }
             AnsiOscType::ResetCursorColor => {
                 self.get_current_buffer().cursor_color = TerminalColor::DefaultCursorColor;
@@ -1029,11 +2115,170 @@ impl TerminalState {
         }
     }
 
-    pub(crate) fn report_cursor_position(&mut self) {
+    pub(crate) fn handle_ftcs(&mut self, marker: &FtcsMarker) {
+        if *marker == FtcsMarker::PromptStart && self.hide_startup_banner_until.take().is_some() {
+            debug!("First prompt mark. Dropping the shell's startup output");
+            self.clear_all();
+            self.set_cursor_pos(Some(1), Some(1));
+            self.changed = true;
+        }
+
+        let current_buffer = self.get_current_buffer();
+        let buf_pos = current_buffer
+            .terminal_buffer
+            .line_start_buf_pos(&current_buffer.cursor_state.pos);
+
+        match marker {
+            FtcsMarker::PromptStart => current_buffer.command_history.prompt_started(buf_pos),
+            FtcsMarker::CommandStart => {
+                let prompt_end = current_buffer
+                    .terminal_buffer
+                    .visible_pos_to_buf_pos_clamped(&current_buffer.cursor_state.pos);
+                current_buffer
+                    .command_history
+                    .command_started(buf_pos, prompt_end);
+            }
+            FtcsMarker::CommandExecuted => {
+                current_buffer.command_history.command_executed(buf_pos);
+            }
+            FtcsMarker::CommandFinished(exit_code) => {
+                current_buffer
+                    .command_history
+                    .command_finished(buf_pos, *exit_code);
+            }
+            FtcsMarker::Unknown(value) => {
+                self.unsupported.record(&format!("OSC 133 marker {value}"));
+            }
+        }
+    }
+
+    /// Scroll the viewport to the output of the most recent command that exited with a non-zero
+    /// status. Calling this again moves to the failed command before that one.
+    ///
+    /// Returns the buffer range of the command output so the caller can highlight it
+    pub fn scroll_to_previous_failed_command(&mut self) -> Option<std::ops::Range<usize>> {
+        let range = self
+            .get_current_buffer()
+            .command_history
+            .previous_failed()?
+            .output_range();
+
+        self.scroll_to_command(range.start);
+        Some(range)
+    }
+
+    /// Scroll the viewport to the output of the command before the one last jumped to, starting
+    /// from the most recent. Returns the buffer range of the command output
+    pub fn scroll_to_previous_command(&mut self) -> Option<std::ops::Range<usize>> {
+        let range = self
+            .get_current_buffer()
+            .command_history
+            .previous_command()?
+            .output_range();
+
+        self.scroll_to_command(range.start);
+        Some(range)
+    }
+
+    /// Scroll the viewport to the output of the command after the one last jumped to. Going past
+    /// the most recent command goes back to the bottom
+    pub fn scroll_to_next_command(&mut self) -> Option<std::ops::Range<usize>> {
+        let next = self
+            .get_current_buffer()
+            .command_history
+            .next_command()
+            .map(CommandMark::output_range);
+
+        let Some(range) = next else {
+            self.move_viewport(ViewportMovement::Bottom);
+            return None;
+        };
+
+        self.scroll_to_command(range.start);
+        Some(range)
+    }
+
+    fn scroll_to_command(&mut self, buf_pos: usize) {
         let current_buffer = self.get_current_buffer();
+        let top_before = current_buffer.terminal_buffer.viewport_line_range().start;
+        current_buffer.terminal_buffer.scroll_to_buf_pos(buf_pos);
+        self.record_viewport_scroll(top_before);
+        self.set_state_changed();
+        self.request_redraw();
+    }
+
+    /// Search the scrollback and the screen for `query`, and scroll to the most recent match.
+    /// Returns which match that is and how many were found
+    pub fn search(&mut self, query: &str) -> Option<(usize, usize)> {
+        let matches = self.get_current_buffer().terminal_buffer.search(query);
+        self.search.search(query, matches);
+        self.scroll_to_search_match();
+        self.set_state_changed();
+        self.request_redraw();
+        self.search.position()
+    }
+
+    /// Scroll to the match before the current one, further up the scrollback
+    pub fn previous_search_match(&mut self) -> Option<(usize, usize)> {
+        self.search.previous_match()?;
+        self.scroll_to_search_match();
+        self.search.position()
+    }
+
+    /// Scroll to the match after the current one, further down towards the screen
+    pub fn next_search_match(&mut self) -> Option<(usize, usize)> {
+        self.search.next_match()?;
+        self.scroll_to_search_match();
+        self.search.position()
+    }
+
+    /// Stop searching, taking the highlights away
+    pub fn clear_search(&mut self) {
+        self.search.clear();
+        self.set_state_changed();
+        self.request_redraw();
+    }
+
+    /// Bring the current search match on to the screen, leaving the viewport where it is if it
+    /// already shows it
+    fn scroll_to_search_match(&mut self) {
+        let Some(range) = self.search.current_match().cloned() else {
+            return;
+        };
 
-        let x = current_buffer.cursor_state.pos.x + 1;
-        let y = current_buffer.cursor_state.pos.y + 1;
+        if self.visible_rows_for_buf_range(&range).is_some() {
+            self.set_state_changed();
+            self.request_redraw();
+            return;
+        }
+
+        self.scroll_to_command(range.start);
+    }
+
+    /// The rows on screen that currently show `buf_range`, if any
+    pub fn visible_rows_for_buf_range(
+        &mut self,
+        buf_range: &std::ops::Range<usize>,
+    ) -> Option<std::ops::Range<usize>> {
+        self.get_current_buffer()
+            .terminal_buffer
+            .buf_range_to_visible_rows(buf_range)
+    }
+
+    /// The rows on screen showing the output of the command that is running, between the
+    /// shell's OSC 133 C and D marks, as far as it has got
+    pub fn running_command_rows(&mut self) -> Option<std::ops::Range<usize>> {
+        let range = self
+            .get_current_buffer()
+            .command_history
+            .running()?
+            .output_range();
+
+        self.visible_rows_for_buf_range(&range)
+    }
+
+    pub(crate) fn report_cursor_position(&mut self) {
+        let (y, x) = self.reported_cursor_pos();
         let output = collect_text(&format!("\x1b[{y};{x}R\x1b\\"));
 
         for input in output.iter() {
@@ -1043,6 +2288,24 @@ impl TerminalState {
         }
     }
 
+    /// Queue an XTWINOPS request for the GUI. Resizes in cells are brought within `size_limits`
+    /// here, with 0 keeping the size the terminal is now, so the window never tries for a
+    /// size the terminal won't take
+    fn push_window_command(&mut self, manip: WindowManipulation) {
+        let manip = match manip {
+            WindowManipulation::ResizeWindowToLinesAndColumns(rows, columns) => {
+                let (width, height) = self.get_win_size();
+                let columns = if columns == 0 { width } else { columns };
+                let rows = if rows == 0 { height } else { rows };
+                let (columns, rows) = self.size_limits.clamp(columns, rows);
+                WindowManipulation::ResizeWindowToLinesAndColumns(rows, columns)
+            }
+            manip => manip,
+        };
+
+        self.window_commands.push(manip);
+    }
+
     pub fn report_window_state(&mut self, minimized: bool) {
         let output = if minimized {
             collect_text(&"\x1b[2t".to_string())
@@ -1155,6 +2418,56 @@ impl TerminalState {
         }
     }
 
+    /// Answer DECRQSS. Only SGR is supported, anything else gets the "invalid request" reply
+    pub fn report_status_string(&mut self, setting: &str) {
+        let report = if setting == "m" {
+            format!(
+                "\x1bP1$r{}m\x1b\\",
+                self.get_current_buffer().cursor_state.sgr_params()
+            )
+        } else {
+            self.unsupported.record(&format!("DECRQSS for {setting}"));
+            "\x1bP0$r\x1b\\".to_string()
+        };
+
+        for input in collect_text(&report).iter() {
+            match self.write(input) {
+                Ok(()) => (),
+                Err(e) => {
+                    error!("Failed to write status string report: {e}");
+                }
+            }
+        }
+    }
+
+    /// Change the kitty keyboard protocol's flags for the screen showing, or report them
+    fn kitty_keyboard(&mut self, request: KittyKeyboardRequest) {
+        let flags = &mut self.get_current_buffer().kitty_keyboard;
+        match request {
+            KittyKeyboardRequest::Push(pushed) => flags.push(pushed),
+            KittyKeyboardRequest::Pop(count) => flags.pop(count),
+            KittyKeyboardRequest::Set { flags: set, mode } => flags.set(set, mode),
+            KittyKeyboardRequest::Query => {
+                let report = format!("\x1b[?{}u", flags.current());
+                self.report_mode(&report);
+            }
+        }
+    }
+
+    /// The keyboard protocols the application asked for, for encoding keys with
+    #[must_use]
+    pub fn keyboard_protocol(&self) -> KeyboardProtocol {
+        let buffer = match self.current_buffer {
+            BufferType::Primary => &self.primary_buffer,
+            BufferType::Alternate => &self.alternate_buffer,
+        };
+
+        KeyboardProtocol {
+            modify_other_keys: self.modes.modify_other_keys,
+            kitty_flags: buffer.kitty_keyboard.current(),
+        }
+    }
+
     pub fn report_mode(&mut self, report: &String) {
         let report = collect_text(report);
         for input in report.iter() {
@@ -1168,52 +2481,120 @@ impl TerminalState {
     }
 
     pub(crate) fn clip_buffer_lines(&mut self) {
-        match self.current_buffer {
-            BufferType::Primary => {
-                let current_buffer = self.get_current_buffer();
+        let current_buffer = self.get_current_buffer();
+        let clipped = if current_buffer.terminal_buffer.has_scrollback() {
+            current_buffer
+                .terminal_buffer
+                .clip_lines_for_primary_buffer()
+        } else {
+            current_buffer
+                .terminal_buffer
+                .clip_lines_for_alternate_buffer()
+        };
 
-                if let Some(range) = current_buffer
-                    .terminal_buffer
-                    .clip_lines_for_primary_buffer()
-                {
-                    match current_buffer.format_tracker.delete_range(range) {
-                        Ok(()) => (),
-                        Err(e) => {
-                            error!("Failed to delete range: {e}");
-                        }
-                    }
+        if let Some(range) = clipped {
+            current_buffer.command_history.clip(&range);
+            current_buffer.line_timestamps.clip(&range);
+            current_buffer.selection = current_buffer
+                .selection
+                .and_then(|selection| selection.clip(&range));
+
+            match current_buffer.format_tracker.delete_range(range) {
+                Ok(()) => (),
+                Err(e) => {
+                    error!("Failed to delete range: {e}");
                 }
             }
-            BufferType::Alternate => {
-                let current_buffer = self.get_current_buffer();
+        }
+    }
 
-                if let Some(range) = current_buffer
-                    .terminal_buffer
-                    .clip_lines_for_alternate_buffer()
-                {
-                    match current_buffer.format_tracker.delete_range(range) {
-                        Ok(()) => (),
-                        Err(e) => {
-                            error!("Failed to delete range: {e}");
-                        }
-                    }
-                }
+    /// Whether DEC private mode `number` is set, or `None` if it isn't one XTSAVE can save. The
+    /// modes that switch screens or hold drawing back are left out, as putting them back later
+    /// would do more than the program asked for
+    #[must_use]
+    pub fn private_mode_is_set(&self, number: usize) -> Option<bool> {
+        let buffer = match self.current_buffer {
+            BufferType::Primary => &self.primary_buffer,
+            BufferType::Alternate => &self.alternate_buffer,
+        };
+
+        Some(match number {
+            1 => self.modes.cursor_key == Decckm::Application,
+            6 => buffer.origin_mode == Decom::Origin,
+            7 => buffer.cursor_state.line_wrap_mode == Decawm::AutoWrap,
+            12 => self.modes.cursor_blinking == XtCBlink::Blinking,
+            25 => buffer.show_cursor == Dectcem::Show,
+            66 => self.modes.keypad == Decnkm::Application,
+            67 => self.modes.backarrow_key == Decbkm::Backspace,
+            9 | 1000 | 1002 | 1003 | 1016 => {
+                self.modes.mouse_tracking.mouse_mode_number() == number
+            }
+            1004 => self.modes.focus_reporting == XtMseWin::Enabled,
+            1005 | 1006 | 1015 => self.modes.mouse_encoding.mouse_mode_number() == number,
+            1007 => self.modes.alternate_scroll == AltScroll::Enabled,
+            2004 => self.modes.bracketed_paste == RlBracket::Enabled,
+            _ => return None,
+        })
+    }
+
+    /// XTSAVE: remember whether each of `modes` is set, for XTRESTORE to put back
+    pub fn save_private_modes(&mut self, modes: &[usize]) {
+        for &number in modes {
+            if let Some(set) = self.private_mode_is_set(number) {
+                self.saved_private_modes.insert(number, set);
+            } else {
+                debug!("XTSAVE: can't save private mode {number}");
             }
         }
     }
 
+    /// XTRESTORE: set or reset each of `modes` as it was when it was saved. Modes that weren't
+    /// saved are left as they are
+    pub fn restore_private_modes(&mut self, modes: &[usize]) {
+        for &number in modes {
+            let Some(&set) = self.saved_private_modes.get(&number) else {
+                debug!("XTRESTORE: private mode {number} wasn't saved");
+                continue;
+            };
+
+            // Only modes that have changed are touched. The mouse modes are one setting between
+            // them, so resetting one that isn't set would turn off another that is
+            if self.private_mode_is_set(number) == Some(set) {
+                continue;
+            }
+
+            let set_mode = if set {
+                SetMode::DecSet
+            } else {
+                SetMode::DecRst
+            };
+            let mode = terminal_mode_from_params(format!("?{number}").as_bytes(), &set_mode);
+            self.set_mode(&mode);
+        }
+    }
+
     pub fn set_top_and_bottom_margins(&mut self, top: usize, bottom: usize) {
         let current_buffer = self.get_current_buffer();
 
-        current_buffer
+        if current_buffer
             .terminal_buffer
-            .set_top_and_bottom_margins(top, bottom);
-        self.set_cursor_pos(Some(1), Some(1));
+            .set_top_and_bottom_margins(top, bottom)
+        {
+            self.set_cursor_pos(Some(1), Some(1));
+        }
     }
 
+    #[allow(clippy::too_many_lines)]
     pub fn handle_incoming_data(&mut self, incoming: &[u8]) {
-        #[cfg(debug_assertions)]
-        let now = Instant::now();
+        let now = self.metrics.enabled.then(Instant::now);
+        if self.input_latency.enabled() {
+            self.input_latency.lock().output_received(Instant::now());
+        }
+        self.term_warning.scan(incoming);
+        self.search.mark_stale();
+        if !incoming.is_empty() {
+            self.bell.output(self.window_focused);
+        }
         // if we have leftover data, prepend it to the incoming data
         let mut incoming = self.leftover_data.take().map_or_else(
             || incoming.to_vec(),
@@ -1245,7 +2626,11 @@ impl TerminalState {
         }
 
         // verify that the incoming data is utf-8
-        let parsed = self.parser.push(&incoming);
+        let mut parsed = self.parser.push(&incoming);
+        self.save_invalid_sequences();
+        for processor in &mut self.output_processors {
+            processor.process(&mut parsed);
+        }
 
         for segment in parsed {
             // if segment is not data, we want to print out the segment
@@ -1259,6 +2644,15 @@ impl TerminalState {
                 debug!("Incoming segment: {segment:?}");
             }
 
+            match &segment {
+                TerminalOutput::Data(data) => {
+                    self.echo_prediction.output_received(data, Instant::now());
+                }
+                // Colors don't move anything, so the echo can still be on its way
+                TerminalOutput::Sgr(_) => (),
+                _ => self.echo_prediction.clear(),
+            }
+
             match segment {
                 TerminalOutput::Data(data) => self.handle_data(&data),
                 TerminalOutput::SetCursorPos { x, y } => self.set_cursor_pos(x, y),
@@ -1271,7 +2665,12 @@ impl TerminalState {
                 TerminalOutput::ClearLineBackwards => self.clear_line_backwards(),
                 TerminalOutput::ClearLine => self.clear_line(),
                 TerminalOutput::CarriageReturn => self.carriage_return(),
-                TerminalOutput::Newline => self.new_line(),
+                TerminalOutput::Newline | TerminalOutput::Index => self.new_line(),
+                TerminalOutput::NextLine => {
+                    self.carriage_return();
+                    self.new_line();
+                }
+                TerminalOutput::ReverseIndex => self.reverse_index(),
                 TerminalOutput::Backspace => self.backspace(),
                 TerminalOutput::InsertLines(num_lines) => self.insert_lines(num_lines),
                 TerminalOutput::Delete(num_chars) => self.delete(num_chars),
@@ -1284,15 +2683,19 @@ impl TerminalState {
                     self.character_replace = dec_special_graphics;
                 }
                 TerminalOutput::CursorReport => self.report_cursor_position(),
-                TerminalOutput::Skipped | TerminalOutput::Bell => (),
-                TerminalOutput::ApplicationKeypadMode => {
-                    self.modes.cursor_key = Decckm::Application;
+                TerminalOutput::Bell => self.bell.ring(self.window_focused),
+                TerminalOutput::Skipped => (),
+                TerminalOutput::Unsupported(feature) => {
+                    self.unsupported.record(&feature);
                 }
-                TerminalOutput::NormalKeypadMode => self.modes.cursor_key = Decckm::Ansi,
+                TerminalOutput::ApplicationKeypadMode => self.modes.keypad = Decnkm::Application,
+                TerminalOutput::NormalKeypadMode => self.modes.keypad = Decnkm::Numeric,
                 TerminalOutput::CursorVisualStyle(style) => {
-                    debug!("Ignoring cursor visual style: {style:?}");
+                    self.requested_cursor_style = Some(style);
                 }
-                TerminalOutput::WindowManipulation(manip) => self.window_commands.push(manip),
+                TerminalOutput::DefaultCursorVisualStyle => self.requested_cursor_style = None,
+                TerminalOutput::SoftReset => self.reset_modes(),
+                TerminalOutput::WindowManipulation(manip) => self.push_window_command(manip),
                 TerminalOutput::SetTopAndBottomMargins {
                     top_margin,
                     bottom_margin,
@@ -1300,6 +2703,13 @@ impl TerminalState {
                     self.set_top_and_bottom_margins(top_margin, bottom_margin);
                 }
                 TerminalOutput::RequestDeviceAttributes => self.report_da(),
+                TerminalOutput::RequestStatusString(setting) => {
+                    self.report_status_string(&setting);
+                }
+                TerminalOutput::SavePrivateModes(modes) => self.save_private_modes(&modes),
+                TerminalOutput::RestorePrivateModes(modes) => self.restore_private_modes(&modes),
+                TerminalOutput::ModifyOtherKeys(level) => self.modes.modify_other_keys = level,
+                TerminalOutput::KittyKeyboard(request) => self.kitty_keyboard(request),
                 TerminalOutput::Invalid => {
                     info!("Unhandled terminal output: {segment:?}");
                 }
@@ -1309,86 +2719,203 @@ impl TerminalState {
         // now ensure total lines in buffer isn't too big
         self.clip_buffer_lines();
 
-        #[cfg(debug_assertions)]
-        // log the frame time
-        let elapsed = now.elapsed();
-        // show either elapsed as micros or millis, depending on the duration
-        #[cfg(debug_assertions)]
-        if elapsed.as_millis() > 0 {
-            debug!("Data processing time: {}ms", elapsed.as_millis());
-        } else {
-            debug!("Data processing time: {}μs", elapsed.as_micros());
+        if let Some(now) = now {
+            self.metrics.data_processing_time.record(now.elapsed());
+            self.metrics.bytes_processed = self
+                .metrics
+                .bytes_processed
+                .saturating_add(u64::try_from(incoming.len()).unwrap_or_default());
         }
 
         self.set_state_changed();
         self.request_redraw();
     }
 
+    /// Send `signal` to the program in the foreground, whatever keyboard modes it has set
+    ///
+    /// # Errors
+    /// Will return an error if the pty has gone away
+    pub fn send_signal(&self, signal: PtySignal) -> Result<()> {
+        self.write_tx.send(PtyWrite::Signal(signal))?;
+        Ok(())
+    }
+
     /// Write data to the terminal
     ///
     /// # Errors
     /// Will return an error if the write fails
     pub fn write(&self, to_write: &TerminalInput) -> Result<()> {
-        match to_write.to_payload(
-            self.get_cursor_key_mode() == Decckm::Application,
+        let payload = to_write.to_payload(
             self.get_cursor_key_mode() == Decckm::Application,
-        ) {
-            TerminalInputPayload::Single(c) => {
-                self.write_tx.send(PtyWrite::Write(vec![c]))?;
-            }
-            TerminalInputPayload::Many(to_write) => {
-                self.write_tx.send(PtyWrite::Write(to_write.to_vec()))?;
-            }
-        };
+            self.modes.keypad == Decnkm::Application,
+            self.modes.backarrow_key == Decbkm::Backspace,
+        );
+        self.write_tx.send(PtyWrite::Write(payload.into_bytes()))?;
 
         Ok(())
     }
 
-    pub fn scroll(&mut self, scroll: f32) {
+    /// Guess at what the shell will echo for `input`, which the user has just typed. Full screen
+    /// applications on the alternate screen draw the line themselves, so there is no guessing
+    /// there
+    pub fn predict_echo(&mut self, input: &TerminalInput) {
         if self.current_buffer == BufferType::Alternate {
-            let key = if scroll < 0.0 {
+            self.echo_prediction.clear();
+            return;
+        }
+
+        self.echo_prediction.typed(input, Instant::now());
+    }
+
+    /// Scroll by `lines`, where positive values scroll up. Fractions of a line are kept until
+    /// enough have built up to scroll a whole line
+    pub fn scroll(&mut self, lines: f32) {
+        let lines = self.scroll_accumulator.accumulate(lines);
+        if lines == 0.0 {
+            return;
+        }
+
+        let count = match lines.abs().approx_as::<usize>() {
+            Ok(count) => count,
+            Err(e) => {
+                error!("Failed to convert scroll to usize: {e}\nUsing default of 1");
+                1
+            }
+        };
+
+        // Without scrollback to move through, full screen applications get arrow keys instead,
+        // unless they have turned alternate scroll mode off
+        if !self.has_scrollback() {
+            if self.modes.alternate_scroll == AltScroll::Disabled {
+                return;
+            }
+
+            let key = if lines < 0.0 {
                 TerminalInput::ArrowDown
             } else {
                 TerminalInput::ArrowUp
             };
 
-            match self.write(&key) {
-                Ok(()) => (),
-                Err(e) => {
+            for _ in 0..count {
+                if let Err(e) = self.write(&key) {
                     error!("Failed to scroll: {e}");
+                    break;
                 }
             }
 
             return;
         }
 
-        let current_buffer = &mut self.get_current_buffer().terminal_buffer;
-        // convert the scroll to usize, with a minimum of 1
-        let mut scroll = scroll.round();
+        let scroller = if lines < 0.0 {
+            ScrollDirection::Down(count)
+        } else {
+            ScrollDirection::Up(count)
+        };
 
-        if scroll < 0.0 {
-            scroll *= -1.0;
-            let scroll_as_usize = match scroll.max(1.0).approx_as::<usize>() {
-                Ok(scroll) => scroll,
-                Err(e) => {
-                    error!("Failed to convert scroll to usize: {e}\nUsing default of 1");
-                    1
-                }
-            };
+        let terminal_buffer = &mut self.get_current_buffer().terminal_buffer;
+        let top_before = terminal_buffer.viewport_line_range().start;
+        terminal_buffer.scroll(&scroller);
+        self.record_viewport_scroll(top_before);
+    }
 
-            let scoller = ScrollDirection::Down(scroll_as_usize);
-            current_buffer.scroll(&scoller);
-        } else {
-            let scroll_as_usize = match scroll.max(1.0).approx_as::<usize>() {
-                Ok(scroll) => scroll,
-                Err(e) => {
-                    error!("Failed to convert scroll to usize: {e}\nUsing default of 1");
-                    1
-                }
-            };
+    /// Whether the screen showing has scrollback to move through. The alternate screen only
+    /// does if it has been given some
+    #[must_use]
+    pub fn has_scrollback(&self) -> bool {
+        match self.current_buffer {
+            BufferType::Primary => true,
+            BufferType::Alternate => self.alternate_buffer.terminal_buffer.has_scrollback(),
+        }
+    }
+
+    /// Move the viewport through the scrollback. Does nothing without any
+    pub fn move_viewport(&mut self, movement: ViewportMovement) {
+        if !self.has_scrollback() {
+            return;
+        }
+
+        let (_, page) = self.get_win_size();
+        let terminal_buffer = &mut self.get_current_buffer().terminal_buffer;
+        let top_before = terminal_buffer.viewport_line_range().start;
+        match movement {
+            ViewportMovement::PageUp => terminal_buffer.scroll_up(&page),
+            ViewportMovement::PageDown => terminal_buffer.scroll_down(&page),
+            ViewportMovement::Top => terminal_buffer.scroll_to_top(),
+            ViewportMovement::Bottom => terminal_buffer.scroll_to_bottom(),
+        }
+        self.record_viewport_scroll(top_before);
+        self.set_state_changed();
+        self.request_redraw();
+    }
+
+    /// Where the viewport is in the scrollback, as the number of its last line (counting from 1)
+    /// and the number of lines in total
+    #[must_use]
+    pub fn viewport_position(&self) -> (usize, usize) {
+        let terminal_buffer = match self.current_buffer {
+            BufferType::Primary => &self.primary_buffer.terminal_buffer,
+            BufferType::Alternate => &self.alternate_buffer.terminal_buffer,
+        };
+
+        (
+            terminal_buffer.viewport_line_range().end,
+            terminal_buffer.line_count(),
+        )
+    }
+
+    /// How many lines the viewport has been scrolled back from the live screen
+    #[must_use]
+    pub fn scrolled_back_lines(&self) -> usize {
+        let (end, total) = self.viewport_position();
+        total.saturating_sub(end)
+    }
+
+    /// Bring the live screen back in to view, as typing does
+    pub fn snap_to_bottom(&mut self) {
+        if self.scrolled_back_lines() > 0 {
+            self.move_viewport(ViewportMovement::Bottom);
+        }
+    }
+
+    /// The row of the live screen shown at `viewport_row`, for reporting positions to
+    /// applications. Applications only know about the live screen, so rows scrolled back in to
+    /// the scrollback have no position to report and give `None`
+    #[must_use]
+    pub fn viewport_row_to_screen_row(&self, viewport_row: usize) -> Option<usize> {
+        viewport_row.checked_sub(self.scrolled_back_lines())
+    }
+
+    /// The row of the viewport showing `screen_row` of the live screen, the other way round to
+    /// `viewport_row_to_screen_row`. `None` if the viewport is scrolled back far enough that the
+    /// row is below the bottom of it
+    #[must_use]
+    pub fn screen_row_to_viewport_row(&mut self, screen_row: usize) -> Option<usize> {
+        let (_, height) = self.get_win_size();
+        let viewport_row = screen_row + self.scrolled_back_lines();
+        (viewport_row < height).then_some(viewport_row)
+    }
 
-            let scroller = ScrollDirection::Up(scroll_as_usize);
-            current_buffer.scroll(&scroller);
+    fn record_viewport_scroll(&mut self, top_before: usize) {
+        // Rows that wrap in the buffer are a single row on screen, so the distance scrolled on
+        // screen isn't known
+        if self.no_wrap_view() {
+            return;
         }
+
+        let top_after = self
+            .get_current_buffer()
+            .terminal_buffer
+            .viewport_line_range()
+            .start;
+
+        let moved = isize::try_from(top_before).unwrap_or(isize::MAX)
+            - isize::try_from(top_after).unwrap_or(isize::MAX);
+        self.viewport_scrolled_lines = self.viewport_scrolled_lines.saturating_add(moved);
+    }
+
+    /// How many lines the user has scrolled the viewport since this was last called.
+    /// Positive values are scrolling up
+    pub fn take_viewport_scrolled_lines(&mut self) -> isize {
+        std::mem::take(&mut self.viewport_scrolled_lines)
     }
 }
@@ -0,0 +1,66 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+/// The kitty keyboard protocol's flag for disambiguating escape codes, the only one we support
+pub const DISAMBIGUATE_ESCAPE_CODES: u8 = 1;
+
+/// Pushing more flags than this forgets the oldest, so a program that never pops can't grow the
+/// stack forever
+pub const MAX_PUSHED_FLAGS: usize = 16;
+
+/// The kitty keyboard protocol's stack of flags for one screen. Programs push the flags they want
+/// when they start and pop them when they leave, which puts back whatever was in use before
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct KittyKeyboardFlags {
+    stack: Vec<u8>,
+}
+
+impl KittyKeyboardFlags {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { stack: Vec::new() }
+    }
+
+    /// The flags in use. Flags we don't support are never kept, so they are left out here too
+    #[must_use]
+    pub fn current(&self) -> u8 {
+        self.stack.last().copied().unwrap_or_default()
+    }
+
+    pub fn push(&mut self, flags: u8) {
+        if self.stack.len() == MAX_PUSHED_FLAGS {
+            self.stack.remove(0);
+        }
+
+        self.stack.push(flags & DISAMBIGUATE_ESCAPE_CODES);
+    }
+
+    /// Pop `count` entries. Popping more than were pushed turns all the flags off
+    pub fn pop(&mut self, count: usize) {
+        self.stack.truncate(self.stack.len().saturating_sub(count));
+    }
+
+    /// Change the flags in use: mode 1 uses `flags`, 2 turns them on as well and 3 turns them
+    /// off. Other modes are ignored
+    pub fn set(&mut self, flags: u8, mode: usize) {
+        let current = self.current();
+        let flags = match mode {
+            1 => flags,
+            2 => current | flags,
+            3 => current & !flags,
+            _ => return,
+        } & DISAMBIGUATE_ESCAPE_CODES;
+
+        match self.stack.last_mut() {
+            Some(last) => *last = flags,
+            None => self.stack.push(flags),
+        }
+    }
+
+    /// Forget every flag pushed
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
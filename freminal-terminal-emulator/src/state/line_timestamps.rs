@@ -0,0 +1,128 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    ops::Range,
+    time::{Duration, SystemTime},
+};
+
+/// Writes to touching parts of the buffer this close together are kept as one
+pub const WRITE_COALESCE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct TimedWrite {
+    range: Range<usize>,
+    time: SystemTime,
+}
+
+/// When each part of the buffer was last written to, so we can tell when a line was output
+/// without going back over the data
+///
+/// Positions are indexes in to the terminal buffer, so they have to be shifted whenever data is
+/// inserted, deleted or clipped from the start of the buffer. Writes close together in time and
+/// place are merged, and writes that have been completely overwritten are forgotten, which keeps
+/// a shell printing line after line down to a handful of entries.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct LineTimestamps {
+    writes: Vec<TimedWrite>,
+}
+
+impl LineTimestamps {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { writes: Vec::new() }
+    }
+
+    /// `range` of the buffer was written to at `time`
+    pub fn written(&mut self, range: Range<usize>, time: SystemTime) {
+        if range.is_empty() {
+            return;
+        }
+
+        let mut range = range;
+        if let Some(last) = self.writes.last() {
+            let touches = range.start <= last.range.end && last.range.start <= range.end;
+            let recent = time
+                .duration_since(last.time)
+                .is_ok_and(|since| since < WRITE_COALESCE_INTERVAL);
+
+            if touches && recent {
+                range = range.start.min(last.range.start)..range.end.max(last.range.end);
+                self.writes.pop();
+            }
+        }
+
+        self.writes
+            .retain(|write| write.range.start < range.start || write.range.end > range.end);
+        self.writes.push(TimedWrite { range, time });
+    }
+
+    /// Data was inserted at `range`, pushing everything after it along
+    pub fn insert_range(&mut self, range: &Range<usize>) {
+        let len = range.end - range.start;
+        if len == 0 {
+            return;
+        }
+
+        for write in &mut self.writes {
+            if write.range.start >= range.start {
+                write.range.start += len;
+            }
+            if write.range.end > range.start {
+                write.range.end += len;
+            }
+        }
+    }
+
+    /// Data in `range` was removed, pulling everything after it back
+    pub fn delete_range(&mut self, range: &Range<usize>) {
+        let len = range.end - range.start;
+        if len == 0 {
+            return;
+        }
+
+        let shift = |pos: usize| {
+            if pos <= range.start {
+                pos
+            } else {
+                pos.saturating_sub(len).max(range.start)
+            }
+        };
+
+        for write in &mut self.writes {
+            write.range = shift(write.range.start)..shift(write.range.end);
+        }
+        self.writes.retain(|write| !write.range.is_empty());
+    }
+
+    /// Shift everything to account for `range` being removed from the start of the buffer
+    pub fn clip(&mut self, range: &Range<usize>) {
+        self.delete_range(range);
+    }
+
+    pub fn clear(&mut self) {
+        self.writes.clear();
+    }
+
+    /// When each of `lines` was last written to, or `None` if we have no record of it. `lines`
+    /// must be in buffer order, like the line ranges the buffer keeps
+    #[must_use]
+    pub fn for_lines(&self, lines: &[Range<usize>]) -> Vec<Option<SystemTime>> {
+        let mut timestamps = vec![None; lines.len()];
+
+        for write in &self.writes {
+            let first = lines.partition_point(|line| line.end <= write.range.start);
+            for (line, timestamp) in lines[first..].iter().zip(&mut timestamps[first..]) {
+                if line.start >= write.range.end {
+                    break;
+                }
+
+                *timestamp = (*timestamp).max(Some(write.time));
+            }
+        }
+
+        timestamps
+    }
+}
@@ -3,9 +3,24 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+pub mod bell;
+pub mod bidi;
 pub mod buffer;
 pub mod cursor;
 pub mod data;
+pub mod echo_prediction;
 pub mod fonts;
 pub mod internal;
+pub mod kitty_keyboard;
+pub mod line_timestamps;
+pub mod output_processor;
+pub mod redaction;
+pub mod redraw;
+pub mod repaint;
+pub mod search;
+pub mod selection;
+pub mod shell_integration;
 pub mod term_char;
+pub mod term_warning;
+pub mod title;
+pub mod unsupported;
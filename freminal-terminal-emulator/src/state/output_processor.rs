@@ -0,0 +1,20 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::fmt::Debug;
+
+use crate::ansi::TerminalOutput;
+
+/// Sees what the parser made of each piece of output before the terminal acts on it
+///
+/// A processor can change, drop or add to the batch, to mask secrets before they reach the
+/// screen and the scrollback for example, or only look at it for logging or metrics. Processors
+/// run in the order they were added, each seeing what the one before it left.
+///
+/// Text can be split between batches wherever a read from the pty ends, so a processor looking
+/// for something in the text may need to remember the end of the last batch.
+pub trait OutputProcessor: Debug + Send {
+    fn process(&mut self, output: &mut Vec<TerminalOutput>);
+}
@@ -0,0 +1,42 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::borrow::Cow;
+
+use freminal_common::redaction::Redactor;
+
+use crate::ansi::TerminalOutput;
+
+use super::output_processor::OutputProcessor;
+
+/// Masks secrets in the output before they reach the screen or the scrollback
+///
+/// Only text is masked, one run of it at a time. A secret split by an escape sequence, such as a
+/// color change part way through, or between two reads from the pty, is only seen whole once it
+/// has been written. `TerminalState::set_redactor` adds this along with masking those in the
+/// cells they were written to.
+#[derive(Debug)]
+pub struct RedactSecrets {
+    redactor: Redactor,
+}
+
+impl RedactSecrets {
+    #[must_use]
+    pub const fn new(redactor: Redactor) -> Self {
+        Self { redactor }
+    }
+}
+
+impl OutputProcessor for RedactSecrets {
+    fn process(&mut self, output: &mut Vec<TerminalOutput>) {
+        for segment in output {
+            if let TerminalOutput::Data(data) = segment {
+                if let Cow::Owned(redacted) = self.redactor.redact(data) {
+                    *data = redacted;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+/// Wakes up whatever is drawing the terminal when there is something new to show
+///
+/// The terminal doesn't know what is drawing it. The GUI hands it one of these for egui, and
+/// other frontends, or tests with no frontend at all, can hand it their own.
+pub trait RedrawNotifier: Debug + Send + Sync {
+    /// Draw a new frame as soon as possible
+    fn request_redraw(&self);
+
+    /// Draw a new frame once `delay` has passed, if one hasn't been drawn by then
+    fn request_redraw_after(&self, delay: Duration);
+}
+
+/// A `RedrawNotifier` shared between the terminal state and the emulator around it
+pub type SharedRedrawNotifier = Arc<dyn RedrawNotifier>;
@@ -0,0 +1,61 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::{Duration, Instant};
+
+/// Limits how often new output causes the GUI to repaint
+///
+/// Output from the pty can arrive in thousands of small chunks a second. Repainting for every one
+/// of them burns CPU (and battery) for frames nobody will ever see, so we space repaints out to at
+/// most `max_fps` a second. When nothing is happening we don't request repaints at all.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RepaintLimiter {
+    min_frame_time: Option<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl Default for RepaintLimiter {
+    fn default() -> Self {
+        Self::new(freminal_common::args::DEFAULT_MAX_FPS)
+    }
+}
+
+impl RepaintLimiter {
+    /// Create a new limiter. A `max_fps` of 0 means repaints are not limited
+    #[must_use]
+    pub fn new(max_fps: u32) -> Self {
+        let min_frame_time = if max_fps == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(1) / max_fps)
+        };
+
+        Self {
+            min_frame_time,
+            last_frame: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn min_frame_time(&self) -> Option<Duration> {
+        self.min_frame_time
+    }
+
+    /// Record that the GUI has drawn a frame
+    pub fn frame_rendered(&mut self) {
+        self.last_frame = Some(Instant::now());
+    }
+
+    /// How long to wait before the next repaint so we stay under the frame cap
+    #[must_use]
+    pub fn delay_until_next_frame(&self) -> Duration {
+        match (self.min_frame_time, self.last_frame) {
+            (Some(min_frame_time), Some(last_frame)) => {
+                min_frame_time.saturating_sub(last_frame.elapsed())
+            }
+            _ => Duration::ZERO,
+        }
+    }
+}
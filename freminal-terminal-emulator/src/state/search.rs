@@ -0,0 +1,215 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::ops::Range;
+
+use freminal_common::colors::TerminalColor;
+
+use crate::format_tracker::FormatTag;
+
+use super::{cursor::ReverseVideo, term_char::TChar};
+
+/// The background of every match of the search
+pub const SEARCH_MATCH_COLOR: TerminalColor = TerminalColor::Yellow;
+/// The background of the match the search is on
+pub const CURRENT_SEARCH_MATCH_COLOR: TerminalColor = TerminalColor::BrightMagenta;
+
+/// Where `query` appears in `buf`, oldest first
+///
+/// Lines that were soft wrapped are searched as one, since they only have a newline where the
+/// line really ended. The search ignores case unless `query` has a capital letter in it.
+#[must_use]
+pub fn find_matches<'a>(
+    buf: impl IntoIterator<Item = &'a TChar>,
+    query: &str,
+) -> Vec<Range<usize>> {
+    let query: Vec<char> = query.chars().collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let case_sensitive = query.iter().any(|c| c.is_uppercase());
+    // The second cell of a double width character isn't a character of its own, so it goes in
+    // to the one before it
+    let mut cells: Vec<(Range<usize>, char)> = Vec::new();
+    for (i, c) in buf.into_iter().enumerate() {
+        if *c != TChar::WideContinuation {
+            cells.push((i..i + 1, c.to_char()));
+        } else if let Some((cell, _)) = cells.last_mut() {
+            cell.end = i + 1;
+        }
+    }
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + query.len() <= cells.len() {
+        let found = cells[i..i + query.len()]
+            .iter()
+            .zip(&query)
+            .all(|((_, c), q)| chars_match(*c, *q, case_sensitive));
+        if !found {
+            i += 1;
+            continue;
+        }
+
+        matches.push(cells[i].0.start..cells[i + query.len() - 1].0.end);
+        i += query.len();
+    }
+
+    matches
+}
+
+fn chars_match(c: char, query: char, case_sensitive: bool) -> bool {
+    c == query || (!case_sensitive && c.to_lowercase().eq(query.to_lowercase()))
+}
+
+/// What the user is searching the scrollback for, and where it was found
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SearchState {
+    query: String,
+    matches: Vec<Range<usize>>,
+    /// The index in to `matches` of the one the search is on
+    current: Option<usize>,
+    /// The buffer has changed since the matches were found
+    stale: bool,
+}
+
+impl SearchState {
+    #[must_use]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    #[must_use]
+    pub fn matches(&self) -> &[Range<usize>] {
+        &self.matches
+    }
+
+    #[must_use]
+    pub fn current_match(&self) -> Option<&Range<usize>> {
+        self.matches.get(self.current?)
+    }
+
+    /// Which match the search is on, counting from 1 at the oldest, and how many there are
+    #[must_use]
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.current
+            .map(|current| (current + 1, self.matches.len()))
+    }
+
+    /// Start searching for `query`, which was found at `matches`, on the most recent match
+    pub fn search(&mut self, query: &str, matches: Vec<Range<usize>>) {
+        query.clone_into(&mut self.query);
+        self.current = matches.len().checked_sub(1);
+        self.matches = matches;
+        self.stale = false;
+    }
+
+    /// The buffer has changed since the matches were found, so they need finding again
+    #[must_use]
+    pub const fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Swap in the matches found again after the buffer changed, staying on the same match if it
+    /// is still there
+    pub fn refresh(&mut self, matches: Vec<Range<usize>>) {
+        let current = self.current_match().cloned();
+        self.matches = matches;
+        self.stale = false;
+        self.current = current
+            .and_then(|current| self.matches.iter().position(|m| *m == current))
+            .or_else(|| self.matches.len().checked_sub(1));
+    }
+
+    pub fn mark_stale(&mut self) {
+        self.stale = self.is_active();
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Move to the match before the current one, going round to the most recent at the top
+    pub fn previous_match(&mut self) -> Option<&Range<usize>> {
+        let len = self.matches.len();
+        self.current = self
+            .current
+            .map(|current| (current + len - 1) % len)
+            .or_else(|| len.checked_sub(1));
+        self.current_match()
+    }
+
+    /// Move to the match after the current one, going round to the oldest at the bottom
+    pub fn next_match(&mut self) -> Option<&Range<usize>> {
+        let len = self.matches.len();
+        self.current = self
+            .current
+            .map(|current| (current + 1) % len)
+            .or_else(|| (len > 0).then_some(0));
+        self.current_match()
+    }
+
+    /// Give the matches in `tags` a highlighted background, splitting tags where a match starts or
+    /// ends part way through one
+    #[must_use]
+    pub fn highlight(&self, tags: Vec<FormatTag>, visible: &Range<usize>) -> Vec<FormatTag> {
+        // Only the matches on screen need drawing
+        let first = self.matches.partition_point(|m| m.end <= visible.start);
+        let last = self.matches.partition_point(|m| m.start < visible.end);
+        if first >= last {
+            return tags;
+        }
+
+        let matches = &self.matches[first..last];
+        let current = self.current_match();
+        let mut highlighted = Vec::with_capacity(tags.len() + matches.len() * 2);
+
+        for tag in tags {
+            let mut start = tag.start;
+            for m in matches {
+                if m.end <= start || m.start >= tag.end {
+                    continue;
+                }
+
+                if m.start > start {
+                    highlighted.push(FormatTag {
+                        start,
+                        end: m.start,
+                        ..tag.clone()
+                    });
+                    start = m.start;
+                }
+
+                let end = m.end.min(tag.end);
+                let mut tag_in_match = FormatTag {
+                    start,
+                    end,
+                    ..tag.clone()
+                };
+                tag_in_match.colors.background_color = if current == Some(m) {
+                    CURRENT_SEARCH_MATCH_COLOR
+                } else {
+                    SEARCH_MATCH_COLOR
+                };
+                tag_in_match.colors.color = TerminalColor::Black;
+                tag_in_match.colors.reverse_video = ReverseVideo::Off;
+                highlighted.push(tag_in_match);
+                start = end;
+            }
+
+            if start < tag.end {
+                highlighted.push(FormatTag { start, ..tag });
+            }
+        }
+
+        highlighted
+    }
+}
@@ -0,0 +1,60 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::ops::Range;
+
+/// Text picked out with the mouse
+///
+/// Both ends are positions in the buffer rather than on screen, so the selection stays on the
+/// same text as the output scrolls or the viewport moves through the scrollback.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Selection {
+    /// Where the selection was started
+    pub anchor: usize,
+    /// Where the selection has been dragged to, before or after the anchor. The character here
+    /// is selected too
+    pub extent: usize,
+    /// The mouse button is still held down, so moving the mouse moves the extent
+    pub dragging: bool,
+}
+
+impl Selection {
+    #[must_use]
+    pub const fn new(buf_pos: usize) -> Self {
+        Self {
+            anchor: buf_pos,
+            extent: buf_pos,
+            dragging: true,
+        }
+    }
+
+    /// The part of the buffer that is selected
+    #[must_use]
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.extent)..self.anchor.max(self.extent) + 1
+    }
+
+    /// Nothing has been dragged over yet, so this was only a click
+    #[must_use]
+    pub const fn is_click(&self) -> bool {
+        self.anchor == self.extent
+    }
+
+    /// Shift the selection to account for `range` being removed from the start of the buffer.
+    /// Returns `None` if some of the selected text was removed
+    #[must_use]
+    pub fn clip(self, range: &Range<usize>) -> Option<Self> {
+        let removed = range.end - range.start;
+        if self.range().start < range.end {
+            return None;
+        }
+
+        Some(Self {
+            anchor: self.anchor - removed,
+            extent: self.extent - removed,
+            ..self
+        })
+    }
+}
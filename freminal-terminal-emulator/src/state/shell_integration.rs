@@ -0,0 +1,192 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::ops::Range;
+
+/// A single command reported by the shell through OSC 133
+///
+/// Positions are indexes in to the terminal buffer, so they have to be shifted whenever
+/// the start of the buffer is clipped.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CommandMark {
+    pub output_start: usize,
+    pub output_end: Option<usize>,
+    pub exit_code: Option<i32>,
+}
+
+impl CommandMark {
+    #[must_use]
+    pub const fn failed(&self) -> bool {
+        matches!(self.exit_code, Some(code) if code != 0)
+    }
+
+    #[must_use]
+    pub fn output_range(&self) -> Range<usize> {
+        self.output_start..self.output_end.unwrap_or(usize::MAX)
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct CommandHistory {
+    marks: Vec<CommandMark>,
+    /// Index of the mark we last jumped to. Repeated jumps walk further back in the history
+    jump_index: Option<usize>,
+    /// Where the line the user is typing a command on starts, from when the shell said the
+    /// prompt was drawn until the command is run
+    command_line_start: Option<usize>,
+    /// A prompt was started below the command being typed, so it is a continuation prompt
+    continuation_pending: bool,
+    /// Continuation prompts (like `> `) shown while typing commands over several lines
+    continuation_prompts: Vec<Range<usize>>,
+}
+
+impl CommandHistory {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            marks: Vec::new(),
+            jump_index: None,
+            command_line_start: None,
+            continuation_pending: false,
+            continuation_prompts: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn marks(&self) -> &[CommandMark] {
+        &self.marks
+    }
+
+    /// The command that is running: the most recent one, if the shell hasn't said it finished
+    #[must_use]
+    pub fn running(&self) -> Option<&CommandMark> {
+        self.marks.last().filter(|mark| mark.output_end.is_none())
+    }
+
+    /// Continuation prompts, so they can be left out when copying a command
+    #[must_use]
+    pub fn continuation_prompts(&self) -> &[Range<usize>] {
+        &self.continuation_prompts
+    }
+
+    /// A prompt is about to be drawn on the line starting at `line_start`
+    ///
+    /// While a command is being typed, a prompt on a later line is a continuation prompt. One on
+    /// the same line is the shell redrawing its prompt.
+    pub fn prompt_started(&mut self, line_start: usize) {
+        self.continuation_pending = self
+            .command_line_start
+            .is_some_and(|command_line_start| line_start > command_line_start);
+    }
+
+    /// The prompt has been drawn, and spans from `line_start` to `buf_pos`. The user types their
+    /// command after it
+    pub fn command_started(&mut self, line_start: usize, buf_pos: usize) {
+        if std::mem::take(&mut self.continuation_pending) && buf_pos > line_start {
+            self.continuation_prompts.push(line_start..buf_pos);
+        }
+
+        self.command_line_start = Some(line_start);
+    }
+
+    pub fn command_executed(&mut self, buf_pos: usize) {
+        self.command_line_start = None;
+        self.continuation_pending = false;
+
+        // A command that never reported finishing is left as is. We just start tracking the new one
+        self.marks.push(CommandMark {
+            output_start: buf_pos,
+            output_end: None,
+            exit_code: None,
+        });
+        self.jump_index = None;
+    }
+
+    pub fn command_finished(&mut self, buf_pos: usize, exit_code: Option<i32>) {
+        let Some(mark) = self.marks.last_mut() else {
+            debug!("Command finished without a command being executed. Ignoring");
+            return;
+        };
+
+        if mark.output_end.is_some() {
+            debug!("Command finished reported twice. Ignoring");
+            return;
+        }
+
+        mark.output_end = Some(buf_pos.max(mark.output_start));
+        mark.exit_code = exit_code;
+        self.jump_index = None;
+    }
+
+    /// Find the failed command before the last one we jumped to, or the most recent failed command
+    /// if we have not jumped yet
+    pub fn previous_failed(&mut self) -> Option<&CommandMark> {
+        let search_end = self.jump_index.unwrap_or(self.marks.len());
+        let index = self.marks[..search_end]
+            .iter()
+            .rposition(CommandMark::failed)?;
+
+        self.jump_index = Some(index);
+        self.marks.get(index)
+    }
+
+    /// The command before the last one we jumped to, or the most recent command if we have not
+    /// jumped yet
+    pub fn previous_command(&mut self) -> Option<&CommandMark> {
+        let index = self.jump_index.unwrap_or(self.marks.len()).checked_sub(1)?;
+
+        self.jump_index = Some(index);
+        self.marks.get(index)
+    }
+
+    /// The command after the last one we jumped to. Going past the most recent command ends the
+    /// jumping, so the next jump back starts from the end again
+    pub fn next_command(&mut self) -> Option<&CommandMark> {
+        let index = self.jump_index? + 1;
+        if index >= self.marks.len() {
+            self.jump_index = None;
+            return None;
+        }
+
+        self.jump_index = Some(index);
+        self.marks.get(index)
+    }
+
+    pub fn reset_jump(&mut self) {
+        self.jump_index = None;
+    }
+
+    /// Shift all of the marks to account for `range` being removed from the start of the buffer
+    pub fn clip(&mut self, range: &Range<usize>) {
+        let removed = range.end - range.start;
+
+        self.marks
+            .retain(|mark| mark.output_end.is_none_or(|end| end > range.end));
+
+        for mark in &mut self.marks {
+            mark.output_start = mark.output_start.saturating_sub(removed);
+            mark.output_end = mark.output_end.map(|end| end.saturating_sub(removed));
+        }
+
+        self.continuation_prompts
+            .retain(|prompt| prompt.start >= range.end);
+        for prompt in &mut self.continuation_prompts {
+            *prompt = prompt.start - removed..prompt.end - removed;
+        }
+        self.command_line_start = self
+            .command_line_start
+            .and_then(|start| start.checked_sub(removed));
+
+        self.jump_index = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.marks.clear();
+        self.jump_index = None;
+        self.command_line_start = None;
+        self.continuation_pending = false;
+        self.continuation_prompts.clear();
+    }
+}
@@ -4,17 +4,23 @@
 // https://opensource.org/licenses/MIT.
 
 use core::fmt;
+use std::borrow::Cow;
 
 use crate::error::ParserFailures;
 use anyhow::Result;
+use freminal_common::char_width::WidthProvider;
 use unicode_segmentation::UnicodeSegmentation;
 
+pub use freminal_common::char_width::{is_joiner, is_zero_width_char};
+
 #[derive(Debug, Clone, Eq)]
 pub enum TChar {
     Ascii(u8),
     Utf8(Vec<u8>),
     Space,
     NewLine,
+    /// The second cell of a double width character. It has no text of its own
+    WideContinuation,
 }
 
 impl TChar {
@@ -51,6 +57,32 @@ impl TChar {
         }
     }
 
+    /// The first `char` of this `TChar`. Multi codepoint graphemes are reduced to their base character
+    #[must_use]
+    pub fn to_char(&self) -> char {
+        match self {
+            Self::Ascii(c) => char::from(*c),
+            Self::Utf8(v) => std::str::from_utf8(v)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+            Self::Space | Self::WideContinuation => ' ',
+            Self::NewLine => '\n',
+        }
+    }
+
+    /// Cells this takes up on screen. A double width character is followed by a
+    /// `WideContinuation` for its second cell, so everything that counts cells can count `TChar`s
+    #[must_use]
+    pub fn cell_width(&self, widths: &WidthProvider) -> usize {
+        match self {
+            Self::Utf8(v) => {
+                std::str::from_utf8(v).map_or(1, |s| widths.grapheme_width(s).clamp(1, 2))
+            }
+            Self::Ascii(_) | Self::Space | Self::NewLine | Self::WideContinuation => 1,
+        }
+    }
+
     /// Convert a vector of u8s to a vector of `TChars`
     /// The assumption here is that the vector of u8s will contain one or more `TChars`.
     /// If the byte vector is known to contain a single `TChar`, then use `TChar::from` instead.
@@ -71,24 +103,48 @@ impl TChar {
     /// Convert a vector of graphemes to a vector of `TChar`
     /// The assumption here is that the vector of graphemes will contain one or more `TChars`.
     ///
+    /// Zero width characters (see `is_zero_width_char`) never get a cell of their own. They are
+    /// kept with the character before them on the line, or the one after them if they start a
+    /// line. Any at the very start are left as one zero width `TChar`, so the caller can keep them
+    /// with whatever comes before the data.
+    ///
     /// # Errors
     /// Will return an error if the vector contains characters that are not valid `TChar`
     ///
     pub fn from_vec_of_graphemes(v: &[&str]) -> Result<Vec<Self>> {
-        v.iter()
-            .map(|s| {
-                Ok(if s.len() == 1 {
-                    Self::new_from_single_char(s.as_bytes()[0])
-                } else {
-                    match Self::new_from_many_chars(s.as_bytes().to_vec()) {
-                        Ok(c) => c,
-                        Err(e) => {
-                            return Err(e);
-                        }
-                    }
-                })
-            })
-            .collect::<Result<Vec<Self>>>()
+        let mut tchars: Vec<Self> = Vec::with_capacity(v.len());
+        // Zero width characters at the start of a line, waiting for a character to go with
+        let mut pending = String::new();
+
+        for s in v {
+            if is_zero_width(s) {
+                match tchars.last_mut() {
+                    Some(previous) if previous != &Self::NewLine => previous.append_zero_width(s),
+                    Some(_) => pending.push_str(s),
+                    None => tchars.push(Self::new_from_many_chars(s.as_bytes().to_vec())?),
+                }
+                continue;
+            }
+
+            let mut tchar = if s.len() == 1 {
+                Self::new_from_single_char(s.as_bytes()[0])
+            } else {
+                Self::new_from_many_chars(s.as_bytes().to_vec())?
+            };
+
+            if tchar == Self::NewLine {
+                if !pending.is_empty() {
+                    trace!("Dropping zero width characters on an empty line");
+                    pending.clear();
+                }
+            } else if !pending.is_empty() {
+                tchar.prepend_zero_width(&std::mem::take(&mut pending));
+            }
+
+            tchars.push(tchar);
+        }
+
+        Ok(tchars)
     }
 
     /// Convert a String to a vector of `TChar`
@@ -98,24 +154,81 @@ impl TChar {
     pub fn from_string(s: &str) -> Result<Vec<Self>> {
         let graphemes = s.graphemes(true).collect::<Vec<&str>>();
 
-        graphemes
-            .iter()
-            .map(|s| {
-                Ok(if s.len() == 1 {
-                    Self::new_from_single_char(s.as_bytes()[0])
-                } else {
-                    match Self::new_from_many_chars(s.as_bytes().to_vec()) {
-                        Ok(c) => c,
-                        Err(e) => {
-                            return Err(e);
-                        }
+        Self::from_vec_of_graphemes(&graphemes)
+    }
+
+    /// Whether this is made up only of zero width characters, so takes up no space on screen
+    #[must_use]
+    pub fn is_zero_width(&self) -> bool {
+        match self {
+            Self::Utf8(v) => std::str::from_utf8(v).is_ok_and(is_zero_width),
+            Self::Ascii(_) | Self::Space | Self::NewLine | Self::WideContinuation => false,
+        }
+    }
+
+    /// Keep zero width characters with this one, without it taking up any more space
+    pub fn append_zero_width(&mut self, zero_width: &str) {
+        let mut bytes = match self {
+            Self::Ascii(c) => vec![*c],
+            Self::Space | Self::WideContinuation => vec![b' '],
+            Self::NewLine => vec![b'\n'],
+            Self::Utf8(v) => std::mem::take(v),
+        };
+        bytes.extend_from_slice(zero_width.as_bytes());
+
+        *self = Self::Utf8(bytes);
+    }
+
+    /// Keep zero width characters that come before this one with it
+    pub fn prepend_zero_width(&mut self, zero_width: &str) {
+        let mut bytes = zero_width.as_bytes().to_vec();
+        match self {
+            Self::Ascii(c) => bytes.push(*c),
+            Self::Space | Self::WideContinuation => bytes.push(b' '),
+            Self::NewLine => bytes.push(b'\n'),
+            Self::Utf8(v) => bytes.append(v),
+        }
+
+        *self = Self::Utf8(bytes);
+    }
+
+    /// The text to draw for this character, which leaves out any zero width characters kept with
+    /// it. Joiners that follow a visible character are kept, as they change how it and the one
+    /// after it are drawn. A joiner on its own has nothing to join, so is left out too
+    #[must_use]
+    pub fn visible_text(&self) -> Cow<'_, str> {
+        match self {
+            Self::Ascii(c) => Cow::Owned(char::from(*c).to_string()),
+            Self::Space => Cow::Borrowed(" "),
+            Self::NewLine => Cow::Borrowed("\n"),
+            // Drawn as part of the character before it
+            Self::WideContinuation => Cow::Borrowed(""),
+            Self::Utf8(v) => {
+                let s = std::str::from_utf8(v).unwrap_or_default();
+                if !s.chars().any(is_zero_width_char) {
+                    return Cow::Borrowed(s);
+                }
+
+                let mut text = String::with_capacity(s.len());
+                let mut after_visible = false;
+                for c in s.chars() {
+                    let zero_width = is_zero_width_char(c);
+                    if !zero_width || (after_visible && is_joiner(c)) {
+                        text.push(c);
                     }
-                })
-            })
-            .collect::<Result<Vec<Self>>>()
+                    after_visible = !zero_width;
+                }
+
+                Cow::Owned(text)
+            }
+        }
     }
 }
 
+fn is_zero_width(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_zero_width_char)
+}
+
 #[must_use]
 pub fn display_vec_tchar_as_string(v: &[TChar]) -> String {
     v.iter().fold(String::new(), |mut acc, c| {
@@ -151,7 +264,7 @@ impl PartialEq<u8> for TChar {
             Self::Ascii(c) => c == other,
             Self::Space => *other == 32,
             Self::NewLine => *other == 10,
-            Self::Utf8(_) => false,
+            Self::Utf8(_) | Self::WideContinuation => false,
         }
     }
 }
@@ -178,6 +291,7 @@ impl PartialEq<Self> for TChar {
             },
             Self::Space => matches!(other, Self::Space),
             Self::NewLine => matches!(other, Self::NewLine),
+            Self::WideContinuation => matches!(other, Self::WideContinuation),
         }
     }
 }
@@ -192,6 +306,7 @@ impl fmt::Display for TChar {
             Self::Utf8(v) => write!(f, "{}", std::str::from_utf8(v).unwrap_or("")),
             Self::Space => write!(f, " "),
             Self::NewLine => writeln!(f),
+            Self::WideContinuation => Ok(()),
         }
     }
 }
@@ -0,0 +1,89 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+/// A `TERM` every machine has a terminfo entry for, and that covers what we support
+pub const SUGGESTED_TERM: &str = "xterm-256color";
+
+/// What ncurses, tput, less, vim and tmux print when they have no terminfo entry for `TERM`
+const UNKNOWN_TERM_MESSAGES: [&[u8]; 6] = [
+    b"unknown terminal type",
+    b"Error opening terminal",
+    b"terminal is not fully functional",
+    b"Terminal entry not found in terminfo",
+    b"missing or unsuitable terminal",
+    b"No entry for terminal type",
+];
+
+/// Watches the output for programs complaining that they don't know our `TERM`, which happens
+/// after logging in to a machine without the freminal terminfo entry
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TermWarning {
+    pub enabled: bool,
+    /// The complaint that gave it away, once one has been seen
+    detected: Option<String>,
+    dismissed: bool,
+    /// The end of the output so far, in case a complaint is split between reads
+    tail: Vec<u8>,
+}
+
+impl TermWarning {
+    #[must_use]
+    pub const fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            detected: None,
+            dismissed: false,
+            tail: Vec::new(),
+        }
+    }
+
+    /// Look through the next piece of output
+    pub fn scan(&mut self, output: &[u8]) {
+        if !self.enabled || self.detected.is_some() {
+            return;
+        }
+
+        let mut haystack = std::mem::take(&mut self.tail);
+        haystack.extend_from_slice(output);
+
+        if let Some(message) = UNKNOWN_TERM_MESSAGES.iter().find(|message| {
+            haystack
+                .windows(message.len())
+                .any(|window| window == **message)
+        }) {
+            self.detected = Some(String::from_utf8_lossy(message).into_owned());
+            return;
+        }
+
+        let longest = UNKNOWN_TERM_MESSAGES
+            .iter()
+            .map(|message| message.len())
+            .max()
+            .unwrap_or_default();
+        let keep = haystack.len().min(longest - 1);
+        haystack.drain(..haystack.len() - keep);
+        self.tail = haystack;
+    }
+
+    /// The hint to show, if a program has complained and the user hasn't dismissed it
+    #[must_use]
+    pub fn hint(&self) -> Option<String> {
+        if self.dismissed {
+            return None;
+        }
+
+        self.detected.as_ref().map(|message| {
+            format!(
+                "A program said \"{message}\". The machine it runs on may not know freminal's \
+                 TERM. Try `export TERM={SUGGESTED_TERM}` there"
+            )
+        })
+    }
+
+    /// Stop showing the hint. It isn't shown again, even if another program complains
+    pub const fn dismiss(&mut self) {
+        self.dismissed = true;
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use crossbeam_channel::{Receiver, Sender};
+use freminal_common::title_format::TitleFormat;
+
+use super::bell::badged_title;
+use crate::ansi_components::osc::WorkingDirectory;
+
+/// The title the window opens with, and goes back to if the title stack runs out
+pub const DEFAULT_WINDOW_TITLE: &str = "Freminal";
+
+/// Sent to subscribers whenever the terminal's title changes
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TitleChanged {
+    pub title: String,
+}
+
+/// The title the terminal asked for. What is actually shown is put together by the title format,
+/// and may have a bell or activity badge added to it
+///
+/// Programs that set the title on every prompt would otherwise have us tell the window manager
+/// about it each time. The title shown is only handed out when it has changed, and only once per
+/// frame however many times it was set.
+#[derive(Debug)]
+pub struct WindowTitle {
+    title: String,
+    stack: Vec<String>,
+    bells: usize,
+    activity: bool,
+    format: TitleFormat,
+    /// The terminal's ID, for the format's `{id}`
+    id: String,
+    working_directory: Option<WorkingDirectory>,
+    /// `working_directory` as the format shows it
+    cwd: Option<String>,
+    /// The program running in the foreground, shown until an application sets a title
+    foreground_process: Option<String>,
+    /// What the window is currently showing
+    shown: String,
+    subscribers: Vec<Sender<TitleChanged>>,
+}
+
+impl Default for WindowTitle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WindowTitle {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_format(TitleFormat::default(), String::new())
+    }
+
+    /// A title shown using `format`, for the terminal with ID `id`
+    #[must_use]
+    pub fn with_format(format: TitleFormat, id: String) -> Self {
+        Self {
+            title: DEFAULT_WINDOW_TITLE.to_string(),
+            stack: Vec::new(),
+            bells: 0,
+            activity: false,
+            format,
+            id,
+            working_directory: None,
+            cwd: None,
+            foreground_process: None,
+            shown: DEFAULT_WINDOW_TITLE.to_string(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn set(&mut self, title: String) {
+        if title == self.title {
+            return;
+        }
+
+        self.title = title;
+
+        let event = TitleChanged {
+            title: self.title.clone(),
+        };
+        // Subscribers that have gone away are forgotten
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    pub fn push(&mut self) {
+        self.stack.push(self.title.clone());
+    }
+
+    pub fn pop(&mut self) {
+        let title = self
+            .stack
+            .pop()
+            .unwrap_or_else(|| DEFAULT_WINDOW_TITLE.to_string());
+        self.set(title);
+    }
+
+    /// The number of bells that rang while the window was unfocused, shown as a badge
+    pub fn set_bells(&mut self, bells: usize) {
+        self.bells = bells;
+    }
+
+    /// Whether there was output while the window was unfocused, shown as a badge if no bells rang
+    pub fn set_activity(&mut self, activity: bool) {
+        self.activity = activity;
+    }
+
+    /// The directory the shell last reported, for the format's `{cwd}`
+    pub fn set_working_directory(&mut self, directory: Option<&WorkingDirectory>) {
+        if directory == self.working_directory.as_ref() {
+            return;
+        }
+
+        self.working_directory = directory.cloned();
+        self.cwd = directory.map(ToString::to_string);
+    }
+
+    /// The program running in the foreground, such as `vim`, for when nothing has set a title
+    pub fn set_foreground_process(&mut self, name: Option<String>) {
+        self.foreground_process = name;
+    }
+
+    /// Get told about every change to the title
+    #[must_use]
+    pub fn subscribe(&mut self) -> Receiver<TitleChanged> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// The title the window should show, if it isn't showing it already
+    pub fn take_update(&mut self) -> Option<String> {
+        let title = match &self.foreground_process {
+            Some(process) if self.title == DEFAULT_WINDOW_TITLE => process,
+            _ => &self.title,
+        };
+        let title = self.format.render(title, self.cwd.as_deref(), &self.id);
+        let title = badged_title(&title, self.bells, self.activity);
+        if title == self.shown {
+            return None;
+        }
+
+        self.shown.clone_from(&title);
+        Some(title)
+    }
+}
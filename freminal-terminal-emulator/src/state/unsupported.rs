@@ -0,0 +1,101 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::fmt::Write;
+
+/// Most different features remembered. Programs can ask for any OSC number they like, so this
+/// keeps a stream of junk from growing the list forever
+pub const MAX_UNSUPPORTED_FEATURES: usize = 256;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnsupportedFeature {
+    /// What was asked for, such as `CSI > u` or `SGR 53 (Overlined)`
+    pub name: String,
+    /// How many times programs have asked for it
+    pub count: usize,
+}
+
+/// The features programs have asked for that freminal doesn't have
+///
+/// Each one is logged as a warning the first time it is seen and only counted after that, so a
+/// program that sends the same thing every frame doesn't flood the log. Once the list is full,
+/// new features are only logged at debug level and counted together. The list can be shown to
+/// the user as a report of what their programs wanted.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UnsupportedFeatures {
+    /// In the order they were first seen
+    features: Vec<UnsupportedFeature>,
+    /// How many times features were asked for that didn't fit in the list
+    overflowed: usize,
+}
+
+impl UnsupportedFeatures {
+    /// A program asked for `name`. Returns true if this is the first time, and it was added to
+    /// the list
+    pub fn record(&mut self, name: &str) -> bool {
+        if let Some(feature) = self.features.iter_mut().find(|f| f.name == name) {
+            feature.count += 1;
+            debug!("Unsupported again: {name}");
+            return false;
+        }
+
+        if self.features.len() >= MAX_UNSUPPORTED_FEATURES {
+            if self.overflowed == 0 {
+                warn!("Too many unsupported features to list, only counting the rest");
+            }
+            self.overflowed += 1;
+            debug!("Unsupported: {name}");
+            return false;
+        }
+
+        warn!("Unsupported: {name}");
+        self.features.push(UnsupportedFeature {
+            name: name.to_string(),
+            count: 1,
+        });
+
+        true
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// The features seen, oldest first
+    #[must_use]
+    pub fn features(&self) -> &[UnsupportedFeature] {
+        &self.features
+    }
+
+    /// How many times features were asked for after the list was full
+    #[must_use]
+    pub const fn overflowed(&self) -> usize {
+        self.overflowed
+    }
+
+    pub fn clear(&mut self) {
+        self.features.clear();
+        self.overflowed = 0;
+    }
+
+    /// One line per feature, with how often it was asked for
+    #[must_use]
+    pub fn report(&self) -> String {
+        let mut report = self
+            .features
+            .iter()
+            .fold(String::new(), |mut report, feature| {
+                let times = if feature.count == 1 { "time" } else { "times" };
+                let _ = writeln!(report, "{} ({} {times})", feature.name, feature.count);
+                report
+            });
+        if self.overflowed > 0 {
+            let _ = writeln!(report, "Others not listed ({} times)", self.overflowed);
+        }
+
+        report
+    }
+}
@@ -9,10 +9,11 @@ use freminal_common::colors::TerminalColor;
 use freminal_terminal_emulator::{
     ansi::{parse_param_as, FreminalAnsiParser, ParserInner, TerminalOutput},
     ansi_components::{
+        capture::{InvalidSequence, CAPTURE_CONTEXT_BYTES},
         csi::{AnsiCsiParser, AnsiCsiParserState},
         mode::{Mode, SetMode},
         modes::{decckm::Decckm, unknown::UnknownMode},
-        osc::{AnsiOscInternalType, AnsiOscType},
+        osc::{AnsiOscInternalType, AnsiOscType, FtcsMarker, WorkingDirectory},
         sgr::SelectGraphicRendition,
     },
 };
@@ -127,9 +128,31 @@ fn test_invalid_csi() {
     let parsed = output_buffer.push(b"\x1b[-23;H");
     assert!(matches!(parsed[0], TerminalOutput::Invalid));
 
+    // A well formed sequence we don't support isn't invalid
     let mut output_buffer = FreminalAnsiParser::new();
     let parsed = output_buffer.push(b"\x1b[asdf");
-    assert!(matches!(parsed[0], TerminalOutput::Invalid));
+    assert_eq!(parsed[0], TerminalOutput::Unsupported("CSI a".to_string()));
+}
+
+#[test]
+fn test_unsupported_sequences_are_named() {
+    let cases: [(&[u8], &str); 5] = [
+        (b"\x1b[>1;2T", "CSI > T"),
+        (b"\x1b[?5W", "CSI ? W"),
+        (b"\x1b[1$w", "CSI $ w"),
+        (b"\x1b]1337;SetMark\x07", "OSC 1337"),
+        (b"\x1b_Ga=T;AAAA\x1b\\", "APC G"),
+    ];
+
+    for (input, name) in cases {
+        let mut parser = FreminalAnsiParser::new();
+        assert_eq!(
+            parser.push(input),
+            vec![TerminalOutput::Unsupported(name.to_string())],
+            "{}",
+            String::from_utf8_lossy(input)
+        );
+    }
 }
 
 #[test]
@@ -146,15 +169,15 @@ fn test_parsing_unknown_csi() {
     let mut parser = AnsiCsiParser::new();
     parser.push(0x40).unwrap();
 
-    assert_eq!(parser.params, &[]);
-    assert_eq!(parser.intermediates, &[]);
+    assert!(parser.params.is_empty());
+    assert!(parser.intermediates.is_empty());
     assert!(matches!(parser.state, AnsiCsiParserState::Finished(0x40)));
 
     let mut parser = AnsiCsiParser::new();
     parser.push(0x7e).unwrap();
 
-    assert_eq!(parser.params, &[]);
-    assert_eq!(parser.intermediates, &[]);
+    assert!(parser.params.is_empty());
+    assert!(parser.intermediates.is_empty());
     assert!(matches!(parser.state, AnsiCsiParserState::Finished(0x7e)));
 }
 
@@ -548,11 +571,17 @@ fn test_fmt_display_terminal_output() {
     let output = TerminalOutput::Skipped;
     assert_eq!(format!("{output}"), "Skipped");
 
+    let output = TerminalOutput::Unsupported("CSI > u".to_string());
+    assert_eq!(format!("{output}"), "Unsupported(CSI > u)");
+
     let output = TerminalOutput::ApplicationKeypadMode;
     assert_eq!(format!("{output}"), "ApplicationKeypadMode");
 
     let output = TerminalOutput::NormalKeypadMode;
     assert_eq!(format!("{output}"), "NormalKeypadMode");
+
+    let output = TerminalOutput::ReverseIndex;
+    assert_eq!(format!("{output}"), "ReverseIndex");
 }
 
 #[test]
@@ -573,6 +602,14 @@ fn test_osc_response() {
         TerminalOutput::OscResponse(AnsiOscType::Ftcs("test".to_string()))
     );
 
+    // FTCS markers keep their parameters
+    let output = output_buffer.push(b"\x1b]133;D;127\x07");
+    assert_eq!(output.len(), 1);
+    assert_eq!(
+        output[0],
+        TerminalOutput::OscResponse(AnsiOscType::Ftcs("D;127".to_string()))
+    );
+
     // test the background color query
     let output = output_buffer.push(b"\x1b]11;?\x07");
     assert_eq!(output.len(), 1);
@@ -594,6 +631,49 @@ fn test_osc_response() {
     );
 }
 
+#[test]
+fn test_osc_colors() {
+    let mut parser = FreminalAnsiParser::new();
+    let output = parser.push(b"\x1b]4;1;?;2;#00ff00\x1b\\");
+    assert_eq!(
+        output,
+        vec![TerminalOutput::OscResponse(AnsiOscType::Palette(vec![
+            (1, AnsiOscInternalType::Query),
+            (2, AnsiOscInternalType::String("#00ff00".to_string())),
+        ]))]
+    );
+
+    let output = parser.push(b"\x1b]11;rgb:28/28/28\x07");
+    assert_eq!(
+        output,
+        vec![TerminalOutput::OscResponse(
+            AnsiOscType::RequestColorQueryBackground(AnsiOscInternalType::String(
+                "rgb:28/28/28".to_string()
+            ))
+        )]
+    );
+
+    let output = parser.push(b"\x1b]104\x07\x1b]104;1;300\x07\x1b]110\x07\x1b]111\x07");
+    assert_eq!(
+        output,
+        vec![
+            TerminalOutput::OscResponse(AnsiOscType::ResetPalette(Vec::new())),
+            TerminalOutput::OscResponse(AnsiOscType::ResetPalette(vec![1, 300])),
+            TerminalOutput::OscResponse(AnsiOscType::ResetForeground),
+            TerminalOutput::OscResponse(AnsiOscType::ResetBackground),
+        ]
+    );
+
+    // An index without a color, or that isn't a number
+    for bad in [
+        b"\x1b]4;1\x07".as_slice(),
+        b"\x1b]4;red;#ff0000\x07",
+        b"\x1b]104;x\x07",
+    ] {
+        assert_eq!(parser.push(bad), vec![TerminalOutput::Invalid]);
+    }
+}
+
 #[test]
 fn test_delete() {
     let mut output_buffer = FreminalAnsiParser::new();
@@ -665,7 +745,7 @@ fn test_verify_parser_state_internal_is_csi() {
     let mut parser = FreminalAnsiParser::new();
     let output = parser.push(b"\x1b[");
     assert_eq!(output.len(), 0);
-    assert!(matches!(parser.inner, ParserInner::Csi(_)));
+    assert!(matches!(parser.inner(), ParserInner::Csi(_)));
 }
 
 #[test]
@@ -673,7 +753,7 @@ fn test_verify_parser_state_internal_is_osc() {
     let mut parser = FreminalAnsiParser::new();
     let output = parser.push(b"\x1b]");
     assert_eq!(output.len(), 0);
-    assert!(matches!(parser.inner, ParserInner::Osc(_)));
+    assert!(matches!(parser.inner(), ParserInner::Osc(_)));
 }
 
 #[test]
@@ -692,6 +772,23 @@ fn test_normal_keypad_support_mode() {
     assert_eq!(output[0], TerminalOutput::NormalKeypadMode);
 }
 
+#[test]
+fn test_index_escapes() {
+    let mut output_buffer = FreminalAnsiParser::new();
+    let output = output_buffer.push(b"a\x1bDb\x1bEc\x1bM");
+    assert_eq!(
+        output,
+        vec![
+            TerminalOutput::Data(b"a".to_vec()),
+            TerminalOutput::Index,
+            TerminalOutput::Data(b"b".to_vec()),
+            TerminalOutput::NextLine,
+            TerminalOutput::Data(b"c".to_vec()),
+            TerminalOutput::ReverseIndex,
+        ]
+    );
+}
+
 #[test]
 fn test_terminal_output_backspace() {
     let mut output_buffer = FreminalAnsiParser::new();
@@ -711,7 +808,248 @@ fn test_terminal_output_bell() {
 #[test]
 fn test_invalid_inner_escape() {
     let mut output_buffer = FreminalAnsiParser::new();
-    let output = output_buffer.push(b"\x1b_");
+    let output = output_buffer.push(b"\x1b!");
     assert_eq!(output.len(), 0);
-    assert!(matches!(output_buffer.inner, ParserInner::Empty));
+    assert!(matches!(output_buffer.inner(), ParserInner::Empty));
+}
+
+#[test]
+fn test_ftcs_marker() {
+    assert_eq!(FtcsMarker::from("A"), FtcsMarker::PromptStart);
+    assert_eq!(FtcsMarker::from("B"), FtcsMarker::CommandStart);
+    assert_eq!(FtcsMarker::from("C"), FtcsMarker::CommandExecuted);
+    assert_eq!(FtcsMarker::from("D"), FtcsMarker::CommandFinished(None));
+    assert_eq!(
+        FtcsMarker::from("D;2"),
+        FtcsMarker::CommandFinished(Some(2))
+    );
+    assert_eq!(
+        FtcsMarker::from("D;nope"),
+        FtcsMarker::CommandFinished(None)
+    );
+    assert_eq!(
+        FtcsMarker::from("test"),
+        FtcsMarker::Unknown("test".to_string())
+    );
+    assert_eq!(
+        FtcsMarker::CommandFinished(Some(1)).to_string(),
+        "CommandFinished(1)"
+    );
+}
+
+#[test]
+fn test_working_directory_from_url() {
+    assert_eq!(
+        WorkingDirectory::from_url("file://devbox/home/fred/My%20Projects"),
+        Some(WorkingDirectory {
+            host: "devbox".to_string(),
+            path: "/home/fred/My Projects".into(),
+        })
+    );
+    assert_eq!(
+        WorkingDirectory::from_url("file:///tmp/100%"),
+        Some(WorkingDirectory {
+            host: String::new(),
+            path: "/tmp/100%".into(),
+        })
+    );
+    assert_eq!(WorkingDirectory::from_url("file://devbox"), None);
+    assert_eq!(WorkingDirectory::from_url("https://example.com/"), None);
+
+    assert!(WorkingDirectory::from_url("file:///tmp")
+        .unwrap()
+        .is_local());
+    assert!(WorkingDirectory::from_url("file://localhost/tmp")
+        .unwrap()
+        .is_local());
+    assert!(
+        !WorkingDirectory::from_url("file://not-this-machine.invalid/tmp")
+            .unwrap()
+            .is_local()
+    );
+}
+
+#[test]
+fn test_soft_reset() {
+    let mut output_buffer = FreminalAnsiParser::new();
+    assert_eq!(
+        output_buffer.push(b"\x1b[!p"),
+        vec![TerminalOutput::SoftReset]
+    );
+}
+
+#[test]
+fn test_request_status_string() {
+    let mut output_buffer = FreminalAnsiParser::new();
+    let output = output_buffer.push(b"\x1bP$qm\x1b\\a");
+    assert_eq!(
+        output,
+        vec![
+            TerminalOutput::RequestStatusString("m".to_string()),
+            TerminalOutput::Data(b"a".to_vec()),
+        ]
+    );
+    assert!(matches!(output_buffer.inner(), ParserInner::Empty));
+
+    // Other device control strings are swallowed, and named so they can be reported
+    let output = output_buffer.push(b"\x1bP1000p\x1b\\");
+    assert_eq!(
+        output,
+        vec![TerminalOutput::Unsupported("DCS p".to_string())]
+    );
+}
+
+/// Everything `parser` produced from `input` pushed a few bytes at a time, with the skipped
+/// sequences left out
+fn push_in_chunks(parser: &mut FreminalAnsiParser, input: &[u8], chunk: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for output in input.chunks(chunk).flat_map(|bytes| parser.push(bytes)) {
+        match output {
+            TerminalOutput::Data(bytes) => data.extend(bytes),
+            TerminalOutput::Skipped | TerminalOutput::Invalid | TerminalOutput::Unsupported(_) => {}
+            other => panic!("Unexpected output {other:?}"),
+        }
+    }
+
+    data
+}
+
+#[test]
+fn test_unknown_control_strings_are_swallowed() {
+    let payload = "x".repeat(10_000);
+    let sequences = [
+        // APC, PM and SOS, with both terminators
+        format!("\x1b_G{payload}\x1b\\"),
+        format!("\x1b^{payload}\x07"),
+        format!("\x1bX{payload}\x1b\\"),
+        // A DCS we don't handle, such as sixel
+        format!("\x1bPq{payload}\x1b\\"),
+        // An OSC we don't know
+        format!("\x1b]9999;{payload}\x07"),
+        // An OSC with control characters in it, which make it invalid
+        format!("\x1b]0;a\tb\nc{payload}\x1b\\"),
+        // Terminators on their own
+        "\x1b]\x07".to_string(),
+        "\x1b]\x1b\\".to_string(),
+        "\x1b_\x1b\\".to_string(),
+    ];
+
+    for sequence in sequences {
+        let input = format!("before{sequence}after");
+        for chunk in [1, 2, 3, 7, 4096, input.len()] {
+            let mut parser = FreminalAnsiParser::new();
+            assert_eq!(
+                push_in_chunks(&mut parser, input.as_bytes(), chunk),
+                b"beforeafter",
+                "{:?} in chunks of {chunk}",
+                &sequence[..sequence.len().min(20)]
+            );
+            assert!(matches!(parser.inner(), ParserInner::Empty));
+        }
+    }
+}
+
+#[test]
+fn test_apc_is_not_request_status_string() {
+    let mut parser = FreminalAnsiParser::new();
+    assert_eq!(
+        parser.push(b"\x1b_$qm\x1b\\"),
+        vec![TerminalOutput::Unsupported("APC $ q".to_string())]
+    );
+}
+
+#[test]
+fn test_sequence_length_limit() {
+    let payload = "m".repeat(2000);
+    for (sequence, expected) in [
+        // Under the limit they're handled as usual
+        (
+            "\x1bP$qm\x1b\\".to_string(),
+            TerminalOutput::RequestStatusString("m".to_string()),
+        ),
+        (
+            format!("\x1b]0;{}\x07", &payload[..1000]),
+            TerminalOutput::OscResponse(AnsiOscType::SetTitleBar(payload[..1000].to_string())),
+        ),
+        // Over it they're dropped and reported as invalid
+        (format!("\x1bP$q{payload}\x1b\\"), TerminalOutput::Invalid),
+        (format!("\x1b]0;{payload}\x07"), TerminalOutput::Invalid),
+        (format!("\x1b]0;{payload}\x1b\\"), TerminalOutput::Invalid),
+        (format!("\x1b_{payload}\x07"), TerminalOutput::Invalid),
+    ] {
+        for chunk in [1, 100, sequence.len()] {
+            let mut parser = FreminalAnsiParser::new();
+            parser.set_max_sequence_length(1024);
+
+            let input = format!("a{sequence}b");
+            let output: Vec<TerminalOutput> = input
+                .as_bytes()
+                .chunks(chunk)
+                .flat_map(|bytes| parser.push(bytes))
+                .collect();
+            let context = format!(
+                "{:?} in chunks of {chunk}",
+                &sequence[..sequence.len().min(10)]
+            );
+            assert_eq!(output.len(), 3, "{context}");
+            assert_eq!(output[0], TerminalOutput::Data(b"a".to_vec()), "{context}");
+            assert_eq!(output[1], expected, "{context}");
+            assert_eq!(output[2], TerminalOutput::Data(b"b".to_vec()), "{context}");
+        }
+    }
+}
+
+#[test]
+fn test_sequence_length_limit_is_captured() {
+    let mut parser = FreminalAnsiParser::new();
+    parser.set_max_sequence_length(16);
+    parser.capture_invalid_sequences();
+    parser.push(format!("\x1bPq{}\x1b\\", "#".repeat(100)).as_bytes());
+
+    let invalid = parser.take_invalid_sequences();
+    assert_eq!(invalid.len(), 1);
+    assert!(invalid[0].sequence.starts_with(b"\x1bPq#"));
+}
+
+#[test]
+fn test_capture_invalid_sequences() {
+    let mut parser = FreminalAnsiParser::new();
+    parser.push(b"\x1b[8J");
+    assert!(parser.take_invalid_sequences().is_empty());
+
+    let mut parser = FreminalAnsiParser::new();
+    parser.capture_invalid_sequences();
+    parser.push(b"hello\x1b[1mok\x1b[8Jbye");
+    assert_eq!(
+        parser.take_invalid_sequences(),
+        vec![InvalidSequence {
+            context: b"hello\x1b[1mok".to_vec(),
+            sequence: b"\x1b[8J".to_vec(),
+        }]
+    );
+    assert!(parser.take_invalid_sequences().is_empty());
+
+    // Split across reads
+    parser.push(b"\x1b[8");
+    assert!(parser.take_invalid_sequences().is_empty());
+    parser.push(b"J");
+    let invalid = parser.take_invalid_sequences();
+    assert_eq!(invalid.len(), 1);
+    assert_eq!(invalid[0].sequence, b"\x1b[8J");
+    assert!(invalid[0].context.ends_with(b"bye"));
+    assert_eq!(
+        invalid[0].to_bytes(),
+        [invalid[0].context.clone(), b"\x1b[8J".to_vec()].concat()
+    );
+}
+
+#[test]
+fn test_capture_context_is_limited() {
+    let mut parser = FreminalAnsiParser::new();
+    parser.capture_invalid_sequences();
+    parser.push(&[b'a'; CAPTURE_CONTEXT_BYTES * 2]);
+    parser.push(b"\x1b[8J");
+
+    let invalid = parser.take_invalid_sequences();
+    assert_eq!(invalid[0].context, vec![b'a'; CAPTURE_CONTEXT_BYTES]);
 }
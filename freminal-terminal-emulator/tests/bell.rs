@@ -0,0 +1,88 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::state::{
+    bell::{badged_title, BellState},
+    internal::TerminalState,
+};
+use test_log::test;
+
+#[test]
+fn test_bell_ignored_when_focused() {
+    let mut bell = BellState::new();
+    bell.ring(true);
+
+    assert_eq!(bell.unfocused_bells(), 0);
+    assert!(!bell.take_attention_request());
+}
+
+#[test]
+fn test_bell_requests_attention_once() {
+    let mut bell = BellState::new();
+    bell.ring(false);
+    bell.ring(false);
+    bell.ring(false);
+
+    assert_eq!(bell.unfocused_bells(), 3);
+    assert!(bell.take_attention_request());
+    assert!(!bell.take_attention_request());
+
+    bell.ring(false);
+    assert!(!bell.take_attention_request());
+
+    bell.clear();
+    assert_eq!(bell.unfocused_bells(), 0);
+
+    bell.ring(false);
+    assert!(bell.take_attention_request());
+}
+
+#[test]
+fn test_output_counts_as_activity() {
+    let mut bell = BellState::new();
+    bell.output(true);
+    assert!(!bell.unfocused_activity());
+    assert!(!bell.take_attention_request());
+
+    bell.output(false);
+    bell.output(false);
+    assert!(bell.unfocused_activity());
+    assert!(bell.take_attention_request());
+
+    // A bell after output doesn't ask again
+    bell.ring(false);
+    assert_eq!(bell.unfocused_bells(), 1);
+    assert!(!bell.take_attention_request());
+
+    bell.clear();
+    assert!(!bell.unfocused_activity());
+    assert_eq!(bell.unfocused_bells(), 0);
+}
+
+#[test]
+fn test_unfocused_output_is_activity() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.handle_incoming_data(b"focused");
+    assert!(!terminal_state.bell.unfocused_activity());
+
+    terminal_state.set_window_focused(false);
+    terminal_state.handle_incoming_data(b"away\x07");
+    assert!(terminal_state.bell.unfocused_activity());
+    assert_eq!(terminal_state.bell.unfocused_bells(), 1);
+    assert!(terminal_state.bell.take_attention_request());
+
+    terminal_state.set_window_focused(true);
+    assert!(!terminal_state.bell.unfocused_activity());
+    assert_eq!(terminal_state.bell.unfocused_bells(), 0);
+}
+
+#[test]
+fn test_badged_title() {
+    assert_eq!(badged_title("vim", 0, false), "vim");
+    assert_eq!(badged_title("vim", 2, false), "(2) vim");
+    assert_eq!(badged_title("vim", 0, true), "(*) vim");
+    assert_eq!(badged_title("vim", 2, true), "(2) vim");
+}
@@ -0,0 +1,116 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::{
+    format_tracker::FormatTag,
+    state::{
+        bidi::{embedding_levels, reorder_lines, visual_order, BidiClass},
+        fonts::FontWeight,
+        term_char::TChar,
+    },
+};
+use test_log::test;
+
+fn to_string(data: &[TChar]) -> String {
+    data.iter().map(TChar::visible_text).collect()
+}
+
+fn bold(start: usize, end: usize) -> FormatTag {
+    FormatTag {
+        start,
+        end,
+        font_weight: FontWeight::Bold,
+        ..FormatTag::default()
+    }
+}
+
+/// Reorder `data` as a single line
+fn reorder_whole(data: &mut [TChar], tags: &mut Vec<FormatTag>) {
+    let line = 0..data.len();
+    reorder_lines(data, tags, std::slice::from_ref(&line));
+}
+
+#[test]
+fn test_bidi_classes() {
+    assert_eq!(BidiClass::of('a'), BidiClass::L);
+    assert_eq!(BidiClass::of('ש'), BidiClass::R);
+    assert_eq!(BidiClass::of('س'), BidiClass::AL);
+    assert_eq!(BidiClass::of('1'), BidiClass::EN);
+    assert_eq!(BidiClass::of('\u{0661}'), BidiClass::AN);
+    assert_eq!(BidiClass::of(' '), BidiClass::WS);
+    assert_eq!(BidiClass::of('!'), BidiClass::ON);
+}
+
+#[test]
+fn test_left_to_right_lines_are_left_alone() {
+    let line = TChar::from_string("hello, world 123").unwrap();
+    assert_eq!(embedding_levels(&line), None);
+}
+
+#[test]
+fn test_right_to_left_paragraph() {
+    let line = TChar::from_string("שלום עולם").unwrap();
+    let levels = embedding_levels(&line).unwrap();
+    assert!(levels.iter().all(|level| *level == 1));
+    assert_eq!(
+        visual_order(&levels),
+        (0..line.len()).rev().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_right_to_left_run_in_left_to_right_paragraph() {
+    let mut data = TChar::from_string("say שלום now\n").unwrap();
+    let mut tags = vec![];
+    reorder_whole(&mut data, &mut tags);
+    assert_eq!(to_string(&data), "say םולש now\n");
+}
+
+#[test]
+fn test_numbers_keep_their_order() {
+    // Numbers inside right to left text still read left to right
+    let mut data = TChar::from_string("שלום 123 עולם").unwrap();
+    let mut tags = vec![];
+    reorder_whole(&mut data, &mut tags);
+    assert_eq!(to_string(&data), "םלוע 123 םולש");
+}
+
+#[test]
+fn test_brackets_are_mirrored() {
+    let mut data = TChar::from_string("שלום (עולם)").unwrap();
+    let mut tags = vec![];
+    reorder_whole(&mut data, &mut tags);
+    assert_eq!(to_string(&data), "(םלוע) םולש");
+}
+
+#[test]
+fn test_each_line_is_reordered_on_its_own() {
+    let mut data = TChar::from_string("אב\nabc\nגד").unwrap();
+    let mut tags = vec![];
+    reorder_lines(&mut data, &mut tags, &[0..3, 3..7, 7..9]);
+    assert_eq!(to_string(&data), "בא\nabc\nדג");
+}
+
+#[test]
+fn test_formatting_follows_the_characters() {
+    // The first word is bold, and ends up on the right
+    let mut data = TChar::from_string("אב גד").unwrap();
+    let mut tags = vec![
+        bold(0, 2),
+        FormatTag {
+            start: 2,
+            end: usize::MAX,
+            ..FormatTag::default()
+        },
+    ];
+    reorder_whole(&mut data, &mut tags);
+
+    assert_eq!(to_string(&data), "דג בא");
+    assert_eq!(tags.len(), 2);
+    assert_eq!((tags[0].start, tags[0].end), (0, 3));
+    assert_eq!(tags[0].font_weight, FontWeight::Normal);
+    assert_eq!((tags[1].start, tags[1].end), (3, 5));
+    assert_eq!(tags[1].font_weight, FontWeight::Bold);
+}
@@ -7,13 +7,17 @@ use test_log::test;
 // https://opensource.org/licenses/MIT.
 #[cfg(test)]
 use anyhow::Result;
+use freminal_common::char_width::{UnicodeVersion, WidthProvider};
 use freminal_terminal_emulator::state::{
     buffer::{TerminalBufferHolder, TerminalBufferInsertResponse},
     cursor::CursorPos,
     internal::BufferType,
-    term_char::TChar,
+    term_char::{display_vec_tchar_as_string, TChar},
 };
 
+mod common;
+use common::TinyRng;
+
 /// Calculate the indexes of the start and end of each line in the buffer given an input width.
 /// Ranges do not include newlines. If a newline appears past the width, it does not result in an
 /// extra line
@@ -84,17 +88,19 @@ fn test_insert_utf8_data() {
     let response = buffer
         .insert_data(&response.new_cursor_pos, bytes_utf8)
         .unwrap();
-    assert_eq!(response.written_range, 4..5);
-    assert_eq!(response.insertion_range, 4..5);
+    // The emoji is double width, so it takes up two cells
+    assert_eq!(response.written_range, 4..6);
+    assert_eq!(response.insertion_range, 4..6);
     let expected = vec![
         TChar::new_from_single_char(b'a'),
         TChar::new_from_single_char(b's'),
         TChar::new_from_single_char(b'd'),
         TChar::new_from_single_char(b'f'),
         TChar::new_from_many_chars(bytes_utf8.to_vec()).unwrap(),
+        TChar::WideContinuation,
         TChar::NewLine,
     ];
-    assert_eq!(response.new_cursor_pos, CursorPos { x: 5, y: 0 });
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 6, y: 0 });
 
     // verify the buffer is correct
     assert_eq!(buffer.data(true).visible, expected);
@@ -999,6 +1005,45 @@ fn test_insert_lines() {
     assert_eq!(response.inserted_range, 11..12);
 }
 
+#[test]
+fn test_delete_lines() {
+    let mut canvas = TerminalBufferHolder::new(5, 4, BufferType::Primary);
+
+    // Nothing to delete on an empty canvas
+    let response = canvas.delete_lines(&CursorPos { x: 0, y: 0 }, 2);
+    assert!(response.deleted_range.is_empty());
+    assert!(response.inserted_range.is_empty());
+
+    // Lines below are pulled up, and blank lines added at the end so the screen keeps its height
+    canvas
+        .insert_data(&CursorPos { x: 0, y: 0 }, b"a\nb\nc")
+        .unwrap();
+    let response = canvas.delete_lines(&CursorPos { x: 0, y: 0 }, 1);
+    assert_eq!(
+        display_vec_tchar_as_string(canvas.buf.make_contiguous()),
+        "b\nc\n\n"
+    );
+    assert_eq!(response.deleted_range, 0..2);
+    assert_eq!(response.inserted_range, 6..7);
+
+    // Only the scrolling region moves. Lines wrapped across it stay where they are on screen
+    let mut canvas = TerminalBufferHolder::new(5, 4, BufferType::Primary);
+    canvas
+        .insert_data(&CursorPos { x: 0, y: 0 }, b"a\n0123456789xy")
+        .unwrap();
+    assert!(canvas.set_top_and_bottom_margins(2, 3));
+    canvas.delete_lines(&CursorPos { x: 0, y: 1 }, 2);
+    assert_eq!(
+        display_vec_tchar_as_string(canvas.buf.make_contiguous()),
+        "a\n\n\nxy\n"
+    );
+
+    // Outside the region nothing happens
+    let response = canvas.delete_lines(&CursorPos { x: 0, y: 0 }, 1);
+    assert!(response.deleted_range.is_empty());
+    assert!(!canvas.set_top_and_bottom_margins(4, 9));
+}
+
 #[test]
 fn test_clear_line() {
     let mut canvas = TerminalBufferHolder::new(5, 5, BufferType::Primary);
@@ -1317,7 +1362,7 @@ fn test_visible_line_ranges_parsing() {
         TChar::NewLine,     // 13 5
         TChar::NewLine,     // 14 6
     ];
-    buf.buf = data;
+    buf.buf = data.into();
     buf.line_ranges_to_visible_line_ranges();
 
     let expected = [5..10, 10..11, 12..12, 13..13, 14..14];
@@ -1725,3 +1770,222 @@ fn test_line_ranges_that_do_overlap_subsequent_range() {
         ]
     );
 }
+
+#[test]
+fn test_insert_data_without_wrap() {
+    let mut buffer = TerminalBufferHolder::new(10, 10, BufferType::Primary);
+    let response = buffer
+        .insert_data_without_wrap(&CursorPos { x: 6, y: 0 }, b"abcdefg")
+        .unwrap();
+
+    // The overflow overwrites the last column, so only the final character survives
+    assert_eq!(
+        display_vec_tchar_as_string(&buffer.data(true).visible),
+        "      abcg\n"
+    );
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 9, y: 0 });
+
+    // Writing again from the last column keeps overwriting it
+    let response = buffer
+        .insert_data_without_wrap(&response.new_cursor_pos, b"xy")
+        .unwrap();
+    assert_eq!(
+        display_vec_tchar_as_string(&buffer.data(true).visible),
+        "      abcy\n"
+    );
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 9, y: 0 });
+}
+
+#[test]
+fn test_wide_characters_take_two_cells() {
+    let mut buffer = TerminalBufferHolder::new(5, 10, BufferType::Primary);
+    let response = buffer
+        .insert_data(&CursorPos { x: 0, y: 0 }, "a日本".as_bytes())
+        .unwrap();
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 5, y: 0 });
+
+    // A wide character can't be split across rows, so it starts the next one instead
+    let mut buffer = TerminalBufferHolder::new(5, 10, BufferType::Primary);
+    let response = buffer
+        .insert_data(&CursorPos { x: 0, y: 0 }, "abcd日".as_bytes())
+        .unwrap();
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 2, y: 1 });
+    assert_eq!(
+        display_vec_tchar_as_string(&buffer.data(true).visible),
+        "abcd 日\n"
+    );
+    assert_eq!(buffer.visible_line_chars(1).unwrap().1[..2], ['日', '日']);
+}
+
+#[test]
+fn test_overwriting_half_a_wide_character() {
+    let mut buffer = TerminalBufferHolder::new(10, 10, BufferType::Primary);
+    buffer
+        .insert_data(&CursorPos { x: 0, y: 0 }, "日本".as_bytes())
+        .unwrap();
+    buffer.insert_data(&CursorPos { x: 1, y: 0 }, b"x").unwrap();
+
+    assert_eq!(
+        display_vec_tchar_as_string(&buffer.data(true).visible),
+        " x本\n"
+    );
+}
+
+#[test]
+fn test_unicode_8_emoji_are_narrow() {
+    let mut buffer = TerminalBufferHolder::new(10, 10, BufferType::Primary);
+    buffer.set_width_provider(WidthProvider::new(UnicodeVersion::Unicode8, false));
+    let response = buffer
+        .insert_data(&CursorPos { x: 0, y: 0 }, "👍".as_bytes())
+        .unwrap();
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 1, y: 0 });
+
+    // CJK was wide long before Unicode 9
+    let response = buffer
+        .insert_data(&response.new_cursor_pos, "日".as_bytes())
+        .unwrap();
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 3, y: 0 });
+}
+
+#[test]
+fn test_ambiguous_wide_characters() {
+    let mut buffer = TerminalBufferHolder::new(10, 10, BufferType::Primary);
+    let response = buffer
+        .insert_data(&CursorPos { x: 0, y: 0 }, "──".as_bytes())
+        .unwrap();
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 2, y: 0 });
+
+    buffer.set_width_provider(WidthProvider::new(UnicodeVersion::Unicode9, true));
+    let response = buffer
+        .insert_data(&response.new_cursor_pos, "──".as_bytes())
+        .unwrap();
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 6, y: 0 });
+}
+
+#[test]
+fn test_scroll_visible_into_scrollback() {
+    for data in [&b"0123456789\nxyzw"[..], b"a\nb\nc\nd\ne\nf\n", b"a"] {
+        let mut canvas = TerminalBufferHolder::new(5, 5, BufferType::Primary);
+        canvas.insert_data(&CursorPos { x: 0, y: 0 }, data).unwrap();
+        let old_end = canvas.buf.len();
+
+        let response = canvas.scroll_visible_into_scrollback().unwrap();
+        assert_eq!(response, old_end..usize::MAX);
+
+        // Everything that was on screen is now in the scrollback, and nothing else was added
+        let after = canvas.data(true);
+        let scrollback = &after.scrollback;
+        assert!(
+            scrollback
+                .iter()
+                .take(old_end)
+                .eq(canvas.buf.range(..old_end)),
+            "{data:?}: {scrollback:?}"
+        );
+        assert!(scrollback.len() <= old_end + 1, "{data:?}: {scrollback:?}");
+        assert!(after.visible.iter().all(|c| *c == TChar::NewLine));
+        assert_eq!(after.visible.len(), 5);
+    }
+}
+
+#[test]
+fn test_scroll_visible_into_scrollback_alternate() {
+    let mut canvas = TerminalBufferHolder::new(5, 5, BufferType::Alternate);
+    canvas
+        .insert_data(&CursorPos { x: 0, y: 0 }, b"abc\ndef")
+        .unwrap();
+    canvas.scroll_visible_into_scrollback().unwrap();
+    assert!(canvas.data(true).scrollback.is_empty());
+    assert_eq!(canvas.buf.len(), 8);
+}
+
+#[test]
+fn test_zero_size_is_one_cell() {
+    // A minimized window can report no rows or columns
+    let mut canvas = TerminalBufferHolder::new(0, 0, BufferType::Primary);
+    assert_eq!(canvas.get_win_size(), (1, 1));
+
+    let response = canvas.insert_data(&CursorPos::default(), b"ab").unwrap();
+    assert_eq!(response.new_cursor_pos, CursorPos { x: 1, y: 0 });
+    assert_eq!(
+        canvas.data(false).visible,
+        vec![TChar::Ascii(b'b'), TChar::NewLine]
+    );
+
+    canvas.set_win_size(0, 3, &response.new_cursor_pos);
+    assert_eq!(canvas.get_win_size(), (1, 3));
+    canvas.scroll_up(&1);
+    canvas.scroll_to_top();
+    canvas.set_win_size(4, 0, &response.new_cursor_pos);
+    assert_eq!(canvas.get_win_size(), (4, 1));
+}
+
+#[test]
+fn test_tiny_geometries_dont_panic() {
+    const DATA: [&[u8]; 6] = [
+        b"a",
+        b"abc",
+        b"\n",
+        b"ab\ncd\n",
+        "日本".as_bytes(),
+        b"\n\nxyz",
+    ];
+
+    let mut rng = TinyRng(0x2545_f491_4f6c_dd1d);
+    for buffer_type in [BufferType::Primary, BufferType::Alternate] {
+        for width in 0..4 {
+            for height in 0..4 {
+                let mut canvas = TerminalBufferHolder::new(width, height, buffer_type);
+                for _ in 0..2000 {
+                    let pos = CursorPos {
+                        x: rng.next(4),
+                        y: rng.next(4),
+                    };
+                    let n = rng.next(4);
+                    match rng.next(24) {
+                        0 => drop(canvas.insert_data(&pos, DATA[rng.next(DATA.len())])),
+                        1 => drop(canvas.insert_data_without_wrap(&pos, b"xy")),
+                        2 => drop(canvas.insert_spaces(&pos, n)),
+                        3 => drop(canvas.insert_lines(&pos, n)),
+                        4 => drop(canvas.delete_lines(&pos, n)),
+                        5 => drop(canvas.clear_backwards(&pos)),
+                        6 => drop(canvas.clear_forwards(&pos)),
+                        7 => drop(canvas.clear_line_forwards(&pos)),
+                        8 => drop(canvas.clear_line(&pos)),
+                        9 => drop(canvas.clear_line_backwards(&pos)),
+                        10 => drop(canvas.clear_visible()),
+                        11 => drop(canvas.scroll_visible_into_scrollback()),
+                        12 => drop(canvas.delete_forwards(&pos, n)),
+                        13 => drop(canvas.erase_forwards(&pos, n)),
+                        14 => drop(canvas.clip_lines_for_primary_buffer()),
+                        15 => drop(canvas.clip_lines_for_alternate_buffer()),
+                        16 => drop(canvas.set_win_size(rng.next(3), rng.next(3), &pos)),
+                        17 => canvas.scroll_up(&n),
+                        18 => canvas.scroll_down(&n),
+                        19 => canvas.scroll_to_top(),
+                        20 => drop(canvas.set_top_and_bottom_margins(n, rng.next(4))),
+                        21 => canvas.set_no_wrap_view(n % 2 == 0),
+                        22 => canvas.scroll_columns(isize::try_from(n).unwrap() - 2),
+                        _ => canvas.clear_all(),
+                    }
+                    canvas.line_ranges_to_visible_line_ranges();
+                    let len = canvas.get_raw_buffer().len();
+                    assert!(canvas
+                        .get_visible_line_ranges()
+                        .iter()
+                        .all(|row| row.start <= row.end && row.end <= len));
+
+                    let _ = canvas.data_for_gui();
+                    let _ = canvas.data(true);
+                    let _ = canvas.show_cursor(&pos);
+                    let _ = canvas.viewport_rows();
+                    let _ = canvas.viewport_cursor_pos(&pos);
+                    let _ = canvas.viewport_line_chars_at(n);
+                    let _ = canvas.visible_pos_to_buf_pos_clamped(&pos);
+                    let _ = canvas.buf_range_to_visible_rows(&(0..n));
+                    let _ = canvas.get_scrolling_region();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,19 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Helpers shared by the integration tests
+
+/// A small xorshift generator, so fuzz tests are the same every run
+pub struct TinyRng(pub u64);
+
+impl TinyRng {
+    /// A number below `below`
+    pub fn next(&mut self, below: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        usize::try_from(self.0 % below as u64).unwrap()
+    }
+}
@@ -0,0 +1,38 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::{
+    ansi_components::capture::InvalidSequence, io::corpus::FuzzCorpus,
+};
+use test_log::test;
+
+#[test]
+fn test_fuzz_corpus() {
+    let dir = tempfile::tempdir().unwrap();
+    let directory = dir.path().join("corpus");
+    let mut corpus = FuzzCorpus::new(&directory).unwrap();
+
+    let invalid = InvalidSequence {
+        context: b"ls\r\n".to_vec(),
+        sequence: b"\x1b[8J".to_vec(),
+    };
+    let path = corpus.save(&invalid).unwrap().unwrap();
+    assert!(path.starts_with(&directory));
+    assert_eq!(std::fs::read(&path).unwrap(), b"ls\r\n\x1b[8J");
+
+    // The same sequence is only saved once, whatever came before it
+    let repeat = InvalidSequence {
+        context: b"other".to_vec(),
+        sequence: b"\x1b[8J".to_vec(),
+    };
+    assert_eq!(corpus.save(&repeat).unwrap(), None);
+
+    let other = InvalidSequence {
+        context: vec![],
+        sequence: b"\x1b[asdf".to_vec(),
+    };
+    assert!(corpus.save(&other).unwrap().is_some());
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 2);
+}
@@ -9,7 +9,10 @@ use freminal_common::{
     colors::TerminalColor, cursor::CursorVisualStyle, window_manipulation::WindowManipulation,
 };
 use freminal_terminal_emulator::{
-    ansi::{split_params_into_semicolon_delimited_usize, ParserInner, TerminalOutput},
+    ansi::{
+        split_params_into_semicolon_delimited_usize, FreminalAnsiParser, ParserInner,
+        TerminalOutput,
+    },
     ansi_components::{
         csi_commands::{
             cha::ansi_parser_inner_csi_finished_set_cursor_position_g,
@@ -28,8 +31,11 @@ use freminal_terminal_emulator::{
             el::ansi_parser_inner_csi_finished_set_position_k,
             ict::ansi_parser_inner_csi_finished_ich,
             il::ansi_parser_inner_csi_finished_set_position_l,
+            kitty_keyboard::{ansi_parser_inner_csi_finished_kitty_keyboard, KittyKeyboardRequest},
             send_device_attributes::ansi_parser_inner_csi_finished_send_da,
             sgr::{ansi_parser_inner_csi_finished_sgr_ansi, handle_custom_color},
+            xtmodkeys::ansi_parser_inner_csi_finished_xtmodkeys,
+            xtsave::ansi_parser_inner_csi_finished_xtsave,
         },
         mode::Mode,
         modes::decckm::Decckm,
@@ -445,6 +451,31 @@ fn test_sgr() {
             TerminalColor::Custom(238, 238, 238)
         ))]
     );
+
+    // The first 16 of the 256 colors are the palette, so they follow the theme
+    for (params, sgr) in [
+        (
+            b"38;5;1".as_slice(),
+            SelectGraphicRendition::Foreground(TerminalColor::Red),
+        ),
+        (
+            b"48;5;12",
+            SelectGraphicRendition::Background(TerminalColor::BrightBlue),
+        ),
+        (
+            b"58;5;15",
+            SelectGraphicRendition::UnderlineColor(TerminalColor::BrightWhite),
+        ),
+        (
+            b"38;5;16",
+            SelectGraphicRendition::Foreground(TerminalColor::Custom(0, 0, 0)),
+        ),
+    ] {
+        let mut output = Vec::new();
+        let result = ansi_parser_inner_csi_finished_sgr_ansi(params, &mut output);
+        assert!(matches!(result, Ok(Some(ParserInner::Empty))));
+        assert_eq!(output, vec![TerminalOutput::Sgr(sgr)]);
+    }
 }
 
 #[test]
@@ -690,9 +721,17 @@ fn test_decscusr() {
     assert!(result.is_ok());
     assert_eq!(
         output,
-        vec![TerminalOutput::CursorVisualStyle(
-            CursorVisualStyle::BlockCursorBlink
-        )],
+        vec![TerminalOutput::DefaultCursorVisualStyle],
+        "Failed for {output:?}"
+    );
+
+    let params = b"";
+    let mut output = vec![];
+    let result = ansi_parser_inner_csi_finished_set_position_q(params, &mut output);
+    assert!(result.is_ok());
+    assert_eq!(
+        output,
+        vec![TerminalOutput::DefaultCursorVisualStyle],
         "Failed for {output:?}"
     );
 
@@ -925,6 +964,18 @@ fn test_decstbm() {
         }]
     );
 
+    let params = b"5";
+    let mut output = Vec::new();
+    let result = ansi_parser_inner_csi_set_top_and_bottom_margins(params, &mut output);
+    assert!(result.is_ok(), "Failed for {result:?}");
+    assert_eq!(
+        output,
+        vec![TerminalOutput::SetTopAndBottomMargins {
+            top_margin: 5,
+            bottom_margin: usize::MAX
+        }]
+    );
+
     let params = b"2;2";
     let mut output = Vec::new();
     let result = ansi_parser_inner_csi_set_top_and_bottom_margins(params, &mut output);
@@ -962,6 +1013,121 @@ fn test_decstbm() {
     assert_eq!(output, vec![TerminalOutput::Invalid]);
 }
 
+#[test]
+fn test_xtsave() {
+    let mut output = Vec::new();
+    let result = ansi_parser_inner_csi_finished_xtsave(b"?1004;2004", b's', &mut output);
+    assert!(result.is_ok(), "Failed for {result:?}");
+    assert_eq!(
+        output,
+        vec![TerminalOutput::SavePrivateModes(vec![1004, 2004])]
+    );
+
+    let mut output = Vec::new();
+    let result = ansi_parser_inner_csi_finished_xtsave(b"?1000", b'r', &mut output);
+    assert!(result.is_ok(), "Failed for {result:?}");
+    assert_eq!(
+        output,
+        vec![TerminalOutput::RestorePrivateModes(vec![1000])]
+    );
+
+    let mut output = Vec::new();
+    let result = ansi_parser_inner_csi_finished_xtsave(b"?1004;x", b's', &mut output);
+    assert!(result.is_err());
+    assert_eq!(output, vec![TerminalOutput::Invalid]);
+
+    // CSI Pt ; Pb r is still DECSTBM
+    let mut parser = FreminalAnsiParser::new();
+    assert_eq!(
+        parser.push(b"\x1b[2;3r\x1b[?25s"),
+        vec![
+            TerminalOutput::SetTopAndBottomMargins {
+                top_margin: 2,
+                bottom_margin: 3
+            },
+            TerminalOutput::SavePrivateModes(vec![25]),
+        ]
+    );
+}
+
+#[test]
+fn test_xtmodkeys() {
+    let cases: [(&[u8], u8, TerminalOutput); 6] = [
+        (b">4;2", b'm', TerminalOutput::ModifyOtherKeys(2)),
+        (b">4;1", b'm', TerminalOutput::ModifyOtherKeys(1)),
+        // Leaving the value out resets it, and so does leaving everything out
+        (b">4", b'm', TerminalOutput::ModifyOtherKeys(0)),
+        (b">", b'm', TerminalOutput::ModifyOtherKeys(0)),
+        (b">4", b'n', TerminalOutput::ModifyOtherKeys(0)),
+        (
+            b">1;2",
+            b'm',
+            TerminalOutput::Unsupported("XTMODKEYS for resource 1".to_string()),
+        ),
+    ];
+    for (params, terminator, expected) in cases {
+        let mut output = Vec::new();
+        let result = ansi_parser_inner_csi_finished_xtmodkeys(params, terminator, &mut output);
+        assert!(result.is_ok(), "Failed for {result:?}");
+        assert_eq!(output, vec![expected]);
+    }
+
+    for params in [b">4;3".as_slice(), b">4;x"] {
+        let mut output = Vec::new();
+        let result = ansi_parser_inner_csi_finished_xtmodkeys(params, b'm', &mut output);
+        assert!(result.is_err());
+        assert_eq!(output, vec![TerminalOutput::Invalid]);
+    }
+
+    // SGR and the cursor position report are still themselves
+    let mut parser = FreminalAnsiParser::new();
+    assert_eq!(
+        parser.push(b"\x1b[>4;2m\x1b[1m\x1b[>4n\x1b[6n"),
+        vec![
+            TerminalOutput::ModifyOtherKeys(2),
+            TerminalOutput::Sgr(SelectGraphicRendition::Bold),
+            TerminalOutput::ModifyOtherKeys(0),
+            TerminalOutput::CursorReport,
+        ]
+    );
+}
+
+#[test]
+fn test_kitty_keyboard() {
+    let cases: [(&[u8], KittyKeyboardRequest); 7] = [
+        (b"?", KittyKeyboardRequest::Query),
+        (b">1", KittyKeyboardRequest::Push(1)),
+        (b">", KittyKeyboardRequest::Push(0)),
+        (b"<", KittyKeyboardRequest::Pop(1)),
+        (b"<3", KittyKeyboardRequest::Pop(3)),
+        (b"=1", KittyKeyboardRequest::Set { flags: 1, mode: 1 }),
+        (b"=5;3", KittyKeyboardRequest::Set { flags: 5, mode: 3 }),
+    ];
+    for (params, expected) in cases {
+        let mut output = Vec::new();
+        let result = ansi_parser_inner_csi_finished_kitty_keyboard(params, &mut output);
+        assert!(result.is_ok(), "Failed for {result:?}");
+        assert_eq!(output, vec![TerminalOutput::KittyKeyboard(expected)]);
+    }
+
+    for params in [b"?1".as_slice(), b">32", b"=1;4", b"<x"] {
+        let mut output = Vec::new();
+        let result = ansi_parser_inner_csi_finished_kitty_keyboard(params, &mut output);
+        assert!(result.is_err(), "{params:?}");
+        assert_eq!(output, vec![TerminalOutput::Invalid]);
+    }
+
+    // CSI u on its own isn't part of the protocol
+    let mut parser = FreminalAnsiParser::new();
+    assert_eq!(
+        parser.push(b"\x1b[>1u\x1b[u"),
+        vec![
+            TerminalOutput::KittyKeyboard(KittyKeyboardRequest::Push(1)),
+            TerminalOutput::Unsupported("CSI u".to_string()),
+        ]
+    );
+}
+
 #[test]
 fn test_request_device_attributes() {
     let params = b"";
@@ -84,3 +84,26 @@ fn test_cursor_state_with() {
         }
     );
 }
+
+#[test]
+fn test_cursor_state_sgr_params() {
+    let mut cursor = CursorState::default();
+    assert_eq!(cursor.sgr_params(), "0");
+
+    cursor.font_weight = FontWeight::Bold;
+    cursor.font_decorations = vec![FontDecorations::Underline, FontDecorations::Italic];
+    cursor.colors.set_color(TerminalColor::Red);
+    cursor
+        .colors
+        .set_background_color(TerminalColor::BrightBlue);
+    assert_eq!(cursor.sgr_params(), "0;1;3;4;31;104");
+
+    cursor.colors.set_color(TerminalColor::Custom(1, 2, 3));
+    cursor
+        .colors
+        .set_background_color(TerminalColor::DefaultBackground);
+    cursor
+        .colors
+        .set_underline_color(TerminalColor::BrightGreen);
+    assert_eq!(cursor.sgr_params(), "0;1;3;4;38;2;1;2;3;58;5;10");
+}
@@ -0,0 +1,138 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::{Duration, Instant};
+
+use freminal_terminal_emulator::{
+    interface::TerminalInput,
+    state::{
+        echo_prediction::{EchoPrediction, PREDICTION_TIMEOUT},
+        internal::TerminalState,
+    },
+};
+use test_log::test;
+
+fn type_text(prediction: &mut EchoPrediction, text: &str, now: Instant) {
+    for c in text.bytes() {
+        prediction.typed(&TerminalInput::Ascii(c), now);
+    }
+}
+
+#[test]
+fn test_disabled_prediction() {
+    let now = Instant::now();
+    let mut prediction = EchoPrediction::default();
+    type_text(&mut prediction, "ls", now);
+    assert!(prediction.is_empty());
+}
+
+#[test]
+fn test_echo_confirms_prediction() {
+    let now = Instant::now();
+    let mut prediction = EchoPrediction::new(true);
+    type_text(&mut prediction, "ls -l", now);
+    assert!(!prediction.is_empty());
+
+    // Nothing is shown until the shell has echoed something
+    assert_eq!(prediction.predicted_text(), "");
+    prediction.typed(&TerminalInput::Backspace, now);
+    prediction.output_received(b"l", now);
+    assert_eq!(prediction.predicted_text(), "s -");
+
+    prediction.output_received(b"s", now);
+    assert_eq!(prediction.predicted_text(), " -");
+    prediction.output_received(b" -", now);
+    assert!(prediction.is_empty());
+    assert_eq!(prediction.expire(now), None);
+}
+
+#[test]
+fn test_unexpected_output_drops_prediction() {
+    let now = Instant::now();
+    let mut prediction = EchoPrediction::new(true);
+    type_text(&mut prediction, "cd", now);
+    prediction.output_received(b"c\x07", now);
+    assert!(prediction.is_empty());
+
+    // Keys that don't type a character can't be predicted
+    type_text(&mut prediction, "cd", now);
+    prediction.typed(&TerminalInput::ArrowLeft, now);
+    assert!(prediction.is_empty());
+
+    // Nor can characters outside ASCII
+    type_text(&mut prediction, "é", now);
+    assert!(prediction.is_empty());
+}
+
+#[test]
+fn test_prediction_times_out() {
+    let now = Instant::now();
+    let mut prediction = EchoPrediction::new(true);
+    type_text(&mut prediction, "secret", now);
+    assert_eq!(
+        prediction.expire(now + Duration::from_millis(500)),
+        Some(PREDICTION_TIMEOUT - Duration::from_millis(500))
+    );
+
+    // No echo, as at a password prompt, so nothing more is predicted until the next line
+    assert_eq!(prediction.expire(now + PREDICTION_TIMEOUT), None);
+    assert!(prediction.is_empty());
+    type_text(&mut prediction, "more", now);
+    assert!(prediction.is_empty());
+
+    prediction.typed(&TerminalInput::Enter, now);
+    type_text(&mut prediction, "ls", now);
+    assert!(!prediction.is_empty());
+}
+
+#[test]
+fn test_no_echo_prompt_is_never_drawn() {
+    let now = Instant::now();
+    let mut prediction = EchoPrediction::new(true);
+
+    // The shell echoed the command, so it is being predicted
+    type_text(&mut prediction, "sudo", now);
+    prediction.output_received(b"s", now);
+    assert_eq!(prediction.predicted_text(), "udo");
+    prediction.output_received(b"udo", now);
+    prediction.typed(&TerminalInput::Enter, now);
+
+    // The password prompt doesn't echo, so nothing typed at it is ever drawn
+    prediction.output_received(b"\r\nPassword: ", now);
+    for (i, c) in "hunter2".bytes().enumerate() {
+        prediction.typed(&TerminalInput::Ascii(c), now);
+        assert_eq!(
+            prediction.predicted_text(),
+            "",
+            "after {} characters",
+            i + 1
+        );
+    }
+    assert_eq!(prediction.expire(now + PREDICTION_TIMEOUT), None);
+    assert_eq!(prediction.predicted_text(), "");
+}
+
+#[test]
+fn test_terminal_state_predicts_echo() {
+    let mut state = TerminalState::default();
+    state.echo_prediction.enabled = true;
+    state.predict_echo(&TerminalInput::Ascii(b'a'));
+    state.predict_echo(&TerminalInput::Ascii(b'b'));
+    state.predict_echo(&TerminalInput::Ascii(b'c'));
+    assert!(!state.echo_prediction.is_empty());
+
+    // Colors don't get in the way of the echo
+    state.handle_incoming_data(b"\x1b[32ma");
+    assert_eq!(state.echo_prediction.predicted_text(), "bc");
+
+    // But moving the cursor does
+    state.handle_incoming_data(b"\x1b[H");
+    assert!(state.echo_prediction.is_empty());
+
+    // Full screen applications draw their own input
+    state.handle_incoming_data(b"\x1b[?1049h");
+    state.predict_echo(&TerminalInput::Ascii(b'a'));
+    assert!(state.echo_prediction.is_empty());
+}
@@ -0,0 +1,66 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::Duration;
+
+use freminal_terminal_emulator::{
+    io::fixture::{Fixture, FixtureChunk},
+    state::{cursor::CursorPos, internal::TerminalState},
+};
+use test_log::test;
+
+#[test]
+fn test_fixture_text() {
+    let mut fixture = Fixture::new(20, 5);
+    fixture.push(Duration::from_millis(3), b"\x1b[H");
+    fixture.push(Duration::from_millis(4), b"");
+    fixture.push(Duration::from_millis(150), b"hi\r\n");
+
+    assert_eq!(fixture.chunks.len(), 2);
+    assert_eq!(fixture.bytes(), b"\x1b[Hhi\r\n");
+
+    let text = fixture.to_text();
+    assert_eq!(text, "size 20x5\n3 27,91,72,\n150 104,105,13,10,\n");
+    assert_eq!(text.parse::<Fixture>().unwrap(), fixture);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hi.fixture");
+    fixture.write(&path).unwrap();
+    assert_eq!(Fixture::read(&path).unwrap(), fixture);
+}
+
+#[test]
+fn test_fixture_parse_errors() {
+    assert!("".parse::<Fixture>().is_err());
+    assert!("0 27,\n".parse::<Fixture>().is_err());
+    assert!("size 80\n".parse::<Fixture>().is_err());
+    assert!("size 80x24\nsoon 27,\n".parse::<Fixture>().is_err());
+    assert!("size 80x24\n0 27,256,\n".parse::<Fixture>().is_err());
+
+    // Blank lines and reads with nothing in them are skipped
+    let fixture: Fixture = "size 80x24\n\n5 \n7 65,\n".parse().unwrap();
+    assert_eq!(
+        fixture.chunks,
+        [FixtureChunk {
+            at: Duration::from_millis(7),
+            data: b"A".to_vec(),
+        }]
+    );
+}
+
+#[test]
+fn test_fixture_replay() {
+    let fixture: Fixture = "size 30x6\n0 104,105,\n10 13,10,116,104,101,114,101,\n"
+        .parse()
+        .unwrap();
+
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    fixture.replay(&mut terminal_state);
+
+    assert_eq!(terminal_state.get_win_size(), (30, 6));
+    assert_eq!(terminal_state.to_plain_text(false), "hi\nthere\n");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 5, y: 1 });
+}
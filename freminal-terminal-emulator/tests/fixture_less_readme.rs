@@ -0,0 +1,44 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::path::Path;
+
+use freminal_terminal_emulator::{
+    io::fixture::Fixture,
+    state::{cursor::CursorPos, internal::TerminalState},
+};
+use test_log::test;
+
+/// Replays the output of `less README.md`, recorded with `cargo xtask record-fixture`
+#[test]
+fn test_fixture_less_readme() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/less_readme.fixture");
+    let fixture = Fixture::read(&path).unwrap();
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    fixture.replay(&mut terminal_state);
+
+    // The screen as it was when the fixture was recorded
+    let screen: &[&str] = &[
+        "# freminal",
+        "",
+        "[![codecov](https://codecov.io/gh/fredclausen/freminal/graph/badge.svg?token=H03IXCMD1Y)](https://codecov.io/gh/fredclausen/freminal)",
+        "[![made-with-rust](https://img.shields.io/badge/Made%20with-Rust-1f425f.svg)](https://www.rust-lang.org/)",
+        "[![GitHub license](https://img.shields.io/github/license/Naereen/StrapDown.js.svg)](https://github.com/fredclausen/freminal/LICENSE)",
+        "[![GitHub issues](https://img.shields.io/github/issues/Naereen/StrapDown.js.svg)](https://github.com/fredclausen/freminal/issues/)",
+        "[![GitHub pull-requests](https://img.shields.io/github/issues-pr/Naereen/StrapDown.js.svg)](https://GitHub.com/fredclausen/freminal/pull/)",
+        "",
+        "## Description",
+        "",
+        "Freminal (or \"Fred's Terminal\") is a terminal emulator, written in Rust, that the world didn't ask for or need. It's simply a personal passion project because I thought it would be cool to have a tool written by myself that I use every day.",
+        "",
+        "Freminal is meant to emulate the old VT220s; however it supports (or will support) modern xterm control codes, unicode, and other modern features.",
+        "",
+        "As of now, I would consider this mostly stable, but hardly performant. Please seREADME.md",
+    ];
+    let expected: String = screen.iter().flat_map(|line| [*line, "\n"]).collect();
+    assert_eq!(terminal_state.to_plain_text(false), expected);
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 9, y: 23 });
+}
@@ -8,7 +8,9 @@ use test_log::test;
 use freminal_common::colors::TerminalColor;
 use freminal_terminal_emulator::{
     ansi_components::modes::decawm::Decawm,
-    format_tracker::{ranges_overlap, FormatTag, FormatTracker},
+    format_tracker::{
+        position_after_delete, position_after_insert, ranges_overlap, FormatTag, FormatTracker,
+    },
     state::{
         cursor::{CursorState, ReverseVideo, StateColors},
         fonts::FontWeight,
@@ -615,3 +617,343 @@ fn test_range_adjustment() {
     format_tracker.push_range_adjustment(10..12);
     range_adjustment_test_three(&format_tracker);
 }
+
+/// Cells the exhaustive tests model. Tags past this are covered by the open ended last tag
+const MODEL_CELLS: usize = 12;
+
+/// Four tags, blue, red, green and an open ended default, so every boundary is in play
+fn striped_tracker() -> FormatTracker {
+    let mut format_tracker = FormatTracker::new();
+    let mut cursor = CursorState::default();
+    for (color, range) in [
+        (TerminalColor::Blue, 0..3),
+        (TerminalColor::Red, 3..6),
+        (TerminalColor::Green, 6..9),
+    ] {
+        cursor.colors.set_color(color);
+        format_tracker.push_range(&cursor, range);
+    }
+
+    format_tracker
+}
+
+/// The color of each of the first `cells` cells, checking the tags cover them without gaps or
+/// overlaps
+fn cell_colors(format_tracker: &FormatTracker, cells: usize) -> Vec<TerminalColor> {
+    let tags = format_tracker.tags();
+    assert_eq!(tags.first().map(|tag| tag.start), Some(0));
+    assert_eq!(tags.last().map(|tag| tag.end), Some(usize::MAX));
+    for pair in tags.windows(2) {
+        assert_eq!(pair[0].end, pair[1].start, "gap or overlap in {tags:?}");
+        assert!(pair[0].start < pair[0].end, "empty tag in {tags:?}");
+    }
+
+    (0..cells)
+        .map(|cell| {
+            tags.iter()
+                .find(|tag| tag.start <= cell && cell < tag.end)
+                .map(|tag| tag.colors.color)
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_position_after_insert() {
+    // Before and at the insertion point nothing moves
+    assert_eq!(position_after_insert(2, 5, 3), 2);
+    assert_eq!(position_after_insert(5, 5, 3), 5);
+    // After it, everything moves along
+    assert_eq!(position_after_insert(6, 5, 3), 9);
+    // Inserting nothing is a no op
+    assert_eq!(position_after_insert(6, 5, 0), 6);
+    // The open end stays open
+    assert_eq!(position_after_insert(usize::MAX, 5, 3), usize::MAX);
+}
+
+#[test]
+fn test_position_after_delete() {
+    assert_eq!(position_after_delete(2, &(5..8)), 2);
+    assert_eq!(position_after_delete(5, &(5..8)), 5);
+    // Inside the cut collapses on to its start
+    assert_eq!(position_after_delete(6, &(5..8)), 5);
+    assert_eq!(position_after_delete(8, &(5..8)), 5);
+    assert_eq!(position_after_delete(10, &(5..8)), 7);
+    assert_eq!(position_after_delete(10, &(5..5)), 10);
+    assert_eq!(position_after_delete(usize::MAX, &(5..8)), usize::MAX);
+}
+
+#[test]
+fn test_delete_range_exhaustive() {
+    let before = cell_colors(&striped_tracker(), MODEL_CELLS);
+
+    for start in 0..MODEL_CELLS {
+        for end in start..=MODEL_CELLS {
+            let mut format_tracker = striped_tracker();
+            format_tracker.delete_range(start..end).unwrap();
+
+            let mut expected = before.clone();
+            expected.drain(start..end);
+            let cells = expected.len();
+            assert_eq!(
+                cell_colors(&format_tracker, cells),
+                expected,
+                "deleting {start}..{end}"
+            );
+            // Everything pulled in from past the end is the open ended tag
+            assert_eq!(
+                cell_colors(&format_tracker, MODEL_CELLS)[cells..],
+                vec![TerminalColor::Default; MODEL_CELLS - cells][..],
+            );
+        }
+    }
+}
+
+#[test]
+fn test_range_adjustment_exhaustive() {
+    let before = cell_colors(&striped_tracker(), MODEL_CELLS);
+
+    for at in 0..MODEL_CELLS {
+        for len in 0..4 {
+            let mut format_tracker = striped_tracker();
+            format_tracker.push_range_adjustment(at..at + len);
+
+            // Inserted cells take on the color of the cell they were inserted in front of
+            let mut expected = before.clone();
+            expected.splice(at..at, std::iter::repeat_n(before[at], len));
+            assert_eq!(
+                cell_colors(&format_tracker, expected.len()),
+                expected,
+                "inserting {len} at {at}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_range_adjustments_for_columns() {
+    // Three 4 cell lines, with 2 cells inserted at column 1 of each, as DECIC would
+    let mut format_tracker = striped_tracker();
+    let before = cell_colors(&format_tracker, MODEL_CELLS);
+    format_tracker.push_range_adjustments(&[9..11, 1..3, 5..7]);
+
+    let mut expected = before;
+    for at in [9, 5, 1] {
+        let color = expected[at];
+        expected.splice(at..at, [color, color]);
+    }
+    assert_eq!(cell_colors(&format_tracker, expected.len()), expected);
+}
+
+#[test]
+fn test_delete_ranges_for_columns() {
+    // Three 4 cell lines, with 2 cells taken out at column 1 of each, as DECDC would
+    let mut format_tracker = striped_tracker();
+    let before = cell_colors(&format_tracker, MODEL_CELLS);
+    format_tracker.delete_ranges(&[9..11, 1..3, 5..7]).unwrap();
+
+    let expected: Vec<TerminalColor> = before
+        .iter()
+        .enumerate()
+        .filter(|(cell, _)| !matches!(cell % 4, 1 | 2))
+        .map(|(_, color)| *color)
+        .collect();
+    assert_eq!(cell_colors(&format_tracker, expected.len()), expected);
+}
+
+#[test]
+fn test_delete_ranges_rejects_overlaps() {
+    let mut format_tracker = striped_tracker();
+    assert!(format_tracker.delete_ranges(&[2..5, 4..6]).is_err());
+    // Nothing was changed
+    assert_eq!(format_tracker, striped_tracker());
+
+    // Touching ranges are fine
+    assert!(format_tracker.delete_ranges(&[2..4, 4..6]).is_ok());
+    assert_eq!(
+        cell_colors(&format_tracker, 5),
+        vec![
+            TerminalColor::Blue,
+            TerminalColor::Blue,
+            TerminalColor::Green,
+            TerminalColor::Green,
+            TerminalColor::Green,
+        ]
+    );
+}
+
+#[test]
+fn test_delete_lines_in_window() {
+    // DL inside scroll margins: the deleted line goes, the lines below it move up, and a blank
+    // line is put back at the bottom margin so nothing outside the window moves
+    let mut format_tracker = striped_tracker();
+    let before = cell_colors(&format_tracker, MODEL_CELLS);
+
+    // Lines are 3 cells, the window is lines 1 and 2 (cells 3..9), delete line 1
+    format_tracker.delete_range(3..6).unwrap();
+    format_tracker.push_range_adjustment(6..9);
+
+    let colors = cell_colors(&format_tracker, MODEL_CELLS);
+    assert_eq!(colors[..3], before[..3]);
+    assert_eq!(colors[3..6], before[6..9]);
+    assert_eq!(colors[9..], before[9..]);
+}
+
+/// A small xorshift generator, so the fuzz tests are the same every run
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        usize::try_from(self.0 % n as u64).unwrap()
+    }
+}
+
+/// The color of every cell, one per cell. Cells past the end are the default color
+fn model_color(model: &[TerminalColor], cell: usize) -> TerminalColor {
+    model.get(cell).copied().unwrap_or(TerminalColor::Default)
+}
+
+#[test]
+fn test_format_tracker_fuzz() {
+    const COLORS: [TerminalColor; 4] = [
+        TerminalColor::Red,
+        TerminalColor::Green,
+        TerminalColor::Blue,
+        TerminalColor::Default,
+    ];
+
+    for seed in 1..=200 {
+        let mut rng = Rng(seed);
+        let mut format_tracker = FormatTracker::new();
+        let mut model: Vec<TerminalColor> = Vec::new();
+
+        for step in 0..100 {
+            let start = rng.below(40);
+            let len = rng.below(8);
+            match rng.below(4) {
+                0 => {
+                    let color = COLORS[rng.below(COLORS.len())];
+                    let cursor = CursorState::default().with_color(color);
+                    format_tracker.push_range(&cursor, start..start + len);
+
+                    if model.len() < start + len {
+                        model.resize(start + len, TerminalColor::Default);
+                    }
+                    model[start..start + len].fill(color);
+                }
+                1 => {
+                    format_tracker.push_range_adjustment(start..start + len);
+
+                    let color = model_color(&model, start);
+                    if start < model.len() {
+                        model.splice(start..start, std::iter::repeat_n(color, len));
+                    }
+                }
+                2 => {
+                    format_tracker.delete_range(start..start + len).unwrap();
+
+                    let end = (start + len).min(model.len());
+                    if start < end {
+                        model.drain(start..end);
+                    }
+                }
+                _ => {
+                    // Two ranges at once, as a column operation on two lines would
+                    let second = start + len + rng.below(8);
+                    let ranges = [second..second + len, start..start + len];
+                    format_tracker.delete_ranges(&ranges).unwrap();
+
+                    for range in ranges {
+                        let end = range.end.min(model.len());
+                        if range.start < end {
+                            model.drain(range.start..end);
+                        }
+                    }
+                }
+            }
+
+            assert!(
+                format_tracker.validate().is_ok(),
+                "seed {seed} step {step}: {:?}",
+                format_tracker.tags()
+            );
+            let cells = model.len() + 4;
+            let expected: Vec<TerminalColor> =
+                (0..cells).map(|cell| model_color(&model, cell)).collect();
+            assert_eq!(
+                cell_colors(&format_tracker, cells),
+                expected,
+                "seed {seed} step {step}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_format_tracker_ignores_bad_ranges() {
+    let mut format_tracker = striped_tracker();
+    let cursor = CursorState::default().with_color(TerminalColor::Magenta);
+
+    // An inverted range is ignored rather than leaving a tag that runs backwards
+    #[allow(clippy::reversed_empty_ranges)]
+    format_tracker.push_range(&cursor, 5..3);
+    assert_eq!(format_tracker, striped_tracker());
+    assert!(format_tracker.validate().is_ok());
+
+    // An empty one doesn't leave an empty tag behind
+    format_tracker.push_range(&cursor, 4..4);
+    assert!(format_tracker.validate().is_ok());
+    assert_eq!(format_tracker.tags(), striped_tracker().tags());
+
+    #[allow(clippy::reversed_empty_ranges)]
+    let deleted = format_tracker.delete_range(6..2);
+    assert!(deleted.is_err());
+    #[allow(clippy::reversed_empty_ranges)]
+    format_tracker.push_range_adjustment(6..2);
+    assert_eq!(format_tracker.tags(), striped_tracker().tags());
+}
+
+#[test]
+fn test_format_tracker_repair_keeps_consistent_tags() {
+    let mut format_tracker = striped_tracker();
+    format_tracker.repair();
+    assert_eq!(format_tracker, striped_tracker());
+
+    let mut format_tracker = FormatTracker::new();
+    format_tracker.repair();
+    assert_eq!(format_tracker, FormatTracker::new());
+}
+
+#[test]
+fn test_push_range_merges_matching_neighbours() {
+    // Text written a piece at a time gets the same tags as text written in one go
+    let mut format_tracker = FormatTracker::new();
+    let mut cursor = CursorState::default();
+    cursor.colors.set_color(TerminalColor::Blue);
+    for range in [0..2, 2..3, 3..6] {
+        format_tracker.push_range(&cursor, range);
+    }
+
+    let mut in_one_go = FormatTracker::new();
+    in_one_go.push_range(&cursor, 0..6);
+    assert_eq!(format_tracker, in_one_go);
+    assert_eq!(format_tracker.tags().len(), 2);
+
+    // Matching tags that don't touch, or different ones that do, stay apart
+    format_tracker.push_range(&CursorState::default(), 6..7);
+    format_tracker.push_range(&cursor, 7..8);
+    assert_eq!(
+        cell_colors(&format_tracker, 9),
+        [
+            vec![TerminalColor::Blue; 6],
+            vec![TerminalColor::Default],
+            vec![TerminalColor::Blue],
+            vec![TerminalColor::Default],
+        ]
+        .concat()
+    );
+    assert_eq!(format_tracker.tags().len(), 4);
+}
@@ -0,0 +1,130 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Random writes, resizes and mode changes, checking nothing panics at any size
+
+use test_log::test;
+
+mod common;
+use common::TinyRng;
+
+use freminal_common::{args::UNLIMITED_SCROLLBACK_LINES, cell_geometry::TerminalGeometry};
+use freminal_terminal_emulator::state::internal::TerminalState;
+
+const INPUT: [&[u8]; 32] = [
+    b"a",
+    b"abc",
+    b"abcdefghijklmnop",
+    "日本".as_bytes(),
+    "e\u{301}".as_bytes(),
+    b"\r\n",
+    b"\n\n\n",
+    b"\x08",
+    b"\t",
+    b"\x1b[2;3H",
+    b"\x1b[99;99H",
+    b"\x1b[H",
+    b"\x1b[J",
+    b"\x1b[1J",
+    b"\x1b[2J",
+    b"\x1b[3J",
+    b"\x1b[K",
+    b"\x1b[1K",
+    b"\x1b[3@",
+    b"\x1b[2P",
+    b"\x1b[5X",
+    b"\x1b[2L",
+    b"\x1b[2M",
+    b"\x1b[2S",
+    b"\x1b[2T",
+    b"\x1b[1;2r",
+    b"\x1b[r",
+    b"\x1bM",
+    b"\x1bD",
+    b"\x1b[?1048h\x1b[?1048l",
+    b"\x1b[?1049h",
+    b"\x1b[?1049l",
+];
+
+/// The scrollback limits tried, small ones included so lines are dropped often
+const SCROLLBACK_LINES: [usize; 5] = [1, 10, 21, 100, UNLIMITED_SCROLLBACK_LINES];
+
+/// Run `steps` random actions on a terminal starting `width` by `height`
+fn fuzz(seed: u64, scrollback_lines: usize, steps: usize) {
+    let mut rng = TinyRng(seed);
+    let mut next = |below| rng.next(below);
+
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    // Nothing stops a window being made this small but the configured limits
+    terminal_state.size_limits.min = TerminalGeometry::new(0, 0);
+    terminal_state.set_scrollback_lines(scrollback_lines);
+    terminal_state.set_alternate_scrollback_lines(next(3) * 5);
+    for _ in 0..steps {
+        match next(10) {
+            0 => drop(terminal_state.set_win_size(next(12), next(8))),
+            1 => terminal_state.scroll(1.0),
+            2 => terminal_state.scroll(-1.0),
+            3 => terminal_state.set_no_wrap_view(next(2) == 0),
+            4 => terminal_state.scroll_columns(2.0),
+            _ => terminal_state.handle_incoming_data(INPUT[next(INPUT.len())]),
+        }
+
+        let _ = terminal_state.render_slice();
+        let _ = terminal_state.visible_text();
+        let _ = terminal_state.to_plain_text(true);
+        let _ = terminal_state.viewport_cursor_pos();
+    }
+}
+
+#[test]
+fn test_fuzz_small_sizes() {
+    for (seed, scrollback_lines) in SCROLLBACK_LINES.iter().enumerate() {
+        for round in 0..8 {
+            fuzz(
+                0x9e37_79b9_7f4a_7c15 ^ (seed as u64 * 8 + round),
+                *scrollback_lines,
+                3000,
+            );
+        }
+    }
+}
+
+#[test]
+fn test_clear_scrollback_then_no_wrap() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.handle_incoming_data(b"a\x1b[3J");
+    terminal_state.set_no_wrap_view(true);
+    let slice = terminal_state.render_slice();
+    assert!(slice.rows.iter().all(|row| row.buffer.is_empty()));
+}
+
+#[test]
+fn test_small_scrollback_at_one_cell() {
+    for scrollback_lines in [10, 21] {
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let mut terminal_state = TerminalState::new(tx);
+        terminal_state.size_limits.min = TerminalGeometry::new(0, 0);
+        terminal_state.set_scrollback_lines(scrollback_lines);
+        terminal_state.handle_incoming_data(b"\x1b[99;99H");
+        terminal_state.set_win_size(1, 1);
+        terminal_state.handle_incoming_data(b"abc");
+        assert_eq!(terminal_state.visible_text(), "c");
+    }
+}
+
+#[test]
+fn test_insert_lines_after_resizes() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(0, 0);
+    terminal_state.handle_incoming_data(b"\x1b[99;99H");
+    terminal_state.set_win_size(11, 5);
+    terminal_state.set_win_size(6, 4);
+    terminal_state.handle_incoming_data(b"\x1b[r\n\x1b[J\x1b[2L");
+    let _ = terminal_state.render_slice();
+    assert_eq!(terminal_state.visible_text().trim(), "");
+}
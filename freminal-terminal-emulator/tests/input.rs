@@ -0,0 +1,820 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::{
+    ansi_components::{
+        mode::{MouseEncoding, MouseTrack},
+        modes::rl_bracket::RlBracket,
+    },
+    input::{
+        encode_keyboard_protocol, encode_mouse_report, is_large_paste, key_to_terminal_input,
+        parse_hex_bytes, paste_to_terminal_input, read_paste_file, sanitize_paste,
+        shortcut_key_matches, Key, KeyboardProtocol, MouseAction, MouseButton, LARGE_PASTE_BYTES,
+        MAX_PASTE_FILE_BYTES,
+    },
+    interface::{KeyModifiers, TerminalInput, TerminalInputPayload},
+};
+use test_log::test;
+
+fn encode(key: Key, modifiers: KeyModifiers, decckm: bool, keypad: bool) -> Vec<u8> {
+    key_to_terminal_input(key, modifiers)
+        .unwrap()
+        .iter()
+        .flat_map(|input| match input.to_payload(decckm, keypad, false) {
+            TerminalInputPayload::Single(c) => vec![c],
+            TerminalInputPayload::Many(bytes) => bytes.to_vec(),
+            TerminalInputPayload::Owned(bytes) => bytes,
+        })
+        .collect()
+}
+
+#[test]
+fn test_key_to_terminal_input() {
+    let cases = [
+        (Key::Enter, TerminalInput::Enter),
+        (Key::Backspace, TerminalInput::Backspace),
+        (Key::ArrowUp, TerminalInput::ArrowUp),
+        (Key::ArrowDown, TerminalInput::ArrowDown),
+        (Key::ArrowLeft, TerminalInput::ArrowLeft),
+        (Key::ArrowRight, TerminalInput::ArrowRight),
+        (Key::Home, TerminalInput::Home),
+        (Key::End, TerminalInput::End),
+        (Key::Delete, TerminalInput::Delete),
+        (Key::Insert, TerminalInput::Insert),
+        (Key::PageUp, TerminalInput::PageUp),
+        (Key::PageDown, TerminalInput::PageDown),
+        (Key::Tab, TerminalInput::Tab),
+        (Key::Escape, TerminalInput::Escape),
+    ];
+
+    for (key, expected) in cases {
+        assert_eq!(
+            key_to_terminal_input(key, KeyModifiers::NONE)
+                .unwrap()
+                .as_ref(),
+            [expected],
+            "{key:?}"
+        );
+    }
+
+    // Printable keys arrive as text events
+    assert!(key_to_terminal_input(Key::Char('a'), KeyModifiers::NONE).is_none());
+    assert!(key_to_terminal_input(Key::Char('1'), KeyModifiers::NONE).is_none());
+}
+
+#[test]
+fn test_ctrl_keys() {
+    assert_eq!(
+        key_to_terminal_input(Key::Char('c'), KeyModifiers::CTRL)
+            .unwrap()
+            .as_ref(),
+        [TerminalInput::Ctrl(b'C')]
+    );
+    assert_eq!(
+        encode(Key::Char('a'), KeyModifiers::CTRL, false, false),
+        [0x01]
+    );
+    assert_eq!(
+        encode(Key::Char('z'), KeyModifiers::CTRL, false, false),
+        [0x1a]
+    );
+    assert_eq!(
+        encode(Key::Char('['), KeyModifiers::CTRL, false, false),
+        [0x1b]
+    );
+    assert_eq!(
+        encode(Key::Char('\\'), KeyModifiers::CTRL, false, false),
+        [0x1c]
+    );
+    assert_eq!(
+        encode(Key::Char(']'), KeyModifiers::CTRL, false, false),
+        [0x1d]
+    );
+
+    // Enter is still enter with ctrl held
+    assert_eq!(
+        encode(Key::Enter, KeyModifiers::CTRL, false, false),
+        [b'\r']
+    );
+
+    // Cursor keys are sent with the modifier
+    assert_eq!(
+        encode(Key::ArrowUp, KeyModifiers::CTRL, false, false),
+        b"\x1b[1;5A"
+    );
+
+    // Anything else with ctrl held is not sent
+    assert!(key_to_terminal_input(Key::Char('1'), KeyModifiers::CTRL).is_none());
+}
+
+#[test]
+fn test_cursor_keys_decckm() {
+    let cases: [(Key, &[u8], &[u8]); 6] = [
+        (Key::ArrowUp, b"\x1b[A", b"\x1bOA"),
+        (Key::ArrowDown, b"\x1b[B", b"\x1bOB"),
+        (Key::ArrowRight, b"\x1b[C", b"\x1bOC"),
+        (Key::ArrowLeft, b"\x1b[D", b"\x1bOD"),
+        (Key::Home, b"\x1b[H", b"\x1bOH"),
+        (Key::End, b"\x1b[F", b"\x1bOF"),
+    ];
+
+    for (key, normal, application) in cases {
+        assert_eq!(
+            encode(key, KeyModifiers::NONE, false, false),
+            normal,
+            "{key:?}"
+        );
+        assert_eq!(
+            encode(key, KeyModifiers::NONE, true, false),
+            application,
+            "{key:?}"
+        );
+    }
+}
+
+#[test]
+fn test_keys_unaffected_by_decckm() {
+    let cases: [(Key, &[u8]); 8] = [
+        (Key::Enter, b"\r"),
+        (Key::Backspace, b"\x7f"),
+        (Key::Tab, b"\t"),
+        (Key::Escape, b"\x1b"),
+        (Key::Delete, b"\x1b[3~"),
+        (Key::Insert, b"\x1b[2~"),
+        (Key::PageUp, b"\x1b[5~"),
+        (Key::PageDown, b"\x1b[6~"),
+    ];
+
+    for (key, expected) in cases {
+        for decckm in [false, true] {
+            assert_eq!(
+                encode(key, KeyModifiers::NONE, decckm, false),
+                expected,
+                "{key:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_function_keys() {
+    let cases: [(Key, &[u8]); 12] = [
+        (Key::Function(1), b"\x1bOP"),
+        (Key::Function(2), b"\x1bOQ"),
+        (Key::Function(3), b"\x1bOR"),
+        (Key::Function(4), b"\x1bOS"),
+        (Key::Function(5), b"\x1b[15~"),
+        (Key::Function(6), b"\x1b[17~"),
+        (Key::Function(7), b"\x1b[18~"),
+        (Key::Function(8), b"\x1b[19~"),
+        (Key::Function(9), b"\x1b[20~"),
+        (Key::Function(10), b"\x1b[21~"),
+        (Key::Function(11), b"\x1b[23~"),
+        (Key::Function(12), b"\x1b[24~"),
+    ];
+
+    for (key, expected) in cases {
+        for decckm in [false, true] {
+            assert_eq!(
+                encode(key, KeyModifiers::NONE, decckm, false),
+                expected,
+                "{key:?}"
+            );
+        }
+    }
+
+    assert!(key_to_terminal_input(Key::Function(13), KeyModifiers::NONE).is_none());
+    assert_eq!(
+        TerminalInput::Function(13).to_payload(false, false, false),
+        TerminalInputPayload::Many(b"")
+    );
+}
+
+#[test]
+fn test_modified_keys() {
+    let shift_ctrl = KeyModifiers::SHIFT | KeyModifiers::CTRL;
+    let cases: [(Key, KeyModifiers, &[u8]); 16] = [
+        (Key::ArrowUp, KeyModifiers::SHIFT, b"\x1b[1;2A"),
+        (Key::ArrowDown, KeyModifiers::ALT, b"\x1b[1;3B"),
+        (Key::ArrowRight, shift_ctrl, b"\x1b[1;6C"),
+        (Key::ArrowLeft, KeyModifiers::CTRL, b"\x1b[1;5D"),
+        (Key::Home, KeyModifiers::SHIFT, b"\x1b[1;2H"),
+        (Key::End, KeyModifiers::CTRL, b"\x1b[1;5F"),
+        (Key::Function(1), KeyModifiers::SHIFT, b"\x1b[1;2P"),
+        (Key::Function(4), KeyModifiers::CTRL, b"\x1b[1;5S"),
+        (Key::Function(5), KeyModifiers::CTRL, b"\x1b[15;5~"),
+        (
+            Key::Function(12),
+            KeyModifiers::SHIFT | KeyModifiers::ALT,
+            b"\x1b[24;4~",
+        ),
+        (Key::Insert, KeyModifiers::SHIFT, b"\x1b[2;2~"),
+        (Key::Delete, KeyModifiers::CTRL, b"\x1b[3;5~"),
+        (Key::PageUp, KeyModifiers::ALT, b"\x1b[5;3~"),
+        (Key::PageDown, shift_ctrl | KeyModifiers::ALT, b"\x1b[6;8~"),
+        (Key::Tab, KeyModifiers::SHIFT, b"\x1b[Z"),
+        (Key::Backspace, KeyModifiers::ALT, b"\x1b\x7f"),
+    ];
+
+    for (key, modifiers, expected) in cases {
+        // The modifiers are sent the same way whatever mode the cursor keys are in
+        for decckm in [false, true] {
+            assert_eq!(
+                encode(key, modifiers, decckm, false),
+                expected,
+                "{key:?} {modifiers:?}"
+            );
+        }
+    }
+
+    // Keys without a modified form are sent as they are
+    assert_eq!(encode(Key::Tab, KeyModifiers::CTRL, false, false), b"\t");
+    assert_eq!(
+        encode(Key::Escape, KeyModifiers::SHIFT, false, false),
+        b"\x1b"
+    );
+    assert_eq!(encode(Key::Enter, KeyModifiers::ALT, false, false), b"\r");
+}
+
+#[test]
+fn test_modifier_parameter() {
+    assert!(KeyModifiers::default().is_none());
+    assert_eq!(KeyModifiers::default().parameter(), 1);
+    let all = KeyModifiers {
+        shift: true,
+        alt: true,
+        ctrl: true,
+    };
+    assert!(!all.is_none());
+    assert_eq!(all.parameter(), 8);
+}
+
+#[test]
+fn test_shortcut_key_matches() {
+    // US layout
+    assert!(shortcut_key_matches(
+        Key::Char('c'),
+        Key::Char('c'),
+        Some(Key::Char('c'))
+    ));
+    assert!(!shortcut_key_matches(
+        Key::Char('c'),
+        Key::Char('v'),
+        Some(Key::Char('v'))
+    ));
+
+    // Dvorak puts C where I is. Letters go by what they type
+    assert!(shortcut_key_matches(
+        Key::Char('c'),
+        Key::Char('c'),
+        Some(Key::Char('i'))
+    ));
+    assert!(!shortcut_key_matches(
+        Key::Char('i'),
+        Key::Char('c'),
+        Some(Key::Char('i'))
+    ));
+    // AZERTY swaps A and Q
+    assert!(!shortcut_key_matches(
+        Key::Char('a'),
+        Key::Char('q'),
+        Some(Key::Char('a'))
+    ));
+
+    // A symbol where a US keyboard has a letter goes by where it is, as do keys the GUI has no
+    // name for, like Cyrillic letters, which egui already reports by position
+    assert!(shortcut_key_matches(
+        Key::Char('w'),
+        Key::Char(','),
+        Some(Key::Char('w'))
+    ));
+    assert!(shortcut_key_matches(Key::Char('c'), Key::Char('c'), None));
+    assert!(!shortcut_key_matches(Key::Char('c'), Key::Char(','), None));
+
+    // AZERTY's 6 key types a minus, so it is both
+    assert!(shortcut_key_matches(
+        Key::Char('-'),
+        Key::Char('-'),
+        Some(Key::Char('6'))
+    ));
+    assert!(shortcut_key_matches(
+        Key::Char('6'),
+        Key::Char('-'),
+        Some(Key::Char('6'))
+    ));
+    // Digits go by what they type, like letters
+    assert!(!shortcut_key_matches(
+        Key::Char('0'),
+        Key::Char('9'),
+        Some(Key::Char('0'))
+    ));
+}
+
+#[test]
+fn test_keypad_modes() {
+    let cases: [(u8, &[u8], u8); 18] = [
+        (0, b"\x1bOp", b'0'),
+        (1, b"\x1bOq", b'1'),
+        (2, b"\x1bOr", b'2'),
+        (3, b"\x1bOs", b'3'),
+        (4, b"\x1bOt", b'4'),
+        (5, b"\x1bOu", b'5'),
+        (6, b"\x1bOv", b'6'),
+        (7, b"\x1bOw", b'7'),
+        (8, b"\x1bOx", b'8'),
+        (9, b"\x1bOy", b'9'),
+        (b'-', b"\x1bOm", b'-'),
+        (b',', b"\x1bOl", b','),
+        (b'.', b"\x1bOn", b'.'),
+        // Enter is a carriage return, like the main Enter key, unless the keypad is in
+        // application mode
+        (b'\n', b"\x1bOM", b'\r'),
+        (b'*', b"\x1bOj", b'*'),
+        (b'+', b"\x1bOk", b'+'),
+        (b'/', b"\x1bOo", b'/'),
+        (b'=', b"\x1bOX", b'='),
+    ];
+
+    for (c, application, numeric) in cases {
+        assert_eq!(
+            TerminalInput::KeyPad(c).to_payload(false, true, false),
+            TerminalInputPayload::Many(application)
+        );
+        assert_eq!(
+            TerminalInput::KeyPad(c).to_payload(false, false, false),
+            TerminalInputPayload::Single(numeric)
+        );
+    }
+}
+
+#[test]
+fn test_keypad_keys() {
+    assert_eq!(
+        key_to_terminal_input(Key::Keypad(7), KeyModifiers::NONE)
+            .unwrap()
+            .as_ref(),
+        [TerminalInput::KeyPad(7)]
+    );
+
+    // Keypad keys are sent the same with anything held, whatever mode the cursor keys are in
+    for modifiers in [KeyModifiers::NONE, KeyModifiers::SHIFT, KeyModifiers::CTRL] {
+        for decckm in [false, true] {
+            assert_eq!(encode(Key::Keypad(7), modifiers, decckm, false), b"7");
+            assert_eq!(encode(Key::Keypad(7), modifiers, decckm, true), b"\x1bOw");
+            assert_eq!(encode(Key::Keypad(b'\n'), modifiers, decckm, false), b"\r");
+            assert_eq!(
+                encode(Key::Keypad(b'\n'), modifiers, decckm, true),
+                b"\x1bOM"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_keypad_enter_operators_and_separators() {
+    let keys = [b'\n', b'-', b',', b'.', b'+', b'*', b'/', b'='];
+    let application: Vec<Vec<u8>> = keys
+        .iter()
+        .map(|c| encode(Key::Keypad(*c), KeyModifiers::NONE, false, true))
+        .collect();
+
+    // In application mode each of them sends its own SS3 sequence, so the decimal point and the
+    // comma, and keypad Enter and the main Enter, can be told apart
+    for (i, sent) in application.iter().enumerate() {
+        assert!(sent.starts_with(b"\x1bO"), "{:?}", keys[i]);
+        for other in &application[i + 1..] {
+            assert_ne!(sent, other);
+        }
+    }
+    assert_ne!(
+        application[0],
+        encode(Key::Enter, KeyModifiers::NONE, false, true)
+    );
+
+    // In numeric mode they send what is printed on them, and Enter what the main Enter does
+    for c in &keys[1..] {
+        assert_eq!(
+            encode(Key::Keypad(*c), KeyModifiers::NONE, false, false),
+            [*c]
+        );
+    }
+    assert_eq!(
+        encode(Key::Keypad(b'\n'), KeyModifiers::NONE, false, false),
+        encode(Key::Enter, KeyModifiers::NONE, false, false)
+    );
+}
+
+#[test]
+fn test_backarrow_key_mode() {
+    assert_eq!(
+        TerminalInput::Backspace.to_payload(false, false, false),
+        TerminalInputPayload::Single(0x7f)
+    );
+    assert_eq!(
+        TerminalInput::Backspace.to_payload(false, false, true),
+        TerminalInputPayload::Single(0x08)
+    );
+}
+
+#[test]
+fn test_modify_other_keys() {
+    let level = |modify_other_keys| KeyboardProtocol {
+        modify_other_keys,
+        ..KeyboardProtocol::default()
+    };
+    let shift_ctrl = KeyModifiers::SHIFT | KeyModifiers::CTRL;
+
+    // Off, nothing changes
+    for modifiers in [KeyModifiers::CTRL, KeyModifiers::ALT, shift_ctrl] {
+        assert!(encode_keyboard_protocol(Key::Char('1'), modifiers, level(0)).is_none());
+    }
+
+    // Level 1 only changes keys whose control character would lose which key it was or what
+    // else was held
+    let cases: [(Key, KeyModifiers, &[u8]); 4] = [
+        (Key::Char('1'), KeyModifiers::CTRL, b"\x1b[27;5;49~"),
+        (Key::Char(';'), KeyModifiers::CTRL, b"\x1b[27;5;59~"),
+        (Key::Char('a'), shift_ctrl, b"\x1b[27;6;65~"),
+        (
+            Key::Char('.'),
+            KeyModifiers::CTRL | KeyModifiers::ALT,
+            b"\x1b[27;7;46~",
+        ),
+    ];
+    for (key, modifiers, expected) in cases {
+        for modify_other_keys in [1, 2] {
+            assert_eq!(
+                encode_keyboard_protocol(key, modifiers, level(modify_other_keys)).unwrap(),
+                expected,
+                "{key:?} {modifiers:?} {modify_other_keys}"
+            );
+        }
+    }
+    for (key, modifiers) in [
+        (Key::Char('a'), KeyModifiers::CTRL),
+        (Key::Char('['), KeyModifiers::CTRL),
+        (Key::Char('a'), KeyModifiers::ALT),
+        (Key::Enter, KeyModifiers::CTRL),
+        (Key::Tab, KeyModifiers::CTRL),
+    ] {
+        assert!(
+            encode_keyboard_protocol(key, modifiers, level(1)).is_none(),
+            "{key:?} {modifiers:?}"
+        );
+    }
+
+    // Level 2 changes every key typed with Ctrl or Alt, and Enter, Tab, Backspace and Escape
+    // with anything held
+    let cases: [(Key, KeyModifiers, &[u8]); 7] = [
+        (Key::Char('a'), KeyModifiers::CTRL, b"\x1b[27;5;97~"),
+        (Key::Char('['), KeyModifiers::CTRL, b"\x1b[27;5;91~"),
+        (Key::Char('a'), KeyModifiers::ALT, b"\x1b[27;3;97~"),
+        (Key::Enter, KeyModifiers::SHIFT, b"\x1b[27;2;13~"),
+        (Key::Tab, KeyModifiers::CTRL, b"\x1b[27;5;9~"),
+        (Key::Backspace, KeyModifiers::ALT, b"\x1b[27;3;127~"),
+        (Key::Escape, KeyModifiers::CTRL, b"\x1b[27;5;27~"),
+    ];
+    for (key, modifiers, expected) in cases {
+        assert_eq!(
+            encode_keyboard_protocol(key, modifiers, level(2)).unwrap(),
+            expected,
+            "{key:?} {modifiers:?}"
+        );
+    }
+
+    // Shift on its own types the shifted character, and Shift+Tab is still back tab
+    for modifiers in [KeyModifiers::NONE, KeyModifiers::SHIFT] {
+        assert!(encode_keyboard_protocol(Key::Char('a'), modifiers, level(2)).is_none());
+    }
+    assert!(encode_keyboard_protocol(Key::Tab, KeyModifiers::SHIFT, level(2)).is_none());
+
+    // Cursor, editing, function and keypad keys keep their usual sequences
+    for key in [Key::ArrowUp, Key::Delete, Key::Function(5), Key::Keypad(1)] {
+        assert!(encode_keyboard_protocol(key, KeyModifiers::CTRL, level(2)).is_none());
+    }
+}
+
+#[test]
+fn test_kitty_keyboard_protocol() {
+    let disambiguate = KeyboardProtocol {
+        kitty_flags: 1,
+        ..KeyboardProtocol::default()
+    };
+    let shift_ctrl = KeyModifiers::SHIFT | KeyModifiers::CTRL;
+
+    let cases: [(Key, KeyModifiers, &[u8]); 15] = [
+        // Escape can't be mistaken for the start of a sequence
+        (Key::Escape, KeyModifiers::NONE, b"\x1b[27u"),
+        (Key::Escape, KeyModifiers::SHIFT, b"\x1b[27;2u"),
+        // Keys with Ctrl or Alt go by what they type with nothing held
+        (Key::Char('c'), KeyModifiers::CTRL, b"\x1b[99;5u"),
+        (Key::Char('a'), shift_ctrl, b"\x1b[97;6u"),
+        (Key::Char('['), KeyModifiers::ALT, b"\x1b[91;3u"),
+        (Key::Char('1'), KeyModifiers::CTRL, b"\x1b[49;5u"),
+        // Enter, Tab and Backspace with anything held
+        (Key::Enter, KeyModifiers::CTRL, b"\x1b[13;5u"),
+        (Key::Tab, KeyModifiers::SHIFT, b"\x1b[9;2u"),
+        (Key::Backspace, KeyModifiers::ALT, b"\x1b[127;3u"),
+        // The keypad has its own numbers
+        (Key::Keypad(0), KeyModifiers::NONE, b"\x1b[57399u"),
+        (Key::Keypad(9), KeyModifiers::NONE, b"\x1b[57408u"),
+        (Key::Keypad(b'.'), KeyModifiers::NONE, b"\x1b[57409u"),
+        (Key::Keypad(b'\n'), KeyModifiers::NONE, b"\x1b[57414u"),
+        (Key::Keypad(b','), KeyModifiers::SHIFT, b"\x1b[57416;2u"),
+        // F3 can't be mistaken for a cursor position report
+        (Key::Function(3), KeyModifiers::CTRL, b"\x1b[13;5~"),
+    ];
+    for (key, modifiers, expected) in cases {
+        assert_eq!(
+            encode_keyboard_protocol(key, modifiers, disambiguate).unwrap(),
+            expected,
+            "{key:?} {modifiers:?}"
+        );
+    }
+
+    // Enter, Tab and Backspace on their own, and text, are sent as usual, so a shell can still
+    // be used if a program leaves the protocol on
+    for (key, modifiers) in [
+        (Key::Enter, KeyModifiers::NONE),
+        (Key::Tab, KeyModifiers::NONE),
+        (Key::Backspace, KeyModifiers::NONE),
+        (Key::Char('a'), KeyModifiers::NONE),
+        (Key::Char('a'), KeyModifiers::SHIFT),
+        (Key::ArrowUp, KeyModifiers::CTRL),
+        (Key::Function(1), KeyModifiers::NONE),
+        (Key::Function(3), KeyModifiers::NONE),
+    ] {
+        assert!(
+            encode_keyboard_protocol(key, modifiers, disambiguate).is_none(),
+            "{key:?} {modifiers:?}"
+        );
+    }
+
+    // The kitty protocol wins over modifyOtherKeys, and flags we don't support do nothing
+    let both = KeyboardProtocol {
+        modify_other_keys: 2,
+        kitty_flags: 1,
+    };
+    assert_eq!(
+        encode_keyboard_protocol(Key::Char('a'), KeyModifiers::CTRL, both).unwrap(),
+        b"\x1b[97;5u"
+    );
+    let unsupported = KeyboardProtocol {
+        kitty_flags: 2,
+        ..KeyboardProtocol::default()
+    };
+    assert!(encode_keyboard_protocol(Key::Escape, KeyModifiers::NONE, unsupported).is_none());
+
+    // The sequence is sent as it is
+    assert_eq!(
+        TerminalInput::Sequence(b"\x1b[27u".to_vec()).to_payload(true, true, true),
+        TerminalInputPayload::Owned(b"\x1b[27u".to_vec())
+    );
+}
+
+#[test]
+fn test_paste_to_terminal_input() {
+    let text = |inputs: &[TerminalInput]| {
+        inputs
+            .iter()
+            .map(|input| match input {
+                TerminalInput::Ascii(c) => *c,
+                _ => panic!("unexpected input {input:?}"),
+            })
+            .collect::<Vec<u8>>()
+    };
+
+    assert_eq!(
+        text(&paste_to_terminal_input("ls", &RlBracket::Disabled)),
+        b"ls"
+    );
+    assert_eq!(
+        text(&paste_to_terminal_input("ls", &RlBracket::Enabled)),
+        b"\x1b[200~ls\x1b[201~"
+    );
+
+    // a paste can't end the bracketed paste itself
+    assert_eq!(
+        text(&paste_to_terminal_input(
+            "ls\x1b[201~; rm -rf ~\n",
+            &RlBracket::Enabled
+        )),
+        b"\x1b[200~ls[201~; rm -rf ~\r\x1b[201~"
+    );
+}
+
+#[test]
+fn test_sanitize_paste() {
+    assert_eq!(sanitize_paste("echo hi"), "echo hi");
+    assert_eq!(sanitize_paste("a\r\nb\nc\rd"), "a\rb\rc\rd");
+    assert_eq!(sanitize_paste("\tindented"), "\tindented");
+    assert_eq!(sanitize_paste("\x1b[31mred\x07\x08\x7f\u{9b}"), "[31mred");
+    assert_eq!(sanitize_paste("caf\u{e9} \u{1f600}"), "caf\u{e9} \u{1f600}");
+}
+
+#[test]
+fn test_read_paste_file() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let text = dir.path().join("commands.txt");
+    std::fs::write(&text, "show version\nshow interfaces\n").unwrap();
+    assert_eq!(
+        read_paste_file(&text).unwrap(),
+        "show version\nshow interfaces\n"
+    );
+
+    let binary = dir.path().join("firmware.bin");
+    std::fs::write(&binary, [0xff, 0xfe, 0x00, 0x80]).unwrap();
+    assert!(read_paste_file(&binary).is_err());
+
+    let huge = dir.path().join("huge.txt");
+    let file = std::fs::File::create(&huge).unwrap();
+    file.set_len(MAX_PASTE_FILE_BYTES + 1).unwrap();
+    assert!(read_paste_file(&huge).is_err());
+
+    assert!(read_paste_file(&dir.path().join("missing.txt")).is_err());
+}
+
+#[test]
+fn test_is_large_paste() {
+    assert!(!is_large_paste("ls"));
+    assert!(!is_large_paste(&"a".repeat(LARGE_PASTE_BYTES)));
+    assert!(is_large_paste(&"a".repeat(LARGE_PASTE_BYTES + 1)));
+}
+
+#[test]
+fn test_parse_hex_bytes() {
+    assert_eq!(parse_hex_bytes("1b 5b 41").unwrap(), b"\x1b[A");
+    assert_eq!(parse_hex_bytes("0x03,0X04").unwrap(), [0x03, 0x04]);
+    assert_eq!(parse_hex_bytes("1B5b41").unwrap(), b"\x1b[A");
+    assert_eq!(parse_hex_bytes("  ff ,  00  ").unwrap(), [0xff, 0x00]);
+    assert_eq!(parse_hex_bytes("").unwrap(), Vec::<u8>::new());
+
+    assert!(parse_hex_bytes("1").is_err());
+    assert!(parse_hex_bytes("1b zz").is_err());
+}
+
+fn mouse(
+    action: MouseAction,
+    modifiers: KeyModifiers,
+    tracking: &MouseTrack,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
+    encode_mouse_report(action, modifiers, 4, 9, tracking, encoding)
+}
+
+#[test]
+fn test_mouse_encodings() {
+    let press = MouseAction::Press(MouseButton::Left);
+    let release = MouseAction::Release(MouseButton::Right);
+    let tracking = &MouseTrack::XtMseX11;
+    let cases: [(MouseEncoding, &[u8], &[u8]); 4] = [
+        (MouseEncoding::X11, b"\x1b[M %*", b"\x1b[M#%*"),
+        (MouseEncoding::Utf8, b"\x1b[M %*", b"\x1b[M#%*"),
+        (MouseEncoding::Sgr, b"\x1b[<0;5;10M", b"\x1b[<2;5;10m"),
+        (MouseEncoding::Urxvt, b"\x1b[32;5;10M", b"\x1b[35;5;10M"),
+    ];
+
+    for (encoding, pressed, released) in cases {
+        assert_eq!(
+            mouse(press, KeyModifiers::NONE, tracking, encoding).unwrap(),
+            pressed,
+            "{encoding:?}"
+        );
+        assert_eq!(
+            mouse(release, KeyModifiers::NONE, tracking, encoding).unwrap(),
+            released,
+            "{encoding:?}"
+        );
+    }
+}
+
+#[test]
+fn test_mouse_buttons_and_modifiers() {
+    let sgr = |action, modifiers| {
+        let report = mouse(action, modifiers, &MouseTrack::XtMseAny, MouseEncoding::Sgr);
+        String::from_utf8(report.unwrap()).unwrap()
+    };
+
+    assert_eq!(
+        sgr(MouseAction::Press(MouseButton::Middle), KeyModifiers::NONE),
+        "\x1b[<1;5;10M"
+    );
+    assert_eq!(
+        sgr(MouseAction::Press(MouseButton::Forward), KeyModifiers::NONE),
+        "\x1b[<129;5;10M"
+    );
+    assert_eq!(
+        sgr(
+            MouseAction::Press(MouseButton::Left),
+            KeyModifiers::SHIFT | KeyModifiers::ALT | KeyModifiers::CTRL
+        ),
+        "\x1b[<28;5;10M"
+    );
+    assert_eq!(
+        sgr(MouseAction::Drag(MouseButton::Left), KeyModifiers::NONE),
+        "\x1b[<32;5;10M"
+    );
+    assert_eq!(sgr(MouseAction::Move, KeyModifiers::NONE), "\x1b[<35;5;10M");
+    assert_eq!(
+        sgr(MouseAction::Wheel((0.0, 1.0)), KeyModifiers::NONE),
+        "\x1b[<64;5;10M"
+    );
+    assert_eq!(
+        sgr(MouseAction::Wheel((0.0, -1.0)), KeyModifiers::CTRL),
+        "\x1b[<81;5;10M"
+    );
+    assert_eq!(
+        sgr(MouseAction::Wheel((-2.0, 0.0)), KeyModifiers::NONE),
+        "\x1b[<67;5;10M"
+    );
+    assert!(mouse(
+        MouseAction::Wheel((0.0, 0.0)),
+        KeyModifiers::NONE,
+        &MouseTrack::XtMseAny,
+        MouseEncoding::Sgr
+    )
+    .is_none());
+}
+
+#[test]
+fn test_mouse_tracking_modes() {
+    let actions = [
+        MouseAction::Press(MouseButton::Left),
+        MouseAction::Release(MouseButton::Left),
+        MouseAction::Drag(MouseButton::Left),
+        MouseAction::Move,
+        MouseAction::Wheel((0.0, 1.0)),
+    ];
+    let cases = [
+        (MouseTrack::NoTracking, [false, false, false, false, false]),
+        (MouseTrack::XtMsex10, [true, false, false, false, false]),
+        (MouseTrack::XtMseX11, [true, true, false, false, true]),
+        (MouseTrack::XtMseBtn, [true, true, true, false, true]),
+        (MouseTrack::XtMseAny, [true, true, true, true, true]),
+        (
+            MouseTrack::XtMseSgrPixels,
+            [false, false, false, false, false],
+        ),
+    ];
+
+    for (tracking, reported) in cases {
+        for (action, reported) in actions.into_iter().zip(reported) {
+            assert_eq!(
+                mouse(action, KeyModifiers::NONE, &tracking, MouseEncoding::Sgr).is_some(),
+                reported,
+                "{tracking} {action:?}"
+            );
+        }
+    }
+
+    // X10 mode leaves out the modifiers
+    assert_eq!(
+        mouse(
+            MouseAction::Press(MouseButton::Left),
+            KeyModifiers::CTRL,
+            &MouseTrack::XtMsex10,
+            MouseEncoding::Sgr
+        )
+        .unwrap(),
+        b"\x1b[<0;5;10M"
+    );
+}
+
+#[test]
+fn test_mouse_far_cells() {
+    let press = MouseAction::Press(MouseButton::Left);
+    let report = |column, encoding| {
+        encode_mouse_report(
+            press,
+            KeyModifiers::NONE,
+            column,
+            0,
+            &MouseTrack::XtMseX11,
+            encoding,
+        )
+    };
+
+    // A byte only goes up to column 223
+    assert_eq!(report(222, MouseEncoding::X11).unwrap(), b"\x1b[M \xff!");
+    assert!(report(223, MouseEncoding::X11).is_none());
+
+    // UTF-8 goes further, with two bytes for the far columns
+    assert_eq!(
+        report(300, MouseEncoding::Utf8).unwrap(),
+        "\x1b[M \u{14d}!".as_bytes()
+    );
+    assert!(report(2015, MouseEncoding::Utf8).is_none());
+
+    // SGR and URXVT write the numbers out
+    assert_eq!(
+        report(1000, MouseEncoding::Sgr).unwrap(),
+        b"\x1b[<0;1001;1M"
+    );
+    assert_eq!(
+        report(1000, MouseEncoding::Urxvt).unwrap(),
+        b"\x1b[32;1001;1M"
+    );
+}
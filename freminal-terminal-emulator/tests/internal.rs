@@ -3,45 +3,136 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
 use test_log::test;
 
-use eframe::egui::Context;
-use freminal_common::colors::TerminalColor;
+mod common;
+use common::TinyRng;
+
+use freminal_common::{
+    args::UNLIMITED_SCROLLBACK_LINES,
+    cell_geometry::{SizeLimits, TerminalGeometry},
+    char_width::{UnicodeVersion, WidthProvider},
+    colors::{Palette, TerminalColor, Theme},
+    cursor::{CursorSettings, CursorVisualStyle},
+    metrics::{Metrics, SharedInputLatency},
+    scroll::{ScrollAccumulator, ScrollSettings, ViewportMovement},
+    text_classes::{UrlSchemes, WordCharacters},
+    window_manipulation::WindowManipulation,
+};
 use freminal_terminal_emulator::{
     ansi::FreminalAnsiParser,
     ansi_components::{
-        line_draw::DecSpecialGraphics, mode::TerminalModes, modes::decckm::Decckm,
+        line_draw::DecSpecialGraphics,
+        mode::{MouseEncoding, MouseTrack, TerminalModes},
+        modes::{
+            bdsm::Bdsm, decawm::Decawm, decckm::Decckm, decnkm::Decnkm, decom::Decom,
+            dectcem::Dectcem, rl_bracket::RlBracket, sync_updates::SynchronizedUpdates,
+            xtmsewin::XtMseWin,
+        },
         sgr::SelectGraphicRendition,
     },
+    format_tracker::FormatTag,
+    input::KeyboardProtocol,
     interface::TerminalInput,
-    io::PtyWrite,
+    io::{PtySignal, PtyWrite},
     state::{
+        bell::BellState,
         cursor::{CursorPos, CursorState, ReverseVideo, StateColors},
+        echo_prediction::EchoPrediction,
         fonts::{FontDecorations, FontWeight},
-        internal::{Buffer, BufferType, TerminalState, TERMINAL_HEIGHT, TERMINAL_WIDTH},
+        internal::{
+            Buffer, BufferType, Hyperlink, TerminalState, STARTUP_BANNER_TIMEOUT,
+            SYNC_UPDATE_TIMEOUT, TERMINAL_HEIGHT, TERMINAL_WIDTH, VISITED_LINKS_LIMIT,
+        },
+        kitty_keyboard::MAX_PUSHED_FLAGS,
+        redraw::RedrawNotifier,
+        repaint::RepaintLimiter,
+        search::SearchState,
         term_char::{display_vec_tchar_as_string, TChar},
+        term_warning::TermWarning,
+        unsupported::UnsupportedFeatures,
     },
 };
 
+/// Counts the frames the terminal asks for
+#[derive(Debug, Default)]
+struct CountingRedrawNotifier {
+    redraws: AtomicUsize,
+    delayed_redraws: AtomicUsize,
+}
+
+impl RedrawNotifier for CountingRedrawNotifier {
+    fn request_redraw(&self) {
+        self.redraws.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn request_redraw_after(&self, _delay: Duration) {
+        self.delayed_redraws.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
 #[test]
 fn test_internal_terminal_state_new() {
     let (tx, _rx) = crossbeam_channel::unbounded();
     let mut terminal_state = TerminalState::new(tx.clone());
-    let ctx = Context::default();
     let expected = TerminalState {
         parser: FreminalAnsiParser::new(),
         primary_buffer: Buffer::new(TERMINAL_WIDTH, TERMINAL_HEIGHT, BufferType::Primary),
         alternate_buffer: Buffer::new(TERMINAL_WIDTH, TERMINAL_HEIGHT, BufferType::Alternate),
         modes: TerminalModes::default(),
+        saved_private_modes: BTreeMap::new(),
         write_tx: tx,
         changed: false,
-        ctx: None,
+        redraw_notifier: None,
         leftover_data: None,
         character_replace: DecSpecialGraphics::DontReplace,
         current_buffer: BufferType::Primary,
         mouse_position: None,
         window_focused: true,
+        window_visible: true,
         window_commands: vec![],
+        repaint_limiter: RepaintLimiter::default(),
+        metrics: Metrics::new(),
+        input_latency: SharedInputLatency::new(),
+        word_characters: WordCharacters::default(),
+        url_schemes: UrlSchemes::default(),
+        scroll_settings: ScrollSettings::default(),
+        cursor_settings: CursorSettings::default(),
+        requested_cursor_style: None,
+        scroll_accumulator: ScrollAccumulator::new(),
+        column_scroll_accumulator: ScrollAccumulator::new(),
+        viewport_scrolled_lines: 0,
+        bell: BellState::new(),
+        trim_trailing_spaces_on_copy: true,
+        clamp_colors_to_palette: false,
+        palette: Palette::default(),
+        user_palette: Palette::default(),
+        scrollback_on_clear: true,
+        bidi_enabled: false,
+        fuzz_corpus: None,
+        output_processors: Vec::new(),
+        redactor: None,
+        working_directory: None,
+        sync_update_started: None,
+        hide_startup_banner_until: None,
+        term_warning: TermWarning::new(false),
+        hovered_link: None,
+        visited_links: VecDeque::new(),
+        style_visited_links: true,
+        search: SearchState::default(),
+        echo_prediction: EchoPrediction::default(),
+        size_limits: SizeLimits::default(),
+        unsupported: UnsupportedFeatures::default(),
     };
 
     assert_eq!(terminal_state, expected);
@@ -59,9 +150,9 @@ fn test_internal_terminal_state_new() {
 
     assert!(!terminal_state.is_changed());
 
-    // test set_ctx()
-    terminal_state.set_ctx(ctx);
-    assert!(terminal_state.ctx.is_some());
+    // test set_redraw_notifier()
+    terminal_state.set_redraw_notifier(Arc::new(CountingRedrawNotifier::default()));
+    assert!(terminal_state.redraw_notifier.is_some());
 
     // get the window size
     let (width, height) = terminal_state.get_win_size();
@@ -244,6 +335,8 @@ fn test_clear_display_from_start_of_display_to_cursor() {
 fn test_clear_display() {
     let (tx, _rx) = crossbeam_channel::unbounded();
     let mut terminal_state = TerminalState::new(tx.clone());
+    // ED 2 blanks the screen in place, rather than scrolling it away
+    terminal_state.scrollback_on_clear = false;
     terminal_state.set_win_size(30, 5);
     // send the control code to clear the display
     // ESC [ Pn J
@@ -600,9 +693,9 @@ fn test_sgr_sequences() {
 
         terminal_state.handle_incoming_data(&data);
         let expectedsgr = match i {
-            38 => SelectGraphicRendition::Foreground(TerminalColor::Custom(0, 0, 0)),
-            48 => SelectGraphicRendition::Background(TerminalColor::Custom(0, 0, 0)),
-            58 => SelectGraphicRendition::UnderlineColor(TerminalColor::Custom(0, 0, 0)),
+            38 => SelectGraphicRendition::Foreground(TerminalColor::Black),
+            48 => SelectGraphicRendition::Background(TerminalColor::Black),
+            58 => SelectGraphicRendition::UnderlineColor(TerminalColor::Black),
             _ => SelectGraphicRendition::from_usize(i as usize),
         };
         // now verify that the SGR sequence changed the cursor state
@@ -784,3 +877,2034 @@ fn test_sgr_sequences() {
         terminal_state.get_current_buffer().cursor_state = CursorState::default();
     }
 }
+
+#[test]
+fn test_scroll_to_previous_failed_command() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // nothing has run yet, so there is nothing to jump to
+    assert!(terminal_state.scroll_to_previous_failed_command().is_none());
+
+    // a command that fails
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07$ false\r\n\x1b]133;C\x07");
+    terminal_state.handle_incoming_data(b"failed output\r\n\x1b]133;D;1\x07");
+    // a command that succeeds and scrolls the failure off of the screen
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07$ seq 30\r\n\x1b]133;C\x07");
+    for i in 0..30 {
+        terminal_state.handle_incoming_data(format!("{i}\r\n").as_bytes());
+    }
+    terminal_state.handle_incoming_data(b"\x1b]133;D;0\x07");
+
+    let marks = terminal_state
+        .get_current_buffer()
+        .command_history
+        .marks()
+        .to_vec();
+    assert_eq!(marks.len(), 2);
+    assert_eq!(marks[0].exit_code, Some(1));
+    assert!(marks[0].failed());
+    assert_eq!(marks[1].exit_code, Some(0));
+    assert!(!marks[1].failed());
+
+    // the failed output is not on screen before we jump
+    assert!(terminal_state
+        .visible_rows_for_buf_range(&marks[0].output_range())
+        .is_none());
+
+    let range = terminal_state.scroll_to_previous_failed_command().unwrap();
+    assert_eq!(range, marks[0].output_range());
+    assert_eq!(
+        terminal_state.visible_rows_for_buf_range(&range),
+        Some(0..1)
+    );
+
+    let (data, _, _) = terminal_state
+        .get_current_buffer()
+        .terminal_buffer
+        .data_for_gui();
+    assert!(display_vec_tchar_as_string(&data.visible).starts_with("failed output"));
+
+    // there is no earlier failure
+    assert!(terminal_state.scroll_to_previous_failed_command().is_none());
+}
+
+#[test]
+fn test_running_command_rows() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 10);
+
+    // Only between the shell's C and D marks
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07$ ls\r\n");
+    assert_eq!(terminal_state.running_command_rows(), None);
+    terminal_state.handle_incoming_data(b"\x1b]133;C\x07one\r\ntwo\r\n");
+    assert_eq!(terminal_state.running_command_rows(), Some(1..3));
+    terminal_state.handle_incoming_data(b"\x1b]133;D;0\x07");
+    assert_eq!(terminal_state.running_command_rows(), None);
+
+    // The output so far of a command still running, scrolled along with the screen
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07$ seq 20\r\n\x1b]133;C\x07");
+    // Nothing to show until it writes something
+    assert_eq!(terminal_state.running_command_rows(), None);
+    terminal_state.handle_incoming_data(b"start");
+    assert_eq!(terminal_state.running_command_rows(), Some(4..5));
+    terminal_state.handle_incoming_data(b"\r\n");
+    for i in 0..20 {
+        terminal_state.handle_incoming_data(format!("{i}\r\n").as_bytes());
+    }
+    assert_eq!(terminal_state.running_command_rows(), Some(0..10));
+
+    // Scrolled back to before it started, it is further down
+    terminal_state.scroll(20.0);
+    let rows = terminal_state.running_command_rows().unwrap();
+    assert!(rows.start > 0);
+
+    assert!(terminal_state
+        .get_current_buffer()
+        .command_history
+        .running()
+        .is_some());
+}
+
+#[test]
+fn test_scroll_reports_viewport_movement() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    for i in 0..60 {
+        terminal_state.handle_incoming_data(format!("{i}\r\n").as_bytes());
+    }
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), 0);
+
+    // fractions of a line don't move the viewport until they add up
+    terminal_state.scroll(0.5);
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), 0);
+    terminal_state.scroll(0.5);
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), 1);
+
+    terminal_state.scroll(3.0);
+    terminal_state.scroll(-1.0);
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), 2);
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), 0);
+
+    // scrolling past the bottom only moves as far as the bottom
+    terminal_state.scroll(-10.0);
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), -3);
+}
+
+#[test]
+fn test_bell_counts_while_unfocused() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    terminal_state.handle_incoming_data(b"\x07");
+    assert_eq!(terminal_state.bell.unfocused_bells(), 0);
+
+    terminal_state.set_window_focused(false);
+    terminal_state.handle_incoming_data(b"a\x07b\x07");
+    assert_eq!(terminal_state.bell.unfocused_bells(), 2);
+    assert!(terminal_state.bell.take_attention_request());
+
+    terminal_state.set_window_focused(true);
+    assert_eq!(terminal_state.bell.unfocused_bells(), 0);
+}
+
+#[test]
+fn test_selection_text_joins_soft_wraps() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // 60 characters wraps on to a second row in a 50 wide terminal
+    let long_line = "0123456789".repeat(6);
+    terminal_state.handle_incoming_data(format!("{long_line}\r\nabc   \r\ndef").as_bytes());
+
+    let text = terminal_state.selection_text(&CursorPos { x: 0, y: 0 }, &CursorPos { x: 2, y: 3 });
+    assert_eq!(text, format!("{long_line}\nabc\ndef"));
+
+    // The selection can be made backwards
+    let text = terminal_state.selection_text(&CursorPos { x: 1, y: 3 }, &CursorPos { x: 45, y: 0 });
+    assert_eq!(text, "567890123456789\nabc\nde");
+}
+
+#[test]
+fn test_selection_text_keeps_trailing_spaces() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.trim_trailing_spaces_on_copy = false;
+
+    terminal_state.handle_incoming_data(b"abc   \r\ndef");
+
+    let text = terminal_state.selection_text(&CursorPos { x: 0, y: 0 }, &CursorPos { x: 2, y: 1 });
+    assert_eq!(text, "abc   \ndef");
+}
+
+#[test]
+fn test_selection_text_strips_continuation_prompts() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07$ \x1b]133;B\x07echo one \\\r\n");
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07> \x1b]133;B\x07two \\\r\n");
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07> \x1b]133;B\x07three\r\n");
+    terminal_state.handle_incoming_data(b"\x1b]133;C\x07one two three\r\n\x1b]133;D;0\x07");
+    // The next prompt is not a continuation of the last command
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07> \x1b]133;B\x07");
+
+    let text = terminal_state.selection_text(&CursorPos { x: 0, y: 0 }, &CursorPos { x: 1, y: 4 });
+    assert_eq!(text, "$ echo one \\\ntwo \\\nthree\none two three\n>");
+
+    // Starting the selection inside a continuation prompt
+    let text = terminal_state.selection_text(&CursorPos { x: 1, y: 1 }, &CursorPos { x: 6, y: 2 });
+    assert_eq!(text, "two \\\nthree");
+}
+
+#[test]
+fn test_request_status_string_sgr() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    terminal_state.handle_incoming_data(b"\x1b[1;32m\x1bP$qm\x1b\\");
+
+    let mut received = String::new();
+    while let Ok(PtyWrite::Write(v)) = rx.try_recv() {
+        received.push_str(&String::from_utf8(v).unwrap());
+    }
+    assert_eq!(received, "\x1bP1$r0;1;32m\x1b\\");
+
+    terminal_state.handle_incoming_data(b"\x1bP$qr\x1b\\");
+
+    let mut received = String::new();
+    while let Ok(PtyWrite::Write(v)) = rx.try_recv() {
+        received.push_str(&String::from_utf8(v).unwrap());
+    }
+    assert_eq!(received, "\x1bP0$r\x1b\\");
+}
+
+fn tag_at(terminal_state: &mut TerminalState, pos: usize) -> FormatTag {
+    terminal_state
+        .get_current_buffer()
+        .format_tracker
+        .tags()
+        .into_iter()
+        .find(|tag| tag.start <= pos && pos < tag.end)
+        .unwrap()
+}
+
+#[test]
+fn test_erase_characters_in_place() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // red text, then erase two cells in the middle with a blue background set
+    terminal_state.handle_incoming_data(b"\x1b[31mabcdef\x1b[44m\x1b[1;3H\x1b[2X");
+
+    let buffer = terminal_state
+        .get_current_buffer()
+        .terminal_buffer
+        .data(true);
+    assert_eq!(display_vec_tchar_as_string(&buffer.visible), "ab  ef\n");
+
+    // Nothing after the erased cells moved
+    let tag = tag_at(&mut terminal_state, 4);
+    assert_eq!(tag.colors.color, TerminalColor::Red);
+    assert_eq!(
+        tag.colors.background_color,
+        TerminalColor::DefaultBackground
+    );
+
+    // The erased cells only keep the background color
+    let tag = tag_at(&mut terminal_state, 2);
+    assert_eq!((tag.start, tag.end), (2, 4));
+    assert_eq!(tag.colors.color, TerminalColor::Default);
+    assert_eq!(tag.colors.background_color, TerminalColor::Blue);
+
+    let tag = tag_at(&mut terminal_state, 1);
+    assert_eq!(tag.colors.color, TerminalColor::Red);
+}
+
+#[test]
+fn test_erase_characters_stops_at_end_of_row() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // 60 characters wraps on to a second row in a 50 wide terminal
+    let long_line = "0123456789".repeat(6);
+    terminal_state.handle_incoming_data(format!("{long_line}\x1b[1;46H\x1b[20X").as_bytes());
+
+    let buffer = terminal_state
+        .get_current_buffer()
+        .terminal_buffer
+        .data(true);
+    let expected = format!("{}     {}\n", &long_line[..45], &long_line[50..]);
+    assert_eq!(display_vec_tchar_as_string(&buffer.visible), expected);
+}
+
+#[test]
+fn test_no_auto_wrap() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    let long_line = "0123456789".repeat(6);
+    terminal_state.handle_incoming_data(format!("\x1b[?7l{long_line}").as_bytes());
+
+    let buffer = terminal_state
+        .get_current_buffer()
+        .terminal_buffer
+        .data(true);
+    let expected = format!("{}9\n", &long_line[..49]);
+    assert_eq!(display_vec_tchar_as_string(&buffer.visible), expected);
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 49, y: 0 }
+    );
+
+    // Turning wrapping back on wraps again
+    terminal_state.handle_incoming_data(b"\x1b[?7hab");
+    let buffer = terminal_state
+        .get_current_buffer()
+        .terminal_buffer
+        .data(true);
+    let expected = format!("{}ab\n", &long_line[..49]);
+    assert_eq!(display_vec_tchar_as_string(&buffer.visible), expected);
+}
+
+#[test]
+fn test_cursor_visual_style() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    assert!(!terminal_state.cursor_blinks());
+
+    terminal_state.handle_incoming_data(b"\x1b[6 q");
+    assert_eq!(
+        terminal_state.cursor_visual_style(),
+        CursorVisualStyle::VerticalLineCursorSteady
+    );
+    assert!(!terminal_state.cursor_blinks());
+
+    // Mode 12 makes a steady cursor blink
+    terminal_state.handle_incoming_data(b"\x1b[?12h");
+    assert!(terminal_state.cursor_blinks());
+    terminal_state.handle_incoming_data(b"\x1b[?12l");
+
+    terminal_state.handle_incoming_data(b"\x1b[3 q");
+    assert_eq!(
+        terminal_state.cursor_visual_style(),
+        CursorVisualStyle::UnderlineCursorBlink
+    );
+    assert!(terminal_state.cursor_blinks());
+}
+
+#[test]
+fn test_cursor_style_default_and_decscusr() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.cursor_settings.style = CursorVisualStyle::VerticalLineCursorBlink;
+    assert_eq!(
+        terminal_state.cursor_visual_style(),
+        CursorVisualStyle::VerticalLineCursorBlink
+    );
+
+    // An application can pick its own cursor for a while
+    terminal_state.handle_incoming_data(b"\x1b[2 q");
+    assert_eq!(
+        terminal_state.cursor_visual_style(),
+        CursorVisualStyle::BlockCursorSteady
+    );
+
+    // DECSCUSR 0 goes back to the user's cursor, not xterm's blinking block
+    terminal_state.handle_incoming_data(b"\x1b[0 q");
+    assert_eq!(
+        terminal_state.cursor_visual_style(),
+        CursorVisualStyle::VerticalLineCursorBlink
+    );
+
+    // So does a soft reset
+    terminal_state.handle_incoming_data(b"\x1b[4 q\x1b[?25l\x1b[!p");
+    assert_eq!(terminal_state.requested_cursor_style, None);
+    assert_eq!(
+        terminal_state.cursor_visual_style(),
+        CursorVisualStyle::VerticalLineCursorBlink
+    );
+    assert_eq!(terminal_state.primary_buffer.show_cursor, Dectcem::Show);
+}
+
+#[test]
+fn test_move_viewport() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+
+    let lines: String = (1..=20).map(|i| format!("{i}\r\n")).collect();
+    terminal_state.handle_incoming_data(lines.as_bytes());
+    let (line, total) = terminal_state.viewport_position();
+    assert_eq!(line, total);
+
+    terminal_state.move_viewport(ViewportMovement::PageUp);
+    assert_eq!(terminal_state.viewport_position(), (total - 5, total));
+
+    terminal_state.move_viewport(ViewportMovement::Top);
+    assert_eq!(terminal_state.viewport_position(), (5, total));
+
+    terminal_state.move_viewport(ViewportMovement::PageDown);
+    assert_eq!(terminal_state.viewport_position(), (10, total));
+
+    terminal_state.move_viewport(ViewportMovement::Bottom);
+    assert_eq!(terminal_state.viewport_position(), (total, total));
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), 0);
+
+    // The alternate screen has no scrollback
+    terminal_state.handle_incoming_data(b"\x1b[?1049h");
+    assert!(!terminal_state.has_scrollback());
+}
+
+#[test]
+fn test_positions_while_scrolled_back() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+
+    let lines: String = (1..=20).map(|i| format!("{i}\r\n")).collect();
+    terminal_state.handle_incoming_data(lines.as_bytes());
+    terminal_state.handle_incoming_data(b"\x1b[3;4H");
+    assert_eq!(terminal_state.scrolled_back_lines(), 0);
+    assert_eq!(terminal_state.viewport_row_to_screen_row(2), Some(2));
+
+    terminal_state.scroll(2.0);
+    assert_eq!(terminal_state.scrolled_back_lines(), 2);
+    // The top two rows of the viewport are scrollback, the rest is the top of the live screen
+    assert_eq!(terminal_state.viewport_row_to_screen_row(0), None);
+    assert_eq!(terminal_state.viewport_row_to_screen_row(1), None);
+    assert_eq!(terminal_state.viewport_row_to_screen_row(2), Some(0));
+    assert_eq!(terminal_state.viewport_row_to_screen_row(4), Some(2));
+
+    // The cursor is reported where it is on the live screen, wherever the viewport is
+    rx.try_iter().for_each(drop);
+    terminal_state.handle_incoming_data(b"\x1b[6n");
+    let written: Vec<u8> = rx
+        .try_iter()
+        .flat_map(|write| match write {
+            PtyWrite::Write(v) => v,
+            PtyWrite::Resize(_) | PtyWrite::Signal(_) => Vec::new(),
+        })
+        .collect();
+    assert!(written.starts_with(b"\x1b[3;4R"));
+    // Replying to the application doesn't move the viewport
+    assert_eq!(terminal_state.scrolled_back_lines(), 2);
+
+    terminal_state.snap_to_bottom();
+    assert_eq!(terminal_state.scrolled_back_lines(), 0);
+    let (line, total) = terminal_state.viewport_position();
+    assert_eq!(line, total);
+
+    // Snapping when already at the bottom does nothing
+    terminal_state.take_viewport_scrolled_lines();
+    terminal_state.snap_to_bottom();
+    assert_eq!(terminal_state.take_viewport_scrolled_lines(), 0);
+}
+
+#[test]
+fn test_cursor_coordinates() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+    let reported = |terminal_state: &mut TerminalState| {
+        rx.try_iter().for_each(drop);
+        terminal_state.handle_incoming_data(b"\x1b[6n");
+        replies(&rx)
+    };
+
+    // CUP and the report count from 1, the positions we keep count from 0
+    terminal_state.handle_incoming_data(b"\x1b[1;1H");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 0, y: 0 });
+    assert_eq!(terminal_state.reported_cursor_pos(), (1, 1));
+    assert!(reported(&mut terminal_state).starts_with("\x1b[1;1R"));
+
+    terminal_state.handle_incoming_data(b"\x1b[5;20H");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 19, y: 4 });
+    assert_eq!(terminal_state.reported_cursor_pos(), (5, 20));
+    assert_eq!(
+        terminal_state.viewport_cursor_pos(),
+        Some(CursorPos { x: 19, y: 4 })
+    );
+
+    // Origin mode reports rows from the top margin, the cursor is still kept on the screen
+    terminal_state.handle_incoming_data(b"\x1b[2;4r\x1b[?6h\x1b[2;3H");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 2, y: 2 });
+    assert_eq!(terminal_state.reported_cursor_pos(), (2, 3));
+    assert!(reported(&mut terminal_state).starts_with("\x1b[2;3R"));
+    assert_eq!(
+        terminal_state.viewport_cursor_pos(),
+        Some(CursorPos { x: 2, y: 2 })
+    );
+    terminal_state.handle_incoming_data(b"\x1b[?6l\x1b[r");
+    assert_eq!(terminal_state.reported_cursor_pos(), (1, 1));
+
+    // Scrolling the viewport back moves where the cursor is drawn, not where it is
+    let lines: String = (1..=20).map(|i| format!("{i}\r\n")).collect();
+    terminal_state.handle_incoming_data(lines.as_bytes());
+    terminal_state.handle_incoming_data(b"\x1b[2;4H");
+    terminal_state.scroll(2.0);
+    assert_eq!(terminal_state.scrolled_back_lines(), 2);
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 3, y: 1 });
+    assert_eq!(terminal_state.reported_cursor_pos(), (2, 4));
+    assert_eq!(terminal_state.screen_row_to_viewport_row(1), Some(3));
+    assert_eq!(terminal_state.viewport_row_to_screen_row(3), Some(1));
+    assert_eq!(
+        terminal_state.viewport_cursor_pos(),
+        Some(CursorPos { x: 3, y: 3 })
+    );
+
+    // Far enough back and the cursor's row isn't shown at all
+    assert_eq!(terminal_state.screen_row_to_viewport_row(3), None);
+    terminal_state.scroll(2.0);
+    assert_eq!(terminal_state.viewport_cursor_pos(), None);
+    assert_eq!(terminal_state.reported_cursor_pos(), (2, 4));
+
+    terminal_state.snap_to_bottom();
+    assert_eq!(
+        terminal_state.viewport_cursor_pos(),
+        Some(CursorPos { x: 3, y: 1 })
+    );
+}
+
+#[test]
+fn test_keypad_and_backarrow_modes() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    let written = |rx: &crossbeam_channel::Receiver<PtyWrite>| -> Vec<u8> {
+        rx.try_iter()
+            .flat_map(|write| match write {
+                PtyWrite::Write(v) => v,
+                PtyWrite::Resize(_) | PtyWrite::Signal(_) => Vec::new(),
+            })
+            .collect()
+    };
+
+    terminal_state.write(&TerminalInput::Backspace).unwrap();
+    assert_eq!(written(&rx), b"\x7f");
+
+    terminal_state.handle_incoming_data(b"\x1b[?67h");
+    terminal_state.write(&TerminalInput::Backspace).unwrap();
+    assert_eq!(written(&rx), b"\x08");
+
+    terminal_state.handle_incoming_data(b"\x1b[?67$p");
+    assert_eq!(written(&rx), b"\x1b[?67;1$y");
+
+    terminal_state.write(&TerminalInput::KeyPad(1)).unwrap();
+    assert_eq!(written(&rx), b"1");
+
+    terminal_state.handle_incoming_data(b"\x1b[?66h");
+    terminal_state.write(&TerminalInput::KeyPad(1)).unwrap();
+    assert_eq!(written(&rx), b"\x1bOq");
+
+    // DECKPNM and DECKPAM switch the same mode
+    terminal_state.handle_incoming_data(b"\x1b>");
+    terminal_state.handle_incoming_data(b"\x1b[?66$p");
+    assert_eq!(written(&rx), b"\x1b[?66;2$y");
+    terminal_state.write(&TerminalInput::KeyPad(b'\n')).unwrap();
+    assert_eq!(written(&rx), b"\r");
+    terminal_state.handle_incoming_data(b"\x1b=");
+    assert_eq!(terminal_state.modes.keypad, Decnkm::Application);
+    assert_eq!(terminal_state.get_cursor_key_mode(), Decckm::Ansi);
+    terminal_state.write(&TerminalInput::KeyPad(b'\n')).unwrap();
+    assert_eq!(written(&rx), b"\x1bOM");
+
+    terminal_state.handle_incoming_data(b"\x1b[?66l");
+    terminal_state.write(&TerminalInput::KeyPad(b'.')).unwrap();
+    assert_eq!(written(&rx), b".");
+}
+
+#[test]
+fn test_keyboard_protocols() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    assert_eq!(
+        terminal_state.keyboard_protocol(),
+        KeyboardProtocol::default()
+    );
+
+    terminal_state.handle_incoming_data(b"\x1b[>4;2m");
+    assert_eq!(terminal_state.keyboard_protocol().modify_other_keys, 2);
+    terminal_state.handle_incoming_data(b"\x1b[>4n");
+    assert_eq!(terminal_state.keyboard_protocol().modify_other_keys, 0);
+
+    // Flags we don't support are dropped, so the query says what is really in use
+    terminal_state.handle_incoming_data(b"\x1b[?u");
+    assert_eq!(replies(&rx), "\x1b[?0u");
+    terminal_state.handle_incoming_data(b"\x1b[>3u\x1b[?u");
+    assert_eq!(replies(&rx), "\x1b[?1u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 1);
+
+    // Popping goes back to the flags in use before
+    terminal_state.handle_incoming_data(b"\x1b[>0u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 0);
+    terminal_state.handle_incoming_data(b"\x1b[<u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 1);
+    terminal_state.handle_incoming_data(b"\x1b[=1;3u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 0);
+    terminal_state.handle_incoming_data(b"\x1b[=1;2u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 1);
+
+    // Each screen has its own flags, and the alternate screen starts without any
+    terminal_state.handle_incoming_data(b"\x1b[?1049h");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 0);
+    terminal_state.handle_incoming_data(b"\x1b[>1u\x1b[<5u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 0);
+    terminal_state.handle_incoming_data(b"\x1b[>1u\x1b[?1049l");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 1);
+
+    // Pushing too much forgets the oldest
+    for _ in 0..MAX_PUSHED_FLAGS {
+        terminal_state.handle_incoming_data(b"\x1b[>0u");
+    }
+    terminal_state.handle_incoming_data(b"\x1b[<15u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 0);
+    terminal_state.handle_incoming_data(b"\x1b[<u");
+    assert_eq!(terminal_state.keyboard_protocol().kitty_flags, 0);
+
+    // A program that dies with them on doesn't leave them on after DECSTR
+    terminal_state.handle_incoming_data(b"\x1b[>1u\x1b[>4;1m\x1b[!p");
+    assert_eq!(
+        terminal_state.keyboard_protocol(),
+        KeyboardProtocol::default()
+    );
+}
+
+#[test]
+fn test_mouse_modes() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // Tracking and encoding are separate, so turning on SGR keeps the tracking mode
+    terminal_state.handle_incoming_data(b"\x1b[?1002h\x1b[?1006h");
+    assert_eq!(terminal_state.modes.mouse_tracking, MouseTrack::XtMseBtn);
+    assert_eq!(terminal_state.modes.mouse_encoding, MouseEncoding::Sgr);
+
+    terminal_state.handle_incoming_data(b"\x1b[?1002$p\x1b[?1006$p\x1b[?1000$p\x1b[?1015$p");
+    assert_eq!(
+        replies(&rx),
+        "\x1b[?1002;1$y\x1b[?1006;1$y\x1b[?1000;2$y\x1b[?1015;2$y"
+    );
+
+    terminal_state.handle_incoming_data(b"\x1b[?1015h");
+    assert_eq!(terminal_state.modes.mouse_encoding, MouseEncoding::Urxvt);
+    terminal_state.handle_incoming_data(b"\x1b[?1015l");
+    assert_eq!(terminal_state.modes.mouse_encoding, MouseEncoding::X11);
+    assert_eq!(terminal_state.modes.mouse_tracking, MouseTrack::XtMseBtn);
+
+    terminal_state.handle_incoming_data(b"\x1b[?1002l");
+    assert_eq!(terminal_state.modes.mouse_tracking, MouseTrack::NoTracking);
+
+    // Pixel positions aren't supported
+    terminal_state.handle_incoming_data(b"\x1b[?1016h");
+    assert_eq!(terminal_state.modes.mouse_tracking, MouseTrack::NoTracking);
+    assert!(!terminal_state.unsupported.is_empty());
+}
+
+#[test]
+fn test_bdsm_mode() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    assert_eq!(terminal_state.modes.bidi, Bdsm::Implicit);
+
+    // BDSM is an ANSI mode, so it has no `?`
+    terminal_state.handle_incoming_data(b"\x1b[8l");
+    assert_eq!(terminal_state.modes.bidi, Bdsm::Explicit);
+
+    rx.try_iter().for_each(drop);
+    terminal_state.handle_incoming_data(b"\x1b[8$p");
+    let written: Vec<u8> = rx
+        .try_iter()
+        .flat_map(|write| match write {
+            PtyWrite::Write(v) => v,
+            PtyWrite::Resize(_) | PtyWrite::Signal(_) => Vec::new(),
+        })
+        .collect();
+    assert_eq!(written, b"\x1b[8;2$y");
+
+    terminal_state.handle_incoming_data(b"\x1b[8h");
+    assert_eq!(terminal_state.modes.bidi, Bdsm::Implicit);
+}
+
+#[test]
+fn test_origin_mode() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    terminal_state.handle_incoming_data(b"\x1b[5;10r\x1b[?6h");
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 0, y: 4 }
+    );
+
+    // Positions are counted from the top margin, and can't leave the scrolling region
+    terminal_state.handle_incoming_data(b"\x1b[2;3H");
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 2, y: 5 }
+    );
+    terminal_state.handle_incoming_data(b"\x1b[20;1H");
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 0, y: 9 }
+    );
+
+    rx.try_iter().for_each(drop);
+    terminal_state.handle_incoming_data(b"\x1b[6n");
+    let written: Vec<u8> = rx
+        .try_iter()
+        .flat_map(|write| match write {
+            PtyWrite::Write(v) => v,
+            PtyWrite::Resize(_) | PtyWrite::Signal(_) => Vec::new(),
+        })
+        .collect();
+    assert!(written.starts_with(b"\x1b[6;1R"));
+
+    terminal_state.handle_incoming_data(b"\x1b[?6l");
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 0, y: 0 }
+    );
+}
+
+#[test]
+fn test_alternate_scrollback() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+    terminal_state.set_win_size(10, 3);
+
+    // By default what scrolls off the alternate screen is gone, and scrolling sends arrow keys
+    terminal_state.handle_incoming_data(b"\x1b[?1049h1\r\n2\r\n3\r\n4\r\n5");
+    assert!(!terminal_state.has_scrollback());
+    assert_eq!(terminal_state.viewport_position(), (3, 3));
+    terminal_state.scroll(1.0);
+    assert_eq!(replies(&rx), "\x1b[A");
+    assert_eq!(viewport_text(&mut terminal_state), vec!["3", "4", "5"]);
+
+    // Unless the program turns alternate scroll mode off, when scrolling does nothing
+    terminal_state.handle_incoming_data(b"\x1b[?1007l\x1b[?1007$p");
+    assert_eq!(replies(&rx), "\x1b[?1007;2$y");
+    terminal_state.scroll(3.0);
+    assert!(replies(&rx).is_empty());
+    assert_eq!(viewport_text(&mut terminal_state), vec!["3", "4", "5"]);
+    terminal_state.handle_incoming_data(b"\x1b[?1007h");
+    terminal_state.scroll(-1.0);
+    assert_eq!(replies(&rx), "\x1b[B");
+
+    // With some scrollback it can be scrolled back to, like the primary screen
+    terminal_state.set_alternate_scrollback_lines(100);
+    terminal_state.handle_incoming_data(b"\r\n6\r\n7\r\n8");
+    assert!(terminal_state.has_scrollback());
+    assert_eq!(terminal_state.viewport_position(), (6, 6));
+    terminal_state.scroll(2.0);
+    assert!(replies(&rx).is_empty());
+    assert_eq!(viewport_text(&mut terminal_state), vec!["4", "5", "6"]);
+    terminal_state.snap_to_bottom();
+
+    // A clear keeps the page that was on the screen
+    terminal_state.handle_incoming_data(b"\x1b[2J\x1b[Hnext page");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["next page", "", ""]
+    );
+    assert!(terminal_state
+        .to_plain_text(true)
+        .starts_with("\n3\n4\n5\n6\n7\n8\nnext page"));
+
+    // Each visit to the alternate screen starts without scrollback, but keeps the setting
+    terminal_state.handle_incoming_data(b"\x1b[?1049l\x1b[?1049hnew");
+    assert!(terminal_state.has_scrollback());
+    assert_eq!(terminal_state.viewport_position(), (1, 1));
+
+    // and only keeps as many lines as it was asked to
+    terminal_state.set_alternate_scrollback_lines(10);
+    for i in 0..50 {
+        terminal_state.handle_incoming_data(format!("\r\n{i}").as_bytes());
+    }
+    assert!(terminal_state.viewport_position().1 <= 10);
+}
+
+#[test]
+fn test_save_and_restore_private_modes() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.handle_incoming_data(b"\x1b[?2004h\x1b[?1000h");
+
+    // A program saves the modes it is about to change, and uses the alternate screen with its
+    // own mouse tracking and focus events
+    terminal_state.handle_incoming_data(b"\x1b[?1004;1000;1002;2004;1049s");
+    terminal_state.handle_incoming_data(b"\x1b[?1049h\x1b[?1004h\x1b[?1002h\x1b[?2004l");
+    assert_eq!(replies(&rx), "\x1b[I");
+    terminal_state.set_window_focused(false);
+    assert_eq!(replies(&rx), "\x1b[O");
+    assert_eq!(terminal_state.modes.mouse_tracking, MouseTrack::XtMseBtn);
+
+    // and puts them back when it leaves, so the shell doesn't get focus events it never asked for
+    terminal_state.handle_incoming_data(b"\x1b[?1049l\x1b[?1004;1000;1002;2004;1049r");
+    assert_eq!(terminal_state.modes.focus_reporting, XtMseWin::Disabled);
+    assert_eq!(terminal_state.modes.mouse_tracking, MouseTrack::XtMseX11);
+    assert_eq!(terminal_state.modes.bracketed_paste, RlBracket::Enabled);
+    assert_eq!(terminal_state.current_buffer, BufferType::Primary);
+    terminal_state.set_window_focused(true);
+    assert!(replies(&rx).is_empty());
+
+    // Screens aren't saved, and modes that weren't saved are left alone
+    assert_eq!(terminal_state.private_mode_is_set(1049), None);
+    terminal_state.handle_incoming_data(b"\x1b[?25l\x1b[?25r");
+    assert_eq!(terminal_state.private_mode_is_set(25), Some(false));
+    terminal_state.handle_incoming_data(b"\x1b[?25s\x1b[?25h\x1b[?25r");
+    assert_eq!(terminal_state.private_mode_is_set(25), Some(false));
+}
+
+#[test]
+fn test_modes_across_alternate_screen() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // Per-screen modes set on the primary screen carry over to the alternate screen
+    terminal_state.handle_incoming_data(b"\x1b[?25l\x1b[?7l\x1b[?2004h\x1b[?1049h");
+    assert_eq!(terminal_state.current_buffer, BufferType::Alternate);
+    assert_eq!(
+        terminal_state.get_current_buffer().show_cursor,
+        Dectcem::Hide
+    );
+    assert_eq!(
+        terminal_state
+            .get_current_buffer()
+            .cursor_state
+            .line_wrap_mode,
+        Decawm::NoAutoWrap
+    );
+
+    // Changes made on the alternate screen stay there
+    terminal_state.handle_incoming_data(b"\x1b[?25h\x1b[?7h\x1b[?6h\x1b[?1h\x1b[?1049l");
+    assert_eq!(terminal_state.current_buffer, BufferType::Primary);
+    assert_eq!(
+        terminal_state.get_current_buffer().show_cursor,
+        Dectcem::Hide
+    );
+    assert_eq!(
+        terminal_state
+            .get_current_buffer()
+            .cursor_state
+            .line_wrap_mode,
+        Decawm::NoAutoWrap
+    );
+    assert_eq!(
+        terminal_state.get_current_buffer().origin_mode,
+        Decom::Normal
+    );
+
+    // Modes shared by both screens keep whatever they were last set to
+    assert_eq!(terminal_state.modes.bracketed_paste, RlBracket::Enabled);
+    assert_eq!(terminal_state.get_cursor_key_mode(), Decckm::Application);
+
+    // The next visit to the alternate screen starts from the primary's modes again
+    terminal_state.handle_incoming_data(b"\x1b[?1049h");
+    assert_eq!(
+        terminal_state.get_current_buffer().show_cursor,
+        Dectcem::Hide
+    );
+    assert_eq!(
+        terminal_state.get_current_buffer().origin_mode,
+        Decom::Normal
+    );
+}
+
+#[test]
+fn test_saved_cursor_per_buffer() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // Saving and restoring without switching screens
+    terminal_state.handle_incoming_data(b"\x1b[3;4H\x1b[?1048h\x1b[10;10H\x1b[?1048l");
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 3, y: 2 }
+    );
+
+    // Entering the alternate screen saves the primary's cursor. Saving again on the alternate
+    // screen, like tmux does, doesn't overwrite it
+    terminal_state.handle_incoming_data(b"\x1b[5;6H\x1b[?1049h\x1b[7;8H\x1b[?1048h\x1b[1;1H");
+    assert_eq!(
+        terminal_state.get_current_buffer().saved_cursor_position,
+        Some(CursorPos { x: 7, y: 6 })
+    );
+    terminal_state.handle_incoming_data(b"\x1b[?1048l");
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 7, y: 6 }
+    );
+
+    terminal_state.handle_incoming_data(b"\x1b[?1049l");
+    assert_eq!(terminal_state.current_buffer, BufferType::Primary);
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 5, y: 4 }
+    );
+}
+
+#[test]
+fn test_line_timestamps() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    let before = std::time::SystemTime::now();
+    terminal_state.handle_incoming_data(b"one\r\ntwo\r\n\r\nfour");
+    let after = std::time::SystemTime::now();
+
+    let timestamps = terminal_state.visible_line_timestamps();
+    let written = |timestamp: &Option<std::time::SystemTime>| {
+        timestamp.is_some_and(|time| before <= time && time <= after)
+    };
+    assert!(written(&timestamps[0]));
+    assert!(written(&timestamps[1]));
+    assert!(written(&timestamps[3]));
+    assert_eq!(terminal_state.line_timestamps().len(), timestamps.len());
+
+    terminal_state.handle_incoming_data(b"\x1b[3J");
+    assert!(terminal_state.line_timestamps().iter().all(Option::is_none));
+}
+
+#[test]
+fn test_zero_width_characters_do_not_move_cursor() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    terminal_state.handle_incoming_data("ab\u{200B}c\u{00AD}\u{FEFF}".as_bytes());
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 3, y: 0 }
+    );
+
+    // A joiner arriving on its own stays with the character already on screen
+    terminal_state.handle_incoming_data("\u{200D}".as_bytes());
+    terminal_state.handle_incoming_data(b"d");
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 4, y: 0 }
+    );
+
+    let data = terminal_state
+        .get_current_buffer()
+        .terminal_buffer
+        .data(false)
+        .visible;
+    assert_eq!(data.iter().filter(|c| **c != TChar::NewLine).count(), 4);
+    assert_eq!(
+        data[2],
+        TChar::Utf8("c\u{00AD}\u{FEFF}\u{200D}".as_bytes().to_vec())
+    );
+
+    // Copying gives back everything that was written
+    assert!(terminal_state
+        .visible_text()
+        .starts_with("ab\u{200B}c\u{00AD}\u{FEFF}\u{200D}d"));
+}
+
+#[test]
+fn test_ambiguous_wide_on_both_screens() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_width_provider(WidthProvider::new(UnicodeVersion::Unicode9, true));
+
+    terminal_state.handle_incoming_data("─".as_bytes());
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 2, y: 0 }
+    );
+
+    // The alternate screen is made fresh each time, and still has to agree
+    terminal_state.handle_incoming_data("\x1b[?1049h─".as_bytes());
+    assert_eq!(terminal_state.current_buffer, BufferType::Alternate);
+    assert_eq!(
+        terminal_state.get_current_buffer().cursor_state.pos,
+        CursorPos { x: 2, y: 0 }
+    );
+}
+
+#[test]
+fn test_to_plain_text() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    assert_eq!(terminal_state.to_plain_text(true), "");
+
+    for i in 0..20 {
+        terminal_state.handle_incoming_data(format!("line {i}   \r\n").as_bytes());
+    }
+    terminal_state.handle_incoming_data(b"\x1b[1mbold\x1b[0m end");
+
+    let all = terminal_state.to_plain_text(true);
+    assert!(all.starts_with("line 0\nline 1\n"));
+    assert!(all.ends_with("line 19\nbold end\n"));
+
+    // Only what is on screen
+    let visible = terminal_state.to_plain_text(false);
+    assert!(!visible.contains("line 0\n"));
+    assert!(visible.ends_with("line 19\nbold end\n"));
+    assert_eq!(visible.lines().count(), terminal_state.get_win_size().1);
+}
+
+#[test]
+fn test_osc7_working_directory() {
+    let mut terminal_state = TerminalState::default();
+    assert_eq!(terminal_state.working_directory, None);
+
+    terminal_state.handle_incoming_data(b"\x1b]7;file://devbox/srv/www\x07");
+    let directory = terminal_state.working_directory.clone().unwrap();
+    assert_eq!(directory.host, "devbox");
+    assert_eq!(directory.path, std::path::PathBuf::from("/srv/www"));
+
+    // Something that isn't a file URL doesn't lose the directory we had
+    terminal_state.handle_incoming_data(b"\x1b]7;nonsense\x07");
+    assert_eq!(terminal_state.working_directory, Some(directory));
+}
+
+#[test]
+fn test_hidden_window_still_parses_output() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.set_window_visible(false);
+
+    terminal_state.handle_incoming_data(b"hidden");
+    assert!(terminal_state.is_changed());
+    assert_eq!(terminal_state.to_plain_text(false).trim_end(), "hidden");
+
+    terminal_state.set_window_visible(true);
+    assert!(terminal_state.window_visible);
+    assert!(terminal_state.is_changed());
+}
+
+#[test]
+fn test_stuck_sync_update_is_released() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.handle_incoming_data(b"\x1b[?2026h");
+    assert!(terminal_state.sync_update_started.is_some());
+    assert!(!terminal_state.is_changed());
+
+    // Still within the timeout
+    assert!(!terminal_state.release_stuck_sync_update());
+    assert!(!terminal_state.is_changed());
+
+    terminal_state.sync_update_started = Some(Instant::now() - SYNC_UPDATE_TIMEOUT);
+    assert!(terminal_state.release_stuck_sync_update());
+    assert_eq!(
+        terminal_state.modes.synchronized_updates,
+        SynchronizedUpdates::Draw
+    );
+    assert!(terminal_state.is_changed());
+
+    // Ending the update normally stops the watchdog
+    terminal_state.handle_incoming_data(b"\x1b[?2026h\x1b[?2026l");
+    assert_eq!(terminal_state.sync_update_started, None);
+    assert!(!terminal_state.release_stuck_sync_update());
+}
+
+#[test]
+fn test_startup_banner_hidden_until_prompt() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.hide_startup_banner();
+    assert!(terminal_state.is_hiding_startup_banner());
+
+    terminal_state.handle_incoming_data(b"Welcome to the machine\r\n\x1b]2;shell\x07");
+    assert!(!terminal_state.is_changed());
+    assert!(!terminal_state.show_cursor());
+    // Nothing but the screen output is dropped
+    assert_eq!(
+        terminal_state.window_commands,
+        vec![WindowManipulation::SetTitleBarText("shell".to_string())]
+    );
+
+    terminal_state.handle_incoming_data(b"\x1b]133;A\x07$ ");
+    assert!(!terminal_state.is_hiding_startup_banner());
+    assert!(terminal_state.is_changed());
+    assert_eq!(terminal_state.to_plain_text(true).trim_end(), "$");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 2, y: 0 });
+}
+
+#[test]
+fn test_startup_banner_shown_without_prompt() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.hide_startup_banner();
+    terminal_state.handle_incoming_data(b"no integration here");
+    assert!(!terminal_state.release_startup_banner());
+
+    terminal_state.hide_startup_banner_until = Some(Instant::now() - STARTUP_BANNER_TIMEOUT);
+    assert!(terminal_state.release_startup_banner());
+    assert!(terminal_state.is_changed());
+    assert!(!terminal_state.release_startup_banner());
+
+    // A prompt that turns up late doesn't clear what has already been shown
+    terminal_state.handle_incoming_data(b"\r\n\x1b]133;A\x07$ ");
+    assert_eq!(
+        terminal_state.to_plain_text(true).trim_end(),
+        "no integration here\n$"
+    );
+}
+
+#[test]
+fn test_clear_display_keeps_scrollback() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.set_win_size(20, 5);
+    terminal_state.handle_incoming_data(b"one\r\ntwo\r\nthree\x1b[H\x1b[2J");
+    assert_eq!(terminal_state.to_plain_text(false).trim(), "");
+    assert_eq!(terminal_state.to_plain_text(true).trim(), "one\ntwo\nthree");
+    // The cursor doesn't move, and writing carries on on the blank screen
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 0, y: 0 });
+    terminal_state.handle_incoming_data(b"$ ");
+    assert_eq!(terminal_state.to_plain_text(false).trim(), "$");
+
+    let mut terminal_state = TerminalState {
+        scrollback_on_clear: false,
+        ..TerminalState::default()
+    };
+    terminal_state.set_win_size(20, 5);
+    terminal_state.handle_incoming_data(b"one\r\ntwo\r\nthree\x1b[H\x1b[2J");
+    assert_eq!(terminal_state.to_plain_text(true).trim(), "");
+}
+
+#[test]
+fn test_send_signal() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    // Signals go through even when the application has taken over the keyboard
+    terminal_state.handle_incoming_data(b"\x1b[?1h\x1b=");
+    rx.try_iter().for_each(drop);
+
+    terminal_state.send_signal(PtySignal::Interrupt).unwrap();
+    terminal_state.send_signal(PtySignal::EndOfFile).unwrap();
+    let sent: Vec<PtySignal> = rx
+        .try_iter()
+        .filter_map(|write| match write {
+            PtyWrite::Signal(signal) => Some(signal),
+            PtyWrite::Write(_) | PtyWrite::Resize(_) => None,
+        })
+        .collect();
+    assert_eq!(sent, vec![PtySignal::Interrupt, PtySignal::EndOfFile]);
+
+    assert_eq!(PtySignal::Interrupt.control_char(), 0x03);
+    assert_eq!(PtySignal::Suspend.control_char(), 0x1a);
+    assert_eq!(PtySignal::Quit.control_char(), 0x1c);
+    assert_eq!(PtySignal::EndOfFile.control_char(), 0x04);
+}
+
+#[test]
+fn test_reset_modes() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.handle_incoming_data(b"\x1b[?2026h\x1b[?25l\x1b[?7l\x1b[?1000h\x1b[?1h\x1b(0");
+    assert_eq!(terminal_state.primary_buffer.show_cursor, Dectcem::Hide);
+
+    terminal_state.reset_modes();
+    assert_eq!(terminal_state.modes, TerminalModes::default());
+    assert_eq!(terminal_state.sync_update_started, None);
+    assert_eq!(
+        terminal_state.character_replace,
+        DecSpecialGraphics::DontReplace
+    );
+    assert_eq!(terminal_state.primary_buffer.show_cursor, Dectcem::Show);
+    assert_eq!(
+        terminal_state.primary_buffer.cursor_state.line_wrap_mode,
+        Decawm::AutoWrap
+    );
+    assert!(terminal_state.is_changed());
+}
+
+/// The OSC 8 hyperlink drawn at `x`, `y`, found the way the GUI finds what is under the mouse
+fn link_at(terminal_state: &mut TerminalState, x: usize, y: usize) -> Option<Hyperlink> {
+    let buf_pos = terminal_state
+        .render_slice()
+        .buffer_offset_at(&CursorPos { x, y })?;
+    terminal_state.hyperlink_at(buf_pos)
+}
+
+/// Press the mouse button on cell `from` and drag it to `to`, as the GUI does
+fn drag_select(terminal_state: &mut TerminalState, from: (usize, usize), to: (usize, usize)) {
+    let slice = terminal_state.render_slice();
+    let nearest = |(x, y)| slice.nearest_buffer_offset(&CursorPos { x, y }).unwrap();
+    terminal_state.start_selection(nearest(from));
+    terminal_state.extend_selection(nearest(to));
+}
+
+/// The selected cells on screen, as the GUI draws them
+fn selected_cells(terminal_state: &mut TerminalState) -> Vec<(usize, Range<usize>)> {
+    let slice = terminal_state.render_slice();
+    terminal_state
+        .selection_range()
+        .map(|range| slice.cells(&range))
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_hyperlink_at_and_selection_hyperlink() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.handle_incoming_data(
+        b"see \x1b]8;;https://example.com/docs\x07the \x1b[1mdocs\x1b[22m\x1b]8;;\x07 here",
+    );
+
+    // The bold part of the link is in its own format tag, but is still the same link
+    let link = link_at(&mut terminal_state, 9, 0).unwrap();
+    assert_eq!(link.url, "https://example.com/docs");
+    assert_eq!(link.text, "the docs");
+    assert_eq!(link_at(&mut terminal_state, 4, 0), Some(link.clone()));
+    assert_eq!(link_at(&mut terminal_state, 1, 0), None);
+    assert_eq!(link_at(&mut terminal_state, 40, 0), None);
+
+    let mut select = |from, to| {
+        drag_select(&mut terminal_state, (from, 0), (to, 0));
+        terminal_state.finish_selection();
+        terminal_state.selection_hyperlink()
+    };
+    assert_eq!(select(5, 10), Some(link.clone()));
+    assert_eq!(select(11, 4), Some(link.clone()));
+
+    // Selections that go outside the link copy text only
+    assert_eq!(select(3, 8), None);
+    assert_eq!(select(8, 13), None);
+    terminal_state.clear_selection();
+    assert_eq!(terminal_state.selection_hyperlink(), None);
+}
+
+#[test]
+fn test_selection_hyperlink_in_scrollback() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+    terminal_state.handle_incoming_data(b"\x1b]8;;https://example.com\x07old link\x1b]8;;\x07\r\n");
+    for line in 0..10 {
+        terminal_state.handle_incoming_data(format!("line {line}\r\n").as_bytes());
+    }
+
+    // Scrolled back, the hovered link and the selected one are the same
+    terminal_state.move_viewport(ViewportMovement::Top);
+    let link = link_at(&mut terminal_state, 2, 0).unwrap();
+    assert_eq!(link.text, "old link");
+    drag_select(&mut terminal_state, (0, 0), (2, 0));
+    terminal_state.finish_selection();
+    assert_eq!(terminal_state.selection_hyperlink(), Some(link.clone()));
+
+    // And stays so once the viewport moves on
+    terminal_state.move_viewport(ViewportMovement::Bottom);
+    assert_eq!(terminal_state.selection_hyperlink(), Some(link));
+}
+
+#[test]
+fn test_hovered_and_visited_links() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.handle_incoming_data(
+        b"see \x1b]8;;https://example.com/docs\x07the \x1b[1mdocs\x1b[22m\x1b]8;;\x07 here",
+    );
+
+    assert!(!terminal_state.set_hovered_link(Some(1)));
+    assert_eq!(terminal_state.hovered_link, None);
+
+    // The whole link is hovered, across both of its tags
+    assert!(terminal_state.set_hovered_link(Some(9)));
+    let (url, range) = terminal_state.hovered_link.clone().unwrap();
+    assert_eq!(url.url, "https://example.com/docs");
+    assert_eq!(range, 4..12);
+
+    // Moving along the same link changes nothing
+    assert!(!terminal_state.set_hovered_link(Some(5)));
+    assert!(terminal_state.set_hovered_link(None));
+    assert_eq!(terminal_state.hovered_link, None);
+
+    terminal_state.mark_link_visited("https://example.com/a");
+    terminal_state.mark_link_visited("https://example.com/b");
+    terminal_state.mark_link_visited("https://example.com/a");
+    assert_eq!(
+        terminal_state.visited_links,
+        ["https://example.com/b", "https://example.com/a"]
+    );
+
+    for i in 0..VISITED_LINKS_LIMIT {
+        terminal_state.mark_link_visited(&format!("https://example.com/{i}"));
+    }
+    assert_eq!(terminal_state.visited_links.len(), VISITED_LINKS_LIMIT);
+    assert_eq!(terminal_state.visited_links[0], "https://example.com/0");
+}
+
+#[test]
+fn test_scroll_between_commands() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.set_win_size(20, 5);
+
+    assert!(terminal_state.scroll_to_previous_command().is_none());
+    assert!(terminal_state.scroll_to_next_command().is_none());
+
+    for command in ["one", "two", "three"] {
+        terminal_state.handle_incoming_data(
+            format!("\x1b]133;A\x07$ {command}\r\n\x1b]133;C\x07").as_bytes(),
+        );
+        for i in 0..10 {
+            terminal_state.handle_incoming_data(format!("{command} {i}\r\n").as_bytes());
+        }
+        terminal_state.handle_incoming_data(b"\x1b]133;D;0\x07");
+    }
+
+    let marks = terminal_state
+        .get_current_buffer()
+        .command_history
+        .marks()
+        .to_vec();
+    assert_eq!(marks.len(), 3);
+
+    // Back through every command, not just the ones that failed
+    assert_eq!(
+        terminal_state.scroll_to_previous_command(),
+        Some(marks[2].output_range())
+    );
+    assert_eq!(
+        terminal_state.scroll_to_previous_command(),
+        Some(marks[1].output_range())
+    );
+    assert_eq!(
+        terminal_state.scroll_to_previous_command(),
+        Some(marks[0].output_range())
+    );
+    assert_eq!(terminal_state.scroll_to_previous_command(), None);
+
+    // And forward again, ending up back at the bottom
+    assert_eq!(
+        terminal_state.scroll_to_next_command(),
+        Some(marks[1].output_range())
+    );
+    assert_eq!(
+        terminal_state.scroll_to_next_command(),
+        Some(marks[2].output_range())
+    );
+    assert_eq!(terminal_state.scroll_to_next_command(), None);
+    let (line, total) = terminal_state.viewport_position();
+    assert_eq!(line, total);
+}
+
+#[test]
+fn test_search_scrollback() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    terminal_state.handle_incoming_data(b"error: first\r\n");
+    for i in 0..30 {
+        terminal_state.handle_incoming_data(format!("line {i}\r\n").as_bytes());
+    }
+    terminal_state.handle_incoming_data(b"Error: second\r\n");
+
+    // the most recent match is on screen, so the viewport stays where it is
+    assert_eq!(terminal_state.search("error"), Some((2, 2)));
+    let second = terminal_state.search.current_match().unwrap().clone();
+    assert!(terminal_state.visible_rows_for_buf_range(&second).is_some());
+
+    // going up finds the first one in the scrollback
+    assert_eq!(terminal_state.previous_search_match(), Some((1, 2)));
+    let first = terminal_state.search.current_match().unwrap().clone();
+    assert_eq!(
+        terminal_state.visible_rows_for_buf_range(&first),
+        Some(0..1)
+    );
+
+    // and goes round back to the bottom
+    assert_eq!(terminal_state.previous_search_match(), Some((2, 2)));
+    assert_eq!(terminal_state.next_search_match(), Some((1, 2)));
+
+    // a capital letter makes the search case sensitive
+    assert_eq!(terminal_state.search("Error"), Some((1, 1)));
+    assert_eq!(terminal_state.search("nowhere"), None);
+    assert_eq!(terminal_state.next_search_match(), None);
+
+    terminal_state.clear_search();
+    assert!(!terminal_state.search.is_active());
+    assert!(terminal_state.search.matches().is_empty());
+}
+
+#[test]
+fn test_search_follows_new_output() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    terminal_state.handle_incoming_data(b"needle\r\n");
+    assert_eq!(terminal_state.search("needle"), Some((1, 1)));
+
+    terminal_state.handle_incoming_data(b"another needle\r\n");
+    assert!(terminal_state.search.is_stale());
+    assert_eq!(terminal_state.search("needle"), Some((2, 2)));
+    assert!(!terminal_state.search.is_stale());
+}
+
+#[test]
+fn test_scrollback_lines() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_scrollback_lines(100);
+
+    for i in 0..500 {
+        terminal_state.handle_incoming_data(format!("line {i}\r\n").as_bytes());
+    }
+
+    let text = terminal_state.to_plain_text(true);
+    let lines: Vec<&str> = text.lines().filter(|line| !line.is_empty()).collect();
+    assert!(lines.len() <= 100, "kept {} lines", lines.len());
+    assert!(lines.len() > 16, "kept {} lines", lines.len());
+    assert_eq!(lines.last(), Some(&"line 499"));
+
+    // The oldest lines go in batches, rather than one at a time as each new one is written
+    let mut kept = Vec::new();
+    for i in 500..700 {
+        terminal_state.handle_incoming_data(format!("line {i}\r\n").as_bytes());
+        kept.push(terminal_state.to_plain_text(true).lines().count());
+    }
+    assert!(kept.iter().all(|lines| *lines <= 100), "{kept:?}");
+    let drops: Vec<_> = kept
+        .windows(2)
+        .filter(|pair| pair[1] < pair[0])
+        .map(|pair| pair[0] - pair[1])
+        .collect();
+    assert!(
+        !drops.is_empty() && drops.iter().all(|drop| *drop > 1),
+        "{drops:?}"
+    );
+
+    // Unless every line is kept
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_scrollback_lines(UNLIMITED_SCROLLBACK_LINES);
+    for i in 0..5000 {
+        terminal_state.handle_incoming_data(format!("line {i}\r\n").as_bytes());
+    }
+    assert!(terminal_state
+        .to_plain_text(true)
+        .starts_with("line 0\nline 1\n"));
+
+    // Never fewer lines than the screen
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_scrollback_lines(1);
+    for i in 0..100 {
+        terminal_state.handle_incoming_data(format!("line {i}\r\n").as_bytes());
+    }
+    let text = terminal_state.to_plain_text(false);
+    assert!(text.contains("line 85\nline 86"), "{text:?}");
+    assert!(text.ends_with("line 99\n"), "{text:?}");
+}
+
+#[test]
+fn test_small_scrollback_with_resizes() {
+    for scrollback_lines in [1, 10, 21] {
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let mut terminal_state = TerminalState::new(tx);
+        terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+        terminal_state.set_scrollback_lines(scrollback_lines);
+        terminal_state.handle_incoming_data(b"\x1b[99;99H");
+        terminal_state.set_win_size(1, 1);
+        terminal_state.handle_incoming_data(b"abc");
+        assert_eq!(terminal_state.visible_text().trim(), "c");
+
+        for (width, height) in [(3, 2), (12, 8), (1, 3), (5, 1)] {
+            terminal_state.set_win_size(width, height);
+            for i in 0..30 {
+                terminal_state.handle_incoming_data(format!("line {i}\r\n").as_bytes());
+            }
+            terminal_state.handle_incoming_data(b"end");
+
+            // The first lines written are long gone, whatever size the screen has been
+            let text = terminal_state.to_plain_text(true);
+            assert!(text.ends_with("end\n"), "{text:?}");
+            assert!(!text.lines().any(|line| line == "line 1"), "{text:?}");
+        }
+    }
+}
+
+#[test]
+fn test_redraw_notifier() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_max_fps(0);
+
+    let notifier = Arc::new(CountingRedrawNotifier::default());
+    terminal_state.set_redraw_notifier(notifier.clone());
+
+    // only the first notifier is kept
+    terminal_state.set_redraw_notifier(Arc::new(CountingRedrawNotifier::default()));
+
+    terminal_state.handle_incoming_data(b"hello");
+    assert!(notifier.redraws.load(Ordering::SeqCst) > 0);
+
+    // a synchronized update asks for a frame later, in case it is never ended
+    terminal_state.handle_incoming_data(b"\x1b[?2026h");
+    assert_eq!(notifier.delayed_redraws.load(Ordering::SeqCst), 1);
+
+    // nothing is drawn while the window is hidden
+    terminal_state.window_visible = false;
+    let redraws = notifier.redraws.load(Ordering::SeqCst);
+    terminal_state.handle_incoming_data(b"world");
+    assert_eq!(notifier.redraws.load(Ordering::SeqCst), redraws);
+}
+
+#[test]
+fn test_mouse_selection() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.handle_incoming_data(b"hello world\r\nsecond line\r\n");
+
+    drag_select(&mut terminal_state, (6, 0), (5, 1));
+    assert!(terminal_state.is_selecting());
+    terminal_state.finish_selection();
+    assert!(!terminal_state.is_selecting());
+
+    assert_eq!(
+        terminal_state.selected_text(),
+        Some("world\nsecond".to_string())
+    );
+    assert_eq!(
+        selected_cells(&mut terminal_state),
+        vec![(0, 6..11), (1, 0..6)]
+    );
+
+    // moving the mouse once the button is up doesn't change the selection
+    terminal_state.extend_selection(0);
+    assert_eq!(
+        terminal_state.selected_text(),
+        Some("world\nsecond".to_string())
+    );
+
+    // past the end of a line selects up to the end of it
+    drag_select(&mut terminal_state, (0, 0), (40, 0));
+    assert_eq!(
+        terminal_state.selected_text(),
+        Some("hello world\n".to_string())
+    );
+
+    // and below the last row to the end of the output
+    drag_select(&mut terminal_state, (7, 1), (0, 30));
+    assert_eq!(terminal_state.selected_text(), Some("line\n".to_string()));
+
+    // a click without a drag drops the selection
+    drag_select(&mut terminal_state, (2, 0), (2, 0));
+    terminal_state.finish_selection();
+    assert_eq!(terminal_state.selected_text(), None);
+    assert!(selected_cells(&mut terminal_state).is_empty());
+
+    drag_select(&mut terminal_state, (0, 1), (5, 1));
+    terminal_state.clear_selection();
+    assert_eq!(terminal_state.selected_text(), None);
+}
+
+#[test]
+fn test_select_word() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.handle_incoming_data(b"cd ~/my-project_2 && ls\r\n");
+
+    terminal_state.select_word(9);
+    assert_eq!(terminal_state.word_buf_range_at(9), Some(8..17));
+    assert_eq!(
+        terminal_state.selected_text(),
+        Some("project_2".to_string())
+    );
+    assert!(!terminal_state.is_selecting());
+
+    // The configured word characters join words that would otherwise be split
+    terminal_state.word_characters = WordCharacters::new("_-/~");
+    terminal_state.select_word(9);
+    assert_eq!(
+        terminal_state.selected_text(),
+        Some("~/my-project_2".to_string())
+    );
+
+    // Anything that isn't part of a word selects nothing
+    terminal_state.select_word(18);
+    assert_eq!(terminal_state.selected_text(), None);
+    terminal_state.select_word(23);
+    assert_eq!(terminal_state.selected_text(), None);
+}
+
+#[test]
+fn test_selection_in_scrollback() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    for i in 0..40 {
+        terminal_state.handle_incoming_data(format!("line {i}\r\n").as_bytes());
+    }
+
+    terminal_state.move_viewport(ViewportMovement::Top);
+    drag_select(&mut terminal_state, (0, 0), (5, 0));
+    terminal_state.finish_selection();
+    assert_eq!(terminal_state.selected_text(), Some("line 0".to_string()));
+
+    // scrolled away, the selection is kept but not drawn
+    terminal_state.move_viewport(ViewportMovement::Bottom);
+    assert_eq!(terminal_state.selected_text(), Some("line 0".to_string()));
+    assert!(selected_cells(&mut terminal_state).is_empty());
+}
+
+fn viewport_text(terminal_state: &mut TerminalState) -> Vec<String> {
+    let terminal_buffer = &terminal_state.get_current_buffer().terminal_buffer;
+    terminal_buffer
+        .viewport_rows()
+        .into_iter()
+        .map(|row| terminal_buffer.text_for_buf_range(row, false))
+        .collect()
+}
+
+#[test]
+fn test_no_wrap_view() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+    terminal_state.set_win_size(10, 3);
+    terminal_state.handle_incoming_data(b"0123456789abcde\r\nshort\r\n$ ");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["abcde", "short", "$ "]
+    );
+    assert_eq!(terminal_state.column_scroll(), (0, 0));
+
+    terminal_state.set_no_wrap_view(true);
+    assert!(terminal_state.no_wrap_view());
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["0123456789", "short", "$ "]
+    );
+    assert_eq!(terminal_state.column_scroll(), (0, 5));
+    assert_eq!(
+        terminal_state.viewport_cursor_pos(),
+        Some(CursorPos { x: 2, y: 2 })
+    );
+
+    terminal_state.scroll_columns(3.0);
+    assert_eq!(terminal_state.column_scroll(), (3, 5));
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["3456789abc", "rt", ""]
+    );
+
+    // the cursor is off the left edge
+    assert_eq!(terminal_state.viewport_cursor_pos(), None);
+
+    // no further than the end of the longest line
+    terminal_state.scroll_columns(100.0);
+    assert_eq!(terminal_state.column_scroll(), (5, 5));
+
+    // selecting works on what is shown
+    drag_select(&mut terminal_state, (0, 0), (4, 0));
+    terminal_state.finish_selection();
+    assert_eq!(terminal_state.selected_text(), Some("56789".to_string()));
+    assert_eq!(selected_cells(&mut terminal_state), vec![(0, 0..5)]);
+
+    terminal_state.scroll_columns(-1.0);
+    assert_eq!(terminal_state.column_scroll(), (4, 5));
+    assert_eq!(selected_cells(&mut terminal_state), vec![(0, 1..6)]);
+
+    terminal_state.set_column_offset(0);
+    assert_eq!(
+        terminal_state.viewport_cursor_pos(),
+        Some(CursorPos { x: 2, y: 2 })
+    );
+
+    // wrapping again goes back to the start of the lines
+    terminal_state.scroll_columns(2.0);
+    terminal_state.set_no_wrap_view(false);
+    assert_eq!(terminal_state.column_scroll(), (0, 0));
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["abcde", "short", "$ "]
+    );
+}
+
+#[test]
+fn test_no_wrap_view_scrolls_whole_lines() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+    terminal_state.set_win_size(10, 3);
+    for i in 0..20 {
+        terminal_state
+            .handle_incoming_data(format!("line {i:02} {}\r\n", "x".repeat(20)).as_bytes());
+    }
+    terminal_state.handle_incoming_data(b"$");
+
+    terminal_state.set_no_wrap_view(true);
+    let first_columns = |terminal_state: &mut TerminalState| -> Vec<String> {
+        viewport_text(terminal_state)
+            .into_iter()
+            .map(|row| row.chars().take(7).collect())
+            .collect()
+    };
+    assert_eq!(
+        first_columns(&mut terminal_state),
+        vec!["line 18", "line 19", "$"]
+    );
+
+    // each line is one row, however many it would wrap on to
+    terminal_state.scroll(1.0);
+    assert_eq!(
+        first_columns(&mut terminal_state),
+        vec!["line 17", "line 18", "line 19"]
+    );
+    terminal_state.scroll(2.0);
+    assert_eq!(
+        first_columns(&mut terminal_state),
+        vec!["line 15", "line 16", "line 17"]
+    );
+    terminal_state.scroll(-1.0);
+    assert_eq!(
+        first_columns(&mut terminal_state),
+        vec!["line 16", "line 17", "line 18"]
+    );
+
+    // the top of the scrollback still fills the screen
+    terminal_state.move_viewport(ViewportMovement::Top);
+    assert_eq!(
+        first_columns(&mut terminal_state),
+        vec!["line 00", "line 01", "line 02"]
+    );
+
+    // the alternate screen always wraps
+    terminal_state.move_viewport(ViewportMovement::Bottom);
+    terminal_state.handle_incoming_data(b"\x1b[?1049h");
+    assert!(!terminal_state.no_wrap_view());
+    assert_eq!(terminal_state.column_scroll(), (0, 0));
+    terminal_state.handle_incoming_data(b"\x1b[?1049l");
+    assert!(terminal_state.no_wrap_view());
+}
+
+#[test]
+fn test_no_wrap_view_after_clearing_scrollback() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.handle_incoming_data(b"a\x1b[3J");
+    terminal_state.set_no_wrap_view(true);
+
+    // Nothing is left to show, and no row points past the end of the emptied buffer
+    let slice = terminal_state.render_slice();
+    assert!(slice.rows.iter().all(|row| row.buffer.is_empty()));
+    assert!(viewport_text(&mut terminal_state)
+        .iter()
+        .all(String::is_empty));
+
+    terminal_state.handle_incoming_data(b"b");
+    assert_eq!(terminal_state.visible_text().trim(), "b");
+}
+
+fn replies(rx: &crossbeam_channel::Receiver<PtyWrite>) -> String {
+    let mut received = String::new();
+    while let Ok(PtyWrite::Write(v)) = rx.try_recv() {
+        received.push_str(&String::from_utf8(v).unwrap());
+    }
+    received
+}
+
+#[test]
+fn test_osc_palette_colors() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    let mut user_palette = Palette::new(Theme::Dracula);
+    user_palette.set(2, (1, 2, 3));
+    terminal_state.set_palette(user_palette);
+
+    terminal_state.handle_incoming_data(b"\x1b]4;1;?;2;?;200;?\x07");
+    assert_eq!(
+        replies(&rx),
+        "\x1b]4;1;rgb:ffff/5555/5555\x1b\\\
+         \x1b]4;2;rgb:0101/0202/0303\x1b\\\
+         \x1b]4;200;rgb:ffff/0000/d7d7\x1b\\"
+    );
+
+    terminal_state.handle_incoming_data(b"\x1b]4;1;#102030;2;rgb:ff/ff/ff;300;?\x07");
+    assert_eq!(terminal_state.palette.get(1), Some((0x10, 0x20, 0x30)));
+    assert_eq!(terminal_state.palette.get(2), Some((255, 255, 255)));
+    assert_eq!(replies(&rx), "");
+
+    // Resetting goes back to the user's colors, not the theme's
+    terminal_state.handle_incoming_data(b"\x1b]104;2\x07");
+    assert_eq!(terminal_state.palette.get(1), Some((0x10, 0x20, 0x30)));
+    assert_eq!(terminal_state.palette.get(2), Some((1, 2, 3)));
+    terminal_state.handle_incoming_data(b"\x1b]104\x07");
+    assert_eq!(terminal_state.palette, user_palette);
+}
+
+#[test]
+fn test_osc_default_colors() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+
+    // The GUI's own colors are used when the theme has none
+    terminal_state.handle_incoming_data(b"\x1b]10;?\x07\x1b]11;?\x07");
+    assert_eq!(
+        replies(&rx),
+        "\x1b]10;rgb:ffff/ffff/ffff\x1b\\\x1b]11;rgb:0000/0000/0000\x1b\\"
+    );
+
+    terminal_state.handle_incoming_data(b"\x1b]10;#eeeeee\x07\x1b]11;rgb:2828/2828/2828\x07");
+    assert_eq!(
+        terminal_state.palette.foreground(),
+        Some((0xee, 0xee, 0xee))
+    );
+    assert_eq!(
+        terminal_state.palette.background(),
+        Some((0x28, 0x28, 0x28))
+    );
+    terminal_state.handle_incoming_data(b"\x1b]11;?\x07");
+    assert_eq!(replies(&rx), "\x1b]11;rgb:2828/2828/2828\x1b\\");
+
+    terminal_state.handle_incoming_data(b"\x1b]110\x07\x1b]111\x07");
+    assert_eq!(terminal_state.palette, Palette::default());
+}
+
+#[test]
+fn test_degenerate_sizes_are_clamped() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.handle_incoming_data(b"hello\r\nworld");
+
+    for (requested, expected) in [
+        ((0, 0), (20, 5)),
+        ((1, 1), (20, 5)),
+        ((10_000, 10_000), (1000, 500)),
+        ((120, 0), (120, 5)),
+    ] {
+        terminal_state.set_win_size(requested.0, requested.1);
+        assert_eq!(terminal_state.get_win_size(), expected, "{requested:?}");
+        terminal_state.handle_incoming_data(b"\r\nmore output");
+    }
+    assert!(terminal_state
+        .to_plain_text(true)
+        .starts_with("hello\nworld\n"));
+
+    // Resizes asked for in cells are held to the limits too, with 0 keeping the current size
+    terminal_state.handle_incoming_data(b"\x1b[8;1;1t\x1b[8;9999;9999t\x1b[8;0;40t\x1b[8t");
+    assert_eq!(
+        terminal_state.window_commands,
+        vec![
+            WindowManipulation::ResizeWindowToLinesAndColumns(5, 20),
+            WindowManipulation::ResizeWindowToLinesAndColumns(500, 1000),
+            WindowManipulation::ResizeWindowToLinesAndColumns(5, 40),
+            WindowManipulation::ResizeWindowToLinesAndColumns(5, 120),
+        ]
+    );
+
+    // So the size reported back is the size the terminal really is
+    terminal_state.window_commands.clear();
+    terminal_state.set_win_size(1, 1);
+    rx.try_iter().for_each(drop);
+    terminal_state.handle_incoming_data(b"\x1b[18t");
+    assert_eq!(
+        terminal_state.window_commands,
+        vec![WindowManipulation::ReportTerminalSizeInCharacters]
+    );
+    let (width, height) = terminal_state.get_win_size();
+    terminal_state.report_terminal_size_in_characters(width, height);
+    assert_eq!(replies(&rx), "\x1b[8;5;20t");
+}
+
+#[test]
+fn test_tiny_configured_size() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits =
+        SizeLimits::new(TerminalGeometry::new(1, 1), TerminalGeometry::new(4, 2)).unwrap();
+
+    terminal_state.set_win_size(1, 1);
+    assert_eq!(terminal_state.get_win_size(), (1, 1));
+    // Wide characters that can't fit, and moves off the edge of the screen, don't panic
+    terminal_state.handle_incoming_data("ab日本\r\n\x1b[5;5H\x1b[2Jc\x1b[Ld\x1b[M".as_bytes());
+    terminal_state.set_win_size(0, 0);
+    terminal_state.set_win_size(100, 100);
+    assert_eq!(terminal_state.get_win_size(), (4, 2));
+    terminal_state.handle_incoming_data(b"\r\nend");
+    assert!(terminal_state
+        .to_plain_text(true)
+        .trim_end()
+        .ends_with("end"));
+}
+
+#[test]
+fn test_tiny_sizes_dont_panic() {
+    const INPUT: [&[u8]; 16] = [
+        b"a",
+        b"abcdef",
+        "日本".as_bytes(),
+        b"\r\n",
+        b"\n\n",
+        b"\x08",
+        b"\t",
+        b"\x1b[2;3H",
+        b"\x1b[2J",
+        b"\x1b[K",
+        b"\x1b[3@",
+        b"\x1b[2L",
+        b"\x1b[2M",
+        b"\x1b[2P",
+        b"\x1b[1;2r",
+        b"\x1bM",
+    ];
+
+    let mut rng = TinyRng(0x9e37_79b9_7f4a_7c15);
+    let mut next = |below| rng.next(below);
+
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    // Nothing stops a window being made this small but the configured limits
+    terminal_state.size_limits.min = TerminalGeometry::new(0, 0);
+    for _ in 0..5000 {
+        match next(8) {
+            0 => drop(terminal_state.set_win_size(next(4), next(4))),
+            1 => terminal_state.scroll(1.0),
+            2 => terminal_state.scroll(-1.0),
+            3 => terminal_state.set_no_wrap_view(next(2) == 0),
+            4 => terminal_state.scroll_columns(2.0),
+            _ => terminal_state.handle_incoming_data(INPUT[next(INPUT.len())]),
+        }
+
+        let _ = terminal_state.visible_text();
+        let _ = terminal_state.to_plain_text(true);
+        let _ = terminal_state.viewport_cursor_pos();
+        let _ = viewport_text(&mut terminal_state);
+    }
+}
+
+#[test]
+fn test_scrolling_region() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+    terminal_state.set_win_size(10, 5);
+    terminal_state.handle_incoming_data(b"top\r\n1\r\n2\r\n3\r\nbottom");
+
+    // Setting the region homes the cursor
+    terminal_state.handle_incoming_data(b"\x1b[2;4r");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 0, y: 0 });
+
+    // A newline at the bottom of the region scrolls just the region, and the line scrolled off
+    // its top is gone rather than in the scrollback
+    terminal_state.handle_incoming_data(b"\x1b[4;1H\r\nnew");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["top", "2", "3", "new", "bottom"]
+    );
+    assert_eq!(
+        terminal_state.to_plain_text(true),
+        "top\n2\n3\nnew\nbottom\n"
+    );
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 3, y: 3 });
+
+    // Inserted lines push the rest of the region down, and off its bottom
+    terminal_state.handle_incoming_data(b"\x1b[2;1H\x1b[L");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["top", "", "2", "3", "bottom"]
+    );
+
+    // Outside the region nothing is inserted, and newlines stop at the bottom of the screen
+    terminal_state.handle_incoming_data(b"\x1b[5;1H\x1b[L\n\n");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 0, y: 4 });
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["top", "", "2", "3", "bottom"]
+    );
+
+    // A region that doesn't fit is ignored
+    terminal_state.handle_incoming_data(b"\x1b[7;9r");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 0, y: 4 });
+
+    // Without a region the whole screen scrolls in to the scrollback again
+    terminal_state.handle_incoming_data(b"\x1b[r\x1b[5;1H\r\nafter");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["", "2", "3", "bottom", "after"]
+    );
+    assert!(terminal_state.to_plain_text(true).starts_with("top\n"));
+}
+
+#[test]
+fn test_scrolling_region_with_wrapped_lines() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+    terminal_state.set_win_size(5, 4);
+    terminal_state.handle_incoming_data(b"a\r\n0123456789xy");
+
+    terminal_state.handle_incoming_data(b"\x1b[2;3r\x1b[3;1H\n");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["a", "56789", "", "xy"]
+    );
+
+    terminal_state.handle_incoming_data(b"\x1b[3;1H\n\n");
+    assert_eq!(viewport_text(&mut terminal_state), vec!["a", "", "", "xy"]);
+    assert_eq!(terminal_state.to_plain_text(true), "a\n\n\nxy\n");
+}
+
+#[test]
+fn test_insert_lines_after_resizes_and_erase() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+    terminal_state.handle_incoming_data(b"\x1b[99;99H");
+    terminal_state.set_win_size(11, 5);
+    terminal_state.set_win_size(6, 4);
+
+    // The rows from before the resizes, and from before the erase, are long gone by now
+    terminal_state.handle_incoming_data(b"\x1b[r\n\x1b[J\x1b[2L");
+    let rows = viewport_text(&mut terminal_state);
+    assert_eq!(rows.len(), 4);
+    assert!(rows.iter().all(|row| row.trim().is_empty()));
+
+    terminal_state.handle_incoming_data(b"\x1b[Hx");
+    assert_eq!(viewport_text(&mut terminal_state)[0].trim_end(), "x");
+}
+
+#[test]
+fn test_index_and_reverse_index() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.size_limits.min = TerminalGeometry::new(1, 1);
+    terminal_state.set_win_size(10, 4);
+    terminal_state.handle_incoming_data(b"one\x1bDtwo\x1bEthree");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["one", "   two", "three"]
+    );
+
+    // Reverse index moves up, then at the top of the screen scrolls it down like less does
+    terminal_state.handle_incoming_data(b"\x1bM\x1bM\x1bM\rzero");
+    assert_eq!(terminal_state.cursor_pos(), CursorPos { x: 4, y: 0 });
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["zero", "one", "   two", "three"]
+    );
+    terminal_state.handle_incoming_data(b"\x1bM\r-1");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["-1", "zero", "one", "   two"]
+    );
+    assert!(!terminal_state.to_plain_text(true).contains("three"));
+
+    // Within a scrolling region only the region moves. "one" is pushed out of the bottom of it,
+    // then "up" out of the top
+    terminal_state.handle_incoming_data(b"\x1b[2;3r\x1b[2;1H\x1bMup\x1b[3;1H\x1bDdown");
+    assert_eq!(
+        viewport_text(&mut terminal_state),
+        vec!["-1", "zero", "down", "   two"]
+    );
+}
+
+#[test]
+fn test_output_times_echo_only_when_measuring() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    let input_latency = terminal_state.input_latency.clone();
+
+    // Not measuring, so nothing is timed, even with a key press in flight
+    input_latency.lock().enabled = true;
+    input_latency.lock().key_pressed(Instant::now());
+    input_latency.lock().written(Instant::now());
+    input_latency.lock().enabled = false;
+    terminal_state.handle_incoming_data(b"a");
+    assert_eq!(input_latency.lock().to_echo.samples(), 0);
+
+    input_latency.set_enabled(true);
+    input_latency.lock().key_pressed(Instant::now());
+    input_latency.lock().written(Instant::now());
+    terminal_state.handle_incoming_data(b"b");
+    assert_eq!(input_latency.lock().to_echo.samples(), 1);
+}
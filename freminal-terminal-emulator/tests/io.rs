@@ -0,0 +1,72 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::io::{coalesce_reads, PtyRead};
+use test_log::test;
+
+fn read(data: &[u8]) -> PtyRead {
+    PtyRead {
+        buf: data.to_vec(),
+        read_amount: data.len(),
+    }
+}
+
+#[test]
+fn test_coalesce_reads() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    tx.send(read(b"two ")).unwrap();
+    tx.send(read(b"three")).unwrap();
+
+    assert_eq!(coalesce_reads(&read(b"one "), &rx, 1024), b"one two three");
+    assert!(rx.is_empty());
+}
+
+#[test]
+fn test_coalesce_reads_stops_at_limit() {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    tx.send(read(b"bbbb")).unwrap();
+    tx.send(read(b"cccc")).unwrap();
+
+    // Reads are never split, so the batch can go over the limit by up to one read
+    assert_eq!(coalesce_reads(&read(b"aaaa"), &rx, 6), b"aaaabbbb");
+    assert_eq!(rx.len(), 1);
+
+    // Only part of the buffer was read in to
+    let partial = PtyRead {
+        buf: b"dd\0\0".to_vec(),
+        read_amount: 2,
+    };
+    assert_eq!(coalesce_reads(&partial, &rx, 2), b"dd");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_process_name() {
+    use freminal_terminal_emulator::io::foreground::process_name;
+
+    let name = process_name(std::process::id()).unwrap();
+    assert!(!name.is_empty());
+    assert!(!name.contains('/'));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_echoes_input() {
+    use freminal_terminal_emulator::io::foreground::echoes_input;
+    use nix::sys::termios::{tcsetattr, LocalFlags, SetArg};
+    use portable_pty::{native_pty_system, PtySize};
+    use std::os::fd::BorrowedFd;
+
+    let pair = native_pty_system().openpty(PtySize::default()).unwrap();
+    assert_eq!(echoes_input(pair.master.as_ref()), Some(true));
+
+    // As a program reading a password does
+    let mut termios = pair.master.get_termios().unwrap();
+    termios.local_flags.remove(LocalFlags::ECHO);
+    // SAFETY: the fd belongs to `pair.master`, which outlives this borrow
+    let fd = unsafe { BorrowedFd::borrow_raw(pair.master.as_raw_fd().unwrap()) };
+    tcsetattr(fd, SetArg::TCSANOW, &termios).unwrap();
+    assert_eq!(echoes_input(pair.master.as_ref()), Some(false));
+}
@@ -0,0 +1,83 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::{Duration, SystemTime};
+
+use freminal_terminal_emulator::state::line_timestamps::{LineTimestamps, WRITE_COALESCE_INTERVAL};
+use test_log::test;
+
+#[test]
+fn test_line_timestamps_for_lines() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+    let later = start + WRITE_COALESCE_INTERVAL * 5;
+
+    let mut timestamps = LineTimestamps::new();
+    timestamps.written(0..10, start);
+    timestamps.written(10..20, later);
+
+    let lines = [0..5, 5..12, 12..20, 20..25];
+    assert_eq!(
+        timestamps.for_lines(&lines),
+        vec![Some(start), Some(later), Some(later), None]
+    );
+}
+
+#[test]
+fn test_line_timestamps_overwrite() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+    let later = start + WRITE_COALESCE_INTERVAL * 5;
+
+    let mut timestamps = LineTimestamps::new();
+    timestamps.written(0..10, start);
+    timestamps.written(0..10, later);
+    assert_eq!(
+        timestamps.for_lines(&[0..10, 10..20]),
+        vec![Some(later), None]
+    );
+
+    // Writes close together are merged, taking the time of the latest
+    let mut timestamps = LineTimestamps::new();
+    timestamps.written(0..10, start);
+    timestamps.written(10..20, start + Duration::from_millis(10));
+    assert_eq!(
+        timestamps.for_lines(&[0..10, 10..20]),
+        vec![
+            Some(start + Duration::from_millis(10)),
+            Some(start + Duration::from_millis(10))
+        ]
+    );
+}
+
+#[test]
+fn test_line_timestamps_shift() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+    let later = start + WRITE_COALESCE_INTERVAL * 5;
+
+    let mut timestamps = LineTimestamps::new();
+    timestamps.written(0..10, start);
+    timestamps.written(10..20, later);
+
+    // The first line scrolls off the top of the scrollback
+    timestamps.clip(&(0..10));
+    assert_eq!(
+        timestamps.for_lines(&[0..10, 10..20]),
+        vec![Some(later), None]
+    );
+
+    timestamps.insert_range(&(0..5));
+    assert_eq!(
+        timestamps.for_lines(&[0..5, 5..15]),
+        vec![None, Some(later)]
+    );
+
+    timestamps.delete_range(&(0..5));
+    assert_eq!(
+        timestamps.for_lines(&[0..10, 10..20]),
+        vec![Some(later), None]
+    );
+
+    timestamps.clear();
+    assert_eq!(timestamps.for_lines(&[0..10, 10..20]), vec![None, None]);
+}
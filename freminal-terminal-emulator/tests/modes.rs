@@ -6,9 +6,10 @@
 use freminal_terminal_emulator::ansi_components::{
     mode::SetMode,
     modes::{
-        decawm::Decawm, decckm::Decckm, dectcem::Dectcem, rl_bracket::RlBracket,
-        sync_updates::SynchronizedUpdates, unknown::UnknownMode, xtcblink::XtCBlink,
-        xtextscrn::XtExtscrn, xtmsewin::XtMseWin, ReportMode,
+        alt_scroll::AltScroll, bdsm::Bdsm, decawm::Decawm, decbkm::Decbkm, decckm::Decckm,
+        decnkm::Decnkm, decom::Decom, dectcem::Dectcem, rl_bracket::RlBracket,
+        save_cursor::SaveCursor, sync_updates::SynchronizedUpdates, unknown::UnknownMode,
+        xtcblink::XtCBlink, xtextscrn::XtExtscrn, xtmsewin::XtMseWin, ReportMode,
     },
 };
 use test_log::test;
@@ -41,6 +42,112 @@ fn test_decckm() {
     assert!(mode.report(Some(SetMode::DecQuery)).contains("\x1b[?1;0$y"));
 }
 
+#[test]
+fn test_decnkm() {
+    let mode = Decnkm::new(&SetMode::DecRst);
+    assert_eq!(mode, Decnkm::Numeric);
+    assert_eq!(mode.to_string(), "Numeric Keypad Mode (DECNKM) Numeric");
+    assert!(mode.report(None).contains("\x1b[?66;2$y"));
+    assert!(mode.report(Some(SetMode::DecSet)).contains("\x1b[?66;1$y"));
+
+    let mode = Decnkm::new(&SetMode::DecSet);
+    assert_eq!(mode, Decnkm::Application);
+    assert_eq!(mode.to_string(), "Numeric Keypad Mode (DECNKM) Application");
+    assert!(mode.report(None).contains("\x1b[?66;1$y"));
+    assert!(mode.report(Some(SetMode::DecRst)).contains("\x1b[?66;2$y"));
+
+    let mode = Decnkm::new(&SetMode::DecQuery);
+    assert_eq!(mode, Decnkm::Query);
+    assert_eq!(mode.to_string(), "Numeric Keypad Mode (DECNKM) Query");
+    assert!(mode.report(None).contains("\x1b[?66;0$y"));
+}
+
+#[test]
+fn test_decbkm() {
+    let mode = Decbkm::new(&SetMode::DecRst);
+    assert_eq!(mode, Decbkm::Delete);
+    assert_eq!(mode.to_string(), "Backarrow Key Mode (DECBKM) Delete");
+    assert!(mode.report(None).contains("\x1b[?67;2$y"));
+    assert!(mode.report(Some(SetMode::DecSet)).contains("\x1b[?67;1$y"));
+
+    let mode = Decbkm::new(&SetMode::DecSet);
+    assert_eq!(mode, Decbkm::Backspace);
+    assert_eq!(mode.to_string(), "Backarrow Key Mode (DECBKM) Backspace");
+    assert!(mode.report(None).contains("\x1b[?67;1$y"));
+    assert!(mode.report(Some(SetMode::DecRst)).contains("\x1b[?67;2$y"));
+
+    let mode = Decbkm::new(&SetMode::DecQuery);
+    assert_eq!(mode, Decbkm::Query);
+    assert_eq!(mode.to_string(), "Backarrow Key Mode (DECBKM) Query");
+    assert!(mode.report(None).contains("\x1b[?67;0$y"));
+}
+
+#[test]
+fn test_decom() {
+    let mode = Decom::new(&SetMode::DecRst);
+    assert_eq!(mode, Decom::Normal);
+    assert_eq!(mode.to_string(), "Origin Mode (DECOM) Normal");
+    assert!(mode.report(None).contains("\x1b[?6;2$y"));
+    assert!(mode.report(Some(SetMode::DecSet)).contains("\x1b[?6;1$y"));
+
+    let mode = Decom::new(&SetMode::DecSet);
+    assert_eq!(mode, Decom::Origin);
+    assert_eq!(mode.to_string(), "Origin Mode (DECOM) Origin");
+    assert!(mode.report(None).contains("\x1b[?6;1$y"));
+    assert!(mode.report(Some(SetMode::DecRst)).contains("\x1b[?6;2$y"));
+
+    let mode = Decom::new(&SetMode::DecQuery);
+    assert_eq!(mode, Decom::Query);
+    assert_eq!(mode.to_string(), "Origin Mode (DECOM) Query");
+    assert!(mode.report(None).contains("\x1b[?6;0$y"));
+}
+
+#[test]
+fn test_bdsm() {
+    let mode = Bdsm::new(&SetMode::DecSet);
+    assert_eq!(mode, Bdsm::Implicit);
+    assert_eq!(
+        mode.to_string(),
+        "Bi-Directional Support Mode (BDSM) Implicit"
+    );
+    assert!(mode.report(None).contains("\x1b[8;1$y"));
+    assert!(mode.report(Some(SetMode::DecRst)).contains("\x1b[8;2$y"));
+
+    let mode = Bdsm::new(&SetMode::DecRst);
+    assert_eq!(mode, Bdsm::Explicit);
+    assert_eq!(
+        mode.to_string(),
+        "Bi-Directional Support Mode (BDSM) Explicit"
+    );
+    assert!(mode.report(None).contains("\x1b[8;2$y"));
+
+    let mode = Bdsm::new(&SetMode::DecQuery);
+    assert_eq!(mode, Bdsm::Query);
+    assert_eq!(mode.to_string(), "Bi-Directional Support Mode (BDSM) Query");
+    assert!(mode.report(None).contains("\x1b[8;0$y"));
+}
+
+#[test]
+fn test_save_cursor() {
+    let mode = SaveCursor::new(&SetMode::DecSet);
+    assert_eq!(mode, SaveCursor::Save);
+    assert_eq!(mode.to_string(), "Save Cursor (XT_SAVE_CURSOR) Save");
+    assert!(mode.report(None).contains("\x1b[?1048;1$y"));
+    assert!(mode
+        .report(Some(SetMode::DecRst))
+        .contains("\x1b[?1048;2$y"));
+
+    let mode = SaveCursor::new(&SetMode::DecRst);
+    assert_eq!(mode, SaveCursor::Restore);
+    assert_eq!(mode.to_string(), "Save Cursor (XT_SAVE_CURSOR) Restore");
+    assert!(mode.report(None).contains("\x1b[?1048;2$y"));
+
+    let mode = SaveCursor::new(&SetMode::DecQuery);
+    assert_eq!(mode, SaveCursor::Query);
+    assert_eq!(mode.to_string(), "Save Cursor (XT_SAVE_CURSOR) Query");
+    assert!(mode.report(None).contains("\x1b[?1048;0$y"));
+}
+
 #[test]
 fn test_decawm() {
     // Test the DECAWM mode
@@ -330,6 +437,29 @@ fn test_xtmsewin() {
         .contains("\x1b[?1004;0$y"));
 }
 
+#[test]
+fn test_alt_scroll() {
+    let mode = AltScroll::default();
+    assert_eq!(mode, AltScroll::Enabled);
+    assert_eq!(mode.to_string(), "Alternate Scroll Mode Enabled");
+    assert!(mode.report(None).contains("\x1b[?1007;1$y"));
+    assert!(mode
+        .report(Some(SetMode::DecRst))
+        .contains("\x1b[?1007;2$y"));
+
+    let mode = AltScroll::new(&SetMode::DecRst);
+    assert_eq!(mode, AltScroll::Disabled);
+    assert_eq!(mode.to_string(), "Alternate Scroll Mode Disabled");
+    assert!(mode.report(None).contains("\x1b[?1007;2$y"));
+    assert!(mode
+        .report(Some(SetMode::DecSet))
+        .contains("\x1b[?1007;1$y"));
+
+    let mode = AltScroll::new(&SetMode::DecQuery);
+    assert_eq!(mode, AltScroll::Query);
+    assert!(mode.report(None).contains("\x1b[?1007;0$y"));
+}
+
 #[test]
 fn test_unknown_mode() {
     let mode = UnknownMode::new(&[0x69]);
@@ -0,0 +1,113 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use freminal_common::redaction::Redactor;
+use freminal_terminal_emulator::{
+    ansi::TerminalOutput,
+    state::{internal::TerminalState, output_processor::OutputProcessor, redaction::RedactSecrets},
+};
+use test_log::test;
+
+/// Masks a password before it reaches the screen
+#[derive(Debug)]
+struct MaskPassword;
+
+impl OutputProcessor for MaskPassword {
+    fn process(&mut self, output: &mut Vec<TerminalOutput>) {
+        for segment in output {
+            if let TerminalOutput::Data(data) = segment {
+                if let Some(start) = data.windows(6).position(|window| window == b"hunter") {
+                    data[start..start + 6].copy_from_slice(b"******");
+                }
+            }
+        }
+    }
+}
+
+/// Drops bells
+#[derive(Debug)]
+struct NoBells;
+
+impl OutputProcessor for NoBells {
+    fn process(&mut self, output: &mut Vec<TerminalOutput>) {
+        output.retain(|segment| *segment != TerminalOutput::Bell);
+    }
+}
+
+/// Counts the text it sees
+#[derive(Debug)]
+struct CountData(Arc<AtomicUsize>);
+
+impl OutputProcessor for CountData {
+    fn process(&mut self, output: &mut Vec<TerminalOutput>) {
+        for segment in output.iter() {
+            if let TerminalOutput::Data(data) = segment {
+                self.0.fetch_add(data.len(), Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_output_processors() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    let counted = Arc::new(AtomicUsize::new(0));
+    terminal_state.window_focused = false;
+
+    terminal_state.add_output_processor(Box::new(MaskPassword));
+    terminal_state.add_output_processor(Box::new(NoBells));
+    terminal_state.add_output_processor(Box::new(CountData(counted.clone())));
+
+    terminal_state.handle_incoming_data(b"password: hunter2\x07");
+
+    assert_eq!(terminal_state.to_plain_text(false), "password: ******2\n");
+    assert_eq!(terminal_state.bell.unfocused_bells(), 0);
+    assert_eq!(counted.load(Ordering::SeqCst), "password: ******2".len());
+}
+
+#[test]
+fn test_redact_secrets() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    let redactor = Redactor::new(&["ghp_[a-z0-9]{6}".to_string()]).unwrap();
+    terminal_state.add_output_processor(Box::new(RedactSecrets::new(redactor)));
+
+    terminal_state
+        .handle_incoming_data(b"export TOKEN=ghp_abc123\r\n\x1b[31mghp_zzz999\x1b[0m done");
+
+    assert_eq!(
+        terminal_state.to_plain_text(false),
+        "export TOKEN=**********\n********** done\n"
+    );
+}
+
+#[test]
+fn test_redact_split_secrets() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_redactor(
+        Redactor::new(&["ghp_[a-z0-9]{6}".to_string(), "秘密[0-9]{4}".to_string()]).unwrap(),
+    );
+
+    // Split between two reads from the pty
+    terminal_state.handle_incoming_data(b"export TOKEN=ghp_ab");
+    terminal_state.handle_incoming_data(b"c123\r\n");
+    // And by a color change
+    terminal_state.handle_incoming_data(b"ghp_zz\x1b[31mz999\x1b[0m done\r\n");
+    // Wide characters keep their cells
+    terminal_state.handle_incoming_data("秘".as_bytes());
+    terminal_state.handle_incoming_data("密1234 end".as_bytes());
+
+    assert_eq!(
+        terminal_state.to_plain_text(false),
+        "export TOKEN=**********\n********** done\n******** end\n"
+    );
+}
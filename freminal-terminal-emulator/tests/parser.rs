@@ -0,0 +1,34 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+#![cfg(feature = "parser")]
+
+use freminal_terminal_emulator::parser::{
+    FreminalAnsiParser, Mode, SelectGraphicRendition, TerminalOutput,
+};
+use test_log::test;
+
+#[test]
+fn test_parser_api() {
+    let mut parser = FreminalAnsiParser::new();
+    let output = parser.push(b"\x1b[1mbold\x1b[?25l");
+
+    assert_eq!(output[0], TerminalOutput::Sgr(SelectGraphicRendition::Bold));
+    assert_eq!(output[1], TerminalOutput::Data(b"bold".to_vec()));
+    assert!(matches!(output[2], TerminalOutput::Mode(Mode::Dectem(_))));
+}
+
+#[test]
+fn test_parser_api_split_sequence() {
+    let mut parser = FreminalAnsiParser::new();
+    assert!(parser.push(b"\x1b[3").is_empty());
+    assert_eq!(
+        parser.push(b";4H"),
+        vec![TerminalOutput::SetCursorPos {
+            x: Some(4),
+            y: Some(3)
+        }]
+    );
+}
@@ -0,0 +1,175 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::redaction::Redactor;
+use freminal_terminal_emulator::{
+    io::recording::{parse_recording, read_recording, Recording, RecordingDestination},
+    state::internal::TerminalState,
+};
+use test_log::test;
+
+#[test]
+fn test_recording_to_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.rec");
+    std::fs::write(&path, "stale").unwrap();
+
+    let mut recording = Recording::new(RecordingDestination::File(path.clone()));
+    assert!(!recording.is_recording());
+
+    // Nothing is written until we start
+    recording.write(b"a");
+    recording.start().unwrap();
+    assert!(recording.is_recording());
+    assert_eq!(recording.path(), Some(path.as_path()));
+
+    recording.write(b"hi");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "104,105,");
+
+    recording.stop();
+    assert!(!recording.is_recording());
+    recording.write(b"x");
+
+    // Starting again carries on where we left off
+    recording.toggle().unwrap();
+    recording.write(b"!");
+    recording.toggle().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "104,105,33,");
+}
+
+#[test]
+fn test_recording_to_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let directory = dir.path().join("recordings");
+
+    let mut recording = Recording::new(RecordingDestination::Directory(directory.clone()));
+    recording.start().unwrap();
+    let first = recording.path().unwrap().to_path_buf();
+    recording.write(b"1");
+    recording.stop();
+
+    recording.start().unwrap();
+    let second = recording.path().unwrap().to_path_buf();
+    recording.write(b"2");
+    recording.stop();
+
+    assert_ne!(first, second);
+    assert!(first.starts_with(&directory));
+    assert!(first
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("freminal-recording-"));
+    assert_eq!(std::fs::read_to_string(&first).unwrap(), "49,");
+    assert_eq!(std::fs::read_to_string(&second).unwrap(), "50,");
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 2);
+}
+
+#[test]
+fn test_recording_is_redacted() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.rec");
+
+    let mut recording = Recording::new(RecordingDestination::File(path.clone()));
+    recording.set_redactor(Redactor::new(&["s3cret".to_string()]).unwrap());
+    recording.start().unwrap();
+    recording.write(b"a s3cret");
+    recording.stop();
+
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "97,32,42,42,42,42,42,42,"
+    );
+}
+
+#[test]
+fn test_recording_redacts_split_secrets() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.rec");
+
+    let mut recording = Recording::new(RecordingDestination::File(path.clone()));
+    recording.set_redactor(Redactor::new(&["s3cret".to_string()]).unwrap());
+    recording.start().unwrap();
+    recording.write(b"a s3");
+    recording.write(b"c\x1b[1mret b s3c");
+    // The start of what may be a secret is held back until the recording stops
+    assert_eq!(
+        parse_recording(&std::fs::read_to_string(&path).unwrap()).unwrap(),
+        b"a ***\x1b[1m***"
+    );
+    recording.stop();
+
+    assert_eq!(
+        parse_recording(&std::fs::read_to_string(&path).unwrap()).unwrap(),
+        b"a ***\x1b[1m*** b s3c"
+    );
+}
+
+#[test]
+fn test_parse_recording() {
+    assert_eq!(parse_recording("104,105,").unwrap(), b"hi");
+    assert_eq!(parse_recording("27, 91,\n").unwrap(), b"\x1b[");
+    assert!(parse_recording("").unwrap().is_empty());
+    assert!(parse_recording("104,,105,").is_err());
+    assert!(parse_recording("256,")
+        .unwrap_err()
+        .to_string()
+        .contains("\"256\""));
+}
+
+/// What is on the screen, and how it is formatted
+fn snapshot(terminal_state: &mut TerminalState) -> impl PartialEq + std::fmt::Debug {
+    let cursor_pos = terminal_state.cursor_pos();
+    let buffer = terminal_state.get_current_buffer();
+    (
+        buffer.terminal_buffer.data(true),
+        buffer.format_tracker.tags(),
+        cursor_pos,
+    )
+}
+
+#[test]
+fn test_playback_matches_live_output() {
+    let output =
+        "plain \x1b[1;31mbold red\x1b[0m \x1b[38;5;208m256\x1b[48;2;10;20;30mtrue\x1b[0m\r\n\
+        \x1b[4;3munder\x1b[24;23m \x1b]8;id=a;https://example.com/a\x1b\\link\x1b]8;;\x1b\\ \
+        \x1b]8;;https://example.com/b\x07bell\x1b]8;;\x07\r\n\
+        \x1b[7m日本\x1b[27m done";
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.rec");
+    let mut recording = Recording::new(RecordingDestination::File(path.clone()));
+    recording.start().unwrap();
+
+    // The shell's output arrives in reads that split sequences and characters part way through
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut live = TerminalState::new(tx);
+    let mut rest = output.as_bytes();
+    for size in [1, 7, 3, 13].into_iter().cycle() {
+        let (read, left) = rest.split_at(size.min(rest.len()));
+        recording.write(read);
+        live.handle_incoming_data(read);
+        rest = left;
+        if rest.is_empty() {
+            break;
+        }
+    }
+    recording.stop();
+
+    // The recording is exactly what the shell wrote
+    let recorded = read_recording(&path).unwrap();
+    assert_eq!(recorded, output.as_bytes());
+
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut played_back = TerminalState::new(tx);
+    played_back.handle_incoming_data(&recorded);
+
+    let live = snapshot(&mut live);
+    assert_eq!(live, snapshot(&mut played_back));
+    // Make sure there was formatting to compare
+    let tags = format!("{live:?}");
+    assert!(tags.contains("https://example.com/a"));
+    assert!(tags.contains("https://example.com/b"));
+}
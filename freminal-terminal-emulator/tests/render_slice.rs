@@ -0,0 +1,236 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::colors::TerminalColor;
+use freminal_terminal_emulator::state::{
+    cursor::CursorPos,
+    data::{RenderRow, RenderSlice},
+    internal::TerminalState,
+    term_char::{display_vec_tchar_as_string, TChar},
+};
+use test_log::test;
+
+/// Check `slice` keeps the promises `RenderSlice` makes about how it lines up with the buffer
+fn assert_lines_up(terminal_state: &mut TerminalState, slice: &RenderSlice) {
+    let buf = &terminal_state.get_current_buffer().terminal_buffer.buf;
+    assert_ne!(slice.text.last(), Some(&TChar::NewLine));
+
+    let mut previous_end = 0;
+    for row in &slice.rows {
+        assert!(row.text.start >= previous_end, "rows overlap: {slice:?}");
+        assert!(slice.text[row.text.clone()]
+            .iter()
+            .eq(buf.range(row.buffer.clone())));
+        previous_end = row.text.end;
+    }
+
+    // The tags cover the text, one after another, and stop where it does
+    let mut covered = 0;
+    for tag in &slice.format_data {
+        assert_eq!(
+            tag.start, covered,
+            "tags don't line up: {:?}",
+            slice.format_data
+        );
+        assert!(tag.start < tag.end);
+        covered = tag.end;
+    }
+    assert_eq!(covered, slice.text.len());
+}
+
+/// The text of the slice on each row
+fn row_text(slice: &RenderSlice) -> Vec<String> {
+    slice
+        .rows
+        .iter()
+        .map(|row| display_vec_tchar_as_string(&slice.text[row.text.clone()]))
+        .collect()
+}
+
+/// The color the text at `text_offset` is drawn in
+fn color_at(slice: &RenderSlice, text_offset: usize) -> TerminalColor {
+    slice
+        .format_data
+        .iter()
+        .find(|tag| (tag.start..tag.end).contains(&text_offset))
+        .map(|tag| tag.colors.color)
+        .unwrap()
+}
+
+#[test]
+fn test_wrapped_slice_lines_up_with_the_buffer() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+    terminal_state.handle_incoming_data(b"one\r\n\x1b[31mtwo\x1b[0m\r\nthree\r\nfour");
+
+    let slice = terminal_state.render_slice();
+    assert_lines_up(&mut terminal_state, &slice);
+    assert_eq!(
+        display_vec_tchar_as_string(&slice.text),
+        "one\ntwo\nthree\nfour"
+    );
+    assert_eq!(row_text(&slice), ["one", "two", "three", "four"]);
+    assert_eq!(slice.buffer_range(), 0..slice.text.len());
+
+    let two = slice.rows[1].buffer.start;
+    assert_eq!(
+        color_at(&slice, slice.text_offset(two).unwrap()),
+        TerminalColor::Red
+    );
+    assert_eq!(
+        color_at(&slice, slice.text_offset(two + 3).unwrap()),
+        TerminalColor::Default
+    );
+    // The last tag runs to the end of the text, rather than on forever
+    assert_eq!(slice.format_data.last().unwrap().end, slice.text.len());
+}
+
+#[test]
+fn test_scrolled_back_slice_lines_up_with_the_buffer() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+    terminal_state.handle_incoming_data(b"line 0\r\n\x1b[32m");
+    for line in 1..12 {
+        terminal_state.handle_incoming_data(format!("line {line}\r\n").as_bytes());
+    }
+    terminal_state.handle_incoming_data(b"\x1b[0mend");
+
+    // The offsets are into the slice, not the buffer, when the top row isn't the buffer's first
+    let slice = terminal_state.render_slice();
+    assert_lines_up(&mut terminal_state, &slice);
+    assert_eq!(row_text(&slice)[0], "line 8");
+    assert_ne!(slice.buffer_range().start, 0);
+    assert_eq!(color_at(&slice, 0), TerminalColor::Green);
+
+    // A tag that carries on past the bottom of the viewport is cut off there, not dropped
+    terminal_state.scroll(5.0);
+    let slice = terminal_state.render_slice();
+    assert_lines_up(&mut terminal_state, &slice);
+    assert_eq!(row_text(&slice)[0], "line 3");
+    for row in &slice.rows {
+        assert_eq!(color_at(&slice, row.text.start), TerminalColor::Green);
+    }
+    let last = slice.format_data.last().unwrap();
+    assert_eq!(last.colors.color, TerminalColor::Green);
+    assert_eq!(last.end, slice.text.len());
+}
+
+#[test]
+fn test_unwrapped_slice_lines_up_with_the_buffer() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+    terminal_state.set_no_wrap_view(true);
+    terminal_state.handle_incoming_data(b"abcdefghijklmnopqrstuvwxyz\r\n\x1b[34mijk\x1b[0m\r\nlm");
+
+    let slice = terminal_state.render_slice();
+    assert_lines_up(&mut terminal_state, &slice);
+    assert_eq!(
+        display_vec_tchar_as_string(&slice.text),
+        "abcdefghijklmnopqrst\nijk\nlm"
+    );
+    assert_eq!(row_text(&slice), ["abcdefghijklmnopqrst", "ijk", "lm"]);
+
+    // Offsets go between the buffer and the text, except for the newlines put between rows
+    let ijk = slice.rows[1].buffer.start;
+    assert_eq!(slice.text_offset(ijk), Some(21));
+    assert_eq!(slice.buffer_offset(21), Some(ijk));
+    assert_eq!(slice.buffer_offset(20), None);
+    assert_eq!(
+        slice.text_offset(21),
+        None,
+        "v is off the edge of the screen"
+    );
+    assert_eq!(color_at(&slice, 21), TerminalColor::Blue);
+    assert_eq!(color_at(&slice, 25), TerminalColor::Default);
+}
+
+#[test]
+fn test_new_slice_trims_the_last_newline() {
+    let text = TChar::from_vec(b"ab\n").unwrap();
+    let rows = vec![RenderRow {
+        buffer: 4..7,
+        text: 0..3,
+        ends_line: true,
+    }];
+    let slice = RenderSlice::new(text, Vec::new(), rows);
+
+    assert_eq!(display_vec_tchar_as_string(&slice.text), "ab");
+    assert_eq!(slice.rows[0].text, 0..2);
+    assert_eq!(slice.buffer_range(), 4..6);
+    assert_eq!(slice.text_offset(6), Some(2));
+    assert_eq!(slice.text_offset(7), None);
+    assert_eq!(RenderSlice::default().buffer_range(), 0..0);
+}
+
+#[test]
+fn test_alternate_screen_slice_lines_up_with_its_buffer() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+    for line in 0..8 {
+        terminal_state.handle_incoming_data(format!("shell {line}\r\n").as_bytes());
+    }
+    let primary = terminal_state.render_slice();
+
+    // The alternate screen's offsets are into its own buffer, which has no scrollback
+    terminal_state.handle_incoming_data(b"\x1b[?1049h\x1b[H\x1b[35mmenu\x1b[0m\r\nitem");
+    let slice = terminal_state.render_slice();
+    assert_lines_up(&mut terminal_state, &slice);
+    assert_eq!(row_text(&slice)[..2], ["menu", "item"]);
+    assert_eq!(slice.buffer_range().start, 0);
+    assert_eq!(color_at(&slice, 0), TerminalColor::Magenta);
+
+    // And the primary screen's slice is back where it was when the program leaves
+    terminal_state.handle_incoming_data(b"\x1b[?1049l");
+    let slice = terminal_state.render_slice();
+    assert_lines_up(&mut terminal_state, &slice);
+    assert_eq!(slice, primary);
+}
+
+#[test]
+fn test_hit_testing_lines_up_with_the_buffer() {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    terminal_state.set_win_size(20, 5);
+    // A line that wraps on to a second row, then one that doesn't
+    terminal_state.handle_incoming_data(b"0123456789abcdefghijKLM\r\nshort\r\n");
+
+    let slice = terminal_state.render_slice();
+    let at = |x, y| slice.buffer_offset_at(&CursorPos { x, y });
+    let nearest = |x, y| slice.nearest_buffer_offset(&CursorPos { x, y });
+    assert_eq!(at(5, 0), Some(5));
+    assert_eq!(at(1, 1), Some(21));
+    assert_eq!(at(3, 1), None, "past the end of the line");
+    assert_eq!(at(0, 2), Some(24));
+
+    // Past the end of a wrapped row is its last character, and of a line its newline
+    assert_eq!(nearest(30, 0), Some(19));
+    assert_eq!(nearest(30, 1), Some(23));
+    assert_eq!(nearest(0, 10), Some(29));
+    assert_eq!(
+        slice.cells(&(18..26)),
+        vec![(0, 18..20), (1, 0..3), (2, 0..2)]
+    );
+
+    // Cut off at the edges of the screen, the columns start at the left edge
+    terminal_state.set_no_wrap_view(true);
+    terminal_state.scroll_columns(3.0);
+    let slice = terminal_state.render_slice();
+    assert_lines_up(&mut terminal_state, &slice);
+    assert_eq!(slice.buffer_offset_at(&CursorPos { x: 0, y: 0 }), Some(3));
+    assert_eq!(slice.buffer_offset_at(&CursorPos { x: 0, y: 1 }), Some(27));
+    assert_eq!(
+        slice.nearest_buffer_offset(&CursorPos { x: 10, y: 1 }),
+        Some(29)
+    );
+    assert_eq!(slice.cells(&(0..28)), vec![(0, 0..20), (1, 0..1)]);
+    assert_eq!(
+        RenderSlice::default().nearest_buffer_offset(&CursorPos::default()),
+        None
+    );
+}
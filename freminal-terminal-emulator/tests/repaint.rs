@@ -0,0 +1,44 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::Duration;
+
+use freminal_terminal_emulator::state::repaint::RepaintLimiter;
+use test_log::test;
+
+#[test]
+fn test_uncapped() {
+    let mut limiter = RepaintLimiter::new(0);
+    assert_eq!(limiter.min_frame_time(), None);
+
+    limiter.frame_rendered();
+    assert_eq!(limiter.delay_until_next_frame(), Duration::ZERO);
+}
+
+#[test]
+fn test_capped() {
+    let mut limiter = RepaintLimiter::new(10);
+    assert_eq!(limiter.min_frame_time(), Some(Duration::from_millis(100)));
+
+    // nothing has been drawn yet, so there is nothing to wait for
+    assert_eq!(limiter.delay_until_next_frame(), Duration::ZERO);
+
+    limiter.frame_rendered();
+    let delay = limiter.delay_until_next_frame();
+    assert!(delay > Duration::ZERO);
+    assert!(delay <= Duration::from_millis(100));
+
+    std::thread::sleep(Duration::from_millis(110));
+    assert_eq!(limiter.delay_until_next_frame(), Duration::ZERO);
+}
+
+#[test]
+fn test_default() {
+    let limiter = RepaintLimiter::default();
+    assert_eq!(
+        limiter.min_frame_time(),
+        Some(Duration::from_secs(1) / freminal_common::args::DEFAULT_MAX_FPS)
+    );
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_common::colors::TerminalColor;
+use freminal_terminal_emulator::{
+    format_tracker::FormatTag,
+    state::{
+        search::{find_matches, SearchState, CURRENT_SEARCH_MATCH_COLOR, SEARCH_MATCH_COLOR},
+        term_char::TChar,
+    },
+};
+use test_log::test;
+
+fn tchars(s: &str) -> Vec<TChar> {
+    TChar::from_vec(s.as_bytes()).unwrap()
+}
+
+#[test]
+fn test_find_matches() {
+    let buf = tchars("Foo bar foo\nfoo");
+    assert_eq!(find_matches(&buf, "foo"), vec![0..3, 8..11, 12..15]);
+    assert_eq!(find_matches(&buf, "Foo"), vec![0..3]);
+    assert_eq!(find_matches(&buf, "o b"), vec![2..5]);
+    // a match can't run across a hard line break
+    assert!(find_matches(&buf, "foofoo").is_empty());
+    assert!(find_matches(&buf, "").is_empty());
+}
+
+#[test]
+fn test_find_matches_wide_characters() {
+    let mut buf = tchars("a");
+    buf.extend(tchars("日"));
+    buf.push(TChar::WideContinuation);
+    buf.extend(tchars("本"));
+    buf.push(TChar::WideContinuation);
+    buf.extend(tchars("b"));
+
+    assert_eq!(find_matches(&buf, "日本"), vec![1..5]);
+    assert_eq!(find_matches(&buf, "本b"), vec![3..6]);
+}
+
+#[test]
+fn test_search_navigation() {
+    let mut search = SearchState::default();
+    search.search("x", vec![0..1, 5..6, 9..10]);
+    assert!(search.is_active());
+    assert_eq!(search.position(), Some((3, 3)));
+
+    assert_eq!(search.previous_match(), Some(&(5..6)));
+    assert_eq!(search.previous_match(), Some(&(0..1)));
+    assert_eq!(search.previous_match(), Some(&(9..10)));
+    assert_eq!(search.next_match(), Some(&(0..1)));
+
+    // new output keeps the search on the same match
+    search.mark_stale();
+    assert!(search.is_stale());
+    search.refresh(vec![0..1, 5..6, 9..10, 20..21]);
+    assert!(!search.is_stale());
+    assert_eq!(search.position(), Some((1, 4)));
+
+    // and if it has gone, moves to the most recent
+    search.refresh(vec![5..6, 40..41]);
+    assert_eq!(search.position(), Some((2, 2)));
+
+    search.clear();
+    assert!(!search.is_active());
+    search.mark_stale();
+    assert!(!search.is_stale());
+    assert_eq!(search.next_match(), None);
+}
+
+#[test]
+fn test_search_highlight() {
+    let mut search = SearchState::default();
+    search.search("x", vec![2..4, 6..7, 30..32]);
+
+    let tags = vec![
+        FormatTag {
+            start: 0,
+            end: 5,
+            ..FormatTag::default()
+        },
+        FormatTag {
+            start: 5,
+            end: usize::MAX,
+            ..FormatTag::default()
+        },
+    ];
+
+    let highlighted = search.highlight(tags.clone(), &(0..10));
+    let ranges: Vec<_> = highlighted.iter().map(|tag| tag.start..tag.end).collect();
+    assert_eq!(ranges, vec![0..2, 2..4, 4..5, 5..6, 6..7, 7..usize::MAX]);
+
+    assert_eq!(
+        highlighted[0].colors.background_color,
+        TerminalColor::DefaultBackground
+    );
+    assert_eq!(highlighted[1].colors.background_color, SEARCH_MATCH_COLOR);
+    assert_eq!(highlighted[4].colors.background_color, SEARCH_MATCH_COLOR);
+
+    // the current match is off screen, so nothing is drawn in its color
+    assert!(highlighted
+        .iter()
+        .all(|tag| tag.colors.background_color != CURRENT_SEARCH_MATCH_COLOR));
+
+    search.previous_match();
+    let highlighted = search.highlight(tags.clone(), &(0..10));
+    assert_eq!(
+        highlighted[4].colors.background_color,
+        CURRENT_SEARCH_MATCH_COLOR
+    );
+
+    // nothing on screen leaves the tags alone
+    assert_eq!(search.highlight(tags.clone(), &(10..20)), tags);
+}
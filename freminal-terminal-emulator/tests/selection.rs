@@ -0,0 +1,44 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::state::selection::Selection;
+use test_log::test;
+
+#[test]
+fn test_selection_range() {
+    let mut selection = Selection::new(5);
+    assert!(selection.dragging);
+    assert!(selection.is_click());
+    assert_eq!(selection.range(), 5..6);
+
+    selection.extent = 9;
+    assert!(!selection.is_click());
+    assert_eq!(selection.range(), 5..10);
+
+    // dragging backwards selects from the anchor back
+    selection.extent = 2;
+    assert_eq!(selection.range(), 2..6);
+}
+
+#[test]
+fn test_selection_clip() {
+    let selection = Selection {
+        anchor: 20,
+        extent: 30,
+        dragging: false,
+    };
+
+    assert_eq!(
+        selection.clip(&(0..10)),
+        Some(Selection {
+            anchor: 10,
+            extent: 20,
+            dragging: false,
+        })
+    );
+
+    // some of the selected text was thrown away
+    assert_eq!(selection.clip(&(0..25)), None);
+}
@@ -5,7 +5,9 @@
 
 use test_log::test;
 
-use freminal_terminal_emulator::state::term_char::{display_vec_tchar_as_string, TChar};
+use freminal_terminal_emulator::state::term_char::{
+    display_vec_tchar_as_string, is_joiner, is_zero_width_char, TChar,
+};
 use unicode_segmentation::UnicodeSegmentation;
 
 #[test]
@@ -197,3 +199,63 @@ fn test_display() {
     ];
     assert_eq!(format!("{}", display_vec_tchar_as_string(&c)), "A \nABC");
 }
+
+#[test]
+fn test_zero_width_characters_take_no_cell() {
+    // Zero width space, soft hyphen and word joiner are kept with the character before them
+    let c = TChar::from_string("a\u{200B}b\u{00AD}c\u{2060}").unwrap();
+    assert_eq!(
+        c,
+        vec![
+            TChar::Utf8("a\u{200B}".as_bytes().to_vec()),
+            TChar::Utf8("b\u{00AD}".as_bytes().to_vec()),
+            TChar::Utf8("c\u{2060}".as_bytes().to_vec()),
+        ]
+    );
+    assert_eq!(c[0].visible_text(), "a");
+
+    // At the start of a line they go with the character after them
+    let c = TChar::from_string("a\n\u{FEFF}b").unwrap();
+    assert_eq!(
+        c,
+        vec![
+            TChar::Ascii(b'a'),
+            TChar::NewLine,
+            TChar::Utf8("\u{FEFF}b".as_bytes().to_vec()),
+        ]
+    );
+
+    // At the very start they are left for the caller to place
+    let c = TChar::from_string("\u{200D}\u{200B}x").unwrap();
+    assert_eq!(c.len(), 2);
+    assert!(c[0].is_zero_width());
+    assert!(!c[1].is_zero_width());
+
+    assert!(is_zero_width_char('\u{200C}'));
+    assert!(!is_zero_width_char('a'));
+}
+
+#[test]
+fn test_joiners_are_drawn_inside_graphemes() {
+    // The joiners in an emoji ZWJ sequence are drawn, so the font can join it up
+    let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+    let c = TChar::from_string(family).unwrap();
+    assert_eq!(c.len(), 1);
+    assert_eq!(c[0].visible_text(), family);
+
+    // As is the non-joiner in Persian text, which ends up at the end of the cell before the letter
+    // it stops joining to
+    let c = TChar::from_string("\u{0645}\u{06CC}\u{200C}\u{062E}").unwrap();
+    assert_eq!(c[1].visible_text(), "\u{06CC}\u{200C}");
+    let drawn: String = c.iter().map(TChar::visible_text).collect();
+    assert_eq!(drawn, "\u{0645}\u{06CC}\u{200C}\u{062E}");
+
+    // A joiner with nothing before it has nothing to join, and other zero width characters are
+    // never drawn
+    let c = TChar::from_string("a\n\u{200D}b").unwrap();
+    assert_eq!(c[2].visible_text(), "b");
+    let c = TChar::from_string("a\u{200B}\u{200D}").unwrap();
+    assert_eq!(c[0].visible_text(), "a");
+    assert!(is_joiner('\u{200D}'));
+    assert!(!is_joiner('\u{200B}'));
+}
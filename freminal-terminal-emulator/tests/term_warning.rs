@@ -0,0 +1,51 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::state::{
+    internal::TerminalState,
+    term_warning::{TermWarning, SUGGESTED_TERM},
+};
+use test_log::test;
+
+#[test]
+fn test_unknown_term_is_noticed() {
+    let mut warning = TermWarning::new(true);
+    warning.scan(b"$ ls\r\nfile\r\n");
+    assert_eq!(warning.hint(), None);
+
+    warning.scan(b"'freminal': unknown terminal type.\r\n");
+    let hint = warning.hint().unwrap();
+    assert!(hint.contains("unknown terminal type"));
+    assert!(hint.contains(SUGGESTED_TERM));
+
+    warning.dismiss();
+    assert_eq!(warning.hint(), None);
+    warning.scan(b"Error opening terminal: freminal.");
+    assert_eq!(warning.hint(), None);
+}
+
+#[test]
+fn test_unknown_term_split_between_reads() {
+    let mut warning = TermWarning::new(true);
+    warning.scan(b"WARNING: terminal is not ful");
+    assert_eq!(warning.hint(), None);
+    warning.scan(b"ly functional\r\n");
+    assert!(warning.hint().is_some());
+}
+
+#[test]
+fn test_unknown_term_warning_disabled() {
+    let mut warning = TermWarning::new(false);
+    warning.scan(b"Error opening terminal: freminal.");
+    assert_eq!(warning.hint(), None);
+}
+
+#[test]
+fn test_terminal_output_is_scanned() {
+    let mut terminal_state = TerminalState::default();
+    terminal_state.term_warning.enabled = true;
+    terminal_state.handle_incoming_data(b"tput: No entry for terminal type \"freminal\";\r\n");
+    assert!(terminal_state.term_warning.hint().is_some());
+}
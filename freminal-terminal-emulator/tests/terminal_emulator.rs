@@ -9,7 +9,7 @@ use freminal_common::colors::TerminalColor;
 use freminal_terminal_emulator::{
     ansi_components::modes::decawm::Decawm,
     format_tracker::FormatTag,
-    interface::split_format_data_for_scrollback,
+    interface::{format_data_for_rows, split_format_data_for_scrollback, EmulatorId},
     state::{
         cursor::{ReverseVideo, StateColors},
         fonts::FontWeight,
@@ -102,6 +102,26 @@ fn test_format_tracker_scrollback_split_on_boundary() {
     );
 }
 
+#[test]
+fn test_format_data_for_rows() {
+    let tags = get_tags();
+
+    // Two rows cut out of the buffer, shown with a newline between them
+    let res = format_data_for_rows(&tags, &[2..6, 8..12]);
+    let ranges: Vec<_> = res.iter().map(|tag| tag.start..tag.end).collect();
+    // The first row's newline takes the format of the buffer after it
+    assert_eq!(ranges, vec![0..3, 3..5, 5..7, 7..9]);
+    assert_eq!(res[0].colors, tags[0].colors);
+    assert_eq!(res[3].colors, tags[3].colors);
+
+    // An empty row still has its newline
+    let res = format_data_for_rows(&tags, &[3..3, 4..6]);
+    let ranges: Vec<_> = res.iter().map(|tag| tag.start..tag.end).collect();
+    assert_eq!(ranges, vec![0..1, 1..2, 2..3]);
+
+    assert!(format_data_for_rows(&tags, &[]).is_empty());
+}
+
 #[test]
 fn test_format_tracker_scrollback_split_segment() {
     let tags = get_tags();
@@ -199,3 +219,15 @@ fn test_format_tracker_scrollback_no_split() {
     assert_eq!(res.scrollback, &[]);
     assert_eq!(res.visible, &tags[..]);
 }
+
+#[test]
+fn test_emulator_ids_are_unique() {
+    let first = EmulatorId::next();
+    let second = EmulatorId::next();
+    assert_ne!(first, second);
+    assert_eq!(first.process_id, std::process::id());
+    assert_eq!(
+        second.to_string(),
+        format!("{}-{}", second.process_id, second.index)
+    );
+}
@@ -0,0 +1,148 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::{
+    ansi_components::osc::WorkingDirectory,
+    state::title::{TitleChanged, WindowTitle, DEFAULT_WINDOW_TITLE},
+};
+use test_log::test;
+
+#[test]
+fn test_title_only_updated_when_changed() {
+    let mut title = WindowTitle::new();
+    // The window opens with the default title already showing
+    assert_eq!(title.take_update(), None);
+
+    title.set("one".to_string());
+    title.set("two".to_string());
+    assert_eq!(title.take_update(), Some("two".to_string()));
+    assert_eq!(title.take_update(), None);
+
+    // Setting the same title again, like a shell does every prompt, doesn't update the window
+    title.set("two".to_string());
+    assert_eq!(title.take_update(), None);
+
+    // Changing away and back before the window is updated
+    title.set("three".to_string());
+    title.set("two".to_string());
+    assert_eq!(title.take_update(), None);
+}
+
+#[test]
+fn test_title_bell_badge() {
+    let mut title = WindowTitle::new();
+    title.set_bells(2);
+    assert_eq!(
+        title.take_update(),
+        Some(format!("(2) {DEFAULT_WINDOW_TITLE}"))
+    );
+    assert_eq!(title.title(), DEFAULT_WINDOW_TITLE);
+
+    title.set_bells(0);
+    assert_eq!(title.take_update(), Some(DEFAULT_WINDOW_TITLE.to_string()));
+
+    title.set_activity(true);
+    assert_eq!(
+        title.take_update(),
+        Some(format!("(*) {DEFAULT_WINDOW_TITLE}"))
+    );
+}
+
+#[test]
+fn test_title_stack() {
+    let mut title = WindowTitle::new();
+    title.set("vim".to_string());
+    title.push();
+    title.set("other".to_string());
+    title.pop();
+    assert_eq!(title.title(), "vim");
+
+    title.pop();
+    assert_eq!(title.title(), DEFAULT_WINDOW_TITLE);
+}
+
+#[test]
+fn test_title_subscribers() {
+    let mut title = WindowTitle::new();
+    let events = title.subscribe();
+
+    title.set("one".to_string());
+    title.set("one".to_string());
+    title.set("two".to_string());
+
+    let received: Vec<TitleChanged> = events.try_iter().collect();
+    assert_eq!(
+        received,
+        vec![
+            TitleChanged {
+                title: "one".to_string()
+            },
+            TitleChanged {
+                title: "two".to_string()
+            },
+        ]
+    );
+
+    // A subscriber that goes away doesn't stop the title changing
+    drop(events);
+    title.set("three".to_string());
+    assert_eq!(title.title(), "three");
+}
+
+#[test]
+fn test_title_format() {
+    let mut title = WindowTitle::with_format(
+        "{title} — {cwd} — freminal:{id}".parse().unwrap(),
+        "42-0".to_string(),
+    );
+    assert_eq!(
+        title.take_update(),
+        Some(format!("{DEFAULT_WINDOW_TITLE} —  — freminal:42-0"))
+    );
+
+    title.set_working_directory(Some(&WorkingDirectory {
+        host: String::new(),
+        path: "/home/fred".into(),
+    }));
+    title.set("vim".to_string());
+    assert_eq!(
+        title.take_update(),
+        Some("vim — /home/fred — freminal:42-0".to_string())
+    );
+    // What the application asked for is still reported back to it as it was
+    assert_eq!(title.title(), "vim");
+
+    title.set_working_directory(Some(&WorkingDirectory {
+        host: "elsewhere.invalid".to_string(),
+        path: "/srv".into(),
+    }));
+    title.set_bells(1);
+    assert_eq!(
+        title.take_update(),
+        Some("(1) vim — elsewhere.invalid:/srv — freminal:42-0".to_string())
+    );
+    assert_eq!(title.take_update(), None);
+}
+
+#[test]
+fn test_title_falls_back_to_foreground_process() {
+    let mut title = WindowTitle::new();
+    title.set_foreground_process(Some("cargo".to_string()));
+    assert_eq!(title.take_update(), Some("cargo".to_string()));
+    // Applications are still told the title they set, not the program name
+    assert_eq!(title.title(), DEFAULT_WINDOW_TITLE);
+
+    title.set("vim".to_string());
+    title.set_foreground_process(Some("vim".to_string()));
+    assert_eq!(title.take_update(), Some("vim".to_string()));
+
+    title.set("~/src".to_string());
+    title.set_foreground_process(Some("ssh".to_string()));
+    assert_eq!(title.take_update(), Some("~/src".to_string()));
+
+    title.pop();
+    title.set_foreground_process(None);
+    assert_eq!(title.take_update(), Some(DEFAULT_WINDOW_TITLE.to_string()));
+}
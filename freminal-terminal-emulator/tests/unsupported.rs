@@ -0,0 +1,92 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use freminal_terminal_emulator::state::{
+    internal::TerminalState,
+    unsupported::{UnsupportedFeatures, MAX_UNSUPPORTED_FEATURES},
+};
+use test_log::test;
+
+#[test]
+fn test_features_are_counted_once_each() {
+    let mut features = UnsupportedFeatures::default();
+    assert!(features.is_empty());
+    assert_eq!(features.report(), "");
+
+    assert!(features.record("CSI > u"));
+    assert!(features.record("OSC 1337"));
+    assert!(!features.record("CSI > u"));
+    assert!(!features.record("CSI > u"));
+
+    let counts: Vec<_> = features
+        .features()
+        .iter()
+        .map(|feature| (feature.name.as_str(), feature.count))
+        .collect();
+    assert_eq!(counts, [("CSI > u", 3), ("OSC 1337", 1)]);
+    assert_eq!(features.report(), "CSI > u (3 times)\nOSC 1337 (1 time)\n");
+
+    features.clear();
+    assert!(features.is_empty());
+    assert!(features.record("CSI > u"));
+}
+
+#[test]
+fn test_features_are_limited() {
+    let mut features = UnsupportedFeatures::default();
+    for number in 0..MAX_UNSUPPORTED_FEATURES + 10 {
+        features.record(&format!("OSC {number}"));
+    }
+
+    assert_eq!(features.features().len(), MAX_UNSUPPORTED_FEATURES);
+
+    // Features that don't fit are counted together rather than each warned about
+    assert_eq!(features.overflowed(), 10);
+    assert!(!features.record("OSC 99999"));
+    assert!(!features.record("OSC 99999"));
+    assert_eq!(features.overflowed(), 12);
+    assert!(features
+        .report()
+        .ends_with("OSC 255 (1 time)\nOthers not listed (12 times)\n"));
+
+    // Ones already listed are still counted against their own name
+    assert!(!features.record("OSC 0"));
+    assert_eq!(features.features()[0].count, 2);
+    assert_eq!(features.overflowed(), 12);
+
+    features.clear();
+    assert_eq!(features.overflowed(), 0);
+    assert!(features.record("OSC 99999"));
+}
+
+#[test]
+fn test_terminal_output_is_recorded() {
+    let mut terminal_state = TerminalState::default();
+    // modifyCursorKeys, twice, an iTerm2 mark, overline and a DECRQSS we don't answer
+    terminal_state.handle_incoming_data(b"\x1b[>1;2m\x1b[>1;2m\x1b]1337;SetMark\x07\x1b[53m");
+    terminal_state.handle_incoming_data(b"\x1bP$qr\x1b\\text");
+
+    let names: Vec<_> = terminal_state
+        .unsupported
+        .features()
+        .iter()
+        .map(|feature| feature.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "XTMODKEYS for resource 1",
+            "OSC 1337",
+            "SGR Overlined",
+            "DECRQSS for r"
+        ]
+    );
+    assert_eq!(terminal_state.unsupported.features()[0].count, 2);
+
+    // Supported sequences aren't
+    let mut terminal_state = TerminalState::default();
+    terminal_state.handle_incoming_data(b"\x1b[1m\x1b[2J\x1b]0;title\x07\x1b[?25l");
+    assert!(terminal_state.unsupported.is_empty());
+}
@@ -0,0 +1,61 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use anyhow::Result;
+use freminal_common::control::{ControlCommand, ControlServer};
+use freminal_terminal_emulator::{
+    input::paste_to_terminal_input,
+    interface::{raw_ascii_bytes_to_terminal_input, TerminalEmulator},
+    io::FreminalTermInputOutput,
+};
+use serde_json::{json, Value};
+
+use super::terminal::FreminalTerminalWidget;
+
+/// Answer every control request that has come in since the last frame
+pub fn handle_control_requests<Io: FreminalTermInputOutput>(
+    server: &ControlServer,
+    terminal_widget: &mut FreminalTerminalWidget,
+    terminal_emulator: &mut TerminalEmulator<Io>,
+) {
+    while let Some(request) = server.try_recv() {
+        debug!("Control request: {:?}", request.command);
+        let result = run_command(&request.command, terminal_widget, terminal_emulator);
+        request.reply(result);
+    }
+}
+
+fn run_command<Io: FreminalTermInputOutput>(
+    command: &ControlCommand,
+    terminal_widget: &mut FreminalTerminalWidget,
+    terminal_emulator: &mut TerminalEmulator<Io>,
+) -> Result<Value> {
+    match command {
+        ControlCommand::PasteText { text } => {
+            let inputs =
+                paste_to_terminal_input(text, &terminal_emulator.internal.modes.bracketed_paste);
+            for input in inputs.iter() {
+                terminal_emulator.write(input)?;
+            }
+
+            Ok(Value::Null)
+        }
+        ControlCommand::SendKeys { text } => {
+            for input in raw_ascii_bytes_to_terminal_input(text.as_bytes()).iter() {
+                terminal_emulator.write(input)?;
+            }
+
+            Ok(Value::Null)
+        }
+        ControlCommand::GetText => Ok(json!({ "text": terminal_emulator.internal.visible_text() })),
+        ControlCommand::SetFontSize { size } => {
+            terminal_widget.set_font_size(*size);
+            terminal_emulator.set_previous_pass_invalid();
+
+            Ok(json!({ "size": terminal_widget.get_font_size() }))
+        }
+        ControlCommand::OpenTab => Err(anyhow::anyhow!("Tabs are not supported")),
+    }
+}
@@ -3,6 +3,8 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+use std::path::{Path, PathBuf};
+
 use eframe::egui::{self, Color32, FontData, FontDefinitions, FontFamily, FontId};
 
 use freminal_terminal_emulator::state::fonts::{FontDecorations, FontWeight};
@@ -12,37 +14,82 @@ const BOLD_FONT_NAME: &str = "hack-bold";
 const ITALIC_FONT_NAME: &str = "hack-italic";
 const BOLD_ITALIC_FONT_NAME: &str = "hack-bold-italic";
 
-pub fn setup_font_files(ctx: &egui::Context) {
+/// Where installed fonts are looked for
+const FONT_DIRECTORIES: [&str; 5] = [
+    "/usr/share/fonts",
+    "/usr/local/share/fonts",
+    "/Library/Fonts",
+    "/System/Library/Fonts",
+    "C:\\Windows\\Fonts",
+];
+/// Where installed fonts are looked for, under the home directory
+const HOME_FONT_DIRECTORIES: [&str; 3] = [".local/share/fonts", ".fonts", "Library/Fonts"];
+/// How far down font directories are searched
+const FONT_SEARCH_DEPTH: usize = 4;
+
+/// The faces of an installed font family, read from its files
+struct InstalledFont {
+    regular: FontData,
+    bold: Option<FontData>,
+    italic: Option<FontData>,
+    bold_italic: Option<FontData>,
+}
+
+/// Load the font the terminal is drawn with: the installed `family` if it is set and can be
+/// found, otherwise the bundled Meslo
+pub fn setup_font_files(ctx: &egui::Context, family: Option<&str>) {
     let mut fonts = FontDefinitions::default();
 
-    fonts.font_data.insert(
-        REGULAR_FONT_NAME.to_owned(),
-        FontData::from_static(include_bytes!(
-            "../../../res/MesloLGSNerdFontMono-Regular.ttf"
-        ))
-        .into(),
-    );
-
-    fonts.font_data.insert(
-        BOLD_FONT_NAME.to_owned(),
-        FontData::from_static(include_bytes!("../../../res/MesloLGSNerdFontMono-Bold.ttf")).into(),
-    );
-
-    fonts.font_data.insert(
-        ITALIC_FONT_NAME.to_owned(),
-        FontData::from_static(include_bytes!(
-            "../../../res/MesloLGSNerdFontMono-Italic.ttf"
-        ))
-        .into(),
-    );
-
-    fonts.font_data.insert(
-        BOLD_ITALIC_FONT_NAME.to_owned(),
-        FontData::from_static(include_bytes!(
-            "../../../res/MesloLGSNerdFontMono-BoldItalic.ttf"
-        ))
-        .into(),
-    );
+    let installed = family.and_then(|family| {
+        let font = find_installed_font(family);
+        if font.is_none() {
+            error!("Failed to find font family {family}, using the bundled font");
+        }
+        font
+    });
+
+    let (regular, bold, italic, bold_italic) = match installed {
+        // Bold text in the regular face keeps every cell the same width, where the bundled bold
+        // face wouldn't
+        Some(font) => (
+            font.regular.clone(),
+            font.bold.unwrap_or(font.regular),
+            font.italic,
+            font.bold_italic,
+        ),
+        None => (
+            FontData::from_static(include_bytes!(
+                "../../../res/MesloLGSNerdFontMono-Regular.ttf"
+            )),
+            FontData::from_static(include_bytes!("../../../res/MesloLGSNerdFontMono-Bold.ttf")),
+            Some(FontData::from_static(include_bytes!(
+                "../../../res/MesloLGSNerdFontMono-Italic.ttf"
+            ))),
+            Some(FontData::from_static(include_bytes!(
+                "../../../res/MesloLGSNerdFontMono-BoldItalic.ttf"
+            ))),
+        ),
+    };
+
+    // Faces that are missing are left out, so `TerminalFont` slants the upright ones instead
+    let faces = [
+        (REGULAR_FONT_NAME, Some(regular)),
+        (BOLD_FONT_NAME, Some(bold)),
+        (ITALIC_FONT_NAME, italic),
+        (BOLD_ITALIC_FONT_NAME, bold_italic),
+    ];
+
+    for (name, data) in faces {
+        let Some(data) = data else {
+            continue;
+        };
+
+        fonts.font_data.insert(name.to_owned(), data.into());
+        fonts.families.insert(
+            FontFamily::Name(name.to_string().into()),
+            vec![name.to_string()],
+        );
+    }
 
     if let Some(f) = fonts.families.get_mut(&FontFamily::Monospace) {
         f.insert(0, REGULAR_FONT_NAME.to_owned());
@@ -52,33 +99,77 @@ pub fn setup_font_files(ctx: &egui::Context) {
         // exit the program
         std::process::exit(1);
     }
-    // .insert(0, REGULAR_FONT_NAME.to_owned());
-
-    fonts.families.insert(
-        FontFamily::Name(REGULAR_FONT_NAME.to_string().into()),
-        vec![REGULAR_FONT_NAME.to_string()],
-    );
-    fonts.families.insert(
-        FontFamily::Name(BOLD_FONT_NAME.to_string().into()),
-        vec![BOLD_FONT_NAME.to_string()],
-    );
-    fonts.families.insert(
-        FontFamily::Name(ITALIC_FONT_NAME.to_string().into()),
-        vec![ITALIC_FONT_NAME.to_string()],
-    );
-    fonts.families.insert(
-        FontFamily::Name(BOLD_ITALIC_FONT_NAME.to_string().into()),
-        vec![BOLD_ITALIC_FONT_NAME.to_string()],
-    );
 
     ctx.set_fonts(fonts);
 }
 
+/// Look through the font directories for the files of `family`, named the usual way: the family
+/// without spaces, then `-Regular`, `-Bold`, `-Italic` or `-BoldItalic`
+fn find_installed_font(family: &str) -> Option<InstalledFont> {
+    let stem: String = family.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut directories: Vec<PathBuf> = FONT_DIRECTORIES.iter().map(PathBuf::from).collect();
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        directories.extend(HOME_FONT_DIRECTORIES.iter().map(|dir| home.join(dir)));
+    }
+
+    let mut files = Vec::new();
+    for directory in &directories {
+        collect_font_files(directory, FONT_SEARCH_DEPTH, &mut files);
+    }
+
+    let load = |style: &str| -> Option<FontData> {
+        let name = format!("{stem}-{style}");
+        let path = files.iter().find(|path| {
+            path.file_stem()
+                .is_some_and(|file| file.to_string_lossy().eq_ignore_ascii_case(&name))
+        })?;
+
+        match std::fs::read(path) {
+            Ok(bytes) => Some(FontData::from_owned(bytes)),
+            Err(e) => {
+                error!("Failed to read font {}: {e}", path.display());
+                None
+            }
+        }
+    };
+
+    Some(InstalledFont {
+        regular: load("Regular")?,
+        bold: load("Bold"),
+        italic: load("Italic"),
+        bold_italic: load("BoldItalic"),
+    })
+}
+
+/// Add the TrueType and OpenType fonts in `directory`, and up to `depth` directories down, to
+/// `files`
+fn collect_font_files(directory: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+
+    for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+        if path.is_dir() {
+            if depth > 0 {
+                collect_font_files(&path, depth - 1, files);
+            }
+        } else if path.extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("ttf") || extension.eq_ignore_ascii_case("otf")
+        }) {
+            files.push(path);
+        }
+    }
+}
+
+/// The font families used for each style of terminal text
+///
+/// Not every font ships an italic face. When one is missing we draw the upright face and let the
+/// renderer shear the glyphs, rather than silently ignoring SGR 3.
 pub struct TerminalFont {
     regular: FontFamily,
     bold: FontFamily,
-    italic: FontFamily,
-    bold_italic: FontFamily,
+    italic: Option<FontFamily>,
+    bold_italic: Option<FontFamily>,
 }
 
 impl Default for TerminalFont {
@@ -98,18 +189,48 @@ impl TerminalFont {
         Self {
             regular,
             bold,
-            italic,
-            bold_italic,
+            italic: Some(italic),
+            bold_italic: Some(bold_italic),
         }
     }
 
+    /// Only use the italic faces that are actually registered with egui
+    #[must_use]
+    pub fn with_available_families(available: &[FontFamily]) -> Self {
+        let mut fonts = Self::new();
+        fonts.italic = fonts.italic.filter(|family| available.contains(family));
+        fonts.bold_italic = fonts
+            .bold_italic
+            .filter(|family| available.contains(family));
+
+        fonts
+    }
+
     #[must_use]
     pub fn get_family(&self, font_decs: &[FontDecorations], weight: &FontWeight) -> FontFamily {
         match (weight, font_decs.contains(&FontDecorations::Italic)) {
             (FontWeight::Bold, false) => self.bold.clone(),
             (FontWeight::Normal, false) => self.regular.clone(),
-            (FontWeight::Normal, true) => self.italic.clone(),
-            (FontWeight::Bold, true) => self.bold_italic.clone(),
+            (FontWeight::Normal, true) => self.italic.as_ref().unwrap_or(&self.regular).clone(),
+            (FontWeight::Bold, true) => self.bold_italic.as_ref().unwrap_or(&self.bold).clone(),
+        }
+    }
+
+    /// True if the text is italic but there is no italic face for it, so the renderer has to
+    /// slant the upright glyphs itself
+    #[must_use]
+    pub fn needs_synthetic_italic(
+        &self,
+        font_decs: &[FontDecorations],
+        weight: &FontWeight,
+    ) -> bool {
+        if !font_decs.contains(&FontDecorations::Italic) {
+            return false;
+        }
+
+        match weight {
+            FontWeight::Normal => self.italic.is_none(),
+            FontWeight::Bold => self.bold_italic.is_none(),
         }
     }
 }
@@ -0,0 +1,494 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use conv::ConvUtil;
+use eframe::egui::{pos2, Color32, Pos2, Rect, Shape, Stroke};
+use freminal_common::{cell_geometry::CellGeometry, mosaic::mosaic_quadrants};
+
+/// Points around the curve of the rounded Powerline separators and box drawing arcs
+const ARC_SEGMENTS: usize = 16;
+
+/// The arms of each box drawing character from U+2500, as left, right, up and down. `l` is a
+/// light line, `h` a heavy one and `d` a double one. Dashed lines, arcs and diagonals are drawn
+/// separately
+const BOX_ARMS: [&[u8; 4]; 128] = [
+    b"ll..", b"hh..", b"..ll", b"..hh", b"ll..", b"hh..", b"..ll", b"..hh", // 2500
+    b"ll..", b"hh..", b"..ll", b"..hh", b".l.l", b".h.l", b".l.h", b".h.h", // 2508
+    b"l..l", b"h..l", b"l..h", b"h..h", b".ll.", b".hl.", b".lh.", b".hh.", // 2510
+    b"l.l.", b"h.l.", b"l.h.", b"h.h.", b".lll", b".hll", b".lhl", b".llh", // 2518
+    b".lhh", b".hhl", b".hlh", b".hhh", b"l.ll", b"h.ll", b"l.hl", b"l.lh", // 2520
+    b"l.hh", b"h.hl", b"h.lh", b"h.hh", b"ll.l", b"hl.l", b"lh.l", b"hh.l", // 2528
+    b"ll.h", b"hl.h", b"lh.h", b"hh.h", b"lll.", b"hll.", b"lhl.", b"hhl.", // 2530
+    b"llh.", b"hlh.", b"lhh.", b"hhh.", b"llll", b"hlll", b"lhll", b"hhll", // 2538
+    b"llhl", b"lllh", b"llhh", b"hlhl", b"lhhl", b"hllh", b"lhlh", b"hhhl", // 2540
+    b"hhlh", b"hlhh", b"lhhh", b"hhhh", b"ll..", b"hh..", b"..ll", b"..hh", // 2548
+    b"dd..", b"..dd", b".d.l", b".l.d", b".d.d", b"d..l", b"l..d", b"d..d", // 2550
+    b".dl.", b".ld.", b".dd.", b"d.l.", b"l.d.", b"d.d.", b".dll", b".ldd", // 2558
+    b".ddd", b"d.ll", b"l.dd", b"d.dd", b"dd.l", b"ll.d", b"dd.d", b"ddl.", // 2560
+    b"lld.", b"ddd.", b"ddll", b"lldd", b"dddd", b"....", b"....", b"....", // 2568
+    b"....", b"....", b"....", b"....", b"l...", b"..l.", b".l..", b"...l", // 2570
+    b"h...", b"..h.", b".h..", b"...h", b"lh..", b"..lh", b"hl..", b"..hl", // 2578
+];
+
+/// Which characters we draw ourselves rather than leaving to the font
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct GlyphSettings {
+    /// Powerline prompt separators
+    pub powerline: bool,
+    /// Box drawing characters and block elements, U+2500 to U+259F
+    pub box_drawing: bool,
+}
+
+impl GlyphSettings {
+    /// Whether `c` is drawn by `glyph_shapes` rather than the font
+    #[must_use]
+    pub const fn draws(self, c: char) -> bool {
+        (self.powerline && is_powerline_separator(c)) || (self.box_drawing && is_box_drawing(c))
+    }
+}
+
+/// The shapes to draw `c` with, filling `cell`. Empty if it isn't a character we draw
+#[must_use]
+pub fn glyph_shapes(c: char, cell: Rect, color: Color32, geometry: &CellGeometry) -> Vec<Shape> {
+    if is_box_drawing(c) {
+        return BoxPainter::new(cell, color, geometry).shapes(c);
+    }
+
+    let line_width = (geometry.cell_width / 8.0).max(1.0);
+    powerline_shape(c, cell, color, line_width)
+        .into_iter()
+        .collect()
+}
+
+/// Box drawing characters and block elements
+///
+/// Fonts rarely make these exactly one cell in size, which leaves gaps between the lines of a
+/// border. Drawn from the cell edges they join up whatever the font.
+#[must_use]
+pub const fn is_box_drawing(c: char) -> bool {
+    matches!(c, '\u{2500}'..='\u{259F}')
+}
+
+/// Whether `c` is one of the Powerline separators we draw ourselves
+///
+/// Fonts draw these slightly smaller or larger than the cell, or not at all, which leaves a gap
+/// or overlap between the separator and the colored block next to it. Drawing them from the cell
+/// edges lines them up exactly.
+#[must_use]
+pub const fn is_powerline_separator(c: char) -> bool {
+    matches!(c, '\u{E0B0}'..='\u{E0BF}')
+}
+
+/// The shape to draw for the Powerline separator `c`, filling `cell`
+fn powerline_shape(c: char, cell: Rect, color: Color32, line_width: f32) -> Option<Shape> {
+    let (left, right, top, bottom) = (cell.left(), cell.right(), cell.top(), cell.bottom());
+    let middle = cell.center().y;
+    let filled = |points: Vec<Pos2>| Shape::convex_polygon(points, color, Stroke::NONE);
+    let line = |points: Vec<Pos2>| Shape::line(points, Stroke::new(line_width, color));
+
+    let shape = match c {
+        // Right and left pointing triangles, solid and as an outline
+        '\u{E0B0}' => filled(vec![
+            pos2(left, top),
+            pos2(right, middle),
+            pos2(left, bottom),
+        ]),
+        '\u{E0B1}' => line(vec![
+            pos2(left, top),
+            pos2(right, middle),
+            pos2(left, bottom),
+        ]),
+        '\u{E0B2}' => filled(vec![
+            pos2(right, top),
+            pos2(right, bottom),
+            pos2(left, middle),
+        ]),
+        '\u{E0B3}' => line(vec![
+            pos2(right, top),
+            pos2(left, middle),
+            pos2(right, bottom),
+        ]),
+        // Half circles bulging to the right and left
+        '\u{E0B4}' => filled(half_circle(cell, true)),
+        '\u{E0B5}' => line(half_circle(cell, true)),
+        '\u{E0B6}' => filled(half_circle(cell, false)),
+        '\u{E0B7}' => line(half_circle(cell, false)),
+        // Triangles filling half the cell, cut along a diagonal, and the diagonals on their own
+        '\u{E0B8}' => filled(vec![
+            pos2(left, top),
+            pos2(right, bottom),
+            pos2(left, bottom),
+        ]),
+        '\u{E0B9}' | '\u{E0BF}' => line(vec![pos2(left, top), pos2(right, bottom)]),
+        '\u{E0BA}' => filled(vec![
+            pos2(right, top),
+            pos2(right, bottom),
+            pos2(left, bottom),
+        ]),
+        '\u{E0BB}' | '\u{E0BD}' => line(vec![pos2(left, bottom), pos2(right, top)]),
+        '\u{E0BC}' => filled(vec![pos2(left, top), pos2(right, top), pos2(left, bottom)]),
+        '\u{E0BE}' => filled(vec![pos2(left, top), pos2(right, top), pos2(right, bottom)]),
+        _ => return None,
+    };
+
+    Some(shape)
+}
+
+/// A half ellipse filling `cell`, with its flat side on the left if `bulge_right`, otherwise on
+/// the right. Points run clockwise, as egui expects for filled shapes
+fn half_circle(cell: Rect, bulge_right: bool) -> Vec<Pos2> {
+    let (flat_x, radius_x) = if bulge_right {
+        (cell.left(), cell.width())
+    } else {
+        (cell.right(), -cell.width())
+    };
+    let radius_y = cell.height() / 2.0;
+    let middle = cell.center().y;
+    let step = PI / ARC_SEGMENTS.approx_as::<f32>().unwrap_or(16.0);
+
+    let mut points: Vec<Pos2> = (0..=ARC_SEGMENTS)
+        .map(|i| {
+            let angle = step.mul_add(i.approx_as::<f32>().unwrap_or_default(), -PI / 2.0);
+            pos2(
+                radius_x.mul_add(angle.cos(), flat_x),
+                radius_y.mul_add(angle.sin(), middle),
+            )
+        })
+        .collect();
+
+    if !bulge_right {
+        points.reverse();
+    }
+
+    points
+}
+
+/// Weights of line, lightest first
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+enum Weight {
+    None,
+    Light,
+    Heavy,
+    Double,
+}
+
+impl Weight {
+    const fn from_arm(arm: u8) -> Self {
+        match arm {
+            b'l' => Self::Light,
+            b'h' => Self::Heavy,
+            b'd' => Self::Double,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Draws box drawing characters and block elements in to one cell, with every edge on a whole
+/// physical pixel so lines are sharp and meet the lines in the next cell
+struct BoxPainter<'a> {
+    cell: Rect,
+    color: Color32,
+    geometry: &'a CellGeometry,
+    /// Thickness of a light line. Heavy lines are twice this, and the two lines of a double line
+    /// are this far apart
+    light: f32,
+    center: Pos2,
+    shapes: Vec<Shape>,
+}
+
+impl<'a> BoxPainter<'a> {
+    fn new(cell: Rect, color: Color32, geometry: &'a CellGeometry) -> Self {
+        let pixel = if geometry.pixels_per_point > 0.0 {
+            1.0 / geometry.pixels_per_point
+        } else {
+            1.0
+        };
+        let light = geometry.snap_to_pixel(cell.width() / 8.0).max(pixel);
+        let center = pos2(
+            geometry.snap_to_pixel(cell.center().x),
+            geometry.snap_to_pixel(cell.center().y),
+        );
+
+        Self {
+            cell,
+            color,
+            geometry,
+            light,
+            center,
+            shapes: Vec::new(),
+        }
+    }
+
+    fn shapes(mut self, c: char) -> Vec<Shape> {
+        let index = u32::from(c) - 0x2500;
+        match c {
+            '\u{2504}'..='\u{250B}' | '\u{254C}'..='\u{254F}' => {
+                let dashes = match c {
+                    '\u{2504}'..='\u{2507}' => 3,
+                    '\u{2508}'..='\u{250B}' => 4,
+                    _ => 2,
+                };
+                self.dashes(*BOX_ARMS[index as usize], dashes);
+            }
+            '\u{256D}' => self.arc(1.0, 1.0),
+            '\u{256E}' => self.arc(-1.0, 1.0),
+            '\u{256F}' => self.arc(-1.0, -1.0),
+            '\u{2570}' => self.arc(1.0, -1.0),
+            '\u{2571}' => self.diagonal(false),
+            '\u{2572}' => self.diagonal(true),
+            '\u{2573}' => {
+                self.diagonal(false);
+                self.diagonal(true);
+            }
+            '\u{2500}'..='\u{257F}' => self.arms(*BOX_ARMS[index as usize]),
+            _ => self.block(c),
+        }
+
+        self.shapes
+    }
+
+    const fn thickness(&self, weight: Weight) -> f32 {
+        match weight {
+            Weight::None => 0.0,
+            Weight::Light => self.light,
+            Weight::Heavy => self.light * 2.0,
+            Weight::Double => self.light * 3.0,
+        }
+    }
+
+    fn fill(&mut self, left: f32, top: f32, right: f32, bottom: f32) {
+        let snap = |points: f32| self.geometry.snap_to_pixel(points);
+        let rect = Rect::from_min_max(pos2(snap(left), snap(top)), pos2(snap(right), snap(bottom)));
+        if rect.is_positive() {
+            self.shapes.push(Shape::rect_filled(rect, 0.0, self.color));
+        }
+    }
+
+    /// A horizontal line from `x0` to `x1`, `thickness` thick and centered on `y`
+    fn hline(&mut self, x0: f32, x1: f32, y: f32, thickness: f32) {
+        let top = self.geometry.snap_to_pixel(y - thickness / 2.0);
+        self.fill(x0.min(x1), top, x0.max(x1), top + thickness);
+    }
+
+    /// A vertical line from `y0` to `y1`, `thickness` thick and centered on `x`
+    fn vline(&mut self, y0: f32, y1: f32, x: f32, thickness: f32) {
+        let left = self.geometry.snap_to_pixel(x - thickness / 2.0);
+        self.fill(left, y0.min(y1), left + thickness, y0.max(y1));
+    }
+
+    /// Lines from the center out to each edge with an arm. Single lines run past the center far
+    /// enough to cover the lines across them, and the lines of double arms stop at or run round
+    /// the lines across them so corners and joins are drawn the way fonts draw them
+    fn arms(&mut self, arms: [u8; 4]) {
+        let [left, right, up, down] = arms.map(Weight::from_arm);
+        let Pos2 { x: cx, y: cy } = self.center;
+        let half = self.light / 2.0;
+
+        let across = |a: Weight, b: Weight, own: Weight| {
+            self.thickness(a)
+                .max(self.thickness(b))
+                .max(self.thickness(own))
+                / 2.0
+        };
+        let horizontal_reach = across(up, down, left.max(right));
+        let vertical_reach = across(left, right, up.max(down));
+
+        // Where the line of a double arm on the side of `near` stops, measured from the center
+        // towards the arm. Past the center, negative, to go round the outside of a corner
+        // The lines of a double arm are a light line's width either side of the center
+        let light = self.light;
+        let offset = |weight: Weight| {
+            if weight == Weight::Double {
+                light
+            } else {
+                0.0
+            }
+        };
+        let double_start = |near: Weight, far: Weight| match (near, far) {
+            (Weight::None, Weight::None) => -half,
+            (Weight::None, far) => -offset(far) - half,
+            (near, _) => offset(near) - half,
+        };
+
+        for (weight, direction) in [(left, -1.0_f32), (right, 1.0)] {
+            let edge = if direction < 0.0 {
+                self.cell.left()
+            } else {
+                self.cell.right()
+            };
+            match weight {
+                Weight::None => (),
+                Weight::Double => {
+                    let gap = self.light;
+                    let top = double_start(up, down);
+                    let bottom = double_start(down, up);
+                    self.hline(direction.mul_add(top, cx), edge, cy - gap, self.light);
+                    self.hline(direction.mul_add(bottom, cx), edge, cy + gap, self.light);
+                }
+                _ => {
+                    let start = direction.mul_add(-horizontal_reach, cx);
+                    self.hline(start, edge, cy, self.thickness(weight));
+                }
+            }
+        }
+
+        for (weight, direction) in [(up, -1.0_f32), (down, 1.0)] {
+            let edge = if direction < 0.0 {
+                self.cell.top()
+            } else {
+                self.cell.bottom()
+            };
+            match weight {
+                Weight::None => (),
+                Weight::Double => {
+                    let gap = self.light;
+                    let left_start = double_start(left, right);
+                    let right_start = double_start(right, left);
+                    self.vline(
+                        direction.mul_add(left_start, cy),
+                        edge,
+                        cx - gap,
+                        self.light,
+                    );
+                    self.vline(
+                        direction.mul_add(right_start, cy),
+                        edge,
+                        cx + gap,
+                        self.light,
+                    );
+                }
+                _ => {
+                    let start = direction.mul_add(-vertical_reach, cy);
+                    self.vline(start, edge, cx, self.thickness(weight));
+                }
+            }
+        }
+    }
+
+    /// A line across the cell, horizontal or vertical as `arms` says, broken in to `count` dashes
+    fn dashes(&mut self, arms: [u8; 4], count: u8) {
+        let weight = Weight::from_arm(arms[0]).max(Weight::from_arm(arms[2]));
+        let thickness = self.thickness(weight);
+        let horizontal = arms[0] != b'.';
+        let (start, length) = if horizontal {
+            (self.cell.left(), self.cell.width())
+        } else {
+            (self.cell.top(), self.cell.height())
+        };
+        let dash = length / f32::from(count);
+        let gap = dash * 0.4;
+
+        for i in 0..count {
+            let from = dash.mul_add(f32::from(i), start) + gap / 2.0;
+            let to = from + dash - gap;
+            if horizontal {
+                self.hline(from, to, self.center.y, thickness);
+            } else {
+                self.vline(from, to, self.center.x, thickness);
+            }
+        }
+    }
+
+    /// A rounded corner joining the middle of the edge `horizontal` of the center (-1 left, 1
+    /// right) to the middle of the edge `vertical` of it (-1 up, 1 down)
+    fn arc(&mut self, horizontal: f32, vertical: f32) {
+        let Pos2 { x: cx, y: cy } = self.center;
+        let radius = (self.cell.width() / 2.0).min(self.cell.height() / 2.0);
+        let edge_x = if horizontal < 0.0 {
+            self.cell.left()
+        } else {
+            self.cell.right()
+        };
+        let edge_y = if vertical < 0.0 {
+            self.cell.top()
+        } else {
+            self.cell.bottom()
+        };
+        let step = FRAC_PI_2 / ARC_SEGMENTS.approx_as::<f32>().unwrap_or(16.0);
+
+        let mut points = vec![pos2(cx, edge_y)];
+        points.extend((0..=ARC_SEGMENTS).map(|i| {
+            let angle = step * i.approx_as::<f32>().unwrap_or_default();
+            pos2(
+                (horizontal * radius).mul_add(1.0 - angle.cos(), cx),
+                (vertical * radius).mul_add(1.0 - angle.sin(), cy),
+            )
+        }));
+        points.push(pos2(edge_x, cy));
+
+        self.shapes
+            .push(Shape::line(points, Stroke::new(self.light, self.color)));
+    }
+
+    /// A line from corner to corner, from the top left if `from_top_left`, otherwise from the top
+    /// right
+    fn diagonal(&mut self, from_top_left: bool) {
+        let cell = self.cell;
+        let points = if from_top_left {
+            [cell.left_top(), cell.right_bottom()]
+        } else {
+            [cell.right_top(), cell.left_bottom()]
+        };
+
+        self.shapes.push(Shape::line_segment(
+            points,
+            Stroke::new(self.light, self.color),
+        ));
+    }
+
+    /// Block elements, U+2580 to U+259F
+    fn block(&mut self, c: char) {
+        let cell = self.cell;
+        let x = |eighths: f32| cell.width().mul_add(eighths / 8.0, cell.left());
+        let y = |eighths: f32| cell.height().mul_add(eighths / 8.0, cell.top());
+        let (left, right, top, bottom) = (cell.left(), cell.right(), cell.top(), cell.bottom());
+
+        match c {
+            '\u{2580}' => self.fill(left, top, right, y(4.0)),
+            // Lower one eighth to full block
+            '\u{2581}'..='\u{2588}' => {
+                let eighths = f32::from(u8::try_from(u32::from(c) - 0x2580).unwrap_or(8));
+                self.fill(left, y(8.0 - eighths), right, bottom);
+            }
+            // Left seven eighths to left one eighth
+            '\u{2589}'..='\u{258F}' => {
+                let eighths = f32::from(u8::try_from(0x2590 - u32::from(c)).unwrap_or(8));
+                self.fill(left, top, x(eighths), bottom);
+            }
+            '\u{2590}' => self.fill(x(4.0), top, right, bottom),
+            '\u{2591}'..='\u{2593}' => {
+                let alpha = match c {
+                    '\u{2591}' => 0.25,
+                    '\u{2592}' => 0.5,
+                    _ => 0.75,
+                };
+                let color = self.color;
+                self.color = color.gamma_multiply(alpha);
+                self.fill(left, top, right, bottom);
+                self.color = color;
+            }
+            '\u{2594}' => self.fill(left, top, right, y(1.0)),
+            '\u{2595}' => self.fill(x(7.0), top, right, bottom),
+            '\u{2596}'..='\u{259F}' => {
+                let quadrants = mosaic_quadrants(c).unwrap_or_default();
+                let (middle_x, middle_y) = (x(4.0), y(4.0));
+                let rects = [
+                    (left, top, middle_x, middle_y),
+                    (middle_x, top, right, middle_y),
+                    (left, middle_y, middle_x, bottom),
+                    (middle_x, middle_y, right, bottom),
+                ];
+                for ((l, t, r, b), filled) in rects.into_iter().zip(quadrants) {
+                    if filled {
+                        self.fill(l, t, r, b);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Conversion of egui's keys and modifiers to the terminal's own
+
+use eframe::egui;
+use freminal_terminal_emulator::{input::Key, interface::KeyModifiers};
+
+/// The terminal's key for an egui key, or `None` for keys the terminal has no use for, like
+/// egui's copy, cut and paste keys
+#[must_use]
+pub fn terminal_key(key: egui::Key) -> Option<Key> {
+    Some(match key {
+        egui::Key::Enter => Key::Enter,
+        egui::Key::Tab => Key::Tab,
+        egui::Key::Backspace => Key::Backspace,
+        egui::Key::Escape => Key::Escape,
+        egui::Key::ArrowUp => Key::ArrowUp,
+        egui::Key::ArrowDown => Key::ArrowDown,
+        egui::Key::ArrowLeft => Key::ArrowLeft,
+        egui::Key::ArrowRight => Key::ArrowRight,
+        egui::Key::Home => Key::Home,
+        egui::Key::End => Key::End,
+        egui::Key::Insert => Key::Insert,
+        egui::Key::Delete => Key::Delete,
+        egui::Key::PageUp => Key::PageUp,
+        egui::Key::PageDown => Key::PageDown,
+        egui::Key::Space => Key::Char(' '),
+        egui::Key::Colon => Key::Char(':'),
+        egui::Key::Comma => Key::Char(','),
+        egui::Key::Backslash => Key::Char('\\'),
+        egui::Key::Slash => Key::Char('/'),
+        egui::Key::Pipe => Key::Char('|'),
+        egui::Key::Questionmark => Key::Char('?'),
+        egui::Key::OpenBracket => Key::Char('['),
+        egui::Key::CloseBracket => Key::Char(']'),
+        egui::Key::Backtick => Key::Char('`'),
+        egui::Key::Minus => Key::Char('-'),
+        egui::Key::Period => Key::Char('.'),
+        egui::Key::Plus => Key::Char('+'),
+        egui::Key::Equals => Key::Char('='),
+        egui::Key::Semicolon => Key::Char(';'),
+        egui::Key::Quote => Key::Char('\''),
+        egui::Key::Copy | egui::Key::Cut | egui::Key::Paste => return None,
+        // Letters, digits and function keys, which egui names A, 0 and F1
+        _ => {
+            let name = key.name();
+            if let Some(number) = name.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                Key::Function(number)
+            } else {
+                let mut chars = name.chars();
+                let c = chars.next().filter(|_| chars.next().is_none())?;
+                Key::Char(c.to_ascii_lowercase())
+            }
+        }
+    })
+}
+
+/// The terminal's key for an event from the numeric keypad: the press of its Enter key, or the
+/// text one of its other keys typed
+///
+/// The text decides the key, so a keypad that types a comma for its decimal key sends what the
+/// VT220's comma key does. Keys typing anything else, or cursor keys with Num Lock off, aren't
+/// the keypad's own and are sent as what they are.
+#[must_use]
+pub fn keypad_key(event: &egui::Event) -> Option<Key> {
+    match event {
+        egui::Event::Key {
+            key: egui::Key::Enter,
+            pressed: true,
+            ..
+        } => Some(Key::Keypad(b'\n')),
+        egui::Event::Text(text) => match text.as_bytes() {
+            [c @ b'0'..=b'9'] => Some(Key::Keypad(c - b'0')),
+            [c @ (b'-' | b',' | b'.' | b'+' | b'*' | b'/' | b'=')] => Some(Key::Keypad(*c)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The modifiers the terminal sends with a key. The command key is only for our shortcuts
+#[must_use]
+pub const fn key_modifiers(modifiers: egui::Modifiers) -> KeyModifiers {
+    KeyModifiers {
+        shift: modifiers.shift,
+        alt: modifiers.alt,
+        ctrl: modifiers.ctrl,
+    }
+}
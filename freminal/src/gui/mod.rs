@@ -6,22 +6,76 @@
 // FIXME: we should probably not do this?
 #![allow(clippy::significant_drop_tightening)]
 
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
 use conv::ConvUtil;
 use eframe::egui::{self, CentralPanel, Pos2, Vec2, ViewportCommand};
-use fonts::get_char_size;
-use freminal_common::window_manipulation::WindowManipulation;
+use freminal_common::{
+    args::Args,
+    cell_geometry::{CellGeometry, TerminalGeometry},
+    config::{Config, ConfigWatcher},
+    control::ControlServer,
+    font::FontSettings,
+    links::LinkOpening,
+    metrics::{InputLatency, Metrics},
+    presentation::PresentationSettings,
+    timestamps::TimestampGutter,
+    window_manipulation::WindowManipulation,
+};
 use freminal_terminal_emulator::interface::TerminalEmulator;
 use freminal_terminal_emulator::io::FreminalPtyInputOutput;
+use freminal_terminal_emulator::state::{
+    bell::BellState, redraw::RedrawNotifier, term_warning::TermWarning, title::WindowTitle,
+};
+use glyphs::GlyphSettings;
 use parking_lot::FairMutex;
-use terminal::FreminalTerminalWidget;
+use terminal::{FreminalTerminalWidget, RESERVED_ROWS};
 pub mod colors;
+pub mod control;
 pub mod fonts;
+pub mod glyphs;
+pub mod keys;
 pub mod mouse;
+pub mod paste_file;
+pub mod presentation;
+pub mod screenshot;
+pub mod search;
+pub mod shortcuts;
+pub mod smooth_scroll;
 pub mod terminal;
 
+/// How often to check which program is running, for the window title
+const FOREGROUND_PROCESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often to check whether the config file has changed
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Repaints the window when the terminal has something new to show
+#[derive(Debug)]
+pub struct EguiRedrawNotifier(egui::Context);
+
+impl EguiRedrawNotifier {
+    #[must_use]
+    pub const fn new(ctx: egui::Context) -> Self {
+        Self(ctx)
+    }
+}
+
+impl RedrawNotifier for EguiRedrawNotifier {
+    fn request_redraw(&self) {
+        self.0.request_repaint();
+    }
+
+    fn request_redraw_after(&self, delay: Duration) {
+        self.0.request_repaint_after(delay);
+    }
+}
+
 fn set_egui_options(ctx: &egui::Context) {
     ctx.style_mut(|style| {
         style.visuals.window_fill = egui::Color32::BLACK;
@@ -33,35 +87,215 @@ fn set_egui_options(ctx: &egui::Context) {
 
     // ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(true));
 }
+
+/// Show the bells that rang and any output while the window was unfocused, and ask for the
+/// user's attention the first time either happens
+fn update_bells(ctx: &egui::Context, window_title: &mut WindowTitle, bell: &mut BellState) {
+    window_title.set_bells(bell.unfocused_bells());
+    window_title.set_activity(bell.unfocused_activity());
+
+    if bell.take_attention_request() {
+        ctx.send_viewport_cmd(ViewportCommand::RequestUserAttention(
+            egui::UserAttentionType::Informational,
+        ));
+    }
+}
+
 struct FreminalGui {
     terminal_emulator: Arc<FairMutex<TerminalEmulator<FreminalPtyInputOutput>>>,
     terminal_widget: FreminalTerminalWidget,
-    window_title_stack: Vec<String>,
+    window_title: WindowTitle,
+    control_server: Option<ControlServer>,
+    /// Size to open the window at. Taken on the first frame, once the font can be measured
+    initial_geometry: Option<TerminalGeometry>,
+    /// When we last checked which program is running
+    foreground_process_checked: Option<Instant>,
+    /// Watches the config file, so changes to it are applied while we run
+    config_watcher: Option<ConfigWatcher>,
+    /// When we last checked the config file
+    config_checked: Option<Instant>,
 }
 
 impl FreminalGui {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         cc: &eframe::CreationContext<'_>,
         terminal_emulator: Arc<FairMutex<TerminalEmulator<FreminalPtyInputOutput>>>,
+        snap_to_cells: bool,
+        timestamp_gutter: TimestampGutter,
+        glyph_settings: GlyphSettings,
+        font_settings: &FontSettings,
+        presentation_settings: PresentationSettings,
+        link_opening: LinkOpening,
+        control_socket: Option<&str>,
+        initial_geometry: TerminalGeometry,
+        window_title: WindowTitle,
+        config_path: Option<PathBuf>,
     ) -> Self {
         set_egui_options(&cc.egui_ctx);
 
+        let control_server = control_socket.and_then(|path| {
+            let ctx = cc.egui_ctx.clone();
+            ControlServer::bind(Path::new(path), move || ctx.request_repaint())
+                .map_err(|e| error!("Failed to open control socket {path}: {e}"))
+                .ok()
+        });
+
         Self {
             terminal_emulator,
-            terminal_widget: FreminalTerminalWidget::new(&cc.egui_ctx),
-            window_title_stack: Vec::new(),
+            terminal_widget: FreminalTerminalWidget::new(
+                &cc.egui_ctx,
+                snap_to_cells,
+                timestamp_gutter,
+                glyph_settings,
+                font_settings,
+                presentation_settings,
+                link_opening,
+            ),
+            window_title,
+            control_server,
+            initial_geometry: Some(initial_geometry),
+            foreground_process_checked: None,
+            config_watcher: config_path.map(ConfigWatcher::new),
+            config_checked: None,
+        }
+    }
+
+    /// Whether it is time to check the config file, and it has changed
+    fn config_changed(&mut self) -> bool {
+        let Some(watcher) = &mut self.config_watcher else {
+            return false;
+        };
+
+        if self
+            .config_checked
+            .is_some_and(|checked| checked.elapsed() < CONFIG_CHECK_INTERVAL)
+        {
+            return false;
         }
+
+        self.config_checked = Some(Instant::now());
+        watcher.changed()
+    }
+
+    /// Read the config file again and apply the settings that can change while we run: the font
+    /// size, the palette, how much scrollback to keep, how links are found and opened and what a
+    /// double click selects. Command line arguments still win
+    fn reload_config(&mut self, terminal_emulator: &mut TerminalEmulator<FreminalPtyInputOutput>) {
+        let Some(watcher) = &self.config_watcher else {
+            return;
+        };
+
+        let path = watcher.path();
+        let args = match Config::load(path)
+            .and_then(|config| Args::parse_with_config(std::env::args(), &config))
+        {
+            Ok(args) => args,
+            Err(e) => {
+                error!("Failed to reload config from {}: {e}", path.display());
+                return;
+            }
+        };
+
+        info!("Reloaded config from {}", path.display());
+        self.terminal_widget
+            .set_base_font_size(args.font_settings.size, args.presentation_settings);
+        self.terminal_widget.set_link_opening(args.link_opening);
+        terminal_emulator.internal.set_palette(args.palette);
+        terminal_emulator.internal.word_characters = args.word_characters;
+        terminal_emulator.internal.url_schemes = args.url_schemes;
+        terminal_emulator
+            .internal
+            .set_scrollback_lines(args.scrollback_lines);
+        terminal_emulator
+            .internal
+            .set_alternate_scrollback_lines(args.alternate_scrollback_lines);
+        terminal_emulator.set_previous_pass_invalid();
     }
 }
 
+/// Toggle fullscreen. Fullscreen always uses the monitor the window is currently on
+pub fn toggle_fullscreen(ctx: &egui::Context) {
+    let current_status = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+    ctx.send_viewport_cmd(ViewportCommand::Fullscreen(!current_status));
+}
+
+/// Open another freminal window with the same settings as this one, in `directory` if we know
+/// where the shell is
+///
+/// # Errors
+/// Will error if we can't find our own executable or start it
+pub fn open_new_window(directory: Option<&Path>) -> Result<()> {
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command.args(Args::new_window_args(std::env::args()));
+    if let Some(directory) = directory {
+        command.current_dir(directory);
+    }
+
+    command.spawn()?;
+    Ok(())
+}
+
+/// Grow the window to the size of the monitor it is currently on, in one or both directions
+///
+/// egui only tells us the size of the current monitor, not where it is, so the window is resized
+/// but left where it is rather than risking moving it on to a different monitor.
+fn maximize_on_current_monitor(ui: &egui::Ui, horizontally: bool, vertically: bool) {
+    let Some(monitor_size) = ui.ctx().input(|i| i.raw.viewport().monitor_size) else {
+        error!("Failed to get monitor size. Not maximizing window");
+        return;
+    };
+
+    let outer = viewport_rect(ui, |viewport| viewport.outer_rect);
+    let inner = viewport_rect(ui, |viewport| viewport.inner_rect);
+    // Title bar and borders
+    let decorations = outer.size() - inner.size();
+
+    let mut size = inner.size();
+
+    if horizontally {
+        size.x = monitor_size.x - decorations.x;
+    }
+
+    if vertically {
+        size.y = monitor_size.y - decorations.y;
+    }
+
+    ui.ctx().send_viewport_cmd(ViewportCommand::InnerSize(size));
+}
+
+/// One of the viewport's rects, in points
+fn viewport_rect(
+    ui: &egui::Ui,
+    rect: impl FnOnce(&egui::ViewportInfo) -> Option<egui::Rect>,
+) -> egui::Rect {
+    ui.ctx()
+        .input(|i| rect(i.raw.viewport()))
+        .unwrap_or_else(|| {
+            error!("Failed to get viewport rect. Using 0 as default");
+            egui::Rect::ZERO
+        })
+}
+
+/// Where the terminal's cells are drawn inside the window, in points. This leaves out the padding
+/// around the terminal
+fn text_area_rect(
+    ui: &egui::Ui,
+    terminal_emulator: &mut TerminalEmulator<FreminalPtyInputOutput>,
+    geometry: &CellGeometry,
+) -> egui::Rect {
+    let (width, height) = terminal_emulator.internal.get_win_size();
+    let (width, height) = geometry.size_for_cells(width, height);
+
+    egui::Rect::from_min_size(ui.max_rect().min, Vec2::new(width, height))
+}
+
 #[allow(clippy::too_many_lines)]
 fn handle_window_manipulation(
     ui: &egui::Ui,
     terminal_emulator: &mut TerminalEmulator<FreminalPtyInputOutput>,
-    font_width: usize,
-    font_height: usize,
-    window_width: egui::Rect,
-    title_stack: &mut Vec<String>,
+    geometry: &CellGeometry,
+    window_title: &mut WindowTitle,
 ) {
     let window_commands: Vec<_> = terminal_emulator
         .internal
@@ -89,9 +323,21 @@ fn handle_window_manipulation(
                 ui.ctx()
                     .send_viewport_cmd(ViewportCommand::OuterPosition(Pos2::new(x, y)));
             }
-            WindowManipulation::ResizeWindow(width, height) => {
-                let width = width.approx_as::<f32>().unwrap_or_default();
-                let height = height.approx_as::<f32>().unwrap_or_default();
+            WindowManipulation::ResizeWindow(height, width) => {
+                // The size of the text area in pixels, with 0 keeping that side as it is. It is
+                // rounded to whole cells so it can be held to the size limits
+                let (columns, rows) = terminal_emulator.internal.get_win_size();
+                let (current_width, current_height) =
+                    geometry.text_area_size_in_pixels(columns, rows);
+                let width = if width == 0 { current_width } else { width };
+                let height = if height == 0 { current_height } else { height };
+
+                let (columns, rows) = geometry.cells_for_size(
+                    geometry.pixels_to_points(width),
+                    geometry.pixels_to_points(height),
+                );
+                let (columns, rows) = terminal_emulator.internal.size_limits.clamp(columns, rows);
+                let (width, height) = geometry.window_size_for_cells(columns, rows + RESERVED_ROWS);
 
                 ui.ctx()
                     .send_viewport_cmd(ViewportCommand::InnerSize(Vec2::new(width, height)));
@@ -99,22 +345,20 @@ fn handle_window_manipulation(
             WindowManipulation::MaximizeWindow => {
                 ui.ctx().send_viewport_cmd(ViewportCommand::Maximized(true));
             }
+            WindowManipulation::MaximizeWindowVertically => {
+                maximize_on_current_monitor(ui, false, true);
+            }
+            WindowManipulation::MaximizeWindowHorizontally => {
+                maximize_on_current_monitor(ui, true, false);
+            }
             WindowManipulation::RestoreNonMaximizedWindow => {
                 ui.ctx()
                     .send_viewport_cmd(ViewportCommand::Maximized(false));
             }
+            // Already held to the size limits by the terminal
             WindowManipulation::ResizeWindowToLinesAndColumns(input_height, input_width) => {
-                let available_height = ui.available_height();
-                let available_width = ui.available_width();
-                let width_difference = window_width.width() - available_width;
-                let height_difference = window_width.height() - available_height;
-                let width = input_width * font_width;
-                let height = input_height * font_height;
-
-                let width = width.approx_as::<f32>().unwrap_or_default() + width_difference;
-                let height = height.approx_as::<f32>().unwrap_or_default() + height_difference;
-
-                // FIXME: We can have an off by one because of all the rounding that happens with font height/width
+                let (width, height) =
+                    geometry.window_size_for_cells(input_width, input_height + RESERVED_ROWS);
 
                 ui.ctx()
                     .send_viewport_cmd(ViewportCommand::InnerSize(Vec2::new(width, height)));
@@ -127,11 +371,7 @@ fn handle_window_manipulation(
                 ui.ctx()
                     .send_viewport_cmd(ViewportCommand::Fullscreen(true));
             }
-            WindowManipulation::ToggleFullScreen => {
-                let current_status = ui.ctx().input(|i| i.viewport().fullscreen.unwrap_or(false));
-                ui.ctx()
-                    .send_viewport_cmd(ViewportCommand::Fullscreen(!current_status));
-            }
+            WindowManipulation::ToggleFullScreen => toggle_fullscreen(ui.ctx()),
             WindowManipulation::ReportWindowState => {
                 let current_status = ui.ctx().input(|i| i.viewport().minimized.unwrap_or(false));
                 terminal_emulator
@@ -139,121 +379,53 @@ fn handle_window_manipulation(
                     .report_window_state(current_status);
             }
             WindowManipulation::ReportWindowPositionWholeWindow => {
-                let position = ui
-                    .ctx()
-                    .input(|i| {
-                        i.raw.viewport().outer_rect.unwrap_or_else(|| {
-                            error!("Failed to get viewport position. Using 0 as default");
-                            egui::Rect::from_min_size(Pos2::new(0.0, 0.0), Vec2::new(0.0, 0.0))
-                        })
-                    })
-                    .min;
-
-                let pos_x = position.x.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position x to usize: {e}. Using 0 as default");
-                    0
-                });
-                let pos_y = position.y.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position y to usize: {e}. Using 0 as default");
-                    0
-                });
+                let position = viewport_rect(ui, |viewport| viewport.outer_rect).min;
 
-                terminal_emulator
-                    .internal
-                    .report_window_position(pos_x, pos_y);
+                terminal_emulator.internal.report_window_position(
+                    geometry.points_to_pixels(position.x),
+                    geometry.points_to_pixels(position.y),
+                );
             }
             WindowManipulation::ReportWindowPositionTextArea => {
-                let position = ui
-                    .ctx()
-                    .input(|i| {
-                        i.raw.viewport().outer_rect.unwrap_or_else(|| {
-                            error!("Failed to get viewport position. Using 0 as default");
-                            egui::Rect::from_min_size(Pos2::new(0.0, 0.0), Vec2::new(0.0, 0.0))
-                        })
-                    })
-                    .min;
-
-                let available_height = ui.available_height();
-                let available_width = ui.available_width();
-                let width_difference = window_width.width() - available_width;
-                let height_difference = window_width.height() - available_height;
-                let pos_x = (position.y + height_difference)
-                    .approx_as::<usize>()
-                    .unwrap_or_else(|e| {
-                        error!("Failed to convert position x to usize: {e}. Using 0 as default");
-                        0
-                    });
-                let pos_y = (position.y + width_difference)
-                    .approx_as::<usize>()
-                    .unwrap_or_else(|e| {
-                        error!("Failed to convert position y to usize: {e}. Using 0 as default");
-                        0
-                    });
-
-                terminal_emulator
-                    .internal
-                    .report_window_position(pos_x, pos_y);
+                // The text area is positioned relative to the window's inner (client) area
+                let inner = viewport_rect(ui, |viewport| viewport.inner_rect).min;
+                let text_area = text_area_rect(ui, terminal_emulator, geometry).min;
+
+                terminal_emulator.internal.report_window_position(
+                    geometry.points_to_pixels(inner.x + text_area.x),
+                    geometry.points_to_pixels(inner.y + text_area.y),
+                );
             }
             WindowManipulation::ReportWindowSizeInPixels => {
-                let position = ui.ctx().input(|i| {
-                    i.raw.viewport().outer_rect.unwrap_or_else(|| {
-                        error!("Failed to get viewport position. Using 0 as default");
-                        egui::Rect::from_min_size(Pos2::new(0.0, 0.0), Vec2::new(0.0, 0.0))
-                    })
-                });
-
-                let width = position.max.x - position.min.x;
-                let height = position.max.y - position.min.y;
+                let size = viewport_rect(ui, |viewport| viewport.outer_rect).size();
 
-                let pos_x = width.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position x to usize: {e}. Using 0 as default");
-                    0
-                });
-                let pos_y = height.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position y to usize: {e}. Using 0 as default");
-                    0
-                });
-
-                terminal_emulator.internal.report_window_size(pos_x, pos_y);
+                terminal_emulator.internal.report_window_size(
+                    geometry.points_to_pixels(size.x),
+                    geometry.points_to_pixels(size.y),
+                );
             }
             WindowManipulation::ReportWindowTextAreaSizeInPixels => {
-                let size = ui.ctx().screen_rect().max;
-                let pos_x = size.x.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position x to usize: {e}. Using 0 as default");
-                    0
-                });
-                let pos_y = size.y.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position y to usize: {e}. Using 0 as default");
-                    0
-                });
+                let (width, height) = terminal_emulator.internal.get_win_size();
+                let (width, height) = geometry.text_area_size_in_pixels(width, height);
 
-                terminal_emulator.internal.report_window_size(pos_x, pos_y);
+                terminal_emulator.internal.report_window_size(width, height);
             }
             WindowManipulation::ReportRootWindowSizeInPixels => {
-                let position = ui.ctx().input(|i| {
-                    i.raw.viewport().outer_rect.unwrap_or_else(|| {
-                        error!("Failed to get viewport position. Using 0 as default");
-                        egui::Rect::from_min_size(Pos2::new(0.0, 0.0), Vec2::new(0.0, 0.0))
-                    })
-                });
-
-                let width = position.max.x - position.min.x;
-                let height = position.max.y - position.min.y;
-
-                let pos_x = width.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position x to usize: {e}. Using 0 as default");
-                    0
-                });
-                let pos_y = height.approx_as::<usize>().unwrap_or_else(|e| {
-                    error!("Failed to convert position y to usize: {e}. Using 0 as default");
-                    0
-                });
+                let size = ui
+                    .ctx()
+                    .input(|i| i.raw.viewport().monitor_size)
+                    .unwrap_or_else(|| {
+                        error!("Failed to get monitor size. Using the window size");
+                        viewport_rect(ui, |viewport| viewport.outer_rect).size()
+                    });
 
-                terminal_emulator
-                    .internal
-                    .report_root_window_size(pos_x, pos_y);
+                terminal_emulator.internal.report_root_window_size(
+                    geometry.points_to_pixels(size.x),
+                    geometry.points_to_pixels(size.y),
+                );
             }
             WindowManipulation::ReportCharacterSizeInPixels => {
+                let (font_width, font_height) = geometry.cell_size_in_pixels();
                 terminal_emulator
                     .internal
                     .report_character_size(font_width, font_height);
@@ -272,43 +444,18 @@ fn handle_window_manipulation(
                     .report_root_terminal_size_in_characters(width, height);
             }
             WindowManipulation::ReportIconLabel => {
-                let title = ui.ctx().input(|r| r.raw.viewport().title.clone());
-                let title = title.unwrap_or_else(|| {
-                    error!("Failed to get viewport title. Using Freminal");
-                    "Freminal".to_string()
-                });
-                terminal_emulator.internal.report_icon_label(&title);
+                terminal_emulator
+                    .internal
+                    .report_icon_label(window_title.title());
             }
             WindowManipulation::ReportTitle => {
-                let title = ui.ctx().input(|r| r.raw.viewport().title.clone());
-                let title = title.unwrap_or_else(|| {
-                    error!("Failed to get viewport title. Using Freminal");
-                    "Freminal".to_string()
-                });
-                terminal_emulator.internal.report_title(&title);
-            }
-            WindowManipulation::SetTitleBarText(title) => {
-                ui.ctx()
-                    .send_viewport_cmd(egui::ViewportCommand::Title(title));
-            }
-            WindowManipulation::SaveWindowTitleToStack => {
-                let title = ui.ctx().input(|r| r.raw.viewport().title.clone());
-                let title = title.unwrap_or_else(|| {
-                    error!("Failed to get viewport title. Using Freminal");
-                    "Freminal".to_string()
-                });
-
-                title_stack.push(title);
-            }
-            WindowManipulation::RestoreWindowTitleFromStack => {
-                if let Some(title) = title_stack.pop() {
-                    ui.ctx()
-                        .send_viewport_cmd(egui::ViewportCommand::Title(title));
-                } else {
-                    ui.ctx()
-                        .send_viewport_cmd(egui::ViewportCommand::Title("Freminal".to_string()));
-                }
+                terminal_emulator
+                    .internal
+                    .report_title(window_title.title());
             }
+            WindowManipulation::SetTitleBarText(title) => window_title.set(title),
+            WindowManipulation::SaveWindowTitleToStack => window_title.push(),
+            WindowManipulation::RestoreWindowTitleFromStack => window_title.pop(),
             // These are ignored. eGui doesn't give us a stacking order thing (that I can tell)
             // refresh window is already happening because we ended up here.
             WindowManipulation::RefreshWindow
@@ -318,65 +465,143 @@ fn handle_window_manipulation(
     }
 }
 
+fn show_metrics_hud(ctx: &egui::Context, metrics: &Metrics, input_latency: &InputLatency) {
+    egui::Area::new(egui::Id::new("freminal_metrics_hud"))
+        .anchor(egui::Align2::RIGHT_TOP, Vec2::new(-8.0, 8.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                if metrics.enabled {
+                    ui.monospace(format!("Frame: {}", metrics.frame_time));
+                    ui.monospace(format!("Data:  {}", metrics.data_processing_time));
+                    ui.monospace(format!("Bytes: {}", metrics.bytes_processed));
+                }
+
+                if input_latency.enabled {
+                    ui.monospace(format!("Key to write: {}", input_latency.to_write));
+                    ui.monospace(format!("Key to echo:  {}", input_latency.to_echo));
+                    ui.monospace(format!("Key to frame: {}", input_latency.to_present));
+                }
+            });
+        });
+}
+
+/// Suggest another `TERM` if a program has said it doesn't know ours
+fn show_term_warning(ctx: &egui::Context, term_warning: &mut TermWarning) {
+    let Some(hint) = term_warning.hint() else {
+        return;
+    };
+
+    egui::Area::new(egui::Id::new("freminal_term_warning"))
+        .anchor(egui::Align2::CENTER_TOP, Vec2::new(0.0, 8.0))
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(hint);
+                    if ui.button("Dismiss").clicked() {
+                        term_warning.dismiss();
+                    }
+                });
+            });
+        });
+}
+
 impl eframe::App for FreminalGui {
+    fn raw_input_hook(&mut self, _ctx: &egui::Context, _raw_input: &mut egui::RawInput) {
+        self.terminal_widget
+            .set_numpad_events(egui_winit::take_numpad_events());
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // log the frame time
-        // time now
-        debug!("Starting new frame");
-        #[cfg(debug_assertions)]
         let now = std::time::Instant::now();
 
+        // Both are checked, so a change to the file isn't picked up a second time next frame
+        if self.terminal_widget.take_reload_config_request() | self.config_changed() {
+            let terminal_emulator = Arc::clone(&self.terminal_emulator);
+            self.reload_config(&mut terminal_emulator.lock());
+        }
+
         let panel_response = CentralPanel::default().show(ctx, |ui| {
+            let geometry = self.terminal_widget.cell_geometry(ui);
             let (width_chars, height_chars) = self.terminal_widget.calculate_available_size(ui);
-            let (font_width, font_height) =
-                get_char_size(ui.ctx(), self.terminal_widget.get_font_size());
+            let (font_width, font_height) = geometry.cell_size_in_pixels();
 
-            let font_width = font_width.round().approx_as::<usize>().unwrap_or_else(|e| {
-                error!("Failed to convert font width to usize: {e}. Using 12 as default");
-                12
-            });
+            let mut lock = self.terminal_emulator.lock();
+            if let Some(control_server) = &self.control_server {
+                control::handle_control_requests(
+                    control_server,
+                    &mut self.terminal_widget,
+                    &mut lock,
+                );
+            }
 
-            let font_height = font_height
-                .round()
-                .approx_as::<usize>()
-                .unwrap_or_else(|e| {
-                    error!("Failed to convert font height to usize: {e}. Using 12 as default");
-                    12
-                });
+            // Minimized, there is nothing to lay out. Output is still parsed in the background
+            let minimized = ui.input(|i| i.viewport().minimized.unwrap_or(false));
+            lock.internal.set_window_visible(!minimized);
+            if minimized {
+                return;
+            }
 
-            let mut lock = self.terminal_emulator.lock();
-            if let Err(e) = lock.set_win_size(width_chars, height_chars, font_width, font_height) {
+            // The window is still at its default size on the first frame, so the pty keeps the
+            // size it was started with until our resize lands
+            if let Some(initial_geometry) = self.initial_geometry.take() {
+                let (columns, rows) = lock
+                    .internal
+                    .size_limits
+                    .clamp(initial_geometry.columns, initial_geometry.rows);
+                let (width, height) = geometry.window_size_for_cells(columns, rows + RESERVED_ROWS);
+                ui.ctx()
+                    .send_viewport_cmd(ViewportCommand::InnerSize(Vec2::new(width, height)));
+            } else if let Err(e) =
+                lock.set_win_size(width_chars, height_chars, font_width, font_height)
+            {
                 error!("failed to set window size {e}");
             }
 
-            let window_width = ctx.input(|i: &egui::InputState| i.screen_rect());
-
-            handle_window_manipulation(
-                ui,
-                &mut lock,
-                font_width,
-                font_height,
-                window_width,
-                &mut self.window_title_stack,
-            );
+            handle_window_manipulation(ui, &mut lock, &geometry, &mut self.window_title);
+
+            update_bells(ui.ctx(), &mut self.window_title, &mut lock.internal.bell);
+            self.window_title
+                .set_working_directory(lock.internal.working_directory.as_ref());
+            if self
+                .foreground_process_checked
+                .is_none_or(|checked| checked.elapsed() >= FOREGROUND_PROCESS_INTERVAL)
+            {
+                self.window_title
+                    .set_foreground_process(lock.foreground_process_name());
+                self.foreground_process_checked = Some(Instant::now());
+            }
+            // Programs can start and stop without writing anything, so keep checking
+            ui.ctx().request_repaint_after(FOREGROUND_PROCESS_INTERVAL);
+            if let Some(title) = self.window_title.take_update() {
+                ui.ctx().send_viewport_cmd(ViewportCommand::Title(title));
+            }
 
             self.terminal_widget.show(ui, &mut lock);
+            // Marked while we still hold the lock, so any echo that arrived was in this frame
+            if lock.internal.input_latency.enabled() {
+                lock.internal
+                    .input_latency
+                    .lock()
+                    .frame_presented(std::time::Instant::now());
+            }
         });
 
         panel_response.response.context_menu(|ui| {
-            self.terminal_widget.show_options(ui);
+            self.terminal_widget
+                .show_options(ui, &mut self.terminal_emulator.lock());
         });
 
-        #[cfg(debug_assertions)]
-        // log the frame time
-        let elapsed = now.elapsed();
-        #[cfg(debug_assertions)]
-        // show either elapsed as micros or millis, depending on the duration
-        if elapsed.as_millis() > 0 {
-            debug!("Frame time: {}ms", elapsed.as_millis());
-        } else {
-            debug!("Frame time: {}μs", elapsed.as_micros());
+        let mut lock = self.terminal_emulator.lock();
+        if lock.internal.metrics.enabled {
+            lock.internal.metrics.frame_time.record(now.elapsed());
+        }
+
+        let input_latency = lock.internal.input_latency.lock().clone();
+        if lock.internal.metrics.enabled || input_latency.enabled {
+            show_metrics_hud(ctx, &lock.internal.metrics, &input_latency);
         }
+        show_term_warning(ctx, &mut lock.internal.term_warning);
     }
 }
 
@@ -386,13 +611,45 @@ impl eframe::App for FreminalGui {
 /// Will return an error if the GUI fails to run
 pub fn run(
     terminal_emulator: Arc<FairMutex<TerminalEmulator<FreminalPtyInputOutput>>>,
+    args: &Args,
 ) -> Result<()> {
     let native_options = eframe::NativeOptions::default();
+    let snap_to_cells = args.snap_to_cells;
+    let timestamp_gutter = args.timestamp_gutter;
+    let glyph_settings = GlyphSettings {
+        powerline: args.powerline_glyphs,
+        box_drawing: args.box_drawing_glyphs,
+    };
+    let font_settings = args.font_settings.clone();
+    let presentation_settings = args.presentation_settings.clone();
+    let link_opening = args.link_opening;
+    let config_path = Config::path_for_args(&std::env::args().collect::<Vec<_>>());
+    let control_socket = args.control_socket.clone();
+    let geometry = args.geometry;
+    let window_title = WindowTitle::with_format(
+        args.title_format.clone(),
+        terminal_emulator.lock().id.to_string(),
+    );
 
     match eframe::run_native(
         "Freminal",
         native_options,
-        Box::new(move |cc| Ok(Box::new(FreminalGui::new(cc, terminal_emulator)))),
+        Box::new(move |cc| {
+            Ok(Box::new(FreminalGui::new(
+                cc,
+                terminal_emulator,
+                snap_to_cells,
+                timestamp_gutter,
+                glyph_settings,
+                &font_settings,
+                presentation_settings,
+                link_opening,
+                control_socket.as_deref(),
+                geometry,
+                window_title,
+                config_path,
+            )))
+        }),
     ) {
         Ok(()) => Ok(()),
         Err(e) => Err(anyhow::anyhow!(e.to_string())),
@@ -5,11 +5,11 @@
 #![allow(clippy::module_name_repetitions)]
 use std::borrow::Cow;
 
-use conv::ConvUtil;
 use eframe::egui::{Modifiers, PointerButton, Vec2};
 use freminal_terminal_emulator::{
     ansi_components::mode::{MouseEncoding, MouseTrack},
-    interface::{collect_text, raw_ascii_bytes_to_terminal_input, TerminalInput},
+    input::{encode_mouse_report, MouseAction, MouseButton},
+    interface::{raw_ascii_bytes_to_terminal_input, KeyModifiers, TerminalInput},
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -63,11 +63,6 @@ impl PreviousMouseState {
     }
 }
 
-pub enum MouseEvent {
-    Button(PointerButton),
-    Scroll(Vec2),
-}
-
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct FreminalMousePosition {
@@ -96,263 +91,95 @@ impl FreminalMousePosition {
 
 impl PartialEq for FreminalMousePosition {
     fn eq(&self, other: &Self) -> bool {
-        self.y_as_character_row == other.y_as_character_row
+        self.x_as_character_column == other.x_as_character_column
+            && self.y_as_character_row == other.y_as_character_row
     }
 }
 
-#[must_use]
-pub fn handle_pointer_button(
-    button: PointerButton,
-    current_state: &PreviousMouseState,
-    mouse_track: &MouseTrack,
-) -> Option<Cow<'static, [TerminalInput]>> {
-    match mouse_track {
-        MouseTrack::XtMsex10 => {
-            if current_state.button_pressed {
-                return Some(encode_x11_mouse_button(
-                    button,
-                    true,
-                    current_state.modifiers,
-                    &current_state.mouse_position,
-                    false,
-                    &mouse_track.get_encoding(),
-                ));
-            }
-            None
-        }
-        MouseTrack::XtMseX11
-        | MouseTrack::XtMseBtn
-        | MouseTrack::XtMseAny
-        | MouseTrack::XtMseSgr => Some(encode_x11_mouse_button(
-            button,
-            current_state.button_pressed,
-            current_state.modifiers,
-            &current_state.mouse_position,
-            false,
-            &mouse_track.get_encoding(),
-        )),
-        MouseTrack::NoTracking
-        | MouseTrack::XtMseUtf
-        | MouseTrack::XtMseUrXvt
-        | MouseTrack::XtMseSgrPixels
-        | MouseTrack::Query(_) => None,
+/// The terminal's name for an egui button
+const fn mouse_button(button: PointerButton) -> MouseButton {
+    match button {
+        PointerButton::Primary => MouseButton::Left,
+        PointerButton::Middle => MouseButton::Middle,
+        PointerButton::Secondary => MouseButton::Right,
+        PointerButton::Extra1 => MouseButton::Back,
+        PointerButton::Extra2 => MouseButton::Forward,
     }
 }
 
-#[must_use]
-pub fn handle_pointer_moved(
-    current_state: &PreviousMouseState,
-    previous_state: &PreviousMouseState,
+/// Turn a mouse report in to input for the terminal
+fn report(
+    action: MouseAction,
+    state: &PreviousMouseState,
     mouse_track: &MouseTrack,
+    encoding: MouseEncoding,
 ) -> Option<Cow<'static, [TerminalInput]>> {
-    match mouse_track {
-        MouseTrack::XtMseBtn => {
-            if current_state.button_pressed && previous_state.should_report(current_state) {
-                return Some(encode_x11_mouse_button(
-                    current_state.button,
-                    true,
-                    current_state.modifiers,
-                    &current_state.mouse_position,
-                    true,
-                    &mouse_track.get_encoding(),
-                ));
-            }
-
-            None
-        }
-        MouseTrack::XtMseAny | MouseTrack::XtMseSgr => {
-            if previous_state.should_report(current_state) {
-                return Some(encode_x11_mouse_button(
-                    current_state.button,
-                    current_state.button_pressed,
-                    current_state.modifiers,
-                    &current_state.mouse_position,
-                    true,
-                    &mouse_track.get_encoding(),
-                ));
-            }
-
-            None
-        }
-        MouseTrack::NoTracking
-        | MouseTrack::XtMsex10
-        | MouseTrack::XtMseX11
-        | MouseTrack::XtMseUtf
-        | MouseTrack::XtMseUrXvt
-        | MouseTrack::XtMseSgrPixels
-        | MouseTrack::Query(_) => None,
-    }
+    let position = &state.mouse_position;
+    // Command is reported as Ctrl, which is what it is on everything but a Mac
+    let modifiers = KeyModifiers {
+        shift: state.modifiers.shift,
+        alt: state.modifiers.alt,
+        ctrl: state.modifiers.ctrl || state.modifiers.command,
+    };
+    encode_mouse_report(
+        action,
+        modifiers,
+        position.x_as_character_column,
+        position.y_as_character_row,
+        mouse_track,
+        encoding,
+    )
+    .map(|bytes| raw_ascii_bytes_to_terminal_input(&bytes))
 }
 
 #[must_use]
-pub fn handle_pointer_scroll(
-    delta: Vec2,
+pub fn handle_pointer_button(
+    button: PointerButton,
     current_state: &PreviousMouseState,
     mouse_track: &MouseTrack,
+    encoding: MouseEncoding,
 ) -> Option<Cow<'static, [TerminalInput]>> {
-    match mouse_track {
-        MouseTrack::XtMseX11
-        | MouseTrack::XtMseBtn
-        | MouseTrack::XtMseAny
-        | MouseTrack::XtMseSgr => encode_x11_mouse_wheel(
-            delta,
-            current_state.modifiers,
-            &current_state.mouse_position,
-            &mouse_track.get_encoding(),
-        ),
-        MouseTrack::NoTracking
-        | MouseTrack::XtMsex10
-        | MouseTrack::XtMseUtf
-        | MouseTrack::XtMseUrXvt
-        | MouseTrack::XtMseSgrPixels
-        | MouseTrack::Query(_) => None,
-    }
-}
-
-fn encode_mouse_for_x11(button: &MouseEvent, pressed: bool) -> usize {
-    if pressed {
-        match button {
-            MouseEvent::Button(PointerButton::Primary) => 0,
-            MouseEvent::Button(PointerButton::Middle) => 1,
-            MouseEvent::Button(PointerButton::Secondary) => 2,
-            MouseEvent::Button(_) => {
-                error!("Unsupported mouse button. Treating as left mouse button");
-                0
-            }
-            MouseEvent::Scroll(amount) => {
-                // FIXME: This is not correct. eframe encodes a x and y event together I think.
-                // For now we'll prefer the y event as the driver for the scroll
-                // If that is the case should we be sending a two different events for scroll?
-
-                if amount.y != 0.0 {
-                    if amount.y > 0.0 {
-                        return 64;
-                    }
-                    return 65;
-                }
-
-                0
-            }
-        }
+    let action = if current_state.button_pressed {
+        MouseAction::Press(mouse_button(button))
     } else {
-        3
-    }
-}
-
-const fn encode_modifiers_for_x11(modifiers: Modifiers) -> usize {
-    let mut cb = 0;
-
-    if modifiers.ctrl || modifiers.command {
-        cb += 16;
-    }
-
-    if modifiers.shift {
-        cb += 4;
-    }
-
-    // This is for meta, but wezterm seems to use alt as the meta?
-    if modifiers.alt {
-        cb += 8;
-    }
-
-    cb
-}
-
-fn encode_cb_and_x_and_y_as_u8_from_usize(cb: usize, x: usize, y: usize) -> (u8, u8, u8) {
-    if x > 0x100 {
-        error!("X: {x} is out of range");
-    }
-    if y > 0x100 {
-        error!("Y: {y} is out of range");
-    }
-
-    let cb = cb.approx_as::<u8>().unwrap_or_else(|_| {
-        error!("Failed to convert {} to char. Using default of 255", cb);
-        255
-    });
-
-    let x = x.approx_as::<u8>().unwrap_or_else(|_| {
-        error!("Failed to convert {} to char. Using default of 255", x);
-        255
-    });
-    let y = y.approx_as::<u8>().unwrap_or_else(|_| {
-        error!("Failed to convert {} to char. Using default of 255", y);
-        255
-    });
+        MouseAction::Release(mouse_button(button))
+    };
 
-    (cb, x, y)
+    report(action, current_state, mouse_track, encoding)
 }
 
 #[must_use]
-fn encode_x11_mouse_wheel(
-    delta: Vec2,
-    modifiers: Modifiers,
-    pos: &FreminalMousePosition,
-    encoding: &MouseEncoding,
+pub fn handle_pointer_moved(
+    current_state: &PreviousMouseState,
+    previous_state: &PreviousMouseState,
+    mouse_track: &MouseTrack,
+    encoding: MouseEncoding,
 ) -> Option<Cow<'static, [TerminalInput]>> {
-    let padding = if encoding == &MouseEncoding::X11 {
-        32
-    } else {
-        0
-    };
-
-    let mut cb = padding;
-
-    cb += encode_mouse_for_x11(&MouseEvent::Scroll(delta), true);
-    if cb == 32 {
+    // Only moving to another cell is reported
+    if !previous_state.should_report(current_state) {
         return None;
     }
-    cb += encode_modifiers_for_x11(modifiers);
-
-    let x = pos.x_as_character_column + padding;
-    let y = pos.y_as_character_row + padding;
-    let (cb, x, y) = encode_cb_and_x_and_y_as_u8_from_usize(cb, x, y);
 
-    if encoding == &MouseEncoding::X11 {
-        Some(raw_ascii_bytes_to_terminal_input(&[
-            b'\x1b', b'[', b'M', cb, x, y,
-        ]))
+    let action = if current_state.button_pressed {
+        MouseAction::Drag(mouse_button(current_state.button))
     } else {
-        Some(collect_text(&format!("\x1b[<{cb};{x};{y}M",)))
-    }
-}
-
-fn encode_x11_mouse_button(
-    button: PointerButton,
-    pressed: bool,
-    modifiers: Modifiers,
-    pos: &FreminalMousePosition,
-    report_motion: bool,
-    encoding: &MouseEncoding,
-) -> Cow<'static, [TerminalInput]> {
-    //Normal tracking mode sends an escape sequence on both button press and release. Modifier key (shift, ctrl, meta) information is also sent. It is enabled by specifying parameter 1000 to DECSET. On button press or release, xterm sends CSI M C b C x C y . The low two bits of C b encode button information: 0=MB1 pressed, 1=MB2 pressed, 2=MB3 pressed, 3=release. The next three bits encode the modifiers which were down when the button was pressed and are added together: 4=Shift, 8=Meta, 16=Control
-
-    let padding = if encoding == &MouseEncoding::X11 {
-        32
-    } else {
-        0
-    };
-
-    let motion = if report_motion { 32 } else { 0 };
-    let mut cb: usize = padding + motion;
-    let internal_pressed = if encoding == &MouseEncoding::X11 {
-        pressed
-    } else {
-        true
+        MouseAction::Move
     };
 
-    cb += encode_mouse_for_x11(&MouseEvent::Button(button), internal_pressed);
-    cb += encode_modifiers_for_x11(modifiers);
+    report(action, current_state, mouse_track, encoding)
+}
 
-    let x = pos.x_as_character_column + padding;
-    let y = pos.y_as_character_row + padding;
-    let (cb, x, y) = encode_cb_and_x_and_y_as_u8_from_usize(cb, x, y);
-    if encoding == &MouseEncoding::X11 {
-        raw_ascii_bytes_to_terminal_input(&[b'\x1b', b'[', b'M', cb, x, y])
-    } else {
-        collect_text(&format!(
-            "\x1b[<{cb};{x};{y}{}",
-            if pressed { "M" } else { "m" }
-        ))
-    }
+#[must_use]
+pub fn handle_pointer_scroll(
+    delta: Vec2,
+    current_state: &PreviousMouseState,
+    mouse_track: &MouseTrack,
+    encoding: MouseEncoding,
+) -> Option<Cow<'static, [TerminalInput]>> {
+    report(
+        MouseAction::Wheel((delta.x, delta.y)),
+        current_state,
+        mouse_track,
+        encoding,
+    )
 }
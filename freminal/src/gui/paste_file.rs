@@ -0,0 +1,219 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::path::{Path, PathBuf};
+
+use eframe::egui::{self, Color32, Context, ScrollArea, Ui};
+use freminal_terminal_emulator::input::{is_large_paste, read_paste_file};
+
+/// Height of the directory listing, in points
+const LISTING_HEIGHT: f32 = 300.0;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Entry {
+    name: String,
+    is_dir: bool,
+}
+
+enum Action {
+    Navigate(PathBuf),
+    Choose(PathBuf),
+    Confirm,
+    Cancel,
+}
+
+/// A small file picker for pasting a file in to the terminal, handy for sending a config to a
+/// device over a serial or SSH session
+///
+/// egui has no native file dialog, so this lists directories itself. Large files are only pasted
+/// once the user has confirmed them.
+#[derive(Debug, Default)]
+pub struct PasteFileDialog {
+    open: bool,
+    directory: PathBuf,
+    entries: Vec<Entry>,
+    /// What the user has typed in to the path box
+    path: String,
+    error: Option<String>,
+    /// A large file that has been read, waiting for the user to confirm it
+    pending: Option<(PathBuf, String)>,
+}
+
+impl PasteFileDialog {
+    #[must_use]
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Show the dialog, starting in the directory freminal was started from
+    pub fn open(&mut self) {
+        let directory = std::env::current_dir()
+            .ok()
+            .or_else(|| std::env::var_os("HOME").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("/"));
+
+        self.open = true;
+        self.path.clear();
+        self.pending = None;
+        self.navigate(directory);
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+        self.pending = None;
+        self.entries.clear();
+    }
+
+    fn navigate(&mut self, directory: PathBuf) {
+        match list_directory(&directory) {
+            Ok(entries) => {
+                self.entries = entries;
+                self.directory = directory;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("Can't open {}: {e}", directory.display())),
+        }
+    }
+
+    /// Draw the dialog if it is open. Returns the text to paste once the user has picked a file
+    pub fn show(&mut self, ctx: &Context) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+
+        let mut open = true;
+        let mut action = None;
+        egui::Window::new("Paste from file")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                action = if self.pending.is_some() {
+                    self.show_confirmation(ui)
+                } else {
+                    self.show_picker(ui)
+                };
+            });
+
+        if !open {
+            self.close();
+            return None;
+        }
+
+        match action? {
+            Action::Navigate(directory) => self.navigate(directory),
+            Action::Choose(path) => match read_paste_file(&path) {
+                Ok(text) if is_large_paste(&text) => self.pending = Some((path, text)),
+                Ok(text) => {
+                    self.close();
+                    return Some(text);
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            },
+            Action::Confirm => {
+                let (_, text) = self.pending.take()?;
+                self.close();
+                return Some(text);
+            }
+            Action::Cancel if self.pending.is_some() => self.pending = None,
+            Action::Cancel => self.close(),
+        }
+
+        None
+    }
+
+    fn show_picker(&mut self, ui: &mut Ui) -> Option<Action> {
+        let mut action = None;
+
+        ui.label(self.directory.display().to_string());
+        ui.separator();
+
+        ScrollArea::vertical()
+            .max_height(LISTING_HEIGHT)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                if let Some(parent) = self.directory.parent() {
+                    if ui.selectable_label(false, "..").clicked() {
+                        action = Some(Action::Navigate(parent.to_path_buf()));
+                    }
+                }
+
+                for entry in &self.entries {
+                    let path = self.directory.join(&entry.name);
+                    if entry.is_dir {
+                        if ui
+                            .selectable_label(false, format!("{}/", entry.name))
+                            .clicked()
+                        {
+                            action = Some(Action::Navigate(path));
+                        }
+                    } else if ui.selectable_label(false, &entry.name).clicked() {
+                        action = Some(Action::Choose(path));
+                    }
+                }
+            });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            let response = ui.text_edit_singleline(&mut self.path);
+            let entered =
+                response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+
+            if (ui.button("Paste").clicked() || entered) && !self.path.is_empty() {
+                action = Some(Action::Choose(self.directory.join(&self.path)));
+            }
+            if ui.button("Cancel").clicked() {
+                action = Some(Action::Cancel);
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        action
+    }
+
+    fn show_confirmation(&self, ui: &mut Ui) -> Option<Action> {
+        let (path, text) = self.pending.as_ref()?;
+        let mut action = None;
+
+        ui.label(format!(
+            "{} is {} KiB. Paste all of it?",
+            path.display(),
+            text.len().div_ceil(1024)
+        ));
+        ui.horizontal(|ui| {
+            if ui.button("Paste").clicked() {
+                action = Some(Action::Confirm);
+            }
+            if ui.button("Cancel").clicked() {
+                action = Some(Action::Cancel);
+            }
+        });
+
+        action
+    }
+}
+
+/// The directories and files in `directory`, directories first, hiding dot files
+fn list_directory(directory: &Path) -> std::io::Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = std::fs::read_dir(directory)?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                return None;
+            }
+
+            let is_dir = entry.path().is_dir();
+            Some(Entry { name, is_dir })
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
@@ -0,0 +1,97 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::Instant;
+
+use eframe::egui::{
+    self, Align2, Context, Event, InputState, Key, KeyboardShortcut, ModifierNames, Modifiers,
+    RichText, Vec2,
+};
+use freminal_common::presentation::KeystrokeLog;
+
+/// Size of the text in the keystroke overlay, in points
+const OVERLAY_FONT_SIZE: f32 = 28.0;
+
+/// What to show in the overlay for a key press, or `None` if it types text, which arrives
+/// separately as a text event
+#[must_use]
+pub fn key_label(key: Key, modifiers: Modifiers) -> Option<String> {
+    let types_text = key == Key::Space || key.name().chars().count() == 1;
+    if types_text && !modifiers.ctrl && !modifiers.alt {
+        return None;
+    }
+
+    let label = match key {
+        Key::Enter => "⏎ Enter",
+        Key::Backspace => "⌫ Backspace",
+        Key::Tab => "⇥ Tab",
+        Key::Escape => "Esc",
+        Key::ArrowUp => "↑",
+        Key::ArrowDown => "↓",
+        Key::ArrowLeft => "←",
+        Key::ArrowRight => "→",
+        _ => key.name(),
+    };
+
+    let modifiers = ModifierNames::NAMES.format(&modifiers, false);
+    if modifiers.is_empty() {
+        Some(label.to_string())
+    } else {
+        Some(format!("{modifiers}+{label}"))
+    }
+}
+
+/// Record the keys typed this frame in `log`. With `hide_text`, as while the terminal isn't
+/// echoing at a password prompt, typed text is left out and only other keys are shown
+pub fn record_keystrokes(input: &InputState, log: &mut KeystrokeLog, hide_text: bool) {
+    let now = Instant::now();
+    for event in &input.raw.events {
+        match event {
+            Event::Text(text) if !hide_text => log.push_text(text, now),
+            Event::Paste(_) => log.push_key("Paste", now),
+            // egui turns Ctrl+C and Ctrl+X in to copy and cut events rather than key presses
+            Event::Copy => log.push_key(&shortcut_label(input.modifiers, Key::C), now),
+            Event::Cut => log.push_key(&shortcut_label(input.modifiers, Key::X), now),
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => {
+                if let Some(label) = key_label(*key, *modifiers) {
+                    log.push_key(&label, now);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn shortcut_label(modifiers: Modifiers, key: Key) -> String {
+    KeyboardShortcut::new(modifiers, key).format(&ModifierNames::NAMES, false)
+}
+
+/// Show the keys in `log` along the bottom of the window, dropping the ones that have been shown
+/// long enough
+pub fn show_keystroke_overlay(ctx: &Context, log: &mut KeystrokeLog) {
+    let Some(next_expiry) = log.expire(Instant::now()) else {
+        return;
+    };
+
+    egui::Area::new(egui::Id::new("freminal_keystrokes"))
+        .anchor(Align2::CENTER_BOTTOM, Vec2::new(0.0, -24.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for label in log.labels() {
+                        ui.label(RichText::new(label).size(OVERLAY_FONT_SIZE).strong());
+                    }
+                });
+            });
+        });
+
+    ctx.request_repaint_after(next_expiry);
+}
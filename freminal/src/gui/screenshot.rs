@@ -0,0 +1,85 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use conv::ConvUtil;
+use eframe::egui::{Color32, ColorImage, Rect};
+
+/// Where we are in taking a screenshot of the terminal
+///
+/// Taking a screenshot takes a few frames. We first need to render a frame without the cursor,
+/// then ask egui for a screenshot of that frame, and finally wait for the screenshot to arrive.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum ScreenshotState {
+    #[default]
+    Idle,
+    /// The next frame should be rendered without the cursor and captured
+    Requested,
+    /// The screenshot has been requested from egui and we are waiting for it
+    Waiting,
+}
+
+/// Build a file name for a new screenshot. Screenshots are stored in the user's home directory,
+/// falling back to the current directory if we can't find it
+#[must_use]
+pub fn screenshot_path() -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let directory = std::env::var_os("HOME").map_or_else(|| PathBuf::from("."), PathBuf::from);
+
+    directory.join(format!("freminal-screenshot-{timestamp}.png"))
+}
+
+/// Crop `image` down to `area` (in points) and save it as a PNG
+///
+/// # Errors
+/// Will error if the image could not be encoded or written
+pub fn save_screenshot(
+    image: &ColorImage,
+    area: Rect,
+    pixels_per_point: f32,
+    path: &Path,
+) -> Result<()> {
+    // Make sure we don't try and crop outside of the screenshot
+    let image_rect = Rect::from_min_size(
+        eframe::egui::Pos2::ZERO,
+        eframe::egui::vec2(
+            image.size[0].approx_as::<f32>().unwrap_or_default(),
+            image.size[1].approx_as::<f32>().unwrap_or_default(),
+        ) / pixels_per_point,
+    );
+    let area = area.intersect(image_rect);
+
+    if !area.is_positive() {
+        return Err(anyhow::anyhow!("Screenshot area {area:?} is empty"));
+    }
+
+    let cropped = image.region(&area, Some(pixels_per_point));
+    let width = u32::try_from(cropped.size[0])?;
+    let height = u32::try_from(cropped.size[1])?;
+    let pixels = cropped
+        .pixels
+        .iter()
+        .flat_map(Color32::to_srgba_unmultiplied)
+        .collect::<Vec<u8>>();
+
+    let Some(output) = image::RgbaImage::from_raw(width, height, pixels) else {
+        return Err(anyhow::anyhow!(
+            "Screenshot buffer does not match size {width}x{height}"
+        ));
+    };
+
+    output.save_with_format(path, image::ImageFormat::Png)?;
+
+    Ok(())
+}
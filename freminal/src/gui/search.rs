@@ -0,0 +1,134 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use eframe::egui::{self, Align2, Context, Key, Modifiers, TextEdit, Vec2};
+
+/// Width of the query box, in points
+const QUERY_WIDTH: f32 = 200.0;
+
+/// What the user asked the search bar to do
+pub enum SearchAction {
+    /// The query changed, so search again
+    Search(String),
+    /// Go to the match further up the scrollback
+    Previous,
+    /// Go to the match further down
+    Next,
+    Close,
+}
+
+/// The box for searching the scrollback, opened with Ctrl+Shift+F
+///
+/// The search runs again as the query is typed. Enter goes to the match above the current one,
+/// since the most recent output is usually at the bottom, and Shift+Enter to the one below.
+#[derive(Debug, Default)]
+pub struct SearchBar {
+    open: bool,
+    query: String,
+    /// Give the query box the keyboard focus next time it is drawn
+    focus: bool,
+}
+
+impl SearchBar {
+    #[must_use]
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    #[must_use]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Show the bar, keeping the last query
+    pub const fn open(&mut self) {
+        self.open = true;
+        self.focus = true;
+    }
+
+    /// Draw the bar if it is open. `position` is which match the search is on and how many
+    /// there are
+    pub fn show(
+        &mut self,
+        ctx: &Context,
+        position: Option<(usize, usize)>,
+    ) -> Option<SearchAction> {
+        if !self.open {
+            return None;
+        }
+
+        let mut action = None;
+        egui::Area::new(egui::Id::new("freminal_search"))
+            .anchor(Align2::RIGHT_TOP, Vec2::new(-8.0, 8.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        action = self.show_contents(ui, position);
+                    });
+                });
+            });
+
+        if matches!(action, Some(SearchAction::Close)) {
+            self.open = false;
+        }
+
+        action
+    }
+
+    fn show_contents(
+        &mut self,
+        ui: &mut egui::Ui,
+        position: Option<(usize, usize)>,
+    ) -> Option<SearchAction> {
+        let mut action = None;
+
+        let response = ui.add(
+            TextEdit::singleline(&mut self.query)
+                .hint_text("Find")
+                .desired_width(QUERY_WIDTH),
+        );
+        if self.focus {
+            response.request_focus();
+            self.focus = false;
+        }
+        if response.changed() {
+            action = Some(SearchAction::Search(self.query.clone()));
+        }
+
+        // Enter takes the focus away from a single line text box, so it is given back to keep
+        // going through the matches
+        if response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+            action = if ui.input(|input| input.modifiers.shift) {
+                Some(SearchAction::Next)
+            } else {
+                Some(SearchAction::Previous)
+            };
+            response.request_focus();
+        }
+
+        if ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Escape)) {
+            action = Some(SearchAction::Close);
+        }
+
+        let label = match position {
+            Some((current, total)) => format!("{current} of {total}"),
+            None if self.query.is_empty() => String::new(),
+            None => "No matches".to_string(),
+        };
+        ui.label(label);
+
+        if ui.button("Previous").clicked() {
+            action = Some(SearchAction::Previous);
+        }
+        if ui.button("Next").clicked() {
+            action = Some(SearchAction::Next);
+        }
+        if ui.button("Close").clicked() {
+            action = Some(SearchAction::Close);
+        }
+
+        action
+    }
+}
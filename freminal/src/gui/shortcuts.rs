@@ -0,0 +1,60 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use eframe::egui::{Event, InputState, Key, Modifiers};
+use freminal_terminal_emulator::input::shortcut_key_matches;
+
+use super::keys::terminal_key;
+
+/// Ctrl with any of these makes the text bigger. Plus is shifted on most layouts
+pub const ZOOM_IN_KEYS: [Key; 2] = [Key::Plus, Key::Equals];
+pub const ZOOM_OUT_KEY: Key = Key::Minus;
+pub const ZOOM_RESET_KEY: Key = Key::Num0;
+
+/// How much each zoom step changes the font size, in points
+pub const ZOOM_STEP: f32 = 1.0;
+
+/// `shortcut_key_matches` for egui's keys
+fn matches_shortcut(wanted: Key, key: Key, physical_key: Option<Key>) -> bool {
+    match (terminal_key(wanted), terminal_key(key)) {
+        (Some(wanted), Some(key)) => {
+            shortcut_key_matches(wanted, key, physical_key.and_then(terminal_key))
+        }
+        _ => wanted == key,
+    }
+}
+
+/// Take a press of `key` with `modifiers` held out of this frame's input, if there was one
+///
+/// Like `InputState::consume_key`, extra Shift and Alt are ignored, but the key is matched with
+/// `shortcut_key_matches` so our shortcuts work on non-US keyboard layouts.
+pub fn consume_shortcut(input: &mut InputState, modifiers: Modifiers, wanted: Key) -> bool {
+    let mut found = false;
+    input.events.retain(|event| {
+        let is_match = matches!(
+            event,
+            Event::Key {
+                key,
+                physical_key,
+                pressed: true,
+                modifiers: event_modifiers,
+                ..
+            } if event_modifiers.matches_logically(modifiers)
+                && matches_shortcut(wanted, *key, *physical_key)
+        );
+        found |= is_match;
+        !is_match
+    });
+
+    found
+}
+
+/// Whether Ctrl+Shift+C was pressed this frame. egui turns it in to a copy event, unless the
+/// layout puts something other than C there, when it arrives as a key press that is taken out of
+/// the input
+pub fn consume_copy_shortcut(input: &mut InputState) -> bool {
+    let copy_event = input.modifiers.shift && input.events.contains(&Event::Copy);
+    consume_shortcut(input, Modifiers::CTRL | Modifiers::SHIFT, Key::C) || copy_event
+}
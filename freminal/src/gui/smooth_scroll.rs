@@ -0,0 +1,52 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::{Duration, Instant};
+
+/// How long it takes the viewport to settle after a scroll
+pub const SMOOTH_SCROLL_DURATION: Duration = Duration::from_millis(100);
+
+/// Eases the terminal output in to place after the viewport moves
+///
+/// The terminal has already moved to its new position by the time we draw. We start by drawing the
+/// new content where the old content was, and slide it the rest of the way over a short time.
+#[derive(Debug, Clone, Default)]
+pub struct SmoothScroll {
+    start_offset: f32,
+    started: Option<Instant>,
+}
+
+impl SmoothScroll {
+    /// Start (or extend) an animation for the viewport moving by `lines`. Positive is up
+    pub fn add_lines(&mut self, lines: isize, line_height: f32) {
+        let lines =
+            f32::from(i16::try_from(lines).unwrap_or(if lines < 0 { i16::MIN } else { i16::MAX }));
+
+        // Carry on from wherever the current animation has got to, so quick wheel flicks
+        // don't snap back
+        self.start_offset = self.offset().unwrap_or(0.0) - lines * line_height;
+        self.started = Some(Instant::now());
+    }
+
+    pub fn stop(&mut self) {
+        self.started = None;
+    }
+
+    /// How far to shift the output vertically right now, or `None` if we aren't animating
+    pub fn offset(&mut self) -> Option<f32> {
+        let started = self.started?;
+        let progress = started.elapsed().as_secs_f32() / SMOOTH_SCROLL_DURATION.as_secs_f32();
+
+        if progress >= 1.0 {
+            self.started = None;
+            return None;
+        }
+
+        // ease out cubic
+        let eased = 1.0 - (1.0 - progress).powi(3);
+
+        Some(self.start_offset * (1.0 - eased))
+    }
+}
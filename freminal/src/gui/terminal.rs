@@ -12,51 +12,115 @@ use crate::gui::{
 };
 
 use freminal_terminal_emulator::{
-    ansi_components::modes::rl_bracket::RlBracket,
+    ansi_components::mode::MouseTrack,
     format_tracker::FormatTag,
-    interface::{collect_text, TerminalInput},
-    io::FreminalTermInputOutput,
-    state::{cursor::CursorPos, fonts::FontDecorations, term_char::TChar},
+    input::{
+        encode_keyboard_protocol, key_to_terminal_input, parse_hex_bytes, paste_to_terminal_input,
+        Key as TerminalKey, KeyboardProtocol,
+    },
+    interface::{collect_text, KeyModifiers, TerminalInput},
+    io::{FreminalTermInputOutput, PtySignal},
+    state::{
+        cursor::CursorPos, data::RenderSlice, fonts::FontDecorations, internal::Hyperlink,
+        term_char::TChar, unsupported::UnsupportedFeatures,
+    },
 };
 
 use eframe::egui::{
-    self, scroll_area::ScrollBarVisibility, text::LayoutJob, Color32, Context, CursorIcon,
-    DragValue, Event, InputState, Key, Modifiers, OpenUrl, PointerButton, Pos2, Rect, Stroke,
-    TextFormat, TextStyle, Ui,
+    self, output::IMEOutput, scroll_area::ScrollBarVisibility, text::LayoutJob, Color32, Context,
+    CursorIcon, DragValue, Event, ImeEvent, InputState, Key, Modifiers, MouseWheelUnit, OpenUrl,
+    PointerButton, Pos2, Rect, Stroke, TextEdit, TextFormat, TextStyle, Ui, UiBuilder, UserData,
+    Vec2, ViewportCommand,
 };
 
 use super::{
     colors::internal_color_to_egui,
     fonts::{get_char_size, setup_font_files, TerminalFont},
+    glyphs::{glyph_shapes, GlyphSettings},
+    keys::{key_modifiers, keypad_key, terminal_key},
+    open_new_window,
+    paste_file::PasteFileDialog,
+    presentation::{record_keystrokes, show_keystroke_overlay},
+    screenshot::{save_screenshot, screenshot_path, ScreenshotState},
+    search::{SearchAction, SearchBar},
+    shortcuts::{
+        consume_copy_shortcut, consume_shortcut, ZOOM_IN_KEYS, ZOOM_OUT_KEY, ZOOM_RESET_KEY,
+        ZOOM_STEP,
+    },
+    smooth_scroll::SmoothScroll,
+    toggle_fullscreen, EguiRedrawNotifier,
 };
 use anyhow::Result;
 use conv::{ConvUtil, ValueFrom};
-use std::borrow::Cow;
-
-fn control_key(key: Key) -> Option<Cow<'static, [TerminalInput]>> {
-    if key >= Key::A && key <= Key::Z {
-        let name = key.name();
-        assert!(name.len() == 1);
-        let name_c = name.as_bytes()[0];
-        return Some(vec![TerminalInput::Ctrl(name_c)].into());
-    } else if key == Key::OpenBracket {
-        return Some([TerminalInput::Ctrl(b'[')].as_ref().into());
-    } else if key == Key::CloseBracket {
-        return Some([TerminalInput::Ctrl(b']')].as_ref().into());
-    } else if key == Key::Backslash {
-        return Some([TerminalInput::Ctrl(b'\\')].as_ref().into());
-    }
+use freminal_common::{
+    cell_geometry::{CellGeometry, CellGrid, SizeLimits, TerminalGeometry},
+    char_width::is_ambiguous_width,
+    colors::{Palette, TerminalColor, THEMES},
+    cursor::{CursorSettings, CursorShape, CURSOR_BLINK_INTERVAL_SECONDS},
+    font::FontSettings,
+    links::LinkOpening,
+    mosaic::{mosaic_quadrants, MosaicRun, MosaicRuns},
+    presentation::{PresentationMode, PresentationSettings},
+    scroll::ViewportMovement,
+    timestamps::{format_time_of_day, time_zone_label, TimestampGutter},
+};
+use std::{
+    borrow::Cow,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
-    None
+/// Rows left free below the terminal output, so the last line is never cut off
+pub const RESERVED_ROWS: usize = 1;
+
+/// How long the output of a failed command is highlighted after jumping to it
+const FAILED_COMMAND_FLASH_DURATION: Duration = Duration::from_secs(1);
+/// How strongly the output of the running command is tinted with the text color
+const RUNNING_COMMAND_TINT: f32 = 0.06;
+/// How much of the row below the output the sideways scroll bar takes up, when the view doesn't
+/// wrap
+const COLUMN_SCROLL_BAR_HEIGHT: f32 = 0.5;
+/// How long the scrollback position is shown after paging through it with the keyboard
+const VIEWPORT_INDICATOR_DURATION: Duration = Duration::from_millis(1500);
+/// Keys that page through the scrollback when held with Shift
+const VIEWPORT_KEYS: [(Key, ViewportMovement); 4] = [
+    (Key::PageUp, ViewportMovement::PageUp),
+    (Key::PageDown, ViewportMovement::PageDown),
+    (Key::Home, ViewportMovement::Top),
+    (Key::End, ViewportMovement::Bottom),
+];
+
+/// Ctrl+Alt with these keys signal the foreground program, even if it has taken over the usual
+/// Ctrl keys
+const SIGNAL_KEYS: [(Key, PtySignal, &str); 4] = [
+    (Key::C, PtySignal::Interrupt, "Interrupt (Ctrl+Alt+C)"),
+    (Key::Z, PtySignal::Suspend, "Suspend (Ctrl+Alt+Z)"),
+    (Key::Backslash, PtySignal::Quit, "Quit (Ctrl+Alt+\\)"),
+    (Key::D, PtySignal::EndOfFile, "End of file (Ctrl+Alt+D)"),
+];
+
+/// What to send for `key`: the sequence the keyboard protocol the application turned on has for
+/// it, or the usual one if that doesn't change the key
+fn key_input(
+    key: TerminalKey,
+    modifiers: KeyModifiers,
+    protocol: KeyboardProtocol,
+) -> Option<Cow<'static, [TerminalInput]>> {
+    encode_keyboard_protocol(key, modifiers, protocol)
+        .map(|bytes| vec![TerminalInput::Sequence(bytes)].into())
+        .or_else(|| key_to_terminal_input(key, modifiers))
 }
 
 #[allow(clippy::cognitive_complexity, clippy::too_many_lines)]
 fn write_input_to_terminal<Io: FreminalTermInputOutput>(
     input: &InputState,
+    numpad_events: &[usize],
     terminal_emulator: &mut TerminalEmulator<Io>,
-    character_size_x: f32,
-    character_size_y: f32,
+    grid: &CellGrid,
+    render_slice: &RenderSlice,
     last_reported_mouse_pos: Option<PreviousMouseState>,
+    pointer_on_scroll_bar: bool,
 ) -> (bool, Option<PreviousMouseState>) {
     if input.raw.events.is_empty() {
         return (false, last_reported_mouse_pos);
@@ -65,117 +129,83 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
     let mut state_changed = false;
     let mut last_reported_mouse_pos = last_reported_mouse_pos;
     let mut left_mouse_button_pressed = false;
+    let protocol = terminal_emulator.internal.keyboard_protocol();
+    // Set when a keyboard protocol sent a key that types something, whose text then isn't sent
+    let mut skip_text = false;
 
-    for event in &input.raw.events {
+    for (index, event) in input.raw.events.iter().enumerate() {
         debug!("event: {:?}", event);
+        let on_keypad = numpad_events.contains(&index);
+        // The keypad's Enter and the text it types are sent the way the keypad mode says
+        let keypad_input = on_keypad
+            .then(|| keypad_key(event))
+            .flatten()
+            .and_then(|key| key_input(key, key_modifiers(input.modifiers), protocol));
         let inputs: Cow<'static, [TerminalInput]> = match event {
-            // FIXME: We don't support separating out numpad vs regular keys
-            // This is an egui issue. See: https://github.com/emilk/egui/issues/3653
-            Event::Text(text) => collect_text(text),
-            Event::Key {
-                key: Key::Enter,
-                pressed: true,
-                ..
-            } => [TerminalInput::Enter].as_ref().into(),
+            Event::Text(_) if skip_text => {
+                skip_text = false;
+                continue;
+            }
+            _ if keypad_input.is_some() => {
+                terminal_emulator
+                    .internal
+                    .input_latency
+                    .lock()
+                    .key_pressed(Instant::now());
+                keypad_input.unwrap_or_default()
+            }
+            // Text an input method composed arrives all at once, when it is done
+            Event::Text(text) | Event::Ime(ImeEvent::Commit(text)) => {
+                terminal_emulator
+                    .internal
+                    .input_latency
+                    .lock()
+                    .key_pressed(Instant::now());
+                collect_text(text)
+            }
             // https://github.com/emilk/egui/issues/3653
             // FIXME: Technically not correct if we were on a mac, but also we are using linux
             // syscalls so we'd have to solve that before this is a problem
-            Event::Copy => [TerminalInput::Ctrl(b'c')].as_ref().into(),
+            // Ctrl+Shift+C copies the selection instead, see `FreminalTerminalWidget::show`
+            Event::Copy if input.modifiers.shift => continue,
+            Event::Copy => {
+                key_input(TerminalKey::Char('c'), KeyModifiers::CTRL, protocol).unwrap_or_default()
+            }
             Event::Key {
                 key,
                 pressed: true,
-                modifiers: Modifiers { ctrl: true, .. },
+                modifiers,
                 ..
             } => {
-                if let Some(inputs) = control_key(*key) {
+                let modifiers = key_modifiers(*modifiers);
+                // What the keypad's keys type is sent from their text
+                let pressed_key = terminal_key(*key)
+                    .filter(|key| !(on_keypad && matches!(key, TerminalKey::Char(_))));
+                let encoded =
+                    pressed_key.and_then(|key| encode_keyboard_protocol(key, modifiers, protocol));
+                skip_text = encoded.is_some() && matches!(pressed_key, Some(TerminalKey::Char(_)));
+                let inputs = encoded
+                    .map(|bytes| vec![TerminalInput::Sequence(bytes)].into())
+                    .or_else(|| pressed_key.and_then(|key| key_to_terminal_input(key, modifiers)));
+                if let Some(inputs) = inputs {
+                    terminal_emulator
+                        .internal
+                        .input_latency
+                        .lock()
+                        .key_pressed(Instant::now());
                     inputs
                 } else {
-                    error!("Unexpected ctrl key: {}", key.name());
+                    if modifiers.ctrl {
+                        error!("Unexpected ctrl key: {}", key.name());
+                    }
                     continue;
                 }
             }
-            Event::Key {
-                key: Key::Backspace,
-                pressed: true,
-                ..
-            } => [TerminalInput::Backspace].as_ref().into(),
-            Event::Key {
-                key: Key::ArrowUp,
-                pressed: true,
-                ..
-            } => [TerminalInput::ArrowUp].as_ref().into(),
-            Event::Key {
-                key: Key::ArrowDown,
-                pressed: true,
-                ..
-            } => [TerminalInput::ArrowDown].as_ref().into(),
-            Event::Key {
-                key: Key::ArrowLeft,
-                pressed: true,
-                ..
-            } => [TerminalInput::ArrowLeft].as_ref().into(),
-            Event::Key {
-                key: Key::ArrowRight,
-                pressed: true,
-                ..
-            } => [TerminalInput::ArrowRight].as_ref().into(),
-            Event::Key {
-                key: Key::Home,
-                pressed: true,
-                ..
-            } => [TerminalInput::Home].as_ref().into(),
-            Event::Key {
-                key: Key::End,
-                pressed: true,
-                ..
-            } => [TerminalInput::End].as_ref().into(),
-            Event::Key {
-                key: Key::Delete,
-                pressed: true,
-                ..
-            } => [TerminalInput::Delete].as_ref().into(),
-            Event::Key {
-                key: Key::Insert,
-                pressed: true,
-                ..
-            } => [TerminalInput::Insert].as_ref().into(),
-            Event::Key {
-                key: Key::PageUp,
-                pressed: true,
-                ..
-            } => [TerminalInput::PageUp].as_ref().into(),
-            Event::Key {
-                key: Key::PageDown,
-                pressed: true,
-                ..
-            } => [TerminalInput::PageDown].as_ref().into(),
-            Event::Key {
-                key: Key::Tab,
-                pressed: true,
-                ..
-            } => [TerminalInput::Tab].as_ref().into(),
-            // log any Event::Key that we don't handle
-            // Event::Key { key, pressed: true, .. } => {
-            //     warn!("Unhandled key event: {:?}", key);
-            //     continue;
-            // }
-            Event::Key {
-                key: Key::Escape,
-                pressed: true,
-                ..
-            } => [TerminalInput::Escape].as_ref().into(),
             Event::Paste(text) => {
-                let bracked_paste_mode = terminal_emulator.internal.modes.bracketed_paste.clone();
-                if bracked_paste_mode == RlBracket::Enabled {
-                    // ESC [ 200 ~, followed by the pasted text, followed by ESC [ 201 ~.
-
-                    collect_text(&format!("\x1b[200~{}{}", text, "\x1b[201~"))
-                } else {
-                    collect_text(text)
-                }
+                paste_to_terminal_input(text, &terminal_emulator.internal.modes.bracketed_paste)
             }
             Event::PointerGone => {
-                terminal_emulator.set_mouse_position(&None);
+                terminal_emulator.set_mouse_position(None);
                 last_reported_mouse_pos = None;
                 continue;
             }
@@ -188,10 +218,26 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
 
                 continue;
             }
+            // The sideways scroll bar has the mouse, so the terminal doesn't get it too
+            Event::PointerMoved(_) | Event::PointerButton { .. } if pointer_on_scroll_bar => {
+                continue;
+            }
             Event::PointerMoved(pos) => {
-                terminal_emulator.set_mouse_position_from_move_event(pos);
-                let (x, y) =
-                    encode_egui_mouse_pos_as_usize(*pos, (character_size_x, character_size_y));
+                terminal_emulator.set_mouse_position(Some((pos.x, pos.y)));
+                let (x, y) = grid.nearest_cell((pos.x, pos.y));
+                if terminal_emulator.internal.is_selecting() {
+                    if let Some(buf_pos) = render_slice.nearest_buffer_offset(&CursorPos { x, y }) {
+                        terminal_emulator.internal.extend_selection(buf_pos);
+                    }
+                    state_changed = true;
+                    continue;
+                }
+
+                // Rows scrolled back in to the scrollback aren't on the screen the application
+                // knows about, so there is nothing to report
+                let Some(y) = terminal_emulator.internal.viewport_row_to_screen_row(y) else {
+                    continue;
+                };
 
                 let position = FreminalMousePosition::new(x, y, pos.x, pos.y);
                 let (previous, current) =
@@ -216,6 +262,7 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
                     &previous,
                     &current,
                     &terminal_emulator.internal.modes.mouse_tracking,
+                    terminal_emulator.internal.modes.mouse_encoding,
                 );
 
                 last_reported_mouse_pos = Some(current);
@@ -234,9 +281,10 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
             } => {
                 state_changed = true;
 
-                let (x, y) =
-                    encode_egui_mouse_pos_as_usize(*pos, (character_size_x, character_size_y));
-                let mouse_pos = FreminalMousePosition::new(x, y, pos.x, pos.y);
+                let (x, y) = grid.nearest_cell((pos.x, pos.y));
+                let screen_row = terminal_emulator.internal.viewport_row_to_screen_row(y);
+                let mouse_pos =
+                    FreminalMousePosition::new(x, screen_row.unwrap_or_default(), pos.x, pos.y);
                 let new_mouse_position =
                     PreviousMouseState::new(*button, *pressed, mouse_pos.clone(), *modifiers);
                 // let previous_mouse_button =
@@ -245,11 +293,20 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
                 //     } else {
                 //         PointerButton::None
                 //     };
-                let response = handle_pointer_button(
-                    *button,
-                    &new_mouse_position,
-                    &terminal_emulator.internal.modes.mouse_tracking,
-                );
+                // With nothing asking for the mouse, or with Shift held, the left button selects
+                // text rather than being reported
+                let selecting = *button == PointerButton::Primary
+                    && (modifiers.shift
+                        || terminal_emulator.internal.modes.mouse_tracking
+                            == MouseTrack::NoTracking);
+                let response = screen_row.filter(|_| !selecting).and_then(|_| {
+                    handle_pointer_button(
+                        *button,
+                        &new_mouse_position,
+                        &terminal_emulator.internal.modes.mouse_tracking,
+                        terminal_emulator.internal.modes.mouse_encoding,
+                    )
+                });
 
                 last_reported_mouse_pos = Some(new_mouse_position.clone());
 
@@ -260,40 +317,105 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
                 if let Some(response) = response {
                     response
                 } else {
+                    let pos = CursorPos { x, y };
+                    if selecting && *pressed {
+                        match render_slice.nearest_buffer_offset(&pos) {
+                            Some(buf_pos) => terminal_emulator.internal.start_selection(buf_pos),
+                            None => terminal_emulator.internal.clear_selection(),
+                        }
+                    } else if selecting {
+                        terminal_emulator.internal.finish_selection();
+                        if input.pointer.button_double_clicked(PointerButton::Primary) {
+                            if let Some(buf_pos) = render_slice.buffer_offset_at(&pos) {
+                                terminal_emulator.internal.select_word(buf_pos);
+                            }
+                        }
+                    }
+
+                    // With nothing asking for the mouse, back and forward jump between commands
+                    if *pressed {
+                        match button {
+                            PointerButton::Extra1 => {
+                                terminal_emulator.internal.scroll_to_previous_command();
+                            }
+                            PointerButton::Extra2 => {
+                                terminal_emulator.internal.scroll_to_next_command();
+                            }
+                            _ => (),
+                        }
+                    }
+
                     continue;
                 }
             }
             Event::MouseWheel {
-                delta, modifiers, ..
+                unit,
+                delta,
+                modifiers,
             } => {
-                // TODO: should we care if we scrolled in the x axis?
-                if delta.y == 0.0 {
+                if delta.y == 0.0 && delta.x == 0.0 {
                     continue;
                 }
 
                 state_changed = true;
 
-                if let Some(last_mouse_position) = &mut last_reported_mouse_pos {
-                    // update the modifiers if necessary
-                    if last_mouse_position.modifiers != *modifiers {
-                        last_mouse_position.modifiers = *modifiers;
-                        *last_mouse_position = last_mouse_position.clone();
-                    }
-                    let response = handle_pointer_scroll(
-                        *delta,
-                        last_mouse_position,
-                        &terminal_emulator.internal.modes.mouse_tracking,
-                    );
+                // When the view doesn't wrap, Shift with the wheel, or a sideways wheel, moves
+                // along the long lines
+                if terminal_emulator.internal.no_wrap_view() && (modifiers.shift || delta.x != 0.0)
+                {
+                    let delta = if delta.x == 0.0 { delta.y } else { delta.x };
+                    let (width, _) = terminal_emulator.get_win_size();
+                    let settings = &terminal_emulator.internal.scroll_settings;
+                    let columns = match unit {
+                        MouseWheelUnit::Line => settings.wheel_ticks_to_lines(delta),
+                        MouseWheelUnit::Point => {
+                            settings.pixels_to_lines(delta, grid.geometry.cell_width)
+                        }
+                        MouseWheelUnit::Page => settings
+                            .apply_direction(delta * width.approx_as::<f32>().unwrap_or(1.0)),
+                    };
+                    // Scrolling up moves back towards the start of the lines, on the left
+                    terminal_emulator.internal.scroll_columns(-columns);
 
-                    if let Some(response) = response {
-                        response
-                    } else {
-                        terminal_emulator.internal.scroll(delta.y);
+                    continue;
+                }
 
-                        continue;
+                let (_, height) = terminal_emulator.get_win_size();
+                let settings = &terminal_emulator.internal.scroll_settings;
+                let lines = match unit {
+                    MouseWheelUnit::Line => settings.wheel_ticks_to_lines(delta.y),
+                    MouseWheelUnit::Point => {
+                        settings.pixels_to_lines(delta.y, grid.geometry.cell_height)
+                    }
+                    MouseWheelUnit::Page => {
+                        settings.apply_direction(delta.y * height.approx_as::<f32>().unwrap_or(1.0))
                     }
+                };
+                let delta = Vec2::new(delta.x, lines);
+
+                // While scrolled back the wheel moves through the scrollback, whatever the
+                // application asked for, so there is always a way back down
+                let scrolled_back = terminal_emulator.internal.scrolled_back_lines() > 0;
+                let response = match &mut last_reported_mouse_pos {
+                    Some(last_mouse_position) if !scrolled_back => {
+                        // update the modifiers if necessary
+                        if last_mouse_position.modifiers != *modifiers {
+                            last_mouse_position.modifiers = *modifiers;
+                        }
+                        handle_pointer_scroll(
+                            delta,
+                            last_mouse_position,
+                            &terminal_emulator.internal.modes.mouse_tracking,
+                            terminal_emulator.internal.modes.mouse_encoding,
+                        )
+                    }
+                    _ => None,
+                };
+
+                if let Some(response) = response {
+                    response
                 } else {
-                    terminal_emulator.internal.scroll(delta.y);
+                    terminal_emulator.internal.scroll(lines);
 
                     continue;
                 }
@@ -303,8 +425,30 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
             }
         };
 
+        // Typing goes to the live screen, so bring it back in to view
+        if matches!(
+            event,
+            Event::Text(_)
+                | Event::Ime(ImeEvent::Commit(_))
+                | Event::Key { .. }
+                | Event::Paste(_)
+                | Event::Copy
+        ) {
+            terminal_emulator.internal.snap_to_bottom();
+        }
+
+        let typed = matches!(
+            event,
+            Event::Text(_) | Event::Ime(ImeEvent::Commit(_)) | Event::Key { .. }
+        );
         for input in inputs.as_ref() {
             state_changed = true;
+            if typed {
+                terminal_emulator.internal.predict_echo(input);
+            } else {
+                terminal_emulator.internal.echo_prediction.clear();
+            }
+
             if let Err(e) = terminal_emulator.write(input) {
                 error!("Failed to write input to terminal emulator: {}", e);
             }
@@ -319,50 +463,28 @@ fn write_input_to_terminal<Io: FreminalTermInputOutput>(
     (left_mouse_button_pressed, last_reported_mouse_pos)
 }
 
-fn encode_egui_mouse_pos_as_usize(pos: Pos2, character_size: (f32, f32)) -> (usize, usize) {
-    let x = ((pos.x / character_size.0).floor())
-        .approx_as::<usize>()
-        .unwrap_or_else(|_| {
-            if pos.x > 0.0 {
-                error!("Failed to convert {} to usize. Using default of 255", pos.x);
-                255
-            } else {
-                error!("Failed to convert {} to usize. Using default of 0", pos.x);
-                0
-            }
-        });
-    let y = ((pos.y / character_size.1).floor())
-        .approx_as::<usize>()
-        .unwrap_or_else(|_| {
-            if pos.x > 0.0 {
-                error!("Failed to convert {} to usize. Using default of 255", pos.y);
-                255
-            } else {
-                error!("Failed to convert {} to usize. Using default of 0", pos.y);
-                0
-            }
-        });
-
-    (x, y)
+/// How the cursor should look this frame
+struct CursorLook {
+    shape: CursorShape,
+    color: Color32,
+    /// Thickness of underline and bar cursors
+    thickness: f32,
+    /// Draw only the outline of the cell, this wide, instead of the shape
+    outline: Option<f32>,
 }
 
-fn paint_cursor(
+/// The area on screen of the cell at `cursor_pos`, a position in the viewport as given by
+/// `TerminalState::viewport_cursor_pos`
+fn cursor_cell_rect(
     label_rect: Rect,
     character_size: (f32, f32),
     cursor_pos: &CursorPos,
-    ui: &Ui,
-    color: Color32,
-) {
-    let painter = ui.painter();
-
-    let top = label_rect.top();
-    let left = label_rect.left();
-
+) -> Option<Rect> {
     let cursor_y = match f32::value_from(cursor_pos.y) {
         Ok(v) => v,
         Err(e) => {
             error!("Failed to convert cursor y ({0}) to f32: {e}", cursor_pos.y);
-            return;
+            return None;
         }
     };
 
@@ -370,26 +492,297 @@ fn paint_cursor(
         Ok(v) => v,
         Err(e) => {
             error!("Failed to convert cursor x ({0}) to f32: {e}", cursor_pos.x);
-            return;
+            return None;
         }
     };
 
     let y_offset: f32 = cursor_y * character_size.1;
     let x_offset: f32 = cursor_x * character_size.0;
-    painter.rect_filled(
-        Rect::from_min_size(
-            egui::pos2(left + x_offset, top + y_offset),
+    Some(Rect::from_min_size(
+        egui::pos2(label_rect.left() + x_offset, label_rect.top() + y_offset),
+        egui::vec2(character_size.0, character_size.1),
+    ))
+}
+
+fn paint_cursor(
+    label_rect: Rect,
+    character_size: (f32, f32),
+    cursor_pos: &CursorPos,
+    ui: &Ui,
+    look: &CursorLook,
+) {
+    let painter = ui.painter();
+
+    let Some(cell) = cursor_cell_rect(label_rect, character_size, cursor_pos) else {
+        return;
+    };
+
+    if let Some(width) = look.outline {
+        // Keep the outline inside the cell so it doesn't cover the neighbouring characters
+        painter.rect_stroke(
+            cell.shrink(width / 2.0),
+            0.0,
+            Stroke::new(width, look.color),
+        );
+        return;
+    }
+
+    let thickness = look.thickness.min(character_size.0).min(character_size.1);
+    let rect = match look.shape {
+        CursorShape::Block => cell,
+        CursorShape::Underline => {
+            Rect::from_min_max(egui::pos2(cell.left(), cell.bottom() - thickness), cell.max)
+        }
+        CursorShape::VerticalLine => {
+            Rect::from_min_max(cell.min, egui::pos2(cell.left() + thickness, cell.bottom()))
+        }
+    };
+
+    painter.rect_filled(rect, 0.0, look.color);
+}
+
+/// Draw the characters we expect the shell to echo, underlined and faded so they can be told
+/// apart from real output, starting at the cursor. Returns where the cursor goes after them
+fn paint_predicted_echo(
+    label_rect: Rect,
+    character_size: (f32, f32),
+    cursor_pos: &CursorPos,
+    columns: usize,
+    text: &str,
+    ui: &Ui,
+    font_size: f32,
+) -> CursorPos {
+    let painter = ui.painter();
+    let visuals = ui.visuals();
+    let color = visuals.text_color().gamma_multiply(0.6);
+    let mut x = cursor_pos.x;
+
+    // Whatever doesn't fit on the line is left for the real echo
+    for c in text.chars() {
+        if x >= columns {
+            break;
+        }
+
+        let left = x.approx_as::<f32>().unwrap_or_default();
+        let top = cursor_pos.y.approx_as::<f32>().unwrap_or_default();
+        let cell = Rect::from_min_size(
+            egui::pos2(
+                left.mul_add(character_size.0, label_rect.left()),
+                top.mul_add(character_size.1, label_rect.top()),
+            ),
             egui::vec2(character_size.0, character_size.1),
+        );
+
+        painter.rect_filled(cell, 0.0, visuals.panel_fill);
+        painter.text(
+            cell.left_top(),
+            egui::Align2::LEFT_TOP,
+            c,
+            egui::FontId::monospace(font_size),
+            color,
+        );
+        painter.hline(cell.x_range(), cell.bottom() - 1.0, Stroke::new(1.0, color));
+        x += 1;
+    }
+
+    CursorPos {
+        x: x.min(columns.saturating_sub(1)),
+        ..cursor_pos.clone()
+    }
+}
+
+/// Whether a blinking cursor is in the on part of its blink. Schedules a repaint for when it next
+/// changes
+fn cursor_blink_visible(ui: &Ui, settings: &CursorSettings, blinking: bool) -> bool {
+    if !settings.blink || !blinking {
+        return true;
+    }
+
+    let time = ui.input(|i| i.time);
+    let until_change =
+        CURSOR_BLINK_INTERVAL_SECONDS - time.rem_euclid(CURSOR_BLINK_INTERVAL_SECONDS);
+    ui.ctx()
+        .request_repaint_after(Duration::from_secs_f64(until_change));
+
+    settings.is_visible_at(blinking, time)
+}
+
+/// Show `text` in the top right corner of the terminal
+fn paint_viewport_indicator(label_rect: Rect, ui: &Ui, text: &str) {
+    let painter = ui.painter();
+    let visuals = &ui.style().visuals;
+    let galley = painter.layout_no_wrap(
+        text.to_string(),
+        TextStyle::Body.resolve(ui.style()),
+        visuals.strong_text_color(),
+    );
+
+    let margin = Vec2::splat(4.0);
+    let rect = egui::Align2::RIGHT_TOP.anchor_size(
+        label_rect.right_top() + egui::vec2(-margin.x, margin.y),
+        galley.size() + margin * 2.0,
+    );
+
+    painter.rect_filled(rect, 4.0, visuals.extreme_bg_color.gamma_multiply(0.9));
+    painter.galley(rect.min + margin, galley, visuals.strong_text_color());
+}
+
+/// Paint the time each row was written at, starting at `left`
+fn paint_timestamp_gutter(
+    label_rect: Rect,
+    left: f32,
+    character_size: (f32, f32),
+    timestamps: &[Option<SystemTime>],
+    ui: &Ui,
+    font_size: f32,
+) {
+    let painter = ui.painter();
+    let color = ui.style().visuals.weak_text_color();
+
+    for (row, timestamp) in timestamps.iter().enumerate() {
+        let Some(timestamp) = timestamp else {
+            continue;
+        };
+
+        let top = row.approx_as::<f32>().unwrap_or_else(|e| {
+            error!("Failed to convert timestamp row ({row}) to f32: {e}");
+            0.0
+        });
+        painter.text(
+            egui::pos2(
+                left + character_size.0,
+                top.mul_add(character_size.1, label_rect.top()),
+            ),
+            egui::Align2::LEFT_TOP,
+            format_time_of_day(*timestamp),
+            egui::FontId::monospace(font_size),
+            color,
+        );
+    }
+}
+
+/// Show when the row under the mouse was written, at the right of the row
+fn paint_hover_timestamp(
+    label_rect: Rect,
+    character_size: (f32, f32),
+    row: usize,
+    timestamp: SystemTime,
+    ui: &Ui,
+) {
+    let painter = ui.painter();
+    let visuals = &ui.style().visuals;
+    let mut time = format_time_of_day(timestamp);
+    if let Some(zone) = time_zone_label() {
+        time.push(' ');
+        time.push_str(zone);
+    }
+    let galley = painter.layout_no_wrap(
+        time,
+        TextStyle::Body.resolve(ui.style()),
+        visuals.strong_text_color(),
+    );
+
+    let row = row.approx_as::<f32>().unwrap_or_else(|e| {
+        error!("Failed to convert hovered row ({row}) to f32: {e}");
+        0.0
+    });
+    let margin = Vec2::new(4.0, 1.0);
+    let rect = egui::Align2::RIGHT_TOP.anchor_size(
+        egui::pos2(
+            label_rect.right() - margin.x,
+            row.mul_add(character_size.1, label_rect.top()),
+        ),
+        galley.size() + margin * 2.0,
+    );
+
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color.gamma_multiply(0.9));
+    painter.galley(rect.min + margin, galley, visuals.strong_text_color());
+}
+
+fn paint_row_highlight(
+    label_rect: Rect,
+    character_size: (f32, f32),
+    rows: &Range<usize>,
+    ui: &Ui,
+    color: Color32,
+) {
+    let start = f32::value_from(rows.start).unwrap_or_else(|e| {
+        error!(
+            "Failed to convert highlight start ({0}) to f32: {e}",
+            rows.start
+        );
+        0.0
+    });
+    let end = f32::value_from(rows.end).unwrap_or_else(|e| {
+        error!(
+            "Failed to convert highlight end ({0}) to f32: {e}",
+            rows.end
+        );
+        0.0
+    });
+
+    ui.painter().rect_filled(
+        Rect::from_min_max(
+            egui::pos2(
+                label_rect.left(),
+                start.mul_add(character_size.1, label_rect.top()),
+            ),
+            egui::pos2(
+                label_rect.right(),
+                end.mul_add(character_size.1, label_rect.top()),
+            ),
         ),
         0.0,
         color,
     );
 }
 
-fn setup_bg_fill(ctx: &egui::Context) {
+/// Shade the selected cells, given as a range of columns on each row
+fn paint_selection(
+    label_rect: Rect,
+    character_size: (f32, f32),
+    cells: &[(usize, Range<usize>)],
+    ui: &Ui,
+    color: Color32,
+) {
+    for (row, columns) in cells {
+        let (Ok(row), Ok(start), Ok(end)) = (
+            f32::value_from(*row),
+            f32::value_from(columns.start),
+            f32::value_from(columns.end),
+        ) else {
+            error!("Failed to convert selection on row {row} ({columns:?}) to f32");
+            continue;
+        };
+
+        ui.painter().rect_filled(
+            Rect::from_min_max(
+                egui::pos2(
+                    start.mul_add(character_size.0, label_rect.left()),
+                    row.mul_add(character_size.1, label_rect.top()),
+                ),
+                egui::pos2(
+                    end.mul_add(character_size.0, label_rect.left()),
+                    (row + 1.0).mul_add(character_size.1, label_rect.top()),
+                ),
+            ),
+            0.0,
+            color,
+        );
+    }
+}
+
+/// The color behind the terminal, from the theme if it has one
+fn background_fill(palette: &Palette) -> Color32 {
+    palette
+        .background()
+        .map_or(Color32::BLACK, |(r, g, b)| Color32::from_rgb(r, g, b))
+}
+
+fn setup_bg_fill(ctx: &egui::Context, fill: Color32) {
     ctx.style_mut(|style| {
-        style.visuals.window_fill = egui::Color32::BLACK;
-        style.visuals.panel_fill = egui::Color32::BLACK;
+        style.visuals.window_fill = fill;
+        style.visuals.panel_fill = fill;
     });
 }
 
@@ -404,6 +797,7 @@ fn create_terminal_output_layout_job(
 
     // Convert data into an array of bytes
     let mut data_converted = Vec::with_capacity(data.len());
+    let mut previous_ambiguous = false;
     for c in data {
         let offset_amount = match c {
             TChar::NewLine => {
@@ -418,11 +812,21 @@ fn create_terminal_output_layout_job(
                 data_converted.push(*c);
                 1
             }
-            TChar::Utf8(all) => {
-                data_converted.extend_from_slice(all);
-                all.len()
+            TChar::Utf8(_) => {
+                // Zero width characters are kept in the buffer but not drawn
+                let text = c.visible_text();
+                data_converted.extend_from_slice(text.as_bytes());
+                text.len()
             }
+            // The double width character before it covers this cell, unless it is an ambiguous
+            // width character that the font draws narrow
+            TChar::WideContinuation if previous_ambiguous => {
+                data_converted.push(b' ');
+                1
+            }
+            TChar::WideContinuation => 0,
         };
+        previous_ambiguous = matches!(c, TChar::Utf8(_)) && is_ambiguous_width(c.to_char());
 
         offset.push(data_converted.len() - offset_amount);
     }
@@ -522,58 +926,339 @@ fn validate_tags_to_buffer(buffer: &[u8], tags: &[FormatTag]) -> Result<()> {
     Ok(())
 }
 
-#[derive(Default, Clone, Debug)]
-pub struct UiJobAction {
-    text: String,
-    adjusted_format_data: Vec<FormatTag>,
-}
-
-#[derive(Debug)]
-pub struct NewJobAction<'a> {
-    text: &'a [TChar],
-    format_data: Vec<FormatTag>,
+/// Cells next to each other on a row that share a background color
+#[derive(Clone, Debug, PartialEq)]
+struct BackgroundRun {
+    row: usize,
+    column: usize,
+    columns: usize,
+    color: Color32,
 }
 
-#[derive(Debug)]
-pub enum UiData<'a> {
-    NewPass(&'a NewJobAction<'a>),
-    PreviousPass(UiJobAction),
+/// A character we draw ourselves rather than leaving to the font
+#[derive(Clone, Debug, PartialEq)]
+struct DrawnGlyph {
+    row: usize,
+    column: usize,
+    columns: usize,
+    c: char,
+    color: Color32,
 }
 
-fn setup_job(ui: &Ui, data_utf8: &str) -> (egui::text::LayoutJob, egui::TextFormat) {
-    let width = ui.available_width();
-    let style = ui.style();
-    let text_style = &style.text_styles[&TextStyle::Monospace];
+/// The row and column of each of `data`, laid out in rows `columns` wide with long lines wrapping
+/// on to the next row. New lines don't take up a cell
+fn cell_positions(data: &[TChar], columns: usize) -> Vec<Option<(usize, usize)>> {
+    let columns = columns.max(1);
+    let mut cells = Vec::with_capacity(data.len());
+    let (mut row, mut column) = (0, 0);
+    for c in data {
+        if *c == TChar::NewLine {
+            cells.push(None);
+            row += 1;
+            column = 0;
+            continue;
+        }
 
-    let mut job = egui::text::LayoutJob::simple(
-        data_utf8.to_string(),
-        text_style.clone(),
-        style.visuals.text_color(),
-        width,
-    );
-    job.wrap.break_anywhere = true;
-    let textformat = job.sections[0].format.clone();
-    job.sections.clear();
+        if column == columns {
+            row += 1;
+            column = 0;
+        }
+        cells.push(Some((row, column)));
+        column += 1;
+    }
 
-    (job, textformat)
+    cells
 }
 
-fn process_tags(
-    adjusted_format_data: &Vec<FormatTag>,
+/// The foreground, or with `background` the background, color `tag` gives its characters.
+/// Transparent if it is the default background
+fn tag_color(
+    tag: &FormatTag,
+    clamp_colors_to_palette: bool,
+    palette: &Palette,
+    default_color: Color32,
+    background: bool,
+) -> Color32 {
+    let mut colors = tag.colors.clone();
+    if clamp_colors_to_palette {
+        colors.clamp_to_palette();
+    }
+
+    let color = if background {
+        colors.get_background_color()
+    } else {
+        colors.get_color()
+    };
+
+    // The panel behind the terminal is already the default background
+    if background && color == TerminalColor::DefaultBackground {
+        return Color32::TRANSPARENT;
+    }
+
+    internal_color_to_egui(
+        default_color,
+        Color32::TRANSPARENT,
+        palette,
+        color,
+        tag.font_decorations.contains(&FontDecorations::Faint),
+    )
+}
+
+/// Work out which cells have a background color other than the default
+fn background_runs(
+    cells: &[Option<(usize, usize)>],
+    format_data: &[FormatTag],
+    clamp_colors_to_palette: bool,
+    palette: &Palette,
+    default_color: Color32,
+) -> Vec<BackgroundRun> {
+    let mut runs: Vec<BackgroundRun> = Vec::new();
+    for tag in format_data {
+        let color = tag_color(tag, clamp_colors_to_palette, palette, default_color, true);
+        if color == Color32::TRANSPARENT {
+            continue;
+        }
+
+        let end = tag.end.min(cells.len());
+        for (row, column) in cells[tag.start.min(end)..end].iter().flatten() {
+            match runs.last_mut() {
+                Some(run)
+                    if run.row == *row
+                        && run.column + run.columns == *column
+                        && run.color == color =>
+                {
+                    run.columns += 1;
+                }
+                _ => runs.push(BackgroundRun {
+                    row: *row,
+                    column: *column,
+                    columns: 1,
+                    color,
+                }),
+            }
+        }
+    }
+
+    runs
+}
+
+/// Find the characters in `data` that we draw ourselves, and blank them out so the font doesn't
+/// draw them too
+///
+/// Half blocks and quadrant blocks are joined in to mosaic runs rather than drawn one glyph at a
+/// time, as image viewers fill whole screens with them.
+fn take_drawn_glyphs(
+    data: &mut Cow<'_, [TChar]>,
+    cells: &[Option<(usize, usize)>],
+    format_data: &[FormatTag],
+    glyph_settings: GlyphSettings,
+    clamp_colors_to_palette: bool,
+    palette: &Palette,
+    default_color: Color32,
+) -> (Vec<DrawnGlyph>, Vec<MosaicRun<Color32>>) {
+    let mut glyphs = Vec::new();
+    let mut mosaics = MosaicRuns::default();
+    for tag in format_data {
+        let mut color = None;
+        let end = tag.end.min(data.len());
+        for i in tag.start.min(end)..end {
+            let c = data[i].to_char();
+            if !matches!(data[i], TChar::Utf8(_)) || !glyph_settings.draws(c) {
+                continue;
+            }
+
+            let Some((row, column)) = cells[i] else {
+                continue;
+            };
+
+            // Ambiguous width characters, like box drawing, can be given two cells
+            let columns = if data.get(i + 1) == Some(&TChar::WideContinuation) {
+                2
+            } else {
+                1
+            };
+
+            data.to_mut()[i] = TChar::Space;
+            let color = *color.get_or_insert_with(|| {
+                tag_color(tag, clamp_colors_to_palette, palette, default_color, false)
+            });
+            if let Some(quadrants) = mosaic_quadrants(c) {
+                mosaics.push(row, column, columns, quadrants, &color);
+            } else {
+                glyphs.push(DrawnGlyph {
+                    row,
+                    column,
+                    columns,
+                    c,
+                    color,
+                });
+            }
+        }
+    }
+
+    (glyphs, mosaics.into_runs())
+}
+
+/// Whether text formatted by `tag` draws anything over spaces
+fn tag_draws_lines(tag: &FormatTag) -> bool {
+    tag.font_decorations.contains(&FontDecorations::Underline)
+        || tag
+            .font_decorations
+            .contains(&FontDecorations::Strikethrough)
+}
+
+/// Fold tags that only cover spaces and new lines in to the tag before them, when neither draws
+/// lines over the text
+///
+/// Nothing is drawn for those characters whatever their format, and every tag is its own section
+/// of text to lay out. A picture drawn in colored blocks has a tag per cell, which once the blocks
+/// are blanked out would be thousands of sections of spaces.
+fn merge_blank_tags(data: &[TChar], format_data: Vec<FormatTag>) -> Vec<FormatTag> {
+    let mut merged: Vec<FormatTag> = Vec::with_capacity(format_data.len());
+    for tag in format_data {
+        if let Some(previous) = merged.last_mut() {
+            let end = tag.end.min(data.len());
+            let blank = data[tag.start.min(end)..end]
+                .iter()
+                .all(|c| matches!(c, TChar::Space | TChar::NewLine));
+            if blank
+                && previous.end == tag.start
+                && !tag_draws_lines(&tag)
+                && !tag_draws_lines(previous)
+            {
+                previous.end = tag.end;
+                continue;
+            }
+        }
+
+        merged.push(tag);
+    }
+
+    merged
+}
+
+/// Fill the cells of each background run edge to edge, so blocks of color and powerline prompts
+/// look solid, then draw the mosaic runs and the glyphs we draw ourselves over them
+fn paint_cells(
+    painter: &egui::Painter,
+    shape: egui::layers::ShapeIdx,
+    origin: Pos2,
+    geometry: &CellGeometry,
+    canvas: &UiJobAction,
+) {
+    let cell_rect = |column: usize, row: usize, columns: usize| {
+        let (left, top, right, bottom) =
+            geometry.cell_span_edges((origin.x, origin.y), column, row, columns);
+        Rect::from_min_max(egui::pos2(left, top), egui::pos2(right, bottom))
+    };
+
+    let backgrounds = canvas.backgrounds.iter().map(|run| {
+        egui::Shape::rect_filled(cell_rect(run.column, run.row, run.columns), 0.0, run.color)
+    });
+    // Half cells split at the middle of the cell, rounded to a whole pixel as the block elements
+    // `glyph_shapes` draws are
+    let half_column_x = |row: usize, half_column: usize| {
+        let (left, _, right, _) =
+            geometry.cell_span_edges((origin.x, origin.y), half_column / 2, row, 1);
+        if half_column % 2 == 0 {
+            left
+        } else {
+            geometry.snap_to_pixel(left + (right - left) / 2.0)
+        }
+    };
+    let mosaics = canvas.mosaics.iter().map(|run| {
+        let (_, top, _, bottom) = geometry.cell_span_edges((origin.x, origin.y), 0, run.row, 1);
+        let middle = geometry.snap_to_pixel(top + (bottom - top) / 2.0);
+        let (top, bottom) = if run.lower {
+            (middle, bottom)
+        } else {
+            (top, middle)
+        };
+        let rect = Rect::from_min_max(
+            egui::pos2(half_column_x(run.row, run.half_column), top),
+            egui::pos2(
+                half_column_x(run.row, run.half_column + run.half_columns),
+                bottom,
+            ),
+        );
+        egui::Shape::rect_filled(rect, 0.0, run.color)
+    });
+    let glyphs = canvas.glyphs.iter().flat_map(|glyph| {
+        glyph_shapes(
+            glyph.c,
+            cell_rect(glyph.column, glyph.row, glyph.columns),
+            glyph.color,
+            geometry,
+        )
+    });
+
+    painter.set(
+        shape,
+        egui::Shape::Vec(backgrounds.chain(mosaics).chain(glyphs).collect()),
+    );
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct UiJobAction {
+    text: String,
+    adjusted_format_data: Vec<FormatTag>,
+    backgrounds: Vec<BackgroundRun>,
+    glyphs: Vec<DrawnGlyph>,
+    mosaics: Vec<MosaicRun<Color32>>,
+}
+
+#[derive(Debug)]
+pub struct NewJobAction<'a> {
+    text: &'a [TChar],
+    format_data: Vec<FormatTag>,
+    clamp_colors_to_palette: bool,
+    /// Width of the terminal, where lines wrap
+    columns: usize,
+    /// Characters to draw ourselves, so they sit flush against the cells either side
+    glyph_settings: GlyphSettings,
+}
+
+#[derive(Debug)]
+pub enum UiData<'a> {
+    NewPass(&'a NewJobAction<'a>),
+    PreviousPass(UiJobAction),
+}
+
+fn setup_job(ui: &Ui, data_utf8: &str) -> (egui::text::LayoutJob, egui::TextFormat) {
+    let width = ui.available_width();
+    let style = ui.style();
+    let text_style = &style.text_styles[&TextStyle::Monospace];
+
+    let mut job = egui::text::LayoutJob::simple(
+        data_utf8.to_string(),
+        text_style.clone(),
+        style.visuals.text_color(),
+        width,
+    );
+    job.wrap.break_anywhere = true;
+    let textformat = job.sections[0].format.clone();
+    job.sections.clear();
+
+    (job, textformat)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_tags(
+    adjusted_format_data: &Vec<FormatTag>,
     data_len: usize,
     textformat: &mut TextFormat,
+    palette: &Palette,
     font_size: f32,
+    terminal_fonts: &TerminalFont,
     job: &mut LayoutJob,
     #[cfg(feature = "validation")] buffer: &[u8],
 ) {
     let default_color = textformat.color;
     let default_background = textformat.background;
-    let terminal_fonts = TerminalFont::new();
 
     for tag in adjusted_format_data {
         let mut range = tag.start..tag.end;
         let color = tag.colors.get_color();
-        let background_color = tag.colors.get_background_color();
         let underline_color = tag.colors.get_underline_color();
 
         if range.end == usize::MAX {
@@ -601,20 +1286,24 @@ fn process_tags(
         textformat.font_id.family =
             terminal_fonts.get_family(&tag.font_decorations, &tag.font_weight);
         textformat.font_id.size = font_size;
+        // egui shears the glyphs when this is set
+        textformat.italics =
+            terminal_fonts.needs_synthetic_italic(&tag.font_decorations, &tag.font_weight);
         let make_faint = tag.font_decorations.contains(&FontDecorations::Faint);
-        textformat.color =
-            internal_color_to_egui(default_color, default_background, color, make_faint);
-        // FIXME: ????? should background be faint? I feel like no, but....
-        textformat.background = internal_color_to_egui(
+        textformat.color = internal_color_to_egui(
+            default_color,
             default_background,
-            default_background,
-            background_color,
+            palette,
+            color,
             make_faint,
         );
+        // Backgrounds are painted a cell at a time by `paint_cell_backgrounds`. egui only fills
+        // behind the glyphs, which leaves gaps between lines
         if tag.font_decorations.contains(&FontDecorations::Underline) {
             let underline_color_converted = internal_color_to_egui(
                 textformat.color,
                 default_background,
+                palette,
                 underline_color,
                 make_faint,
             );
@@ -652,60 +1341,101 @@ fn add_terminal_data_to_ui(
     ui: &mut Ui,
     data: &UiData,
     font_size: f32,
+    geometry: &CellGeometry,
+    palette: &Palette,
 ) -> Result<(egui::Response, Option<UiJobAction>)> {
-    let data_utf8: String;
-    let adjusted_format_data: Vec<FormatTag>;
-    let data_len: usize;
-
-    match data {
+    let canvas = match data {
         UiData::NewPass(data) => {
-            let (data_utf8_new, adjusted_format_data_new) =
-                create_terminal_output_layout_job(data.text, &data.format_data)?;
-            data_len = data_utf8_new.len();
-            data_utf8 = data_utf8_new;
-            adjusted_format_data = adjusted_format_data_new;
-        }
-        UiData::PreviousPass(data) => {
-            data_utf8 = data.text.clone();
-            adjusted_format_data = data.adjusted_format_data.clone();
-            data_len = data_utf8.len();
+            let default_color = ui.style().visuals.text_color();
+            let cells = cell_positions(data.text, data.columns);
+            let backgrounds = background_runs(
+                &cells,
+                &data.format_data,
+                data.clamp_colors_to_palette,
+                palette,
+                default_color,
+            );
+
+            let mut text = Cow::Borrowed(data.text);
+            let (glyphs, mosaics) = take_drawn_glyphs(
+                &mut text,
+                &cells,
+                &data.format_data,
+                data.glyph_settings,
+                data.clamp_colors_to_palette,
+                palette,
+                default_color,
+            );
+
+            let format_data = merge_blank_tags(&text, data.format_data.clone());
+            let (text, mut adjusted_format_data) =
+                create_terminal_output_layout_job(&text, &format_data)?;
+            if data.clamp_colors_to_palette {
+                for tag in &mut adjusted_format_data {
+                    tag.colors.clamp_to_palette();
+                }
+            }
+
+            UiJobAction {
+                text,
+                adjusted_format_data,
+                backgrounds,
+                glyphs,
+                mosaics,
+            }
         }
-    }
+        UiData::PreviousPass(data) => data.clone(),
+    };
 
-    let (mut job, mut textformat) = setup_job(ui, &data_utf8);
+    let (mut job, mut textformat) = setup_job(ui, &canvas.text);
+    let terminal_fonts =
+        TerminalFont::with_available_families(&ui.fonts(egui::epaint::Fonts::families));
     process_tags(
-        &adjusted_format_data,
-        data_len,
+        &canvas.adjusted_format_data,
+        canvas.text.len(),
         &mut textformat,
+        palette,
         font_size,
+        &terminal_fonts,
         &mut job,
         #[cfg(feature = "validation")]
-        data_utf8.as_bytes(),
+        canvas.text.as_bytes(),
     );
 
+    // The backgrounds go underneath the text, but where the text ends up is only known once it
+    // has been added
+    let cells_shape = ui.painter().add(egui::Shape::Noop);
+    let label = ui.label(job);
+    paint_cells(ui.painter(), cells_shape, label.rect.min, geometry, &canvas);
+
     match data {
-        UiData::NewPass(_) => {
-            let response = UiJobAction {
-                text: data_utf8,
-                adjusted_format_data,
-            };
-            Ok((ui.label(job), Some(response)))
-        }
-        UiData::PreviousPass(_) => Ok((ui.label(job), None)),
+        UiData::NewPass(_) => Ok((label, Some(canvas))),
+        UiData::PreviousPass(_) => Ok((label, None)),
     }
 }
 
+/// How to draw the terminal output
+struct CanvasSettings {
+    font_size: f32,
+    geometry: CellGeometry,
+    glyph_settings: GlyphSettings,
+    palette: Palette,
+}
+
 #[derive(Clone)]
 struct TerminalOutputRenderResponse {
     canvas_area: Rect,
     canvas: UiJobAction,
+    /// What was drawn, for finding what is under the mouse and where the selection is
+    render_slice: Arc<RenderSlice>,
 }
 
 fn render_terminal_output<Io: FreminalTermInputOutput>(
     ui: &mut egui::Ui,
     terminal_emulator: &mut TerminalEmulator<Io>,
-    font_size: f32,
+    settings: &CanvasSettings,
     previous_pass: Option<&TerminalOutputRenderResponse>,
+    y_offset: Option<f32>,
 ) -> TerminalOutputRenderResponse {
     let response = egui::ScrollArea::new([false, true])
         .auto_shrink([false, false])
@@ -716,65 +1446,89 @@ fn render_terminal_output<Io: FreminalTermInputOutput>(
         .show(ui, |ui| {
             ui.style_mut().interaction.selectable_labels = false;
 
-            let error_logged_rect =
-                |response: Result<(egui::Response, Option<UiJobAction>)>| match response {
-                    Ok((v, action)) => (v.rect, action),
-                    Err(e) => {
-                        error!("failed to add terminal data to ui: {}", e);
-                        (Rect::NOTHING, None)
-                    }
-                };
-
-            let canvas_response: (Rect, Option<UiJobAction>);
+            let Some(y_offset) = y_offset else {
+                return render_terminal_canvas(ui, terminal_emulator, settings, previous_pass);
+            };
 
-            if let Some(previous_pass) = previous_pass {
-                _ = error_logged_rect(add_terminal_data_to_ui(
-                    ui,
-                    &UiData::PreviousPass(previous_pass.canvas.clone()),
-                    font_size,
-                ));
+            // Draw the output shifted while a scroll is animating. The scroll area clips it
+            let offset = Vec2::new(0.0, y_offset);
+            let mut response = ui
+                .scope_builder(
+                    UiBuilder::new().max_rect(ui.max_rect().translate(offset)),
+                    |ui| render_terminal_canvas(ui, terminal_emulator, settings, previous_pass),
+                )
+                .inner;
 
-                (*previous_pass).clone()
-            } else {
-                let (terminal_data, format_data) = terminal_emulator.data_and_format_data_for_gui();
-                if !terminal_data.scrollback.is_empty() {
-                    error!(
-                        "Scrollback is not empty: {}",
-                        terminal_data.scrollback.len()
-                    );
-                }
+            // Everything else works off where the output will end up, not where it is drawn now
+            if previous_pass.is_none() {
+                response.canvas_area = response.canvas_area.translate(-offset);
+            }
 
-                let mut canvas_data = terminal_data.visible;
+            response
+        });
 
-                if canvas_data.ends_with(&[TChar::NewLine]) {
-                    canvas_data = canvas_data[0..canvas_data.len() - 1].to_vec();
-                }
-                canvas_response = error_logged_rect(add_terminal_data_to_ui(
-                    ui,
-                    &UiData::NewPass(&NewJobAction {
-                        text: &canvas_data,
-                        format_data: format_data.visible,
-                    }),
-                    font_size,
-                ));
+    response.inner
+}
 
-                // We want the program to crash here if we're testing
-                #[cfg(feature = "validation")]
-                return TerminalOutputRenderResponse {
-                    canvas_area: canvas_response.0,
-                    #[allow(clippy::unwrap_used)]
-                    canvas: canvas_response.1.unwrap(),
-                };
+fn render_terminal_canvas<Io: FreminalTermInputOutput>(
+    ui: &mut egui::Ui,
+    terminal_emulator: &mut TerminalEmulator<Io>,
+    settings: &CanvasSettings,
+    previous_pass: Option<&TerminalOutputRenderResponse>,
+) -> TerminalOutputRenderResponse {
+    let error_logged_rect = |response: Result<(egui::Response, Option<UiJobAction>)>| match response
+    {
+        Ok((v, action)) => (v.rect, action),
+        Err(e) => {
+            error!("failed to add terminal data to ui: {}", e);
+            (Rect::NOTHING, None)
+        }
+    };
 
-                #[cfg(not(any(feature = "validation")))]
-                return TerminalOutputRenderResponse {
-                    canvas_area: canvas_response.0,
-                    canvas: canvas_response.1.unwrap_or_default(),
-                };
-            }
-        });
+    let canvas_response: (Rect, Option<UiJobAction>);
+
+    if let Some(previous_pass) = previous_pass {
+        _ = error_logged_rect(add_terminal_data_to_ui(
+            ui,
+            &UiData::PreviousPass(previous_pass.canvas.clone()),
+            settings.font_size,
+            &settings.geometry,
+            &settings.palette,
+        ));
+
+        (*previous_pass).clone()
+    } else {
+        let render_slice = Arc::new(terminal_emulator.render_slice());
+        canvas_response = error_logged_rect(add_terminal_data_to_ui(
+            ui,
+            &UiData::NewPass(&NewJobAction {
+                text: &render_slice.text,
+                format_data: render_slice.format_data.clone(),
+                clamp_colors_to_palette: terminal_emulator.internal.clamp_colors_to_palette,
+                columns: terminal_emulator.get_win_size().0,
+                glyph_settings: settings.glyph_settings,
+            }),
+            settings.font_size,
+            &settings.geometry,
+            &settings.palette,
+        ));
+
+        // We want the program to crash here if we're testing
+        #[cfg(feature = "validation")]
+        return TerminalOutputRenderResponse {
+            canvas_area: canvas_response.0,
+            #[allow(clippy::unwrap_used)]
+            canvas: canvas_response.1.unwrap(),
+            render_slice,
+        };
 
-    response.inner
+        #[cfg(not(any(feature = "validation")))]
+        return TerminalOutputRenderResponse {
+            canvas_area: canvas_response.0,
+            canvas: canvas_response.1.unwrap_or_default(),
+            render_slice,
+        };
+    }
 }
 
 struct DebugRenderer {
@@ -796,67 +1550,297 @@ impl DebugRenderer {
     }
 }
 
+/// Open a new window with the same settings, starting where the shell reported it was with OSC 7.
+/// A directory on another machine, from a shell over SSH, is no use to us, so the new window
+/// starts where we were started instead
+fn duplicate_window<Io: FreminalTermInputOutput>(terminal_emulator: &TerminalEmulator<Io>) {
+    let directory = terminal_emulator
+        .internal
+        .working_directory
+        .as_ref()
+        .filter(|directory| directory.is_local() && directory.path.is_dir())
+        .map(|directory| directory.path.as_path());
+
+    if let Err(e) = open_new_window(directory) {
+        error!("Failed to open a new window: {e}");
+    }
+}
+
+/// Send `signal` to the foreground program, and go back to the bottom to see what it does
+fn send_signal<Io: FreminalTermInputOutput>(
+    terminal_emulator: &mut TerminalEmulator<Io>,
+    signal: PtySignal,
+) {
+    terminal_emulator.internal.snap_to_bottom();
+    if let Err(e) = terminal_emulator.internal.send_signal(signal) {
+        error!("Failed to send {signal:?}: {e}");
+    }
+}
+
+/// Put the selected text on the clipboard
+/// Open `url` with the system's handler for it, and show it as visited
+fn open_link<Io: FreminalTermInputOutput>(
+    ctx: &Context,
+    terminal_emulator: &mut TerminalEmulator<Io>,
+    url: &str,
+) {
+    terminal_emulator.internal.mark_link_visited(url);
+    terminal_emulator.set_previous_pass_invalid();
+    ctx.output_mut(|output| {
+        output.cursor_icon = CursorIcon::Wait;
+        output.open_url = Some(OpenUrl {
+            url: url.to_string(),
+            new_tab: true,
+        });
+    });
+}
+
+fn copy_selection<Io: FreminalTermInputOutput>(
+    ctx: &Context,
+    terminal_emulator: &mut TerminalEmulator<Io>,
+) {
+    if let Some(text) = terminal_emulator.internal.selected_text() {
+        ctx.copy_text(text);
+    }
+}
+
 pub struct FreminalTerminalWidget {
     font_size: f32,
+    /// The font size from the config, which Ctrl+0 goes back to
+    configured_font_size: f32,
     character_size: (f32, f32),
     previous_font_size: Option<f32>,
     debug_renderer: DebugRenderer,
     previous_pass: TerminalOutputRenderResponse,
     previous_mouse_state: Option<PreviousMouseState>,
+    /// Which of this frame's input events came from the numeric keypad, by index
+    numpad_events: Vec<usize>,
+    failed_command_flash: Option<(Range<usize>, Instant)>,
+    /// Tint the output of the running command, so it is easy to see where it starts
+    highlight_running_command: bool,
+    /// When the viewport was last moved from the keyboard, to show where it is for a moment
+    viewport_indicator: Option<Instant>,
+    smooth_scroll: SmoothScroll,
+    screenshot_state: ScreenshotState,
+    paste_file: PasteFileDialog,
+    search_bar: SearchBar,
+    /// The OSC 8 hyperlink the context menu was opened on
+    context_menu_link: Option<Hyperlink>,
+    /// What it takes to open a link by clicking on it
+    link_opening: LinkOpening,
+    /// A link that was clicked on, waiting for the user to say whether to open it
+    pending_link: Option<String>,
+    /// Bytes typed in hex in the context menu, waiting to be sent
+    hex_bytes: String,
+    hex_bytes_error: Option<String>,
+    /// The user asked for the config file to be read again
+    reload_config_requested: bool,
+    /// Bigger text and the keys being typed, for screencasts and teaching
+    presentation: PresentationMode,
+    snap_to_cells: bool,
+    timestamp_gutter: TimestampGutter,
+    /// Characters to draw ourselves rather than with the font
+    glyph_settings: GlyphSettings,
     ctx: Context,
 }
 
 impl FreminalTerminalWidget {
     #[must_use]
-    pub fn new(ctx: &Context) -> Self {
-        setup_font_files(ctx);
-        setup_bg_fill(ctx);
+    pub fn new(
+        ctx: &Context,
+        snap_to_cells: bool,
+        timestamp_gutter: TimestampGutter,
+        glyph_settings: GlyphSettings,
+        font_settings: &FontSettings,
+        presentation_settings: PresentationSettings,
+        link_opening: LinkOpening,
+    ) -> Self {
+        setup_font_files(ctx, font_settings.family.as_deref());
+        setup_bg_fill(ctx, Color32::BLACK);
 
         Self {
-            font_size: 12.0,
+            font_size: font_settings.size,
+            configured_font_size: font_settings.size,
             character_size: (0.0, 0.0),
             previous_font_size: None,
             debug_renderer: DebugRenderer::new(),
             previous_pass: TerminalOutputRenderResponse {
                 canvas_area: Rect::NOTHING,
                 canvas: UiJobAction::default(),
+                render_slice: Arc::default(),
             },
             previous_mouse_state: None,
+            numpad_events: Vec::new(),
+            failed_command_flash: None,
+            highlight_running_command: true,
+            viewport_indicator: None,
+            smooth_scroll: SmoothScroll::default(),
+            screenshot_state: ScreenshotState::Idle,
+            paste_file: PasteFileDialog::default(),
+            search_bar: SearchBar::default(),
+            context_menu_link: None,
+            link_opening,
+            pending_link: None,
+            hex_bytes: String::new(),
+            hex_bytes_error: None,
+            reload_config_requested: false,
+            presentation: PresentationMode::new(presentation_settings),
+            snap_to_cells,
+            timestamp_gutter,
+            glyph_settings,
             ctx: ctx.clone(),
         }
     }
 
+    /// Say which of the coming frame's input events came from the numeric keypad. See
+    /// `egui_winit::take_numpad_events`
+    pub fn set_numpad_events(&mut self, numpad_events: Vec<usize>) {
+        self.numpad_events = numpad_events;
+    }
+
+    /// Whether the user has asked for the config file to be read again since this was last called
+    pub fn take_reload_config_request(&mut self) -> bool {
+        std::mem::take(&mut self.reload_config_requested)
+    }
+
     #[must_use]
     pub const fn get_font_size(&self) -> f32 {
         self.font_size
     }
 
-    #[must_use]
-    pub fn calculate_available_size(&self, ui: &Ui) -> (usize, usize) {
-        let character_size = get_char_size(ui.ctx(), self.font_size);
-        let width_chars =
-            match ((ui.available_width() / character_size.0).floor()).approx_as::<usize>() {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Failed to calculate width chars: {}", e);
-                    10
-                }
-            };
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.font_size = font_size.clamp(1.0, 100.0);
+    }
 
-        let height_chars =
-            match ((ui.available_height() / character_size.1).floor()).approx_as::<usize>() {
-                Ok(v) => {
-                    if v > 1 {
-                        v - 1
-                    } else {
-                        1
+    /// Change the font size to go back to when presentation mode is turned off, and how it
+    /// changes the font size, as when the config file is reloaded
+    pub fn set_base_font_size(&mut self, font_size: f32, presentation: PresentationSettings) {
+        self.configured_font_size = font_size;
+        self.presentation.set_settings(presentation);
+        let font_size = self.presentation.resize(font_size);
+        self.set_font_size(font_size);
+    }
+
+    pub fn set_link_opening(&mut self, link_opening: LinkOpening) {
+        self.link_opening = link_opening;
+    }
+
+    /// Ask whether to open the link that was clicked on, if there is one waiting
+    fn show_link_confirmation<Io: FreminalTermInputOutput>(
+        &mut self,
+        ctx: &Context,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) {
+        let Some(url) = &self.pending_link else {
+            return;
+        };
+
+        let mut open = true;
+        let mut answer = None;
+        egui::Window::new("Open link?")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(url);
+                ui.horizontal(|ui| {
+                    if ui.button("Open").clicked() {
+                        answer = Some(true);
                     }
+                    if ui.button("Cancel").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if !open {
+            self.pending_link = None;
+            return;
+        }
+
+        match answer {
+            Some(true) => {
+                if let Some(url) = self.pending_link.take() {
+                    open_link(ctx, terminal_emulator, &url);
                 }
-                Err(e) => {
-                    error!("Failed to calculate height chars: {}", e);
-                    10
-                }
-            };
+            }
+            Some(false) => self.pending_link = None,
+            None => (),
+        }
+    }
+
+    /// Ctrl+Plus and Ctrl+Minus make the text bigger and smaller, and Ctrl+0 puts it back to the
+    /// size in the config
+    fn handle_zoom_shortcuts(&mut self, ui: &Ui) {
+        let zoom_in = ui.input_mut(|input_state| {
+            ZOOM_IN_KEYS
+                .iter()
+                .any(|&key| consume_shortcut(input_state, Modifiers::CTRL, key))
+        });
+        if zoom_in {
+            self.set_font_size(self.font_size + ZOOM_STEP);
+        }
+
+        if ui.input_mut(|input_state| consume_shortcut(input_state, Modifiers::CTRL, ZOOM_OUT_KEY))
+        {
+            self.set_font_size(self.font_size - ZOOM_STEP);
+        }
+
+        if ui
+            .input_mut(|input_state| consume_shortcut(input_state, Modifiers::CTRL, ZOOM_RESET_KEY))
+        {
+            let font_size = self.presentation.resize(self.configured_font_size);
+            self.set_font_size(font_size);
+        }
+    }
+
+    /// Turn presentation mode on or off
+    pub fn toggle_presentation(&mut self) {
+        let font_size = self.presentation.toggle(self.font_size);
+        self.set_font_size(font_size);
+    }
+
+    /// The cell geometry for the current font size, with the padding around the terminal measured
+    /// from `ui`
+    #[must_use]
+    pub fn cell_geometry(&self, ui: &Ui) -> CellGeometry {
+        let (char_width, char_height) = get_char_size(ui.ctx(), self.font_size);
+        // Everything around the terminal, like the panel margins
+        let padding = (ui.ctx().screen_rect().size() - ui.available_size()).max(Vec2::ZERO);
+
+        CellGeometry::new(char_width, char_height, ui.ctx().pixels_per_point())
+            .with_padding(padding.x, padding.y)
+    }
+
+    /// Where the cells were drawn last frame, to work out which one the pointer is over
+    fn cell_grid<Io: FreminalTermInputOutput>(
+        &self,
+        ui: &Ui,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) -> CellGrid {
+        let origin = self.previous_pass.canvas_area.min;
+        let (columns, rows) = terminal_emulator.get_win_size();
+
+        CellGrid::new(
+            CellGeometry::new(
+                self.character_size.0,
+                self.character_size.1,
+                ui.ctx().pixels_per_point(),
+            ),
+            (origin.x, origin.y),
+            columns,
+            rows,
+        )
+    }
+
+    #[must_use]
+    pub fn calculate_available_size(&self, ui: &Ui) -> (usize, usize) {
+        let (width_chars, height_chars) = self
+            .cell_geometry(ui)
+            .cells_for_size(ui.available_width(), ui.available_height());
+        let height_chars = height_chars.saturating_sub(RESERVED_ROWS);
+        // The timestamp gutter sits to the right of the output
+        let width_chars = width_chars.saturating_sub(self.timestamp_gutter.columns());
 
         (width_chars, height_chars)
     }
@@ -875,7 +1859,10 @@ impl FreminalTerminalWidget {
             {
                 debug!("Font size changed, updating character size");
                 self.character_size = get_char_size(ui.ctx(), self.font_size);
-                terminal_emulator.set_egui_ctx_if_missing(self.ctx.clone());
+                terminal_emulator.set_redraw_notifier_if_missing(Arc::new(
+                    EguiRedrawNotifier::new(self.ctx.clone()),
+                ));
+                self.update_window_size_constraints(ui, &terminal_emulator.internal.size_limits);
 
                 let (width_chars, height_chars) = terminal_emulator.get_win_size();
                 let width_chars = match f32::value_from(width_chars) {
@@ -899,27 +1886,209 @@ impl FreminalTerminalWidget {
                 self.previous_font_size = Some(self.font_size);
             }
 
-            let (left_mouse_button_pressed, new_mouse_pos) = ui.input(|input_state| {
-                write_input_to_terminal(
+            if self.screenshot_state == ScreenshotState::Waiting {
+                self.handle_screenshot_response(ui);
+            }
+
+            // The theme can change while we run, from the menu or the config file
+            let fill = background_fill(&terminal_emulator.internal.palette);
+            if ui.visuals().panel_fill != fill {
+                setup_bg_fill(ui.ctx(), fill);
+                ui.ctx().request_repaint();
+            }
+
+            // Ctrl+Shift+Up jumps to the output of the previous command that failed
+            if ui.input_mut(|input_state| {
+                consume_shortcut(
                     input_state,
-                    terminal_emulator,
-                    self.character_size.0,
-                    self.character_size.1,
-                    self.previous_mouse_state.clone(),
+                    Modifiers::CTRL | Modifiers::SHIFT,
+                    Key::ArrowUp,
                 )
-            });
-            self.previous_mouse_state = new_mouse_pos;
+            }) {
+                if let Some(range) = terminal_emulator
+                    .internal
+                    .scroll_to_previous_failed_command()
+                {
+                    self.failed_command_flash = Some((range, Instant::now()));
+                    terminal_emulator.set_previous_pass_invalid();
+                } else {
+                    debug!("No failed command to jump to");
+                }
+            }
 
-            if terminal_emulator.needs_redraw() {
+            // Shift+PageUp/PageDown/Home/End move through the scrollback. The alternate screen
+            // usually has none, so the keys go to the application
+            if terminal_emulator.internal.has_scrollback() {
+                for (key, movement) in VIEWPORT_KEYS {
+                    if ui.input_mut(|input_state| {
+                        consume_shortcut(input_state, Modifiers::SHIFT, key)
+                    }) {
+                        terminal_emulator.internal.move_viewport(movement);
+                        self.viewport_indicator = Some(Instant::now());
+                    }
+                }
+            }
+
+            for (key, signal, _) in SIGNAL_KEYS {
+                if ui.input_mut(|input_state| {
+                    consume_shortcut(input_state, Modifiers::CTRL | Modifiers::ALT, key)
+                }) {
+                    send_signal(terminal_emulator, signal);
+                }
+            }
+
+            // F11 toggles fullscreen, whatever the application running in the terminal wants
+            if ui.input_mut(|input_state| consume_shortcut(input_state, Modifiers::NONE, Key::F11))
+            {
+                toggle_fullscreen(ui.ctx());
+            }
+
+            // Ctrl+Shift+N opens a new window in the shell's working directory
+            if ui.input_mut(|input_state| {
+                consume_shortcut(input_state, Modifiers::CTRL | Modifiers::SHIFT, Key::N)
+            }) {
+                duplicate_window(terminal_emulator);
+            }
+
+            // Ctrl+Shift+R starts and stops recording the shell's output
+            if ui.input_mut(|input_state| {
+                consume_shortcut(input_state, Modifiers::CTRL | Modifiers::SHIFT, Key::R)
+            }) {
+                terminal_emulator.toggle_recording();
+            }
+
+            // Ctrl+Shift+F searches the scrollback
+            if ui.input_mut(|input_state| {
+                consume_shortcut(input_state, Modifiers::CTRL | Modifiers::SHIFT, Key::F)
+            }) {
+                self.open_search(terminal_emulator);
+            }
+
+            // Ctrl+Shift+P turns presentation mode on and off
+            if ui.input_mut(|input_state| {
+                consume_shortcut(input_state, Modifiers::CTRL | Modifiers::SHIFT, Key::P)
+            }) {
+                self.toggle_presentation();
+            }
+
+            self.handle_zoom_shortcuts(ui);
+
+            let position = terminal_emulator.internal.search.position();
+            if let Some(action) = self.search_bar.show(ui.ctx(), position) {
+                match action {
+                    SearchAction::Search(query) => {
+                        terminal_emulator.internal.search(&query);
+                    }
+                    SearchAction::Previous => {
+                        terminal_emulator.internal.previous_search_match();
+                    }
+                    SearchAction::Next => {
+                        terminal_emulator.internal.next_search_match();
+                    }
+                    SearchAction::Close => terminal_emulator.internal.clear_search(),
+                }
+                terminal_emulator.set_previous_pass_invalid();
+            }
+
+            self.show_link_confirmation(ui.ctx(), terminal_emulator);
+
+            if let Some(text) = self.paste_file.show(ui.ctx()) {
+                let inputs = paste_to_terminal_input(
+                    &text,
+                    &terminal_emulator.internal.modes.bracketed_paste,
+                );
+                for input in inputs.iter() {
+                    if let Err(e) = terminal_emulator.write(input) {
+                        error!("Failed to paste file: {e}");
+                        break;
+                    }
+                }
+            }
+
+            // Ctrl+Shift+C copies the selection
+            if ui.input_mut(consume_copy_shortcut) {
+                copy_selection(ui.ctx(), terminal_emulator);
+            }
+
+            // While the paste dialog, the search bar or a text box in the menu is open, typing
+            // goes to it rather than the terminal
+            let typing_elsewhere = self.paste_file.is_open()
+                || self.search_bar.is_open()
+                || ui.ctx().wants_keyboard_input();
+            if self.presentation.shows_keys() && !typing_elsewhere {
+                // Don't show passwords to the audience
+                let hide_text = terminal_emulator.echoes_input() == Some(false);
+                ui.input(|input_state| {
+                    record_keystrokes(input_state, self.presentation.keystrokes_mut(), hide_text);
+                });
+            }
+
+            let pointer_on_scroll_bar = self.show_column_scroll_bar(ui, terminal_emulator);
+            let grid = self.cell_grid(ui, terminal_emulator);
+            let left_mouse_button_pressed = !typing_elsewhere && {
+                let (pressed, new_mouse_pos) = ui.input(|input_state| {
+                    write_input_to_terminal(
+                        input_state,
+                        &self.numpad_events,
+                        terminal_emulator,
+                        &grid,
+                        &self.previous_pass.render_slice,
+                        self.previous_mouse_state.clone(),
+                        pointer_on_scroll_bar,
+                    )
+                });
+                self.previous_mouse_state = new_mouse_pos;
+                pressed
+            };
+
+            let scrolled_lines = terminal_emulator.internal.take_viewport_scrolled_lines();
+            if scrolled_lines != 0 && terminal_emulator.internal.scroll_settings.smooth {
+                self.smooth_scroll
+                    .add_lines(scrolled_lines, self.character_size.1);
+            }
+
+            if self.screenshot_state == ScreenshotState::Requested {
+                self.smooth_scroll.stop();
+            }
+
+            let y_offset = self.smooth_scroll.offset();
+            if y_offset.is_some() {
+                ui.ctx().request_repaint_after(
+                    terminal_emulator
+                        .internal
+                        .repaint_limiter
+                        .min_frame_time()
+                        .unwrap_or_default(),
+                );
+            }
+
+            let settings = CanvasSettings {
+                font_size: self.font_size,
+                geometry: CellGeometry::new(
+                    self.character_size.0,
+                    self.character_size.1,
+                    ui.ctx().pixels_per_point(),
+                ),
+                glyph_settings: self.glyph_settings,
+                palette: terminal_emulator.internal.palette,
+            };
+            // Make sure there is a frame to show the shell's startup output if its first prompt
+            // never comes
+            if let Some(left) = terminal_emulator.internal.startup_banner_time_left() {
+                ui.ctx().request_repaint_after(left);
+            }
+
+            if terminal_emulator.needs_redraw() {
                 self.previous_pass =
-                    render_terminal_output(ui, terminal_emulator, self.font_size, None);
+                    render_terminal_output(ui, terminal_emulator, &settings, None, y_offset);
             } else {
                 debug!("Reusing previous terminal output");
                 let _response = render_terminal_output(
                     ui,
                     terminal_emulator,
-                    self.font_size,
+                    &settings,
                     Some(&self.previous_pass),
+                    y_offset,
                 );
             }
 
@@ -927,58 +2096,216 @@ impl FreminalTerminalWidget {
             self.debug_renderer
                 .render(ui, self.previous_pass.canvas_area, Color32::BLUE);
 
-            if terminal_emulator.show_cursor() {
-                let default_foreground_color = ui.style().visuals.text_color();
-                let default_background_color = ui.style().visuals.window_fill();
-                let color = internal_color_to_egui(
-                    default_foreground_color,
-                    default_background_color,
-                    terminal_emulator.internal.get_current_buffer().cursor_color,
-                    false,
+            if self.highlight_running_command {
+                if let Some(rows) = terminal_emulator.internal.running_command_rows() {
+                    paint_row_highlight(
+                        self.previous_pass.canvas_area,
+                        self.character_size,
+                        &rows,
+                        ui,
+                        ui.visuals()
+                            .text_color()
+                            .gamma_multiply(RUNNING_COMMAND_TINT),
+                    );
+                }
+            }
+
+            if let Some((range, started)) = &self.failed_command_flash {
+                let elapsed = started.elapsed();
+
+                if elapsed >= FAILED_COMMAND_FLASH_DURATION {
+                    self.failed_command_flash = None;
+                } else {
+                    if let Some(rows) = terminal_emulator.internal.visible_rows_for_buf_range(range)
+                    {
+                        // fade the highlight out over the duration of the flash
+                        let remaining = 1.0
+                            - elapsed.as_secs_f32() / FAILED_COMMAND_FLASH_DURATION.as_secs_f32();
+                        paint_row_highlight(
+                            self.previous_pass.canvas_area,
+                            self.character_size,
+                            &rows,
+                            ui,
+                            Color32::RED.gamma_multiply(0.35 * remaining),
+                        );
+                    }
+
+                    // keep the fade going, but no faster than the frame cap allows
+                    ui.ctx().request_repaint_after(
+                        terminal_emulator
+                            .internal
+                            .repaint_limiter
+                            .min_frame_time()
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+
+            let selected = terminal_emulator
+                .internal
+                .selection_range()
+                .map(|range| self.previous_pass.render_slice.cells(&range))
+                .unwrap_or_default();
+            if !selected.is_empty() {
+                paint_selection(
+                    self.previous_pass.canvas_area,
+                    self.character_size,
+                    &selected,
+                    ui,
+                    ui.visuals().selection.bg_fill.gamma_multiply(0.5),
                 );
-                paint_cursor(
+            }
+
+            self.show_timestamps(ui, terminal_emulator);
+
+            if self.presentation.shows_keys() {
+                show_keystroke_overlay(ui.ctx(), self.presentation.keystrokes_mut());
+            }
+
+            if let Some(shown) = self.viewport_indicator {
+                if let Some(remaining) = VIEWPORT_INDICATOR_DURATION.checked_sub(shown.elapsed()) {
+                    let (line, total) = terminal_emulator.internal.viewport_position();
+                    paint_viewport_indicator(
+                        self.previous_pass.canvas_area,
+                        ui,
+                        &format!("line {line}/{total}"),
+                    );
+                    ui.ctx().request_repaint_after(remaining);
+                } else {
+                    self.viewport_indicator = None;
+                }
+            }
+
+            // The cursor is left out of screenshots
+            let taking_screenshot = self.screenshot_state == ScreenshotState::Requested;
+            if taking_screenshot {
+                ui.ctx()
+                    .send_viewport_cmd(ViewportCommand::Screenshot(UserData::default()));
+                self.screenshot_state = ScreenshotState::Waiting;
+            }
+
+            // Typed characters the shell hasn't echoed yet go where the echo will, and the cursor
+            // after them
+            // A view that doesn't wrap may have the cursor off the edge of the screen
+            let cursor_in_view = terminal_emulator.internal.viewport_cursor_pos();
+            let mut cursor_pos = cursor_in_view.clone().unwrap_or_default();
+            let prediction = &mut terminal_emulator.internal.echo_prediction;
+            if let Some(left) = prediction.expire(Instant::now()) {
+                ui.ctx().request_repaint_after(left);
+            }
+            let text = prediction.predicted_text();
+            if !text.is_empty()
+                && cursor_in_view.is_some()
+                && terminal_emulator.internal.scrolled_back_lines() == 0
+            {
+                let (columns, _) = terminal_emulator.get_win_size();
+                cursor_pos = paint_predicted_echo(
                     self.previous_pass.canvas_area,
                     self.character_size,
-                    &terminal_emulator.cursor_pos(),
+                    &cursor_pos,
+                    columns,
+                    &text,
                     ui,
-                    color,
+                    self.font_size,
                 );
             }
 
-            // lets see if we're hovering over a URL
-            if let Some(mouse_position) = terminal_emulator.get_mouse_position() {
-                // convert the mouse position x and y to character positions
-                let mut x = ((mouse_position.x / self.character_size.0).floor())
-                    .approx_as::<usize>()
-                    .unwrap_or_default();
-                let mut y = ((mouse_position.y / self.character_size.1).floor())
-                    .approx_as::<usize>()
-                    .unwrap_or_default();
+            // The input method puts its window by the cursor, wherever that is drawn
+            if terminal_emulator.internal.window_focused {
+                if let Some(cursor_rect) = cursor_in_view.as_ref().and_then(|_| {
+                    cursor_cell_rect(
+                        self.previous_pass.canvas_area,
+                        self.character_size,
+                        &cursor_pos,
+                    )
+                }) {
+                    ui.ctx().output_mut(|output| {
+                        output.ime = Some(IMEOutput {
+                            rect: self.previous_pass.canvas_area,
+                            cursor_rect,
+                        });
+                    });
+                }
+            }
+
+            if terminal_emulator.show_cursor() && cursor_in_view.is_some() && !taking_screenshot {
+                let cursor_color = terminal_emulator.internal.get_current_buffer().cursor_color;
+                let internal = &terminal_emulator.internal;
+                let settings = &internal.cursor_settings;
+                // An unfocused window shows a steady outline, so there is nothing to blink
+                let focused = internal.window_focused;
+                if cursor_blink_visible(ui, settings, focused && internal.cursor_blinks()) {
+                    let default_foreground_color = ui.style().visuals.text_color();
+                    let default_background_color = ui.style().visuals.window_fill();
+                    let look = CursorLook {
+                        shape: internal.cursor_visual_style().shape(),
+                        color: internal_color_to_egui(
+                            default_foreground_color,
+                            default_background_color,
+                            &internal.palette,
+                            cursor_color,
+                            false,
+                        ),
+                        thickness: settings.thickness,
+                        outline: (!focused).then_some(settings.outline_width),
+                    };
+                    paint_cursor(
+                        self.previous_pass.canvas_area,
+                        self.character_size,
+                        &cursor_pos,
+                        ui,
+                        &look,
+                    );
+                }
+            }
 
-                x = x.saturating_sub(1);
-                y = y.saturating_sub(1);
+            // lets see if we're hovering over a URL
+            let hovered_cell = terminal_emulator
+                .get_mouse_position()
+                .and_then(|mouse_position| grid.cell_at(mouse_position));
+            if let Some((x, y)) = hovered_cell {
+                let buf_pos = self
+                    .previous_pass
+                    .render_slice
+                    .buffer_offset_at(&CursorPos { x, y });
+
+                // Underline the whole of the link under the mouse
+                if terminal_emulator.internal.set_hovered_link(buf_pos) {
+                    terminal_emulator.set_previous_pass_invalid();
+                }
 
-                let cursor_pos = CursorPos { x, y };
+                // Remember the link the context menu was opened on, so it can offer to copy it
+                if ui.input(|input_state| input_state.pointer.secondary_clicked()) {
+                    self.context_menu_link = buf_pos
+                        .and_then(|buf_pos| terminal_emulator.internal.hyperlink_at(buf_pos));
+                }
 
-                if let Some(url) = terminal_emulator.is_mouse_hovered_on_url(&cursor_pos) {
+                let url =
+                    buf_pos.and_then(|buf_pos| terminal_emulator.is_mouse_hovered_on_url(buf_pos));
+                if let Some(url) = url {
                     debug!("Mouse is hovering over a URL");
-                    if left_mouse_button_pressed {
-                        ui.ctx().output_mut(|output| {
-                            output.cursor_icon = CursorIcon::Wait;
-                            output.open_url = Some(OpenUrl {
-                                url: url.to_string(),
-                                new_tab: true,
+                    // The link is underlined whenever the mouse is over it, but the pointer only
+                    // says it can be clicked when a click would open it
+                    let ctrl = ui.input(|input_state| input_state.modifiers.ctrl);
+                    if self.link_opening.click_opens(ctrl) {
+                        if !left_mouse_button_pressed {
+                            ui.ctx().output_mut(|output| {
+                                output.cursor_icon = CursorIcon::PointingHand;
                             });
-                        });
-                    } else {
-                        ui.ctx().output_mut(|output| {
-                            output.cursor_icon = CursorIcon::PointingHand;
-                        });
+                        } else if self.link_opening.asks_first() {
+                            self.pending_link = Some(url);
+                        } else {
+                            open_link(ui.ctx(), terminal_emulator, &url);
+                        }
                     }
                 }
             } else {
                 debug!("No mouse position");
 
+                if terminal_emulator.internal.set_hovered_link(None) {
+                    terminal_emulator.set_previous_pass_invalid();
+                }
+
                 ui.ctx().output_mut(|output| {
                     output.cursor_icon = CursorIcon::Default;
                 });
@@ -986,17 +2313,540 @@ impl FreminalTerminalWidget {
         });
 
         terminal_emulator.set_previous_pass_valid();
+        terminal_emulator.frame_rendered();
 
         self.debug_renderer
             .render(ui, frame_response.response.rect, Color32::RED);
     }
 
-    pub fn show_options(&mut self, ui: &mut Ui) {
+    fn show_timestamps<Io: FreminalTermInputOutput>(
+        &self,
+        ui: &Ui,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) {
+        let canvas_area = self.previous_pass.canvas_area;
+
+        match self.timestamp_gutter {
+            TimestampGutter::Off => (),
+            TimestampGutter::Always => {
+                let (width, _) = terminal_emulator.get_win_size();
+                let width = width.approx_as::<f32>().unwrap_or_else(|e| {
+                    error!("Failed to convert terminal width ({width}) to f32: {e}");
+                    0.0
+                });
+
+                paint_timestamp_gutter(
+                    canvas_area,
+                    width.mul_add(self.character_size.0, canvas_area.left()),
+                    self.character_size,
+                    &terminal_emulator.visible_line_timestamps(),
+                    ui,
+                    self.font_size,
+                );
+            }
+            TimestampGutter::Hover => {
+                let Some(pointer) = ui.ctx().pointer_hover_pos() else {
+                    return;
+                };
+                if !canvas_area.contains(pointer) {
+                    return;
+                }
+
+                let Some((_, row)) = self
+                    .cell_grid(ui, terminal_emulator)
+                    .cell_at((pointer.x, pointer.y))
+                else {
+                    return;
+                };
+                if let Some(Some(timestamp)) = terminal_emulator.visible_line_timestamps().get(row)
+                {
+                    paint_hover_timestamp(canvas_area, self.character_size, row, *timestamp, ui);
+                }
+            }
+        }
+    }
+
+    fn handle_screenshot_response(&mut self, ui: &Ui) {
+        let image = ui.input(|input_state| {
+            input_state.raw.events.iter().find_map(|event| match event {
+                Event::Screenshot { image, .. } => Some(image.clone()),
+                _ => None,
+            })
+        });
+
+        let Some(image) = image else {
+            // The screenshot hasn't arrived yet. Make sure we get another frame to look for it
+            ui.ctx().request_repaint();
+            return;
+        };
+
+        self.screenshot_state = ScreenshotState::Idle;
+
+        let path = screenshot_path();
+        match save_screenshot(
+            &image,
+            self.previous_pass.canvas_area,
+            ui.ctx().pixels_per_point(),
+            &path,
+        ) {
+            Ok(()) => info!("Saved screenshot to {}", path.display()),
+            Err(e) => error!("Failed to save screenshot to {}: {e}", path.display()),
+        }
+    }
+
+    /// The bar in the row below the output for scrolling along long lines, when the view doesn't
+    /// wrap and some lines don't fit. Returns true if the mouse is on it or dragging it
+    fn show_column_scroll_bar<Io: FreminalTermInputOutput>(
+        &self,
+        ui: &Ui,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) -> bool {
+        let (offset, max_offset) = terminal_emulator.internal.column_scroll();
+        let canvas_area = self.previous_pass.canvas_area;
+        if max_offset == 0 || !canvas_area.is_positive() {
+            return false;
+        }
+
+        let (columns, rows) = terminal_emulator.get_win_size();
+        let (Ok(columns), Ok(rows), Ok(offset_f32), Ok(max_offset_f32)) = (
+            f32::value_from(columns),
+            f32::value_from(rows),
+            f32::value_from(offset),
+            f32::value_from(max_offset),
+        ) else {
+            error!("Failed to convert the column scroll of {offset}/{max_offset} to f32");
+            return false;
+        };
+
+        let track = Rect::from_min_size(
+            egui::pos2(
+                canvas_area.left(),
+                rows.mul_add(self.character_size.1, canvas_area.top()),
+            ),
+            Vec2::new(
+                columns * self.character_size.0,
+                self.character_size.1 * COLUMN_SCROLL_BAR_HEIGHT,
+            ),
+        );
+        let response = ui.interact(
+            track,
+            ui.id().with("column_scroll_bar"),
+            egui::Sense::click_and_drag(),
+        );
+
+        // The thumb is as much of the track as the screen is of the longest line
+        let thumb_width = (track.width() * columns / (columns + max_offset_f32))
+            .max(track.height())
+            .min(track.width());
+        let travel = track.width() - thumb_width;
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let fraction =
+                ((pointer.x - track.left() - thumb_width / 2.0) / travel).clamp(0.0, 1.0);
+            match (fraction * max_offset_f32).round().approx_as::<usize>() {
+                Ok(column) if column != offset => {
+                    terminal_emulator.internal.set_column_offset(column);
+                    terminal_emulator.set_previous_pass_invalid();
+                }
+                Ok(_) => (),
+                Err(e) => error!("Failed to convert the scroll bar position to a column: {e}"),
+            }
+        }
+
+        let visuals = ui.style().interact(&response);
+        let rounding = track.height() / 2.0;
+        ui.painter()
+            .rect_filled(track, rounding, ui.visuals().extreme_bg_color);
+        ui.painter().rect_filled(
+            Rect::from_min_size(
+                egui::pos2(
+                    (offset_f32 / max_offset_f32).mul_add(travel, track.left()),
+                    track.top(),
+                ),
+                Vec2::new(thumb_width, track.height()),
+            ),
+            rounding,
+            visuals.fg_stroke.color,
+        );
+
+        response.contains_pointer() || response.dragged()
+    }
+
+    /// Keep the window within the terminal's size `limits`, and tell the window manager our cell
+    /// size so it can snap resizes to it where the platform supports that
+    fn update_window_size_constraints(&self, ui: &Ui, limits: &SizeLimits) {
+        let geometry = self.cell_geometry(ui);
+        let window_size = |size: TerminalGeometry| {
+            let (width, height) = geometry.window_size_for_cells(
+                size.columns + self.timestamp_gutter.columns(),
+                size.rows + RESERVED_ROWS,
+            );
+            Vec2::new(width, height)
+        };
+
+        ui.ctx()
+            .send_viewport_cmd(ViewportCommand::MinInnerSize(window_size(limits.min)));
+        ui.ctx()
+            .send_viewport_cmd(ViewportCommand::MaxInnerSize(window_size(limits.max)));
+
+        let increments = self
+            .snap_to_cells
+            .then(|| Vec2::new(geometry.cell_width, geometry.cell_height));
+        ui.ctx()
+            .send_viewport_cmd(ViewportCommand::ResizeIncrements(increments));
+    }
+
+    /// Send arbitrary bytes, for talking to devices that want control characters a keyboard
+    /// can't easily type
+    fn show_send_hex_bytes<Io: FreminalTermInputOutput>(
+        &mut self,
+        ui: &mut Ui,
+        terminal_emulator: &TerminalEmulator<Io>,
+    ) {
+        ui.horizontal(|ui| {
+            ui.label("Send hex bytes:");
+            ui.add(TextEdit::singleline(&mut self.hex_bytes).hint_text("03 1b5b41"));
+            if ui.button("Send").clicked() {
+                match parse_hex_bytes(&self.hex_bytes) {
+                    Ok(bytes) => {
+                        for byte in bytes {
+                            if let Err(e) = terminal_emulator.write(&TerminalInput::Ascii(byte)) {
+                                error!("Failed to send hex bytes: {e}");
+                                break;
+                            }
+                        }
+                        self.hex_bytes.clear();
+                        self.hex_bytes_error = None;
+                        ui.close_menu();
+                    }
+                    Err(e) => self.hex_bytes_error = Some(e.to_string()),
+                }
+            }
+        });
+        if let Some(error) = &self.hex_bytes_error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+
+    /// The one off actions at the top of the context menu
+    fn show_actions<Io: FreminalTermInputOutput>(
+        &mut self,
+        ui: &mut Ui,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) {
+        if ui
+            .add_enabled(
+                terminal_emulator
+                    .internal
+                    .get_current_buffer()
+                    .selection
+                    .is_some(),
+                egui::Button::new("Copy (Ctrl+Shift+C)"),
+            )
+            .clicked()
+        {
+            copy_selection(ui.ctx(), terminal_emulator);
+            ui.close_menu();
+        }
+        if ui.button("Paste (Ctrl+Shift+V)").clicked() {
+            // The clipboard comes back as a paste event next frame, and goes the same way as a
+            // paste from the keyboard
+            ui.ctx().send_viewport_cmd(ViewportCommand::RequestPaste);
+            ui.close_menu();
+        }
+        // A selection inside a link picks the link. Without a selection, the link that was
+        // right-clicked on does
+        let link = if terminal_emulator
+            .internal
+            .get_current_buffer()
+            .selection
+            .is_some()
+        {
+            terminal_emulator.internal.selection_hyperlink()
+        } else {
+            self.context_menu_link.clone()
+        };
+        if let Some(link) = &link {
+            if ui.button("Copy link").on_hover_text(&link.url).clicked() {
+                ui.ctx().copy_text(link.url.clone());
+                ui.close_menu();
+            }
+            if ui.button("Copy link text").clicked() {
+                ui.ctx().copy_text(link.text.clone());
+                ui.close_menu();
+            }
+            ui.separator();
+        }
+        if ui.button("New window here (Ctrl+Shift+N)").clicked() {
+            duplicate_window(terminal_emulator);
+            ui.close_menu();
+        }
+        if ui
+            .button("Reset modes")
+            .on_hover_text("Undo modes a program that crashed left behind, like a hidden cursor")
+            .clicked()
+        {
+            terminal_emulator.internal.reset_modes();
+            terminal_emulator.set_previous_pass_invalid();
+            ui.close_menu();
+        }
+        ui.menu_button("Send to foreground program", |ui| {
+            for (_, signal, label) in SIGNAL_KEYS {
+                if ui.button(label).clicked() {
+                    send_signal(terminal_emulator, signal);
+                    ui.close_menu();
+                }
+            }
+        });
+        if ui.button("Save screenshot").clicked() {
+            self.screenshot_state = ScreenshotState::Requested;
+            ui.ctx().request_repaint();
+            ui.close_menu();
+        }
+        if ui.button("Paste from file…").clicked() {
+            self.paste_file.open();
+            ui.close_menu();
+        }
+        if ui.button("Find…").clicked() {
+            self.open_search(terminal_emulator);
+            ui.close_menu();
+        }
+        if ui
+            .button("Reload config")
+            .on_hover_text("Apply changes to the font size, palette and scrollback length")
+            .clicked()
+        {
+            self.reload_config_requested = true;
+            ui.close_menu();
+        }
+        self.show_send_hex_bytes(ui, terminal_emulator);
+    }
+
+    /// Open the search bar, searching again for whatever was last looked for
+    fn open_search<Io: FreminalTermInputOutput>(
+        &mut self,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) {
+        self.search_bar.open();
+        if !self.search_bar.query().is_empty() {
+            terminal_emulator.internal.search(self.search_bar.query());
+            terminal_emulator.set_previous_pass_invalid();
+        }
+    }
+
+    /// Options that change how the output is drawn, without changing the output itself
+    fn show_drawing_options<Io: FreminalTermInputOutput>(
+        &mut self,
+        ui: &mut Ui,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) {
+        let mut theme = terminal_emulator.internal.palette.theme();
+        egui::ComboBox::from_label("Theme")
+            .selected_text(theme.to_string())
+            .show_ui(ui, |ui| {
+                for choice in THEMES {
+                    ui.selectable_value(&mut theme, choice, choice.to_string());
+                }
+            });
+        if theme != terminal_emulator.internal.palette.theme() {
+            let mut palette = terminal_emulator.internal.user_palette;
+            palette.set_theme(theme);
+            terminal_emulator.internal.set_palette(palette);
+            terminal_emulator.set_previous_pass_invalid();
+        }
+
+        let previous_glyph_settings = self.glyph_settings;
+        ui.checkbox(
+            &mut self.glyph_settings.powerline,
+            "Draw Powerline separators",
+        )
+        .on_hover_text("Line prompt separators up with the cells either side");
+        ui.checkbox(
+            &mut self.glyph_settings.box_drawing,
+            "Draw box drawing characters",
+        )
+        .on_hover_text("Join up borders whatever the font");
+        if self.glyph_settings != previous_glyph_settings {
+            terminal_emulator.set_previous_pass_invalid();
+        }
+
+        if ui
+            .checkbox(
+                &mut terminal_emulator.internal.bidi_enabled,
+                "Right to left text (bidi)",
+            )
+            .changed()
+        {
+            terminal_emulator.set_previous_pass_invalid();
+        }
+
+        let mut no_wrap = terminal_emulator
+            .internal
+            .primary_buffer
+            .terminal_buffer
+            .no_wrap_view();
+        if ui
+            .checkbox(&mut no_wrap, "Don't wrap long lines")
+            .on_hover_text("Scroll sideways with Shift and the mouse wheel instead")
+            .changed()
+        {
+            terminal_emulator.internal.set_no_wrap_view(no_wrap);
+            terminal_emulator.set_previous_pass_invalid();
+        }
+
+        if ui
+            .checkbox(
+                &mut terminal_emulator.internal.style_visited_links,
+                "Color links that have been opened",
+            )
+            .changed()
+        {
+            terminal_emulator.set_previous_pass_invalid();
+        }
+    }
+
+    pub fn show_options<Io: FreminalTermInputOutput>(
+        &mut self,
+        ui: &mut Ui,
+        terminal_emulator: &mut TerminalEmulator<Io>,
+    ) {
         ui.horizontal(|ui| {
             ui.label("Font size:");
             ui.add(DragValue::new(&mut self.font_size).range(1.0..=100.0));
         });
+
+        self.show_actions(ui, terminal_emulator);
+
+        let mut presenting = self.presentation.is_active();
+        if ui
+            .checkbox(&mut presenting, "Presentation mode (Ctrl+Shift+P)")
+            .on_hover_text("Bigger text, and the keys being typed shown at the bottom")
+            .changed()
+        {
+            self.toggle_presentation();
+        }
+
+        let mut recording = terminal_emulator.is_recording();
+        if ui
+            .checkbox(&mut recording, "Record output (Ctrl+Shift+R)")
+            .changed()
+        {
+            terminal_emulator.toggle_recording();
+        }
+
+        let mut show_metrics = terminal_emulator.internal.metrics.enabled;
+        if ui
+            .checkbox(&mut show_metrics, "Show performance metrics")
+            .changed()
+        {
+            terminal_emulator.internal.metrics.set_enabled(show_metrics);
+        }
+
+        let mut measure_latency = terminal_emulator.internal.input_latency.enabled();
+        if ui
+            .checkbox(&mut measure_latency, "Measure input latency")
+            .changed()
+        {
+            terminal_emulator
+                .internal
+                .input_latency
+                .set_enabled(measure_latency);
+        }
+
+        show_unsupported_features(ui, &mut terminal_emulator.internal.unsupported);
+
+        ui.checkbox(
+            &mut terminal_emulator.internal.scroll_settings.smooth,
+            "Smooth scrolling",
+        );
+
+        ui.checkbox(
+            &mut self.highlight_running_command,
+            "Highlight the running command's output",
+        )
+        .on_hover_text("Needs a shell that marks its commands with OSC 133");
+
+        ui.checkbox(
+            &mut terminal_emulator.internal.cursor_settings.blink,
+            "Blinking cursor",
+        );
+
+        ui.horizontal(|ui| {
+            match time_zone_label() {
+                Some(zone) => ui.label(format!("Line timestamps ({zone}):")),
+                None => ui.label("Line timestamps:"),
+            };
+            let previous = self.timestamp_gutter;
+            for (gutter, label) in [
+                (TimestampGutter::Off, "Off"),
+                (TimestampGutter::Hover, "On hover"),
+                (TimestampGutter::Always, "Always"),
+            ] {
+                ui.radio_value(&mut self.timestamp_gutter, gutter, label);
+            }
+
+            if self.timestamp_gutter != previous {
+                // Recalculates the window's minimum size for the new gutter
+                self.previous_font_size = None;
+            }
+        });
+
+        let mut width_provider = terminal_emulator.internal.width_provider();
+        if ui
+            .checkbox(
+                &mut width_provider.ambiguous_wide,
+                "Wide ambiguous width characters",
+            )
+            .on_hover_text("Only affects output from now on")
+            .changed()
+        {
+            terminal_emulator
+                .internal
+                .set_width_provider(width_provider);
+        }
+
+        self.show_drawing_options(ui, terminal_emulator);
+
+        ui.checkbox(
+            &mut terminal_emulator.internal.trim_trailing_spaces_on_copy,
+            "Trim trailing spaces when copying",
+        );
+
+        let prediction = &mut terminal_emulator.internal.echo_prediction;
+        if ui
+            .checkbox(&mut prediction.enabled, "Predict echo (experimental)")
+            .on_hover_text("Show typed characters before the shell echoes them")
+            .changed()
+        {
+            prediction.clear();
+        }
+
         #[cfg(debug_assertions)]
         ui.checkbox(&mut self.debug_renderer.enable, "Debug render");
     }
 }
+
+/// What programs have asked for that we don't support, so the user can tell why one misbehaves
+fn show_unsupported_features(ui: &mut Ui, unsupported: &mut UnsupportedFeatures) {
+    ui.add_enabled_ui(!unsupported.is_empty(), |ui| {
+        ui.menu_button("Unsupported features requested", |ui| {
+            for feature in unsupported.features() {
+                ui.monospace(format!("{} ×{}", feature.name, feature.count));
+            }
+            if unsupported.overflowed() > 0 {
+                ui.monospace(format!("Others not listed ×{}", unsupported.overflowed()));
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Copy report").clicked() {
+                    ui.ctx().copy_text(unsupported.report());
+                    ui.close_menu();
+                }
+                if ui.button("Clear").clicked() {
+                    unsupported.clear();
+                    ui.close_menu();
+                }
+            });
+        });
+    });
+}
@@ -18,9 +18,12 @@
 #[macro_use]
 extern crate tracing;
 
-use freminal_terminal_emulator::interface::TerminalEmulator;
+use freminal_terminal_emulator::{
+    interface::TerminalEmulator,
+    io::{coalesce_reads, BACKGROUND_BATCH_BYTES, BACKGROUND_BATCH_INTERVAL},
+};
 use parking_lot::FairMutex;
-use std::{process, sync::Arc};
+use std::{io::Write, process, sync::Arc};
 use tracing::Level;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::{
@@ -33,14 +36,29 @@ use tracing_subscriber::{
 
 pub mod gui;
 
-use freminal_common::args::Args;
+use freminal_common::{
+    args::Args,
+    config::Config,
+    control::send_request,
+    msg::{MsgArgs, MSG_USAGE},
+    timestamps::read_local_offset,
+};
 
+#[allow(clippy::too_many_lines)]
 fn main() {
+    // Before any threads are started, as reading the time zone isn't safe with other threads
+    let local_offset = read_local_offset();
+
     // use env for filtering
     // example
     // RUST_LOG=none,freminal=debug cargo run
 
-    let args = Args::parse(std::env::args()).unwrap_or_else(|_| {
+    // `freminal msg ...` talks to a running terminal instead of starting one
+    if std::env::args().nth(1).as_deref() == Some("msg") {
+        process::exit(run_msg(std::env::args().skip(2).collect()));
+    }
+
+    let args = Args::parse_with_config(std::env::args(), &load_config()).unwrap_or_else(|_| {
         process::exit(1);
     });
 
@@ -126,22 +144,40 @@ fn main() {
     }
 
     info!("Starting freminal");
+    if let Err(e) = local_offset {
+        warn!("Failed to read the local time zone, line timestamps will be in UTC: {e}");
+    }
 
     let res = match TerminalEmulator::new(&args) {
         Ok((terminal, rx)) => {
             let terminal = Arc::new(FairMutex::new(terminal));
             let terminal_clone = Arc::clone(&terminal);
-
-            std::thread::spawn(move || loop {
-                if let Ok(read) = rx.recv() {
-                    terminal
-                        .lock()
-                        .internal
-                        .handle_incoming_data(&read.buf[0..read.read_amount]);
+            let terminal_for_shutdown = Arc::clone(&terminal);
+
+            std::thread::spawn(move || {
+                while let Ok(read) = rx.recv() {
+                    let visible = terminal.lock().internal.window_visible;
+                    if visible {
+                        terminal
+                            .lock()
+                            .internal
+                            .handle_incoming_data(&read.buf[0..read.read_amount]);
+                    } else {
+                        // Nobody can see the output, so let it pile up and parse it in big batches
+                        std::thread::sleep(BACKGROUND_BATCH_INTERVAL);
+                        let batch = coalesce_reads(&read, &rx, BACKGROUND_BATCH_BYTES);
+                        terminal.lock().internal.handle_incoming_data(&batch);
+                    }
                 }
             });
 
-            gui::run(terminal_clone)
+            let res = gui::run(terminal_clone, &args);
+            let mut terminal = terminal_for_shutdown.lock();
+            if let Some(path) = &args.dump_on_exit {
+                dump_plain_text(path, &terminal.internal.to_plain_text(true));
+            }
+            terminal.shutdown();
+            res
         }
         Err(e) => {
             error!("Failed to create terminal emulator: {}", e);
@@ -155,3 +191,65 @@ fn main() {
 
     info!("Shutting down freminal");
 }
+
+/// Read the config file picked with `--config`, or the default one. Logging isn't set up yet, so
+/// problems are printed like bad arguments are
+fn load_config() -> Config {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(path) = Config::path_for_args(&args) else {
+        return Config::default();
+    };
+
+    Config::load(&path).unwrap_or_else(|e| {
+        println!("Invalid config file {}: {e}", path.display());
+        process::exit(1);
+    })
+}
+
+/// Write the terminal's text to `path`, or to stdout if it is `-`
+fn dump_plain_text(path: &str, text: &str) {
+    let result = if path == "-" {
+        std::io::stdout().write_all(text.as_bytes())
+    } else {
+        std::fs::write(path, text)
+    };
+
+    if let Err(e) = result {
+        error!("Failed to write terminal text to {path}: {e}");
+    }
+}
+
+/// Send a single control command and print the result. Returns the exit code
+fn run_msg(args: Vec<String>) -> i32 {
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{MSG_USAGE}");
+        return 0;
+    }
+
+    let msg = match MsgArgs::parse(args.into_iter()) {
+        Ok(msg) => msg,
+        Err(e) => {
+            eprintln!("freminal msg: {e}\n\n{MSG_USAGE}");
+            return 1;
+        }
+    };
+
+    let result = msg
+        .socket_path()
+        .and_then(|socket| send_request(socket.as_ref(), msg.command));
+
+    match result {
+        Ok(serde_json::Value::Null) => 0,
+        Ok(value) => {
+            match value.get("text").and_then(serde_json::Value::as_str) {
+                Some(text) => print!("{text}"),
+                None => println!("{value}"),
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("freminal msg: {e}");
+            1
+        }
+    }
+}
@@ -36,36 +36,76 @@ impl EnvEntry {
     }
 }
 
+/// The shell to run when nothing else is configured: `$SHELL` if it is executable, then the
+/// user's entry in the password database, then the platform default
 #[cfg(unix)]
-fn get_shell() -> String {
+pub fn default_shell() -> String {
+    use nix::unistd::{access, AccessFlags};
+
+    if let Ok(shell) = std::env::var("SHELL") {
+        match access(Path::new(&shell), AccessFlags::X_OK) {
+            Ok(()) => return shell,
+            Err(err) => log::warn!(
+                "$SHELL -> {shell:?} which is \
+                 not executable ({err:#}), falling back to password db lookup"
+            ),
+        }
+    }
+
+    passwd_shell().unwrap_or_else(fallback_shell)
+}
+
+/// Windows has no password database shell. PowerShell is always there on supported versions
+#[cfg(windows)]
+pub fn default_shell() -> String {
+    "powershell.exe".into()
+}
+
+#[cfg(unix)]
+fn passwd_shell() -> Option<String> {
     use nix::unistd::{access, AccessFlags};
     use std::ffi::CStr;
     use std::str;
 
     let ent = unsafe { libc::getpwuid(libc::getuid()) };
-    if !ent.is_null() {
-        let shell = unsafe { CStr::from_ptr((*ent).pw_shell) };
-        match shell.to_str().map(str::to_owned) {
-            Err(err) => {
+    if ent.is_null() {
+        return None;
+    }
+
+    let shell = unsafe { CStr::from_ptr((*ent).pw_shell) };
+    match shell.to_str().map(str::to_owned) {
+        Err(err) => {
+            log::warn!(
+                "passwd database shell could not be \
+                 represented as utf-8: {err:#}, \
+                 falling back to {}",
+                fallback_shell()
+            );
+            None
+        }
+        Ok(shell) => {
+            if let Err(err) = access(Path::new(&shell), AccessFlags::X_OK) {
                 log::warn!(
-                    "passwd database shell could not be \
-                     represented as utf-8: {err:#}, \
-                     falling back to /bin/sh"
+                    "passwd database shell={shell:?} which is \
+                     not executable ({err:#}), falling back to {}",
+                    fallback_shell()
                 );
-            }
-            Ok(shell) => {
-                if let Err(err) = access(Path::new(&shell), AccessFlags::X_OK) {
-                    log::warn!(
-                        "passwd database shell={shell:?} which is \
-                         not executable ({err:#}), falling back to /bin/sh"
-                    );
-                } else {
-                    return shell;
-                }
+                None
+            } else {
+                Some(shell)
             }
         }
     }
-    "/bin/sh".into()
+}
+
+/// zsh has been the default shell on macOS since Catalina
+#[cfg(unix)]
+fn fallback_shell() -> String {
+    if cfg!(target_os = "macos") && Path::new("/bin/zsh").exists() {
+        "/bin/zsh".into()
+    } else {
+        "/bin/sh".into()
+    }
 }
 
 fn get_base_env() -> BTreeMap<OsString, EnvEntry> {
@@ -92,7 +132,7 @@ fn get_base_env() -> BTreeMap<OsString, EnvEntry> {
                 EnvEntry {
                     is_from_base_env: true,
                     preferred_key: "SHELL".into(),
-                    value: get_shell().into(),
+                    value: passwd_shell().unwrap_or_else(fallback_shell).into(),
                 },
             );
         }
@@ -549,7 +589,7 @@ impl CommandBuilder {
             }
         }
 
-        get_shell()
+        passwd_shell().unwrap_or_else(fallback_shell)
     }
 
     fn get_home_dir(&self) -> anyhow::Result<String> {
@@ -47,7 +47,7 @@ use std::io::Result as IoResult;
 use std::os::windows::prelude::{AsRawHandle, RawHandle};
 
 pub mod cmdbuilder;
-pub use cmdbuilder::CommandBuilder;
+pub use cmdbuilder::{default_shell, CommandBuilder};
 
 #[cfg(unix)]
 pub mod unix;
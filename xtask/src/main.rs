@@ -19,9 +19,12 @@ use clap::{Parser, Subcommand};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use color_eyre::{eyre::Context, Result};
 use duct::cmd;
+use record_fixture::{record_fixture, RecordFixtureArgs};
 use tracing::level_filters::LevelFilter;
 use tracing_log::AsTrace;
 
+mod record_fixture;
+
 fn main() -> Result<()> {
     color_eyre::install()?;
     let args = Args::parse();
@@ -117,6 +120,10 @@ enum Command {
     #[command(visible_alias = "typos")]
     FixTypos,
 
+    /// Record a program's output in to a test fixture, with a test that replays it
+    #[command(visible_alias = "rf")]
+    RecordFixture(RecordFixtureArgs),
+
     /// Run tests
     #[command(visible_alias = "t")]
     Test,
@@ -154,6 +161,7 @@ impl Command {
             Self::FixClippy => fix_clippy(),
             Self::FixFormatting => fix_format(),
             Self::FixTypos => fix_typos(),
+            Self::RecordFixture(args) => record_fixture(&args),
             Self::Test => test(),
             Self::TestDocs => test_docs(),
             Self::TestLibs => test_libs(),
@@ -287,7 +295,15 @@ fn test_docs() -> Result<()> {
 
 /// Run lib tests for the workspace's default packages
 fn test_libs() -> Result<()> {
-    run_cargo(vec!["test", "--all-targets", "--all-features"])
+    run_cargo(vec!["test", "--all-targets", "--all-features"])?;
+    // The emulator has to build and pass without the stable parser API turned on
+    run_cargo(vec![
+        "test",
+        "--package",
+        "freminal-terminal-emulator",
+        "--all-targets",
+        "--no-default-features",
+    ])
 }
 
 /// Run a cargo subcommand with the default toolchain
@@ -0,0 +1,262 @@
+// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::{
+    fmt::Write as _,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use cargo_metadata::MetadataCommand;
+use color_eyre::{
+    eyre::{bail, eyre, Context},
+    Result,
+};
+use freminal_terminal_emulator::{
+    io::{
+        fixture::{Fixture, DEFAULT_FIXTURE_COLUMNS, DEFAULT_FIXTURE_ROWS},
+        PtyWrite,
+    },
+    state::{cursor::CursorPos, internal::TerminalState},
+};
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+
+/// The crate whose tests the fixtures are for
+const FIXTURE_CRATE: &str = "freminal-terminal-emulator";
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct RecordFixtureArgs {
+    /// Name of the fixture and its test, in `snake_case`, such as `htop_startup`
+    name: String,
+
+    /// Columns in the pty the program runs in
+    #[arg(long, default_value_t = DEFAULT_FIXTURE_COLUMNS)]
+    columns: usize,
+
+    /// Rows in the pty the program runs in
+    #[arg(long, default_value_t = DEFAULT_FIXTURE_ROWS)]
+    rows: usize,
+
+    /// Stop after this many seconds, for programs that don't exit on their own
+    #[arg(long)]
+    seconds: Option<u64>,
+
+    /// TERM to run the program with
+    #[arg(long, default_value = "xterm-256color")]
+    term: String,
+
+    /// Replace a fixture and test with the same name
+    #[arg(long)]
+    force: bool,
+
+    /// The program to run and its arguments, after `--`
+    #[arg(required = true, last = true)]
+    command: Vec<String>,
+}
+
+/// Run a program in a pty and save what it writes in to `tests/fixtures`, with a test that
+/// replays it through `TerminalState` and checks the screen comes out the same
+pub fn record_fixture(args: &RecordFixtureArgs) -> Result<()> {
+    let name = &args.name;
+    if !is_test_name(name) {
+        bail!("{name} isn't a valid test name. Use lower case letters, digits and _");
+    }
+
+    let crate_dir = fixture_crate_dir()?;
+    let fixture_path = crate_dir
+        .join("tests")
+        .join("fixtures")
+        .join(format!("{name}.fixture"));
+    let test_path = crate_dir.join("tests").join(format!("fixture_{name}.rs"));
+    for path in [&fixture_path, &test_path] {
+        if path.exists() && !args.force {
+            bail!(
+                "{} already exists. Use --force to replace it",
+                path.display()
+            );
+        }
+    }
+
+    let fixture = record(args)?;
+    if let Some(directory) = fixture_path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    fixture
+        .write(&fixture_path)
+        .map_err(|e| eyre!("failed to write the fixture: {e}"))?;
+    std::fs::write(&test_path, skeleton_test(name, &args.command, &fixture))
+        .wrap_err("failed to write the test")?;
+
+    tracing::info!(
+        "recorded {} bytes in {} reads to {}",
+        fixture.bytes().len(),
+        fixture.chunks.len(),
+        fixture_path.display()
+    );
+    tracing::info!(
+        "wrote {}. Check what it expects is right",
+        test_path.display()
+    );
+    Ok(())
+}
+
+/// Run the program until it exits, or for as long as it was given, keeping everything it writes
+///
+/// What it writes is fed through a `TerminalState` as well, and the terminal's replies go back to
+/// it, so programs that ask the terminal questions at startup don't sit waiting for answers.
+fn record(args: &RecordFixtureArgs) -> Result<Fixture> {
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows: u16::try_from(args.rows)?,
+            cols: u16::try_from(args.columns)?,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| eyre!("failed to open a pty: {e}"))?;
+
+    let mut command = CommandBuilder::from_argv(args.command.iter().map(Into::into).collect());
+    command.cwd(std::env::current_dir()?);
+    command.env("TERM", &args.term);
+    let mut child = pair
+        .slave
+        .spawn_command(command)
+        .map_err(|e| eyre!("failed to run {}: {e}", args.command.join(" ")))?;
+    // Only the program keeps the other end open, so reading stops when it exits
+    drop(pair.slave);
+
+    let mut reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| eyre!("failed to read from the pty: {e}"))?;
+    let mut writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| eyre!("failed to write to the pty: {e}"))?;
+
+    let started = Instant::now();
+    let (read_tx, read_rx) = crossbeam_channel::unbounded();
+    std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        while let Ok(read) = reader.read(&mut buf) {
+            if read == 0
+                || read_tx
+                    .send((started.elapsed(), buf[..read].to_vec()))
+                    .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let (reply_tx, reply_rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(reply_tx);
+    terminal_state.set_win_size(args.columns, args.rows);
+    let deadline = args
+        .seconds
+        .map(|seconds| started + Duration::from_secs(seconds));
+
+    let mut fixture = Fixture::new(args.columns, args.rows);
+    loop {
+        let read = deadline.map_or_else(
+            || read_rx.recv().ok(),
+            |deadline| read_rx.recv_deadline(deadline).ok(),
+        );
+        let Some((at, data)) = read else {
+            break;
+        };
+
+        fixture.push(at, &data);
+        terminal_state.handle_incoming_data(&data);
+        for reply in reply_rx.try_iter() {
+            if let PtyWrite::Write(reply) = reply {
+                writer.write_all(&reply)?;
+            }
+        }
+    }
+
+    if child.try_wait()?.is_none() {
+        child.kill()?;
+    }
+
+    Ok(fixture)
+}
+
+/// Where the crate the fixtures are for is
+fn fixture_crate_dir() -> Result<PathBuf> {
+    let meta = MetadataCommand::new()
+        .exec()
+        .wrap_err("failed to get cargo metadata")?;
+    let package = meta
+        .workspace_packages()
+        .into_iter()
+        .find(|package| package.name == FIXTURE_CRATE)
+        .ok_or_else(|| eyre!("{FIXTURE_CRATE} isn't in the workspace"))?;
+
+    package
+        .manifest_path
+        .parent()
+        .map(|dir| dir.as_std_path().to_path_buf())
+        .ok_or_else(|| eyre!("{FIXTURE_CRATE} has no directory"))
+}
+
+/// A name that can go in a file name and a function name
+fn is_test_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// A test that replays the fixture and checks the screen and cursor end up where they did when
+/// it was recorded, for the person adding the fixture to check and add to
+fn skeleton_test(name: &str, command: &[String], fixture: &Fixture) -> String {
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    fixture.replay(&mut terminal_state);
+    let CursorPos { x, y } = terminal_state.cursor_pos();
+    let screen = terminal_state.to_plain_text(false);
+
+    let mut screen_lines = String::new();
+    for line in screen.lines() {
+        let _ = writeln!(screen_lines, "        {line:?},");
+    }
+
+    let command = command.join(" ").replace('\n', " ");
+    let fixture_file = Path::new("tests/fixtures").join(format!("{name}.fixture"));
+    format!(
+        r#"// Copyright (C) 2024-2025 Fred Clausen
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::path::Path;
+
+use freminal_terminal_emulator::{{
+    io::fixture::Fixture,
+    state::{{cursor::CursorPos, internal::TerminalState}},
+}};
+use test_log::test;
+
+/// Replays the output of `{command}`, recorded with `cargo xtask record-fixture`
+#[test]
+fn test_fixture_{name}() {{
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("{fixture_file}");
+    let fixture = Fixture::read(&path).unwrap();
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    let mut terminal_state = TerminalState::new(tx);
+    fixture.replay(&mut terminal_state);
+
+    // The screen as it was when the fixture was recorded
+    let screen: &[&str] = &[
+{screen_lines}    ];
+    let expected: String = screen.iter().flat_map(|line| [*line, "\n"]).collect();
+    assert_eq!(terminal_state.to_plain_text(false), expected);
+    assert_eq!(terminal_state.cursor_pos(), CursorPos {{ x: {x}, y: {y} }});
+}}
+"#,
+        fixture_file = fixture_file.display(),
+    )
+}